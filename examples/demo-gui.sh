@@ -1,7 +1,7 @@
 #!/bin/bash
 # SpaceComms GUI Demo
 #
-# Starts the SpaceComms node and opens the web UI dashboard
+# Starts the SpaceComms node, which serves the web UI dashboard at /ui/
 
 set -e
 
@@ -16,7 +16,7 @@ echo ""
 # Build
 echo "[1/3] Building SpaceComms..."
 cd "$PROJECT_ROOT"
-cargo build --release -p spacecomms 2>/dev/null || {
+cargo build --release -p spacecomms-cli 2>/dev/null || {
     echo "Build failed. Make sure Rust is installed."
     exit 1
 }
@@ -36,33 +36,18 @@ cleanup() {
 }
 trap cleanup EXIT
 
-# Serve UI files
-echo "[3/3] Starting UI server..."
-cd "$PROJECT_ROOT/ui"
-
-# Try to use Python's built-in HTTP server
-if command -v python3 &> /dev/null; then
-    echo ""
-    echo "═══════════════════════════════════════════════════════════"
-    echo "SpaceComms is running!"
-    echo ""
-    echo "  Node API:  http://localhost:8080"
-    echo "  Dashboard: http://localhost:3000"
-    echo ""
-    echo "Open http://localhost:3000 in your browser to see the dashboard."
-    echo "Press Ctrl+C to stop."
-    echo "═══════════════════════════════════════════════════════════"
-    echo ""
-    python3 -m http.server 3000
-else
-    echo ""
-    echo "═══════════════════════════════════════════════════════════"
-    echo "SpaceComms is running at http://localhost:8080"
-    echo ""
-    echo "To view the dashboard, open ui/index.html in a browser"
-    echo "or run: python3 -m http.server 3000"
-    echo ""
-    echo "Press Ctrl+C to stop."
-    echo "═══════════════════════════════════════════════════════════"
-    wait
-fi
+# The node serves the dashboard itself
+echo "[3/3] Dashboard ready."
+echo ""
+echo "═══════════════════════════════════════════════════════════"
+echo "SpaceComms is running!"
+echo ""
+echo "  Node API:  http://localhost:8080"
+echo "  Dashboard: http://localhost:8080/ui/?demo"
+echo ""
+echo "Open the dashboard in your browser. Drop ?demo to hide the"
+echo "demo banner and Constellation Hub alerts."
+echo "Press Ctrl+C to stop."
+echo "═══════════════════════════════════════════════════════════"
+echo ""
+wait $NODE_PID
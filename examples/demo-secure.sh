@@ -25,7 +25,7 @@ fi
 # Build
 echo "[1/4] Building SpaceComms..."
 cd "$PROJECT_ROOT"
-cargo build --release -p spacecomms 2>/dev/null || {
+cargo build --release -p spacecomms-cli 2>/dev/null || {
     echo "Build failed. Make sure Rust is installed."
     exit 1
 }
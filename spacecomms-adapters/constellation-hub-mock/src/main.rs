@@ -2,8 +2,8 @@
 //!
 //! Simulates a constellation operations platform that:
 //! - Manages registered satellites
-//! - Watches for CDMs from SpaceComms nodes
-//! - Generates alerts when registered satellites are involved in conjunctions
+//! - Keeps them on the SpaceComms node's watchlist
+//! - Shows and acknowledges the alerts the node raises for them
 
 use axum::{
     extract::{Path, State},
@@ -11,11 +11,12 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use spacecomms_client::{AlertFilter, AlertList, SpaceCommsClient};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
-use tracing::{info, warn, error};
+use tracing::{info, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -34,22 +35,6 @@ struct Satellite {
     registered_at: DateTime<Utc>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-struct Alert {
-    id: String,
-    satellite_id: String,
-    satellite_name: String,
-    cdm_id: String,
-    tca: String,
-    miss_distance_m: f64,
-    collision_probability: f64,
-    other_object_id: String,
-    other_object_name: String,
-    severity: String,
-    created_at: DateTime<Utc>,
-    acknowledged: bool,
-}
-
 #[derive(Deserialize)]
 struct RegisterSatelliteRequest {
     norad_id: String,
@@ -81,38 +66,30 @@ struct ManeuverRecommendationResponse {
     confidence: f64,
 }
 
-#[derive(Serialize)]
-struct AlertListResponse {
-    alerts: Vec<Alert>,
-    total: usize,
-    unacknowledged: usize,
-}
-
 #[derive(Clone)]
 struct AppState {
     satellites: Arc<RwLock<HashMap<String, Satellite>>>,
-    alerts: Arc<RwLock<Vec<Alert>>>,
     spacecomms_url: String,
 }
 
-// ============================================================================
-// CDM Poller Types (for fetching from SpaceComms node)
-// ============================================================================
+impl AppState {
+    fn client(&self) -> SpaceCommsClient {
+        SpaceCommsClient::new(&self.spacecomms_url)
+    }
+}
 
-#[derive(Deserialize)]
-struct CdmListResponse {
-    cdms: Vec<CdmSummary>,
-    total: usize,
+/// Catalog number without the `NORAD-` prefix or leading zeros
+fn catalog_number(id: &str) -> &str {
+    id.trim_start_matches("NORAD-").trim_start_matches('0')
 }
 
-#[derive(Clone, Deserialize)]
-struct CdmSummary {
-    cdm_id: String,
-    tca: DateTime<Utc>,
-    miss_distance_m: f64,
-    collision_probability: f64,
-    object1_id: String,
-    object2_id: String,
+/// Alerts are raised and kept by the SpaceComms node; this adapter only
+/// relays them
+async fn node_alerts(state: &AppState) -> Result<AlertList, axum::http::StatusCode> {
+    state.client().alerts(&AlertFilter::default()).await.map_err(|e| {
+        warn!("Could not fetch alerts from SpaceComms at {}: {}", state.spacecomms_url, e);
+        axum::http::StatusCode::BAD_GATEWAY
+    })
 }
 
 // ============================================================================
@@ -159,6 +136,9 @@ async fn register_satellite(
         let mut satellites = state.satellites.write().unwrap();
         satellites.insert(id.clone(), satellite);
     }
+    if let Err(e) = state.client().watch_assets(&[&request.norad_id]).await {
+        warn!("Could not watch {} on SpaceComms: {}", request.norad_id, e);
+    }
 
     info!(
         satellite_id = %id,
@@ -177,9 +157,12 @@ async fn unregister_satellite(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
-    let mut satellites = state.satellites.write().unwrap();
-    if satellites.remove(&id).is_some() {
+    let removed = state.satellites.write().unwrap().remove(&id);
+    if let Some(satellite) = removed {
         info!(satellite_id = %id, "Satellite unregistered");
+        if let Err(e) = state.client().unwatch_asset(&satellite.norad_id).await {
+            warn!("Could not unwatch {} on SpaceComms: {}", satellite.norad_id, e);
+        }
         Ok(Json(serde_json::json!({
             "id": id,
             "status": "unregistered"
@@ -189,43 +172,46 @@ async fn unregister_satellite(
     }
 }
 
-async fn list_alerts(State(state): State<AppState>) -> Json<AlertListResponse> {
-    let alerts = state.alerts.read().unwrap();
-    let unacknowledged = alerts.iter().filter(|a| !a.acknowledged).count();
-    
-    Json(AlertListResponse {
-        total: alerts.len(),
-        unacknowledged,
-        alerts: alerts.clone(),
-    })
+async fn list_alerts(State(state): State<AppState>) -> Result<Json<AlertList>, axum::http::StatusCode> {
+    node_alerts(&state).await.map(Json)
 }
 
 async fn get_alerts_for_satellite(
     State(state): State<AppState>,
     Path(satellite_id): Path<String>,
-) -> Json<Vec<Alert>> {
-    let alerts = state.alerts.read().unwrap();
-    let filtered: Vec<Alert> = alerts
-        .iter()
-        .filter(|a| a.satellite_id == satellite_id)
-        .cloned()
+) -> Result<Json<Vec<serde_json::Value>>, axum::http::StatusCode> {
+    let norad_id = state
+        .satellites
+        .read()
+        .unwrap()
+        .get(&satellite_id)
+        .map(|s| s.norad_id.clone())
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let alerts = node_alerts(&state).await?;
+    let filtered = alerts
+        .alerts
+        .into_iter()
+        .filter(|a| catalog_number(&a.asset_id) == catalog_number(&norad_id))
+        .map(|a| serde_json::to_value(a).unwrap_or_default())
         .collect();
-    
-    Json(filtered)
+
+    Ok(Json(filtered))
 }
 
 async fn acknowledge_alert(
     State(state): State<AppState>,
     Path(alert_id): Path<String>,
-) -> Result<Json<Alert>, axum::http::StatusCode> {
-    let mut alerts = state.alerts.write().unwrap();
-    
-    if let Some(alert) = alerts.iter_mut().find(|a| a.id == alert_id) {
-        alert.acknowledged = true;
-        info!(alert_id = %alert_id, "Alert acknowledged");
-        Ok(Json(alert.clone()))
-    } else {
-        Err(axum::http::StatusCode::NOT_FOUND)
+) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    match state.client().acknowledge_alert(&alert_id, Some("constellation-hub")).await {
+        Ok(alert) => {
+            info!(alert_id = %alert_id, "Alert acknowledged");
+            Ok(Json(serde_json::to_value(alert).unwrap_or_default()))
+        }
+        Err(e) => {
+            warn!(alert_id = %alert_id, "Could not acknowledge alert: {}", e);
+            let status = e.status().unwrap_or(502);
+            Err(axum::http::StatusCode::from_u16(status).unwrap_or(axum::http::StatusCode::BAD_GATEWAY))
+        }
     }
 }
 
@@ -262,111 +248,45 @@ struct StatsResponse {
 }
 
 async fn stats(State(state): State<AppState>) -> Json<StatsResponse> {
-    let satellites = state.satellites.read().unwrap();
-    let alerts = state.alerts.read().unwrap();
-    let unacknowledged = alerts.iter().filter(|a| !a.acknowledged).count();
+    let satellites_registered = state.satellites.read().unwrap().len();
+    // Counts are zero while the node is unreachable
+    let alerts = node_alerts(&state).await.ok();
 
     Json(StatsResponse {
-        satellites_registered: satellites.len(),
-        total_alerts: alerts.len(),
-        unacknowledged_alerts: unacknowledged,
+        satellites_registered,
+        total_alerts: alerts.as_ref().map_or(0, |a| a.total),
+        unacknowledged_alerts: alerts.as_ref().map_or(0, |a| a.open),
         spacecomms_url: state.spacecomms_url.clone(),
     })
 }
 
 // ============================================================================
-// CDM Poller (Background Task)
+// Watchlist Sync (Background Task)
 // ============================================================================
 
-fn calculate_severity(probability: f64) -> String {
-    if probability >= 1e-4 {
-        "CRITICAL".to_string()
-    } else if probability >= 1e-5 {
-        "HIGH".to_string()
-    } else if probability >= 1e-6 {
-        "MEDIUM".to_string()
-    } else {
-        "LOW".to_string()
-    }
-}
+/// Keep every registered satellite on the node's watchlist, so alerts
+/// resume after the node restarts with an empty list
+async fn sync_watchlist(state: AppState) {
+    let client = state.client();
 
-async fn poll_cdms(state: AppState) {
-    let client = reqwest::Client::new();
-    let mut known_cdms: std::collections::HashSet<String> = std::collections::HashSet::new();
-    
     loop {
-        tokio::time::sleep(Duration::from_secs(10)).await;
-
-        // Fetch CDMs from SpaceComms node
-        let url = format!("{}/cdms", state.spacecomms_url);
-        
-        match client.get(&url).send().await {
-            Ok(response) => {
-                if let Ok(cdm_list) = response.json::<CdmListResponse>().await {
-                    let satellites = state.satellites.read().unwrap();
-                    let satellite_norad_ids: Vec<String> = satellites
-                        .values()
-                        .map(|s| s.norad_id.clone())
-                        .collect();
-                    drop(satellites);
-
-                    for cdm in cdm_list.cdms {
-                        // Skip if we've already processed this CDM
-                        if known_cdms.contains(&cdm.cdm_id) {
-                            continue;
-                        }
-                        known_cdms.insert(cdm.cdm_id.clone());
-
-                        // Check if either object is one of our registered satellites
-                        let satellites = state.satellites.read().unwrap();
-                        
-                        let matching_sat = satellites.values().find(|s| {
-                            s.norad_id == cdm.object1_id || s.norad_id == cdm.object2_id
-                        });
-
-                        if let Some(satellite) = matching_sat {
-                            let other_object_id = if satellite.norad_id == cdm.object1_id {
-                                cdm.object2_id.clone()
-                            } else {
-                                cdm.object1_id.clone()
-                            };
-
-                            let alert = Alert {
-                                id: Uuid::new_v4().to_string(),
-                                satellite_id: satellite.id.clone(),
-                                satellite_name: satellite.name.clone(),
-                                cdm_id: cdm.cdm_id.clone(),
-                                tca: cdm.tca.to_rfc3339(),
-                                miss_distance_m: cdm.miss_distance_m,
-                                collision_probability: cdm.collision_probability,
-                                other_object_id,
-                                other_object_name: "Unknown".to_string(),
-                                severity: calculate_severity(cdm.collision_probability),
-                                created_at: Utc::now(),
-                                acknowledged: false,
-                            };
-
-                            drop(satellites);
-
-                            info!(
-                                alert_id = %alert.id,
-                                satellite = %alert.satellite_name,
-                                cdm_id = %alert.cdm_id,
-                                severity = %alert.severity,
-                                "New CDM alert created for registered satellite"
-                            );
-
-                            let mut alerts = state.alerts.write().unwrap();
-                            alerts.push(alert);
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                // Only log occasionally to avoid spam
-                warn!("Could not connect to SpaceComms at {}: {}", state.spacecomms_url, e);
+        let norad_ids: Vec<String> = state
+            .satellites
+            .read()
+            .unwrap()
+            .values()
+            .map(|s| s.norad_id.clone())
+            .collect();
+        let ids: Vec<&str> = norad_ids.iter().map(String::as_str).collect();
+        match client.watch_assets(&ids).await {
+            Ok(update) if !update.changed.is_empty() => {
+                info!(added = ?update.changed, "Satellites added to the SpaceComms watchlist");
             }
+            Ok(_) => {}
+            Err(e) => warn!("Could not connect to SpaceComms at {}: {}", state.spacecomms_url, e),
         }
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
     }
 }
 
@@ -422,14 +342,13 @@ async fn main() {
 
     let state = AppState {
         satellites: Arc::new(RwLock::new(satellites)),
-        alerts: Arc::new(RwLock::new(Vec::new())),
         spacecomms_url: spacecomms_url.clone(),
     };
 
-    // Start background CDM poller
-    let poller_state = state.clone();
+    // Keep the node's watchlist in step with the registered satellites
+    let sync_state = state.clone();
     tokio::spawn(async move {
-        poll_cdms(poller_state).await;
+        sync_watchlist(sync_state).await;
     });
 
     let app = Router::new()
@@ -460,7 +379,7 @@ async fn main() {
     info!("  POST /satellites                - Register satellite");
     info!("  GET  /satellites/:id            - Get satellite");
     info!("  DELETE /satellites/:id          - Unregister satellite");
-    info!("  GET  /alerts                    - List all alerts");
+    info!("  GET  /alerts                    - List the node's alerts");
     info!("  GET  /alerts/satellite/:id      - Get alerts for satellite");
     info!("  POST /alerts/:id/acknowledge    - Acknowledge alert");
     info!("  POST /maneuver-recommendation   - Get maneuver recommendation");
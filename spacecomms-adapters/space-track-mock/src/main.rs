@@ -31,6 +31,8 @@ struct CatalogEntry {
     rcs_size: Option<String>,
     #[serde(default)]
     country_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    intl_designator: Option<String>,
 }
 
 // Full CDM structure matching our internal format
@@ -91,6 +93,11 @@ struct CatalogQuery {
     object_type: Option<String>,
     #[serde(default)]
     owner: Option<String>,
+    /// Matched regardless of case
+    #[serde(default)]
+    object_name: Option<String>,
+    #[serde(default)]
+    intl_designator: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -139,6 +146,7 @@ fn default_catalog() -> Vec<CatalogEntry> {
             perigee_km: 540.0,
             rcs_size: Some("MEDIUM".to_string()),
             country_code: Some("US".to_string()),
+            intl_designator: Some("2023-065A".to_string()),
         },
     ]
 }
@@ -169,6 +177,16 @@ async fn get_catalog(
                     return false;
                 }
             }
+            if let Some(ref name) = params.object_name {
+                if !e.object_name.eq_ignore_ascii_case(name) {
+                    return false;
+                }
+            }
+            if let Some(ref designator) = params.intl_designator {
+                if e.intl_designator.as_deref() != Some(designator.as_str()) {
+                    return false;
+                }
+            }
             true
         })
         .cloned()
@@ -263,6 +281,7 @@ async fn main() {
     info!("  GET /catalog          - List catalog entries");
     info!("  GET /catalog?norad_id=12345");
     info!("  GET /catalog?object_type=DEBRIS");
+    info!("  GET /catalog?object_name=STARLINK-1234");
     info!("  GET /cdms             - List all CDMs");
     info!("  GET /cdms?object_id=12345");
     info!("  GET /cdms/:id         - Get specific CDM");
@@ -0,0 +1,186 @@
+//! Authenticated, rate-limited Space-Track API client
+
+use crate::convert::{GpRecord, PublicCdm};
+use reqwest::header::{COOKIE, SET_COOKIE};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Space-Track usage policy: at most 30 requests per minute and 300 per hour
+const RATE_LIMITS: [(usize, Duration); 2] = [
+    (30, Duration::from_secs(60)),
+    (300, Duration::from_secs(3600)),
+];
+
+/// Sliding-window request limiter
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    /// How long to wait before another request may be sent at `now`
+    pub fn delay(&mut self, now: Instant) -> Option<Duration> {
+        let longest = RATE_LIMITS.iter().map(|(_, w)| *w).max().unwrap_or_default();
+        while self.sent.front().is_some_and(|t| now.duration_since(*t) >= longest) {
+            self.sent.pop_front();
+        }
+        RATE_LIMITS
+            .iter()
+            .filter_map(|(limit, window)| {
+                let in_window: Vec<&Instant> = self.sent.iter().filter(|t| now.duration_since(**t) < *window).collect();
+                (in_window.len() >= *limit).then(|| *window - now.duration_since(*in_window[in_window.len() - limit]))
+            })
+            .max()
+    }
+
+    /// Wait until a request may be sent and record it
+    pub async fn acquire(&mut self) {
+        while let Some(delay) = self.delay(Instant::now()) {
+            debug!("Rate limit reached, waiting {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
+        self.sent.push_back(Instant::now());
+    }
+}
+
+/// Client for the Space-Track REST API
+pub struct SpaceTrackClient {
+    http: reqwest::Client,
+    base_url: String,
+    identity: String,
+    password: String,
+    session: Option<String>,
+    limiter: RateLimiter,
+}
+
+impl SpaceTrackClient {
+    /// Create a client; the session is established on the first query
+    pub fn new(base_url: &str, identity: String, password: String) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            identity,
+            password,
+            session: None,
+            limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Log in and keep the session cookie
+    pub async fn login(&mut self) -> Result<(), String> {
+        self.limiter.acquire().await;
+        let resp = self
+            .http
+            .post(format!("{}/ajaxauth/login", self.base_url))
+            .form(&[("identity", &self.identity), ("password", &self.password)])
+            .send()
+            .await
+            .map_err(|e| format!("login request failed: {}", e))?;
+
+        let cookies: Vec<String> = resp
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next())
+            .map(str::to_string)
+            .collect();
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+
+        // Failed logins are reported as 200 with {"Login":"Failed"}
+        if !status.is_success() || body.contains("Failed") || cookies.is_empty() {
+            return Err(format!("login rejected ({})", status));
+        }
+        info!("Logged in to {}", self.base_url);
+        self.session = Some(cookies.join("; "));
+        Ok(())
+    }
+
+    /// CDMs with an ID above `cursor`, or created in the last `lookback_days` without a cursor
+    pub async fn public_cdms(&mut self, cursor: Option<u64>, lookback_days: u32, limit: usize) -> Result<Vec<PublicCdm>, String> {
+        let filter = match cursor {
+            Some(cursor) => format!("CDM_ID/%3E{}", cursor),
+            None => format!("CREATED/%3Enow-{}", lookback_days),
+        };
+        self.query(&format!(
+            "/basicspacedata/query/class/cdm_public/{}/orderby/CDM_ID%20asc/limit/{}/format/json",
+            filter, limit
+        ))
+        .await
+    }
+
+    /// Latest general perturbations elements for a set of catalog numbers
+    pub async fn gp(&mut self, norad_ids: &[String]) -> Result<Vec<GpRecord>, String> {
+        if norad_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.query(&format!(
+            "/basicspacedata/query/class/gp/NORAD_CAT_ID/{}/format/json",
+            norad_ids.join(",")
+        ))
+        .await
+    }
+
+    async fn query<T: DeserializeOwned>(&mut self, path: &str) -> Result<Vec<T>, String> {
+        for attempt in 0..2 {
+            if self.session.is_none() {
+                self.login().await?;
+            }
+            self.limiter.acquire().await;
+            let resp = self
+                .http
+                .get(format!("{}{}", self.base_url, path))
+                .header(COOKIE, self.session.clone().unwrap_or_default())
+                .send()
+                .await
+                .map_err(|e| format!("query failed: {}", e))?;
+
+            match resp.status() {
+                // Sessions expire after about two hours; log in again once
+                StatusCode::UNAUTHORIZED if attempt == 0 => {
+                    debug!("Session expired, logging in again");
+                    self.session = None;
+                }
+                status if status.is_success() => {
+                    return resp.json().await.map_err(|e| format!("invalid response for {}: {}", path, e));
+                }
+                status => return Err(format!("query {} returned {}", path, status)),
+            }
+        }
+        Err("not authorized after login".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        for _ in 0..30 {
+            assert_eq!(limiter.delay(start), None);
+            limiter.sent.push_back(start);
+        }
+        assert_eq!(limiter.delay(start), Some(Duration::from_secs(60)));
+        assert_eq!(limiter.delay(start + Duration::from_secs(45)), Some(Duration::from_secs(15)));
+        assert_eq!(limiter.delay(start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn test_hourly_rate_limit() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        limiter.sent.extend((0..300).map(|i| start + Duration::from_secs(i * 10)));
+        let now = start + Duration::from_secs(3000);
+        assert_eq!(limiter.delay(now), Some(Duration::from_secs(600)));
+    }
+}
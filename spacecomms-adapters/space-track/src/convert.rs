@@ -0,0 +1,281 @@
+//! Conversion of Space-Track records to SpaceComms CDMs
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use spacecomms::cdm::{CdmObject, CdmRecord};
+use spacecomms::protocol::{parse_timestamp, ObjectType, RcsSize, StateVector};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+const EARTH_MU_KM3_S2: f64 = 398600.4418;
+
+/// Record of the `cdm_public` class (Space-Track returns every value as a string)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct PublicCdm {
+    pub cdm_id: String,
+    pub created: String,
+    pub tca: String,
+    /// Minimum range in meters
+    pub min_rng: Option<String>,
+    pub pc: Option<String>,
+    pub sat_1_id: String,
+    pub sat_1_name: Option<String>,
+    #[serde(rename = "SAT1_OBJECT_TYPE")]
+    pub sat1_object_type: Option<String>,
+    #[serde(rename = "SAT1_RCS")]
+    pub sat1_rcs: Option<String>,
+    pub sat_2_id: String,
+    pub sat_2_name: Option<String>,
+    #[serde(rename = "SAT2_OBJECT_TYPE")]
+    pub sat2_object_type: Option<String>,
+    #[serde(rename = "SAT2_RCS")]
+    pub sat2_rcs: Option<String>,
+}
+
+/// Record of the `gp` class (mean Keplerian elements)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct GpRecord {
+    pub norad_cat_id: String,
+    pub epoch: String,
+    pub mean_motion: String,
+    pub eccentricity: String,
+    pub inclination: String,
+    pub ra_of_asc_node: String,
+    pub arg_of_pericenter: String,
+    pub mean_anomaly: String,
+    pub country_code: Option<String>,
+}
+
+impl PublicCdm {
+    /// Numeric CDM ID used as the fetch cursor
+    pub fn id(&self) -> Option<u64> {
+        self.cdm_id.parse().ok()
+    }
+}
+
+fn number(value: &str, field: &str) -> Result<f64, String> {
+    value.trim().parse().map_err(|_| format!("invalid {}: {:?}", field, value))
+}
+
+fn timestamp(value: &str, field: &str) -> Result<DateTime<Utc>, String> {
+    parse_timestamp(value).map_err(|e| format!("invalid {}: {}", field, e))
+}
+
+fn object_type(value: Option<&str>) -> ObjectType {
+    match value.map(|v| v.trim().to_ascii_uppercase()).as_deref() {
+        Some("PAYLOAD") => ObjectType::Payload,
+        Some("DEBRIS") => ObjectType::Debris,
+        Some("ROCKET BODY") => ObjectType::RocketBody,
+        _ => ObjectType::Unknown,
+    }
+}
+
+fn rcs_size(value: Option<&str>) -> Option<RcsSize> {
+    match value.map(|v| v.trim().to_ascii_uppercase()).as_deref() {
+        Some("SMALL") => Some(RcsSize::Small),
+        Some("MEDIUM") => Some(RcsSize::Medium),
+        Some("LARGE") => Some(RcsSize::Large),
+        _ => None,
+    }
+}
+
+impl GpRecord {
+    /// Two-body TEME state at the element epoch
+    ///
+    /// SGP4 mean elements are treated as osculating, which is accurate to a
+    /// few kilometres and good enough for display and catalog purposes.
+    pub fn state_vector(&self) -> Result<StateVector, String> {
+        let n = number(&self.mean_motion, "MEAN_MOTION")? * TAU / 86400.0;
+        let e = number(&self.eccentricity, "ECCENTRICITY")?;
+        let i = number(&self.inclination, "INCLINATION")?.to_radians();
+        let raan = number(&self.ra_of_asc_node, "RA_OF_ASC_NODE")?.to_radians();
+        let argp = number(&self.arg_of_pericenter, "ARG_OF_PERICENTER")?.to_radians();
+        let m = number(&self.mean_anomaly, "MEAN_ANOMALY")?.to_radians();
+        if n <= 0.0 || !(0.0..1.0).contains(&e) {
+            return Err(format!("unsupported orbit for {}", self.norad_cat_id));
+        }
+
+        // Solve Kepler's equation for the eccentric anomaly
+        let mut ecc_anomaly = m;
+        for _ in 0..20 {
+            let delta = (ecc_anomaly - e * ecc_anomaly.sin() - m) / (1.0 - e * ecc_anomaly.cos());
+            ecc_anomaly -= delta;
+            if delta.abs() < 1e-12 {
+                break;
+            }
+        }
+
+        let a = (EARTH_MU_KM3_S2 / (n * n)).cbrt();
+        let nu = 2.0 * ((1.0 + e).sqrt() * (ecc_anomaly / 2.0).sin()).atan2((1.0 - e).sqrt() * (ecc_anomaly / 2.0).cos());
+        let r = a * (1.0 - e * ecc_anomaly.cos());
+        let h = (EARTH_MU_KM3_S2 / (a * (1.0 - e * e))).sqrt();
+
+        // Perifocal position and velocity
+        let (p, q) = (r * nu.cos(), r * nu.sin());
+        let (vp, vq) = (-h * nu.sin(), h * (e + nu.cos()));
+
+        // Rotate perifocal -> TEME
+        let (so, co) = raan.sin_cos();
+        let (sw, cw) = argp.sin_cos();
+        let (si, ci) = i.sin_cos();
+        let rotate = |x: f64, y: f64| {
+            (
+                (co * cw - so * sw * ci) * x + (-co * sw - so * cw * ci) * y,
+                (so * cw + co * sw * ci) * x + (-so * sw + co * cw * ci) * y,
+                (sw * si) * x + (cw * si) * y,
+            )
+        };
+        let (x_km, y_km, z_km) = rotate(p, q);
+        let (vx_km_s, vy_km_s, vz_km_s) = rotate(vp, vq);
+
+        Ok(StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(timestamp(&self.epoch, "EPOCH")?),
+            x_km,
+            y_km,
+            z_km,
+            vx_km_s,
+            vy_km_s,
+            vz_km_s,
+        })
+    }
+}
+
+fn object(
+    id: &str,
+    name: Option<&str>,
+    object_type_value: Option<&str>,
+    rcs: Option<&str>,
+    elements: &HashMap<String, GpRecord>,
+) -> Result<CdmObject, String> {
+    let gp = elements
+        .get(id)
+        .ok_or_else(|| format!("no orbital elements for {}", id))?;
+    Ok(CdmObject {
+        object_id: format!("NORAD-{}", id),
+        object_name: name.filter(|n| !n.is_empty()).unwrap_or("Unknown").to_string(),
+        object_type: object_type(object_type_value),
+        owner_operator: gp.country_code.clone().filter(|c| !c.is_empty()),
+        maneuverable: false,
+        rcs_size: rcs_size(rcs),
+        state_vector: gp.state_vector()?,
+        covariance_rtm: None,
+    })
+}
+
+/// Convert a public CDM, using `elements` (keyed by catalog number) for object states
+pub fn to_cdm_record(cdm: &PublicCdm, elements: &HashMap<String, GpRecord>) -> Result<CdmRecord, String> {
+    let miss_distance_m = match &cdm.min_rng {
+        Some(v) => number(v, "MIN_RNG")?,
+        None => return Err("MIN_RNG missing".to_string()),
+    };
+    let collision_probability = match cdm.pc.as_deref().filter(|v| !v.is_empty()) {
+        Some(v) => number(v, "PC")?,
+        None => 0.0,
+    };
+
+    Ok(CdmRecord {
+        cdm_id: format!("SPACETRACK-{}", cdm.cdm_id),
+        creation_date: timestamp(&cdm.created, "CREATED")?,
+        originator: "SPACE-TRACK".to_string(),
+        message_for: "PUBLIC".to_string(),
+        tca: timestamp(&cdm.tca, "TCA")?,
+        miss_distance_m,
+        collision_probability,
+        object1: object(
+            &cdm.sat_1_id,
+            cdm.sat_1_name.as_deref(),
+            cdm.sat1_object_type.as_deref(),
+            cdm.sat1_rcs.as_deref(),
+            elements,
+        )?,
+        object2: object(
+            &cdm.sat_2_id,
+            cdm.sat_2_name.as_deref(),
+            cdm.sat2_object_type.as_deref(),
+            cdm.sat2_rcs.as_deref(),
+            elements,
+        )?,
+        relative_state: None,
+        screening_data: None,
+        data_quality_score: None,
+        conjunction_category: None,
+        recommended_action: None,
+        organization: None,
+        involves_watched_asset: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacecomms::cdm::validate_cdm;
+
+    fn gp(id: &str, mean_motion: &str, eccentricity: &str) -> GpRecord {
+        serde_json::from_value(serde_json::json!({
+            "NORAD_CAT_ID": id,
+            "OBJECT_NAME": "TEST",
+            "EPOCH": "2024-01-15T12:00:00.000000",
+            "MEAN_MOTION": mean_motion,
+            "ECCENTRICITY": eccentricity,
+            "INCLINATION": "51.6",
+            "RA_OF_ASC_NODE": "120.0",
+            "ARG_OF_PERICENTER": "30.0",
+            "MEAN_ANOMALY": "45.0",
+            "COUNTRY_CODE": "ISS"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_gp_state_vector() {
+        // ISS-like circular orbit: ~6795 km radius, ~7.66 km/s
+        let state = gp("25544", "15.5", "0.0").state_vector().unwrap();
+        let r = (state.x_km.powi(2) + state.y_km.powi(2) + state.z_km.powi(2)).sqrt();
+        let v = (state.vx_km_s.powi(2) + state.vy_km_s.powi(2) + state.vz_km_s.powi(2)).sqrt();
+        assert!((r - 6796.0).abs() < 5.0, "{}", r);
+        assert!((v - 7.66).abs() < 0.01, "{}", v);
+        assert!((state.z_km / r).abs() <= 51.6_f64.to_radians().sin() + 1e-9);
+
+        assert!(gp("1", "15.5", "1.2").state_vector().is_err());
+    }
+
+    #[test]
+    fn test_to_cdm_record() {
+        let cdm: PublicCdm = serde_json::from_value(serde_json::json!({
+            "CDM_ID": "812345678",
+            "CREATED": "2024-01-15 10:00:00.000000",
+            "EMERGENCY_REPORTABLE": "Y",
+            "TCA": "2024-01-17T08:30:00.250000",
+            "MIN_RNG": "129",
+            "PC": "1.2e-04",
+            "SAT_1_ID": "25544",
+            "SAT_1_NAME": "ISS (ZARYA)",
+            "SAT1_OBJECT_TYPE": "PAYLOAD",
+            "SAT1_RCS": "LARGE",
+            "SAT_2_ID": "99999",
+            "SAT_2_NAME": "",
+            "SAT2_OBJECT_TYPE": "DEBRIS",
+            "SAT2_RCS": null
+        }))
+        .unwrap();
+        let elements: HashMap<String, GpRecord> = [gp("25544", "15.5", "0.0006"), gp("99999", "14.2", "0.001")]
+            .into_iter()
+            .map(|g| (g.norad_cat_id.clone(), g))
+            .collect();
+
+        let record = to_cdm_record(&cdm, &elements).unwrap();
+        assert_eq!(cdm.id(), Some(812345678));
+        assert_eq!(record.cdm_id, "SPACETRACK-812345678");
+        assert_eq!(record.miss_distance_m, 129.0);
+        assert_eq!(record.object1.object_id, "NORAD-25544");
+        assert_eq!(record.object1.rcs_size, Some(RcsSize::Large));
+        assert_eq!(record.object2.object_name, "Unknown");
+        assert_eq!(record.object2.object_type, ObjectType::Debris);
+        assert!(validate_cdm(&record).is_ok());
+
+        assert!(to_cdm_record(&cdm, &HashMap::new()).is_err());
+    }
+}
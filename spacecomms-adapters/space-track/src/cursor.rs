@@ -0,0 +1,55 @@
+//! Persistent fetch cursor for incremental CDM polling
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Highest CDM ID already delivered to the node
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    pub last_cdm_id: Option<u64>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Cursor {
+    /// Load the cursor, starting fresh if the file is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        let mut cursor: Cursor = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        cursor.path = path.to_path_buf();
+        cursor
+    }
+
+    /// Advance past a CDM (never moves backwards)
+    pub fn advance(&mut self, cdm_id: u64) {
+        self.last_cdm_id = Some(self.last_cdm_id.map_or(cdm_id, |last| last.max(cdm_id)));
+    }
+
+    /// Persist the cursor atomically
+    pub fn save(&self) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+
+        let mut cursor = Cursor::load(&path);
+        assert_eq!(cursor.last_cdm_id, None);
+        cursor.advance(42);
+        cursor.advance(7);
+        cursor.save().unwrap();
+
+        assert_eq!(Cursor::load(&path).last_cdm_id, Some(42));
+    }
+}
@@ -0,0 +1,149 @@
+//! Space-Track Live Adapter
+//!
+//! Polls space-track.org for public CDMs, converts them to SpaceComms
+//! `CdmRecord`s using the latest orbital elements of both objects, and
+//! pushes them into a SpaceComms node via `POST /cdm`.
+
+mod client;
+mod convert;
+mod cursor;
+
+use client::SpaceTrackClient;
+use convert::{to_cdm_record, GpRecord};
+use cursor::Cursor;
+use spacecomms_client::SpaceCommsClient;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+/// CDMs fetched per query
+const PAGE_SIZE: usize = 100;
+
+/// Adapter settings, read from the environment
+struct AdapterConfig {
+    space_track_url: String,
+    identity: String,
+    password: String,
+    node_url: String,
+    node_token: Option<String>,
+    poll_interval: Duration,
+    lookback_days: u32,
+    cursor_file: PathBuf,
+}
+
+impl AdapterConfig {
+    fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let required = |name: &str| var(name).ok_or_else(|| format!("{} is required", name));
+        let number = |name: &str, default: u64| -> Result<u64, String> {
+            var(name).map_or(Ok(default), |v| v.parse().map_err(|_| format!("{} must be a number", name)))
+        };
+
+        // Space-Track asks clients not to poll CDMs more than once an hour
+        let poll_interval_seconds = number("POLL_INTERVAL_SECONDS", 3600)?.max(3600);
+
+        Ok(Self {
+            space_track_url: var("SPACETRACK_URL").unwrap_or_else(|| "https://www.space-track.org".to_string()),
+            identity: required("SPACETRACK_IDENTITY")?,
+            password: required("SPACETRACK_PASSWORD")?,
+            node_url: var("SPACECOMMS_URL").unwrap_or_else(|| "http://localhost:8080".to_string()),
+            node_token: var("SPACECOMMS_TOKEN"),
+            poll_interval: Duration::from_secs(poll_interval_seconds),
+            lookback_days: number("LOOKBACK_DAYS", 3)? as u32,
+            cursor_file: var("CURSOR_FILE").unwrap_or_else(|| "space-track-cursor.json".to_string()).into(),
+        })
+    }
+}
+
+/// Fetch and deliver every CDM newer than the cursor
+///
+/// The cursor only advances past CDMs the node accepted or permanently
+/// rejected, so transient node failures are retried on the next poll.
+async fn sync(
+    space_track: &mut SpaceTrackClient,
+    node: &SpaceCommsClient,
+    cursor: &mut Cursor,
+    lookback_days: u32,
+) -> Result<usize, String> {
+    let mut delivered = 0;
+    loop {
+        let page = space_track.public_cdms(cursor.last_cdm_id, lookback_days, PAGE_SIZE).await?;
+        if page.is_empty() {
+            return Ok(delivered);
+        }
+
+        let mut norad_ids: Vec<String> = page
+            .iter()
+            .flat_map(|c| [c.sat_1_id.clone(), c.sat_2_id.clone()])
+            .collect();
+        norad_ids.sort();
+        norad_ids.dedup();
+        let mut elements: HashMap<String, GpRecord> = HashMap::new();
+        for chunk in norad_ids.chunks(PAGE_SIZE) {
+            for gp in space_track.gp(chunk).await? {
+                elements.insert(gp.norad_cat_id.clone(), gp);
+            }
+        }
+
+        for cdm in &page {
+            let Some(id) = cdm.id() else {
+                warn!("Skipping CDM with non-numeric ID {}", cdm.cdm_id);
+                continue;
+            };
+            match to_cdm_record(cdm, &elements) {
+                // Keyed by Space-Track's CDM ID so a push retried after a
+                // timeout is not forwarded twice
+                Ok(record) => match node.ingest_cdm_with_key(&record, &format!("space-track-{}", id)).await {
+                    Ok(_) => delivered += 1,
+                    // Retrying will not help
+                    Err(e) if e.is_rejected() => warn!("Node rejected {}: {}", record.cdm_id, e),
+                    Err(e) => return Err(format!("node push failed: {}", e)),
+                },
+                Err(e) => warn!("Skipping CDM {}: {}", cdm.cdm_id, e),
+            }
+            cursor.advance(id);
+            cursor.save().map_err(|e| format!("failed to save cursor: {}", e))?;
+        }
+
+        if page.len() < PAGE_SIZE {
+            return Ok(delivered);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::from_default_env().add_directive("info".parse().unwrap()))
+        .init();
+
+    let config = match AdapterConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut space_track = SpaceTrackClient::new(&config.space_track_url, config.identity, config.password);
+    let mut node = SpaceCommsClient::new(config.node_url);
+    if let Some(token) = config.node_token {
+        node = node.with_token(token);
+    }
+    let mut cursor = Cursor::load(&config.cursor_file);
+
+    info!("Space-Track adapter started, pushing to {}", node.base_url());
+    info!("  Poll interval: {:?}", config.poll_interval);
+    info!("  Cursor: {:?}", cursor.last_cdm_id);
+
+    loop {
+        match sync(&mut space_track, &node, &mut cursor, config.lookback_days).await {
+            Ok(count) => info!("Delivered {} CDMs (cursor {:?})", count, cursor.last_cdm_id),
+            Err(e) => warn!("Sync failed: {}", e),
+        }
+        tokio::time::sleep(config.poll_interval).await;
+    }
+}
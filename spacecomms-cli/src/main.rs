@@ -0,0 +1,1717 @@
+//! SpaceComms CLI Entry Point
+
+use clap::{Parser, Subcommand, ValueEnum};
+use spacecomms::cdm::{parse_cdm, validate_cdm, CdmDiff, CdmRecord, ConjunctionCategory, SyntheticScenario};
+use spacecomms::node::{
+    diff, load_message_log, replay, AlertState, CdmEvent, CdmEventKind, Divergence, ImportLine, LogLevelHook, PeerSimulator, Playback,
+    PlaybackOptions, PlaybackReport, preflight, CheckLevel, PreflightReport, ReplayOutcome, RoutingSimulationRequest, Scenario, SimulationReport,
+};
+use spacecomms::config::ConfigOverride;
+use spacecomms::orbit::PropagationModel;
+use spacecomms::protocol::{parse_timestamp, CdmQuery, ProvenanceSigner};
+use spacecomms::{Config, Error, Result};
+use spacecomms_client::{AddPeer, AlertFilter, CdmFilter, SpaceCommsClient, UpcomingOrder};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, Level};
+use std::sync::Arc;
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Layer, Registry};
+
+#[derive(Parser)]
+#[command(name = "spacecomms")]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Bearer token for nodes with API authentication enabled
+    #[arg(long, global = true, env = "SPACECOMMS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Start the SpaceComms node
+    Start {
+        /// Path to configuration file
+        #[arg(short, long, env = "SPACECOMMS_CONFIG", default_value = "config.yaml")]
+        config: PathBuf,
+        /// Run an ephemeral developer node with generated traffic
+        /// (ignores --config)
+        #[arg(long)]
+        dev: bool,
+        /// Override a configuration key, e.g. --set server.port=9090
+        /// (repeatable; applied after SPACECOMMS_* environment overrides)
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        overrides: Vec<ConfigOverride>,
+    },
+    /// Validate configuration file and run preflight checks against it
+    ValidateConfig {
+        /// Path to configuration file
+        #[arg(short, long, env = "SPACECOMMS_CONFIG", default_value = "config.yaml")]
+        config: PathBuf,
+        /// Override a configuration key, e.g. --set server.port=9090
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        overrides: Vec<ConfigOverride>,
+        /// Fail on preflight warnings as well as errors
+        #[arg(long)]
+        strict: bool,
+        /// Print the preflight report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generate an Ed25519 key for signing CDM provenance hops
+    ProvenanceKey,
+    /// Describe the configuration file format
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Add a peer to a running node
+    Peer {
+        #[command(subcommand)]
+        command: PeerCommands,
+    },
+    /// CDM operations
+    Cdm {
+        #[command(subcommand)]
+        command: CdmCommands,
+    },
+    /// Inspect the mesh beyond this node's peers
+    Network {
+        #[command(subcommand)]
+        command: NetworkCommands,
+    },
+    /// Review originators' ingest and release held ones
+    Originators {
+        #[command(subcommand)]
+        command: OriginatorCommands,
+    },
+    /// Manage the assets this node's operator owns
+    Watchlist {
+        #[command(subcommand)]
+        command: WatchlistCommands,
+    },
+    /// Work the alerts raised for watched assets
+    Alerts {
+        #[command(subcommand)]
+        command: AlertCommands,
+    },
+    /// List tracked objects, or review one object's CDMs
+    Objects {
+        #[command(subcommand)]
+        command: Option<ObjectCommands>,
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Act as a remote peer and run a scripted protocol scenario
+    SimulatePeer {
+        /// Base address of the node under test
+        #[arg(long, default_value = "http://localhost:8080")]
+        target: String,
+        /// Path to scenario YAML file
+        #[arg(long)]
+        script: PathBuf,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Replay a message log in-process and diff the outcome
+    Replay {
+        /// JSON Lines message log
+        #[arg(long)]
+        log: PathBuf,
+        /// Configuration to replay through
+        #[arg(short, long, default_value = "config.yaml")]
+        config: PathBuf,
+        /// Second configuration to replay through and compare with
+        #[arg(long, conflicts_with = "baseline")]
+        against: Option<PathBuf>,
+        /// Outcome saved by an earlier run (e.g. the previous release) to compare with
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Save this run's outcome for later comparison
+        #[arg(long)]
+        save: Option<PathBuf>,
+        /// Print the divergences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Play a message log into a running node with its recorded timing
+    Simulate {
+        /// Base address of the node to play into
+        #[arg(long, default_value = "http://localhost:8080")]
+        target: String,
+        /// JSON Lines message log, as read by `replay`
+        #[arg(long)]
+        log: PathBuf,
+        /// Playback rate relative to the recording; 0 sends without pausing
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Longest pause between two messages, in seconds
+        #[arg(long)]
+        max_gap: Option<f64>,
+        /// Send every message as this peer instead of its recorded sender
+        #[arg(long)]
+        as_peer: Option<String>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the JSON Schema of the configuration file
+    Schema,
+}
+
+#[derive(Subcommand)]
+enum PeerCommands {
+    /// Add a new peer
+    Add {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peer ID
+        #[arg(long)]
+        peer_id: String,
+        /// Peer address
+        #[arg(long)]
+        peer_address: String,
+    },
+    /// List configured peers
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Shut a peer's session down for maintenance, keeping its configuration
+    Disable {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peer ID
+        peer_id: String,
+        /// Note passed on to the peer, such as a maintenance window
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Bring a disabled peer's session back up
+    Enable {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peer ID
+        peer_id: String,
+    },
+    /// Block a node: drop its session, purge its queued envelopes and
+    /// refuse anything from or through it
+    Block {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Node ID, a peer's or any other node's
+        node_id: String,
+        /// Why the node is blocked
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Take a node off the blocklist
+    Unblock {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Node ID
+        node_id: String,
+    },
+    /// List blocked nodes
+    Blocked {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Write the node's peers with their settings and policies as a YAML
+    /// (or JSON) document, to replicate them on another node
+    Export {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Write JSON instead of YAML
+        #[arg(long)]
+        json: bool,
+        /// Include the peers' auth tokens
+        #[arg(long)]
+        include_secrets: bool,
+        /// File to write; standard output when omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Add and update peers from a document written by `peer export`
+    Import {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peering document, JSON if named *.json and YAML otherwise
+        file: PathBuf,
+        /// Also remove the peers the document does not list
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Pull CDMs matching a filter from a connected peer
+    Query {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peer ID
+        peer_id: String,
+        /// Only CDMs involving this object (repeatable)
+        #[arg(long = "object")]
+        object_ids: Vec<String>,
+        /// Only CDMs with TCA at or after this time
+        #[arg(long)]
+        from: Option<String>,
+        /// Only CDMs with TCA before this time
+        #[arg(long)]
+        to: Option<String>,
+        /// Most CDMs to pull
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Compare digests with a connected peer and pull the CDMs missing here
+    Sync {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Peer ID
+        peer_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum NetworkCommands {
+    /// Print the nodes and links of the mesh as seen from a node
+    Topology {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Print a Graphviz DOT graph instead of JSON
+        #[arg(long)]
+        dot: bool,
+    },
+    /// Find the nodes announcing state for an object
+    Sources {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Object ID
+        object_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum OriginatorCommands {
+    /// List the originators seen in the last hour, with any hold
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Take a held originator's CDMs again
+    Release {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Originator, as in the CDMs' `originator` field
+        originator: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WatchlistCommands {
+    /// Watch assets by NORAD catalog number
+    Add {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Catalog numbers, with or without the NORAD- prefix
+        #[arg(required = true)]
+        norad_ids: Vec<String>,
+    },
+    /// List watched assets
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Stop watching an asset
+    Remove {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Catalog number
+        norad_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AlertCommands {
+    /// List alerts, ordered by TCA
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Only alerts in this state
+        #[arg(long, value_enum)]
+        state: Option<AlertStateArg>,
+        /// Only alerts at or above this severity
+        #[arg(long, value_enum)]
+        min_severity: Option<SeverityArg>,
+        /// Only alerts assigned to this person or token
+        #[arg(long)]
+        assignee: Option<String>,
+    },
+    /// Acknowledge an open alert
+    Ack {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Alert ID
+        alert_id: String,
+        /// Who is acknowledging; the API token when omitted
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// Assign an alert to a person or token
+    Assign {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Alert ID
+        alert_id: String,
+        /// Assignee; omit to unassign
+        assignee: Option<String>,
+    },
+    /// Resolve an alert
+    Resolve {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Alert ID
+        alert_id: String,
+        /// How the alert was resolved
+        #[arg(long)]
+        resolution: Option<String>,
+    },
+}
+
+/// Alert state for `alerts list --state`
+#[derive(Clone, Copy, ValueEnum)]
+enum AlertStateArg {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+impl From<AlertStateArg> for AlertState {
+    fn from(state: AlertStateArg) -> Self {
+        match state {
+            AlertStateArg::Open => AlertState::Open,
+            AlertStateArg::Acknowledged => AlertState::Acknowledged,
+            AlertStateArg::Resolved => AlertState::Resolved,
+        }
+    }
+}
+
+/// Severity for `alerts list --min-severity`
+#[derive(Clone, Copy, ValueEnum)]
+enum SeverityArg {
+    Low,
+    Medium,
+    High,
+}
+
+impl From<SeverityArg> for ConjunctionCategory {
+    fn from(severity: SeverityArg) -> Self {
+        match severity {
+            SeverityArg::Low => ConjunctionCategory::Low,
+            SeverityArg::Medium => ConjunctionCategory::Medium,
+            SeverityArg::High => ConjunctionCategory::High,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ObjectCommands {
+    /// Show active and withdrawn CDMs involving an object, ordered by TCA
+    History {
+        /// Object ID
+        id: String,
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Predict an object's state at an epoch, with accuracy caveats
+    State {
+        /// Object ID
+        id: String,
+        /// Epoch to predict at (RFC 3339); now when omitted
+        #[arg(long)]
+        at: Option<String>,
+        /// Ignore J2 and propagate with point-mass gravity only
+        #[arg(long)]
+        two_body: bool,
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Look up an object name, catalog number or designator in the node's catalog
+    Resolve {
+        /// Name, NORAD catalog number or international designator
+        query: String,
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum QuarantineCommands {
+    /// List quarantined CDMs, newest first, with the rules they failed
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// Store and propagate a quarantined CDM
+    Release {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        cdm_id: String,
+    },
+    /// Drop a quarantined CDM
+    Discard {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        cdm_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CdmCommands {
+    /// Inject a CDM from file
+    Inject {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Path to CDM JSON file
+        file: PathBuf,
+    },
+    /// Import a historical CDM archive (NDJSON, optionally gzip
+    /// compressed) without announcing it to peers
+    Import {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Path to the archive, one CDM per line
+        file: PathBuf,
+    },
+    /// Show how the node would route a CDM or envelope, and to which
+    /// peers, without storing or sending it
+    Route {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Path to a CDM or envelope JSON file
+        file: PathBuf,
+        /// Route it as arriving from this peer instead of ingested here
+        #[arg(long)]
+        from_peer: Option<String>,
+    },
+    /// Review CDMs held by quarantining validation rules
+    Quarantine {
+        #[command(subcommand)]
+        command: QuarantineCommands,
+    },
+    /// Show which peers confirmed receipt of a CDM's latest announcement
+    /// or withdrawal
+    Propagation {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        cdm_id: String,
+    },
+    /// Show which watching nodes a CDM this node originated reached
+    Receipts {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        cdm_id: String,
+    },
+    /// List active CDMs
+    List {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Only CDMs involving a watched asset
+        #[arg(long)]
+        watched: bool,
+    },
+    /// Generate synthetic CDMs (printed as JSON unless --post is given)
+    Generate {
+        /// Primary (maneuverable) object ID
+        #[arg(long, default_value = "NORAD-12345")]
+        object1_id: String,
+        /// Primary object name
+        #[arg(long, default_value = "STARLINK-1234")]
+        object1_name: String,
+        /// Secondary (debris) object ID
+        #[arg(long, default_value = "NORAD-99999")]
+        object2_id: String,
+        /// Secondary object name
+        #[arg(long, default_value = "FENGYUN-1C-DEB")]
+        object2_name: String,
+        /// Hours from now until TCA
+        #[arg(long, default_value_t = 48.0)]
+        tca_hours: f64,
+        /// Miss distance in meters
+        #[arg(long, default_value_t = 150.0)]
+        miss_distance: f64,
+        /// Collision probability
+        #[arg(long, default_value_t = 1.2e-4)]
+        pc: f64,
+        /// Number of CDMs to generate
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        /// Seed for the CDM IDs; with --epoch the output is the same every run
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Creation time of the CDMs, which --tca-hours counts from (default: now)
+        #[arg(long)]
+        epoch: Option<String>,
+        /// Post the CDMs to the node instead of printing them
+        #[arg(long)]
+        post: bool,
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+    },
+    /// List conjunctions with TCA in the coming hours, soonest first
+    Upcoming {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// How far ahead to look, such as 6h or 3d
+        #[arg(long, default_value = "24h")]
+        window: String,
+        /// Order by TCA or by risk
+        #[arg(long, value_enum, default_value_t = UpcomingSort::Tca)]
+        sort: UpcomingSort,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Print CDMs and withdrawals as they arrive
+    Watch {
+        /// Node API address
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Only show CDMs at or above this collision probability
+        #[arg(long, default_value_t = 0.0)]
+        min_probability: f64,
+        /// Only show CDMs involving a watched asset
+        #[arg(long)]
+        watched: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Compare two CDMs field by field: Pc, miss distance, TCA, state
+    /// vectors and covariances
+    Diff {
+        /// Earlier CDM, as a JSON file or the ID of a CDM on the node
+        from: String,
+        /// Later CDM, as a JSON file or the ID of a CDM on the node
+        to: String,
+        /// Node API address, for CDMs given by ID
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        address: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+/// Order for `cdm upcoming`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum UpcomingSort {
+    /// Soonest TCA first
+    Tca,
+    /// Highest category, then highest probability first
+    Risk,
+}
+
+/// Output format for `cdm watch`, `cdm upcoming` and `objects history`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// JSON (one event per line when watching)
+    Json,
+    /// Aligned columns
+    Table,
+}
+
+/// Seconds each events poll is held open by the node
+const WATCH_POLL_SECONDS: u64 = 30;
+
+fn print_event(event: &CdmEvent, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(event)?);
+        return Ok(());
+    }
+
+    let kind = match event.kind {
+        CdmEventKind::Announced => "ANNOUNCED",
+        CdmEventKind::Withdrawn => "WITHDRAWN",
+        CdmEventKind::Escalated => "ESCALATED",
+    };
+    let pc = event.collision_probability.map_or("-".to_string(), |pc| format!("{:.2e}", pc));
+    let details = match &event.cdm {
+        Some(cdm) => format!(
+            "{:<20} {:>10.1} {:<9} {} vs {}",
+            cdm.tca.format("%Y-%m-%dT%H:%M:%SZ"),
+            cdm.miss_distance_m,
+            pc,
+            cdm.object1.object_name,
+            cdm.object2.object_name
+        ),
+        None => format!(
+            "{:<20} {:>10} {:<9} {}",
+            "-",
+            "-",
+            pc,
+            event.reason.as_deref().unwrap_or_default()
+        ),
+    };
+    println!("{:<20} {:<9} {:<28} {}", event.at.format("%Y-%m-%dT%H:%M:%SZ"), kind, event.cdm_id, details);
+    Ok(())
+}
+
+/// Client for the node API at `address`, authenticating with `token`
+fn api_client(address: impl Into<String>, token: Option<&str>) -> SpaceCommsClient {
+    let client = SpaceCommsClient::new(address);
+    match token {
+        Some(token) => client.with_token(token),
+        None => client,
+    }
+}
+
+/// Report a failed node API call and exit
+fn fail(action: &str, error: spacecomms_client::Error) -> ! {
+    eprintln!("Failed to {}: {}", action, error);
+    std::process::exit(1)
+}
+
+async fn watch_cdms(
+    address: &str,
+    token: Option<&str>,
+    min_probability: f64,
+    watched: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut events = api_client(address, token)
+        .stream_events(None)
+        .with_poll_timeout(Duration::from_secs(WATCH_POLL_SECONDS));
+    if watched {
+        events = events.watched_only();
+    }
+    if format == OutputFormat::Table {
+        println!(
+            "{:<20} {:<9} {:<28} {:<20} {:>10} {:<9} DETAILS",
+            "TIME", "EVENT", "CDM ID", "TCA", "MISS (m)", "PC"
+        );
+    }
+
+    // Diagnostics go to stderr so the event stream can be piped
+    loop {
+        let page = match events.next_page().await {
+            Ok(page) => page,
+            Err(e) if e.is_rejected() => fail("watch CDMs", e),
+            Err(e) => {
+                eprintln!("Event stream interrupted ({}), retrying", e);
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                continue;
+            }
+        };
+        if page.missed > 0 {
+            eprintln!("Fell behind: {} events were dropped by the node", page.missed);
+        }
+        for event in &page.events {
+            if event.collision_probability.is_none_or(|pc| pc >= min_probability) {
+                print_event(event, format)?;
+            }
+        }
+    }
+}
+
+async fn upcoming_conjunctions(
+    address: &str,
+    token: Option<&str>,
+    window: &str,
+    sort: UpcomingSort,
+    format: OutputFormat,
+) -> Result<()> {
+    let sort = match sort {
+        UpcomingSort::Tca => UpcomingOrder::Tca,
+        UpcomingSort::Risk => UpcomingOrder::Risk,
+    };
+    let upcoming = api_client(address, token)
+        .upcoming_conjunctions(Some(window), sort)
+        .await
+        .unwrap_or_else(|e| fail("fetch upcoming conjunctions", e));
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&upcoming)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<10} {:<20} {:<9} {:>10} {:<8} {:<28} WATCHED",
+        "IN", "TCA", "PC", "MISS (m)", "CATEGORY", "OBJECTS"
+    );
+    for conjunction in &upcoming.conjunctions {
+        let category = conjunction
+            .conjunction_category
+            .as_ref()
+            .map_or("-".to_string(), |c| format!("{:?}", c).to_uppercase());
+        let watched: Vec<&str> = conjunction
+            .watched_assets
+            .iter()
+            .map(|asset| asset.name.as_deref().unwrap_or(&asset.norad_id))
+            .collect();
+        println!(
+            "{:<10} {:<20} {:<9.2e} {:>10.1} {:<8} {:<28} {}",
+            conjunction.countdown,
+            conjunction.tca.format("%Y-%m-%dT%H:%M:%SZ"),
+            conjunction.collision_probability,
+            conjunction.miss_distance_m,
+            category,
+            format!("{} / {}", conjunction.object1_id, conjunction.object2_id),
+            if watched.is_empty() { "-".to_string() } else { watched.join(", ") }
+        );
+    }
+    println!(
+        "{} conjunctions before {}",
+        upcoming.total,
+        upcoming.until.format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    Ok(())
+}
+
+/// A CDM from a JSON file, or from the node if no such file exists
+async fn load_cdm(address: &str, token: Option<&str>, source: &str) -> Result<CdmRecord> {
+    let path = std::path::Path::new(source);
+    if path.is_file() {
+        return parse_cdm(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+    }
+    Ok(api_client(address, token)
+        .get_cdm(source)
+        .await
+        .unwrap_or_else(|e| fail(&format!("fetch CDM {}", source), e)))
+}
+
+fn print_cdm_diff(diff: &CdmDiff, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(diff)?);
+        return Ok(());
+    }
+
+    println!("CDM            {} -> {}", diff.from_cdm_id, diff.to_cdm_id);
+    if diff.objects_differ {
+        println!("WARNING        the CDMs are about different objects");
+    }
+    let pc = &diff.collision_probability;
+    let ratio = diff.pc_ratio.map_or(String::new(), |r| format!(" (x{:.2})", r));
+    println!("Pc             {:.2e} -> {:.2e}{}", pc.from, pc.to, ratio);
+    let miss = &diff.miss_distance_m;
+    println!("Miss distance  {:.1} m -> {:.1} m ({:+.1} m)", miss.from, miss.to, miss.delta);
+    println!("TCA shift      {:+.3} s", diff.tca_shift_seconds);
+    for (label, object) in [("Object 1", &diff.object1), ("Object 2", &diff.object2)] {
+        let sigma = object.sigma_ratio_rtn.map_or("-".to_string(), |[r, t, n]| {
+            format!("x{:.2} / x{:.2} / x{:.2}", r, t, n)
+        });
+        println!(
+            "{:<14} {}: position {:.3} km, velocity {:.3} m/s{}, sigma R/T/N {}",
+            label,
+            object.object_id,
+            object.position_change_km,
+            object.velocity_change_m_s,
+            if object.frame_changed { " (frame changed)" } else { "" },
+            sigma
+        );
+    }
+
+    if diff.is_reissue() {
+        println!("No fields changed besides the CDM ID and creation date");
+        return Ok(());
+    }
+    println!();
+    println!("{:<40} {:<26} {:<26} DELTA", "FIELD", "FROM", "TO");
+    for change in &diff.changes {
+        let delta = change.delta.map_or("-".to_string(), |d| format!("{:+.6e}", d));
+        println!(
+            "{:<40} {:<26} {:<26} {}",
+            change.field,
+            compact(&change.from),
+            compact(&change.to),
+            delta
+        );
+    }
+    Ok(())
+}
+
+/// A JSON value on one line, cut to fit a column
+fn compact(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.chars().count() > 25 {
+        format!("{}...", text.chars().take(22).collect::<String>())
+    } else {
+        text
+    }
+}
+
+async fn object_history(address: &str, token: Option<&str>, id: &str, format: OutputFormat) -> Result<()> {
+    let history = api_client(address, token)
+        .object_history(id)
+        .await
+        .unwrap_or_else(|e| fail("fetch CDM history", e));
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<9} {:<28} {:<16} {:>10} {:<9} {:<8} ORIGINATOR",
+        "TCA", "STATUS", "CDM ID", "OTHER OBJECT", "MISS (m)", "PC", "CATEGORY"
+    );
+    for entry in &history.cdms {
+        let cdm = &entry.cdm;
+        let category = cdm
+            .conjunction_category
+            .as_ref()
+            .map_or("-".to_string(), |c| format!("{:?}", c).to_uppercase());
+        println!(
+            "{:<20} {:<9} {:<28} {:<16} {:>10.1} {:<9.2e} {:<8} {}",
+            cdm.tca.format("%Y-%m-%dT%H:%M:%SZ"),
+            entry.status.to_uppercase(),
+            cdm.cdm_id,
+            entry.other_object_id,
+            cdm.miss_distance_m,
+            cdm.collision_probability,
+            category,
+            cdm.originator
+        );
+    }
+    println!(
+        "{} CDMs ({} active, {} withdrawn)",
+        history.total, history.active, history.withdrawn
+    );
+    Ok(())
+}
+
+/// Install the subscriber, returning a hook that changes its level
+fn print_report(report: &SimulationReport) {
+    println!("{:<5} {:>4}  {:<28} {:<22} {:>6} {:>7}  DETAIL", "", "STEP", "NAME", "MESSAGE", "STATUS", "MS");
+    for step in &report.steps {
+        let status = step.status.map_or("-".to_string(), |s| s.to_string());
+        let detail = if step.passed() {
+            step.reply.as_ref().map(|r| r.to_string()).unwrap_or_default()
+        } else {
+            step.failures.join("; ")
+        };
+        println!(
+            "{:<5} {:>4}  {:<28} {:<22} {:>6} {:>7}  {}",
+            if step.passed() { "PASS" } else { "FAIL" },
+            step.step,
+            step.name,
+            step.message_type.to_string(),
+            status,
+            step.elapsed_ms,
+            detail
+        );
+    }
+    println!("{} passed, {} failed", report.passed, report.failed);
+}
+
+fn print_playback(report: &PlaybackReport) {
+    for rejection in &report.rejections {
+        let status = rejection.status.map_or("-".to_string(), |s| s.to_string());
+        println!(
+            "REJECTED {:>6}  {:<22} {:>6}  {}",
+            rejection.index,
+            rejection.message_type.to_string(),
+            status,
+            rejection.reason
+        );
+    }
+    if report.rejected > report.rejections.len() {
+        println!("... and {} more rejections", report.rejected - report.rejections.len());
+    }
+    println!(
+        "{} sent in {} ms: {} accepted, {} rejected; latency max {} ms, mean {:.1} ms; up to {} ms behind schedule",
+        report.sent,
+        report.elapsed_ms,
+        report.accepted,
+        report.rejected,
+        report.max_latency_ms,
+        report.mean_latency_ms,
+        report.max_lag_ms
+    );
+}
+
+fn print_outcome(outcome: &ReplayOutcome) {
+    println!(
+        "{}: {} messages, {} rejected, {} CDMs and {} objects stored",
+        outcome.label,
+        outcome.messages.len(),
+        outcome.rejected(),
+        outcome.state.cdms.len(),
+        outcome.state.objects.len()
+    );
+}
+
+fn print_preflight(report: &PreflightReport) {
+    let width = report.checks.iter().map(|check| check.check.len()).max().unwrap_or(0);
+    for check in &report.checks {
+        let level = match check.level {
+            CheckLevel::Ok => "ok",
+            CheckLevel::Warning => "WARN",
+            CheckLevel::Error => "ERROR",
+        };
+        println!("{:<5} {:<width$}  {}", level, check.check, check.message, width = width);
+    }
+    println!(
+        "Preflight: {} errors, {} warnings",
+        report.count(CheckLevel::Error),
+        report.count(CheckLevel::Warning)
+    );
+}
+
+fn print_divergence(divergence: &Divergence) -> Result<()> {
+    let show = |value: &Option<serde_json::Value>| -> Result<String> {
+        Ok(match value {
+            Some(value) => serde_json::to_string(value)?,
+            None => "(absent)".to_string(),
+        })
+    };
+    println!("{:?} {}", divergence.kind, divergence.key);
+    println!("  - {}", show(&divergence.baseline)?);
+    println!("  + {}", show(&divergence.candidate)?);
+    Ok(())
+}
+
+fn setup_logging(level: Level) -> LogLevelHook {
+    init_logging(level, None)
+}
+
+/// Install the log subscriber, exporting spans through `telemetry` if given
+fn init_logging(level: Level, telemetry: Option<Box<dyn Layer<Registry> + Send + Sync>>) -> LogLevelHook {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(level.as_str()));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    tracing_subscriber::registry()
+        .with(telemetry)
+        .with(fmt::layer().with_target(true))
+        .with(filter)
+        .init();
+
+    Arc::new(move |level: Level| {
+        handle
+            .reload(EnvFilter::new(level.as_str()))
+            .map_err(|e| Error::Internal(format!("failed to change log level: {}", e)))
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let token = cli.token.as_deref();
+
+    match cli.command {
+        Commands::Start { config, dev, overrides } => {
+            let cfg = if dev {
+                Config::dev().with_overrides(&overrides)?
+            } else {
+                Config::load_with(&config, &overrides)?
+            };
+            let telemetry = spacecomms::telemetry::layer(cfg.telemetry.as_ref(), &cfg.node.id)?;
+            let log_level_hook = init_logging(cfg.logging_level(), telemetry);
+            
+            info!("Starting SpaceComms node: {}", cfg.node.id);
+            
+            let mut node = spacecomms::node::Node::new(cfg).await?.with_log_level_hook(log_level_hook);
+            if !dev {
+                node = node.with_config_path(config).with_config_overrides(overrides);
+            }
+            let result = node.run().await;
+            // Export blocks until the last spans are flushed
+            let _ = tokio::task::spawn_blocking(spacecomms::telemetry::shutdown).await;
+            result?;
+        }
+        Commands::ValidateConfig { config, overrides, strict, json } => {
+            setup_logging(Level::INFO);
+            
+            match Config::load_with(&config, &overrides) {
+                Ok(cfg) => {
+                    info!("Configuration valid");
+                    info!("  Node ID: {}", cfg.node.id);
+                    info!("  Server: {}:{}", cfg.server.host, cfg.server.port);
+                    match &cfg.server.advertise_address {
+                        Some(address) => info!("  Advertised to peers: {}", address),
+                        None => info!(
+                            "  Advertised to peers: not set; peers must be configured with an address that reaches {}:{} (server.advertise_address)",
+                            cfg.server.host, cfg.server.port
+                        ),
+                    }
+                    info!("  Peers configured: {}", cfg.peers.len());
+                    if let Some(signer) = cfg.provenance.signer() {
+                        info!("  Provenance public key: {}", signer.public_key());
+                    }
+                    for rule in &cfg.validation.rules {
+                        info!("  Validation rule {} ({:?})", rule.display_name(), rule.action);
+                    }
+                    if !cfg.policy_templates.is_empty() {
+                        let names: Vec<_> = cfg.policy_templates.keys().map(String::as_str).collect();
+                        info!("  Policy templates: {}", names.join(", "));
+                    }
+                    // Each member with the policies its group resolved to
+                    for (name, group) in &cfg.peer_groups {
+                        let members: Vec<_> = cfg
+                            .peers
+                            .iter()
+                            .filter(|peer| peer.group.as_deref() == Some(name.as_str()))
+                            .collect();
+                        info!(
+                            "  Peer group {} (template: {}): {} peers",
+                            name,
+                            group.template.as_deref().unwrap_or("none"),
+                            members.len()
+                        );
+                        for peer in members {
+                            info!("    {}: {}", peer.id, serde_json::to_string(&peer.policies)?);
+                        }
+                    }
+
+                    let report = preflight(&cfg).await;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&report)?);
+                    } else {
+                        print_preflight(&report);
+                    }
+                    if !report.passed(strict) {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Configuration invalid: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ProvenanceKey => {
+            let seed = ProvenanceSigner::generate_seed();
+            let signer = ProvenanceSigner::from_seed(&seed)?;
+            // The seed goes in this node's configuration, the public key to
+            // the operators of nodes that should trust its hops
+            println!("signing_key: {}", seed);
+            println!("public_key: {}", signer.public_key());
+        }
+        Commands::Config { command: ConfigCommands::Schema } => {
+            println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+        }
+        Commands::Peer { command } => {
+            setup_logging(Level::INFO);
+            
+            match command {
+                PeerCommands::Add { address, peer_id, peer_address } => {
+                    let added = api_client(address, token)
+                        .add_peer(&AddPeer::new(peer_id, peer_address))
+                        .await
+                        .unwrap_or_else(|e| fail("add peer", e));
+                    info!("Peer added successfully");
+                    println!("{}", serde_json::to_string(&added)?);
+                }
+                PeerCommands::List { address } => {
+                    let peers = api_client(address, token)
+                        .list_peers()
+                        .await
+                        .unwrap_or_else(|e| fail("list peers", e));
+                    println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "peers": peers }))?);
+                }
+                PeerCommands::Disable { address, peer_id, reason } => {
+                    let peer = api_client(address, token)
+                        .disable_peer(&peer_id, reason.as_deref())
+                        .await
+                        .unwrap_or_else(|e| fail("disable peer", e));
+                    info!("Peer {} disabled", peer_id);
+                    println!("{}", serde_json::to_string_pretty(&peer)?);
+                }
+                PeerCommands::Enable { address, peer_id } => {
+                    let peer = api_client(address, token)
+                        .enable_peer(&peer_id)
+                        .await
+                        .unwrap_or_else(|e| fail("enable peer", e));
+                    info!("Peer {} enabled", peer_id);
+                    println!("{}", serde_json::to_string_pretty(&peer)?);
+                }
+                PeerCommands::Block { address, node_id, reason } => {
+                    let block = api_client(address, token)
+                        .block_peer(&node_id, reason.as_deref())
+                        .await
+                        .unwrap_or_else(|e| fail("block node", e));
+                    info!("Node {} blocked, {} queued envelopes purged", node_id, block.purged);
+                    println!("{}", serde_json::to_string_pretty(&block)?);
+                }
+                PeerCommands::Unblock { address, node_id } => {
+                    let node = api_client(address, token)
+                        .unblock_peer(&node_id)
+                        .await
+                        .unwrap_or_else(|e| fail("unblock node", e));
+                    info!("Node {} unblocked", node_id);
+                    println!("{}", serde_json::to_string_pretty(&node)?);
+                }
+                PeerCommands::Blocked { address } => {
+                    let nodes = api_client(address, token)
+                        .blocked_peers()
+                        .await
+                        .unwrap_or_else(|e| fail("list blocked nodes", e));
+                    println!("{}", serde_json::to_string_pretty(&nodes)?);
+                }
+                PeerCommands::Export {
+                    address,
+                    json,
+                    include_secrets,
+                    output,
+                } => {
+                    let document = api_client(address, token)
+                        .export_peers(json, include_secrets)
+                        .await
+                        .unwrap_or_else(|e| fail("export peers", e));
+                    match output {
+                        Some(path) => {
+                            std::fs::write(&path, document)?;
+                            info!("Peers written to {}", path.display());
+                        }
+                        None => println!("{}", document.trim_end()),
+                    }
+                }
+                PeerCommands::Import { address, file, prune } => {
+                    let document = std::fs::read_to_string(&file)?;
+                    let json = file.extension().is_some_and(|ext| ext == "json");
+                    let report = api_client(address, token)
+                        .import_peers(document, json, prune)
+                        .await
+                        .unwrap_or_else(|e| fail("import peers", e));
+                    info!(
+                        "Peers imported from {}: {} added, {} updated, {} removed",
+                        report.source_node_id,
+                        report.added.len(),
+                        report.updated.len(),
+                        report.removed.len()
+                    );
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                PeerCommands::Query { address, peer_id, object_ids, from, to, limit } => {
+                    let query = CdmQuery {
+                        object_ids,
+                        tca_from: from.as_deref().map(parse_timestamp).transpose()?,
+                        tca_to: to.as_deref().map(parse_timestamp).transpose()?,
+                        limit,
+                        ..Default::default()
+                    };
+                    let report = api_client(address, token)
+                        .query_peer(&peer_id, &query)
+                        .await
+                        .unwrap_or_else(|e| fail("query peer", e));
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                PeerCommands::Sync { address, peer_id } => {
+                    let report = api_client(address, token)
+                        .sync_peer(&peer_id)
+                        .await
+                        .unwrap_or_else(|e| fail("sync with peer", e));
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+            }
+        }
+        Commands::Cdm { command } => {
+            setup_logging(Level::INFO);
+
+            match command {
+                CdmCommands::Inject { address, file } => {
+                    let content = std::fs::read_to_string(&file)?;
+                    let cdm: CdmRecord = serde_json::from_str(&content)?;
+
+                    let ingested = api_client(address, token)
+                        .ingest_cdm(&cdm)
+                        .await
+                        .unwrap_or_else(|e| fail("inject CDM", e));
+                    if ingested.status == "quarantined" {
+                        info!("CDM quarantined by a validation rule");
+                    } else {
+                        info!("CDM injected successfully");
+                    }
+                    println!("{}", serde_json::to_string(&ingested)?);
+                }
+                CdmCommands::Import { address, file } => {
+                    let archive = tokio::fs::File::open(&file).await?;
+                    let mut import = api_client(address, token)
+                        .import_cdms(archive)
+                        .await
+                        .unwrap_or_else(|e| fail("import CDMs", e));
+                    let mut records = 0u64;
+                    loop {
+                        match import.next().await.unwrap_or_else(|e| fail("import CDMs", e)) {
+                            Some(ImportLine::Record(record)) => {
+                                records += 1;
+                                if let Some(error) = &record.error {
+                                    eprintln!(
+                                        "Line {} ({}): {}",
+                                        record.line,
+                                        record.cdm_id.as_deref().unwrap_or("no cdm_id"),
+                                        error
+                                    );
+                                }
+                                if records.is_multiple_of(10_000) {
+                                    info!("{} lines processed", records);
+                                }
+                            }
+                            Some(ImportLine::Summary(summary)) => {
+                                println!("{}", serde_json::to_string_pretty(&summary)?);
+                                if !summary.complete {
+                                    std::process::exit(1);
+                                }
+                                break;
+                            }
+                            None => {
+                                eprintln!("Import cut off after {} lines", records);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                }
+                CdmCommands::Quarantine { command } => match command {
+                    QuarantineCommands::List { address } => {
+                        let cdms = api_client(address, token)
+                            .quarantined_cdms()
+                            .await
+                            .unwrap_or_else(|e| fail("list quarantined CDMs", e));
+                        println!("{}", serde_json::to_string_pretty(&cdms)?);
+                    }
+                    QuarantineCommands::Release { address, cdm_id } => {
+                        let released = api_client(address, token)
+                            .release_quarantined(&cdm_id)
+                            .await
+                            .unwrap_or_else(|e| fail("release quarantined CDM", e));
+                        info!("CDM {} released to {} peers", cdm_id, released.propagated_to.len());
+                    }
+                    QuarantineCommands::Discard { address, cdm_id } => {
+                        api_client(address, token)
+                            .discard_quarantined(&cdm_id)
+                            .await
+                            .unwrap_or_else(|e| fail("discard quarantined CDM", e));
+                        info!("CDM {} discarded", cdm_id);
+                    }
+                },
+                CdmCommands::Route { address, file, from_peer } => {
+                    let content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+                    // Envelopes are told from CDMs by their message type
+                    let request = if content.get("message_type").is_some() {
+                        RoutingSimulationRequest {
+                            envelope: Some(serde_json::from_value(content)?),
+                            from_peer,
+                            ..Default::default()
+                        }
+                    } else {
+                        RoutingSimulationRequest {
+                            cdm: Some(serde_json::from_value(content)?),
+                            from_peer,
+                            ..Default::default()
+                        }
+                    };
+                    let simulation = api_client(address, token)
+                        .simulate_routing(&request)
+                        .await
+                        .unwrap_or_else(|e| fail("simulate routing", e));
+                    println!("{}", serde_json::to_string_pretty(&simulation)?);
+                }
+                CdmCommands::Propagation { address, cdm_id } => {
+                    let status = api_client(address, token)
+                        .propagation_status(&cdm_id)
+                        .await
+                        .unwrap_or_else(|e| fail("get propagation status", e));
+                    println!("{}", serde_json::to_string_pretty(&status)?);
+                }
+                CdmCommands::Receipts { address, cdm_id } => {
+                    let receipts = api_client(address, token)
+                        .delivery_receipts(&cdm_id)
+                        .await
+                        .unwrap_or_else(|e| fail("get delivery receipts", e));
+                    println!("{}", serde_json::to_string_pretty(&receipts)?);
+                }
+                CdmCommands::List { address, watched } => {
+                    let filter = CdmFilter {
+                        watched: watched.then_some(true),
+                        ..Default::default()
+                    };
+                    let cdms = api_client(address, token)
+                        .list_cdms(&filter)
+                        .await
+                        .unwrap_or_else(|e| fail("list CDMs", e));
+                    println!("{}", serde_json::to_string_pretty(&cdms)?);
+                }
+                CdmCommands::Generate {
+                    object1_id,
+                    object1_name,
+                    object2_id,
+                    object2_name,
+                    tca_hours,
+                    miss_distance,
+                    pc,
+                    count,
+                    seed,
+                    epoch,
+                    post,
+                    address,
+                } => {
+                    let created = epoch.as_deref().map(parse_timestamp).transpose()?.unwrap_or_else(chrono::Utc::now);
+                    let scenario = SyntheticScenario {
+                        object1_id,
+                        object1_name,
+                        object2_id,
+                        object2_name,
+                        tca: created + chrono::Duration::seconds((tca_hours * 3600.0) as i64),
+                        miss_distance_m: miss_distance,
+                        collision_probability: pc,
+                    };
+                    let cdms = scenario.generate(count, created, seed);
+                    if let Some(Err(e)) = cdms.first().map(validate_cdm) {
+                        eprintln!("Invalid scenario: {}", e);
+                        std::process::exit(1);
+                    }
+
+                    if !post {
+                        match cdms.as_slice() {
+                            [cdm] => println!("{}", serde_json::to_string_pretty(cdm)?),
+                            _ => println!("{}", serde_json::to_string_pretty(&cdms)?),
+                        }
+                        return Ok(());
+                    }
+
+                    let client = api_client(address, token);
+                    for cdm in &cdms {
+                        match client.ingest_cdm(cdm).await {
+                            Ok(_) => info!("CDM {} posted", cdm.cdm_id),
+                            Err(e) => fail(&format!("post CDM {}", cdm.cdm_id), e),
+                        }
+                    }
+                }
+                CdmCommands::Upcoming {
+                    address,
+                    window,
+                    sort,
+                    format,
+                } => upcoming_conjunctions(&address, token, &window, sort, format).await?,
+                CdmCommands::Watch {
+                    address,
+                    min_probability,
+                    watched,
+                    format,
+                } => watch_cdms(&address, token, min_probability, watched, format).await?,
+                CdmCommands::Diff { from, to, address, format } => {
+                    let from = load_cdm(&address, token, &from).await?;
+                    let to = load_cdm(&address, token, &to).await?;
+                    print_cdm_diff(&CdmDiff::between(&from, &to), format)?;
+                }
+            }
+        }
+        Commands::Network { command } => {
+            setup_logging(Level::INFO);
+
+            match command {
+                NetworkCommands::Topology { address, dot: true } => {
+                    let dot = api_client(address, token)
+                        .network_topology_dot()
+                        .await
+                        .unwrap_or_else(|e| fail("get network topology", e));
+                    print!("{}", dot);
+                }
+                NetworkCommands::Topology { address, dot: false } => {
+                    let topology = api_client(address, token)
+                        .network_topology()
+                        .await
+                        .unwrap_or_else(|e| fail("get network topology", e));
+                    println!("{}", serde_json::to_string_pretty(&topology)?);
+                }
+                NetworkCommands::Sources { address, object_id } => {
+                    let sources = api_client(address, token)
+                        .object_sources(&object_id)
+                        .await
+                        .unwrap_or_else(|e| fail("find object sources", e));
+                    println!("{}", serde_json::to_string_pretty(&sources)?);
+                }
+            }
+        }
+        Commands::Originators { command } => {
+            setup_logging(Level::INFO);
+
+            match command {
+                OriginatorCommands::List { address } => {
+                    let originators = api_client(address, token)
+                        .originators()
+                        .await
+                        .unwrap_or_else(|e| fail("list originators", e));
+                    println!("{}", serde_json::to_string_pretty(&originators)?);
+                }
+                OriginatorCommands::Release { address, originator } => {
+                    let anomaly = api_client(address, token)
+                        .release_originator(&originator)
+                        .await
+                        .unwrap_or_else(|e| fail("release originator", e));
+                    info!("Originator {} released", originator);
+                    println!("{}", serde_json::to_string_pretty(&anomaly)?);
+                }
+            }
+        }
+        Commands::Watchlist { command } => {
+            setup_logging(Level::INFO);
+
+            match command {
+                WatchlistCommands::Add { address, norad_ids } => {
+                    let ids: Vec<&str> = norad_ids.iter().map(String::as_str).collect();
+                    let update = api_client(address, token)
+                        .watch_assets(&ids)
+                        .await
+                        .unwrap_or_else(|e| fail("update watchlist", e));
+                    println!("{}", serde_json::to_string_pretty(&update)?);
+                }
+                WatchlistCommands::List { address } => {
+                    let assets = api_client(address, token)
+                        .watchlist()
+                        .await
+                        .unwrap_or_else(|e| fail("list watchlist", e));
+                    println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "assets": assets }))?);
+                }
+                WatchlistCommands::Remove { address, norad_id } => {
+                    let update = api_client(address, token)
+                        .unwatch_asset(&norad_id)
+                        .await
+                        .unwrap_or_else(|e| fail("update watchlist", e));
+                    println!("{}", serde_json::to_string_pretty(&update)?);
+                }
+            }
+        }
+        Commands::Alerts { command } => {
+            setup_logging(Level::INFO);
+
+            let output = match command {
+                AlertCommands::List {
+                    address,
+                    state,
+                    min_severity,
+                    assignee,
+                } => {
+                    let filter = AlertFilter {
+                        state: state.map(Into::into),
+                        min_severity: min_severity.map(Into::into),
+                        assignee,
+                        ..Default::default()
+                    };
+                    let alerts = api_client(address, token)
+                        .alerts(&filter)
+                        .await
+                        .unwrap_or_else(|e| fail("list alerts", e));
+                    serde_json::to_value(alerts)?
+                }
+                AlertCommands::Ack { address, alert_id, by } => {
+                    let alert = api_client(address, token)
+                        .acknowledge_alert(&alert_id, by.as_deref())
+                        .await
+                        .unwrap_or_else(|e| fail("acknowledge alert", e));
+                    serde_json::to_value(alert)?
+                }
+                AlertCommands::Assign {
+                    address,
+                    alert_id,
+                    assignee,
+                } => {
+                    let alert = api_client(address, token)
+                        .assign_alert(&alert_id, assignee.as_deref())
+                        .await
+                        .unwrap_or_else(|e| fail("assign alert", e));
+                    serde_json::to_value(alert)?
+                }
+                AlertCommands::Resolve {
+                    address,
+                    alert_id,
+                    resolution,
+                } => {
+                    let alert = api_client(address, token)
+                        .resolve_alert(&alert_id, resolution.as_deref())
+                        .await
+                        .unwrap_or_else(|e| fail("resolve alert", e));
+                    serde_json::to_value(alert)?
+                }
+            };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        Commands::Objects {
+            command: Some(ObjectCommands::History { id, address, format }),
+            ..
+        } => object_history(&address, token, &id, format).await?,
+        Commands::Objects {
+            command: Some(ObjectCommands::State { id, at, two_body, address }),
+            ..
+        } => {
+            let at = at.as_deref().map(parse_timestamp).transpose()?;
+            let model = if two_body { PropagationModel::TwoBody } else { PropagationModel::J2 };
+            let state = api_client(address, token)
+                .object_state(&id, at, model)
+                .await
+                .unwrap_or_else(|e| fail("predict object state", e));
+            println!("{}", serde_json::to_string_pretty(&state)?);
+        }
+        Commands::Objects {
+            command: Some(ObjectCommands::Resolve { query, address }),
+            ..
+        } => {
+            let resolved = api_client(address, token)
+                .resolve_object(&query)
+                .await
+                .unwrap_or_else(|e| fail("resolve object", e));
+            println!("{}", serde_json::to_string_pretty(&resolved)?);
+        }
+        Commands::Objects { command: None, address } => {
+            setup_logging(Level::INFO);
+
+            let objects = api_client(address, token)
+                .list_objects()
+                .await
+                .unwrap_or_else(|e| fail("list objects", e));
+            println!("{}", serde_json::to_string_pretty(&objects)?);
+        }
+        Commands::Replay {
+            log,
+            config,
+            against,
+            baseline,
+            save,
+            json,
+        } => {
+            setup_logging(Level::WARN);
+
+            let messages = load_message_log(&log)?;
+            let outcome = replay(&config.display().to_string(), Config::load(&config)?, &messages).await?;
+            if let Some(path) = &save {
+                std::fs::write(path, serde_json::to_string_pretty(&outcome)?)?;
+            }
+
+            let (baseline, candidate) = match (against, baseline) {
+                (Some(other), _) => {
+                    let other = replay(&other.display().to_string(), Config::load(&other)?, &messages).await?;
+                    (outcome, other)
+                }
+                (None, Some(path)) => (ReplayOutcome::load(&path)?, outcome),
+                (None, None) => {
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&outcome)?);
+                    } else {
+                        print_outcome(&outcome);
+                    }
+                    return Ok(());
+                }
+            };
+
+            let divergences = diff(&baseline, &candidate);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&divergences)?);
+            } else {
+                print_outcome(&baseline);
+                print_outcome(&candidate);
+                for divergence in &divergences {
+                    print_divergence(divergence)?;
+                }
+                println!("{} divergences", divergences.len());
+            }
+            if !divergences.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::SimulatePeer { target, script, json } => {
+            setup_logging(if json { Level::WARN } else { Level::INFO });
+
+            let scenario = match Scenario::load(&script) {
+                Ok(scenario) => scenario,
+                Err(e) => {
+                    eprintln!("Invalid scenario: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            info!("Simulating peer {} against {}", scenario.node_id, target);
+
+            let report = PeerSimulator::new(&target, scenario).run().await;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_report(&report);
+            }
+            if !report.success() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Simulate {
+            target,
+            log,
+            speed,
+            max_gap,
+            as_peer,
+            json,
+        } => {
+            setup_logging(if json { Level::WARN } else { Level::INFO });
+
+            if !(speed >= 0.0 && speed.is_finite()) || max_gap.is_some_and(|gap| !(gap >= 0.0 && gap.is_finite())) {
+                eprintln!("--speed and --max-gap must be finite and not negative");
+                std::process::exit(2);
+            }
+            let messages = load_message_log(&log)?;
+            info!("Playing {} messages into {} at {}x", messages.len(), target, speed);
+
+            let options = PlaybackOptions {
+                speed,
+                max_gap: max_gap.map(Duration::from_secs_f64),
+                as_peer,
+                auth_token: token.map(str::to_string),
+                ..Default::default()
+            };
+            let report = Playback::new(&target, options).run(&messages).await;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_playback(&report);
+            }
+            if !report.success() {
+                std::process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! `spacecomms cdm generate` run as a user would
+
+use spacecomms::cdm::{parse_cdm, validate_cdm};
+use std::process::Command;
+
+/// Run the CLI and return what it printed
+fn spacecomms(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_spacecomms"))
+        .args(args)
+        .output()
+        .expect("CLI runs");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("output is UTF-8")
+}
+
+#[test]
+fn test_generated_cdms_parse_and_validate() {
+    let printed = spacecomms(&["cdm", "generate", "--count", "3", "--tca-hours", "6", "--miss-distance", "40", "--pc", "3e-3"]);
+    let values: Vec<serde_json::Value> = serde_json::from_str(&printed).unwrap();
+    assert_eq!(values.len(), 3);
+    for value in values {
+        let cdm = parse_cdm(value).unwrap();
+        assert!(validate_cdm(&cdm).is_ok());
+        assert_eq!(cdm.miss_distance_m, 40.0);
+        assert_eq!(cdm.collision_probability, 3e-3);
+        assert_eq!((cdm.tca - cdm.creation_date).num_hours(), 6);
+    }
+
+    // A single CDM is printed on its own
+    let single: serde_json::Value = serde_json::from_str(&spacecomms(&["cdm", "generate"])).unwrap();
+    assert!(parse_cdm(single).is_ok());
+}
+
+#[test]
+fn test_seeded_generation_is_reproducible() {
+    let args = ["cdm", "generate", "--count", "2", "--seed", "42", "--epoch", "2024-01-15T12:00:00Z"];
+    let first = spacecomms(&args);
+    assert_eq!(first, spacecomms(&args));
+    assert_ne!(first, spacecomms(&["cdm", "generate", "--count", "2", "--seed", "43", "--epoch", "2024-01-15T12:00:00Z"]));
+
+    let cdms: Vec<serde_json::Value> = serde_json::from_str(&first).unwrap();
+    assert_ne!(cdms[0]["cdm_id"], cdms[1]["cdm_id"]);
+    let cdm = parse_cdm(cdms[0].clone()).unwrap();
+    assert_eq!(cdm.creation_date.to_rfc3339(), "2024-01-15T12:00:00+00:00");
+    assert_eq!(cdm.tca.to_rfc3339(), "2024-01-17T12:00:00+00:00");
+}
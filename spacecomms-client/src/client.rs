@@ -0,0 +1,663 @@
+//! HTTP client for one node
+
+use crate::types::*;
+use crate::{Error, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Body, Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use spacecomms::catalog::ResolvedObject;
+use spacecomms::cdm::CdmRecord;
+use spacecomms::node::{
+    Alert, CdmEventPage, CdmQueryReport, ImportLine, ObjectSources, PeeringImportReport, OriginatorAnomaly, OriginatorStatus, PeerInfo, PropagationStatus,
+    QuarantinedCdm, RoutingSimulation, RoutingSimulationRequest, SyncReport, Topology, WatchedAsset, IDEMPOTENCY_KEY_HEADER,
+};
+use spacecomms::orbit::PropagationModel;
+use spacecomms::protocol::{CdmQuery, DeliveryReceiptPayload};
+use spacecomms::storage::BlockedNode;
+use std::time::Duration;
+
+/// Seconds each events poll is held open by the node, by default
+const DEFAULT_POLL_SECONDS: u64 = 30;
+
+/// Client for a node's REST API
+#[derive(Debug, Clone)]
+pub struct SpaceCommsClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl SpaceCommsClient {
+    /// Client for the node at `base_url`, e.g. `http://localhost:8080`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// Send a bearer token with every request
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Use a preconfigured HTTP client (TLS roots, proxies, timeouts)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Base URL of the node
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Send a request, turning error statuses into [`Error::Api`]
+    async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        let resp = request.send().await?;
+        if resp.status().is_success() {
+            return Ok(resp.json().await?);
+        }
+        Err(Self::api_error(resp).await)
+    }
+
+    /// The [`Error::Api`] an error response describes
+    async fn api_error(resp: Response) -> Error {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        let (error, message) = match serde_json::from_str::<ErrorBody>(&body) {
+            Ok(body) => (body.error, body.message),
+            Err(_) => ("http_error".to_string(), body),
+        };
+        Error::Api {
+            status: status.as_u16(),
+            error,
+            message,
+        }
+    }
+
+    /// Node status and counters
+    pub async fn health(&self) -> Result<Health> {
+        Self::send(self.request(Method::GET, "/health")).await
+    }
+
+    /// Submit a CDM for validation, storage and propagation
+    pub async fn ingest_cdm(&self, cdm: &CdmRecord) -> Result<IngestResponse> {
+        Self::send(self.request(Method::POST, "/cdm").json(cdm)).await
+    }
+
+    /// Submit a CDM under an idempotency key; resending with the same key
+    /// and CDM returns the first response without the node forwarding the
+    /// CDM again, so it is safe to retry after a timeout
+    pub async fn ingest_cdm_with_key(&self, cdm: &CdmRecord, key: &str) -> Result<IngestResponse> {
+        Self::send(self.request(Method::POST, "/cdm").header(IDEMPOTENCY_KEY_HEADER, key).json(cdm)).await
+    }
+
+    /// Import historical CDMs from a body of NDJSON lines, gzip compressed
+    /// or not, such as an open archive file. They are stored without being
+    /// announced to peers; read the outcome of each line from the returned
+    /// [`CdmImport`] as the node works through the body.
+    pub async fn import_cdms(&self, body: impl Into<Body>) -> Result<CdmImport> {
+        let resp = self
+            .request(Method::POST, "/cdm/import")
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(Self::api_error(resp).await);
+        }
+        Ok(CdmImport {
+            resp,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Active CDMs matching a filter
+    pub async fn list_cdms(&self, filter: &CdmFilter) -> Result<CdmList> {
+        Self::send(self.request(Method::GET, "/cdms").query(filter)).await
+    }
+
+    /// A stored CDM; [`Error::is_not_found`] if the node does not have it
+    pub async fn get_cdm(&self, cdm_id: &str) -> Result<CdmRecord> {
+        Self::send(self.request(Method::GET, &format!("/cdms/{}", cdm_id))).await
+    }
+
+    /// Withdraw a CDM and propagate the withdrawal to peers
+    pub async fn withdraw_cdm(&self, cdm_id: &str, reason: &str) -> Result<WithdrawResponse> {
+        let body = serde_json::json!({ "reason": reason });
+        Self::send(self.request(Method::DELETE, &format!("/cdms/{}", cdm_id)).json(&body)).await
+    }
+
+    /// Which peers the node forwarded a CDM's latest announcement or
+    /// withdrawal to, and which of them confirmed receipt
+    pub async fn propagation_status(&self, cdm_id: &str) -> Result<PropagationStatus> {
+        Self::send(self.request(Method::GET, &format!("/cdms/{}/propagation", cdm_id))).await
+    }
+
+    /// Delivery receipts recorded on a CDM the node originated: the nodes
+    /// it reached whose watchlists matched, oldest first
+    pub async fn delivery_receipts(&self, cdm_id: &str) -> Result<Vec<DeliveryReceiptPayload>> {
+        #[derive(serde::Deserialize)]
+        struct Receipts {
+            receipts: Vec<DeliveryReceiptPayload>,
+        }
+        let list: Receipts = Self::send(self.request(Method::GET, &format!("/cdms/{}/receipts", cdm_id))).await?;
+        Ok(list.receipts)
+    }
+
+    /// CDMs held by a quarantining validation rule, newest first
+    pub async fn quarantined_cdms(&self) -> Result<Vec<QuarantinedCdm>> {
+        #[derive(serde::Deserialize)]
+        struct QuarantineList {
+            cdms: Vec<QuarantinedCdm>,
+        }
+        let list: QuarantineList = Self::send(self.request(Method::GET, "/cdms/quarantine")).await?;
+        Ok(list.cdms)
+    }
+
+    /// Store and propagate a quarantined CDM
+    pub async fn release_quarantined(&self, cdm_id: &str) -> Result<QuarantineRelease> {
+        Self::send(self.request(Method::POST, &format!("/cdms/quarantine/{}/release", cdm_id))).await
+    }
+
+    /// Drop a quarantined CDM
+    pub async fn discard_quarantined(&self, cdm_id: &str) -> Result<QuarantinedCdm> {
+        Self::send(self.request(Method::DELETE, &format!("/cdms/quarantine/{}", cdm_id))).await
+    }
+
+    /// Conjunctions with TCA within `window` (such as `24h`; the node's
+    /// default when `None`)
+    pub async fn upcoming_conjunctions(&self, window: Option<&str>, sort: UpcomingOrder) -> Result<UpcomingConjunctions> {
+        let mut request = self.request(Method::GET, "/conjunctions/upcoming").query(&[("sort", sort)]);
+        if let Some(window) = window {
+            request = request.query(&[("window", window)]);
+        }
+        Self::send(request).await
+    }
+
+    /// Every CDM of a conjunction and which way its risk is moving
+    pub async fn conjunction_trend(&self, conjunction_id: &str) -> Result<ConjunctionTrend> {
+        Self::send(self.request(Method::GET, &format!("/conjunctions/{}/trend", conjunction_id))).await
+    }
+
+    /// Objects the node tracks
+    pub async fn list_objects(&self) -> Result<ObjectList> {
+        Self::send(self.request(Method::GET, "/objects")).await
+    }
+
+    /// Active and withdrawn CDMs involving an object, ordered by TCA
+    pub async fn object_history(&self, object_id: &str) -> Result<ObjectCdmHistory> {
+        Self::send(self.request(Method::GET, &format!("/objects/{}/cdms", object_id))).await
+    }
+
+    /// Catalog entry for an object name, catalog number or international
+    /// designator, with the IDs the object is also known by
+    pub async fn resolve_object(&self, query: &str) -> Result<ResolvedObject> {
+        Self::send(self.request(Method::GET, "/catalog/resolve").query(&[("query", query)])).await
+    }
+
+    /// An object's state propagated to `at`, or to now when `None`
+    pub async fn object_state(
+        &self,
+        object_id: &str,
+        at: Option<DateTime<Utc>>,
+        model: PropagationModel,
+    ) -> Result<ObjectState> {
+        let mut request = self
+            .request(Method::GET, &format!("/objects/{}/state", object_id))
+            .query(&[("model", model)]);
+        if let Some(at) = at {
+            request = request.query(&[("at", at.to_rfc3339_opts(SecondsFormat::Millis, true))]);
+        }
+        Self::send(request).await
+    }
+
+    /// Versions of an object's stored state with epochs between `from` and
+    /// `to`, each with its difference from the version before
+    pub async fn object_state_history(
+        &self,
+        object_id: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<ObjectStateHistory> {
+        let mut request = self.request(Method::GET, &format!("/objects/{}/history", object_id));
+        for (name, bound) in [("from", from), ("to", to)] {
+            if let Some(bound) = bound {
+                request = request.query(&[(name, bound.to_rfc3339_opts(SecondsFormat::Millis, true))]);
+            }
+        }
+        Self::send(request).await
+    }
+
+    /// Configured peers and their link status
+    pub async fn list_peers(&self) -> Result<Vec<PeerInfo>> {
+        #[derive(serde::Deserialize)]
+        struct PeerList {
+            peers: Vec<PeerInfo>,
+        }
+        let list: PeerList = Self::send(self.request(Method::GET, "/peers")).await?;
+        Ok(list.peers)
+    }
+
+    /// Add a peer; the node starts connecting to it straight away
+    pub async fn add_peer(&self, peer: &AddPeer) -> Result<PeerChange> {
+        Self::send(self.request(Method::POST, "/peers").json(peer)).await
+    }
+
+    /// Remove a peer and close its session
+    pub async fn remove_peer(&self, peer_id: &str) -> Result<PeerChange> {
+        Self::send(self.request(Method::DELETE, &format!("/peers/{}", peer_id))).await
+    }
+
+    /// Shut a peer's session down for maintenance, keeping its configuration;
+    /// the peer is told why with `reason`
+    pub async fn disable_peer(&self, peer_id: &str, reason: Option<&str>) -> Result<PeerInfo> {
+        let body = serde_json::json!({ "reason": reason });
+        Self::send(self.request(Method::POST, &format!("/peers/{}/disable", peer_id)).json(&body)).await
+    }
+
+    /// Bring a disabled peer's session back up
+    pub async fn enable_peer(&self, peer_id: &str) -> Result<PeerInfo> {
+        Self::send(self.request(Method::POST, &format!("/peers/{}/enable", peer_id))).await
+    }
+
+    /// Block a node, peer or not: its session is dropped, its queued
+    /// envelopes purged and anything from or through it refused
+    pub async fn block_peer(&self, node_id: &str, reason: Option<&str>) -> Result<PeerBlock> {
+        let body = serde_json::json!({ "reason": reason });
+        Self::send(self.request(Method::POST, &format!("/peers/{}/block", node_id)).json(&body)).await
+    }
+
+    /// Take a node off the blocklist
+    pub async fn unblock_peer(&self, node_id: &str) -> Result<BlockedNode> {
+        Self::send(self.request(Method::DELETE, &format!("/peers/{}/block", node_id))).await
+    }
+
+    /// Nodes on the blocklist
+    pub async fn blocked_peers(&self) -> Result<Vec<BlockedNode>> {
+        Self::send(self.request(Method::GET, "/peers/blocked")).await
+    }
+
+    /// The node's peers with their settings and policies, as a YAML or JSON
+    /// document for [`Self::import_peers`]
+    pub async fn export_peers(&self, json: bool, include_secrets: bool) -> Result<String> {
+        let format = if json { "json" } else { "yaml" };
+        let resp = self
+            .request(Method::GET, "/peers/export")
+            .query(&[("format", format)])
+            .query(&[("include_secrets", include_secrets)])
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(Self::api_error(resp).await);
+        }
+        Ok(resp.text().await?)
+    }
+
+    /// Apply a peering document from [`Self::export_peers`]; with `prune`,
+    /// peers the document does not list are removed
+    pub async fn import_peers(&self, document: String, json: bool, prune: bool) -> Result<PeeringImportReport> {
+        let content_type = if json { "application/json" } else { "application/yaml" };
+        let request = self
+            .request(Method::POST, "/peers/import")
+            .query(&[("prune", prune)])
+            .header(CONTENT_TYPE, content_type)
+            .body(document);
+        Self::send(request).await
+    }
+
+    /// Have the node pull CDMs matching `query` from a connected peer
+    pub async fn query_peer(&self, peer_id: &str, query: &CdmQuery) -> Result<CdmQueryReport> {
+        Self::send(self.request(Method::POST, &format!("/peers/{}/cdm-query", peer_id)).json(query)).await
+    }
+
+    /// Have the node compare digests with a connected peer and pull the
+    /// CDMs it is missing
+    pub async fn sync_peer(&self, peer_id: &str) -> Result<SyncReport> {
+        Self::send(self.request(Method::POST, &format!("/peers/{}/sync", peer_id))).await
+    }
+
+    /// How the node would route an envelope or CDM, and to which peers;
+    /// nothing is stored or sent
+    pub async fn simulate_routing(&self, request: &RoutingSimulationRequest) -> Result<RoutingSimulation> {
+        Self::send(self.request(Method::POST, "/routing/simulate").json(request)).await
+    }
+
+    /// Nodes and links of the mesh seen from the node: its peers, and nodes
+    /// further out that stored CDMs passed through
+    pub async fn network_topology(&self) -> Result<Topology> {
+        Self::send(self.request(Method::GET, "/network/topology")).await
+    }
+
+    /// The mesh seen from the node, as a Graphviz DOT graph
+    pub async fn network_topology_dot(&self) -> Result<String> {
+        let resp = self.request(Method::GET, "/network/topology").query(&[("format", "dot")]).send().await?;
+        if !resp.status().is_success() {
+            return Err(Self::api_error(resp).await);
+        }
+        Ok(resp.text().await?)
+    }
+
+    /// Nodes in the mesh announcing an object, and the peers interested in it
+    pub async fn object_sources(&self, object_id: &str) -> Result<ObjectSources> {
+        Self::send(self.request(Method::GET, &format!("/network/objects/{}/sources", object_id))).await
+    }
+
+    /// Ingest counts of the originators seen in the last hour, and any hold
+    pub async fn originators(&self) -> Result<Vec<OriginatorStatus>> {
+        Self::send(self.request(Method::GET, "/originators")).await
+    }
+
+    /// Take a held originator's CDMs again
+    pub async fn release_originator(&self, originator: &str) -> Result<OriginatorAnomaly> {
+        Self::send(self.request(Method::DELETE, &format!("/originators/{}/hold", originator))).await
+    }
+
+    /// Assets on the node's watchlist
+    pub async fn watchlist(&self) -> Result<Vec<WatchedAsset>> {
+        #[derive(serde::Deserialize)]
+        struct Watchlist {
+            assets: Vec<WatchedAsset>,
+        }
+        let list: Watchlist = Self::send(self.request(Method::GET, "/watchlist")).await?;
+        Ok(list.assets)
+    }
+
+    /// Add assets by NORAD catalog number; CDMs involving them are tagged
+    /// `involves_watched_asset`
+    pub async fn watch_assets(&self, norad_ids: &[&str]) -> Result<WatchlistUpdate> {
+        let assets: Vec<_> = norad_ids.iter().map(|id| serde_json::json!({ "norad_id": id })).collect();
+        let body = serde_json::json!({ "assets": assets });
+        Self::send(self.request(Method::POST, "/watchlist").json(&body)).await
+    }
+
+    /// Remove an asset from the watchlist
+    pub async fn unwatch_asset(&self, norad_id: &str) -> Result<WatchlistUpdate> {
+        Self::send(self.request(Method::DELETE, &format!("/watchlist/{}", norad_id))).await
+    }
+
+    /// Alerts raised for conjunctions of watched assets, ordered by TCA
+    pub async fn alerts(&self, filter: &AlertFilter) -> Result<AlertList> {
+        Self::send(self.request(Method::GET, "/alerts").query(filter)).await
+    }
+
+    /// One alert
+    pub async fn get_alert(&self, alert_id: &str) -> Result<Alert> {
+        Self::send(self.request(Method::GET, &format!("/alerts/{}", alert_id))).await
+    }
+
+    /// Acknowledge an open alert, as `by` or else as the client's token
+    pub async fn acknowledge_alert(&self, alert_id: &str, by: Option<&str>) -> Result<Alert> {
+        let body = serde_json::json!({ "by": by });
+        Self::send(self.request(Method::POST, &format!("/alerts/{}/acknowledge", alert_id)).json(&body)).await
+    }
+
+    /// Assign an alert to a person or token, or unassign it with `None`
+    pub async fn assign_alert(&self, alert_id: &str, assignee: Option<&str>) -> Result<Alert> {
+        let body = serde_json::json!({ "assignee": assignee });
+        Self::send(self.request(Method::POST, &format!("/alerts/{}/assign", alert_id)).json(&body)).await
+    }
+
+    /// Resolve an alert with an optional note
+    pub async fn resolve_alert(&self, alert_id: &str, resolution: Option<&str>) -> Result<Alert> {
+        let body = serde_json::json!({ "resolution": resolution });
+        Self::send(self.request(Method::POST, &format!("/alerts/{}/resolve", alert_id)).json(&body)).await
+    }
+
+    /// Follow CDM announcements and withdrawals from sequence number
+    /// `since`, or only new events when `None`
+    pub fn stream_events(&self, since: Option<u64>) -> EventStream {
+        EventStream {
+            client: self.clone(),
+            since,
+            poll: Duration::from_secs(DEFAULT_POLL_SECONDS),
+            watched: false,
+        }
+    }
+}
+
+/// Cursor over the node's CDM event feed, read by long polling
+#[derive(Debug, Clone)]
+pub struct EventStream {
+    client: SpaceCommsClient,
+    since: Option<u64>,
+    poll: Duration,
+    watched: bool,
+}
+
+impl EventStream {
+    /// How long the node holds each poll open waiting for events
+    pub fn with_poll_timeout(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// Only return events about CDMs involving a watched asset
+    pub fn watched_only(mut self) -> Self {
+        self.watched = true;
+        self
+    }
+
+    /// Sequence number the next page starts from
+    pub fn position(&self) -> Option<u64> {
+        self.since
+    }
+
+    /// Wait for the next page of events and advance past it
+    ///
+    /// Returns an empty page when the poll times out. Events the node
+    /// dropped before they were read are counted in `missed`. On error the
+    /// position is unchanged, so calling again resumes where it left off.
+    pub async fn next_page(&mut self) -> Result<CdmEventPage> {
+        let mut request = self
+            .client
+            .request(Method::GET, "/events/cdms")
+            .query(&[("timeout_seconds", self.poll.as_secs())])
+            .timeout(self.poll + Duration::from_secs(10));
+        if let Some(since) = self.since {
+            request = request.query(&[("since", since)]);
+        }
+        if self.watched {
+            request = request.query(&[("watched", true)]);
+        }
+        let page: CdmEventPage = SpaceCommsClient::send(request).await?;
+        self.since = Some(page.next_seq);
+        Ok(page)
+    }
+}
+
+/// Results of a running CDM import, one per input line, then its summary
+#[derive(Debug)]
+pub struct CdmImport {
+    resp: Response,
+    /// Response bytes after the last complete line
+    pending: Vec<u8>,
+}
+
+impl CdmImport {
+    /// The next result, or `None` after the summary
+    ///
+    /// An import that ends without an [`ImportLine::Summary`] was cut off;
+    /// lines after the last result returned may not have been imported.
+    pub async fn next(&mut self) -> Result<Option<ImportLine>> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return serde_json::from_slice(&line).map(Some).map_err(|e| Error::Decode(e.to_string()));
+            }
+            match self.resp.chunk().await? {
+                Some(chunk) => self.pending.extend_from_slice(&chunk),
+                None if self.pending.iter().all(u8::is_ascii_whitespace) => return Ok(None),
+                None => self.pending.push(b'\n'),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacecomms::cdm::generate_demo_cdm;
+    use spacecomms::node::{NodeServer, PeerManager, RoutingEngine};
+    use spacecomms::storage::MemoryStorage;
+    use spacecomms::Config;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    /// Serve a fresh node on an ephemeral port
+    async fn spawn_node() -> SpaceCommsClient {
+        let config = Config::dev();
+        let server = NodeServer::new(
+            config.clone(),
+            Arc::new(MemoryStorage::new()),
+            Arc::new(RwLock::new(PeerManager::new())),
+            Arc::new(RoutingEngine::new(config)),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, server.router()).await });
+        SpaceCommsClient::new(format!("http://{}/", address))
+    }
+
+    #[tokio::test]
+    async fn test_cdm_round_trip() {
+        let client = spawn_node().await;
+        let cdm = generate_demo_cdm();
+        let mut events = client.stream_events(Some(0)).with_poll_timeout(Duration::from_secs(1));
+
+        let ingested = client.ingest_cdm_with_key(&cdm, "key-1").await.unwrap();
+        assert_eq!(ingested.cdm_id, cdm.cdm_id);
+        let retried = client.ingest_cdm_with_key(&cdm, "key-1").await.unwrap();
+        assert_eq!(retried.propagated_to, ingested.propagated_to);
+        assert_eq!(client.health().await.unwrap().cdms_active, 1);
+        assert_eq!(client.get_cdm(&cdm.cdm_id).await.unwrap().cdm_id, cdm.cdm_id);
+
+        let involving = CdmFilter {
+            object_id: Some(cdm.object2.object_id.clone()),
+            ..Default::default()
+        };
+        assert_eq!(client.list_cdms(&involving).await.unwrap().total, 1);
+        let unlikely = CdmFilter {
+            min_probability: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(client.list_cdms(&unlikely).await.unwrap().total, 0);
+
+        let update = client.watch_assets(&["NORAD-12345"]).await.unwrap();
+        assert_eq!((update.changed, update.retagged), (vec!["12345".to_string()], 1));
+        assert_eq!(client.watchlist().await.unwrap()[0].norad_id, "12345");
+        let watched = CdmFilter {
+            watched: Some(true),
+            ..Default::default()
+        };
+        assert!(client.list_cdms(&watched).await.unwrap().cdms[0].involves_watched_asset);
+        assert_eq!(client.upcoming_conjunctions(None, UpcomingOrder::Tca).await.unwrap().total, 0);
+        let upcoming = client.upcoming_conjunctions(Some("3d"), UpcomingOrder::Risk).await.unwrap();
+        assert_eq!(upcoming.conjunctions[0].watched_assets[0].norad_id, "12345");
+        let alerts = client.alerts(&AlertFilter::default()).await.unwrap();
+        assert_eq!((alerts.total, alerts.open), (1, 1));
+        let alert_id = &alerts.alerts[0].alert_id;
+        let acked = client.acknowledge_alert(alert_id, Some("alice")).await.unwrap();
+        assert_eq!(acked.acknowledged_by.as_deref(), Some("alice"));
+        assert_eq!(client.assign_alert(alert_id, Some("bob")).await.unwrap().assignee.as_deref(), Some("bob"));
+        client.resolve_alert(alert_id, Some("screened")).await.unwrap();
+        assert_eq!(client.get_alert(alert_id).await.unwrap().resolution.as_deref(), Some("screened"));
+        assert_eq!(client.assign_alert(alert_id, None).await.unwrap_err().status(), Some(409));
+        client.unwatch_asset("12345").await.unwrap();
+        assert!(client.unwatch_asset("12345").await.unwrap_err().is_not_found());
+        assert_eq!(client.list_cdms(&watched).await.unwrap().total, 0);
+
+        client.withdraw_cdm(&cdm.cdm_id, "test").await.unwrap();
+        let history = client.object_history(&cdm.object1.object_id).await.unwrap();
+        assert_eq!((history.active, history.withdrawn), (0, 1));
+        assert_eq!(history.cdms[0].cdm.cdm_id, cdm.cdm_id);
+
+        // The retry was not announced again
+        let page = events.next_page().await.unwrap();
+        assert_eq!(page.events.len(), 2);
+        assert_eq!(events.position(), Some(page.next_seq));
+
+        let missing = client.get_cdm(&cdm.cdm_id).await.unwrap_err();
+        assert!(missing.is_not_found());
+        assert!(client.object_state_history("NORAD-0", None, None).await.unwrap_err().is_not_found());
+        assert!(missing.is_rejected());
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        use spacecomms::node::ImportStatus;
+
+        let client = spawn_node().await;
+        let mut archive = String::new();
+        for i in 0..2000 {
+            let mut cdm = generate_demo_cdm();
+            cdm.cdm_id = format!("CDM-ARCHIVE-{}", i);
+            if i == 7 {
+                cdm.collision_probability = 2.0;
+            }
+            archive.push_str(&serde_json::to_string(&cdm).unwrap());
+            archive.push('\n');
+        }
+
+        let mut import = client.import_cdms(archive).await.unwrap();
+        let mut records = 0;
+        let summary = loop {
+            match import.next().await.unwrap().unwrap() {
+                ImportLine::Record(record) => {
+                    records += 1;
+                    let expected = if record.line == 8 { ImportStatus::Rejected } else { ImportStatus::Imported };
+                    assert_eq!(record.status, expected);
+                }
+                ImportLine::Summary(summary) => break summary,
+            }
+        };
+        assert!(import.next().await.unwrap().is_none());
+        assert_eq!(records, 2000);
+        assert_eq!((summary.imported, summary.rejected, summary.complete), (1999, 1, true));
+        assert_eq!(client.health().await.unwrap().cdms_active, 1999);
+    }
+
+    #[tokio::test]
+    async fn test_peers_and_errors() {
+        let client = spawn_node().await;
+        let added = client.add_peer(&AddPeer::new("peer-x", "http://127.0.0.1:9")).await.unwrap();
+        assert_eq!(added.peer_id, "peer-x");
+        let peers = client.list_peers().await.unwrap();
+        assert!(peers.iter().any(|p| p.id == "peer-x"));
+        let query = CdmQuery::default();
+        match client.query_peer("peer-x", &query).await.unwrap_err() {
+            // Added but not connected
+            Error::Api { status, error, .. } => assert_eq!((status, error.as_str()), (409, "peer_unavailable")),
+            other => panic!("unexpected error: {}", other),
+        }
+        client.remove_peer("peer-x").await.unwrap();
+
+        let mut invalid = generate_demo_cdm();
+        invalid.collision_probability = 2.0;
+        match client.ingest_cdm(&invalid).await.unwrap_err() {
+            Error::Api { status, error, .. } => assert_eq!((status, error.as_str()), (400, "validation_failed")),
+            other => panic!("unexpected error: {}", other),
+        }
+
+        let missing = client.object_state("NORAD-0", None, PropagationModel::J2).await.unwrap_err();
+        assert!(missing.is_not_found());
+
+        let unreachable = SpaceCommsClient::new("http://127.0.0.1:9");
+        assert!(!unreachable.health().await.unwrap_err().is_rejected());
+    }
+}
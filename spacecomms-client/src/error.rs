@@ -0,0 +1,49 @@
+//! Client error types
+
+use thiserror::Error;
+
+/// Result type for client calls
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by client calls
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The node could not be reached or its response could not be read
+    #[error("HTTP client error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The node answered with an error status
+    #[error("{error} ({status}): {message}")]
+    Api {
+        status: u16,
+        /// Machine-readable code, e.g. `validation_failed`
+        error: String,
+        message: String,
+    },
+
+    /// The node's response could not be understood
+    #[error("Invalid response: {0}")]
+    Decode(String),
+}
+
+impl Error {
+    /// HTTP status of an API error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            Error::Http(e) => e.status().map(|s| s.as_u16()),
+            Error::Decode(_) => None,
+        }
+    }
+
+    /// Returns true if the node does not know the requested record
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+
+    /// Returns true if the node refused the request itself, so sending it
+    /// again unchanged will not help
+    pub fn is_rejected(&self) -> bool {
+        matches!(self, Error::Api { status, .. } if (400..500).contains(status) && *status != 429)
+    }
+}
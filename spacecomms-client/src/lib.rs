@@ -0,0 +1,31 @@
+//! Typed client for the SpaceComms node REST API
+//!
+//! Wraps the endpoints operators and adapters use day to day (CDM ingest,
+//! archive import and listing, objects, peers and the CDM event feed) with typed requests
+//! and responses. CDMs, objects, peers and events use the `spacecomms`
+//! types directly, so records round-trip without conversion.
+//!
+//! ```no_run
+//! # async fn example(cdm: spacecomms::cdm::CdmRecord) -> spacecomms_client::Result<()> {
+//! use spacecomms_client::{CdmFilter, SpaceCommsClient};
+//!
+//! let client = SpaceCommsClient::new("http://localhost:8080");
+//! client.ingest_cdm(&cdm).await?;
+//! let risky = client
+//!     .list_cdms(&CdmFilter {
+//!         min_probability: Some(1e-4),
+//!         ..Default::default()
+//!     })
+//!     .await?;
+//! println!("{} CDMs above 1e-4", risky.total);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod types;
+
+pub use client::*;
+pub use error::{Error, Result};
+pub use types::*;
@@ -0,0 +1,290 @@
+//! Request and response bodies of the node API
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use spacecomms::cdm::{ConjunctionCategory, ConjunctionCdm, RecommendedAction, RiskTrend};
+use spacecomms::config::PeerTransport;
+use spacecomms::node::{Alert, AlertState, WatchedAsset};
+use spacecomms::orbit::Prediction;
+use spacecomms::protocol::{Encoding, TimestampFormat};
+use spacecomms::storage::{BlockedNode, ObjectStateChange, StateDiff};
+
+/// `GET /health`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Health {
+    pub status: String,
+    pub node_id: String,
+    pub uptime_seconds: i64,
+    pub peers: PeerCounts,
+    pub objects_tracked: usize,
+    pub cdms_active: usize,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerCounts {
+    pub connected: usize,
+    pub total: usize,
+}
+
+/// `POST /cdm`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestResponse {
+    pub cdm_id: String,
+    pub status: String,
+    pub propagated_to: Vec<String>,
+}
+
+/// Query for `GET /cdms`; unset fields do not filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CdmFilter {
+    /// Only CDMs involving this object (either side)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+    /// Only CDMs from this originator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originator: Option<String>,
+    /// Only CDMs at or above this collision probability
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_probability: Option<f64>,
+    /// Only CDMs that do (`true`) or do not (`false`) involve a watched asset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watched: Option<bool>,
+}
+
+/// `GET /cdms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdmList {
+    pub cdms: Vec<CdmSummary>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdmSummary {
+    pub cdm_id: String,
+    pub tca: DateTime<Utc>,
+    pub miss_distance_m: f64,
+    pub collision_probability: f64,
+    pub object1_id: String,
+    pub object2_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conjunction_category: Option<ConjunctionCategory>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommended_action: Option<RecommendedAction>,
+    #[serde(default)]
+    pub involves_watched_asset: bool,
+}
+
+/// `DELETE /cdms/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawResponse {
+    pub cdm_id: String,
+    pub status: String,
+    pub reason: String,
+}
+
+/// `POST /cdms/quarantine/{id}/release`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRelease {
+    pub cdm_id: String,
+    pub propagated_to: Vec<String>,
+}
+
+/// `GET /objects`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectList {
+    pub objects: Vec<ObjectSummary>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectSummary {
+    pub object_id: String,
+    pub object_name: String,
+    pub object_type: String,
+    pub last_updated: DateTime<Utc>,
+}
+
+/// `GET /objects/{id}/cdms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectCdmHistory {
+    pub object_id: String,
+    /// Ordered by TCA
+    pub cdms: Vec<ObjectCdmEntry>,
+    pub active: usize,
+    pub withdrawn: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectCdmEntry {
+    /// "active" or "withdrawn"
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawn_at: Option<DateTime<Utc>>,
+    /// The object on the other side of the conjunction
+    pub other_object_id: String,
+    pub other_object_name: String,
+    #[serde(flatten)]
+    pub cdm: ConjunctionCdm,
+}
+
+/// `GET /objects/{id}/state`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectState {
+    pub object_id: String,
+    pub object_name: String,
+    #[serde(flatten)]
+    pub prediction: Prediction,
+}
+
+/// `GET /objects/{id}/history`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectStateHistory {
+    pub object_id: String,
+    /// Oldest version first
+    pub states: Vec<ObjectStateVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectStateVersion {
+    #[serde(flatten)]
+    pub state: ObjectStateChange,
+    /// Difference from the previous version, if the node still keeps it
+    #[serde(default)]
+    pub diff: Option<StateDiff>,
+}
+
+/// Order of `GET /conjunctions/upcoming`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpcomingOrder {
+    /// Soonest TCA first
+    #[default]
+    Tca,
+    /// Highest category, then highest probability first
+    Risk,
+}
+
+/// `GET /conjunctions/upcoming`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpcomingConjunctions {
+    pub generated_at: DateTime<Utc>,
+    /// End of the window
+    pub until: DateTime<Utc>,
+    pub conjunctions: Vec<UpcomingConjunction>,
+    pub total: usize,
+}
+
+/// A conjunction in the window; the node sends the full conjunction
+/// summary, of which these are the fields needed at a glance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpcomingConjunction {
+    pub conjunction_id: String,
+    pub object1_id: String,
+    pub object2_id: String,
+    pub cdm_count: usize,
+    pub providers: Vec<String>,
+    pub tca: DateTime<Utc>,
+    pub seconds_to_tca: i64,
+    pub countdown: String,
+    pub collision_probability: f64,
+    pub miss_distance_m: f64,
+    #[serde(default)]
+    pub conjunction_category: Option<ConjunctionCategory>,
+    pub watched_assets: Vec<WatchedAsset>,
+}
+
+/// `GET /conjunctions/{id}/trend`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConjunctionTrend {
+    pub conjunction_id: String,
+    pub conjunction_key: String,
+    pub trend: RiskTrend,
+}
+
+/// Body of `POST /peers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddPeer {
+    pub peer_id: String,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    #[serde(default)]
+    pub transport: PeerTransport,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_format: Option<TimestampFormat>,
+}
+
+impl AddPeer {
+    /// A peer with the default transport, encoding and timestamps
+    pub fn new(peer_id: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            peer_id: peer_id.into(),
+            address: address.into(),
+            auth_token: None,
+            transport: PeerTransport::default(),
+            encoding: Encoding::default(),
+            timestamp_format: None,
+        }
+    }
+}
+
+/// `POST /peers` and `DELETE /peers/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerChange {
+    pub peer_id: String,
+    pub status: String,
+}
+
+/// `POST /peers/{id}/block`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerBlock {
+    pub blocked: BlockedNode,
+    /// Envelopes queued for the peer that were dropped
+    pub purged: usize,
+}
+
+/// `POST /watchlist` and `DELETE /watchlist/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchlistUpdate {
+    /// Catalog numbers newly added or removed
+    pub changed: Vec<String>,
+    /// Active CDMs whose watched tag changed
+    pub retagged: usize,
+    /// Assets now on the watchlist
+    pub total: usize,
+}
+
+/// Query for `GET /alerts`; unset fields do not filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<AlertState>,
+    /// Only alerts at or above this severity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<ConjunctionCategory>,
+    /// Only alerts assigned to this person or token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cdm_id: Option<String>,
+}
+
+/// `GET /alerts`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertList {
+    pub alerts: Vec<Alert>,
+    pub total: usize,
+    /// Alerts not yet acknowledged or resolved
+    pub open: usize,
+}
+
+/// Error body returned by the node
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ErrorBody {
+    pub error: String,
+    pub message: String,
+}
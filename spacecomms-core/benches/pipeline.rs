@@ -0,0 +1,118 @@
+//! CDM ingest pipeline benchmarks: parse, validate, store and forward
+//!
+//! Run with `cargo bench -p spacecomms --bench pipeline`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use spacecomms::cdm::{generate_demo_cdm, validate_cdm, CdmRecord};
+use spacecomms::protocol::{Encoding, Envelope, MessageType, TimestampFormat};
+use spacecomms::storage::{MemoryStorage, Storage};
+use std::hint::black_box;
+
+/// Distinct CDM IDs cycled through when storing, bounding memory use
+const STORED_IDS: usize = 10_000;
+
+/// Peers a CDM is forwarded to
+const PEERS: [u64; 3] = [1, 8, 32];
+
+fn cdm_json() -> Vec<u8> {
+    serde_json::to_vec(&generate_demo_cdm()).unwrap()
+}
+
+fn parse_and_validate(c: &mut Criterion) {
+    let json = cdm_json();
+    let cdm = generate_demo_cdm();
+    let mut group = c.benchmark_group("cdm");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("parse", |b| {
+        b.iter(|| serde_json::from_slice::<CdmRecord>(black_box(&json)).unwrap())
+    });
+    group.bench_function("validate", |b| b.iter(|| validate_cdm(black_box(&cdm)).unwrap()));
+    group.finish();
+}
+
+fn store(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let storage = MemoryStorage::new();
+    let template = generate_demo_cdm();
+    let mut next = 0;
+    let mut group = c.benchmark_group("storage");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("store_cdm", |b| {
+        b.to_async(&runtime).iter(|| {
+            next += 1;
+            let cdm = CdmRecord {
+                cdm_id: format!("CDM-BENCH-{}", next % STORED_IDS),
+                ..template.clone()
+            };
+            storage.store_cdm(cdm)
+        })
+    });
+    group.finish();
+}
+
+/// Encode one announcement for each peer, as the fan-out does: every copy
+/// carries its own link sequence number
+fn forward(c: &mut Criterion) {
+    let envelope = Envelope::new(
+        "node-bench".to_string(),
+        MessageType::CdmAnnounce,
+        serde_json::to_value(generate_demo_cdm()).unwrap(),
+    );
+    let mut group = c.benchmark_group("forward");
+    for peers in PEERS {
+        group.throughput(Throughput::Elements(peers));
+        for (name, encoding, format) in [
+            ("json", Encoding::Json, TimestampFormat::Auto),
+            ("json_millis", Encoding::Json, TimestampFormat::Millis),
+            ("cbor", Encoding::Cbor, TimestampFormat::Auto),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, peers), &peers, |b, &peers| {
+                b.iter(|| {
+                    // A fresh announcement each time, so its payload is encoded anew
+                    let announced = envelope.forwarded().unwrap();
+                    for sequence in 0..peers {
+                        let mut copy = announced.clone();
+                        copy.sequence = Some(sequence);
+                        black_box(copy.encode_with(encoding, format).unwrap());
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Parse, validate, store and forward to eight peers
+fn ingest(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let storage = MemoryStorage::new();
+    let json = cdm_json();
+    let mut next = 0;
+    let mut group = c.benchmark_group("ingest");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("announce_to_8_peers", |b| {
+        b.to_async(&runtime).iter(|| {
+            next += 1;
+            let json = &json;
+            let storage = &storage;
+            let id = next % STORED_IDS;
+            async move {
+                let mut cdm: CdmRecord = serde_json::from_slice(json).unwrap();
+                validate_cdm(&cdm).unwrap();
+                cdm.cdm_id = format!("CDM-BENCH-{}", id);
+                let payload = serde_json::to_value(&cdm).unwrap();
+                storage.store_cdm(cdm).await.unwrap();
+                let envelope = Envelope::new("node-bench".to_string(), MessageType::CdmAnnounce, payload);
+                for sequence in 0..8 {
+                    let mut copy = envelope.clone();
+                    copy.sequence = Some(sequence);
+                    black_box(copy.encode(Encoding::Json).unwrap());
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_and_validate, store, forward, ingest);
+criterion_main!(benches);
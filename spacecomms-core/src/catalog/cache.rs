@@ -0,0 +1,391 @@
+//! Cached catalog lookups and record enrichment
+
+use crate::catalog::{CatalogEntry, CatalogMatch, CatalogProvider, CatalogSearch};
+use crate::cdm::{CdmObject, CdmRecord, ObjectIdentifier, ObjectRecord};
+use crate::protocol::{ObjectType, RcsSize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// What was searched for, and the value searched for in upper case
+type SearchKey = (CatalogSearch, String);
+
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+/// Catalog provider front-end with a local cache
+///
+/// Hits and misses are cached for the refresh interval. If the provider
+/// fails, the last known entry is served and the next query is deferred
+/// until the interval elapses again, so an unreachable catalog never
+/// blocks or slows down ingest for long. Searches by name or designator
+/// are cached the same way, as the catalog number they found, and every
+/// entry fetched answers searches for its name and designator.
+pub struct CatalogCache {
+    provider: Arc<dyn CatalogProvider>,
+    refresh_interval: Duration,
+    max_entries: usize,
+    entries: RwLock<HashMap<String, Cached<Option<CatalogEntry>>>>,
+    searches: RwLock<HashMap<SearchKey, Cached<Option<String>>>>,
+}
+
+impl CatalogCache {
+    /// Create a cache in front of a provider
+    pub fn new(provider: Arc<dyn CatalogProvider>, refresh_interval: Duration, max_entries: usize) -> Self {
+        Self {
+            provider,
+            refresh_interval,
+            max_entries,
+            entries: RwLock::new(HashMap::new()),
+            searches: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Look up an object, using the cache while the entry is fresh
+    pub async fn lookup(&self, object_id: &str) -> Option<CatalogEntry> {
+        let stale = {
+            let entries = self.entries.read().await;
+            match entries.get(object_id) {
+                Some(cached) if cached.fetched_at.elapsed() < self.refresh_interval => {
+                    return cached.value.clone();
+                }
+                Some(cached) => cached.value.clone(),
+                None => None,
+            }
+        };
+
+        let entry = match self.provider.lookup(object_id).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Catalog lookup for {} failed: {}", object_id, e);
+                stale
+            }
+        };
+        self.remember(object_id, entry.clone()).await;
+        entry
+    }
+
+    /// Catalog entry for a canonical object ID: looked up by catalog
+    /// number, or searched for by designator or name
+    pub async fn resolve(&self, object_id: &str) -> Option<(CatalogEntry, CatalogMatch)> {
+        match ObjectIdentifier::parse(object_id) {
+            ObjectIdentifier::Norad(_) => Some((self.lookup(object_id).await?, CatalogMatch::CatalogNumber)),
+            ObjectIdentifier::Designator(designator) => Some((
+                self.search(CatalogSearch::Designator, &designator).await?,
+                CatalogMatch::InternationalDesignator,
+            )),
+            ObjectIdentifier::Other(name) => Some((self.search(CatalogSearch::Name, &name).await?, CatalogMatch::Name)),
+        }
+    }
+
+    async fn search(&self, by: CatalogSearch, value: &str) -> Option<CatalogEntry> {
+        let key = (by, value.to_ascii_uppercase());
+        let (fresh, stale) = {
+            let searches = self.searches.read().await;
+            match searches.get(&key) {
+                Some(cached) => (cached.fetched_at.elapsed() < self.refresh_interval, cached.value.clone()),
+                None => (false, None),
+            }
+        };
+        if fresh {
+            return match stale {
+                Some(object_id) => self.lookup(&object_id).await,
+                None => None,
+            };
+        }
+
+        match self.provider.search(by, value).await {
+            Ok(Some(entry)) => {
+                self.remember(&entry.object_id.clone(), Some(entry.clone())).await;
+                Some(entry)
+            }
+            Ok(None) => {
+                self.record_search(key, None).await;
+                None
+            }
+            Err(e) => {
+                warn!("Catalog search for {} failed: {}", value, e);
+                self.record_search(key, stale.clone()).await;
+                match stale {
+                    Some(object_id) => self.lookup(&object_id).await,
+                    None => None,
+                }
+            }
+        }
+    }
+
+    /// Cache an entry, and index it for searches by name and designator
+    async fn remember(&self, object_id: &str, entry: Option<CatalogEntry>) {
+        if let Some(entry) = &entry {
+            let id = Some(entry.object_id.clone());
+            self.record_search((CatalogSearch::Name, entry.object_name.to_ascii_uppercase()), id.clone()).await;
+            if let Some(designator) = &entry.international_designator {
+                self.record_search((CatalogSearch::Designator, designator.to_ascii_uppercase()), id).await;
+            }
+        }
+        let mut entries = self.entries.write().await;
+        if !insert_bounded(&mut entries, object_id.to_string(), entry, self.max_entries, self.refresh_interval) {
+            debug!("Catalog cache full, not caching {}", object_id);
+        }
+    }
+
+    async fn record_search(&self, key: SearchKey, object_id: Option<String>) {
+        let mut searches = self.searches.write().await;
+        insert_bounded(&mut searches, key, object_id, self.max_entries, self.refresh_interval);
+    }
+
+    /// Number of cached entries
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    /// Whether the cache is empty
+    pub async fn is_empty(&self) -> bool {
+        self.entries.read().await.is_empty()
+    }
+
+    /// Replace an object ID that is not a catalog number with the one the
+    /// catalog resolves it to
+    async fn resolve_id(&self, object_id: &mut String) {
+        if ObjectIdentifier::parse(object_id).norad_number().is_some() {
+            return;
+        }
+        if let Some((entry, _)) = self.resolve(object_id).await {
+            debug!("Object {} resolved to {}", object_id, entry.object_id);
+            *object_id = entry.object_id;
+        }
+    }
+
+    /// Resolve both CDM objects to catalog numbers where they are not, and
+    /// fill in their missing descriptive fields
+    pub async fn enrich_cdm(&self, cdm: &mut CdmRecord) {
+        self.enrich_cdm_object(&mut cdm.object1).await;
+        self.enrich_cdm_object(&mut cdm.object2).await;
+    }
+
+    async fn enrich_cdm_object(&self, object: &mut CdmObject) {
+        self.resolve_id(&mut object.object_id).await;
+        if !needs_enrichment(&object.object_name, &object.object_type, &object.owner_operator, &object.rcs_size) {
+            return;
+        }
+        if let Some(entry) = self.lookup(&object.object_id).await {
+            apply(
+                entry,
+                &mut object.object_name,
+                &mut object.object_type,
+                &mut object.owner_operator,
+                &mut object.rcs_size,
+            );
+        }
+    }
+
+    /// Resolve an object record to its catalog number where it is not one,
+    /// and fill in its missing descriptive fields
+    pub async fn enrich_object(&self, object: &mut ObjectRecord) {
+        self.resolve_id(&mut object.object_id).await;
+        if !needs_enrichment(&object.object_name, &object.object_type, &object.owner_operator, &object.rcs_size) {
+            return;
+        }
+        if let Some(entry) = self.lookup(&object.object_id).await {
+            apply(
+                entry,
+                &mut object.object_name,
+                &mut object.object_type,
+                &mut object.owner_operator,
+                &mut object.rcs_size,
+            );
+        }
+    }
+}
+
+/// Insert into a map holding at most `max_entries`, dropping expired
+/// entries to make room; false if there was none
+fn insert_bounded<K: Eq + Hash, T>(
+    map: &mut HashMap<K, Cached<T>>,
+    key: K,
+    value: T,
+    max_entries: usize,
+    refresh_interval: Duration,
+) -> bool {
+    if map.len() >= max_entries && !map.contains_key(&key) {
+        map.retain(|_, cached| cached.fetched_at.elapsed() < refresh_interval);
+    }
+    if map.len() >= max_entries && !map.contains_key(&key) {
+        return false;
+    }
+    map.insert(
+        key,
+        Cached {
+            value,
+            fetched_at: Instant::now(),
+        },
+    );
+    true
+}
+
+fn unknown_name(name: &str) -> bool {
+    let name = name.trim();
+    name.is_empty() || name.eq_ignore_ascii_case("unknown")
+}
+
+fn needs_enrichment(
+    name: &str,
+    object_type: &ObjectType,
+    owner: &Option<String>,
+    rcs_size: &Option<RcsSize>,
+) -> bool {
+    unknown_name(name) || *object_type == ObjectType::Unknown || owner.is_none() || rcs_size.is_none()
+}
+
+/// Copy catalog fields over values the announcement left unknown
+fn apply(
+    entry: CatalogEntry,
+    name: &mut String,
+    object_type: &mut ObjectType,
+    owner: &mut Option<String>,
+    rcs_size: &mut Option<RcsSize>,
+) {
+    if unknown_name(name) && !unknown_name(&entry.object_name) {
+        *name = entry.object_name;
+    }
+    if *object_type == ObjectType::Unknown {
+        *object_type = entry.object_type;
+    }
+    if owner.is_none() {
+        *owner = entry.owner;
+    }
+    if rcs_size.is_none() {
+        *rcs_size = entry.rcs_size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::{Error, Result};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct MockProvider {
+        calls: AtomicUsize,
+        searches: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    fn fengyun_debris(object_id: &str) -> CatalogEntry {
+        CatalogEntry {
+            object_id: object_id.to_string(),
+            object_name: "FENGYUN 1C DEB".to_string(),
+            object_type: ObjectType::Debris,
+            owner: Some("PRC".to_string()),
+            rcs_size: Some(RcsSize::Small),
+            international_designator: Some("1999-025AB".to_string()),
+        }
+    }
+
+    #[async_trait]
+    impl CatalogProvider for MockProvider {
+        async fn lookup(&self, object_id: &str) -> Result<Option<CatalogEntry>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(Error::Internal("catalog unavailable".into()));
+            }
+            Ok((object_id == "NORAD-99999").then(|| fengyun_debris(object_id)))
+        }
+
+        async fn search(&self, by: CatalogSearch, value: &str) -> Result<Option<CatalogEntry>> {
+            self.searches.fetch_add(1, Ordering::SeqCst);
+            let found = match by {
+                CatalogSearch::Name => value == "FENGYUN 1C DEB",
+                CatalogSearch::Designator => value == "1999-025AB",
+            };
+            Ok(found.then(|| fengyun_debris("NORAD-99999")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enrich_unknown_fields_only() {
+        let provider = Arc::new(MockProvider::default());
+        let cache = CatalogCache::new(provider.clone(), Duration::from_secs(60), 10);
+
+        let mut cdm = generate_demo_cdm();
+        cdm.object1.owner_operator = Some("Operator A".to_string());
+        cdm.object2.object_id = "NORAD-99999".to_string();
+        cdm.object2.object_name = "Unknown".to_string();
+        cdm.object2.object_type = ObjectType::Unknown;
+        cdm.object2.owner_operator = None;
+        let object1 = cdm.object1.clone();
+
+        cache.enrich_cdm(&mut cdm).await;
+        assert_eq!(cdm.object2.object_name, "FENGYUN 1C DEB");
+        assert_eq!(cdm.object2.object_type, ObjectType::Debris);
+        assert_eq!(cdm.object2.owner_operator.as_deref(), Some("PRC"));
+        assert_eq!(cdm.object2.rcs_size, Some(RcsSize::Small));
+        // Values supplied by the announcement are never overwritten
+        assert_eq!(cdm.object1.object_name, object1.object_name);
+        assert_eq!(cdm.object1.owner_operator, object1.owner_operator);
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_refresh() {
+        let provider = Arc::new(MockProvider::default());
+        let cache = CatalogCache::new(provider.clone(), Duration::from_secs(60), 10);
+
+        assert!(cache.lookup("NORAD-99999").await.is_some());
+        assert!(cache.lookup("NORAD-99999").await.is_some());
+        // Misses are cached too
+        assert!(cache.lookup("NORAD-1").await.is_none());
+        assert!(cache.lookup("NORAD-1").await.is_none());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+
+        // Expired entries are re-fetched; stale data is served on failure
+        let cache = CatalogCache::new(provider.clone(), Duration::ZERO, 10);
+        assert!(cache.lookup("NORAD-99999").await.is_some());
+        provider.failing.store(true, Ordering::SeqCst);
+        assert!(cache.lookup("NORAD-99999").await.is_some());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cache_bound() {
+        let provider = Arc::new(MockProvider::default());
+        let cache = CatalogCache::new(provider, Duration::from_secs(60), 2);
+        for id in ["NORAD-1", "NORAD-2", "NORAD-3"] {
+            cache.lookup(id).await;
+        }
+        assert_eq!(cache.len().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_by_designator_and_name() {
+        let provider = Arc::new(MockProvider::default());
+        let cache = CatalogCache::new(provider.clone(), Duration::from_secs(60), 10);
+
+        let (entry, matched_by) = cache.resolve("1999-025AB").await.unwrap();
+        assert_eq!((entry.object_id.as_str(), matched_by), ("NORAD-99999", CatalogMatch::InternationalDesignator));
+        // The entry found answers searches for its name without a query
+        let (_, matched_by) = cache.resolve("Fengyun 1C Deb").await.unwrap();
+        assert_eq!(matched_by, CatalogMatch::Name);
+        assert!(cache.resolve("UNKNOWN DEBRIS").await.is_none());
+        assert!(cache.resolve("UNKNOWN DEBRIS").await.is_none());
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 0);
+
+        // Ingest replaces the designator a provider used with the catalog number
+        let mut cdm = generate_demo_cdm();
+        cdm.object2.object_id = "1999-025AB".to_string();
+        cdm.object2.object_name = "Unknown".to_string();
+        let object1 = cdm.object1.object_id.clone();
+        cache.enrich_cdm(&mut cdm).await;
+        assert_eq!(cdm.object2.object_id, "NORAD-99999");
+        assert_eq!(cdm.object2.object_name, "FENGYUN 1C DEB");
+        assert_eq!(cdm.object1.object_id, object1);
+    }
+}
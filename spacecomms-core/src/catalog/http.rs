@@ -0,0 +1,224 @@
+//! HTTP catalog provider
+
+use crate::catalog::{CatalogEntry, CatalogProvider, CatalogSearch};
+use crate::cdm::{canonical_object_id, ObjectIdentifier};
+use crate::protocol::{ObjectType, RcsSize};
+use crate::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Catalog provider querying a Space-Track style HTTP API
+///
+/// `query_path` is appended to the base URL with `{id}` replaced by the
+/// numeric catalog number, e.g. `/catalog?norad_id={id}` for the bundled
+/// mock or `/basicspacedata/query/class/satcat/NORAD_CAT_ID/{id}/format/json`
+/// for Space-Track. The response must be a JSON array of catalog records.
+/// Searches by name or designator use their own paths, with `{query}`
+/// replaced by the percent-encoded value, and take the catalog number from
+/// the record found; without a path the catalog is not searched that way.
+pub struct HttpCatalogProvider {
+    client: reqwest::Client,
+    base_url: String,
+    query_path: String,
+    name_query_path: Option<String>,
+    designator_query_path: Option<String>,
+    auth_token: Option<String>,
+}
+
+impl HttpCatalogProvider {
+    /// Create a provider for a catalog service
+    pub fn new(base_url: &str, query_path: &str, timeout: Duration) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            query_path: query_path.to_string(),
+            name_query_path: None,
+            designator_query_path: None,
+            auth_token: None,
+        }
+    }
+
+    /// Send a bearer token with every query
+    pub fn with_auth_token(mut self, token: String) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    /// Search by name and by international designator through these paths
+    pub fn with_search_paths(mut self, name_query_path: Option<String>, designator_query_path: Option<String>) -> Self {
+        self.name_query_path = name_query_path;
+        self.designator_query_path = designator_query_path;
+        self
+    }
+
+    async fn query(&self, path: &str) -> Result<Vec<CatalogRecord>> {
+        let mut request = self.client.get(format!("{}{}", self.base_url, path));
+        if let Some(token) = &self.auth_token {
+            request = request.bearer_auth(token);
+        }
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+}
+
+/// Catalog record in either the mock's or Space-Track's field naming
+#[derive(Debug, Deserialize)]
+struct CatalogRecord {
+    #[serde(alias = "OBJECT_NAME")]
+    object_name: String,
+    #[serde(default, alias = "OBJECT_TYPE")]
+    object_type: Option<String>,
+    #[serde(default, alias = "COUNTRY")]
+    owner: Option<String>,
+    #[serde(default, alias = "RCS_SIZE")]
+    rcs_size: Option<String>,
+    #[serde(default, alias = "NORAD_CAT_ID")]
+    norad_id: Option<String>,
+    #[serde(default, alias = "INTLDES", alias = "intl_designator")]
+    international_designator: Option<String>,
+}
+
+impl CatalogRecord {
+    fn into_entry(self, object_id: &str) -> CatalogEntry {
+        CatalogEntry {
+            object_id: object_id.to_string(),
+            object_name: self.object_name,
+            object_type: self.object_type.as_deref().map(parse_object_type).unwrap_or(ObjectType::Unknown),
+            owner: self.owner.filter(|o| !o.is_empty()),
+            rcs_size: self.rcs_size.as_deref().and_then(parse_rcs_size),
+            international_designator: self
+                .international_designator
+                .filter(|d| !d.trim().is_empty())
+                .map(|d| canonical_object_id(&d, &BTreeMap::new())),
+        }
+    }
+
+    /// Entry for a search result, under the catalog number it carries
+    fn into_found_entry(self) -> Option<CatalogEntry> {
+        let object_id = canonical_object_id(self.norad_id.as_deref()?, &BTreeMap::new());
+        ObjectIdentifier::parse(&object_id).norad_number()?;
+        Some(self.into_entry(&object_id))
+    }
+}
+
+/// Catalog number for an object ID ("NORAD-12345" -> "12345")
+fn catalog_number(object_id: &str) -> &str {
+    object_id.strip_prefix("NORAD-").unwrap_or(object_id)
+}
+
+/// Percent-encode a search value for a URL path or query
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn parse_object_type(value: &str) -> ObjectType {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "PAYLOAD" => ObjectType::Payload,
+        "DEBRIS" => ObjectType::Debris,
+        "ROCKET BODY" | "ROCKET_BODY" | "R/B" => ObjectType::RocketBody,
+        _ => ObjectType::Unknown,
+    }
+}
+
+fn parse_rcs_size(value: &str) -> Option<RcsSize> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "SMALL" => Some(RcsSize::Small),
+        "MEDIUM" => Some(RcsSize::Medium),
+        "LARGE" => Some(RcsSize::Large),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl CatalogProvider for HttpCatalogProvider {
+    async fn lookup(&self, object_id: &str) -> Result<Option<CatalogEntry>> {
+        let records = self.query(&self.query_path.replace("{id}", catalog_number(object_id))).await?;
+        Ok(records.into_iter().next().map(|r| r.into_entry(object_id)))
+    }
+
+    async fn search(&self, by: CatalogSearch, value: &str) -> Result<Option<CatalogEntry>> {
+        let path = match by {
+            CatalogSearch::Name => &self.name_query_path,
+            CatalogSearch::Designator => &self.designator_query_path,
+        };
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let records = self.query(&path.replace("{query}", &encode_query(value))).await?;
+        Ok(records.into_iter().find_map(CatalogRecord::into_found_entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_number() {
+        assert_eq!(catalog_number("NORAD-12345"), "12345");
+        assert_eq!(catalog_number("25544"), "25544");
+        assert_eq!(encode_query("ISS (ZARYA)"), "ISS%20%28ZARYA%29");
+        assert_eq!(encode_query("1998-067A"), "1998-067A");
+    }
+
+    #[test]
+    fn test_parse_mock_record() {
+        let json = r#"[{
+            "norad_id": "99999",
+            "object_name": "FENGYUN 1C DEB",
+            "object_type": "DEBRIS",
+            "owner": "PRC",
+            "launch_date": "1999-05-10",
+            "rcs_size": "SMALL"
+        }]"#;
+        let records: Vec<CatalogRecord> = serde_json::from_str(json).unwrap();
+        let entry = records.into_iter().next().unwrap().into_entry("NORAD-99999");
+        assert_eq!(entry.object_id, "NORAD-99999");
+        assert_eq!(entry.object_name, "FENGYUN 1C DEB");
+        assert_eq!(entry.object_type, ObjectType::Debris);
+        assert_eq!(entry.owner.as_deref(), Some("PRC"));
+        assert_eq!(entry.rcs_size, Some(RcsSize::Small));
+    }
+
+    #[test]
+    fn test_parse_space_track_record() {
+        let json = r#"[{
+            "NORAD_CAT_ID": "25544",
+            "OBJECT_NAME": "ISS (ZARYA)",
+            "OBJECT_TYPE": "PAYLOAD",
+            "COUNTRY": "ISS",
+            "RCS_SIZE": "LARGE"
+        }]"#;
+        let records: Vec<CatalogRecord> = serde_json::from_str(json).unwrap();
+        let entry = records.into_iter().next().unwrap().into_entry("25544");
+        assert_eq!(entry.object_type, ObjectType::Payload);
+        assert_eq!(entry.international_designator, None);
+        assert_eq!(entry.rcs_size, Some(RcsSize::Large));
+        assert_eq!(parse_object_type("ROCKET BODY"), ObjectType::RocketBody);
+        assert_eq!(parse_object_type("TBA"), ObjectType::Unknown);
+    }
+
+    #[test]
+    fn test_parse_search_result() {
+        let json = r#"[
+            {"OBJECT_NAME": "UNKNOWN", "NORAD_CAT_ID": ""},
+            {"NORAD_CAT_ID": "025544", "OBJECT_NAME": "ISS (ZARYA)", "INTLDES": "98067A"}
+        ]"#;
+        let records: Vec<CatalogRecord> = serde_json::from_str(json).unwrap();
+        let entry = records.into_iter().find_map(CatalogRecord::into_found_entry).unwrap();
+        assert_eq!(entry.object_id, "NORAD-25544");
+        assert_eq!(entry.international_designator.as_deref(), Some("1998-067A"));
+    }
+}
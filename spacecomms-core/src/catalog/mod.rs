@@ -0,0 +1,149 @@
+//! Object catalog enrichment
+//!
+//! Peers and STM providers often announce objects with placeholder names
+//! ("Unknown") and no owner. A [`CatalogProvider`] looks objects up in an
+//! external catalog (Space-Track or a compatible service) so missing
+//! descriptive fields can be filled in at ingest time. Objects announced
+//! under a name or international designator are resolved to their catalog
+//! number the same way, so providers naming an object differently agree on
+//! its ID.
+
+mod cache;
+mod http;
+
+pub use cache::*;
+pub use http::*;
+
+use crate::cdm::{canonical_object_id, ObjectIdentifier};
+use crate::config::{Config, IdentifiersConfig};
+use crate::protocol::{ObjectType, RcsSize};
+use crate::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Descriptive catalog data for one object
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    /// Object identifier as used in CDMs (e.g. "NORAD-12345")
+    pub object_id: String,
+    /// Catalog name
+    pub object_name: String,
+    /// Object type
+    pub object_type: ObjectType,
+    /// Owner or country of registry
+    pub owner: Option<String>,
+    /// Radar cross-section size class
+    pub rcs_size: Option<RcsSize>,
+    /// International designator, as `YYYY-NNNP`
+    pub international_designator: Option<String>,
+}
+
+/// What a catalog is searched by, other than catalog number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CatalogSearch {
+    Name,
+    Designator,
+}
+
+/// Source of catalog data
+#[async_trait]
+pub trait CatalogProvider: Send + Sync {
+    /// Look up an object, returning `None` if the catalog does not know it
+    async fn lookup(&self, object_id: &str) -> Result<Option<CatalogEntry>>;
+
+    /// Find an object by name or international designator, returning
+    /// `None` if the catalog does not know it or cannot be searched
+    async fn search(&self, by: CatalogSearch, value: &str) -> Result<Option<CatalogEntry>> {
+        let _ = (by, value);
+        Ok(None)
+    }
+}
+
+/// How a query was matched to a catalog entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogMatch {
+    CatalogNumber,
+    InternationalDesignator,
+    Name,
+    /// An entry of `identifiers.aliases`
+    Alias,
+}
+
+/// A catalog entry found for a name, catalog number or designator
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResolvedObject {
+    pub query: String,
+    /// Canonical object ID, as CDMs are stored under
+    pub object_id: String,
+    pub matched_by: CatalogMatch,
+    pub object_name: String,
+    pub object_type: ObjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcs_size: Option<RcsSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub international_designator: Option<String>,
+    /// Other IDs the object is known by: its bare catalog number, its
+    /// designator, its catalog name and its configured aliases
+    pub aliases: Vec<String>,
+}
+
+/// Resolve a name, catalog number or designator, after `identifiers`
+/// aliases, to a catalog entry
+pub async fn resolve_object(catalog: &CatalogCache, identifiers: &IdentifiersConfig, query: &str) -> Option<ResolvedObject> {
+    let canonical = identifiers.canonical(query);
+    let (entry, mut matched_by) = catalog.resolve(&canonical).await?;
+    if canonical != canonical_object_id(query, &BTreeMap::new()) {
+        matched_by = CatalogMatch::Alias;
+    }
+
+    let configured = identifiers
+        .aliases
+        .iter()
+        .filter(|(_, target)| identifiers.canonical(target) == entry.object_id)
+        .map(|(alias, _)| alias.clone());
+    let mut aliases: Vec<String> = Vec::new();
+    let number = ObjectIdentifier::parse(&entry.object_id).norad_number().map(str::to_string);
+    let known = number.into_iter().chain(entry.international_designator.clone()).chain([entry.object_name.clone()]);
+    for alias in known.chain(configured) {
+        if !alias.trim().is_empty() && !aliases.contains(&alias) {
+            aliases.push(alias);
+        }
+    }
+    Some(ResolvedObject {
+        query: query.to_string(),
+        object_id: entry.object_id,
+        matched_by,
+        object_name: entry.object_name,
+        object_type: entry.object_type,
+        owner: entry.owner,
+        rcs_size: entry.rcs_size,
+        international_designator: entry.international_designator,
+        aliases,
+    })
+}
+
+/// Create the catalog cache from configuration, if a catalog is configured
+pub fn create_catalog(config: &Config) -> Option<Arc<CatalogCache>> {
+    let catalog = config.catalog.as_ref()?;
+    let mut provider = HttpCatalogProvider::new(
+        &catalog.url,
+        &catalog.query_path,
+        Duration::from_secs(catalog.timeout_seconds),
+    );
+    if let Some(token) = &catalog.auth_token {
+        provider = provider.with_auth_token(token.clone());
+    }
+    provider = provider.with_search_paths(catalog.name_query_path.clone(), catalog.designator_query_path.clone());
+    Some(Arc::new(CatalogCache::new(
+        Arc::new(provider),
+        Duration::from_secs(catalog.refresh_interval_seconds),
+        catalog.max_entries,
+    )))
+}
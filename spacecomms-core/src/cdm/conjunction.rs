@@ -0,0 +1,310 @@
+//! Conjunction identity and provider agreement
+//!
+//! Providers screening the same pair of objects issue their own CDMs with
+//! their own IDs. A conjunction key (the object pair, in either order, plus
+//! the TCA rounded down to a bucket) lets those CDMs be grouped as one event.
+//! [`conjunction_id`] hashes the key into a short stable ID that any party
+//! using the same bucket width derives alike.
+
+use crate::cdm::{CdmRecord, ConjunctionCategory, FusedAssessment, RecommendedAction};
+use crate::config::{FusionConfig, SeverityConfig};
+use chrono::{DateTime, TimeZone, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Identity of a conjunction across providers
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConjunctionKey {
+    /// Lower of the two object IDs
+    pub object1_id: String,
+    /// Higher of the two object IDs
+    pub object2_id: String,
+    /// Start of the TCA bucket
+    pub tca_bucket: DateTime<Utc>,
+}
+
+impl ConjunctionKey {
+    /// Key for two objects in either order, with the TCA grouped into
+    /// buckets of `bucket_seconds`
+    pub fn new(object_a: &str, object_b: &str, tca: DateTime<Utc>, bucket_seconds: u64) -> Self {
+        let (object1_id, object2_id) = if object_a <= object_b { (object_a, object_b) } else { (object_b, object_a) };
+        let bucket = bucket_seconds.max(1) as i64;
+        let start = tca.timestamp().div_euclid(bucket) * bucket;
+        Self {
+            object1_id: object1_id.to_string(),
+            object2_id: object2_id.to_string(),
+            tca_bucket: Utc.timestamp_opt(start, 0).single().unwrap_or(tca),
+        }
+    }
+
+    /// Key for a CDM, with TCAs grouped into buckets of `bucket_seconds`
+    pub fn for_cdm(cdm: &CdmRecord, bucket_seconds: u64) -> Self {
+        Self::new(&cdm.object1.object_id, &cdm.object2.object_id, cdm.tca, bucket_seconds)
+    }
+
+    /// Stable ID of the key: `cj-` and the first 96 bits of the SHA-256 of
+    /// both object IDs and the bucket start in Unix seconds, each written as
+    /// its length in bytes, a colon, the field and a newline, so no two keys
+    /// hash the same input
+    pub fn id(&self) -> String {
+        let timestamp = self.tca_bucket.timestamp().to_string();
+        let input: String = [self.object1_id.as_str(), self.object2_id.as_str(), timestamp.as_str()]
+            .iter()
+            .map(|field| format!("{}:{}\n", field.len(), field))
+            .collect();
+        let hash = digest(&SHA256, input.as_bytes());
+        let hex: String = hash.as_ref()[..12].iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("cj-{}", hex)
+    }
+}
+
+/// Stable ID of the conjunction between two objects (in either order) at
+/// `tca`, as the `/conjunctions` API reports it for a node whose
+/// `storage.conjunction_bucket_seconds` is `bucket_seconds`
+pub fn conjunction_id(object_a: &str, object_b: &str, tca: DateTime<Utc>, bucket_seconds: u64) -> String {
+    ConjunctionKey::new(object_a, object_b, tca, bucket_seconds).id()
+}
+
+impl std::fmt::Display for ConjunctionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}@{}",
+            self.object1_id,
+            self.object2_id,
+            self.tca_bucket.format("%Y-%m-%dT%H:%M:%SZ")
+        )
+    }
+}
+
+impl Serialize for ConjunctionKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// One CDM within a conjunction
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConjunctionCdm {
+    pub cdm_id: String,
+    pub originator: String,
+    pub creation_date: DateTime<Utc>,
+    pub tca: DateTime<Utc>,
+    pub miss_distance_m: f64,
+    pub collision_probability: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_quality_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conjunction_category: Option<ConjunctionCategory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_action: Option<RecommendedAction>,
+}
+
+impl From<&CdmRecord> for ConjunctionCdm {
+    fn from(cdm: &CdmRecord) -> Self {
+        Self {
+            cdm_id: cdm.cdm_id.clone(),
+            originator: cdm.originator.clone(),
+            creation_date: cdm.creation_date,
+            tca: cdm.tca,
+            miss_distance_m: cdm.miss_distance_m,
+            collision_probability: cdm.collision_probability,
+            data_quality_score: cdm.data_quality_score,
+            conjunction_category: cdm.conjunction_category.clone(),
+            recommended_action: cdm.recommended_action.clone(),
+        }
+    }
+}
+
+/// How far providers' latest CDMs for a conjunction disagree
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct ProviderDisagreement {
+    /// Providers compared (one latest CDM each)
+    pub providers: usize,
+    pub min_probability: f64,
+    pub max_probability: f64,
+    /// Orders of magnitude between the highest and lowest probability
+    pub probability_spread_log10: f64,
+    pub min_miss_distance_m: f64,
+    pub max_miss_distance_m: f64,
+    pub miss_distance_spread_m: f64,
+    /// Seconds between the earliest and latest TCA
+    pub tca_spread_seconds: f64,
+    /// Providers assigned different categories
+    pub category_conflict: bool,
+}
+
+impl ProviderDisagreement {
+    fn measure(latest: &[&CdmRecord]) -> Self {
+        let pcs = latest.iter().map(|c| c.collision_probability);
+        let misses = latest.iter().map(|c| c.miss_distance_m);
+        let (min_probability, max_probability) = bounds(pcs);
+        let (min_miss_distance_m, max_miss_distance_m) = bounds(misses);
+        let tcas = latest.iter().map(|c| c.tca);
+        let tca_spread = match (tcas.clone().min(), tcas.max()) {
+            (Some(first), Some(last)) => (last - first).num_milliseconds() as f64 / 1000.0,
+            _ => 0.0,
+        };
+        // A zero probability has no magnitude; floor it for the ratio
+        let floor = f64::MIN_POSITIVE;
+        let categories: Vec<_> = latest.iter().filter_map(|c| c.conjunction_category.as_ref()).collect();
+        Self {
+            providers: latest.len(),
+            min_probability,
+            max_probability,
+            probability_spread_log10: (max_probability.max(floor) / min_probability.max(floor)).log10(),
+            min_miss_distance_m,
+            max_miss_distance_m,
+            miss_distance_spread_m: max_miss_distance_m - min_miss_distance_m,
+            tca_spread_seconds: tca_spread,
+            category_conflict: categories.windows(2).any(|pair| pair[0] != pair[1]),
+        }
+    }
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)))
+}
+
+/// A conjunction and the CDMs providers issued for it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConjunctionSummary {
+    /// See [`conjunction_id`]
+    pub conjunction_id: String,
+    /// `object1/object2@bucket`
+    #[schema(value_type = String)]
+    pub conjunction_key: ConjunctionKey,
+    pub object1_id: String,
+    pub object2_id: String,
+    pub tca_bucket: DateTime<Utc>,
+    pub cdm_count: usize,
+    /// Originators with at least one CDM, sorted
+    pub providers: Vec<String>,
+    /// Most recently created CDM
+    pub latest: ConjunctionCdm,
+    /// Highest data quality among each provider's latest CDM, newest on ties
+    pub best: ConjunctionCdm,
+    pub disagreement: ProviderDisagreement,
+    /// Trust-weighted figure across providers (absent if none has weight)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fused: Option<FusedAssessment>,
+}
+
+impl ConjunctionSummary {
+    /// Summarize a group of CDMs sharing a key; `None` if the group is empty
+    pub fn new(
+        key: ConjunctionKey,
+        cdms: &[CdmRecord],
+        fusion: &FusionConfig,
+        severity: &SeverityConfig,
+    ) -> Option<Self> {
+        let newest = |a: &&CdmRecord, b: &&CdmRecord| {
+            a.creation_date.cmp(&b.creation_date).then_with(|| a.cdm_id.cmp(&b.cdm_id))
+        };
+        let latest = cdms.iter().max_by(newest)?;
+
+        // Older CDMs from a provider are superseded by its newer ones
+        let mut per_provider: BTreeMap<&str, &CdmRecord> = BTreeMap::new();
+        for cdm in cdms {
+            let entry = per_provider.entry(&cdm.originator).or_insert(cdm);
+            if newest(&cdm, entry).is_gt() {
+                *entry = cdm;
+            }
+        }
+        let current: Vec<&CdmRecord> = per_provider.values().copied().collect();
+        let best = current
+            .iter()
+            .max_by(|a, b| {
+                let quality = |c: &CdmRecord| c.data_quality_score.unwrap_or(0.0);
+                quality(a).total_cmp(&quality(b)).then_with(|| newest(a, b))
+            })
+            .copied()
+            .unwrap_or(latest);
+
+        Some(Self {
+            object1_id: key.object1_id.clone(),
+            object2_id: key.object2_id.clone(),
+            tca_bucket: key.tca_bucket,
+            conjunction_id: key.id(),
+            conjunction_key: key,
+            cdm_count: cdms.len(),
+            providers: per_provider.keys().map(|p| p.to_string()).collect(),
+            latest: latest.into(),
+            best: best.into(),
+            disagreement: ProviderDisagreement::measure(&current),
+            fused: FusedAssessment::fuse(&current, fusion, severity),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use chrono::Duration;
+
+    fn cdm(id: &str, originator: &str, pc: f64, age_minutes: i64) -> CdmRecord {
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = id.into();
+        cdm.originator = originator.into();
+        cdm.collision_probability = pc;
+        cdm.tca = Utc.with_ymd_and_hms(2024, 1, 17, 8, 30, 0).unwrap();
+        cdm.creation_date = cdm.tca - Duration::hours(24) - Duration::minutes(age_minutes);
+        cdm
+    }
+
+    #[test]
+    fn test_key_ignores_object_order_and_bucket_offset() {
+        let a = cdm("A", "P1", 1e-4, 0);
+        let mut b = a.clone();
+        std::mem::swap(&mut b.object1, &mut b.object2);
+        b.tca += Duration::seconds(20);
+        assert_eq!(ConjunctionKey::for_cdm(&a, 60), ConjunctionKey::for_cdm(&b, 60));
+        b.tca += Duration::seconds(60);
+        assert_ne!(ConjunctionKey::for_cdm(&a, 60), ConjunctionKey::for_cdm(&b, 60));
+    }
+
+    #[test]
+    fn test_conjunction_id() {
+        let tca = Utc.with_ymd_and_hms(2024, 1, 17, 8, 31, 4).unwrap();
+        let id = conjunction_id("NORAD-12345", "NORAD-99999", tca, 300);
+        // Fixed so other implementations can check theirs against it
+        assert_eq!(id, "cj-ba847f3f2c752512d5526fe3");
+        assert_eq!(conjunction_id("NORAD-99999", "NORAD-12345", tca - Duration::seconds(60), 300), id);
+        assert_ne!(conjunction_id("NORAD-12345", "NORAD-99999", tca, 60), id);
+        let mut cdm = cdm("A", "P1", 1e-4, 0);
+        cdm.object1.object_id = "NORAD-99999".into();
+        cdm.object2.object_id = "NORAD-12345".into();
+        cdm.tca = tca;
+        assert_eq!(ConjunctionKey::for_cdm(&cdm, 300).id(), id);
+        // A separator inside an ID does not move it into the other field
+        assert_ne!(conjunction_id("A\nB", "C", tca, 300), conjunction_id("A", "B\nC", tca, 300));
+    }
+
+    #[test]
+    fn test_summary_compares_latest_per_provider() {
+        let mut p1_new = cdm("P1-2", "P1", 1e-4, 0);
+        p1_new.data_quality_score = Some(0.5);
+        let p1_old = cdm("P1-1", "P1", 1e-2, 60);
+        let mut p2 = cdm("P2-1", "P2", 1e-6, 30);
+        p2.data_quality_score = Some(0.9);
+        p2.tca += Duration::seconds(4);
+        let cdms = [p1_old, p1_new, p2];
+
+        let (fusion, severity) = (FusionConfig::default(), SeverityConfig::default());
+        let summary = ConjunctionSummary::new(ConjunctionKey::for_cdm(&cdms[0], 60), &cdms, &fusion, &severity).unwrap();
+        assert_eq!(summary.cdm_count, 3);
+        assert_eq!(summary.providers, ["P1", "P2"]);
+        assert_eq!(summary.latest.cdm_id, "P1-2");
+        assert_eq!(summary.best.cdm_id, "P2-1");
+        // P1's superseded 1e-2 is not counted
+        assert_eq!(summary.disagreement.providers, 2);
+        assert_eq!(summary.disagreement.max_probability, 1e-4);
+        assert!((summary.disagreement.probability_spread_log10 - 2.0).abs() < 1e-9);
+        assert_eq!(summary.disagreement.tca_spread_seconds, 4.0);
+        assert_eq!(summary.fused.unwrap().contributors.len(), 2);
+        assert!(ConjunctionSummary::new(summary.conjunction_key, &[], &fusion, &severity).is_none());
+    }
+}
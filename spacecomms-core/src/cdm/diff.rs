@@ -0,0 +1,220 @@
+//! Field-level CDM comparison
+//!
+//! When a conjunction is updated, analysts compare the new CDM with the one
+//! before it: how Pc and miss distance moved, whether TCA shifted, and how
+//! far each object's state vector and covariance changed. [`CdmDiff`]
+//! gathers those, along with every other field that differs, for
+//! `spacecomms cdm diff`.
+//!
+//! State vectors are given at each CDM's TCA, so a TCA shift shows up in
+//! the state vector deltas as well.
+
+use crate::cdm::{CdmObject, CdmRecord};
+use crate::orbit::norm;
+use crate::protocol::CovarianceRtn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A number in both CDMs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NumberChange {
+    pub from: f64,
+    pub to: f64,
+    /// `to - from`
+    pub delta: f64,
+}
+
+impl NumberChange {
+    fn new(from: f64, to: f64) -> Self {
+        Self { from, to, delta: to - from }
+    }
+}
+
+/// A field that differs between the CDMs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Dotted path, such as `object1.state_vector.x_km`
+    pub field: String,
+    /// Value in the first CDM; null when it lacks the field
+    pub from: Value,
+    /// Value in the second CDM; null when it lacks the field
+    pub to: Value,
+    /// `to - from`, for numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+}
+
+/// How one object's state and covariance changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDiff {
+    /// Object ID in the second CDM
+    pub object_id: String,
+    /// Change in position along x, y and z, in km
+    pub position_delta_km: [f64; 3],
+    /// Length of the position change, in km
+    pub position_change_km: f64,
+    /// Change in velocity along x, y and z, in m/s
+    pub velocity_delta_m_s: [f64; 3],
+    /// Length of the velocity change, in m/s
+    pub velocity_change_m_s: f64,
+    /// The state vectors are in different frames, so the deltas mix them
+    pub frame_changed: bool,
+    /// Second CDM's 1-sigma over the first's along R, T and N; None unless
+    /// both CDMs have a covariance for the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sigma_ratio_rtn: Option<[f64; 3]>,
+}
+
+/// How a later CDM differs from an earlier one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CdmDiff {
+    pub from_cdm_id: String,
+    pub to_cdm_id: String,
+    /// The CDMs are not about the same pair of objects
+    pub objects_differ: bool,
+    pub collision_probability: NumberChange,
+    /// Second Pc over the first; None when the first is zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_ratio: Option<f64>,
+    pub miss_distance_m: NumberChange,
+    /// Seconds TCA moved by, later positive
+    pub tca_shift_seconds: f64,
+    pub object1: ObjectDiff,
+    pub object2: ObjectDiff,
+    /// Every field that differs, in field order
+    pub changes: Vec<FieldChange>,
+}
+
+impl CdmDiff {
+    /// Compare `to` with `from`
+    pub fn between(from: &CdmRecord, to: &CdmRecord) -> Self {
+        let mut changes = Vec::new();
+        // Serializing a CDM cannot fail: every field is plain data
+        let from_value = serde_json::to_value(from).unwrap_or_default();
+        let to_value = serde_json::to_value(to).unwrap_or_default();
+        collect_changes("", &from_value, &to_value, &mut changes);
+
+        let pc = NumberChange::new(from.collision_probability, to.collision_probability);
+        Self {
+            from_cdm_id: from.cdm_id.clone(),
+            to_cdm_id: to.cdm_id.clone(),
+            objects_differ: from.object1.object_id != to.object1.object_id
+                || from.object2.object_id != to.object2.object_id,
+            collision_probability: pc,
+            pc_ratio: (pc.from > 0.0).then(|| pc.to / pc.from),
+            miss_distance_m: NumberChange::new(from.miss_distance_m, to.miss_distance_m),
+            tca_shift_seconds: (to.tca - from.tca).num_milliseconds() as f64 / 1000.0,
+            object1: ObjectDiff::between(&from.object1, &to.object1),
+            object2: ObjectDiff::between(&from.object2, &to.object2),
+            changes,
+        }
+    }
+
+    /// Whether the CDMs differ in nothing but their ID and creation date
+    pub fn is_reissue(&self) -> bool {
+        self.changes.iter().all(|c| c.field == "cdm_id" || c.field == "creation_date")
+    }
+}
+
+impl ObjectDiff {
+    fn between(from: &CdmObject, to: &CdmObject) -> Self {
+        let (a, b) = (&from.state_vector, &to.state_vector);
+        let position = [b.x_km - a.x_km, b.y_km - a.y_km, b.z_km - a.z_km];
+        let velocity = [
+            (b.vx_km_s - a.vx_km_s) * 1000.0,
+            (b.vy_km_s - a.vy_km_s) * 1000.0,
+            (b.vz_km_s - a.vz_km_s) * 1000.0,
+        ];
+        Self {
+            object_id: to.object_id.clone(),
+            position_delta_km: position,
+            position_change_km: norm(position),
+            velocity_delta_m_s: velocity,
+            velocity_change_m_s: norm(velocity),
+            frame_changed: !a.reference_frame.eq_ignore_ascii_case(&b.reference_frame),
+            sigma_ratio_rtn: match (&from.covariance_rtm, &to.covariance_rtm) {
+                (Some(from), Some(to)) => Some(sigma_ratios(from, to)),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn sigma_ratios(from: &CovarianceRtn, to: &CovarianceRtn) -> [f64; 3] {
+    let ratio = |from: f64, to: f64| if from > 0.0 { (to / from).sqrt() } else { f64::NAN };
+    [ratio(from.cr_r, to.cr_r), ratio(from.ct_t, to.ct_t), ratio(from.cn_n, to.cn_n)]
+}
+
+/// Walk two JSON trees, recording the leaves that differ
+fn collect_changes(path: &str, from: &Value, to: &Value, changes: &mut Vec<FieldChange>) {
+    let child = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, value) in from {
+                collect_changes(&child(key), value, to.get(key).unwrap_or(&Value::Null), changes);
+            }
+            for (key, value) in to.iter().filter(|(key, _)| !from.contains_key(*key)) {
+                collect_changes(&child(key), &Value::Null, value, changes);
+            }
+        }
+        (from, to) if from != to => changes.push(FieldChange {
+            field: path.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+            delta: match (from.as_f64(), to.as_f64()) {
+                (Some(from), Some(to)) => Some(to - from),
+                _ => None,
+            },
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use chrono::Duration;
+
+    #[test]
+    fn test_cdm_diff() {
+        let from = generate_demo_cdm();
+        let mut to = from.clone();
+        to.cdm_id = format!("{}-2", from.cdm_id);
+        to.collision_probability = from.collision_probability * 4.0;
+        to.miss_distance_m = from.miss_distance_m - 50.0;
+        to.tca = from.tca + Duration::milliseconds(1500);
+        to.object2.state_vector.y_km += 3.0;
+        to.object2.state_vector.z_km += 4.0;
+        to.object2.state_vector.vz_km_s += 0.002;
+        to.object1.covariance_rtm.as_mut().unwrap().cr_r *= 4.0;
+        to.object2.covariance_rtm = None;
+
+        let diff = CdmDiff::between(&from, &to);
+        assert!(!diff.objects_differ);
+        assert_eq!(diff.pc_ratio, Some(4.0));
+        assert_eq!(diff.miss_distance_m.delta, -50.0);
+        assert_eq!(diff.tca_shift_seconds, 1.5);
+        assert_eq!(diff.object2.position_change_km, 5.0);
+        assert!((diff.object2.velocity_change_m_s - 2.0).abs() < 1e-9);
+        assert_eq!(diff.object1.position_change_km, 0.0);
+        assert_eq!(diff.object1.sigma_ratio_rtn, Some([2.0, 1.0, 1.0]));
+        assert_eq!(diff.object2.sigma_ratio_rtn, None);
+        let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+        for field in ["cdm_id", "collision_probability", "object1.covariance_rtm.cr_r", "object2.state_vector.y_km"] {
+            assert!(fields.contains(&field), "{}", field);
+        }
+        let y = diff.changes.iter().find(|c| c.field == "object2.state_vector.y_km").unwrap();
+        assert_eq!(y.delta, Some(3.0));
+        // A covariance only one CDM has is one change of the whole
+        let removed = diff.changes.iter().find(|c| c.field == "object2.covariance_rtm").unwrap();
+        assert!(removed.from.is_object() && removed.to.is_null());
+        assert!(!diff.is_reissue());
+
+        let mut reissued = from.clone();
+        reissued.cdm_id = "CDM-REISSUED".into();
+        assert!(CdmDiff::between(&from, &reissued).is_reissue());
+        to.object2.object_id = "NORAD-99998".into();
+        assert!(CdmDiff::between(&from, &to).objects_differ);
+    }
+}
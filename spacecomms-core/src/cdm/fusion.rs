@@ -0,0 +1,170 @@
+//! Trust-weighted fusion of several originators' CDMs
+//!
+//! Each originator contributes its latest CDM for a conjunction, weighted by
+//! the trust configured for it. The fused Pc is the weighted mean of the
+//! contributors' Pc. The fused miss distance is the maximum-likelihood
+//! estimate under independent Gaussian errors: when every contributor carries
+//! the relative state and covariances needed for the encounter plane, each
+//! miss distance is weighted by trust over its variance along the miss
+//! direction; otherwise by trust alone.
+
+use crate::cdm::{categorize, recommended_action, CdmRecord, ConjunctionCategory, EncounterGeometry, RecommendedAction};
+use crate::config::{FusionConfig, SeverityConfig};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// How a fused miss distance was weighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MissDistanceWeighting {
+    /// Trust over encounter-plane variance
+    InverseVariance,
+    /// Trust only; some contributor lacked covariance
+    Trust,
+}
+
+/// One originator's share of a fused assessment
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FusionContributor {
+    pub originator: String,
+    pub cdm_id: String,
+    pub weight: f64,
+}
+
+/// Consolidated risk figure for a conjunction
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FusedAssessment {
+    pub collision_probability: f64,
+    pub miss_distance_m: f64,
+    /// One-sigma uncertainty of the fused miss distance (inverse variance only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miss_distance_sigma_m: Option<f64>,
+    pub miss_distance_weighting: MissDistanceWeighting,
+    pub conjunction_category: ConjunctionCategory,
+    pub recommended_action: RecommendedAction,
+    /// Contributing CDMs; zero-weight originators are left out
+    pub contributors: Vec<FusionContributor>,
+}
+
+impl FusedAssessment {
+    /// Fuse one CDM per originator; `None` if no originator has weight
+    pub fn fuse(latest: &[&CdmRecord], fusion: &FusionConfig, severity: &SeverityConfig) -> Option<Self> {
+        let weighted: Vec<(&CdmRecord, f64)> = latest
+            .iter()
+            .map(|cdm| (*cdm, fusion.weight(&cdm.originator)))
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect();
+        let total: f64 = weighted.iter().map(|(_, w)| w).sum();
+        if weighted.is_empty() || total <= 0.0 {
+            return None;
+        }
+
+        let collision_probability = weighted
+            .iter()
+            .map(|(cdm, w)| w * cdm.collision_probability)
+            .sum::<f64>()
+            / total;
+
+        // Variance along the miss direction, if every contributor has one
+        let variances: Option<Vec<f64>> = weighted
+            .iter()
+            .map(|(cdm, _)| {
+                EncounterGeometry::from_cdm(cdm)
+                    .ok()
+                    .map(|g| g.covariance_m2[0])
+                    .filter(|v| v.is_finite() && *v > 0.0)
+            })
+            .collect();
+        let (miss_distance_m, miss_distance_sigma_m, miss_distance_weighting) = match variances {
+            Some(variances) => {
+                let precisions: Vec<f64> = weighted.iter().zip(&variances).map(|((_, w), v)| w / v).collect();
+                let precision: f64 = precisions.iter().sum();
+                let miss = weighted
+                    .iter()
+                    .zip(&precisions)
+                    .map(|((cdm, _), p)| p * cdm.miss_distance_m)
+                    .sum::<f64>()
+                    / precision;
+                (miss, Some(precision.recip().sqrt()), MissDistanceWeighting::InverseVariance)
+            }
+            None => {
+                let miss = weighted.iter().map(|(cdm, w)| w * cdm.miss_distance_m).sum::<f64>() / total;
+                (miss, None, MissDistanceWeighting::Trust)
+            }
+        };
+
+        let conjunction_category = categorize(collision_probability, miss_distance_m, severity);
+        Some(Self {
+            collision_probability,
+            miss_distance_m,
+            miss_distance_sigma_m,
+            miss_distance_weighting,
+            recommended_action: recommended_action(&conjunction_category),
+            conjunction_category,
+            contributors: weighted
+                .iter()
+                .map(|(cdm, weight)| FusionContributor {
+                    originator: cdm.originator.clone(),
+                    cdm_id: cdm.cdm_id.clone(),
+                    weight: *weight,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn cdm(originator: &str, pc: f64, miss: f64) -> CdmRecord {
+        let mut cdm = generate_demo_cdm();
+        cdm.originator = originator.into();
+        cdm.collision_probability = pc;
+        cdm.miss_distance_m = miss;
+        cdm
+    }
+
+    #[test]
+    fn test_trust_weighted_fusion() {
+        let mut latest = [cdm("A", 1e-4, 100.0), cdm("B", 1e-6, 400.0), cdm("C", 0.5, 0.0)];
+        for cdm in &mut latest {
+            cdm.object1.covariance_rtm = None;
+        }
+        let refs: Vec<&CdmRecord> = latest.iter().collect();
+        let mut fusion = FusionConfig::default();
+        fusion.originator_weights.insert("A".into(), 3.0);
+        fusion.originator_weights.insert("C".into(), 0.0);
+
+        let fused = FusedAssessment::fuse(&refs, &fusion, &SeverityConfig::default()).unwrap();
+        assert_eq!(fused.contributors.len(), 2);
+        assert!((fused.collision_probability - (3e-4 + 1e-6) / 4.0).abs() < 1e-12);
+        assert_eq!(fused.miss_distance_weighting, MissDistanceWeighting::Trust);
+        assert!((fused.miss_distance_m - 175.0).abs() < 1e-9);
+        // Within the default 200 m HIGH miss distance
+        assert_eq!(fused.conjunction_category, ConjunctionCategory::High);
+
+        fusion.default_weight = 0.0;
+        fusion.originator_weights.clear();
+        assert!(FusedAssessment::fuse(&refs, &fusion, &SeverityConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_inverse_variance_miss_distance() {
+        let precise = cdm("A", 1e-4, 100.0);
+        let mut loose = cdm("B", 1e-4, 400.0);
+        for object in [&mut loose.object1, &mut loose.object2] {
+            let covariance = object.covariance_rtm.as_mut().unwrap();
+            covariance.cr_r *= 9.0;
+            covariance.ct_t *= 9.0;
+            covariance.cn_n *= 9.0;
+        }
+        let fused = FusedAssessment::fuse(&[&precise, &loose], &FusionConfig::default(), &SeverityConfig::default())
+            .unwrap();
+        assert_eq!(fused.miss_distance_weighting, MissDistanceWeighting::InverseVariance);
+        // Nine times the variance gets a ninth of the weight
+        assert!((fused.miss_distance_m - 130.0).abs() < 1e-6);
+        assert!(fused.miss_distance_sigma_m.is_some());
+    }
+}
@@ -3,8 +3,86 @@
 use crate::cdm::{CdmObject, CdmRecord, RelativeState, ScreenType, ScreeningData};
 use crate::protocol::{ObjectType, StateVector};
 use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use uuid::Uuid;
 
+/// A synthetic conjunction between a maneuverable payload and debris
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticScenario {
+    pub object1_id: String,
+    pub object1_name: String,
+    pub object2_id: String,
+    pub object2_name: String,
+    pub tca: DateTime<Utc>,
+    pub miss_distance_m: f64,
+    pub collision_probability: f64,
+}
+
+impl SyntheticScenario {
+    /// `count` CDMs for the scenario created at `created`
+    ///
+    /// With a seed the CDM IDs come from it, so the same seed and creation
+    /// time give the same CDMs; without one they are random.
+    pub fn generate(&self, count: usize, created: DateTime<Utc>, seed: Option<u64>) -> Vec<CdmRecord> {
+        let mut rng = seed.map(StdRng::seed_from_u64);
+        (0..count)
+            .map(|_| {
+                let suffix = match rng.as_mut() {
+                    Some(rng) => format!("{:08X}", rng.gen::<u32>()),
+                    None => Uuid::new_v4().to_string()[..8].to_uppercase(),
+                };
+                self.cdm(format!("CDM-{}-{}", created.format("%Y%m%d"), suffix), created)
+            })
+            .collect()
+    }
+
+    /// The scenario's CDM with the given ID, created at `created`
+    pub fn cdm(&self, cdm_id: String, created: DateTime<Utc>) -> CdmRecord {
+        let miss_distance_m = self.miss_distance_m;
+        let collision_probability = self.collision_probability;
+        CdmRecord {
+            cdm_id,
+            creation_date: created,
+            originator: "SYNTHETIC-GENERATOR".to_string(),
+            message_for: "DEMO-OPERATOR".to_string(),
+            tca: self.tca,
+            miss_distance_m,
+            collision_probability,
+            object1: generate_object(&self.object1_id, &self.object1_name, ObjectType::Payload, true, created),
+            object2: generate_object(&self.object2_id, &self.object2_name, ObjectType::Debris, false, created),
+            relative_state: Some(RelativeState {
+                relative_position_r_m: miss_distance_m * 0.3,
+                relative_position_t_m: miss_distance_m * 0.6,
+                relative_position_n_m: miss_distance_m * 0.1,
+                relative_velocity_r_m_s: 0.5,
+                relative_velocity_t_m_s: 15000.0,
+                relative_velocity_n_m_s: 0.1,
+            }),
+            screening_data: Some(ScreeningData {
+                screen_type: ScreenType::Routine,
+                screen_volume_shape: Some("ELLIPSOID".to_string()),
+                hard_body_radius_m: Some(15.0),
+            }),
+            data_quality_score: Some(0.95),
+            conjunction_category: if collision_probability > 1e-3 {
+                Some(crate::cdm::ConjunctionCategory::High)
+            } else if collision_probability > 1e-5 {
+                Some(crate::cdm::ConjunctionCategory::Medium)
+            } else {
+                Some(crate::cdm::ConjunctionCategory::Low)
+            },
+            recommended_action: if collision_probability > 1e-4 {
+                Some(crate::cdm::RecommendedAction::Prepare)
+            } else {
+                Some(crate::cdm::RecommendedAction::Monitor)
+            },
+            organization: None,
+            involves_watched_asset: false,
+        }
+    }
+}
+
 /// Generate a synthetic CDM for testing
 pub fn generate_synthetic_cdm(
     object1_id: &str,
@@ -15,49 +93,16 @@ pub fn generate_synthetic_cdm(
     miss_distance_m: f64,
     collision_probability: f64,
 ) -> CdmRecord {
-    let now = Utc::now();
-    let cdm_id = format!("CDM-{}-{}", 
-        now.format("%Y%m%d"),
-        &Uuid::new_v4().to_string()[..8].to_uppercase()
-    );
-    
-    CdmRecord {
-        cdm_id,
-        creation_date: now,
-        originator: "SYNTHETIC-GENERATOR".to_string(),
-        message_for: "DEMO-OPERATOR".to_string(),
+    let scenario = SyntheticScenario {
+        object1_id: object1_id.to_string(),
+        object1_name: object1_name.to_string(),
+        object2_id: object2_id.to_string(),
+        object2_name: object2_name.to_string(),
         tca,
         miss_distance_m,
         collision_probability,
-        object1: generate_object(object1_id, object1_name, ObjectType::Payload, true, now),
-        object2: generate_object(object2_id, object2_name, ObjectType::Debris, false, now),
-        relative_state: Some(RelativeState {
-            relative_position_r_m: miss_distance_m * 0.3,
-            relative_position_t_m: miss_distance_m * 0.6,
-            relative_position_n_m: miss_distance_m * 0.1,
-            relative_velocity_r_m_s: 0.5,
-            relative_velocity_t_m_s: 15000.0,
-            relative_velocity_n_m_s: 0.1,
-        }),
-        screening_data: Some(ScreeningData {
-            screen_type: ScreenType::Routine,
-            screen_volume_shape: Some("ELLIPSOID".to_string()),
-            hard_body_radius_m: Some(15.0),
-        }),
-        data_quality_score: Some(0.95),
-        conjunction_category: if collision_probability > 1e-3 {
-            Some(crate::cdm::ConjunctionCategory::High)
-        } else if collision_probability > 1e-5 {
-            Some(crate::cdm::ConjunctionCategory::Medium)
-        } else {
-            Some(crate::cdm::ConjunctionCategory::Low)
-        },
-        recommended_action: if collision_probability > 1e-4 {
-            Some(crate::cdm::RecommendedAction::Prepare)
-        } else {
-            Some(crate::cdm::RecommendedAction::Monitor)
-        },
-    }
+    };
+    scenario.generate(1, Utc::now(), None).remove(0)
 }
 
 fn generate_object(
@@ -78,6 +123,7 @@ fn generate_object(
         object_type,
         owner_operator: if maneuverable { Some("Demo Operator".to_string()) } else { None },
         maneuverable,
+        rcs_size: None,
         state_vector: StateVector {
             reference_frame: "TEME".to_string(),
             epoch: Some(epoch),
@@ -141,4 +187,28 @@ mod tests {
         assert!(cdm.object1.maneuverable);
         assert!(!cdm.object2.maneuverable);
     }
+
+    #[test]
+    fn test_seeded_scenario() {
+        let created = "2024-01-15T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let scenario = SyntheticScenario {
+            object1_id: "SAT-001".into(),
+            object1_name: "Test Satellite".into(),
+            object2_id: "DEB-001".into(),
+            object2_name: "Test Debris".into(),
+            tca: created + Duration::hours(6),
+            miss_distance_m: 40.0,
+            collision_probability: 3e-3,
+        };
+        let json = |seed| serde_json::to_value(scenario.generate(3, created, seed)).unwrap();
+        assert_eq!(json(Some(42)), json(Some(42)));
+        assert_ne!(json(Some(42)), json(Some(43)));
+        // Unseeded IDs differ between runs
+        assert_ne!(json(None), json(None));
+
+        let cdms = scenario.generate(3, created, Some(42));
+        assert_ne!(cdms[0].cdm_id, cdms[1].cdm_id);
+        assert!(cdms.iter().all(|cdm| cdm.cdm_id.starts_with("CDM-20240115-") && cdm.creation_date == created));
+        assert!(cdms.iter().all(|cdm| validate_cdm(cdm).is_ok()));
+    }
 }
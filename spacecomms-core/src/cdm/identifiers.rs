@@ -0,0 +1,215 @@
+//! Object identifier canonicalization
+//!
+//! Providers name the same object `25544`, `NORAD-25544`, `norad 025544` or
+//! by its international designator, `1998-067A` or `98067A`. Object IDs are
+//! brought to one form as CDMs and object states enter the node, so storage,
+//! conjunction grouping and watchlist matching see one ID per object:
+//! `NORAD-` and the catalog number without leading zeros, or, for an object
+//! known only by designator, `YYYY-NNNP`. Other IDs are kept as given,
+//! trimmed, with each run of inner whitespace made one space. The
+//! `identifiers.aliases` table maps designators and provider names to the
+//! object they stand for; aliases match in canonical form and regardless of
+//! case. An ID with control characters, or longer than
+//! [`MAX_OBJECT_ID_BYTES`], is refused on ingest; see [`check_object_id`].
+
+use crate::cdm::CdmRecord;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Longest catalog number taken as one
+const MAX_CATALOG_DIGITS: usize = 9;
+
+/// Longest object ID taken on ingest
+pub const MAX_OBJECT_ID_BYTES: usize = 64;
+
+/// An object ID in canonical form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectIdentifier {
+    /// NORAD catalog number, without leading zeros
+    Norad(String),
+    /// International designator, as `YYYY-NNNP`
+    Designator(String),
+    /// Any other ID, trimmed, inner whitespace runs made one space
+    Other(String),
+}
+
+impl ObjectIdentifier {
+    pub fn parse(id: &str) -> Self {
+        let id = id.trim();
+        if let Some(number) = catalog_number(id) {
+            Self::Norad(number)
+        } else if let Some(designator) = designator(id) {
+            Self::Designator(designator)
+        } else {
+            Self::Other(collapse_spaces(id))
+        }
+    }
+
+    /// The catalog number, for NORAD IDs
+    pub fn norad_number(&self) -> Option<&str> {
+        match self {
+            Self::Norad(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// Form aliases are looked up by: canonical, and upper case for other IDs
+    fn alias_key(&self) -> String {
+        match self {
+            Self::Other(id) => id.to_ascii_uppercase(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ObjectIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Norad(number) => write!(f, "NORAD-{}", number),
+            Self::Designator(designator) => f.write_str(designator),
+            Self::Other(id) => f.write_str(id),
+        }
+    }
+}
+
+/// `id` with each run of whitespace made one space; control characters,
+/// tabs and line breaks included, are kept for [`check_object_id`] to refuse
+fn collapse_spaces(id: &str) -> String {
+    let mut collapsed = String::with_capacity(id.len());
+    for c in id.chars() {
+        if !c.is_whitespace() || c.is_control() {
+            collapsed.push(c);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    collapsed
+}
+
+/// Catalog number of `25544`, `00005`, `NORAD-25544` or `norad 25544`
+fn catalog_number(id: &str) -> Option<String> {
+    let digits = match id.get(..5) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("NORAD") => {
+            let rest = &id[5..];
+            rest.strip_prefix(['-', '_', ':', ' ']).unwrap_or(rest)
+        }
+        _ => id,
+    };
+    if digits.is_empty() || digits.len() > MAX_CATALOG_DIGITS || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let trimmed = digits.trim_start_matches('0');
+    Some(if trimmed.is_empty() { "0" } else { trimmed }.to_string())
+}
+
+/// `YYYY-NNNP` form of `1998-067A`, `1998067A`, `98-067A` or `98067a`;
+/// two-digit years from 57 on are 19xx
+fn designator(id: &str) -> Option<String> {
+    let id = id.to_ascii_uppercase();
+    let (year, rest) = match id.split_once('-') {
+        Some(parts) => parts,
+        None => id.split_at(id.bytes().take_while(u8::is_ascii_digit).count().checked_sub(3)?),
+    };
+    if !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year: u32 = match year.len() {
+        4 => year.parse().ok().filter(|year| *year >= 1957)?,
+        2 => match year.parse().ok()? {
+            short @ 57.. => 1900 + short,
+            short => 2000 + short,
+        },
+        _ => return None,
+    };
+    let (launch, piece) = rest.split_at_checked(3)?;
+    let valid = launch.bytes().all(|b| b.is_ascii_digit())
+        && (1..=3).contains(&piece.len())
+        && piece.bytes().all(|b| b.is_ascii_uppercase());
+    valid.then(|| format!("{}-{}{}", year, launch, piece))
+}
+
+/// Canonical form of an object ID, following `aliases` (alias to object ID)
+pub fn canonical_object_id(id: &str, aliases: &BTreeMap<String, String>) -> String {
+    let parsed = ObjectIdentifier::parse(id);
+    if !aliases.is_empty() {
+        let key = parsed.alias_key();
+        if let Some((_, target)) = aliases.iter().find(|(alias, _)| ObjectIdentifier::parse(alias).alias_key() == key) {
+            return ObjectIdentifier::parse(target).to_string();
+        }
+    }
+    parsed.to_string()
+}
+
+/// Refuse an object ID with control characters or over
+/// [`MAX_OBJECT_ID_BYTES`], returning why
+pub fn check_object_id(id: &str) -> std::result::Result<(), String> {
+    if id.len() > MAX_OBJECT_ID_BYTES {
+        Err(format!("is longer than {} bytes", MAX_OBJECT_ID_BYTES))
+    } else if id.chars().any(char::is_control) {
+        Err("contains control characters".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Bring both object IDs of a CDM to canonical form
+pub fn canonicalize_cdm(cdm: &mut CdmRecord, aliases: &BTreeMap<String, String>) {
+    for object in [&mut cdm.object1, &mut cdm.object2] {
+        object.object_id = canonical_object_id(&object.object_id, aliases);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[test]
+    fn test_canonical_object_id() {
+        let none = BTreeMap::new();
+        for id in ["25544", "NORAD-25544", "norad 025544", " NORAD_25544 ", "NORAD:25544"] {
+            assert_eq!(canonical_object_id(id, &none), "NORAD-25544", "{}", id);
+        }
+        assert_eq!(canonical_object_id("000", &none), "NORAD-0");
+        for id in ["1998-067A", "1998067A", "98-067a", "98067A"] {
+            assert_eq!(canonical_object_id(id, &none), "1998-067A", "{}", id);
+        }
+        assert_eq!(canonical_object_id("19-074BZ", &none), "2019-074BZ");
+        assert_eq!(ObjectIdentifier::parse("98067A").norad_number(), None);
+        for id in ["STARLINK-1234", "NORAD-", "1998-67A", "1998-067ABCD", "1234567890"] {
+            assert!(matches!(ObjectIdentifier::parse(id), ObjectIdentifier::Other(_)), "{}", id);
+        }
+        assert_eq!(canonical_object_id(" ISS (ZARYA) ", &none), "ISS (ZARYA)");
+        assert_eq!(canonical_object_id("ISS \u{a0}\u{3000} (ZARYA)", &none), "ISS (ZARYA)");
+    }
+
+    #[test]
+    fn test_check_object_id() {
+        let none = BTreeMap::new();
+        assert!(check_object_id(&canonical_object_id(" NORAD 25544\n", &none)).is_ok());
+        assert!(check_object_id(&"X".repeat(MAX_OBJECT_ID_BYTES)).is_ok());
+        assert!(check_object_id(&"X".repeat(MAX_OBJECT_ID_BYTES + 1)).is_err());
+        for id in ["SAT\nB", "SAT\tB", "SAT\u{0}", "SAT\u{1b}[31m", "SAT\r\nB"] {
+            assert!(check_object_id(&canonical_object_id(id, &none)).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases: BTreeMap<String, String> = [("98067A", "25544"), ("iss", "NORAD-25544"), ("SAT-X", "SAT-Y")]
+            .into_iter()
+            .map(|(alias, id)| (alias.to_string(), id.to_string()))
+            .collect();
+        for id in ["1998-067A", "ISS", "Iss", "25544"] {
+            assert_eq!(canonical_object_id(id, &aliases), "NORAD-25544", "{}", id);
+        }
+        assert_eq!(canonical_object_id("sat-x", &aliases), "SAT-Y");
+        assert_eq!(canonical_object_id("2000-001A", &aliases), "2000-001A");
+
+        let mut cdm = generate_demo_cdm();
+        cdm.object1.object_id = "012345".into();
+        cdm.object2.object_id = "98067A".into();
+        canonicalize_cdm(&mut cdm, &aliases);
+        assert_eq!((cdm.object1.object_id.as_str(), cdm.object2.object_id.as_str()), ("NORAD-12345", "NORAD-25544"));
+    }
+}
@@ -1,9 +1,31 @@
 //! CDM module - Conjunction Data Message handling
 
+mod conjunction;
+mod diff;
+mod fusion;
 mod parser;
 mod generator;
+mod identifiers;
+mod omm;
+mod opm;
+mod pc;
+mod quality;
+mod severity;
+mod trend;
 mod types;
+mod units;
 
+pub use conjunction::*;
+pub use diff::*;
+pub use fusion::*;
 pub use parser::*;
 pub use generator::*;
+pub use identifiers::*;
+pub use omm::*;
+pub use opm::*;
+pub use pc::*;
+pub use quality::*;
+pub use severity::*;
+pub use trend::*;
 pub use types::*;
+pub use units::*;
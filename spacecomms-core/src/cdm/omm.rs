@@ -0,0 +1,310 @@
+//! Orbit Mean-Elements Message (OMM) parsing
+//!
+//! Catalogs such as CelesTrak and Space-Track publish CCSDS OMMs rather than
+//! state vectors. Both encodings are accepted: JSON (one message object or
+//! an array of them, keyed by the CCSDS keyword names) and KVN (`KEY = value`
+//! lines, with a new message starting at each `CCSDS_OMM_VERS`). The mean
+//! elements are turned into a TEME state vector at the message epoch by SGP4
+//! initialization, so only SGP4 element sets are accepted.
+
+use super::ObjectRecord;
+use crate::orbit::{sgp4_epoch_state, MeanElements};
+use crate::protocol::{parse_timestamp, ObjectType, RcsSize, StateVector};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Keyword that opens a KVN message
+const VERSION_KEYWORD: &str = "CCSDS_OMM_VERS";
+
+/// One OMM element set
+#[derive(Debug, Clone, PartialEq)]
+pub struct OmmRecord {
+    pub object_name: String,
+    /// International designator (e.g. `1998-067A`)
+    pub object_id: String,
+    pub norad_cat_id: Option<u64>,
+    pub epoch: DateTime<Utc>,
+    pub elements: MeanElements,
+    /// SGP4 drag term (1/earth radii)
+    pub bstar: Option<f64>,
+    /// Space-Track extension: PAYLOAD, DEBRIS, ROCKET BODY, ...
+    pub object_type: Option<String>,
+    /// Space-Track extension: SMALL, MEDIUM or LARGE
+    pub rcs_size: Option<String>,
+}
+
+impl OmmRecord {
+    /// Identifier the object is tracked under: `NORAD-<catalog number>`
+    /// when the message has one, the international designator otherwise
+    pub fn tracking_id(&self) -> String {
+        match self.norad_cat_id {
+            Some(number) => format!("NORAD-{}", number),
+            None => self.object_id.clone(),
+        }
+    }
+
+    /// Object type from the message, falling back to the catalog naming
+    /// convention (`DEB` and `R/B` in the name)
+    pub fn classify(&self) -> ObjectType {
+        let declared = self.object_type.as_deref().map(str::to_ascii_uppercase);
+        match declared.as_deref() {
+            Some("PAYLOAD") => return ObjectType::Payload,
+            Some("DEBRIS") => return ObjectType::Debris,
+            Some("ROCKET BODY") => return ObjectType::RocketBody,
+            _ => {}
+        }
+        let name = self.object_name.to_ascii_uppercase();
+        if name.split_whitespace().any(|word| word == "DEB") {
+            ObjectType::Debris
+        } else if name.contains("R/B") {
+            ObjectType::RocketBody
+        } else {
+            ObjectType::Unknown
+        }
+    }
+
+    /// Build an object record from the element set, with its state vector
+    /// from SGP4 at the message epoch
+    pub fn to_object(&self, source_node: &str) -> Result<ObjectRecord> {
+        let state = sgp4_epoch_state(&self.elements)?;
+        let rcs_size = match self.rcs_size.as_deref().map(str::to_ascii_uppercase).as_deref() {
+            Some("SMALL") => Some(RcsSize::Small),
+            Some("MEDIUM") => Some(RcsSize::Medium),
+            Some("LARGE") => Some(RcsSize::Large),
+            _ => None,
+        };
+        Ok(ObjectRecord {
+            object_id: self.tracking_id(),
+            object_name: self.object_name.clone(),
+            object_type: self.classify(),
+            owner_operator: None,
+            rcs_size,
+            epoch: self.epoch,
+            state_vector: StateVector {
+                reference_frame: "TEME".to_string(),
+                epoch: Some(self.epoch),
+                x_km: state[0],
+                y_km: state[1],
+                z_km: state[2],
+                vx_km_s: state[3],
+                vy_km_s: state[4],
+                vz_km_s: state[5],
+            },
+            covariance: None,
+            source_node: source_node.to_string(),
+            last_updated: Utc::now(),
+            organization: None,
+        })
+    }
+}
+
+/// Parse an OMM document in JSON or KVN
+pub fn parse_omm(input: &str) -> Result<Vec<OmmRecord>> {
+    let trimmed = input.trim_start();
+    let messages = if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        json_messages(trimmed)?
+    } else {
+        kvn_messages(input)?
+    };
+    if messages.is_empty() {
+        return Err(Error::Ccsds("no element sets in document".to_string()));
+    }
+    messages
+        .iter()
+        .enumerate()
+        .map(|(index, fields)| {
+            from_fields(fields).map_err(|e| match e {
+                Error::Ccsds(message) => Error::Ccsds(format!("element set {}: {}", index, message)),
+                e => e,
+            })
+        })
+        .collect()
+}
+
+fn json_messages(input: &str) -> Result<Vec<HashMap<String, String>>> {
+    let value: Value = serde_json::from_str(input)?;
+    let items = match value {
+        Value::Array(items) => items,
+        item => vec![item],
+    };
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let Value::Object(map) = item else {
+                return Err(Error::Ccsds(format!("element set {} is not an object", index)));
+            };
+            // Space-Track quotes numbers; CelesTrak does not
+            Ok(map
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let value = match value {
+                        Value::String(s) => s,
+                        Value::Number(n) => n.to_string(),
+                        _ => return None,
+                    };
+                    Some((key.to_ascii_uppercase(), value))
+                })
+                .collect())
+        })
+        .collect()
+}
+
+/// Keyword/value pairs of a CCSDS KVN document, in order, skipping blank
+/// and `COMMENT` lines
+pub(crate) fn kvn_pairs(input: &str) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("COMMENT") {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(Error::Ccsds(format!("line {}: expected KEY = value", number + 1)));
+        };
+        // Values may be followed by their unit in brackets: `15.5 [rev/day]`
+        let value = value.split('[').next().unwrap_or_default().trim().to_string();
+        pairs.push((key.trim().to_ascii_uppercase(), value));
+    }
+    Ok(pairs)
+}
+
+fn kvn_messages(input: &str) -> Result<Vec<HashMap<String, String>>> {
+    let mut messages: Vec<HashMap<String, String>> = Vec::new();
+    for (key, value) in kvn_pairs(input)? {
+        if key == VERSION_KEYWORD || messages.is_empty() {
+            messages.push(HashMap::new());
+        }
+        if let Some(message) = messages.last_mut() {
+            message.insert(key, value);
+        }
+    }
+    Ok(messages)
+}
+
+fn from_fields(fields: &HashMap<String, String>) -> Result<OmmRecord> {
+    let text = |key: &str| fields.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let required = |key: &str| text(key).ok_or_else(|| Error::Ccsds(format!("{} is required", key)));
+    let number = |key: &str| -> Result<Option<f64>> {
+        text(key)
+            .map(|v| v.parse::<f64>().map_err(|_| Error::Ccsds(format!("{} is not a number: {}", key, v))))
+            .transpose()
+    };
+    let required_number = |key: &str| number(key)?.ok_or_else(|| Error::Ccsds(format!("{} is required", key)));
+
+    for (key, expected) in [
+        ("MEAN_ELEMENT_THEORY", "SGP4"),
+        ("CENTER_NAME", "EARTH"),
+        ("REF_FRAME", "TEME"),
+        ("TIME_SYSTEM", "UTC"),
+    ] {
+        if let Some(value) = text(key) {
+            if !value.eq_ignore_ascii_case(expected) {
+                return Err(Error::Ccsds(format!("{} {} is not supported (expected {})", key, value, expected)));
+            }
+        }
+    }
+    if text("MEAN_MOTION").is_none() && text("SEMI_MAJOR_AXIS").is_some() {
+        return Err(Error::Ccsds("MEAN_MOTION is required; SGP4 elements do not use SEMI_MAJOR_AXIS".to_string()));
+    }
+
+    let epoch = required("EPOCH")?;
+    let epoch = parse_timestamp(epoch).map_err(|_| Error::Ccsds(format!("EPOCH is not a timestamp: {}", epoch)))?;
+    let norad_cat_id = text("NORAD_CAT_ID")
+        .map(|v| v.parse::<u64>().map_err(|_| Error::Ccsds(format!("NORAD_CAT_ID is not a catalog number: {}", v))))
+        .transpose()?;
+
+    Ok(OmmRecord {
+        object_name: required("OBJECT_NAME")?.to_string(),
+        object_id: required("OBJECT_ID")?.to_string(),
+        norad_cat_id,
+        epoch,
+        elements: MeanElements {
+            mean_motion_rev_day: required_number("MEAN_MOTION")?,
+            eccentricity: required_number("ECCENTRICITY")?,
+            inclination_deg: required_number("INCLINATION")?,
+            raan_deg: required_number("RA_OF_ASC_NODE")?,
+            arg_of_perigee_deg: required_number("ARG_OF_PERICENTER")?,
+            mean_anomaly_deg: required_number("MEAN_ANOMALY")?,
+        },
+        bstar: number("BSTAR")?,
+        object_type: text("OBJECT_TYPE").map(str::to_string),
+        rcs_size: text("RCS_SIZE").map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KVN: &str = "\
+CCSDS_OMM_VERS = 2.0
+CREATION_DATE = 2000-06-28T00:00:00
+ORIGINATOR = TEST
+OBJECT_NAME = VANGUARD 1
+OBJECT_ID = 1958-002B
+CENTER_NAME = EARTH
+REF_FRAME = TEME
+TIME_SYSTEM = UTC
+MEAN_ELEMENT_THEORY = SGP4
+COMMENT mean elements
+EPOCH = 2000-06-27T18:50:19.733568
+MEAN_MOTION = 10.82419157 [rev/day]
+ECCENTRICITY = 0.1859667
+INCLINATION = 34.2682 [deg]
+RA_OF_ASC_NODE = 348.7242 [deg]
+ARG_OF_PERICENTER = 331.7664 [deg]
+MEAN_ANOMALY = 19.3264 [deg]
+NORAD_CAT_ID = 5
+BSTAR = 0.000028098
+";
+
+    #[test]
+    fn test_parse_omm_kvn_and_json() {
+        let kvn = parse_omm(KVN).unwrap();
+        assert_eq!(kvn.len(), 1);
+        assert_eq!(kvn[0].tracking_id(), "NORAD-5");
+        assert_eq!(kvn[0].elements.mean_motion_rev_day, 10.82419157);
+        assert_eq!(kvn[0].bstar, Some(0.000028098));
+
+        let json = r#"[{
+            "OBJECT_NAME": "VANGUARD 1", "OBJECT_ID": "1958-002B",
+            "EPOCH": "2000-06-27T18:50:19.733568", "MEAN_MOTION": 10.82419157,
+            "ECCENTRICITY": "0.1859667", "INCLINATION": 34.2682, "RA_OF_ASC_NODE": 348.7242,
+            "ARG_OF_PERICENTER": 331.7664, "MEAN_ANOMALY": 19.3264, "NORAD_CAT_ID": 5,
+            "OBJECT_TYPE": "PAYLOAD", "RCS_SIZE": "SMALL"
+        }]"#;
+        let json = parse_omm(json).unwrap();
+        assert_eq!(json[0].elements, kvn[0].elements);
+        assert_eq!(json[0].epoch, kvn[0].epoch);
+
+        let object = json[0].to_object("node-a").unwrap();
+        assert_eq!(object.object_type, ObjectType::Payload);
+        assert_eq!(object.rcs_size, Some(RcsSize::Small));
+        assert_eq!(object.state_vector.reference_frame, "TEME");
+        assert!((object.state_vector.x_km - 7022.465).abs() < 1e-2);
+
+        let two = format!("{}{}", KVN, KVN.replace("NORAD_CAT_ID = 5", "NORAD_CAT_ID = 6"));
+        let two = parse_omm(&two).unwrap();
+        assert_eq!(two[1].tracking_id(), "NORAD-6");
+    }
+
+    #[test]
+    fn test_parse_omm_rejects_unsupported() {
+        let err = parse_omm(&KVN.replace("SGP4", "DSST")).unwrap_err().to_string();
+        assert!(err.contains("MEAN_ELEMENT_THEORY"), "{}", err);
+        let err = parse_omm(&KVN.replace("MEAN_MOTION = 10.82419157 [rev/day]", "")).unwrap_err().to_string();
+        assert!(err.contains("element set 0: MEAN_MOTION is required"), "{}", err);
+        assert!(parse_omm("").is_err());
+        assert!(parse_omm("OBJECT_NAME VANGUARD").is_err());
+
+        let debris = OmmRecord {
+            object_name: "COSMOS 2251 DEB".to_string(),
+            object_type: None,
+            ..parse_omm(KVN).unwrap().remove(0)
+        };
+        assert_eq!(debris.classify(), ObjectType::Debris);
+    }
+}
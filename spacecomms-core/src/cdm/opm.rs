@@ -0,0 +1,271 @@
+//! Orbit Parameter Message (OPM) parsing
+//!
+//! Flight dynamics teams describe planned maneuvers as CCSDS OPMs in KVN: a
+//! state vector plus one or more maneuver blocks (`MAN_EPOCH_IGNITION`,
+//! `MAN_DURATION`, `MAN_REF_FRAME`, `MAN_DV_1..3`). An OPM submitted as a
+//! maneuver plan carries the predicted post-maneuver state, so the state
+//! epoch must not precede the end of the burn. One maneuver per message is
+//! accepted, since a single announced intent has one start and one delta-V.
+
+use super::omm::kvn_pairs;
+use crate::orbit::{cross, dot, position_velocity, rtn_basis, unit};
+use crate::protocol::{parse_timestamp, DeltaV, ManeuverIntentPayload, ManeuverType, StateVector};
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+/// Prefix of the keywords in a maneuver block
+const MANEUVER_PREFIX: &str = "MAN_";
+
+/// Keyword that opens a maneuver block
+const MANEUVER_START: &str = "MAN_EPOCH_IGNITION";
+
+/// One maneuver from an OPM
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpmManeuver {
+    pub ignition: DateTime<Utc>,
+    pub duration_s: f64,
+    /// Frame of `delta_v_km_s`: RTN (or RSW), TNW, VNB or the state's frame
+    pub ref_frame: String,
+    pub delta_v_km_s: [f64; 3],
+}
+
+/// A parsed OPM
+#[derive(Debug, Clone)]
+pub struct OpmRecord {
+    pub object_name: String,
+    pub object_id: String,
+    pub state_vector: StateVector,
+    pub maneuvers: Vec<OpmManeuver>,
+}
+
+impl OpmRecord {
+    /// Build a maneuver intent from the message's single maneuver, with the
+    /// message state as the predicted post-maneuver state
+    pub fn to_intent(
+        &self,
+        maneuver_id: String,
+        object_id: Option<String>,
+        related_cdm_id: Option<String>,
+        maneuver_type: ManeuverType,
+    ) -> Result<ManeuverIntentPayload> {
+        let maneuver = match self.maneuvers.as_slice() {
+            [maneuver] => maneuver,
+            [] => return Err(Error::Ccsds("OPM has no maneuver block".to_string())),
+            _ => {
+                return Err(Error::Ccsds(format!(
+                    "OPM has {} maneuvers; submit one maneuver per message",
+                    self.maneuvers.len()
+                )))
+            }
+        };
+        let burn_end = maneuver.ignition + Duration::milliseconds((maneuver.duration_s * 1000.0) as i64);
+        let epoch = self.state_vector.epoch.unwrap_or(maneuver.ignition);
+        if epoch < burn_end {
+            return Err(Error::Ccsds(format!(
+                "state EPOCH {} precedes the end of the maneuver at {}; expected the post-maneuver state",
+                epoch, burn_end
+            )));
+        }
+        let [v, n, b] = to_vnb(maneuver, &self.state_vector)?;
+        Ok(ManeuverIntentPayload {
+            maneuver_id,
+            object_id: object_id.unwrap_or_else(|| self.object_id.clone()),
+            related_cdm_id,
+            planned_start: maneuver.ignition,
+            planned_duration_s: maneuver.duration_s,
+            maneuver_type,
+            delta_v: Some(DeltaV {
+                reference_frame: "VNB".to_string(),
+                dv_v_m_s: v * 1000.0,
+                dv_n_m_s: n * 1000.0,
+                dv_b_m_s: b * 1000.0,
+            }),
+            predicted_post_maneuver_state: Some(self.state_vector.clone()),
+        })
+    }
+}
+
+/// Delta-V in the VNB frame of the given state; local frames are oriented
+/// with the post-maneuver state, which is close enough for announcement
+fn to_vnb(maneuver: &OpmManeuver, state: &StateVector) -> Result<[f64; 3]> {
+    let (r, v) = position_velocity(state);
+    let dv = maneuver.delta_v_km_s;
+    let frame = maneuver.ref_frame.to_ascii_uppercase();
+    if frame == "VNB" {
+        return Ok(dv);
+    }
+
+    let degenerate = || Error::Ccsds("state vector is degenerate".to_string());
+    let w = unit(cross(r, v)).ok_or_else(degenerate)?;
+    let inertial = match frame.as_str() {
+        "RTN" | "RSW" => {
+            let [radial, transverse, w] = rtn_basis(r, v).ok_or_else(degenerate)?;
+            [0, 1, 2].map(|i| dv[0] * radial[i] + dv[1] * transverse[i] + dv[2] * w[i])
+        }
+        "TNW" => {
+            let tangent = unit(v).ok_or_else(degenerate)?;
+            let normal = cross(w, tangent);
+            [0, 1, 2].map(|i| dv[0] * tangent[i] + dv[1] * normal[i] + dv[2] * w[i])
+        }
+        _ if frame.eq_ignore_ascii_case(&state.reference_frame) => dv,
+        _ => {
+            return Err(Error::Ccsds(format!(
+                "MAN_REF_FRAME {} is not supported (expected RTN, RSW, TNW, VNB or {})",
+                maneuver.ref_frame, state.reference_frame
+            )))
+        }
+    };
+    let along = unit(v).ok_or_else(degenerate)?;
+    let binormal = cross(along, w);
+    Ok([dot(inertial, along), dot(inertial, w), dot(inertial, binormal)])
+}
+
+/// Parse an OPM in KVN
+pub fn parse_opm(input: &str) -> Result<OpmRecord> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut blocks: Vec<HashMap<String, String>> = Vec::new();
+    for (key, value) in kvn_pairs(input)? {
+        if key == MANEUVER_START {
+            blocks.push(HashMap::new());
+        }
+        if key.starts_with(MANEUVER_PREFIX) {
+            let Some(block) = blocks.last_mut() else {
+                return Err(Error::Ccsds(format!("{} before {}", key, MANEUVER_START)));
+            };
+            block.insert(key, value);
+        } else {
+            fields.insert(key, value);
+        }
+    }
+
+    let text = |fields: &HashMap<String, String>, key: &str| {
+        fields
+            .get(key)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| Error::Ccsds(format!("{} is required", key)))
+    };
+    let number = |fields: &HashMap<String, String>, key: &str| -> Result<f64> {
+        let value = text(fields, key)?;
+        value
+            .parse::<f64>()
+            .map_err(|_| Error::Ccsds(format!("{} is not a number: {}", key, value)))
+    };
+    let timestamp = |fields: &HashMap<String, String>, key: &str| -> Result<DateTime<Utc>> {
+        let value = text(fields, key)?;
+        parse_timestamp(&value).map_err(|_| Error::Ccsds(format!("{} is not a timestamp: {}", key, value)))
+    };
+
+    for (key, expected) in [("CENTER_NAME", "EARTH"), ("TIME_SYSTEM", "UTC")] {
+        let value = text(&fields, key)?;
+        if !value.eq_ignore_ascii_case(expected) {
+            return Err(Error::Ccsds(format!("{} {} is not supported (expected {})", key, value, expected)));
+        }
+    }
+
+    let maneuvers = blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| {
+            let maneuver = OpmManeuver {
+                ignition: timestamp(block, MANEUVER_START)?,
+                duration_s: number(block, "MAN_DURATION")?,
+                ref_frame: text(block, "MAN_REF_FRAME")?,
+                delta_v_km_s: [
+                    number(block, "MAN_DV_1")?,
+                    number(block, "MAN_DV_2")?,
+                    number(block, "MAN_DV_3")?,
+                ],
+            };
+            if maneuver.duration_s < 0.0 {
+                return Err(Error::Ccsds(format!("maneuver {}: MAN_DURATION is negative", index)));
+            }
+            Ok(maneuver)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(OpmRecord {
+        object_name: text(&fields, "OBJECT_NAME")?,
+        object_id: text(&fields, "OBJECT_ID")?,
+        state_vector: StateVector {
+            reference_frame: text(&fields, "REF_FRAME")?,
+            epoch: Some(timestamp(&fields, "EPOCH")?),
+            x_km: number(&fields, "X")?,
+            y_km: number(&fields, "Y")?,
+            z_km: number(&fields, "Z")?,
+            vx_km_s: number(&fields, "X_DOT")?,
+            vy_km_s: number(&fields, "Y_DOT")?,
+            vz_km_s: number(&fields, "Z_DOT")?,
+        },
+        maneuvers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPM: &str = "\
+CCSDS_OPM_VERS = 2.0
+CREATION_DATE = 2024-01-15T18:00:00
+ORIGINATOR = OPERATOR-A
+OBJECT_NAME = SAT-ALPHA
+OBJECT_ID = 2020-001A
+CENTER_NAME = EARTH
+REF_FRAME = GCRF
+TIME_SYSTEM = UTC
+COMMENT post-maneuver state
+EPOCH = 2024-01-16T06:10:00.000
+X = 7000.0 [km]
+Y = 0.0 [km]
+Z = 0.0 [km]
+X_DOT = 0.0 [km/s]
+Y_DOT = 7.5465 [km/s]
+Z_DOT = 0.0 [km/s]
+MAN_EPOCH_IGNITION = 2024-01-16T06:00:00.000
+MAN_DURATION = 30.0 [s]
+MAN_DELTA_MASS = -0.1 [kg]
+MAN_REF_FRAME = RTN
+MAN_DV_1 = 0.0 [km/s]
+MAN_DV_2 = 0.0005 [km/s]
+MAN_DV_3 = 0.0001 [km/s]
+";
+
+    #[test]
+    fn test_opm_to_intent() {
+        let opm = parse_opm(OPM).unwrap();
+        assert_eq!(opm.object_id, "2020-001A");
+        assert_eq!(opm.maneuvers.len(), 1);
+
+        let intent = opm
+            .to_intent("MNVR-1".to_string(), Some("NORAD-45000".to_string()), None, ManeuverType::Other)
+            .unwrap();
+        assert_eq!(intent.object_id, "NORAD-45000");
+        assert_eq!(intent.planned_duration_s, 30.0);
+        assert_eq!(intent.predicted_post_maneuver_state.unwrap().reference_frame, "GCRF");
+        // Circular orbit: transverse is along-track, normal is cross-track
+        let dv = intent.delta_v.unwrap();
+        assert!((dv.dv_v_m_s - 0.5).abs() < 1e-9);
+        assert!((dv.dv_n_m_s - 0.1).abs() < 1e-9);
+        assert!(dv.dv_b_m_s.abs() < 1e-9);
+
+        let tnw = parse_opm(&OPM.replace("MAN_REF_FRAME = RTN", "MAN_REF_FRAME = TNW")).unwrap();
+        let dv = tnw.to_intent("MNVR-2".to_string(), None, None, ManeuverType::Other).unwrap().delta_v.unwrap();
+        assert!(dv.dv_v_m_s.abs() < 1e-9 && (dv.dv_b_m_s + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_opm_rejects_unusable_plans() {
+        let intent = |text: &str| parse_opm(text).and_then(|opm| opm.to_intent("M".into(), None, None, ManeuverType::Other));
+
+        let early = OPM.replace("EPOCH = 2024-01-16T06:10:00.000", "EPOCH = 2024-01-16T05:00:00.000");
+        assert!(intent(&early).unwrap_err().to_string().contains("post-maneuver"));
+        let twice = format!("{}MAN_EPOCH_IGNITION = 2024-01-16T06:05:00\nMAN_DURATION = 1\nMAN_REF_FRAME = RTN\nMAN_DV_1 = 0\nMAN_DV_2 = 0\nMAN_DV_3 = 0\n", OPM);
+        assert!(intent(&twice).unwrap_err().to_string().contains("one maneuver"));
+        let frame = OPM.replace("MAN_REF_FRAME = RTN", "MAN_REF_FRAME = ITRF");
+        assert!(intent(&frame).unwrap_err().to_string().contains("MAN_REF_FRAME"));
+        assert!(intent(&OPM.replace("X_DOT", "XDOT")).unwrap_err().to_string().contains("X_DOT is required"));
+        assert!(intent(&OPM.replace("CENTER_NAME = EARTH", "CENTER_NAME = MOON")).is_err());
+    }
+}
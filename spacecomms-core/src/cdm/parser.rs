@@ -1,7 +1,190 @@
 //! CDM parser and validator
+//!
+//! Besides the fixed checks in [`validate_cdm`], each node may declare its
+//! own acceptance criteria as [`ValidationRule`]s in its configuration.
+//! A rule names a CDM field, compares it to a threshold, and warns,
+//! quarantines or rejects when the CDM fails the comparison.
 
-use crate::cdm::CdmRecord;
+use crate::cdm::{check_object_id, normalize_units, score_covariance_quality, CdmRecord};
+use crate::protocol::wildcard_match;
 use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+/// Seconds since the CDM was created, for rules on CDM age
+pub const AGE_SECONDS_FIELD: &str = "age_seconds";
+
+/// Seconds until the CDM's TCA (negative once passed)
+pub const TIME_TO_TCA_SECONDS_FIELD: &str = "time_to_tca_seconds";
+
+/// How a rule compares a field with its threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOperator {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    /// The field equals one of a list
+    In,
+    /// The field equals none of a list
+    NotIn,
+    /// The field is present and not null; takes no threshold
+    Exists,
+    /// The field is a string matching a pattern where `*` is any run
+    Matches,
+}
+
+/// What happens to a CDM failing a rule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    #[default]
+    Reject,
+    /// Accept the CDM and report the failure
+    Warn,
+    /// Hold the CDM, unstored and not passed on, until an admin releases
+    /// or discards it
+    Quarantine,
+}
+
+/// A condition every accepted CDM must meet
+///
+/// `field` is a dotted path into the CDM as JSON (`object1.covariance_rtm`,
+/// `screening_data.hard_body_radius_m`), or one of [`AGE_SECONDS_FIELD`]
+/// and [`TIME_TO_TCA_SECONDS_FIELD`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationRule {
+    /// Shown in reports; `field operator` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub field: String,
+    pub operator: RuleOperator,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<Value>,
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+/// A rule a CDM failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RuleViolation {
+    pub rule: String,
+    pub field: String,
+    pub action: RuleAction,
+    pub message: String,
+}
+
+impl ValidationRule {
+    /// Name shown in reports
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let operator = serde_json::to_value(self.operator).ok();
+            format!("{} {}", self.field, operator.as_ref().and_then(Value::as_str).unwrap_or_default())
+        })
+    }
+
+    /// Check the threshold suits the operator
+    pub fn check(&self) -> std::result::Result<(), String> {
+        if self.field.is_empty() {
+            return Err("field is required".into());
+        }
+        let threshold = self.threshold.as_ref();
+        let suits = match self.operator {
+            RuleOperator::Exists => threshold.is_none(),
+            RuleOperator::Eq | RuleOperator::Ne => threshold.is_some(),
+            RuleOperator::Lt | RuleOperator::Lte | RuleOperator::Gt | RuleOperator::Gte => {
+                threshold.is_some_and(Value::is_number)
+            }
+            RuleOperator::In | RuleOperator::NotIn => threshold.is_some_and(Value::is_array),
+            RuleOperator::Matches => threshold.is_some_and(Value::is_string),
+        };
+        if suits {
+            Ok(())
+        } else {
+            Err(format!("threshold does not suit operator {:?}", self.operator))
+        }
+    }
+
+    /// Why `value` fails the rule, or `None` if it passes
+    fn failure(&self, value: Option<&Value>) -> Option<String> {
+        let Some(value) = value.filter(|v| !v.is_null()) else {
+            return Some(format!("{} is missing", self.field));
+        };
+        let threshold = self.threshold.as_ref().unwrap_or(&Value::Null);
+        let number = |v: &Value| v.as_f64();
+        let equal = |a: &Value, b: &Value| match (number(a), number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        };
+        let compare = |pass: fn(f64, f64) -> bool| match (number(value), number(threshold)) {
+            (Some(a), Some(b)) => pass(a, b),
+            _ => false,
+        };
+        let list = || threshold.as_array().map(Vec::as_slice).unwrap_or_default();
+        let passes = match self.operator {
+            RuleOperator::Exists => true,
+            RuleOperator::Eq => equal(value, threshold),
+            RuleOperator::Ne => !equal(value, threshold),
+            RuleOperator::Lt => compare(|a, b| a < b),
+            RuleOperator::Lte => compare(|a, b| a <= b),
+            RuleOperator::Gt => compare(|a, b| a > b),
+            RuleOperator::Gte => compare(|a, b| a >= b),
+            RuleOperator::In => list().iter().any(|t| equal(value, t)),
+            RuleOperator::NotIn => !list().iter().any(|t| equal(value, t)),
+            RuleOperator::Matches => match (value.as_str(), threshold.as_str()) {
+                (Some(value), Some(pattern)) => wildcard_match(pattern, value),
+                _ => false,
+            },
+        };
+        (!passes).then(|| format!("{} is {}, expected {:?} {}", self.field, value, self.operator, threshold))
+    }
+}
+
+/// Evaluate rules against a CDM, returning every rule it fails
+pub fn evaluate_rules(cdm: &CdmRecord, rules: &[ValidationRule], now: DateTime<Utc>) -> Vec<RuleViolation> {
+    if rules.is_empty() {
+        return Vec::new();
+    }
+    let json = serde_json::to_value(cdm).unwrap_or_default();
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let derived = match rule.field.as_str() {
+                AGE_SECONDS_FIELD => Some(Value::from((now - cdm.creation_date).num_seconds())),
+                TIME_TO_TCA_SECONDS_FIELD => Some(Value::from((cdm.tca - now).num_seconds())),
+                _ => None,
+            };
+            let value = match &derived {
+                Some(value) => Some(value),
+                None => rule.field.split('.').try_fold(&json, |value, key| value.get(key)),
+            };
+            rule.failure(value).map(|message| RuleViolation {
+                rule: rule.display_name(),
+                field: rule.field.clone(),
+                action: rule.action,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Apply rules to a CDM: an error if it fails a rejecting rule, otherwise
+/// the warnings and quarantining failures it raised
+pub fn check_rules(cdm: &CdmRecord, rules: &[ValidationRule], now: DateTime<Utc>) -> Result<Vec<RuleViolation>> {
+    let (rejections, warnings): (Vec<_>, Vec<_>) = evaluate_rules(cdm, rules, now)
+        .into_iter()
+        .partition(|violation| violation.action == RuleAction::Reject);
+    if rejections.is_empty() {
+        return Ok(warnings);
+    }
+    let reasons: Vec<String> = rejections.iter().map(|v| format!("{}: {}", v.rule, v.message)).collect();
+    Err(Error::CdmValidation(format!("rejected by rule {}", reasons.join("; "))))
+}
 
 /// Validate a CDM record
 pub fn validate_cdm(cdm: &CdmRecord) -> Result<()> {
@@ -48,6 +231,9 @@ fn validate_cdm_object(obj: &crate::cdm::CdmObject, field_name: &str) -> Result<
     if obj.object_id.is_empty() {
         return Err(Error::CdmValidation(format!("{}.object_id is required", field_name)));
     }
+    if let Err(reason) = check_object_id(&obj.object_id) {
+        return Err(Error::CdmValidation(format!("{}.object_id {}", field_name, reason)));
+    }
     
     if obj.object_name.is_empty() {
         return Err(Error::CdmValidation(format!("{}.object_name is required", field_name)));
@@ -57,9 +243,15 @@ fn validate_cdm_object(obj: &crate::cdm::CdmObject, field_name: &str) -> Result<
 }
 
 /// Parse CDM from JSON value
-pub fn parse_cdm(value: serde_json::Value) -> Result<CdmRecord> {
-    let cdm: CdmRecord = serde_json::from_value(value)?;
+///
+/// Annotated units are converted first; see [`normalize_units`]. The
+/// quality score is lowered for implausible covariances; see
+/// [`score_covariance_quality`].
+pub fn parse_cdm(mut value: serde_json::Value) -> Result<CdmRecord> {
+    normalize_units(&mut value)?;
+    let mut cdm: CdmRecord = serde_json::from_value(value)?;
     validate_cdm(&cdm)?;
+    score_covariance_quality(&mut cdm);
     Ok(cdm)
 }
 
@@ -88,6 +280,7 @@ mod tests {
                 object_type: ObjectType::Payload,
                 owner_operator: Some("Operator A".to_string()),
                 maneuverable: true,
+                rcs_size: None,
                 state_vector: StateVector {
                     reference_frame: "TEME".to_string(),
                     epoch: Some(now),
@@ -106,6 +299,7 @@ mod tests {
                 object_type: ObjectType::Debris,
                 owner_operator: None,
                 maneuverable: false,
+                rcs_size: None,
                 state_vector: StateVector {
                     reference_frame: "TEME".to_string(),
                     epoch: Some(now),
@@ -127,6 +321,8 @@ mod tests {
             data_quality_score: None,
             conjunction_category: None,
             recommended_action: None,
+            organization: None,
+            involves_watched_asset: false,
         }
     }
 
@@ -156,4 +352,69 @@ mod tests {
         cdm.tca = cdm.creation_date - chrono::Duration::hours(1);
         assert!(validate_cdm(&cdm).is_err());
     }
+
+    #[test]
+    fn test_parse_cdm_tolerant_timestamps() {
+        let mut value = serde_json::to_value(create_test_cdm()).unwrap();
+        value["creation_date"] = "2024-01-15 10:00:00".into();
+        value["tca"] = "2024-017T08:30:00.250".into();
+
+        let cdm = parse_cdm(value).unwrap();
+        assert_eq!(cdm.creation_date.to_rfc3339(), "2024-01-15T10:00:00+00:00");
+        assert_eq!(cdm.tca.to_rfc3339(), "2024-01-17T08:30:00.250+00:00");
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let rules: Vec<ValidationRule> = serde_yaml::from_str(
+            "
+            - { name: fresh, field: age_seconds, operator: lte, threshold: 86400 }
+            - { field: originator, operator: in, threshold: [TEST-PROVIDER, LEOLABS] }
+            - { field: object1.covariance_rtm, operator: exists, action: warn }
+            - { field: screening_data.hard_body_radius_m, operator: gte, threshold: 5, action: warn }
+            ",
+        )
+        .unwrap();
+        assert!(rules.iter().all(|rule| rule.check().is_ok()));
+        let cdm = create_test_cdm();
+        let now = cdm.creation_date + chrono::Duration::hours(1);
+
+        // Accepted, with the missing covariance reported
+        let warnings = check_rules(&cdm, &rules, now).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].rule, "object1.covariance_rtm exists");
+        assert_eq!(warnings[0].action, RuleAction::Warn);
+
+        // Too old, and from an originator not on the list
+        let mut stale = cdm.clone();
+        stale.originator = "OTHER".into();
+        let later = now + chrono::Duration::days(2);
+        let error = check_rules(&stale, &rules, later).unwrap_err().to_string();
+        assert!(error.contains("fresh: age_seconds") && error.contains("originator in"), "{}", error);
+
+        // Soft rules hold the CDM instead
+        let soft: Vec<ValidationRule> = rules
+            .iter()
+            .cloned()
+            .map(|rule| match rule.action {
+                RuleAction::Reject => ValidationRule {
+                    action: RuleAction::Quarantine,
+                    ..rule
+                },
+                _ => rule,
+            })
+            .collect();
+        let violations = check_rules(&stale, &soft, later).unwrap();
+        let held = violations.iter().filter(|v| v.action == RuleAction::Quarantine).count();
+        assert_eq!((violations.len(), held), (3, 2));
+
+        let mismatched = ValidationRule {
+            name: None,
+            field: "miss_distance_m".into(),
+            operator: RuleOperator::Lt,
+            threshold: Some("far".into()),
+            action: RuleAction::Reject,
+        };
+        assert!(mismatched.check().is_err());
+    }
 }
@@ -0,0 +1,470 @@
+//! Collision probability (Pc) computation
+//!
+//! All built-in methods use the short-encounter model. Both objects'
+//! position covariances are summed and projected onto the encounter plane,
+//! which is perpendicular to the relative velocity at TCA. Pc is then the
+//! probability mass of that 2D Gaussian inside the combined hard-body disc.
+//! Covariances are in km² and are assumed to share one RTN frame, which is
+//! accurate for the close approaches CDMs describe.
+
+use crate::cdm::CdmRecord;
+use crate::orbit::{cross, dot, norm, scale, sub, Vec3};
+use crate::protocol::CovarianceRtn;
+use crate::{Error, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Combined hard-body radius used when the CDM does not carry one
+pub const DEFAULT_HARD_BODY_RADIUS_M: f64 = 20.0;
+
+/// Conjunction geometry in the encounter plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncounterGeometry {
+    /// Miss distance along the plane's x axis in meters (y component is zero)
+    pub miss_m: f64,
+    /// Combined position covariance in the plane (m²): xx, xy, yy
+    pub covariance_m2: [f64; 3],
+    /// Combined hard-body radius in meters
+    pub hard_body_radius_m: f64,
+}
+
+impl EncounterGeometry {
+    /// Build the encounter geometry from a CDM's relative state and covariances
+    pub fn from_cdm(cdm: &CdmRecord) -> Result<Self> {
+        let relative = cdm
+            .relative_state
+            .as_ref()
+            .ok_or_else(|| Error::CdmValidation("relative_state is required to compute Pc".into()))?;
+        let (c1, c2) = match (&cdm.object1.covariance_rtm, &cdm.object2.covariance_rtm) {
+            (Some(c1), Some(c2)) => (c1, c2),
+            _ => return Err(Error::CdmValidation("both object covariances are required to compute Pc".into())),
+        };
+
+        let r = [
+            relative.relative_position_r_m,
+            relative.relative_position_t_m,
+            relative.relative_position_n_m,
+        ];
+        let v = [
+            relative.relative_velocity_r_m_s,
+            relative.relative_velocity_t_m_s,
+            relative.relative_velocity_n_m_s,
+        ];
+        let speed = norm(v);
+        if speed == 0.0 {
+            return Err(Error::CdmValidation("relative velocity must be non-zero".into()));
+        }
+        let v_hat = scale(v, 1.0 / speed);
+
+        // x axis along the miss vector projected onto the plane, y completes the basis
+        let r_plane = sub(r, scale(v_hat, dot(r, v_hat)));
+        let miss_m = norm(r_plane);
+        let x_hat = if miss_m > 0.0 {
+            scale(r_plane, 1.0 / miss_m)
+        } else {
+            any_perpendicular(v_hat)
+        };
+        let y_hat = cross(v_hat, x_hat);
+
+        let combined = add_matrix(to_matrix_m2(c1), to_matrix_m2(c2));
+        let hard_body_radius_m = cdm
+            .screening_data
+            .as_ref()
+            .and_then(|s| s.hard_body_radius_m)
+            .unwrap_or(DEFAULT_HARD_BODY_RADIUS_M);
+
+        Ok(Self {
+            miss_m,
+            covariance_m2: [
+                quadratic(&combined, x_hat, x_hat),
+                quadratic(&combined, x_hat, y_hat),
+                quadratic(&combined, y_hat, y_hat),
+            ],
+            hard_body_radius_m,
+        })
+    }
+
+    /// Principal standard deviations and the miss vector in principal axes
+    fn principal(&self) -> Result<([f64; 2], [f64; 2])> {
+        let [xx, xy, yy] = self.covariance_m2;
+        let mean = (xx + yy) / 2.0;
+        let spread = (((xx - yy) / 2.0).powi(2) + xy * xy).sqrt();
+        let (l1, l2) = (mean + spread, mean - spread);
+        if l2 <= 0.0 || !l2.is_finite() {
+            return Err(Error::CdmValidation("encounter-plane covariance is not positive definite".into()));
+        }
+        let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+        let (sin, cos) = angle.sin_cos();
+        Ok(([l1.sqrt(), l2.sqrt()], [self.miss_m * cos, -self.miss_m * sin]))
+    }
+}
+
+/// A collision probability algorithm
+pub trait PcMethod: Send + Sync {
+    /// Method name used in configuration, requests and results
+    fn name(&self) -> &'static str;
+
+    /// Compute Pc for an encounter
+    fn compute(&self, geometry: &EncounterGeometry) -> Result<f64>;
+}
+
+/// Pc labeled with the method that produced it
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PcResult {
+    pub method: String,
+    pub pc: f64,
+}
+
+/// Foster (1992): direct numerical integration over the hard-body disc
+pub struct Foster;
+
+impl PcMethod for Foster {
+    fn name(&self) -> &'static str {
+        "foster"
+    }
+
+    fn compute(&self, geometry: &EncounterGeometry) -> Result<f64> {
+        const RADIAL_STEPS: usize = 64;
+        const ANGULAR_STEPS: usize = 128;
+        let ([sx, sy], [mx, my]) = geometry.principal()?;
+        let radius = geometry.hard_body_radius_m;
+        let dr = radius / RADIAL_STEPS as f64;
+        let dtheta = 2.0 * PI / ANGULAR_STEPS as f64;
+
+        // Midpoint rule in polar coordinates around the disc centre
+        let mut sum = 0.0;
+        for i in 0..RADIAL_STEPS {
+            let rho = (i as f64 + 0.5) * dr;
+            for j in 0..ANGULAR_STEPS {
+                let theta = (j as f64 + 0.5) * dtheta;
+                let x = rho * theta.cos() - mx;
+                let y = rho * theta.sin() - my;
+                sum += (-0.5 * ((x / sx).powi(2) + (y / sy).powi(2))).exp() * rho;
+            }
+        }
+        Ok(sum * dr * dtheta / (2.0 * PI * sx * sy))
+    }
+}
+
+/// Chan (1997): series expansion of the equivalent circular distribution
+///
+/// Exact for circular covariance; approximate as the aspect ratio grows.
+pub struct Chan;
+
+impl PcMethod for Chan {
+    fn name(&self) -> &'static str {
+        "chan"
+    }
+
+    fn compute(&self, geometry: &EncounterGeometry) -> Result<f64> {
+        let ([sx, sy], [mx, my]) = geometry.principal()?;
+        let u = geometry.hard_body_radius_m.powi(2) / (sx * sy);
+        let v = (mx / sx).powi(2) + (my / sy).powi(2);
+
+        // Pc = e^(-v/2) Σ (v/2)^m/m! · (1 - e^(-u/2) Σ_{k≤m} (u/2)^k/k!)
+        let (half_u, half_v) = (u / 2.0, v / 2.0);
+        let mut v_term = (-half_v).exp();
+        let mut u_term = (-half_u).exp();
+        let mut u_partial = u_term;
+        let mut pc = 0.0;
+        for m in 0..500 {
+            if m > 0 {
+                v_term *= half_v / m as f64;
+                u_term *= half_u / m as f64;
+                u_partial += u_term;
+            }
+            let term = v_term * (1.0 - u_partial).max(0.0);
+            pc += term;
+            if m as f64 > half_v && term < pc * 1e-12 {
+                break;
+            }
+        }
+        Ok(pc)
+    }
+}
+
+/// Alfano (2005): one-dimensional integral of error functions across the disc
+pub struct Alfano;
+
+impl PcMethod for Alfano {
+    fn name(&self) -> &'static str {
+        "alfano"
+    }
+
+    fn compute(&self, geometry: &EncounterGeometry) -> Result<f64> {
+        const STEPS: usize = 400;
+        let ([sx, sy], [mx, my]) = geometry.principal()?;
+        let radius = geometry.hard_body_radius_m;
+        let h = 2.0 * radius / STEPS as f64;
+
+        // Simpson's rule over x in [-R, R]
+        let f = |x: f64| {
+            let half_chord = (radius * radius - x * x).max(0.0).sqrt();
+            let a = (half_chord - my) / (std::f64::consts::SQRT_2 * sy);
+            let b = (half_chord + my) / (std::f64::consts::SQRT_2 * sy);
+            erf_sum(a, b) * (-0.5 * ((x - mx) / sx).powi(2)).exp()
+        };
+        let mut sum = f(-radius) + f(radius);
+        for i in 1..STEPS {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            sum += weight * f(-radius + i as f64 * h);
+        }
+        Ok(sum * h / 3.0 / ((8.0 * PI).sqrt() * sx))
+    }
+}
+
+/// Monte Carlo sampling of the encounter-plane distribution
+pub struct MonteCarlo {
+    /// Number of samples
+    pub samples: usize,
+    /// RNG seed, fixed so repeated computations agree
+    pub seed: u64,
+}
+
+impl Default for MonteCarlo {
+    fn default() -> Self {
+        Self {
+            samples: 200_000,
+            seed: 0x5ace,
+        }
+    }
+}
+
+impl PcMethod for MonteCarlo {
+    fn name(&self) -> &'static str {
+        "monte_carlo"
+    }
+
+    fn compute(&self, geometry: &EncounterGeometry) -> Result<f64> {
+        let ([sx, sy], [mx, my]) = geometry.principal()?;
+        let r2 = geometry.hard_body_radius_m.powi(2);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let hits = (0..self.samples)
+            .filter(|_| {
+                let x = mx + sx * standard_normal(&mut rng);
+                let y = my + sy * standard_normal(&mut rng);
+                x * x + y * y <= r2
+            })
+            .count();
+        Ok(hits as f64 / self.samples as f64)
+    }
+}
+
+/// Registry of available Pc methods with a node default
+#[derive(Clone)]
+pub struct PcMethods {
+    methods: BTreeMap<&'static str, Arc<dyn PcMethod>>,
+    default: &'static str,
+}
+
+impl PcMethods {
+    /// Names of the built-in methods
+    pub const BUILTIN: [&'static str; 4] = ["foster", "chan", "alfano", "monte_carlo"];
+
+    /// Built-in methods with `default` as the node default
+    pub fn new(default: &str) -> Result<Self> {
+        let mut methods = Self {
+            methods: BTreeMap::new(),
+            default: "foster",
+        };
+        methods.register(Arc::new(Foster));
+        methods.register(Arc::new(Chan));
+        methods.register(Arc::new(Alfano));
+        methods.register(Arc::new(MonteCarlo::default()));
+        methods.set_default(default)?;
+        Ok(methods)
+    }
+
+    /// Add or replace a method
+    pub fn register(&mut self, method: Arc<dyn PcMethod>) {
+        self.methods.insert(method.name(), method);
+    }
+
+    /// Select the default method
+    pub fn set_default(&mut self, name: &str) -> Result<()> {
+        let (&key, _) = self
+            .methods
+            .get_key_value(name)
+            .ok_or_else(|| Error::Config(format!("unknown Pc method: {}", name)))?;
+        self.default = key;
+        Ok(())
+    }
+
+    /// Name of the default method
+    pub fn default_method(&self) -> &str {
+        self.default
+    }
+
+    /// Compute Pc with the named method, or the default
+    pub fn compute(&self, cdm: &CdmRecord, method: Option<&str>) -> Result<PcResult> {
+        let name = method.unwrap_or(self.default);
+        let method = self
+            .methods
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("Pc method {}", name)))?;
+        let geometry = EncounterGeometry::from_cdm(cdm)?;
+        Ok(PcResult {
+            method: name.to_string(),
+            pc: method.compute(&geometry)?,
+        })
+    }
+
+    /// Compute Pc with every registered method
+    pub fn compare(&self, cdm: &CdmRecord) -> Result<Vec<PcResult>> {
+        let geometry = EncounterGeometry::from_cdm(cdm)?;
+        self.methods
+            .iter()
+            .map(|(name, method)| {
+                Ok(PcResult {
+                    method: name.to_string(),
+                    pc: method.compute(&geometry)?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for PcMethods {
+    fn default() -> Self {
+        Self::new("foster").expect("foster is built in")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Numerics
+// ----------------------------------------------------------------------------
+
+type Mat3 = [[f64; 3]; 3];
+
+fn any_perpendicular(v: Vec3) -> Vec3 {
+    let axis = if v[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let p = cross(v, axis);
+    scale(p, 1.0 / norm(p))
+}
+
+/// Covariance in km² as a matrix in m²
+fn to_matrix_m2(c: &CovarianceRtn) -> Mat3 {
+    let k = 1.0e6;
+    [
+        [c.cr_r * k, c.ct_r * k, c.cn_r * k],
+        [c.ct_r * k, c.ct_t * k, c.cn_t * k],
+        [c.cn_r * k, c.cn_t * k, c.cn_n * k],
+    ]
+}
+
+fn add_matrix(a: Mat3, b: Mat3) -> Mat3 {
+    let mut m = a;
+    for (row, b_row) in m.iter_mut().zip(b) {
+        for (x, y) in row.iter_mut().zip(b_row) {
+            *x += y;
+        }
+    }
+    m
+}
+
+/// aᵀ M b
+fn quadratic(m: &Mat3, a: Vec3, b: Vec3) -> f64 {
+    (0..3).map(|i| (0..3).map(|j| a[i] * m[i][j] * b[j]).sum::<f64>()).sum()
+}
+
+/// Complementary error function (fractional error below 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// erf(a) + erf(b) without cancellation when the terms nearly offset
+fn erf_sum(a: f64, b: f64) -> f64 {
+    match (a >= 0.0, b >= 0.0) {
+        (true, true) => 2.0 - erfc(a) - erfc(b),
+        (false, false) => erfc(-a) + erfc(-b) - 2.0,
+        (true, false) => erfc(-b) - erfc(a),
+        (false, true) => erfc(-a) - erfc(b),
+    }
+}
+
+/// Standard normal sample via the Box-Muller transform
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn geometry(miss_m: f64, sx: f64, sy: f64, radius: f64) -> EncounterGeometry {
+        EncounterGeometry {
+            miss_m,
+            covariance_m2: [sx * sx, 0.0, sy * sy],
+            hard_body_radius_m: radius,
+        }
+    }
+
+    #[test]
+    fn test_methods_agree() {
+        let cases = [
+            geometry(0.0, 100.0, 100.0, 20.0),
+            geometry(150.0, 200.0, 50.0, 15.0),
+            geometry(1000.0, 300.0, 80.0, 10.0),
+        ];
+        for g in cases {
+            let foster = Foster.compute(&g).unwrap();
+            // Chan's series is exact only for circular covariance
+            for (method, tolerance) in [(&Chan as &dyn PcMethod, 2e-2), (&Alfano, 1e-3)] {
+                let pc = method.compute(&g).unwrap();
+                assert!((pc - foster).abs() <= foster * tolerance, "{} {} vs {}", method.name(), pc, foster);
+            }
+            let mc = MonteCarlo::default().compute(&g).unwrap();
+            let sigma = (foster / MonteCarlo::default().samples as f64).sqrt();
+            assert!((mc - foster).abs() <= 4.0 * sigma + 1e-6, "mc {} vs {}", mc, foster);
+        }
+    }
+
+    #[test]
+    fn test_circular_closed_form() {
+        // Zero miss, circular covariance: Pc = 1 - exp(-R²/2σ²)
+        let g = geometry(0.0, 100.0, 100.0, 20.0);
+        let expected = 1.0 - (-(20.0f64 * 20.0) / (2.0 * 100.0 * 100.0)).exp();
+        assert!((Chan.compute(&g).unwrap() - expected).abs() < 1e-9);
+        assert!((Foster.compute(&g).unwrap() - expected).abs() < expected * 1e-3);
+    }
+
+    #[test]
+    fn test_registry() {
+        let cdm = generate_demo_cdm();
+        let methods = PcMethods::new("chan").unwrap();
+        assert_eq!(methods.default_method(), "chan");
+        assert_eq!(methods.compute(&cdm, None).unwrap().method, "chan");
+        assert_eq!(methods.compute(&cdm, Some("alfano")).unwrap().method, "alfano");
+        assert!(methods.compute(&cdm, Some("magic")).is_err());
+        assert!(PcMethods::new("magic").is_err());
+
+        let results = methods.compare(&cdm).unwrap();
+        assert_eq!(results.len(), PcMethods::BUILTIN.len());
+        assert!(results.iter().all(|r| (0.0..=1.0).contains(&r.pc)));
+
+        let mut missing = cdm;
+        missing.object2.covariance_rtm = None;
+        assert!(matches!(methods.compare(&missing), Err(Error::CdmValidation(_))));
+    }
+}
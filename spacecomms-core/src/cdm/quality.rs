@@ -0,0 +1,134 @@
+//! Covariance sanity checks and data quality scoring
+//!
+//! A covariance that is not positive definite, or whose position sigmas are
+//! implausibly small or large, yields a misleading Pc. Such CDMs are kept,
+//! but their `data_quality_score` is lowered so fusion and operators weigh
+//! them less, and a node may refuse CDMs scoring below a floor.
+
+use crate::cdm::CdmRecord;
+use crate::protocol::CovarianceRtn;
+use crate::{Error, Result};
+use serde::Serialize;
+
+/// Smallest plausible position variance, km² (a 1 cm sigma)
+pub const MIN_POSITION_VARIANCE_KM2: f64 = 1.0e-10;
+
+/// Largest plausible position variance, km² (a 1000 km sigma)
+pub const MAX_POSITION_VARIANCE_KM2: f64 = 1.0e6;
+
+/// Score factor for a covariance that is not positive definite
+const NOT_POSITIVE_DEFINITE_FACTOR: f64 = 0.1;
+
+/// Score factor for a covariance with implausible magnitudes
+const IMPLAUSIBLE_MAGNITUDE_FACTOR: f64 = 0.5;
+
+/// Something wrong with a position covariance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CovarianceIssue {
+    /// Not symmetric positive definite, or not finite
+    NotPositiveDefinite,
+    /// A variance below [`MIN_POSITION_VARIANCE_KM2`]
+    TooSmall,
+    /// A variance above [`MAX_POSITION_VARIANCE_KM2`]
+    TooLarge,
+}
+
+impl CovarianceIssue {
+    fn factor(self) -> f64 {
+        match self {
+            CovarianceIssue::NotPositiveDefinite => NOT_POSITIVE_DEFINITE_FACTOR,
+            CovarianceIssue::TooSmall | CovarianceIssue::TooLarge => IMPLAUSIBLE_MAGNITUDE_FACTOR,
+        }
+    }
+}
+
+/// Check a position covariance, returning its issues
+pub fn check_covariance(covariance: &CovarianceRtn) -> Vec<CovarianceIssue> {
+    let c = covariance;
+    let entries = [c.cr_r, c.ct_r, c.ct_t, c.cn_r, c.cn_t, c.cn_n];
+    if !entries.iter().all(|v| v.is_finite()) {
+        return vec![CovarianceIssue::NotPositiveDefinite];
+    }
+
+    let mut issues = Vec::new();
+    // Sylvester's criterion: every leading principal minor is positive
+    let minor2 = c.cr_r * c.ct_t - c.ct_r * c.ct_r;
+    let minor3 = c.cr_r * (c.ct_t * c.cn_n - c.cn_t * c.cn_t) - c.ct_r * (c.ct_r * c.cn_n - c.cn_t * c.cn_r)
+        + c.cn_r * (c.ct_r * c.cn_t - c.ct_t * c.cn_r);
+    if c.cr_r <= 0.0 || minor2 <= 0.0 || minor3 <= 0.0 {
+        issues.push(CovarianceIssue::NotPositiveDefinite);
+    }
+    let variances = [c.cr_r, c.ct_t, c.cn_n];
+    if variances.iter().any(|&v| v > 0.0 && v < MIN_POSITION_VARIANCE_KM2) {
+        issues.push(CovarianceIssue::TooSmall);
+    }
+    if variances.iter().any(|&v| v > MAX_POSITION_VARIANCE_KM2) {
+        issues.push(CovarianceIssue::TooLarge);
+    }
+    issues
+}
+
+/// Lower the CDM's quality score for each issue in its covariances
+///
+/// A CDM without a score starts from 1.0. CDMs whose covariances pass, or
+/// that carry none, are left as they are. Returns the issues found.
+pub fn score_covariance_quality(cdm: &mut CdmRecord) -> Vec<CovarianceIssue> {
+    let issues: Vec<CovarianceIssue> = [&cdm.object1, &cdm.object2]
+        .into_iter()
+        .filter_map(|object| object.covariance_rtm.as_ref())
+        .flat_map(check_covariance)
+        .collect();
+    if !issues.is_empty() {
+        let factor: f64 = issues.iter().map(|issue| issue.factor()).product();
+        let score = cdm.data_quality_score.unwrap_or(1.0).clamp(0.0, 1.0);
+        cdm.data_quality_score = Some(score * factor);
+    }
+    issues
+}
+
+/// Refuse a CDM scoring below `min_score` (0 accepts every CDM)
+///
+/// CDMs without a score are accepted.
+pub fn check_quality_floor(cdm: &CdmRecord, min_score: f64) -> Result<()> {
+    match cdm.data_quality_score {
+        Some(score) if score < min_score => Err(Error::CdmValidation(format!(
+            "data_quality_score {:.3} is below the node's minimum of {:.3}",
+            score, min_score
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[test]
+    fn test_covariance_quality() {
+        let mut cdm = generate_demo_cdm();
+        cdm.data_quality_score = Some(0.9);
+        assert!(score_covariance_quality(&mut cdm).is_empty());
+        assert_eq!(cdm.data_quality_score, Some(0.9));
+
+        // Correlation above 1 between R and T
+        let covariance = cdm.object1.covariance_rtm.as_mut().unwrap();
+        covariance.ct_r = 2.0e-4;
+        assert_eq!(check_covariance(covariance), [CovarianceIssue::NotPositiveDefinite]);
+        // A 1 mm sigma on the other object
+        cdm.object2.covariance_rtm.as_mut().unwrap().cn_n = 1.0e-12;
+        let issues = score_covariance_quality(&mut cdm);
+        assert_eq!(issues, [CovarianceIssue::NotPositiveDefinite, CovarianceIssue::TooSmall]);
+        assert!((cdm.data_quality_score.unwrap() - 0.9 * 0.1 * 0.5).abs() < 1e-12);
+
+        assert!(check_quality_floor(&cdm, 0.0).is_ok());
+        assert!(check_quality_floor(&cdm, 0.5).is_err());
+
+        let mut unscored = generate_demo_cdm();
+        unscored.data_quality_score = None;
+        unscored.object1.covariance_rtm.as_mut().unwrap().cr_r = f64::NAN;
+        score_covariance_quality(&mut unscored);
+        assert_eq!(unscored.data_quality_score, Some(0.1));
+    }
+}
@@ -0,0 +1,85 @@
+//! Conjunction severity classification
+
+use crate::cdm::{CdmRecord, ConjunctionCategory, RecommendedAction};
+use crate::config::SeverityConfig;
+
+/// Category for a conjunction under the given thresholds
+pub fn conjunction_category(cdm: &CdmRecord, thresholds: &SeverityConfig) -> ConjunctionCategory {
+    categorize(cdm.collision_probability, cdm.miss_distance_m, thresholds)
+}
+
+/// Category for a collision probability and miss distance in meters
+pub fn categorize(pc: f64, miss: f64, thresholds: &SeverityConfig) -> ConjunctionCategory {
+    if pc >= thresholds.high_probability || miss <= thresholds.high_miss_distance_m {
+        ConjunctionCategory::High
+    } else if pc >= thresholds.medium_probability || miss <= thresholds.medium_miss_distance_m {
+        ConjunctionCategory::Medium
+    } else {
+        ConjunctionCategory::Low
+    }
+}
+
+/// Operator response suggested for a category
+pub fn recommended_action(category: &ConjunctionCategory) -> RecommendedAction {
+    match category {
+        ConjunctionCategory::High => RecommendedAction::Maneuver,
+        ConjunctionCategory::Medium => RecommendedAction::Prepare,
+        ConjunctionCategory::Low => RecommendedAction::Monitor,
+    }
+}
+
+/// Fill in whichever of category and recommended action the originator left out
+///
+/// Values supplied by the originator are kept. A missing action follows the
+/// CDM's category, supplied or computed.
+pub fn classify(cdm: &mut CdmRecord, thresholds: &SeverityConfig) {
+    let category = match &cdm.conjunction_category {
+        Some(category) => category.clone(),
+        None => {
+            let category = conjunction_category(cdm, thresholds);
+            cdm.conjunction_category = Some(category.clone());
+            category
+        }
+    };
+    if cdm.recommended_action.is_none() {
+        cdm.recommended_action = Some(recommended_action(&category));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[test]
+    fn test_classify() {
+        let thresholds = SeverityConfig::default();
+        let mut cdm = generate_demo_cdm();
+        cdm.conjunction_category = None;
+        cdm.recommended_action = None;
+        cdm.miss_distance_m = 5000.0;
+
+        for (pc, category, action) in [
+            (1e-3, ConjunctionCategory::High, RecommendedAction::Maneuver),
+            (5e-5, ConjunctionCategory::Medium, RecommendedAction::Prepare),
+            (1e-7, ConjunctionCategory::Low, RecommendedAction::Monitor),
+        ] {
+            let mut cdm = cdm.clone();
+            cdm.collision_probability = pc;
+            classify(&mut cdm, &thresholds);
+            assert_eq!(cdm.conjunction_category, Some(category));
+            assert_eq!(cdm.recommended_action, Some(action));
+        }
+
+        // A close approach is HIGH even with a low probability
+        cdm.collision_probability = 1e-7;
+        cdm.miss_distance_m = 50.0;
+        assert_eq!(conjunction_category(&cdm, &thresholds), ConjunctionCategory::High);
+
+        // Originator-supplied values win
+        cdm.conjunction_category = Some(ConjunctionCategory::Low);
+        classify(&mut cdm, &thresholds);
+        assert_eq!(cdm.conjunction_category, Some(ConjunctionCategory::Low));
+        assert_eq!(cdm.recommended_action, Some(RecommendedAction::Monitor));
+    }
+}
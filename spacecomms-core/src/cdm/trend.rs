@@ -0,0 +1,168 @@
+//! Risk trend of a conjunction across CDM updates
+//!
+//! Providers reissue CDMs for a conjunction as tracking improves, and the
+//! collision probability across those updates often rises or falls steadily.
+//! The trend lists every CDM of the conjunction, withdrawn and superseded
+//! ones included, in creation order. Its direction compares the newest CDM
+//! with the one before it from the same originator, or from anyone when the
+//! originator has only one, so a change of provider is not read as a change
+//! of risk unless nothing better is available.
+
+use crate::cdm::CdmRecord;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Which way the collision probability is moving
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendDirection {
+    /// Grew by at least the configured factor
+    Increasing,
+    /// Shrank by at least the configured factor
+    Decreasing,
+    Steady,
+    /// Only one CDM so far
+    Unknown,
+}
+
+/// One CDM in a conjunction's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TrendPoint {
+    pub cdm_id: String,
+    pub originator: String,
+    pub creation_date: DateTime<Utc>,
+    pub tca: DateTime<Utc>,
+    pub collision_probability: f64,
+    pub miss_distance_m: f64,
+    /// Withdrawn or superseded since
+    pub withdrawn: bool,
+}
+
+impl TrendPoint {
+    pub fn new(cdm: &CdmRecord, withdrawn: bool) -> Self {
+        Self {
+            cdm_id: cdm.cdm_id.clone(),
+            originator: cdm.originator.clone(),
+            creation_date: cdm.creation_date,
+            tca: cdm.tca,
+            collision_probability: cdm.collision_probability,
+            miss_distance_m: cdm.miss_distance_m,
+            withdrawn,
+        }
+    }
+}
+
+/// How a conjunction's risk evolved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RiskTrend {
+    /// Oldest CDM first
+    pub points: Vec<TrendPoint>,
+    pub direction: TrendDirection,
+    /// CDM the newest one was compared with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compared_with: Option<String>,
+    /// Newest probability over the compared one; absent when that was zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_ratio: Option<f64>,
+    /// Change in miss distance, negative when the objects come closer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub miss_distance_change_m: Option<f64>,
+    /// Flagged for escalation
+    pub risk_increasing: bool,
+}
+
+impl RiskTrend {
+    /// Trend over a conjunction's CDMs; `factor` (above 1) is the change in
+    /// probability that counts as a rise or fall
+    pub fn new(mut points: Vec<TrendPoint>, factor: f64) -> Self {
+        points.sort_by(|a, b| a.creation_date.cmp(&b.creation_date).then_with(|| a.cdm_id.cmp(&b.cdm_id)));
+        let comparison = points.split_last().and_then(|(newest, earlier)| {
+            let previous = earlier
+                .iter()
+                .rev()
+                .find(|p| p.originator == newest.originator)
+                .or_else(|| earlier.last())?;
+            Some((newest, previous))
+        });
+
+        let Some((newest, previous)) = comparison else {
+            return Self {
+                points,
+                direction: TrendDirection::Unknown,
+                compared_with: None,
+                pc_ratio: None,
+                miss_distance_change_m: None,
+                risk_increasing: false,
+            };
+        };
+        let (pc, before) = (newest.collision_probability, previous.collision_probability);
+        let direction = if before <= 0.0 {
+            if pc > 0.0 {
+                TrendDirection::Increasing
+            } else {
+                TrendDirection::Steady
+            }
+        } else if pc >= before * factor {
+            TrendDirection::Increasing
+        } else if pc * factor <= before {
+            TrendDirection::Decreasing
+        } else {
+            TrendDirection::Steady
+        };
+        let compared_with = Some(previous.cdm_id.clone());
+        let pc_ratio = (before > 0.0).then(|| pc / before);
+        let miss_distance_change_m = Some(newest.miss_distance_m - previous.miss_distance_m);
+        Self {
+            points,
+            direction,
+            compared_with,
+            pc_ratio,
+            miss_distance_change_m,
+            risk_increasing: direction == TrendDirection::Increasing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use chrono::Duration;
+
+    fn point(id: &str, originator: &str, pc: f64, hour: i64) -> TrendPoint {
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = id.into();
+        cdm.originator = originator.into();
+        cdm.collision_probability = pc;
+        cdm.creation_date = cdm.tca - Duration::hours(48 - hour);
+        TrendPoint::new(&cdm, false)
+    }
+
+    #[test]
+    fn test_risk_trend() {
+        let single = RiskTrend::new(vec![point("A", "P1", 1e-5, 0)], 2.0);
+        assert_eq!((single.direction, single.risk_increasing), (TrendDirection::Unknown, false));
+
+        // Out of order; P1 went from 1e-5 to 4e-5, P2's lower figure in
+        // between does not count against it
+        let rising = RiskTrend::new(
+            vec![point("C", "P1", 4e-5, 2), point("A", "P1", 1e-5, 0), point("B", "P2", 1e-6, 1)],
+            2.0,
+        );
+        let ids: Vec<&str> = rising.points.iter().map(|p| p.cdm_id.as_str()).collect();
+        assert_eq!(ids, ["A", "B", "C"]);
+        assert_eq!(rising.direction, TrendDirection::Increasing);
+        assert_eq!(rising.compared_with.as_deref(), Some("A"));
+        assert!((rising.pc_ratio.unwrap() - 4.0).abs() < 1e-9);
+        assert!(rising.risk_increasing);
+
+        let falling = RiskTrend::new(vec![point("A", "P1", 1e-4, 0), point("B", "P1", 4e-5, 1)], 2.0);
+        assert_eq!(falling.direction, TrendDirection::Decreasing);
+        let steady = RiskTrend::new(vec![point("A", "P1", 1e-4, 0), point("B", "P1", 1.5e-4, 1)], 2.0);
+        assert_eq!(steady.direction, TrendDirection::Steady);
+        // A newcomer is compared with whoever came before
+        let newcomer = RiskTrend::new(vec![point("A", "P1", 0.0, 0), point("B", "P2", 1e-6, 1)], 2.0);
+        assert_eq!((newcomer.direction, newcomer.pc_ratio), (TrendDirection::Increasing, None));
+    }
+}
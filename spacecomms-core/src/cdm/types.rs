@@ -1,16 +1,18 @@
 //! CDM types aligned with CCSDS 508.0-B-1
 
-use crate::protocol::{CovarianceRtn, ObjectType, StateVector};
+use crate::protocol::{CovarianceRtn, ObjectType, RcsSize, StateVector};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// Conjunction Data Message record
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CdmRecord {
     /// Unique CDM identifier
     pub cdm_id: String,
     
     /// Creation timestamp
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub creation_date: DateTime<Utc>,
     
     /// Originator (STM provider)
@@ -20,6 +22,7 @@ pub struct CdmRecord {
     pub message_for: String,
     
     /// Time of closest approach
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub tca: DateTime<Utc>,
     
     /// Miss distance in meters
@@ -54,10 +57,18 @@ pub struct CdmRecord {
     /// Suggested operator response
     #[serde(skip_serializing_if = "Option::is_none")]
     pub recommended_action: Option<RecommendedAction>,
+
+    /// Organization on this node the CDM belongs to; local, not forwarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+
+    /// Either object is on this node's watchlist; local, not forwarded
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub involves_watched_asset: bool,
 }
 
 /// Object within a CDM
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CdmObject {
     /// Object identifier (e.g., NORAD ID)
     pub object_id: String,
@@ -75,6 +86,10 @@ pub struct CdmObject {
     /// Whether object can maneuver
     #[serde(default)]
     pub maneuverable: bool,
+
+    /// Radar cross-section size class
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcs_size: Option<RcsSize>,
     
     /// State vector at TCA
     pub state_vector: StateVector,
@@ -85,7 +100,7 @@ pub struct CdmObject {
 }
 
 /// Relative state at TCA
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RelativeState {
     /// Relative position in radial direction (meters)
     pub relative_position_r_m: f64,
@@ -107,7 +122,7 @@ pub struct RelativeState {
 }
 
 /// Screening configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ScreeningData {
     /// Type of screening performed
     pub screen_type: ScreenType,
@@ -122,7 +137,7 @@ pub struct ScreeningData {
 }
 
 /// Screening type
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ScreenType {
     Routine,
@@ -131,7 +146,7 @@ pub enum ScreenType {
 }
 
 /// Conjunction category (TraCSS extension)
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ConjunctionCategory {
     High,
@@ -140,7 +155,7 @@ pub enum ConjunctionCategory {
 }
 
 /// Recommended action (TraCSS extension)
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RecommendedAction {
     Monitor,
@@ -149,7 +164,7 @@ pub enum RecommendedAction {
 }
 
 /// Object record for tracking
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ObjectRecord {
     /// Object identifier
     pub object_id: String,
@@ -164,7 +179,12 @@ pub struct ObjectRecord {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_operator: Option<String>,
     
+    /// Radar cross-section size class
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcs_size: Option<RcsSize>,
+
     /// State vector epoch
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub epoch: DateTime<Utc>,
     
     /// Current state vector
@@ -178,5 +198,10 @@ pub struct ObjectRecord {
     pub source_node: String,
     
     /// Last update time
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub last_updated: DateTime<Utc>,
+
+    /// Organization on this node that registered the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
 }
@@ -0,0 +1,349 @@
+//! Unit normalization for submitted CDMs
+//!
+//! Internally a CDM is in fixed units, named in its field suffixes
+//! (`miss_distance_m`, `x_km`, `vx_km_s`, ...) and km² for covariances.
+//! Providers that work in other units may submit the unsuffixed field names
+//! with a `units` object naming the unit of each quantity:
+//!
+//! ```json
+//! { "miss_distance": 0.15, "units": { "miss_distance": "km" }, ... }
+//! ```
+//!
+//! Normalization converts annotated values to the internal units and drops
+//! `units`. Input whose unit cannot be told for certain is rejected: an
+//! unsuffixed field without an annotation, a field given both with and
+//! without its suffix, or an annotation for fields that already carry their
+//! unit in their name.
+
+use crate::{Error, Result};
+use serde_json::{Map, Value};
+
+/// Key of the unit annotations in a submitted CDM
+pub const UNITS_FIELD: &str = "units";
+
+/// What a quantity measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Speed,
+    Area,
+}
+
+impl Dimension {
+    /// Size of `unit` in SI units, if it measures this dimension
+    fn si_factor(self, unit: &str) -> Option<f64> {
+        match (self, unit) {
+            (Dimension::Length, "m") => Some(1.0),
+            (Dimension::Length, "km") => Some(1.0e3),
+            (Dimension::Speed, "m/s") => Some(1.0),
+            (Dimension::Speed, "km/s") => Some(1.0e3),
+            (Dimension::Speed, "m/min") => Some(1.0 / 60.0),
+            (Dimension::Speed, "km/min") => Some(1.0e3 / 60.0),
+            (Dimension::Area, "m2" | "m^2") => Some(1.0),
+            (Dimension::Area, "km2" | "km^2") => Some(1.0e6),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Dimension::Length => "length (m, km)",
+            Dimension::Speed => "speed (m/s, km/s, m/min, km/min)",
+            Dimension::Area => "area (m2, km2)",
+        }
+    }
+}
+
+/// A quantity that may be annotated, and the fields holding it
+struct Quantity {
+    /// Key under `units`
+    key: &'static str,
+    dimension: Dimension,
+    /// Internal unit
+    canonical_unit: &'static str,
+    /// Objects holding the fields, as paths from the CDM
+    parents: &'static [&'static [&'static str]],
+    /// Unsuffixed and internal field names; equal when the name carries
+    /// no unit
+    fields: &'static [(&'static str, &'static str)],
+}
+
+const STATE_VECTORS: &[&[&str]] = &[&["object1", "state_vector"], &["object2", "state_vector"]];
+
+const QUANTITIES: &[Quantity] = &[
+    Quantity {
+        key: "miss_distance",
+        dimension: Dimension::Length,
+        canonical_unit: "m",
+        parents: &[&[]],
+        fields: &[("miss_distance", "miss_distance_m")],
+    },
+    Quantity {
+        key: "hard_body_radius",
+        dimension: Dimension::Length,
+        canonical_unit: "m",
+        parents: &[&["screening_data"]],
+        fields: &[("hard_body_radius", "hard_body_radius_m")],
+    },
+    Quantity {
+        key: "position",
+        dimension: Dimension::Length,
+        canonical_unit: "km",
+        parents: STATE_VECTORS,
+        fields: &[("x", "x_km"), ("y", "y_km"), ("z", "z_km")],
+    },
+    Quantity {
+        key: "velocity",
+        dimension: Dimension::Speed,
+        canonical_unit: "km/s",
+        parents: STATE_VECTORS,
+        fields: &[("vx", "vx_km_s"), ("vy", "vy_km_s"), ("vz", "vz_km_s")],
+    },
+    Quantity {
+        key: "relative_position",
+        dimension: Dimension::Length,
+        canonical_unit: "m",
+        parents: &[&["relative_state"]],
+        fields: &[
+            ("relative_position_r", "relative_position_r_m"),
+            ("relative_position_t", "relative_position_t_m"),
+            ("relative_position_n", "relative_position_n_m"),
+        ],
+    },
+    Quantity {
+        key: "relative_velocity",
+        dimension: Dimension::Speed,
+        canonical_unit: "m/s",
+        parents: &[&["relative_state"]],
+        fields: &[
+            ("relative_velocity_r", "relative_velocity_r_m_s"),
+            ("relative_velocity_t", "relative_velocity_t_m_s"),
+            ("relative_velocity_n", "relative_velocity_n_m_s"),
+        ],
+    },
+    Quantity {
+        key: "covariance",
+        dimension: Dimension::Area,
+        canonical_unit: "km2",
+        parents: &[&["object1", "covariance_rtm"], &["object2", "covariance_rtm"]],
+        fields: &[
+            ("cr_r", "cr_r"),
+            ("ct_r", "ct_r"),
+            ("ct_t", "ct_t"),
+            ("cn_r", "cn_r"),
+            ("cn_t", "cn_t"),
+            ("cn_n", "cn_n"),
+        ],
+    },
+];
+
+fn invalid(message: String) -> Error {
+    Error::CdmValidation(message)
+}
+
+/// Convert a submitted CDM's annotated quantities to the internal units
+///
+/// A CDM without `units` and without unsuffixed fields is left unchanged.
+pub fn normalize_units(cdm: &mut Value) -> Result<()> {
+    let Some(root) = cdm.as_object_mut() else {
+        return Ok(());
+    };
+    let mut units = match root.remove(UNITS_FIELD) {
+        None => Map::new(),
+        Some(Value::Object(units)) => units,
+        Some(_) => return Err(invalid("units must be an object".into())),
+    };
+
+    for quantity in QUANTITIES {
+        let factor = match units.remove(quantity.key) {
+            None => None,
+            Some(unit) => {
+                let unit = unit
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("units.{} must be a string", quantity.key)))?;
+                let from = quantity.dimension.si_factor(unit).ok_or_else(|| {
+                    invalid(format!(
+                        "units.{}: {:?} is not a unit of {}",
+                        quantity.key,
+                        unit,
+                        quantity.dimension.name()
+                    ))
+                })?;
+                let to = quantity.dimension.si_factor(quantity.canonical_unit).unwrap_or(1.0);
+                Some(from / to)
+            }
+        };
+        for parent in quantity.parents {
+            if let Some(object) = lookup(root, parent) {
+                normalize_fields(object, quantity, parent, factor)?;
+            }
+        }
+    }
+
+    if let Some(key) = units.keys().next() {
+        let known: Vec<&str> = QUANTITIES.iter().map(|q| q.key).collect();
+        return Err(invalid(format!("units.{} is not a known quantity ({})", key, known.join(", "))));
+    }
+    Ok(())
+}
+
+fn lookup<'a>(root: &'a mut Map<String, Value>, path: &[&str]) -> Option<&'a mut Map<String, Value>> {
+    path.iter()
+        .try_fold(root, |object, key| object.get_mut(*key).and_then(Value::as_object_mut))
+}
+
+fn normalize_fields(
+    object: &mut Map<String, Value>,
+    quantity: &Quantity,
+    parent: &[&str],
+    factor: Option<f64>,
+) -> Result<()> {
+    let field_path = |field: &str| parent.iter().chain([&field]).copied().collect::<Vec<_>>().join(".");
+    for &(raw, canonical) in quantity.fields {
+        if raw == canonical {
+            // The name carries no unit; the annotation applies in place
+            if let (Some(factor), Some(value)) = (factor, object.get_mut(raw)) {
+                *value = convert(value, factor, &field_path(raw))?;
+            }
+            continue;
+        }
+        match (object.remove(raw), object.contains_key(canonical)) {
+            (Some(_), true) => {
+                return Err(invalid(format!(
+                    "{} and {} are both given",
+                    field_path(raw),
+                    field_path(canonical)
+                )))
+            }
+            (Some(value), false) => {
+                let factor = factor.ok_or_else(|| {
+                    invalid(format!(
+                        "{} has no unit: annotate units.{} or send {}",
+                        field_path(raw),
+                        quantity.key,
+                        field_path(canonical)
+                    ))
+                })?;
+                object.insert(canonical.to_string(), convert(&value, factor, &field_path(raw))?);
+            }
+            (None, true) if factor.is_some() => {
+                return Err(invalid(format!(
+                    "units.{} is given, but {} already carries its unit",
+                    quantity.key,
+                    field_path(canonical)
+                )))
+            }
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+fn convert(value: &Value, factor: f64, field: &str) -> Result<Value> {
+    let number = value
+        .as_f64()
+        .ok_or_else(|| invalid(format!("{} must be a number", field)))?;
+    serde_json::Number::from_f64(number * factor)
+        .map(Value::Number)
+        .ok_or_else(|| invalid(format!("{} is out of range", field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::{generate_demo_cdm, parse_cdm, CdmRecord};
+
+    /// Express a CDM in the given units, the way a provider might send it
+    fn annotate(cdm: &CdmRecord, units: &[(&str, &str)]) -> Value {
+        let mut value = serde_json::to_value(cdm).unwrap();
+        let root = value.as_object_mut().unwrap();
+        for &(key, unit) in units {
+            let quantity = QUANTITIES.iter().find(|q| q.key == key).unwrap();
+            let factor = quantity.dimension.si_factor(quantity.canonical_unit).unwrap()
+                / quantity.dimension.si_factor(unit).unwrap();
+            for parent in quantity.parents {
+                let object = lookup(root, parent).unwrap();
+                for &(raw, canonical) in quantity.fields {
+                    let converted = object[canonical].as_f64().unwrap() * factor;
+                    object.remove(canonical);
+                    object.insert(raw.to_string(), converted.into());
+                }
+            }
+        }
+        let units: Map<String, Value> = units.iter().map(|&(k, u)| (k.to_string(), u.into())).collect();
+        root.insert(UNITS_FIELD.to_string(), units.into());
+        value
+    }
+
+    fn assert_close(a: &Value, b: &Value) {
+        match (a, b) {
+            (Value::Number(x), Value::Number(y)) => {
+                let (x, y) = (x.as_f64().unwrap(), y.as_f64().unwrap());
+                assert!((x - y).abs() <= 1e-9 * x.abs().max(y.abs()), "{} != {}", x, y);
+            }
+            (Value::Object(x), Value::Object(y)) => {
+                assert_eq!(x.keys().collect::<Vec<_>>(), y.keys().collect::<Vec<_>>());
+                x.iter().for_each(|(k, v)| assert_close(v, &y[k]));
+            }
+            _ => assert_eq!(a, b),
+        }
+    }
+
+    #[test]
+    fn test_unit_round_trip() {
+        let mut cdm = generate_demo_cdm();
+        cdm.relative_state = Some(serde_json::from_value(serde_json::json!({
+            "relative_position_r_m": 12.5, "relative_position_t_m": -140.0, "relative_position_n_m": 30.0,
+            "relative_velocity_r_m_s": 3.0, "relative_velocity_t_m_s": -14000.0, "relative_velocity_n_m_s": 7.5,
+        })).unwrap());
+        let canonical = serde_json::to_value(&cdm).unwrap();
+
+        let unit_sets: [&[(&str, &str)]; 3] = [
+            &[("miss_distance", "km"), ("position", "m"), ("velocity", "m/s"), ("covariance", "m2")],
+            &[("velocity", "km/min"), ("relative_position", "km"), ("relative_velocity", "m/min")],
+            &[("hard_body_radius", "km"), ("miss_distance", "m"), ("covariance", "km^2")],
+        ];
+        for units in unit_sets {
+            let mut submitted = annotate(&cdm, units);
+            normalize_units(&mut submitted).unwrap();
+            assert_close(&submitted, &canonical);
+            let parsed = parse_cdm(annotate(&cdm, units)).unwrap();
+            assert_close(&serde_json::to_value(parsed).unwrap(), &canonical);
+        }
+
+        // Canonical input passes through untouched
+        let mut unchanged = canonical.clone();
+        normalize_units(&mut unchanged).unwrap();
+        assert_eq!(unchanged, canonical);
+    }
+
+    #[test]
+    fn test_ambiguous_units_rejected() {
+        let cdm = generate_demo_cdm();
+        let reject = |edit: &dyn Fn(&mut Value)| {
+            let mut value = serde_json::to_value(&cdm).unwrap();
+            edit(&mut value);
+            normalize_units(&mut value).unwrap_err().to_string()
+        };
+
+        // No unit for an unsuffixed field
+        let error = reject(&|v| {
+            let distance = v.as_object_mut().unwrap().remove("miss_distance_m").unwrap();
+            v["miss_distance"] = distance;
+        });
+        assert!(error.contains("miss_distance has no unit"), "{}", error);
+        // Both spellings of one field
+        let error = reject(&|v| {
+            v["miss_distance"] = 0.15.into();
+            v["units"] = serde_json::json!({ "miss_distance": "km" });
+        });
+        assert!(error.contains("both given"), "{}", error);
+        // An annotation for a field that names its unit
+        let error = reject(&|v| v["units"] = serde_json::json!({ "position": "m" }));
+        assert!(error.contains("already carries its unit"), "{}", error);
+        // Wrong dimension, unknown unit and unknown quantity
+        assert!(reject(&|v| v["units"] = serde_json::json!({ "velocity": "km" })).contains("not a unit of speed"));
+        assert!(reject(&|v| v["units"] = serde_json::json!({ "covariance": "ft2" })).contains("not a unit of area"));
+        assert!(reject(&|v| v["units"] = serde_json::json!({ "tca": "s" })).contains("not a known quantity"));
+    }
+}
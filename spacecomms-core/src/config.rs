@@ -1,12 +1,63 @@
 //! Configuration handling
 
+use crate::cdm::{
+    canonical_object_id, ConjunctionCategory, PcMethods, ScreenType, ValidationRule, DEFAULT_HARD_BODY_RADIUS_M,
+};
+use crate::node::{role_scopes, Scope, BUILTIN_ROLES};
+use crate::orbit::{OrbitalRegime, ScreeningOptions, MAX_PROPAGATION_DAYS};
+use crate::protocol::{
+    check_public_key, feature, Encoding, Interests, MessageType, ProvenanceSigner, TimestampFormat, DEFAULT_TTL,
+    LATEST_VERSION, MAX_BATCH_ENVELOPES, SUPPORTED_VERSIONS,
+};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::str::FromStr;
 use tracing::Level;
+use utoipa::ToSchema;
+
+/// Parse a configuration document, by file extension: `.json` as JSON,
+/// `.toml` as TOML and anything else as YAML
+fn parse_document(path: &Path, content: &str) -> Result<Value> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(serde_json::from_str(content)?),
+        Some("toml") => {
+            toml::from_str(content).map_err(|e| Error::Config(format!("invalid TOML in {}: {}", path.display(), e)))
+        }
+        _ => Ok(serde_yaml::from_str(content)?),
+    }
+}
+
+/// Point schema references at `$defs` rather than OpenAPI components
+fn rewrite_refs(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    serde_json::Value::String(target) if key == "$ref" => {
+                        if let Some(name) = target.strip_prefix("#/components/schemas/") {
+                            *target = format!("#/$defs/{}", name);
+                        }
+                    }
+                    _ => rewrite_refs(value),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(rewrite_refs),
+        _ => {}
+    }
+}
+
+/// Prefix of environment variables that override configuration keys
+pub const ENV_PREFIX: &str = "SPACECOMMS_";
+
+/// Separator between key segments in override environment variables
+pub const ENV_SEPARATOR: &str = "__";
 
 /// SpaceComms configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Config {
     /// Node identity configuration
     pub node: NodeConfig,
@@ -21,6 +72,14 @@ pub struct Config {
     /// Peer configurations
     #[serde(default)]
     pub peers: Vec<PeerConfig>,
+
+    /// Named sets of peer policies, for peer groups to start from
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub policy_templates: BTreeMap<String, PolicyTemplate>,
+
+    /// Named groups of peers sharing policies, joined with a peer's `group`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub peer_groups: BTreeMap<String, PeerGroupConfig>,
     
     /// Storage configuration
     #[serde(default)]
@@ -33,17 +92,211 @@ pub struct Config {
     /// Protocol settings
     #[serde(default)]
     pub protocol: ProtocolConfig,
+
+    /// External object catalog used to enrich announced objects
+    #[serde(default)]
+    pub catalog: Option<CatalogConfig>,
+
+    /// Developer mode settings (traffic generator enabled when set)
+    #[serde(default)]
+    pub dev: Option<DevConfig>,
+
+    /// Collision probability computation settings
+    #[serde(default)]
+    pub pc: PcConfig,
+
+    /// Criteria for `/health/ready`
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+
+    /// Trust weights for fusing CDMs from several originators
+    #[serde(default)]
+    pub fusion: FusionConfig,
+
+    /// Concurrency, timeouts, retries and circuit breaking for forwarding
+    #[serde(default)]
+    pub fanout: FanoutConfig,
+
+    /// Escalation of active CDMs as their TCA approaches
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+
+    /// Where alerts for watched assets are sent
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+
+    /// Objects this node wants announcements about, advertised to peers
+    /// (everything unless set)
+    #[serde(default, skip_serializing_if = "Interests::is_all")]
+    pub interests: Interests,
+
+    /// Retention and archival of withdrawn, expired and stale records
+    /// (disabled unless set)
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+
+    /// Peers found through DNS SRV records and seed addresses (disabled
+    /// unless set)
+    #[serde(default)]
+    pub discovery: Option<DiscoveryConfig>,
+
+    /// Size thresholds for compressing peer payloads and API responses
+    #[serde(default)]
+    pub compression: CompressionConfig,
+
+    /// Peer health scoring and quarantine of peers that keep failing
+    #[serde(default)]
+    pub peer_health: PeerHealthConfig,
+
+    /// Per-originator ingest quotas, and the anomalies that hold back an
+    /// originator's CDMs
+    #[serde(default)]
+    pub ingest_quotas: IngestQuotaConfig,
+
+    /// Signing of this node's CDM provenance hops, and the keys other
+    /// nodes' hops are checked against
+    #[serde(default)]
+    pub provenance: ProvenanceConfig,
+
+    /// Sampling and retention of the statistics history
+    #[serde(default)]
+    pub stats: StatsConfig,
+
+    /// This node's own acceptance rules for CDMs
+    #[serde(default)]
+    pub validation: ValidationConfig,
+
+    /// Aliases object IDs are canonicalized through
+    #[serde(default)]
+    pub identifiers: IdentifiersConfig,
+
+    /// OpenTelemetry trace export over OTLP (disabled unless set)
+    #[serde(default)]
+    pub telemetry: Option<TelemetryConfig>,
+
+    /// Leader election between instances sharing this node ID (a single
+    /// instance unless set)
+    #[serde(default)]
+    pub ha: Option<HaConfig>,
+
+    /// Conjunction screening of the object catalog, and which hits become
+    /// CDMs (disabled unless set)
+    #[serde(default)]
+    pub screening: Option<ScreeningConfig>,
 }
 
 impl Config {
-    /// Load configuration from a YAML file
+    /// Load configuration from a YAML, JSON or TOML file, with environment
+    /// overrides
     pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with(path, &[])
+    }
+
+    /// Load configuration from a YAML, JSON or TOML file, layering
+    /// environment overrides and then `overrides` on top
+    pub fn load_with(path: &Path, overrides: &[ConfigOverride]) -> Result<Self> {
         let content = std::fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
+        let layers = ConfigOverride::from_env();
+        Self::layered(parse_document(path, &content)?, layers.iter().chain(overrides))
+    }
+
+    /// JSON Schema (draft 2020-12) of the configuration file, for checking
+    /// configs before rollout
+    pub fn json_schema() -> serde_json::Value {
+        let mut definitions = Vec::new();
+        <Config as ToSchema>::schemas(&mut definitions);
+        let mut defs = serde_json::Map::new();
+        for (name, schema) in definitions {
+            defs.insert(name, serde_json::to_value(schema).unwrap_or_default());
+        }
+
+        let mut schema = match serde_json::to_value(<Config as utoipa::PartialSchema>::schema()) {
+            Ok(serde_json::Value::Object(root)) => root,
+            _ => serde_json::Map::new(),
+        };
+        schema.insert("$schema".into(), "https://json-schema.org/draft/2020-12/schema".into());
+        schema.insert("title".into(), "SpaceComms configuration".into());
+        schema.insert("$defs".into(), serde_json::Value::Object(defs));
+        let mut schema = serde_json::Value::Object(schema);
+        rewrite_refs(&mut schema);
+        schema
+    }
+
+    /// Layer environment overrides and then `overrides` over this configuration
+    pub fn with_overrides(self, overrides: &[ConfigOverride]) -> Result<Self> {
+        let layers = ConfigOverride::from_env();
+        Self::layered(serde_yaml::to_value(&self)?, layers.iter().chain(overrides))
+    }
+
+    /// Apply overrides in order to a parsed document, resolve peer groups,
+    /// then validate
+    fn layered<'a>(mut document: Value, overrides: impl IntoIterator<Item = &'a ConfigOverride>) -> Result<Self> {
+        for layer in overrides {
+            layer.apply(&mut document)?;
+        }
+        resolve_peer_groups(&mut document)?;
+        // Round-trip through text so overridden scalars take the field's
+        // type: `node.id=123` is a string, `server.port=123` a number
+        let config: Config = serde_yaml::from_str(&serde_yaml::to_string(&document)?)?;
         config.validate()?;
         Ok(config)
     }
 
+    /// Ephemeral developer configuration
+    ///
+    /// Random node ID, memory storage, bound to localhost, with the
+    /// synthetic traffic generator enabled.
+    pub fn dev() -> Self {
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            node: NodeConfig {
+                id: format!("dev-{}", &suffix[..8]),
+                name: "SpaceComms Dev Node".to_string(),
+                mode: NodeMode::Full,
+            },
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                ..Default::default()
+            },
+            // Lets a dashboard under development call the node from its own
+            // dev server
+            api: ApiConfig {
+                cors: CorsConfig {
+                    allowed_origins: vec!["*".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            peers: Vec::new(),
+            policy_templates: BTreeMap::new(),
+            peer_groups: BTreeMap::new(),
+            storage: StorageConfig::default(),
+            logging: LoggingConfig::default(),
+            protocol: ProtocolConfig::default(),
+            catalog: None,
+            dev: Some(DevConfig::default()),
+            pc: PcConfig::default(),
+            readiness: ReadinessConfig::default(),
+            fusion: FusionConfig::default(),
+            fanout: FanoutConfig::default(),
+            alerts: AlertsConfig::default(),
+            notifications: NotificationsConfig::default(),
+            interests: Interests::default(),
+            archive: None,
+            discovery: None,
+            compression: CompressionConfig::default(),
+            peer_health: PeerHealthConfig::default(),
+            ingest_quotas: IngestQuotaConfig::default(),
+            provenance: ProvenanceConfig::default(),
+            stats: StatsConfig::default(),
+            validation: ValidationConfig::default(),
+            identifiers: IdentifiersConfig::default(),
+            telemetry: None,
+            ha: None,
+            screening: None,
+        }
+    }
+
     /// Validate the configuration
     fn validate(&self) -> Result<()> {
         if self.node.id.is_empty() {
@@ -52,6 +305,365 @@ impl Config {
         if self.server.port == 0 {
             return Err(Error::Config("server.port must be non-zero".into()));
         }
+        if let Some(grpc_port) = self.server.grpc_port {
+            if grpc_port == 0 || grpc_port == self.server.port {
+                return Err(Error::Config(
+                    "server.grpc_port must be non-zero and differ from server.port".into(),
+                ));
+            }
+        }
+        if let Some(address) = &self.server.advertise_address {
+            let valid = reqwest::Url::parse(address).is_ok_and(|url| {
+                matches!(url.scheme(), "http" | "https")
+                    && url.host_str().is_some()
+                    && url.path() == "/"
+                    && url.query().is_none()
+                    && url.fragment().is_none()
+            });
+            if !valid {
+                return Err(Error::Config(format!(
+                    "server.advertise_address must be an http(s) base URL such as https://node.example.org:8443, got {}",
+                    address
+                )));
+            }
+        }
+        let limits = &self.storage.object_limits;
+        if limits.max_objects == Some(0) || limits.max_objects_per_source == Some(0) {
+            return Err(Error::Config("storage.object_limits values must be non-zero".into()));
+        }
+        if limits.alert_percent == 0 || limits.alert_percent > 100 {
+            return Err(Error::Config("storage.object_limits.alert_percent must be 1-100".into()));
+        }
+        if self.storage.conjunction_bucket_seconds == 0 {
+            return Err(Error::Config("storage.conjunction_bucket_seconds must be non-zero".into()));
+        }
+        let memory = &self.storage.memory;
+        if memory.max_bytes == Some(0) {
+            return Err(Error::Config("storage.memory.max_bytes must be non-zero".into()));
+        }
+        if memory.alert_percent == 0 || memory.alert_percent > 100 {
+            return Err(Error::Config("storage.memory.alert_percent must be 1-100".into()));
+        }
+        if let Some(encryption) = &self.storage.encryption {
+            encryption.check().map_err(|e| Error::Config(format!("storage.encryption: {}", e)))?;
+        }
+        if self.protocol.max_envelope_bytes == 0 || self.protocol.max_payload_depth == 0 {
+            return Err(Error::Config(
+                "protocol.max_envelope_bytes and protocol.max_payload_depth must be non-zero".into(),
+            ));
+        }
+        if !SUPPORTED_VERSIONS.contains(&self.protocol.version.as_str()) {
+            return Err(Error::Config(format!(
+                "protocol.version must be one of {}",
+                SUPPORTED_VERSIONS.join(", ")
+            )));
+        }
+        if let Some(unknown) = self.protocol.disabled_features.iter().find(|c| feature(c).is_none()) {
+            return Err(Error::Config(format!(
+                "protocol.disabled_features: {} is not a negotiated feature",
+                unknown
+            )));
+        }
+        if let Some(message_type) = self
+            .protocol
+            .ttl
+            .message_types
+            .keys()
+            .find(|t| !t.is_relayed() && **t != MessageType::DeliveryReceipt)
+        {
+            return Err(Error::Config(format!(
+                "protocol.ttl.message_types: {} is never forwarded",
+                message_type
+            )));
+        }
+        if self.protocol.max_query_results == 0 {
+            return Err(Error::Config("protocol.max_query_results must be non-zero".into()));
+        }
+        if !(1..=crate::protocol::MAX_DIGEST_BUCKETS).contains(&self.protocol.sync.digest_buckets) {
+            return Err(Error::Config(format!(
+                "protocol.sync.digest_buckets must be between 1 and {}",
+                crate::protocol::MAX_DIGEST_BUCKETS
+            )));
+        }
+        if self.protocol.delivery.ack_timeout_seconds == 0 {
+            return Err(Error::Config("protocol.delivery.ack_timeout_seconds must be non-zero".into()));
+        }
+        let severity = &self.protocol.severity;
+        if !(0.0 < severity.medium_probability && severity.medium_probability <= severity.high_probability) {
+            return Err(Error::Config(
+                "protocol.severity probabilities must satisfy 0 < medium_probability <= high_probability".into(),
+            ));
+        }
+        if !(0.0 <= severity.high_miss_distance_m && severity.high_miss_distance_m <= severity.medium_miss_distance_m) {
+            return Err(Error::Config(
+                "protocol.severity miss distances must satisfy 0 <= high_miss_distance_m <= medium_miss_distance_m"
+                    .into(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.protocol.min_data_quality) {
+            return Err(Error::Config("protocol.min_data_quality must be between 0 and 1".into()));
+        }
+        let fusion = &self.fusion;
+        if !std::iter::once(&fusion.default_weight)
+            .chain(fusion.originator_weights.values())
+            .all(|w| w.is_finite() && *w >= 0.0)
+        {
+            return Err(Error::Config("fusion weights must be finite and non-negative".into()));
+        }
+        let health = &self.peer_health;
+        if health.window == 0 || health.min_samples > health.window {
+            return Err(Error::Config(
+                "peer_health.window must be non-zero and at least peer_health.min_samples".into(),
+            ));
+        }
+        if !(health.max_error_rate > 0.0 && health.max_error_rate <= 1.0) {
+            return Err(Error::Config("peer_health.max_error_rate must be above 0 and at most 1".into()));
+        }
+        let quotas = &self.ingest_quotas;
+        if !(quotas.spike_factor.is_finite() && quotas.spike_factor >= 0.0) {
+            return Err(Error::Config("ingest_quotas.spike_factor must be finite and non-negative".into()));
+        }
+        if quotas.action == AnomalyAction::Throttle && quotas.throttle_seconds == 0 {
+            return Err(Error::Config("ingest_quotas.throttle_seconds must be non-zero".into()));
+        }
+        if self.stats.retention_hours == 0 {
+            return Err(Error::Config("stats.retention_hours must be non-zero".into()));
+        }
+        for (i, rule) in self.validation.rules.iter().enumerate() {
+            rule.check()
+                .map_err(|e| Error::Config(format!("validation.rules[{}] ({}): {}", i, rule.display_name(), e)))?;
+        }
+        if let Some(alias) = self.identifiers.aliases.iter().find(|(a, id)| a.trim().is_empty() || id.trim().is_empty()) {
+            return Err(Error::Config(format!("identifiers.aliases {:?}: alias and object ID must be non-empty", alias.0)));
+        }
+        if let Some(seed) = &self.provenance.signing_key {
+            ProvenanceSigner::from_seed(seed)?;
+        }
+        for (node_id, key) in &self.provenance.trusted_keys {
+            check_public_key(key).map_err(|e| Error::Config(format!("provenance.trusted_keys {}: {}", node_id, e)))?;
+        }
+        let fanout = &self.fanout;
+        if fanout.max_in_flight_per_peer == 0 || fanout.queue_per_peer == 0 || fanout.send_timeout_ms == 0 {
+            return Err(Error::Config(
+                "fanout.max_in_flight_per_peer, fanout.queue_per_peer and fanout.send_timeout_ms must be non-zero"
+                    .into(),
+            ));
+        }
+        if fanout.batch.max_envelopes == 0 || fanout.batch.max_envelopes > MAX_BATCH_ENVELOPES {
+            return Err(Error::Config(format!(
+                "fanout.batch.max_envelopes must be between 1 and {}",
+                MAX_BATCH_ENVELOPES
+            )));
+        }
+        let mut classes = std::collections::HashSet::new();
+        for class in &fanout.priority_classes {
+            if class.name.is_empty() || class.name == DEFAULT_PRIORITY_CLASS || !classes.insert(&class.name) {
+                return Err(Error::Config(format!(
+                    "fanout.priority_classes names must be unique, non-empty and not {}",
+                    DEFAULT_PRIORITY_CLASS
+                )));
+            }
+        }
+        let alerts = &self.alerts;
+        if alerts.check_interval_seconds == 0 || alerts.thresholds_hours.contains(&0) {
+            return Err(Error::Config(
+                "alerts.check_interval_seconds and alerts.thresholds_hours must be non-zero".into(),
+            ));
+        }
+        if !(alerts.risk_increase_factor > 1.0 && alerts.risk_increase_factor.is_finite()) {
+            return Err(Error::Config("alerts.risk_increase_factor must be a number above 1".into()));
+        }
+        if let Some(url) = alerts
+            .webhooks
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err(Error::Config(format!("alerts.webhooks entry {} must be an http(s) URL", url)));
+        }
+        self.notifications.validate()?;
+        let api = &self.api;
+        if let Some(org) = api
+            .organizations
+            .iter()
+            .enumerate()
+            .find(|(i, o)| o.id.is_empty() || api.organizations[..*i].iter().any(|p| p.id == o.id))
+        {
+            return Err(Error::Config(format!(
+                "api.organizations IDs must be non-empty and unique (entry {})",
+                org.0
+            )));
+        }
+        if let Some(token) = api
+            .auth
+            .tokens
+            .iter()
+            .find(|t| t.organization.as_deref().is_some_and(|org| api.organization(org).is_none()))
+        {
+            return Err(Error::Config(format!(
+                "api.auth.tokens {}: unknown organization",
+                token.id
+            )));
+        }
+        for (i, role) in api.auth.roles.iter().enumerate() {
+            if role.name.is_empty()
+                || api.auth.roles[..i].iter().any(|r| r.name == role.name)
+                || BUILTIN_ROLES.iter().any(|(name, _)| *name == role.name)
+            {
+                return Err(Error::Config(format!(
+                    "api.auth.roles names must be non-empty, unique and not built-in (entry {})",
+                    i
+                )));
+            }
+            if let Some(scope) = role.scopes.iter().find(|s| Scope::parse(s).is_none()) {
+                return Err(Error::Config(format!("api.auth.roles {}: invalid scope {:?}", role.name, scope)));
+            }
+        }
+        for token in &api.auth.tokens {
+            if let Some(scope) = token.permissions.iter().find(|s| Scope::parse(s).is_none()) {
+                return Err(Error::Config(format!("api.auth.tokens {}: invalid permission {:?}", token.id, scope)));
+            }
+            if let Some(role) = token.roles.iter().find(|r| role_scopes(&api.auth, r).is_none()) {
+                return Err(Error::Config(format!("api.auth.tokens {}: unknown role {}", token.id, role)));
+            }
+        }
+        if api.tenant_isolation && !api.auth.enabled {
+            return Err(Error::Config("api.tenant_isolation requires api.auth.enabled".into()));
+        }
+        api.cors.check().map_err(|e| Error::Config(format!("api.cors: {}", e)))?;
+        if let Some(policy) = &api.security_headers.content_security_policy {
+            if axum::http::HeaderValue::from_str(policy).is_err() {
+                return Err(Error::Config(
+                    "api.security_headers.content_security_policy is not a valid header value".into(),
+                ));
+            }
+        }
+        for peer in &self.peers {
+            let redact = &peer.policies.redact;
+            if ![redact.position_resolution_km, redact.velocity_resolution_km_s]
+                .into_iter()
+                .flatten()
+                .all(|r| r.is_finite() && r > 0.0)
+            {
+                return Err(Error::Config(format!(
+                    "peers {}: policies.redact resolutions must be positive",
+                    peer.id
+                )));
+            }
+        }
+        let interests = &self.interests;
+        if interests.object_ids.iter().chain(&interests.owners).any(|p| p.is_empty()) {
+            return Err(Error::Config("interests patterns must be non-empty".into()));
+        }
+        if self.dev.as_ref().is_some_and(|dev| dev.traffic_interval_seconds == 0) {
+            return Err(Error::Config("dev.traffic_interval_seconds must be non-zero".into()));
+        }
+        if !PcMethods::BUILTIN.contains(&self.pc.method.as_str()) {
+            return Err(Error::Config(format!(
+                "pc.method must be one of {}",
+                PcMethods::BUILTIN.join(", ")
+            )));
+        }
+        if let Some(catalog) = &self.catalog {
+            if catalog.url.is_empty() || !catalog.query_path.contains("{id}") {
+                return Err(Error::Config(
+                    "catalog.url is required and catalog.query_path must contain {id}".into(),
+                ));
+            }
+            let search_paths = [&catalog.name_query_path, &catalog.designator_query_path];
+            if search_paths.into_iter().flatten().any(|path| !path.contains("{query}")) {
+                return Err(Error::Config(
+                    "catalog.name_query_path and catalog.designator_query_path must contain {query}".into(),
+                ));
+            }
+            if catalog.refresh_interval_seconds == 0 || catalog.max_entries == 0 {
+                return Err(Error::Config(
+                    "catalog.refresh_interval_seconds and catalog.max_entries must be non-zero".into(),
+                ));
+            }
+        }
+        if let Some(archive) = &self.archive {
+            if archive.directory.is_empty() {
+                return Err(Error::Config("archive.directory is required".into()));
+            }
+            if archive.interval_seconds == 0 {
+                return Err(Error::Config("archive.interval_seconds must be non-zero".into()));
+            }
+        }
+        if self.node.mode == NodeMode::ReadOnly {
+            let peering = [
+                ("peers", !self.peers.is_empty()),
+                ("discovery", self.discovery.is_some()),
+                ("server.grpc_port", self.server.grpc_port.is_some()),
+                ("ha", self.ha.is_some()),
+            ];
+            if let Some((key, _)) = peering.iter().find(|(_, set)| *set) {
+                return Err(Error::Config(format!("{} cannot be set when node.mode is read-only", key)));
+            }
+        }
+        if let Some(discovery) = &self.discovery {
+            if discovery.refresh_interval_seconds == 0 {
+                return Err(Error::Config("discovery.refresh_interval_seconds must be non-zero".into()));
+            }
+            if discovery.seeds.is_empty() && discovery.dns.is_empty() {
+                return Err(Error::Config("discovery needs at least one seed or dns entry".into()));
+            }
+            for seed in &discovery.seeds {
+                if !seed.address.starts_with("http://") && !seed.address.starts_with("https://") {
+                    return Err(Error::Config(format!("discovery seed {} must be an http(s) URL", seed.address)));
+                }
+                if seed.id.as_deref().is_some_and(str::is_empty) {
+                    return Err(Error::Config(format!("discovery seed {}: id must not be empty", seed.address)));
+                }
+            }
+            for dns in &discovery.dns {
+                if dns.name.is_empty() {
+                    return Err(Error::Config("discovery.dns name is required".into()));
+                }
+                if !matches!(dns.scheme.as_str(), "http" | "https") {
+                    return Err(Error::Config(format!("discovery.dns {}: scheme must be http or https", dns.name)));
+                }
+                if dns.nameserver.as_deref().is_some_and(|ns| parse_nameserver(ns).is_none()) {
+                    return Err(Error::Config(format!(
+                        "discovery.dns {}: nameserver must be an IP address, with an optional port",
+                        dns.name
+                    )));
+                }
+            }
+        }
+        if let Some(telemetry) = &self.telemetry {
+            if !telemetry.otlp_endpoint.starts_with("http://") && !telemetry.otlp_endpoint.starts_with("https://") {
+                return Err(Error::Config("telemetry.otlp_endpoint must be an http(s) URL".into()));
+            }
+            if !(0.0..=1.0).contains(&telemetry.sample_ratio) {
+                return Err(Error::Config("telemetry.sample_ratio must be between 0 and 1".into()));
+            }
+        }
+        if let Some(screening) = &self.screening {
+            screening.validate()?;
+        }
+        if let Some(ha) = &self.ha {
+            if ha.instance_id.is_empty() {
+                return Err(Error::Config("ha.instance_id is required".into()));
+            }
+            // Each instance would hold its own lease in private memory and
+            // lead alongside the others
+            if self.storage.storage_type == "memory" {
+                return Err(Error::Config(
+                    "ha needs a storage backend shared by the instances; storage.storage_type memory is private to each".into(),
+                ));
+            }
+            if ha.renew_interval_seconds == 0 || ha.renew_interval_seconds >= ha.lease_seconds {
+                return Err(Error::Config(
+                    "ha.renew_interval_seconds must be non-zero and below ha.lease_seconds".into(),
+                ));
+            }
+            // A follower notices a dead leader one renewal after its lease ends
+            if ha.lease_seconds + ha.renew_interval_seconds > self.protocol.session_timeout_seconds {
+                return Err(Error::Config(
+                    "ha.lease_seconds plus ha.renew_interval_seconds must not exceed protocol.session_timeout_seconds".into(),
+                ));
+            }
+        }
         Ok(())
     }
 
@@ -68,8 +680,105 @@ impl Config {
     }
 }
 
+/// One configuration key set over the file, e.g. `server.port=9090`
+///
+/// Keys are dotted paths; numeric segments index into lists
+/// (`peers.0.address`). Values are parsed as YAML scalars or flow
+/// collections, so `true`, `9090` and `[a, b]` keep their types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+    pub path: Vec<String>,
+    pub value: Value,
+}
+
+impl ConfigOverride {
+    fn new(path: Vec<String>, value: &str) -> Result<Self> {
+        if path.is_empty() || path.iter().any(String::is_empty) {
+            return Err(Error::Config(format!("invalid configuration key {:?}", path.join("."))));
+        }
+        let value = serde_yaml::from_str(value)
+            .map_err(|e| Error::Config(format!("invalid value for {}: {}", path.join("."), e)))?;
+        Ok(Self { path, value })
+    }
+
+    /// Overrides from `SPACECOMMS_<SECTION>__<KEY>` environment variables
+    pub fn from_env() -> Vec<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Overrides from environment-style variables; other variables, and
+    /// ones without a section (like `SPACECOMMS_CONFIG`), are skipped
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<Self> {
+        let mut overrides: Vec<(String, Self)> = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(ENV_PREFIX)?;
+                if !key.contains(ENV_SEPARATOR) {
+                    return None;
+                }
+                let path = key.split(ENV_SEPARATOR).map(str::to_lowercase).collect();
+                match Self::new(path, &value) {
+                    Ok(layer) => Some((name, layer)),
+                    Err(e) => {
+                        tracing::warn!("Ignoring {}: {}", name, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        // Apply in a stable order regardless of the environment's
+        overrides.sort_by(|a, b| a.0.cmp(&b.0));
+        overrides.into_iter().map(|(_, layer)| layer).collect()
+    }
+
+    /// Set this key in a parsed configuration document
+    fn apply(&self, document: &mut Value) -> Result<()> {
+        let key = self.path.join(".");
+        let mut node = document;
+        for segment in &self.path {
+            if node.is_null() {
+                *node = match segment.parse::<usize>() {
+                    Ok(_) => Value::Sequence(Vec::new()),
+                    Err(_) => Value::Mapping(Default::default()),
+                };
+            }
+            node = match node {
+                Value::Mapping(map) => map
+                    .entry(Value::String(segment.clone()))
+                    .or_insert(Value::Null),
+                Value::Sequence(items) => {
+                    let index: usize = segment
+                        .parse()
+                        .map_err(|_| Error::Config(format!("{}: {} is not a list index", key, segment)))?;
+                    if index == items.len() {
+                        items.push(Value::Null);
+                    }
+                    items.get_mut(index).ok_or_else(|| {
+                        Error::Config(format!("{}: index {} is past the end of the list", key, index))
+                    })?
+                }
+                _ => return Err(Error::Config(format!("{}: {} is not a section", key, segment))),
+            };
+        }
+        *node = self.value.clone();
+        Ok(())
+    }
+}
+
+impl FromStr for ConfigOverride {
+    type Err = Error;
+
+    /// Parse `key.path=value`
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((key, value)) = s.split_once('=') else {
+            return Err(Error::Config(format!("expected key=value, got {:?}", s)));
+        };
+        Self::new(key.trim().split('.').map(str::to_string).collect(), value)
+    }
+}
+
 /// Node identity configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NodeConfig {
     /// Unique node identifier
     pub id: String,
@@ -77,10 +786,26 @@ pub struct NodeConfig {
     /// Human-readable node name
     #[serde(default)]
     pub name: String,
+
+    /// `full` or `read-only`
+    #[serde(default)]
+    pub mode: NodeMode,
+}
+
+/// What a node does with data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeMode {
+    /// Ingests, stores, serves and forwards
+    #[default]
+    Full,
+    /// Serves queries and streams from its storage and archive, but takes
+    /// no CDMs, objects or maneuvers and has no peers
+    ReadOnly,
 }
 
 /// Server configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ServerConfig {
     /// Host to bind to
     #[serde(default = "default_host")]
@@ -93,6 +818,28 @@ pub struct ServerConfig {
     /// TLS configuration
     #[serde(default)]
     pub tls: Option<TlsConfig>,
+
+    /// Port for the gRPC peer stream listener (disabled when unset)
+    #[serde(default)]
+    pub grpc_port: Option<u16>,
+
+    /// Base URL peers reach this node at, advertised in HELLO, when it
+    /// differs from the bind address (NAT, containers, load balancers)
+    #[serde(default)]
+    pub advertise_address: Option<String>,
+
+    /// Largest request body accepted, in bytes (0 for no limit)
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+
+    /// Time allowed to receive a request and produce its response (0 for
+    /// no limit)
+    #[serde(default = "default_request_timeout_seconds")]
+    pub request_timeout_seconds: u64,
+
+    /// Open HTTP connections served at once (0 for no limit)
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
 }
 
 impl Default for ServerConfig {
@@ -101,10 +848,27 @@ impl Default for ServerConfig {
             host: default_host(),
             port: default_port(),
             tls: None,
+            grpc_port: None,
+            advertise_address: None,
+            max_body_bytes: default_max_body_bytes(),
+            request_timeout_seconds: default_request_timeout_seconds(),
+            max_connections: default_max_connections(),
         }
     }
 }
 
+fn default_max_body_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_request_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_max_connections() -> usize {
+    1024
+}
+
 fn default_host() -> String {
     "0.0.0.0".to_string()
 }
@@ -114,7 +878,7 @@ fn default_port() -> u16 {
 }
 
 /// TLS configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TlsConfig {
     /// Path to TLS certificate
     pub cert_path: String,
@@ -124,15 +888,155 @@ pub struct TlsConfig {
 }
 
 /// API configuration
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct ApiConfig {
     /// Authentication configuration
     #[serde(default)]
     pub auth: AuthConfig,
+
+    /// Organizations served by this node
+    #[serde(default)]
+    pub organizations: Vec<OrganizationConfig>,
+
+    /// Limit tokens bound to an organization to that organization's data
+    /// on read endpoints (requires `auth.enabled`)
+    #[serde(default)]
+    pub tenant_isolation: bool,
+
+    /// Cross-origin access for browser clients
+    #[serde(default)]
+    pub cors: CorsConfig,
+
+    /// Security headers added to API responses
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+}
+
+/// Which web origins may call the API from a browser
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CorsConfig {
+    /// Origins such as `https://ops.example.org`, or `*` for any; empty
+    /// refuses cross-origin calls
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+
+    /// Methods cross-origin calls may use
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+
+    /// Request headers cross-origin calls may send
+    #[serde(default = "default_cors_headers")]
+    pub allowed_headers: Vec<String>,
+
+    /// How long browsers may cache a preflight answer
+    #[serde(default = "default_cors_max_age_seconds")]
+    pub max_age_seconds: u64,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE"].map(String::from).to_vec()
+}
+
+fn default_cors_headers() -> Vec<String> {
+    ["authorization", "content-type", "accept", "idempotency-key", "traceparent"].map(String::from).to_vec()
+}
+
+fn default_cors_max_age_seconds() -> u64 {
+    600
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: default_cors_methods(),
+            allowed_headers: default_cors_headers(),
+            max_age_seconds: default_cors_max_age_seconds(),
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Describe the first unusable entry
+    fn check(&self) -> std::result::Result<(), String> {
+        if self.allowed_origins.len() > 1 && self.allowed_origins.iter().any(|o| o == "*") {
+            return Err("allowed_origins may not list * alongside other origins".into());
+        }
+        for origin in self.allowed_origins.iter().filter(|o| *o != "*") {
+            // Browsers send the bare scheme, host and port
+            let valid = reqwest::Url::parse(origin).is_ok_and(|url| {
+                matches!(url.scheme(), "http" | "https") && url.origin().ascii_serialization() == *origin
+            });
+            if !valid {
+                return Err(format!("origin {:?} must look like https://host[:port]", origin));
+            }
+        }
+        if let Some(method) = self.allowed_methods.iter().find(|m| axum::http::Method::from_str(m).is_err()) {
+            return Err(format!("invalid method {:?}", method));
+        }
+        if let Some(header) = self
+            .allowed_headers
+            .iter()
+            .find(|h| axum::http::HeaderName::from_str(h).is_err())
+        {
+            return Err(format!("invalid header name {:?}", header));
+        }
+        Ok(())
+    }
+}
+
+/// Response headers hardening browser use of the API
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SecurityHeadersConfig {
+    /// Send `X-Content-Type-Options`, `X-Frame-Options` and
+    /// `Referrer-Policy`
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// `Content-Security-Policy` value (not sent when unset)
+    #[serde(default)]
+    pub content_security_policy: Option<String>,
+
+    /// `Strict-Transport-Security` max-age, for nodes served over HTTPS
+    /// (not sent when unset)
+    #[serde(default)]
+    pub hsts_max_age_seconds: Option<u64>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            content_security_policy: None,
+            hsts_max_age_seconds: None,
+        }
+    }
+}
+
+impl ApiConfig {
+    /// Organization with this ID
+    pub fn organization(&self, id: &str) -> Option<&OrganizationConfig> {
+        self.organizations.iter().find(|o| o.id == id)
+    }
+}
+
+/// An operator served by a multi-tenant node
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrganizationConfig {
+    /// Organization identifier
+    pub id: String,
+
+    /// Other names CDMs address the organization by in `message_for`
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// Registered object ID patterns (`*` matches any characters)
+    #[serde(default)]
+    pub objects: Vec<String>,
 }
 
 /// Authentication configuration
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct AuthConfig {
     /// Whether authentication is enabled
     #[serde(default)]
@@ -141,10 +1045,23 @@ pub struct AuthConfig {
     /// Configured tokens
     #[serde(default)]
     pub tokens: Vec<TokenConfig>,
+
+    /// Roles tokens may hold, besides the built-in ones
+    #[serde(default)]
+    pub roles: Vec<RoleConfig>,
+}
+
+/// Named set of permission scopes
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleConfig {
+    pub name: String,
+
+    /// Levels (`read`, `write`, `admin`) or endpoint scopes (`METHOD /path`)
+    pub scopes: Vec<String>,
 }
 
 /// Token configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TokenConfig {
     /// Token identifier
     pub id: String,
@@ -152,13 +1069,21 @@ pub struct TokenConfig {
     /// Token secret
     pub secret: String,
     
-    /// Token permissions
+    /// Token permissions: levels or endpoint scopes
     #[serde(default)]
     pub permissions: Vec<String>,
+
+    /// Roles whose scopes the token also holds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+
+    /// Organization the token acts for; unset for operator tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
 }
 
 /// Peer configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PeerConfig {
     /// Peer identifier
     pub id: String,
@@ -166,49 +1091,300 @@ pub struct PeerConfig {
     /// Peer address (URL)
     pub address: String,
     
-    /// Authentication token for this peer
+    /// Secret shared with this peer: sent to it, and required from it
     #[serde(default)]
     pub auth_token: Option<String>,
+
+    /// Transport used for the peer session
+    #[serde(default)]
+    pub transport: PeerTransport,
+
+    /// Preferred envelope encoding (CBOR requires `ENCODING_CBOR` from the peer)
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// Timestamp profile override (defaults to `protocol.timestamp_format`)
+    #[serde(default)]
+    pub timestamp_format: Option<TimestampFormat>,
+
+    /// Peer group whose policies this peer starts from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
     
-    /// Routing policies for this peer
+    /// Routing policies for this peer, over those of its group
     #[serde(default)]
     pub policies: PeerPolicies,
 }
 
-/// Peer routing policies
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct PeerPolicies {
-    /// Accept CDM messages from this peer
-    #[serde(default = "default_true")]
-    pub accept_cdm: bool,
-    
-    /// Accept object state messages from this peer
-    #[serde(default = "default_true")]
-    pub accept_object_state: bool,
-    
-    /// Accept maneuver messages from this peer
-    #[serde(default = "default_true")]
-    pub accept_maneuver: bool,
-    
-    /// Forward CDMs received from this peer to other peers
-    #[serde(default = "default_true")]
-    pub forward_cdm: bool,
+/// A named, partial set of peer policies
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PolicyTemplate {
+    /// Template whose policies this one starts from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Policy keys set by this template, as in a peer's `policies`
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub policies: Mapping,
 }
 
-fn default_true() -> bool {
-    true
+/// A named group of peers sharing policies
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PeerGroupConfig {
+    /// Template the group's policies start from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// Policy keys set for the group, over those of its template
+    #[serde(default, skip_serializing_if = "Mapping::is_empty")]
+    #[schema(value_type = Object)]
+    pub policies: Mapping,
 }
 
-/// Storage configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StorageConfig {
-    /// Storage type: "memory" or "file"
-    #[serde(default = "default_storage_type")]
-    pub storage_type: String,
-    
-    /// File path for file-based storage
+/// Set each peer's policies, and those of the discovery template, from its
+/// group: the group's template chain, then the group's own policies, then
+/// the peer's, each overriding the one before key by key
+///
+/// Resolved peers keep their `group` and carry full policies, so resolving
+/// a configuration again leaves it as it is.
+fn resolve_peer_groups(document: &mut Value) -> Result<()> {
+    let templates = document.get("policy_templates").cloned().unwrap_or(Value::Null);
+    let groups = document.get("peer_groups").cloned().unwrap_or(Value::Null);
+
+    // Every template must resolve and parse, whether a group uses it or not
+    if let Some(names) = templates.as_mapping() {
+        for name in names.keys() {
+            let name = name.as_str().unwrap_or_default();
+            let policies = template_policies(&templates, name, &mut Vec::new())?;
+            serde_yaml::from_value::<PeerPolicies>(policies)
+                .map_err(|e| Error::Config(format!("policy_templates {}: {}", name, e)))?;
+        }
+    }
+    if let Some(groups) = groups.as_mapping() {
+        for (name, group) in groups {
+            if let Some(template) = group.get("template") {
+                let template = template.as_str().unwrap_or_default();
+                if templates.get(template).is_none() {
+                    return Err(Error::Config(format!(
+                        "peer_groups {}: unknown policy template {}",
+                        name.as_str().unwrap_or_default(),
+                        template
+                    )));
+                }
+            }
+        }
+    }
+
+    let resolve = |owner: String, peer: &mut Value| -> Result<()> {
+        let Some(name) = peer.get("group") else {
+            return Ok(());
+        };
+        let name = name.as_str().unwrap_or_default().to_string();
+        let group = groups
+            .get(&name)
+            .ok_or_else(|| Error::Config(format!("{}: unknown peer group {}", owner, name)))?;
+        let mut policies = match group.get("template").and_then(Value::as_str) {
+            Some(template) => template_policies(&templates, template, &mut Vec::new())?,
+            None => Value::Mapping(Mapping::new()),
+        };
+        for layer in [group.get("policies"), peer.get("policies")].into_iter().flatten() {
+            merge_policies(&mut policies, layer);
+        }
+        if let Some(peer) = peer.as_mapping_mut() {
+            peer.insert("policies".into(), policies);
+        }
+        Ok(())
+    };
+    if let Some(peers) = document.get_mut("peers").and_then(Value::as_sequence_mut) {
+        for peer in peers {
+            let owner = format!("peers {}", peer.get("id").and_then(Value::as_str).unwrap_or("?"));
+            resolve(owner, peer)?;
+        }
+    }
+    if let Some(template) = document.get_mut("discovery").and_then(|d| d.get_mut("template")) {
+        resolve("discovery.template".to_string(), template)?;
+    }
+    Ok(())
+}
+
+/// A template's policies, over those of the templates it extends
+fn template_policies(templates: &Value, name: &str, chain: &mut Vec<String>) -> Result<Value> {
+    if chain.iter().any(|seen| seen == name) {
+        chain.push(name.to_string());
+        return Err(Error::Config(format!("policy_templates extend in a loop: {}", chain.join(" -> "))));
+    }
+    let template = templates
+        .get(name)
+        .ok_or_else(|| Error::Config(format!("unknown policy template {}", name)))?;
+    chain.push(name.to_string());
+    let mut policies = match template.get("extends").and_then(Value::as_str) {
+        Some(parent) => template_policies(templates, parent, chain)?,
+        None => Value::Mapping(Mapping::new()),
+    };
+    let mut own = template.clone();
+    if let Some(own) = own.as_mapping_mut() {
+        own.remove("extends");
+    }
+    merge_policies(&mut policies, &own);
+    Ok(policies)
+}
+
+/// Set the keys of `layer` in `policies`, merging nested sections such as
+/// `redact`
+fn merge_policies(policies: &mut Value, layer: &Value) {
+    let (Some(policies), Some(layer)) = (policies.as_mapping_mut(), layer.as_mapping()) else {
+        return;
+    };
+    for (key, value) in layer {
+        match policies.get_mut(key) {
+            Some(existing) if existing.is_mapping() && value.is_mapping() => merge_policies(existing, value),
+            _ => {
+                policies.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Peer session transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerTransport {
+    /// One HTTP POST per envelope to the protocol endpoint
+    #[default]
+    Http,
+    /// Bidirectional gRPC envelope stream (requires `GRPC_STREAM` capability)
+    Grpc,
+}
+
+/// Peer routing policies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PeerPolicies {
+    /// Accept CDM messages from this peer
+    #[serde(default = "default_true")]
+    pub accept_cdm: bool,
+    
+    /// Accept object state messages from this peer
+    #[serde(default = "default_true")]
+    pub accept_object_state: bool,
+    
+    /// Accept maneuver messages from this peer
+    #[serde(default = "default_true")]
+    pub accept_maneuver: bool,
+    
+    /// Forward CDMs received from this peer to other peers
+    #[serde(default = "default_true")]
+    pub forward_cdm: bool,
+
+    /// Answer CDM_REQUEST queries from this peer
+    #[serde(default = "default_true")]
+    pub serve_cdm_queries: bool,
+
+    /// Screen the shards this peer hands over in SCREENING_REQUEST
+    #[serde(default)]
+    pub serve_screening: bool,
+
+    /// Message types neither taken from nor forwarded to this peer,
+    /// whatever the accept flags say
+    #[serde(default)]
+    pub block_message_types: Vec<MessageType>,
+
+    /// Fields stripped or coarsened in CDMs and object states sent to this peer
+    #[serde(default)]
+    pub redact: RedactionPolicy,
+
+    /// Highest TTL of envelopes forwarded to this peer, and of those from
+    /// it when passed on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ttl: Option<u32>,
+}
+
+impl Default for PeerPolicies {
+    fn default() -> Self {
+        Self {
+            accept_cdm: true,
+            accept_object_state: true,
+            accept_maneuver: true,
+            forward_cdm: true,
+            serve_cdm_queries: true,
+            serve_screening: false,
+            block_message_types: Vec::new(),
+            redact: RedactionPolicy::default(),
+            max_ttl: None,
+        }
+    }
+}
+
+/// Sensitive fields removed or coarsened before CDMs and object states
+/// leave for a peer; the stored originals are not changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RedactionPolicy {
+    /// Remove covariance matrices
+    #[serde(default)]
+    pub drop_covariance: bool,
+
+    /// Round state vector positions to this many km
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_resolution_km: Option<f64>,
+
+    /// Round state vector velocities to this many km/s
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity_resolution_km_s: Option<f64>,
+
+    /// Replace owner/operator names with `REDACTED`
+    #[serde(default)]
+    pub anonymize_owner: bool,
+}
+
+impl RedactionPolicy {
+    /// Whether the policy leaves everything as is
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Storage configuration
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StorageConfig {
+    /// Storage type: "memory" or "file"
+    #[serde(default = "default_storage_type")]
+    pub storage_type: String,
+    
+    /// File path for file-based storage
     #[serde(default)]
     pub file_path: Option<String>,
+
+    /// Object catalog capacity limits
+    #[serde(default)]
+    pub object_limits: ObjectLimitsConfig,
+
+    /// Memory budget for stored records, dedup state and outbound queues
+    #[serde(default)]
+    pub memory: MemoryLimitsConfig,
+
+    /// Width of the TCA buckets grouping CDMs into conjunctions
+    #[serde(default = "default_conjunction_bucket")]
+    pub conjunction_bucket_seconds: u64,
+
+    /// Withdrawn CDMs kept for object history; 0 keeps none
+    #[serde(default = "default_cdm_history_limit")]
+    pub cdm_history_limit: usize,
+
+    /// States kept per object for `GET /objects/{id}/history`; 0 keeps none
+    #[serde(default = "default_object_history_limit")]
+    pub object_history_limit: usize,
+
+    /// How long `Idempotency-Key` responses to `POST /cdm` are remembered
+    #[serde(default = "default_idempotency_ttl")]
+    pub idempotency_ttl_seconds: u64,
+
+    /// Encrypt records written to disk (unencrypted if unset)
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
 }
 
 impl Default for StorageConfig {
@@ -216,6 +1392,13 @@ impl Default for StorageConfig {
         Self {
             storage_type: default_storage_type(),
             file_path: None,
+            object_limits: ObjectLimitsConfig::default(),
+            memory: MemoryLimitsConfig::default(),
+            conjunction_bucket_seconds: default_conjunction_bucket(),
+            cdm_history_limit: default_cdm_history_limit(),
+            object_history_limit: default_object_history_limit(),
+            idempotency_ttl_seconds: default_idempotency_ttl(),
+            encryption: None,
         }
     }
 }
@@ -224,8 +1407,203 @@ fn default_storage_type() -> String {
     "memory".to_string()
 }
 
+fn default_conjunction_bucket() -> u64 {
+    300
+}
+
+fn default_cdm_history_limit() -> usize {
+    1000
+}
+
+fn default_object_history_limit() -> usize {
+    32
+}
+
+fn default_idempotency_ttl() -> u64 {
+    86400
+}
+
+/// AES-256-GCM encryption of records at rest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EncryptionConfig {
+    /// The first key encrypts new records; all of them decrypt, so a
+    /// retired key stays listed until nothing sealed with it is left
+    pub keys: Vec<EncryptionKeyConfig>,
+}
+
+/// One encryption key and where to read it; exactly one source is set
+///
+/// Keys are 32 bytes, base64 encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EncryptionKeyConfig {
+    /// Recorded with everything the key encrypts
+    pub id: String,
+
+    /// The key itself
+    #[serde(default)]
+    pub key: Option<String>,
+
+    /// Environment variable holding the key
+    #[serde(default)]
+    pub key_env: Option<String>,
+
+    /// Shell command printing the key, e.g. a KMS or vault client
+    #[serde(default)]
+    pub key_command: Option<String>,
+}
+
+impl EncryptionConfig {
+    /// Describe the first unusable entry
+    fn check(&self) -> std::result::Result<(), String> {
+        if self.keys.is_empty() {
+            return Err("at least one key is required".into());
+        }
+        let mut ids = std::collections::HashSet::new();
+        for key in &self.keys {
+            if key.id.is_empty() || key.id.len() > u8::MAX as usize {
+                return Err("key ids must be 1-255 bytes".into());
+            }
+            if !ids.insert(&key.id) {
+                return Err(format!("duplicate key id {}", key.id));
+            }
+            let sources = [key.key.is_some(), key.key_env.is_some(), key.key_command.is_some()];
+            if sources.iter().filter(|set| **set).count() != 1 {
+                return Err(format!("key {} needs exactly one of key, key_env and key_command", key.id));
+            }
+            if let Some(literal) = &key.key {
+                crate::storage::decode_key(literal).map_err(|e| format!("key {}: {}", key.id, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Object catalog capacity limits
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ObjectLimitsConfig {
+    /// Maximum number of tracked objects (unlimited if unset)
+    #[serde(default)]
+    pub max_objects: Option<usize>,
+
+    /// Maximum number of objects from a single untrusted source node
+    #[serde(default)]
+    pub max_objects_per_source: Option<usize>,
+
+    /// What happens to a new object when the catalog is full
+    #[serde(default)]
+    pub eviction: EvictionPolicy,
+
+    /// Source nodes whose objects are evicted last and exempt from quotas
+    /// (the local node is always trusted)
+    #[serde(default)]
+    pub trusted_sources: Vec<String>,
+
+    /// Catalog fill percentage that raises a capacity alert
+    #[serde(default = "default_capacity_alert_percent")]
+    pub alert_percent: u8,
+}
+
+impl Default for ObjectLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_objects: None,
+            max_objects_per_source: None,
+            eviction: EvictionPolicy::default(),
+            trusted_sources: Vec::new(),
+            alert_percent: default_capacity_alert_percent(),
+        }
+    }
+}
+
+fn default_capacity_alert_percent() -> u8 {
+    90
+}
+
+/// Node memory budget
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryLimitsConfig {
+    /// Maximum estimated bytes for CDMs, objects, dedup state and outbound
+    /// queues; accepts a byte count or a size such as "2GB" or "512MiB"
+    /// (unlimited if unset)
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    #[schema(value_type = Option<ByteSize>)]
+    pub max_bytes: Option<usize>,
+
+    /// What happens to a new record when the budget is exhausted; with
+    /// `oldest_epoch`, CDMs with the earliest TCA and objects in catalog
+    /// eviction order make room
+    #[serde(default)]
+    pub eviction: EvictionPolicy,
+
+    /// Budget fill percentage that raises a memory alert
+    #[serde(default = "default_capacity_alert_percent")]
+    pub alert_percent: u8,
+}
+
+impl Default for MemoryLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            eviction: EvictionPolicy::default(),
+            alert_percent: default_capacity_alert_percent(),
+        }
+    }
+}
+
+/// Parse a byte size such as "1048576", "512MiB" or "2GB"
+pub fn parse_byte_size(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let multiplier: usize = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// A byte size as written in configuration
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+enum ByteSize {
+    /// A count of bytes
+    Bytes(usize),
+    /// A size with a unit, such as "2GB" or "512MiB"
+    Text(String),
+}
+
+fn deserialize_byte_size<'de, D>(deserializer: D) -> std::result::Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<ByteSize>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(ByteSize::Bytes(n)) => Ok(Some(n)),
+        Some(ByteSize::Text(s)) => parse_byte_size(&s)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid byte size: {}", s))),
+    }
+}
+
+/// Catalog eviction policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Reject new objects once the catalog is full
+    #[default]
+    Reject,
+    /// Evict untrusted sources first, then the oldest state vector epoch
+    OldestEpoch,
+}
+
 /// Logging configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct LoggingConfig {
     /// Log level: trace, debug, info, warn, error
     #[serde(default = "default_log_level")]
@@ -254,8 +1632,23 @@ fn default_log_format() -> String {
 }
 
 /// Protocol settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProtocolConfig {
+    /// Highest protocol version offered in HELLO; an older one keeps every
+    /// session on that version during a rolling upgrade
+    #[serde(default = "default_protocol_version")]
+    pub version: String,
+
+    /// Negotiated features, by capability, neither offered to nor used with
+    /// peers
+    #[serde(default)]
+    pub disabled_features: Vec<String>,
+
+    /// Refuse envelopes from senders presenting no peer's `auth_token`;
+    /// when off, peers configured without a token need not present one
+    #[serde(default = "default_true")]
+    pub require_peer_auth: bool,
+
     /// Heartbeat interval in seconds
     #[serde(default = "default_heartbeat_interval")]
     pub heartbeat_interval_seconds: u64,
@@ -267,73 +1660,2116 @@ pub struct ProtocolConfig {
     /// Maximum hop count for message propagation
     #[serde(default = "default_max_hop_count")]
     pub max_hop_count: u32,
+
+    /// TTLs of originated envelopes, and the most passed on, by message type
+    #[serde(default)]
+    pub ttl: TtlConfig,
+
+    /// Default timestamp profile for outbound envelopes
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+
+    /// Maximum encoded size of a received envelope in bytes
+    #[serde(default = "default_max_envelope_bytes")]
+    pub max_envelope_bytes: usize,
+
+    /// Maximum nesting depth of a received payload
+    #[serde(default = "default_max_payload_depth")]
+    pub max_payload_depth: usize,
+
+    /// Reject received envelopes created longer ago than this (0 disables)
+    #[serde(default = "default_max_message_age")]
+    pub max_message_age_seconds: u64,
+
+    /// Reject received envelopes timestamped further ahead than this (0 disables)
+    #[serde(default = "default_max_clock_skew")]
+    pub max_clock_skew_seconds: u64,
+
+    /// Estimating and compensating peers' clock offsets
+    #[serde(default)]
+    pub clock_skew: ClockSkewConfig,
+
+    /// Most CDMs returned in one CDM_RESPONSE, or listed in one
+    /// SYNC_DIGEST reply
+    #[serde(default = "default_max_query_results")]
+    pub max_query_results: usize,
+
+    /// Digest exchanges that find and fetch the CDMs a peer holds and this
+    /// node lacks
+    #[serde(default)]
+    pub sync: SyncConfig,
+
+    /// Acknowledgement and redelivery of CDM announcements and withdrawals
+    #[serde(default)]
+    pub delivery: DeliveryConfig,
+
+    /// Envelopes each peer may forward between two of this node's
+    /// heartbeats, advertised as the credit window (0: no limit)
+    #[serde(default)]
+    pub receive_window: u64,
+
+    /// Thresholds for classifying CDMs received without a category
+    #[serde(default)]
+    pub severity: SeverityConfig,
+
+    /// Refuse CDMs whose data quality score, after covariance checks, is
+    /// below this (0 accepts every CDM)
+    #[serde(default)]
+    pub min_data_quality: f64,
 }
 
 impl Default for ProtocolConfig {
     fn default() -> Self {
         Self {
+            version: default_protocol_version(),
+            disabled_features: Vec::new(),
+            require_peer_auth: true,
             heartbeat_interval_seconds: default_heartbeat_interval(),
             session_timeout_seconds: default_session_timeout(),
             max_hop_count: default_max_hop_count(),
+            ttl: TtlConfig::default(),
+            timestamp_format: TimestampFormat::default(),
+            max_envelope_bytes: default_max_envelope_bytes(),
+            max_payload_depth: default_max_payload_depth(),
+            max_message_age_seconds: default_max_message_age(),
+            max_clock_skew_seconds: default_max_clock_skew(),
+            clock_skew: ClockSkewConfig::default(),
+            max_query_results: default_max_query_results(),
+            sync: SyncConfig::default(),
+            delivery: DeliveryConfig::default(),
+            receive_window: 0,
+            severity: SeverityConfig::default(),
+            min_data_quality: 0.0,
         }
     }
 }
 
-fn default_heartbeat_interval() -> u64 {
-    30
+/// Envelope TTLs by message type
+///
+/// Envelopes this node originates start with their type's TTL, and those it
+/// forwards leave with no more than it, so a type can be kept close to its
+/// origin or sent further than the rest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TtlConfig {
+    /// TTL of message types not listed in `message_types`
+    #[serde(default = "default_ttl")]
+    pub default: u32,
+
+    /// TTL by message type, for relayed types and DELIVERY_RECEIPT
+    #[serde(default)]
+    pub message_types: BTreeMap<MessageType, u32>,
 }
 
-fn default_session_timeout() -> u64 {
-    120
+fn default_ttl() -> u32 {
+    DEFAULT_TTL
 }
 
-fn default_max_hop_count() -> u32 {
-    10
+impl Default for TtlConfig {
+    fn default() -> Self {
+        Self {
+            default: default_ttl(),
+            message_types: BTreeMap::new(),
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
-    use std::io::Write;
+impl TtlConfig {
+    /// TTL of a message type
+    pub fn for_message_type(&self, message_type: &MessageType) -> u32 {
+        self.message_types.get(message_type).copied().unwrap_or(self.default)
+    }
+}
 
-    #[test]
-    fn test_load_config() {
-        let config_content = r#"
-node:
-  id: "test-node"
-  name: "Test Node"
+/// Differential sync with peers offering SYNC_DIGEST
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SyncConfig {
+    /// Time between digest exchanges with each peer, the first as soon as
+    /// a session is up (0 disables)
+    #[serde(default = "default_sync_interval")]
+    pub interval_seconds: u64,
 
-server:
-  host: "127.0.0.1"
-  port: 8080
+    /// Buckets CDM IDs are spread over; more buckets make larger digests
+    /// but smaller replies when nodes differ
+    #[serde(default = "default_digest_buckets")]
+    pub digest_buckets: usize,
+}
 
-peers: []
-"#;
+fn default_sync_interval() -> u64 {
+    600
+}
 
-        let mut file = NamedTempFile::new().unwrap();
-        file.write_all(config_content.as_bytes()).unwrap();
-        
-        let config = Config::load(file.path()).unwrap();
-        assert_eq!(config.node.id, "test-node");
-        assert_eq!(config.server.port, 8080);
+fn default_digest_buckets() -> usize {
+    256
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: default_sync_interval(),
+            digest_buckets: default_digest_buckets(),
+        }
     }
+}
 
-    #[test]
-    fn test_invalid_config_missing_node_id() {
-        let config_content = r#"
-node:
-  id: ""
-  name: "Test"
+/// Delivery of CDM announcements and withdrawals to peers offering ACK
+///
+/// Such peers acknowledge each one they take in. Under at-least-once
+/// delivery, an envelope not acknowledged within `ack_timeout_seconds` is
+/// sent again, up to `max_redeliveries` times, then dead-lettered.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryConfig {
+    #[serde(default)]
+    pub guarantee: DeliveryGuarantee,
 
-server:
-  port: 8080
-"#;
+    /// Time to wait for a peer's ACK before sending again
+    #[serde(default = "default_ack_timeout")]
+    pub ack_timeout_seconds: u64,
 
-        let mut file = NamedTempFile::new().unwrap();
-        file.write_all(config_content.as_bytes()).unwrap();
-        
-        let result = Config::load(file.path());
-        assert!(result.is_err());
+    /// Times an unacknowledged envelope is sent again before giving up
+    #[serde(default = "default_max_redeliveries")]
+    pub max_redeliveries: u32,
+
+    /// Send a DELIVERY_RECEIPT toward the originator of each CDM taken in
+    /// from a peer that involves a watched object
+    #[serde(default = "default_true")]
+    pub receipts: bool,
+}
+
+fn default_ack_timeout() -> u64 {
+    30
+}
+
+fn default_max_redeliveries() -> u32 {
+    3
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            guarantee: DeliveryGuarantee::default(),
+            ack_timeout_seconds: default_ack_timeout(),
+            max_redeliveries: default_max_redeliveries(),
+            receipts: true,
+        }
+    }
+}
+
+/// What a node does about envelopes peers have not acknowledged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryGuarantee {
+    /// Send once; ACKs are only recorded
+    AtMostOnce,
+    /// Send again until acknowledged or out of redeliveries
+    #[default]
+    AtLeastOnce,
+}
+
+/// How peers' clock offsets are handled
+///
+/// Offsets are estimated from HELLO exchanges and heartbeats. Envelopes a
+/// peer originates have their timestamp corrected by its offset before
+/// `max_message_age_seconds` and `max_clock_skew_seconds` are applied.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClockSkewConfig {
+    /// Flag peers whose clock is off by more than this
+    #[serde(default = "default_clock_skew_warn")]
+    pub warn_seconds: u64,
+
+    /// Correct peers' timestamps by their estimated offset
+    #[serde(default = "default_true")]
+    pub correct: bool,
+
+    /// Largest offset compensated; a peer further off is judged on what
+    /// remains
+    #[serde(default = "default_clock_skew_max_correction")]
+    pub max_correction_seconds: u64,
+}
+
+fn default_clock_skew_warn() -> u64 {
+    5
+}
+
+fn default_clock_skew_max_correction() -> u64 {
+    3600
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        Self {
+            warn_seconds: default_clock_skew_warn(),
+            correct: true,
+            max_correction_seconds: default_clock_skew_max_correction(),
+        }
+    }
+}
+
+/// Conjunction severity thresholds
+///
+/// A conjunction takes the higher of its probability tier and its
+/// miss-distance tier.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SeverityConfig {
+    /// Collision probability at or above which a conjunction is HIGH
+    #[serde(default = "default_high_probability")]
+    pub high_probability: f64,
+
+    /// Collision probability at or above which a conjunction is MEDIUM
+    #[serde(default = "default_medium_probability")]
+    pub medium_probability: f64,
+
+    /// Miss distance in meters at or below which a conjunction is HIGH
+    #[serde(default = "default_high_miss_distance")]
+    pub high_miss_distance_m: f64,
+
+    /// Miss distance in meters at or below which a conjunction is MEDIUM
+    #[serde(default = "default_medium_miss_distance")]
+    pub medium_miss_distance_m: f64,
+}
+
+impl Default for SeverityConfig {
+    fn default() -> Self {
+        Self {
+            high_probability: default_high_probability(),
+            medium_probability: default_medium_probability(),
+            high_miss_distance_m: default_high_miss_distance(),
+            medium_miss_distance_m: default_medium_miss_distance(),
+        }
+    }
+}
+
+fn default_high_probability() -> f64 {
+    1e-4
+}
+
+fn default_medium_probability() -> f64 {
+    1e-5
+}
+
+fn default_high_miss_distance() -> f64 {
+    200.0
+}
+
+fn default_medium_miss_distance() -> f64 {
+    1000.0
+}
+
+fn default_heartbeat_interval() -> u64 {
+    30
+}
+
+fn default_session_timeout() -> u64 {
+    120
+}
+
+fn default_protocol_version() -> String {
+    LATEST_VERSION.to_string()
+}
+
+fn default_max_hop_count() -> u32 {
+    10
+}
+
+fn default_max_envelope_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_payload_depth() -> usize {
+    32
+}
+
+fn default_max_message_age() -> u64 {
+    3600
+}
+
+fn default_max_clock_skew() -> u64 {
+    300
+}
+
+fn default_max_query_results() -> usize {
+    500
+}
+
+/// External object catalog settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CatalogConfig {
+    /// Base URL of the catalog service
+    pub url: String,
+
+    /// Query path appended to the URL, `{id}` is replaced by the catalog number
+    #[serde(default = "default_catalog_query_path")]
+    pub query_path: String,
+
+    /// Query path for searches by object name, `{query}` is replaced by the
+    /// name; objects announced by name are not resolved without it
+    #[serde(default)]
+    pub name_query_path: Option<String>,
+
+    /// Query path for searches by international designator, `{query}` is
+    /// replaced by the designator as `YYYY-NNNP`
+    #[serde(default)]
+    pub designator_query_path: Option<String>,
+
+    /// Bearer token sent with catalog queries
+    #[serde(default)]
+    pub auth_token: Option<String>,
+
+    /// How long a cached catalog entry is used before it is re-fetched
+    #[serde(default = "default_catalog_refresh_interval")]
+    pub refresh_interval_seconds: u64,
+
+    /// Catalog query timeout in seconds
+    #[serde(default = "default_catalog_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Maximum number of cached catalog entries
+    #[serde(default = "default_catalog_max_entries")]
+    pub max_entries: usize,
+}
+
+/// Collision probability computation settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PcConfig {
+    /// Default Pc method: foster, chan, alfano or monte_carlo
+    #[serde(default = "default_pc_method")]
+    pub method: String,
+}
+
+impl Default for PcConfig {
+    fn default() -> Self {
+        Self {
+            method: default_pc_method(),
+        }
+    }
+}
+
+fn default_pc_method() -> String {
+    "foster".to_string()
+}
+
+/// Criteria a node must meet to report ready
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessConfig {
+    /// Peers that must be connected (0 disables the check)
+    #[serde(default)]
+    pub min_connected_peers: usize,
+
+    /// Require storage to answer queries
+    #[serde(default = "default_true")]
+    pub check_storage: bool,
+
+    /// Require the last configuration reload to have succeeded
+    #[serde(default = "default_true")]
+    pub check_config: bool,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            min_connected_peers: 0,
+            check_storage: true,
+            check_config: true,
+        }
+    }
+}
+
+/// When payloads and responses are sent compressed
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CompressionConfig {
+    /// Payloads of at least this many bytes of JSON are sent gzip
+    /// compressed to peers that offer `PAYLOAD_GZIP` (0 disables)
+    #[serde(default = "default_min_payload_bytes")]
+    pub min_payload_bytes: usize,
+
+    /// API responses of at least this many bytes are gzip compressed for
+    /// clients that accept it (0 disables)
+    #[serde(default = "default_min_response_bytes")]
+    pub min_response_bytes: usize,
+}
+
+fn default_min_payload_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_min_response_bytes() -> usize {
+    4 * 1024
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            min_payload_bytes: default_min_payload_bytes(),
+            min_response_bytes: default_min_response_bytes(),
+        }
+    }
+}
+
+/// How often statistics are sampled, and how long samples are kept
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StatsConfig {
+    /// Seconds between samples (0 disables the history)
+    #[serde(default = "default_stats_interval")]
+    pub sample_interval_seconds: u64,
+
+    /// Hours samples are kept
+    #[serde(default = "default_stats_retention")]
+    pub retention_hours: u64,
+}
+
+fn default_stats_interval() -> u64 {
+    60
+}
+
+fn default_stats_retention() -> u64 {
+    24
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval_seconds: default_stats_interval(),
+            retention_hours: default_stats_retention(),
+        }
+    }
+}
+
+/// CDM acceptance rules, checked in order on every CDM the node takes in
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ValidationConfig {
+    #[serde(default)]
+    pub rules: Vec<ValidationRule>,
+}
+
+/// Object identifier aliases
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct IdentifiersConfig {
+    /// Object IDs by alias, such as an international designator or a
+    /// provider's own name for the object
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl IdentifiersConfig {
+    /// Canonical form of an object ID
+    pub fn canonical(&self, object_id: &str) -> String {
+        canonical_object_id(object_id, &self.aliases)
+    }
+}
+
+/// Provenance signing settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ProvenanceConfig {
+    /// Ed25519 seed, base64, signing this node's hops (unsigned if unset)
+    #[serde(default)]
+    pub signing_key: Option<String>,
+
+    /// Public keys, base64, that other nodes' hops are verified with, by
+    /// node ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trusted_keys: BTreeMap<String, String>,
+}
+
+impl ProvenanceConfig {
+    /// Signer for this node's hops, when a key is set
+    pub fn signer(&self) -> Option<ProvenanceSigner> {
+        self.signing_key.as_deref().and_then(|seed| ProvenanceSigner::from_seed(seed).ok())
+    }
+}
+
+/// How peers are scored on their recent exchanges, and when they are
+/// quarantined
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PeerHealthConfig {
+    /// Recent exchanges a peer's score is taken over
+    #[serde(default = "default_health_window")]
+    pub window: usize,
+
+    /// Exchanges counted before a peer can be quarantined
+    #[serde(default = "default_health_min_samples")]
+    pub min_samples: usize,
+
+    /// Share of failed exchanges above which a peer is quarantined
+    #[serde(default = "default_max_error_rate")]
+    pub max_error_rate: f64,
+
+    /// How long a quarantine lasts (0 disables quarantine)
+    #[serde(default = "default_quarantine_seconds")]
+    pub quarantine_seconds: u64,
+}
+
+fn default_health_window() -> usize {
+    100
+}
+
+fn default_health_min_samples() -> usize {
+    20
+}
+
+fn default_max_error_rate() -> f64 {
+    0.5
+}
+
+fn default_quarantine_seconds() -> u64 {
+    300
+}
+
+impl Default for PeerHealthConfig {
+    fn default() -> Self {
+        Self {
+            window: default_health_window(),
+            min_samples: default_health_min_samples(),
+            max_error_rate: default_max_error_rate(),
+            quarantine_seconds: default_quarantine_seconds(),
+        }
+    }
+}
+
+/// Limits on the CDMs taken from each originator, by `POST /cdm` or from
+/// peers
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IngestQuotaConfig {
+    /// CDMs accepted from one originator in a minute (0 is unlimited)
+    #[serde(default)]
+    pub max_cdms_per_minute: u32,
+
+    /// How many times its usual rate an originator must send in a minute
+    /// for a rate spike (0 disables spike detection)
+    #[serde(default = "default_spike_factor")]
+    pub spike_factor: f64,
+
+    /// CDMs in a minute below which no rate spike is flagged
+    #[serde(default = "default_spike_min_cdms")]
+    pub spike_min_cdms: u32,
+
+    /// Copies of one payload, whatever their CDM IDs, an originator may
+    /// send in a minute before it is flagged (0 disables)
+    #[serde(default = "default_max_duplicate_payloads")]
+    pub max_duplicate_payloads: u32,
+
+    /// What happens to an originator flagged for an anomaly
+    #[serde(default)]
+    pub action: AnomalyAction,
+
+    /// How long a throttled originator's CDMs are refused
+    #[serde(default = "default_throttle_seconds")]
+    pub throttle_seconds: u64,
+}
+
+fn default_spike_factor() -> f64 {
+    10.0
+}
+
+fn default_spike_min_cdms() -> u32 {
+    100
+}
+
+fn default_max_duplicate_payloads() -> u32 {
+    20
+}
+
+fn default_throttle_seconds() -> u64 {
+    300
+}
+
+impl Default for IngestQuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_cdms_per_minute: 0,
+            spike_factor: default_spike_factor(),
+            spike_min_cdms: default_spike_min_cdms(),
+            max_duplicate_payloads: default_max_duplicate_payloads(),
+            action: AnomalyAction::default(),
+            throttle_seconds: default_throttle_seconds(),
+        }
+    }
+}
+
+/// What happens to an originator flagged for an anomaly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyAction {
+    /// Refuse its CDMs for `throttle_seconds`
+    #[default]
+    Throttle,
+    /// Refuse its CDMs until an operator releases it
+    Quarantine,
+}
+
+/// Per-originator trust used when fusing CDMs for one conjunction
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FusionConfig {
+    /// Weight of originators not listed below
+    #[serde(default = "default_fusion_weight")]
+    pub default_weight: f64,
+
+    /// Weight by originator; 0 leaves an originator out of the fused figure
+    #[serde(default)]
+    pub originator_weights: std::collections::BTreeMap<String, f64>,
+}
+
+impl FusionConfig {
+    /// Trust weight for an originator
+    pub fn weight(&self, originator: &str) -> f64 {
+        self.originator_weights
+            .get(originator)
+            .copied()
+            .unwrap_or(self.default_weight)
+    }
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        Self {
+            default_weight: default_fusion_weight(),
+            originator_weights: Default::default(),
+        }
+    }
+}
+
+fn default_fusion_weight() -> f64 {
+    1.0
+}
+
+/// How envelopes are forwarded to peers
+///
+/// Each peer has its own queue and concurrency limit, so a slow peer only
+/// delays (and eventually drops) its own envelopes.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FanoutConfig {
+    /// Sends to one peer running at the same time
+    #[serde(default = "default_max_in_flight_per_peer")]
+    pub max_in_flight_per_peer: usize,
+
+    /// Envelopes waiting for one peer before new ones are dropped
+    #[serde(default = "default_queue_per_peer")]
+    pub queue_per_peer: usize,
+
+    /// Time allowed for a single send attempt in milliseconds
+    #[serde(default = "default_send_timeout_ms")]
+    pub send_timeout_ms: u64,
+
+    /// Retries after a failed or timed out send
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry in milliseconds; doubles with each
+    /// retry and is jittered by up to half
+    #[serde(default = "default_retry_base_ms")]
+    pub retry_base_ms: u64,
+
+    /// Consecutive failed deliveries that open a peer's circuit (0 disables)
+    #[serde(default = "default_breaker_failures")]
+    pub breaker_failures: u32,
+
+    /// Seconds an open circuit drops envelopes before a trial delivery
+    #[serde(default = "default_breaker_cooldown")]
+    pub breaker_cooldown_seconds: u64,
+
+    /// Priority classes, most urgent first; queued envelopes go out in
+    /// class order, and envelopes matching no class go last
+    #[serde(default = "default_priority_classes")]
+    pub priority_classes: Vec<PriorityClass>,
+
+    /// Envelopes sent ahead of a waiting, less urgent class before one of
+    /// its envelopes goes (0: strict priority)
+    #[serde(default = "default_starvation_limit")]
+    pub starvation_limit: u32,
+
+    /// Coalescing of envelopes into ENVELOPE_BATCH requests, for peers
+    /// that offer BATCHING over HTTP
+    #[serde(default)]
+    pub batch: BatchConfig,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight_per_peer: default_max_in_flight_per_peer(),
+            queue_per_peer: default_queue_per_peer(),
+            send_timeout_ms: default_send_timeout_ms(),
+            max_retries: default_max_retries(),
+            retry_base_ms: default_retry_base_ms(),
+            breaker_failures: default_breaker_failures(),
+            breaker_cooldown_seconds: default_breaker_cooldown(),
+            priority_classes: default_priority_classes(),
+            starvation_limit: default_starvation_limit(),
+            batch: BatchConfig::default(),
+        }
+    }
+}
+
+fn default_max_in_flight_per_peer() -> usize {
+    4
+}
+
+fn default_queue_per_peer() -> usize {
+    1000
+}
+
+fn default_send_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_base_ms() -> u64 {
+    200
+}
+
+fn default_breaker_failures() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown() -> u64 {
+    30
+}
+
+fn default_priority_classes() -> Vec<PriorityClass> {
+    vec![
+        PriorityClass {
+            name: "emergency".to_string(),
+            message_types: vec![MessageType::CdmAnnounce],
+            min_severity: Some(ConjunctionCategory::High),
+            screen_types: vec![ScreenType::Emergency],
+        },
+        PriorityClass {
+            name: "conjunction".to_string(),
+            message_types: vec![
+                MessageType::CdmAnnounce,
+                MessageType::CdmWithdraw,
+                MessageType::ManeuverIntent,
+                MessageType::ManeuverStatus,
+            ],
+            min_severity: None,
+            screen_types: Vec::new(),
+        },
+    ]
+}
+
+fn default_starvation_limit() -> u32 {
+    8
+}
+
+/// Batching of envelopes to one peer
+///
+/// Whenever a send slot frees up, the envelopes then waiting for the peer
+/// go in one request, up to these limits.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchConfig {
+    /// Most envelopes in one batch (1 disables batching)
+    #[serde(default = "default_batch_max_envelopes")]
+    pub max_envelopes: usize,
+
+    /// Approximate largest batch in bytes; keep it under the peers'
+    /// `protocol.max_envelope_bytes`
+    #[serde(default = "default_batch_max_bytes")]
+    pub max_bytes: usize,
+
+    /// Milliseconds to wait for more envelopes before sending a batch that
+    /// is not full (0 sends what is waiting at once)
+    #[serde(default)]
+    pub max_delay_ms: u64,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_envelopes: default_batch_max_envelopes(),
+            max_bytes: default_batch_max_bytes(),
+            max_delay_ms: 0,
+        }
+    }
+}
+
+fn default_batch_max_envelopes() -> usize {
+    100
+}
+
+fn default_batch_max_bytes() -> usize {
+    512 * 1024
+}
+
+/// Name of the class for envelopes matching no priority class
+pub const DEFAULT_PRIORITY_CLASS: &str = "routine";
+
+/// A forwarding priority class
+///
+/// An envelope is in the class if its type is listed and, when the class
+/// sets `min_severity` or `screen_types`, it is a CDM announcement meeting
+/// either one.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PriorityClass {
+    pub name: String,
+
+    /// Message types in the class; empty matches any
+    #[serde(default)]
+    pub message_types: Vec<MessageType>,
+
+    /// CDMs at or above this conjunction category
+    #[serde(default)]
+    pub min_severity: Option<ConjunctionCategory>,
+
+    /// CDMs from these screening types
+    #[serde(default)]
+    pub screen_types: Vec<ScreenType>,
+}
+
+/// TCA countdown alerts
+///
+/// Active CDMs are checked against a ladder of time-to-TCA thresholds. Each
+/// threshold a conjunction crosses raises its recommended action one step
+/// and is announced on the event feed and to the webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AlertsConfig {
+    /// Check active CDMs on a timer
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Hours before TCA at which a conjunction escalates
+    #[serde(default = "default_alert_thresholds")]
+    pub thresholds_hours: Vec<u64>,
+
+    /// Least severe category that escalates
+    #[serde(default = "default_alert_min_category")]
+    pub min_category: ConjunctionCategory,
+
+    /// Seconds between checks
+    #[serde(default = "default_alert_interval")]
+    pub check_interval_seconds: u64,
+
+    /// Growth in collision probability between successive CDMs of a
+    /// conjunction that flags its risk as increasing; such conjunctions
+    /// escalate whatever their category
+    #[serde(default = "default_risk_increase_factor")]
+    pub risk_increase_factor: f64,
+
+    /// URLs each escalation event and object catalog capacity alert is
+    /// POSTed to as JSON
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds_hours: default_alert_thresholds(),
+            min_category: default_alert_min_category(),
+            check_interval_seconds: default_alert_interval(),
+            risk_increase_factor: default_risk_increase_factor(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+fn default_alert_thresholds() -> Vec<u64> {
+    vec![72, 24, 6, 1]
+}
+
+fn default_alert_min_category() -> ConjunctionCategory {
+    ConjunctionCategory::Medium
+}
+
+fn default_alert_interval() -> u64 {
+    60
+}
+
+fn default_risk_increase_factor() -> f64 {
+    2.0
+}
+
+/// Notification channels for operator alerts
+///
+/// Each channel is sent the alerts at or above its severity and, unless
+/// turned off, the TCA escalations of alerted conjunctions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+}
+
+impl NotificationsConfig {
+    fn validate(&self) -> Result<()> {
+        for (i, channel) in self.channels.iter().enumerate() {
+            if channel.id.is_empty() || self.channels[..i].iter().any(|c| c.id == channel.id) {
+                return Err(Error::Config(format!(
+                    "notifications.channels IDs must be non-empty and unique (entry {})",
+                    i
+                )));
+            }
+            if channel.max_per_hour == 0 {
+                return Err(Error::Config(format!(
+                    "notifications channel {}: max_per_hour must be non-zero",
+                    channel.id
+                )));
+            }
+            let url = match &channel.kind {
+                ChannelKind::Slack { webhook_url } => webhook_url,
+                ChannelKind::Webhook { url } => url,
+                ChannelKind::Smtp(smtp) => {
+                    if smtp.host.is_empty() || smtp.from.is_empty() || smtp.to.is_empty() {
+                        return Err(Error::Config(format!(
+                            "notifications channel {}: smtp needs host, from and at least one to address",
+                            channel.id
+                        )));
+                    }
+                    if smtp.username.is_some() != smtp.password.is_some() {
+                        return Err(Error::Config(format!(
+                            "notifications channel {}: set both username and password, or neither",
+                            channel.id
+                        )));
+                    }
+                    continue;
+                }
+            };
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(Error::Config(format!(
+                    "notifications channel {}: {} must be an http(s) URL",
+                    channel.id, url
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One destination for alert notifications
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChannelConfig {
+    pub id: String,
+
+    #[serde(flatten)]
+    pub kind: ChannelKind,
+
+    /// Least severe alert sent
+    #[serde(default = "default_channel_min_severity")]
+    pub min_severity: ConjunctionCategory,
+
+    /// Also send TCA escalations of alerted conjunctions
+    #[serde(default = "default_true")]
+    pub escalations: bool,
+
+    /// Notifications sent in any hour at most; the rest are recorded on
+    /// their alerts as rate limited
+    #[serde(default = "default_channel_max_per_hour")]
+    pub max_per_hour: u32,
+}
+
+/// How a channel delivers, selected by `type`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelKind {
+    /// Email through an SMTP relay
+    Smtp(SmtpChannelConfig),
+    /// Slack incoming webhook
+    Slack { webhook_url: String },
+    /// JSON POST of the alert notice
+    Webhook { url: String },
+}
+
+/// SMTP relay settings
+///
+/// Mail is sent over plain SMTP, so point this at a relay on a trusted
+/// network (such as a local Postfix) that handles TLS onward.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SmtpChannelConfig {
+    pub host: String,
+
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+
+    /// Sender address
+    pub from: String,
+
+    /// Recipient addresses
+    pub to: Vec<String>,
+
+    /// Credentials for AUTH PLAIN, when the relay requires them
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+fn default_channel_min_severity() -> ConjunctionCategory {
+    ConjunctionCategory::High
+}
+
+fn default_channel_max_per_hour() -> u32 {
+    60
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+/// Developer mode settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DevConfig {
+    /// Interval between generated traffic batches in seconds
+    #[serde(default = "default_traffic_interval")]
+    pub traffic_interval_seconds: u64,
+}
+
+impl Default for DevConfig {
+    fn default() -> Self {
+        Self {
+            traffic_interval_seconds: default_traffic_interval(),
+        }
+    }
+}
+
+fn default_traffic_interval() -> u64 {
+    5
+}
+
+fn default_catalog_query_path() -> String {
+    "/catalog?norad_id={id}".to_string()
+}
+
+fn default_catalog_refresh_interval() -> u64 {
+    3600
+}
+
+fn default_catalog_timeout() -> u64 {
+    5
+}
+
+fn default_catalog_max_entries() -> usize {
+    100_000
+}
+
+/// Retention and archival settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveConfig {
+    /// Directory holding the archive files
+    pub directory: String,
+
+    /// Seconds between retention sweeps
+    #[serde(default = "default_archive_interval")]
+    pub interval_seconds: u64,
+
+    /// Hours after TCA at which a CDM expires
+    #[serde(default = "default_cdm_expiry_hours")]
+    pub cdm_expiry_hours: u64,
+
+    /// Hours without an update after which an object state is stale
+    #[serde(default = "default_object_stale_hours")]
+    pub object_stale_hours: u64,
+
+    /// Gzip the archive files
+    #[serde(default = "default_true")]
+    pub compress: bool,
+}
+
+fn default_archive_interval() -> u64 {
+    60
+}
+
+fn default_cdm_expiry_hours() -> u64 {
+    24
+}
+
+fn default_object_stale_hours() -> u64 {
+    168
+}
+
+/// Peer discovery settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DiscoveryConfig {
+    /// Seconds between discovery rounds
+    #[serde(default = "default_discovery_interval")]
+    pub refresh_interval_seconds: u64,
+
+    /// Remove discovered peers once discovery no longer finds them; peers
+    /// from the configuration file or the API are never removed
+    #[serde(default)]
+    pub remove_missing: bool,
+
+    /// Settings given to every discovered peer
+    #[serde(default)]
+    pub template: PeerTemplate,
+
+    /// Fixed peer addresses
+    #[serde(default)]
+    pub seeds: Vec<DiscoverySeed>,
+
+    /// DNS SRV names listing peers
+    #[serde(default)]
+    pub dns: Vec<DnsDiscoveryConfig>,
+}
+
+fn default_discovery_interval() -> u64 {
+    300
+}
+
+/// Peer settings applied to discovered peers, as in `peers` entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PeerTemplate {
+    #[serde(default)]
+    pub auth_token: Option<String>,
+
+    #[serde(default)]
+    pub transport: PeerTransport,
+
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    #[serde(default)]
+    pub timestamp_format: Option<TimestampFormat>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    #[serde(default)]
+    pub policies: PeerPolicies,
+}
+
+impl PeerTemplate {
+    /// Configuration for a peer found at `address`
+    pub fn peer(&self, id: &str, address: &str) -> PeerConfig {
+        PeerConfig {
+            id: id.to_string(),
+            address: address.to_string(),
+            auth_token: self.auth_token.clone(),
+            transport: self.transport,
+            encoding: self.encoding,
+            timestamp_format: self.timestamp_format,
+            group: self.group.clone(),
+            policies: self.policies.clone(),
+        }
+    }
+}
+
+/// A peer address to probe
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DiscoverySeed {
+    /// Base URL of the peer's node
+    pub address: String,
+
+    /// Node ID of the peer; learned from its HELLO reply when unset
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+/// A DNS SRV name whose records are peer nodes
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DnsDiscoveryConfig {
+    /// SRV name, e.g. `_spacecomms._tcp.example.org`
+    pub name: String,
+
+    /// URL scheme of the discovered addresses
+    #[serde(default = "default_dns_scheme")]
+    pub scheme: String,
+
+    /// DNS server to ask (`ip` or `ip:port`); the first `nameserver` in
+    /// `/etc/resolv.conf` when unset
+    #[serde(default)]
+    pub nameserver: Option<String>,
+}
+
+fn default_dns_scheme() -> String {
+    "http".to_string()
+}
+
+/// A nameserver setting as a socket address, port 53 unless given
+pub fn parse_nameserver(nameserver: &str) -> Option<std::net::SocketAddr> {
+    nameserver
+        .parse()
+        .ok()
+        .or_else(|| nameserver.parse::<std::net::IpAddr>().ok().map(|ip| (ip, 53).into()))
+}
+
+/// Leader election settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HaConfig {
+    /// This instance's name, unique among the instances of the node
+    pub instance_id: String,
+
+    /// How long the leader's lease lasts without a renewal
+    #[serde(default = "default_lease_seconds")]
+    pub lease_seconds: u64,
+
+    /// Seconds between lease renewals, and between takeover attempts by
+    /// followers
+    #[serde(default = "default_renew_interval_seconds")]
+    pub renew_interval_seconds: u64,
+}
+
+fn default_lease_seconds() -> u64 {
+    30
+}
+
+fn default_renew_interval_seconds() -> u64 {
+    10
+}
+
+/// OpenTelemetry trace export settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TelemetryConfig {
+    /// OTLP/gRPC collector endpoint
+    #[serde(default = "default_otlp_endpoint")]
+    pub otlp_endpoint: String,
+
+    /// `service.name` reported with every span
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+
+    /// Fraction of new traces recorded; traces continued from a peer
+    /// follow the peer's decision
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_service_name() -> String {
+    "spacecomms".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+/// Conjunction screening settings
+///
+/// The catalog is screened every `interval_seconds` over the next
+/// `window_hours`. A close approach becomes a CDM when it falls inside the
+/// screening volume of its orbital regime and either its Pc reaches the
+/// regime's `min_pc` or its miss distance is at most `max_miss_distance_m`.
+/// Pc needs both objects' covariances; without them only the miss distance
+/// can trigger. Pairs with a CDM are screened again every
+/// `emergency.interval_seconds` once their TCA is within
+/// `emergency.within_hours`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScreeningConfig {
+    /// Seconds between routine screenings of the whole catalog
+    #[serde(default = "default_screening_interval")]
+    pub interval_seconds: u64,
+
+    /// Hours ahead screened by a routine screening
+    #[serde(default = "default_screening_window")]
+    pub window_hours: u64,
+
+    /// Seconds between the coarse distance samples
+    #[serde(default = "default_screening_step")]
+    pub step_seconds: u64,
+
+    /// Width of the perigee and apogee bands the catalog is partitioned by
+    /// (km)
+    #[serde(default = "default_altitude_band")]
+    pub altitude_band_km: f64,
+
+    /// Width of the inclination bands the catalog is partitioned by
+    /// (degrees)
+    #[serde(default = "default_inclination_band")]
+    pub inclination_band_deg: f64,
+
+    /// Worker tasks screening in parallel (one per CPU when 0)
+    #[serde(default)]
+    pub workers: usize,
+
+    /// Peers sharing the shards of each screening; those connected and
+    /// offering SCREENING each take a part, and a part a peer fails to
+    /// screen is screened here
+    #[serde(default)]
+    pub worker_nodes: Vec<String>,
+
+    /// Combined hard-body radius reported in the CDMs and used for Pc
+    #[serde(default = "default_hard_body_radius")]
+    pub hard_body_radius_m: f64,
+
+    /// Re-screening of pairs with a CDM as their TCA nears
+    #[serde(default)]
+    pub emergency: EmergencyScreeningConfig,
+
+    /// Screening volume and CDM triggers for each orbital regime
+    #[serde(default)]
+    pub regimes: RegimePolicies,
+}
+
+impl ScreeningConfig {
+    fn validate(&self) -> Result<()> {
+        if self.interval_seconds == 0 || self.step_seconds == 0 || self.window_hours == 0 {
+            return Err(Error::Config(
+                "screening.interval_seconds, screening.step_seconds and screening.window_hours must be non-zero".into(),
+            ));
+        }
+        if self.window_hours > MAX_PROPAGATION_DAYS as u64 * 24 {
+            return Err(Error::Config(format!(
+                "screening.window_hours must not exceed {} days",
+                MAX_PROPAGATION_DAYS
+            )));
+        }
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+        if !positive(self.altitude_band_km) || !positive(self.inclination_band_deg) || !positive(self.hard_body_radius_m) {
+            return Err(Error::Config(
+                "screening.altitude_band_km, screening.inclination_band_deg and screening.hard_body_radius_m must be positive".into(),
+            ));
+        }
+        let mut worker_nodes = std::collections::HashSet::new();
+        if let Some(id) = self.worker_nodes.iter().find(|id| id.is_empty() || !worker_nodes.insert(*id)) {
+            return Err(Error::Config(format!("screening.worker_nodes: {:?} is empty or listed twice", id)));
+        }
+        let emergency = &self.emergency;
+        if emergency.within_hours == 0 || emergency.interval_seconds == 0 || emergency.interval_seconds > self.interval_seconds {
+            return Err(Error::Config(
+                "screening.emergency.within_hours must be non-zero and screening.emergency.interval_seconds non-zero and at most screening.interval_seconds".into(),
+            ));
+        }
+        for (regime, policy) in self.regimes.iter() {
+            let volume = &policy.volume;
+            if ![volume.radial_km, volume.in_track_km, volume.cross_track_km].into_iter().all(positive) {
+                return Err(Error::Config(format!("screening.regimes.{}.volume extents must be positive", regime)));
+            }
+            if !(policy.min_pc > 0.0 && policy.min_pc <= 1.0) {
+                return Err(Error::Config(format!("screening.regimes.{}.min_pc must be above 0 and at most 1", regime)));
+            }
+            if !(policy.max_miss_distance_m >= 0.0 && policy.max_miss_distance_m.is_finite()) {
+                return Err(Error::Config(format!(
+                    "screening.regimes.{}.max_miss_distance_m must be non-negative",
+                    regime
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Screening run options; the screening distance is the largest extent
+    /// of any regime's volume
+    pub fn options(&self, window: chrono::Duration) -> ScreeningOptions {
+        let threshold_km = self
+            .regimes
+            .iter()
+            .map(|(_, policy)| policy.volume.largest_extent_km())
+            .fold(0.0, f64::max);
+        let defaults = ScreeningOptions::default();
+        ScreeningOptions {
+            threshold_km,
+            window,
+            step_seconds: self.step_seconds as f64,
+            altitude_band_km: self.altitude_band_km,
+            inclination_band_deg: self.inclination_band_deg,
+            workers: if self.workers == 0 { defaults.workers } else { self.workers },
+            ..defaults
+        }
+    }
+}
+
+fn default_screening_interval() -> u64 {
+    8 * 3600
+}
+
+fn default_screening_window() -> u64 {
+    72
+}
+
+fn default_screening_step() -> u64 {
+    60
+}
+
+fn default_altitude_band() -> f64 {
+    50.0
+}
+
+fn default_inclination_band() -> f64 {
+    10.0
+}
+
+fn default_hard_body_radius() -> f64 {
+    DEFAULT_HARD_BODY_RADIUS_M
+}
+
+/// Re-screening of pairs nearing TCA
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmergencyScreeningConfig {
+    /// Hours before TCA from which a pair with a CDM is re-screened
+    #[serde(default = "default_emergency_within")]
+    pub within_hours: u64,
+
+    /// Seconds between re-screenings of such pairs
+    #[serde(default = "default_emergency_interval")]
+    pub interval_seconds: u64,
+}
+
+impl Default for EmergencyScreeningConfig {
+    fn default() -> Self {
+        Self {
+            within_hours: default_emergency_within(),
+            interval_seconds: default_emergency_interval(),
+        }
+    }
+}
+
+fn default_emergency_within() -> u64 {
+    24
+}
+
+fn default_emergency_interval() -> u64 {
+    3600
+}
+
+/// Screening policy for each orbital regime
+///
+/// A regime given in the file replaces its default policy whole.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegimePolicies {
+    #[serde(default = "default_leo_policy")]
+    pub leo: ScreeningPolicy,
+    #[serde(default = "default_meo_policy")]
+    pub meo: ScreeningPolicy,
+    #[serde(default = "default_geo_policy")]
+    pub geo: ScreeningPolicy,
+    #[serde(default = "default_heo_policy")]
+    pub heo: ScreeningPolicy,
+}
+
+impl RegimePolicies {
+    /// Policy for a regime
+    pub fn get(&self, regime: OrbitalRegime) -> &ScreeningPolicy {
+        match regime {
+            OrbitalRegime::Leo => &self.leo,
+            OrbitalRegime::Meo => &self.meo,
+            OrbitalRegime::Geo => &self.geo,
+            OrbitalRegime::Heo => &self.heo,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&'static str, &ScreeningPolicy)> {
+        [("leo", &self.leo), ("meo", &self.meo), ("geo", &self.geo), ("heo", &self.heo)].into_iter()
+    }
+}
+
+impl Default for RegimePolicies {
+    fn default() -> Self {
+        Self {
+            leo: default_leo_policy(),
+            meo: default_meo_policy(),
+            geo: default_geo_policy(),
+            heo: default_heo_policy(),
+        }
+    }
+}
+
+fn policy(radial_km: f64, in_track_km: f64, cross_track_km: f64, min_pc: f64, max_miss_distance_m: f64) -> ScreeningPolicy {
+    ScreeningPolicy {
+        volume: ScreeningVolume {
+            radial_km,
+            in_track_km,
+            cross_track_km,
+        },
+        min_pc,
+        max_miss_distance_m,
+    }
+}
+
+fn default_leo_policy() -> ScreeningPolicy {
+    policy(2.0, 25.0, 25.0, 1e-7, 1000.0)
+}
+
+fn default_meo_policy() -> ScreeningPolicy {
+    policy(5.0, 25.0, 25.0, 1e-7, 2000.0)
+}
+
+fn default_geo_policy() -> ScreeningPolicy {
+    policy(10.0, 30.0, 30.0, 1e-7, 5000.0)
+}
+
+fn default_heo_policy() -> ScreeningPolicy {
+    policy(5.0, 25.0, 25.0, 1e-7, 2000.0)
+}
+
+/// When a close approach in one orbital regime becomes a CDM
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScreeningPolicy {
+    /// Ellipsoid around the first object a close approach must fall inside
+    pub volume: ScreeningVolume,
+
+    /// Least Pc that produces a CDM
+    pub min_pc: f64,
+
+    /// Miss distance (m) at or below which a CDM is produced whatever the Pc
+    pub max_miss_distance_m: f64,
+}
+
+/// Semi-axes of a screening ellipsoid in the first object's RTN frame
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScreeningVolume {
+    pub radial_km: f64,
+    pub in_track_km: f64,
+    pub cross_track_km: f64,
+}
+
+impl ScreeningVolume {
+    /// Whether an RTN offset in km lies inside the ellipsoid
+    pub fn contains(&self, [r, t, n]: [f64; 3]) -> bool {
+        (r / self.radial_km).powi(2) + (t / self.in_track_km).powi(2) + (n / self.cross_track_km).powi(2) <= 1.0
+    }
+
+    fn largest_extent_km(&self) -> f64 {
+        self.radial_km.max(self.in_track_km).max(self.cross_track_km)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    #[test]
+    fn test_load_config() {
+        let config_content = r#"
+node:
+  id: "test-node"
+  name: "Test Node"
+
+server:
+  host: "127.0.0.1"
+  port: 8080
+
+peers: []
+"#;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(config_content.as_bytes()).unwrap();
+        
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.node.id, "test-node");
+        assert_eq!(config.server.port, 8080);
+    }
+
+    #[test]
+    fn test_load_json_and_toml() {
+        let load = |suffix: &str, content: &str| {
+            let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            Config::load(file.path())
+        };
+
+        let config = load(
+            ".json",
+            r#"{ "node": { "id": "json-node" }, "server": { "port": 9090 }, "storage": { "memory": { "max_bytes": "2GB" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(config.node.id, "json-node");
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.storage.memory.max_bytes, Some(2_000_000_000));
+
+        let config = load(
+            ".toml",
+            "[node]\nid = \"toml-node\"\n\n[server]\nport = 9091\n\n[[peers]]\nid = \"a\"\naddress = \"http://a\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.node.id, "toml-node");
+        assert_eq!(config.server.port, 9091);
+        assert_eq!(config.peers[0].id, "a");
+
+        assert!(matches!(load(".toml", "node = {"), Err(Error::Config(_))));
+        assert!(load(".json", "node: { id: n }").is_err());
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = Config::json_schema();
+        assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+        assert!(schema["required"].as_array().unwrap().contains(&"node".into()));
+        for key in ["node", "server", "peers", "storage", "api", "validation"] {
+            assert!(schema["properties"].get(key).is_some(), "{}", key);
+        }
+
+        // Every reference resolves within the document
+        fn refs(value: &serde_json::Value, found: &mut Vec<String>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    if let Some(serde_json::Value::String(target)) = map.get("$ref") {
+                        found.push(target.clone());
+                    }
+                    map.values().for_each(|value| refs(value, found));
+                }
+                serde_json::Value::Array(items) => items.iter().for_each(|value| refs(value, found)),
+                _ => {}
+            }
+        }
+        let mut found = Vec::new();
+        refs(&schema, &mut found);
+        assert!(!found.is_empty());
+        for target in found {
+            let name = target.strip_prefix("#/$defs/").unwrap_or_else(|| panic!("{}", target));
+            assert!(schema["$defs"].get(name).is_some(), "{}", target);
+        }
+
+        // Byte sizes take either form
+        let memory = &schema["$defs"]["MemoryLimitsConfig"]["properties"]["max_bytes"];
+        assert!(memory.to_string().contains("ByteSize"));
+        assert_eq!(schema["$defs"]["ByteSize"]["oneOf"].as_array().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn test_invalid_config_missing_node_id() {
+        let config_content = r#"
+node:
+  id: ""
+  name: "Test"
+
+server:
+  port: 8080
+"#;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(config_content.as_bytes()).unwrap();
+        
+        let result = Config::load(file.path());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_peer_transport() {
+        let config_content = r#"
+node:
+  id: "test-node"
+
+server:
+  port: 8080
+  grpc_port: 9090
+
+peers:
+  - id: "peer-http"
+    address: "http://localhost:8081"
+  - id: "peer-grpc"
+    address: "http://localhost:8082"
+    transport: grpc
+    encoding: cbor
+"#;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(config_content.as_bytes()).unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.server.grpc_port, Some(9090));
+        assert_eq!(config.peers[0].transport, PeerTransport::Http);
+        assert_eq!(config.peers[1].transport, PeerTransport::Grpc);
+        assert!(config.peers[0].policies.accept_cdm);
+        assert_eq!(config.peers[0].encoding, Encoding::Json);
+        assert_eq!(config.peers[1].encoding, Encoding::Cbor);
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let config_content = r#"
+node:
+  id: "test-node"
+
+server:
+  port: 8080
+
+protocol:
+  timestamp_format: millis
+
+peers:
+  - id: "peer-default"
+    address: "http://localhost:8081"
+  - id: "peer-micros"
+    address: "http://localhost:8082"
+    timestamp_format: micros
+"#;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(config_content.as_bytes()).unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.protocol.timestamp_format, TimestampFormat::Millis);
+        assert_eq!(config.peers[0].timestamp_format, None);
+        assert_eq!(config.peers[1].timestamp_format, Some(TimestampFormat::Micros));
+    }
+
+    #[test]
+    fn test_envelope_limits() {
+        let config: Config = serde_yaml::from_str("node: { id: n }\nserver: {}").unwrap();
+        assert_eq!(config.protocol.max_envelope_bytes, 1024 * 1024);
+        assert_eq!(config.protocol.max_payload_depth, 32);
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"node: { id: n }\nserver: {}\nprotocol: { max_payload_depth: 0 }").unwrap();
+        assert!(Config::load(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_ttl_config() {
+        let config: Config = serde_yaml::from_str(
+            "node: { id: n }\nserver: {}\nprotocol: { ttl: { default: 4, message_types: { OBJECT_STATE_ANNOUNCE: 1, CDM_ANNOUNCE: 12 } } }",
+        )
+        .unwrap();
+        let ttl = &config.protocol.ttl;
+        assert_eq!(ttl.for_message_type(&MessageType::ObjectStateAnnounce), 1);
+        assert_eq!(ttl.for_message_type(&MessageType::CdmAnnounce), 12);
+        assert_eq!(ttl.for_message_type(&MessageType::ManeuverIntent), 4);
+        assert!(config.validate().is_ok());
+
+        let config: Config =
+            serde_yaml::from_str("node: { id: n }\nserver: {}\nprotocol: { ttl: { message_types: { HEARTBEAT: 3 } } }")
+                .unwrap();
+        assert_eq!(config.protocol.ttl.default, DEFAULT_TTL);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_protocol_features() {
+        let config: Config = serde_yaml::from_str("node: { id: n }\nserver: {}").unwrap();
+        assert_eq!(config.protocol.version, LATEST_VERSION);
+        let config: Config = serde_yaml::from_str(
+            "node: { id: n }\nserver: {}\nprotocol: { version: '1.0', disabled_features: [BATCHING, SIGNING] }",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        for protocol in ["{ version: '2.0' }", "{ disabled_features: [CDM] }"] {
+            let config: Config =
+                serde_yaml::from_str(&format!("node: {{ id: n }}\nserver: {{}}\nprotocol: {}", protocol)).unwrap();
+            assert!(config.validate().is_err(), "{}", protocol);
+        }
+    }
+
+    #[test]
+    fn test_catalog_config() {
+        let config: Config =
+            serde_yaml::from_str("node: { id: n }\nserver: {}\ncatalog: { url: 'http://localhost:9000' }").unwrap();
+        let catalog = config.catalog.unwrap();
+        assert_eq!(catalog.query_path, "/catalog?norad_id={id}");
+        assert_eq!(catalog.refresh_interval_seconds, 3600);
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"node: { id: n }\nserver: {}\ncatalog: { url: 'http://x', query_path: '/satcat' }")
+            .unwrap();
+        assert!(Config::load(file.path()).is_err());
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"node: { id: n }\nserver: {}\ncatalog: { url: 'http://x', name_query_path: '/catalog' }")
+            .unwrap();
+        assert!(Config::load(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_dev_config() {
+        let config = Config::dev();
+        assert!(config.node.id.starts_with("dev-"));
+        assert_ne!(config.node.id, Config::dev().node.id);
+        assert_eq!(config.storage.storage_type, "memory");
+        assert_eq!(config.dev.unwrap().traffic_interval_seconds, 5);
+        assert!(Config::dev().validate().is_ok());
+    }
+
+    #[test]
+    fn test_memory_limits() {
+        assert_eq!(parse_byte_size("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_byte_size("512 MiB"), Some(512 << 20));
+        assert_eq!(parse_byte_size("4096"), Some(4096));
+        assert_eq!(parse_byte_size("2 parsecs"), None);
+
+        let config: Config =
+            serde_yaml::from_str("node: { id: n }\nserver: {}\nstorage: { memory: { max_bytes: 1GiB } }").unwrap();
+        assert_eq!(config.storage.memory.max_bytes, Some(1 << 30));
+        let config: Config =
+            serde_yaml::from_str("node: { id: n }\nserver: {}\nstorage: { memory: { max_bytes: 1000 } }").unwrap();
+        assert_eq!(config.storage.memory.max_bytes, Some(1000));
+        assert!(serde_yaml::from_str::<Config>("node: { id: n }\nserver: {}\nstorage: { memory: { max_bytes: lots } }").is_err());
+    }
+
+    #[test]
+    fn test_api_organizations() {
+        let api = |api: &str| serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\napi: {}", api)).unwrap();
+        let config = api(
+            "{ auth: { enabled: true, tokens: [{ id: t, secret: s, permissions: [read], organization: acme }] }, \
+             organizations: [{ id: acme, objects: ['NORAD-1*'] }], tenant_isolation: true }",
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.api.organization("acme").unwrap().objects, vec!["NORAD-1*"]);
+
+        // Tokens must name a configured organization
+        let config = api("{ auth: { tokens: [{ id: t, secret: s, organization: other }] }, organizations: [{ id: acme }] }");
+        assert!(config.validate().is_err());
+        let config = api("{ organizations: [{ id: acme }, { id: acme }] }");
+        assert!(config.validate().is_err());
+        // Isolation without authentication would isolate nothing
+        let config = api("{ tenant_isolation: true }");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_auth_roles() {
+        let auth = |auth: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\napi: {{ auth: {} }}", auth))
+                .unwrap()
+                .validate()
+        };
+        assert!(auth("{ roles: [{ name: ingest, scopes: ['POST /cdm'] }], tokens: [{ id: t, secret: s, roles: [ingest, viewer] }] }").is_ok());
+        assert!(auth("{ tokens: [{ id: t, secret: s, roles: [superuser] }] }").is_err());
+        assert!(auth("{ tokens: [{ id: t, secret: s, permissions: [everything] }] }").is_err());
+        assert!(auth("{ roles: [{ name: admin, scopes: [read] }] }").is_err());
+        assert!(auth("{ roles: [{ name: ingest, scopes: ['SEND /cdm'] }] }").is_err());
+    }
+
+    #[test]
+    fn test_cors() {
+        let cors = |cors: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\napi: {{ cors: {} }}", cors)).unwrap()
+        };
+        let config = cors("{ allowed_origins: ['https://ops.example.org', 'http://localhost:3000'] }");
+        assert!(config.validate().is_ok());
+        assert!(config.api.cors.allowed_headers.contains(&"authorization".to_string()));
+        assert!(config.api.security_headers.enabled);
+        assert!(cors("{ allowed_origins: ['*'] }").validate().is_ok());
+        assert!(cors("{ allowed_origins: ['*', 'https://ops.example.org'] }").validate().is_err());
+        assert!(cors("{ allowed_origins: ['https://ops.example.org/'] }").validate().is_err());
+        assert!(cors("{ allowed_origins: ['ops.example.org'] }").validate().is_err());
+        assert!(cors("{ allowed_methods: ['GET POST'] }").validate().is_err());
+        assert!(cors("{ allowed_headers: ['bad header'] }").validate().is_err());
+    }
+
+    #[test]
+    fn test_read_only_mode() {
+        let parse = |yaml: &str| serde_yaml::from_str::<Config>(yaml).unwrap();
+        assert_eq!(parse("node: { id: n }\nserver: {}").node.mode, NodeMode::Full);
+        let config = parse("node: { id: n, mode: read-only }\nserver: {}\narchive: { directory: /srv/archive }");
+        assert_eq!(config.node.mode, NodeMode::ReadOnly);
+        assert!(config.validate().is_ok());
+        let config = parse("node: { id: n, mode: read-only }\nserver: {}\npeers: [{ id: p, address: 'http://p:8080' }]");
+        assert!(config.validate().is_err());
+        assert!(parse("node: { id: n, mode: read-only }\nserver: { grpc_port: 9090 }").validate().is_err());
+    }
+
+    #[test]
+    fn test_ha() {
+        let parse = |extra: &str| {
+            let yaml = format!("node: {{ id: n }}\nserver: {{}}\nstorage: {{ storage_type: shared }}\n{}", extra);
+            serde_yaml::from_str::<Config>(&yaml).unwrap()
+        };
+        let config = parse("ha: { instance_id: a }");
+        let ha = config.ha.as_ref().unwrap();
+        assert_eq!((ha.lease_seconds, ha.renew_interval_seconds), (30, 10));
+        assert!(config.validate().is_ok());
+        assert!(parse("ha: { instance_id: '' }").validate().is_err());
+        assert!(parse("ha: { instance_id: a, lease_seconds: 10, renew_interval_seconds: 10 }").validate().is_err());
+        assert!(parse("ha: { instance_id: a, lease_seconds: 120 }").validate().is_err());
+        assert!(parse("ha: { instance_id: a }\nprotocol: { session_timeout_seconds: 30 }").validate().is_err());
+
+        // In-memory storage is private to each instance: every one would lead
+        let memory = serde_yaml::from_str::<Config>("node: { id: n }\nserver: {}\nha: { instance_id: a }").unwrap();
+        let error = memory.validate().unwrap_err().to_string();
+        assert!(error.contains("storage.storage_type"), "{}", error);
+    }
+
+    #[test]
+    fn test_storage_encryption() {
+        let storage = |encryption: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\nstorage: {{ encryption: {} }}", encryption))
+                .unwrap()
+        };
+        let key = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+        let config = storage(&format!("{{ keys: [{{ id: k2, key_env: ARCHIVE_KEY }}, {{ id: k1, key: '{}' }}] }}", key));
+        assert!(config.validate().is_ok());
+        assert!(storage("{ keys: [] }").validate().is_err());
+        assert!(storage("{ keys: [{ id: k1 }] }").validate().is_err());
+        assert!(storage("{ keys: [{ id: k1, key_env: A, key_command: 'echo' }] }").validate().is_err());
+        assert!(storage("{ keys: [{ id: k1, key: c2hvcnQ= }] }").validate().is_err());
+        assert!(storage("{ keys: [{ id: k1, key_env: A }, { id: k1, key_env: B }] }").validate().is_err());
+    }
+
+    #[test]
+    fn test_notification_channels() {
+        let parse = |channels: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\nnotifications: {{ channels: {} }}", channels))
+                .unwrap()
+        };
+        let config = parse(
+            "[{ id: ops-mail, type: smtp, host: relay, from: node@example.com, to: [ops@example.com] }, \
+             { id: ops-slack, type: slack, webhook_url: 'https://hooks.slack.com/x', min_severity: MEDIUM }]",
+        );
+        assert!(config.validate().is_ok());
+        let mail = &config.notifications.channels[0];
+        assert!(matches!(&mail.kind, ChannelKind::Smtp(smtp) if smtp.port == 25));
+        assert_eq!((mail.min_severity.clone(), mail.max_per_hour), (ConjunctionCategory::High, 60));
+
+        let config = parse("[{ id: a, type: webhook, url: 'ftp://x' }]");
+        assert!(config.validate().is_err());
+        let config = parse("[{ id: a, type: webhook, url: 'http://x' }, { id: a, type: webhook, url: 'http://y' }]");
+        assert!(config.validate().is_err());
+        let config = parse("[{ id: a, type: smtp, host: relay, from: f, to: [], max_per_hour: 5 }]");
+        assert!(config.validate().is_err());
+        let config = parse("[{ id: a, type: smtp, host: relay, from: f, to: [t], username: u }]");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_advertise_address() {
+        let parse = |server: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {}", server)).unwrap()
+        };
+        assert_eq!(parse("{}").server.advertise_address, None);
+        for valid in ["https://node-a.example.org:8443", "http://203.0.113.7:8080/"] {
+            let config = parse(&format!("{{ host: 0.0.0.0, advertise_address: '{}' }}", valid));
+            assert!(config.validate().is_ok(), "{}", valid);
+        }
+        for invalid in ["node-a.example.org:8443", "ftp://node-a", "https://node-a/api", "https://node-a/?x=1"] {
+            let config = parse(&format!("{{ advertise_address: '{}' }}", invalid));
+            assert!(config.validate().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_discovery() {
+        let parse = |discovery: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\ndiscovery: {}", discovery)).unwrap()
+        };
+        let config = parse("{ dns: [{ name: _spacecomms._tcp.example.org, nameserver: '10.0.0.2' }] }");
+        assert!(config.validate().is_ok());
+        let discovery = config.discovery.unwrap();
+        assert_eq!((discovery.refresh_interval_seconds, discovery.remove_missing), (300, false));
+        assert_eq!(discovery.dns[0].scheme, "http");
+        assert_eq!(parse_nameserver("10.0.0.2"), Some("10.0.0.2:53".parse().unwrap()));
+        assert_eq!(parse_nameserver("[::1]:5353"), Some("[::1]:5353".parse().unwrap()));
+
+        assert!(parse("{}").validate().is_err());
+        assert!(parse("{ refresh_interval_seconds: 0, seeds: [{ address: 'http://a' }] }").validate().is_err());
+        assert!(parse("{ seeds: [{ address: 'node-a:8080' }] }").validate().is_err());
+        assert!(parse("{ dns: [{ name: x, scheme: ftp }] }").validate().is_err());
+        assert!(parse("{ dns: [{ name: x, nameserver: ns.example.org }] }").validate().is_err());
+    }
+
+    #[test]
+    fn test_screening() {
+        let parse = |screening: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\nscreening: {}", screening)).unwrap()
+        };
+        let config = parse("{ regimes: { geo: { volume: { radial_km: 20, in_track_km: 200, cross_track_km: 200 }, min_pc: 1e-5, max_miss_distance_m: 10000 } } }");
+        assert!(config.validate().is_ok());
+        let screening = config.screening.unwrap();
+        assert_eq!((screening.interval_seconds, screening.window_hours), (28800, 72));
+        assert_eq!((screening.emergency.within_hours, screening.emergency.interval_seconds), (24, 3600));
+        assert_eq!(screening.regimes.get(OrbitalRegime::Geo).min_pc, 1e-5);
+        assert_eq!(screening.regimes.get(OrbitalRegime::Leo).volume.radial_km, 2.0);
+        assert!(screening.regimes.leo.volume.contains([1.0, 20.0, 0.0]));
+        assert!(!screening.regimes.leo.volume.contains([2.5, 0.0, 0.0]));
+
+        let options = screening.options(chrono::Duration::hours(72));
+        assert_eq!((options.threshold_km, options.step_seconds), (200.0, 60.0));
+        assert!(options.workers >= 1);
+
+        assert!(parse("{ window_hours: 0 }").validate().is_err());
+        assert!(parse("{ window_hours: 800 }").validate().is_err());
+        assert!(parse("{ altitude_band_km: 0 }").validate().is_err());
+        assert!(parse("{ emergency: { interval_seconds: 30000 } }").validate().is_err());
+        assert!(parse("{ worker_nodes: [node-b, node-c] }").validate().is_ok());
+        assert!(parse("{ worker_nodes: [node-b, node-b] }").validate().is_err());
+        assert!(parse("{ regimes: { leo: { volume: { radial_km: 0, in_track_km: 25, cross_track_km: 25 }, min_pc: 1e-7, max_miss_distance_m: 1000 } } }")
+            .validate()
+            .is_err());
+        assert!(parse("{ regimes: { meo: { volume: { radial_km: 5, in_track_km: 25, cross_track_km: 25 }, min_pc: 0, max_miss_distance_m: 1000 } } }")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_telemetry() {
+        let parse = |telemetry: &str| {
+            serde_yaml::from_str::<Config>(&format!("node: {{ id: n }}\nserver: {{}}\ntelemetry: {}", telemetry)).unwrap()
+        };
+        let config = parse("{}");
+        assert!(config.validate().is_ok());
+        let telemetry = config.telemetry.unwrap();
+        assert_eq!(telemetry.otlp_endpoint, "http://localhost:4317");
+        assert_eq!((telemetry.service_name.as_str(), telemetry.sample_ratio), ("spacecomms", 1.0));
+
+        assert!(parse("{ otlp_endpoint: 'collector:4317' }").validate().is_err());
+        assert!(parse("{ sample_ratio: 1.5 }").validate().is_err());
+    }
+
+    #[test]
+    fn test_override_layering() {
+        let file: Value = serde_yaml::from_str(
+            "node: { id: file-node }\nserver: { port: 8080 }\npeers:\n  - { id: b, address: 'http://b' }",
+        )
+        .unwrap();
+        let env = ConfigOverride::from_vars([
+            ("SPACECOMMS_SERVER__PORT".to_string(), "9090".to_string()),
+            ("SPACECOMMS_NODE__ID".to_string(), "123".to_string()),
+            ("SPACECOMMS_PEERS__0__ADDRESS".to_string(), "http://b2".to_string()),
+            ("SPACECOMMS_CONFIG".to_string(), "/etc/spacecomms.yaml".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ]);
+        assert_eq!(env.len(), 3);
+        let cli: Vec<ConfigOverride> = ["server.port=9191", "logging.level=debug", "peers.1={ id: c, address: 'http://c' }"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        let config = Config::layered(file, env.iter().chain(&cli)).unwrap();
+        assert_eq!(config.node.id, "123");
+        assert_eq!(config.server.port, 9191);
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.peers[0].address, "http://b2");
+        assert_eq!(config.peers[1].id, "c");
+    }
+
+    #[test]
+    fn test_invalid_overrides() {
+        assert!("server.port".parse::<ConfigOverride>().is_err());
+        assert!("server..port=1".parse::<ConfigOverride>().is_err());
+
+        let file: Value = serde_yaml::from_str("node: { id: n }\nserver: {}").unwrap();
+        let layered = |s: &str| Config::layered(file.clone(), [&s.parse::<ConfigOverride>().unwrap()]);
+        // Overrides go through the same validation as the file
+        assert!(matches!(layered("server.port=0"), Err(Error::Config(_))));
+        assert!(matches!(layered("peers.3.id=x"), Err(Error::Config(_))));
+        assert!(matches!(layered("node.id.x=1"), Err(Error::Config(_))));
+        assert!(layered("server.port=not-a-port").is_err());
+    }
+
+    #[test]
+    fn test_peer_groups() {
+        let layered = |yaml: &str| {
+            let document: Value = serde_yaml::from_str(&format!("node: {{ id: n }}\nserver: {{}}\n{}", yaml)).unwrap();
+            Config::layered(document, [])
+        };
+        let config = layered(
+            "policy_templates:
+  restricted: { accept_maneuver: false, redact: { drop_covariance: true } }
+  commercial: { extends: restricted, forward_cdm: false, redact: { anonymize_owner: true } }
+peer_groups:
+  commercial-operators: { template: commercial, policies: { serve_cdm_queries: false } }
+peers:
+  - { id: a, address: 'http://a', group: commercial-operators, policies: { forward_cdm: true } }
+  - { id: b, address: 'http://b' }
+discovery:
+  template: { group: commercial-operators }
+  seeds: [{ address: 'http://c' }]",
+        )
+        .unwrap();
+        let policies = &config.peers[0].policies;
+        assert!(!policies.accept_maneuver && !policies.serve_cdm_queries);
+        // The peer's own keys win, and nested sections merge
+        assert!(policies.forward_cdm && policies.accept_cdm);
+        assert!(policies.redact.drop_covariance && policies.redact.anonymize_owner);
+        assert_eq!(config.peers[1].policies, PeerPolicies::default());
+        assert!(!config.discovery.as_ref().unwrap().template.policies.forward_cdm);
+
+        // Resolving again changes nothing
+        let again = config.clone().with_overrides(&[]).unwrap();
+        assert_eq!(again.peers[0].policies, config.peers[0].policies);
+
+        let invalid = [
+            "peers: [{ id: a, address: 'http://a', group: missing }]",
+            "peer_groups: { g: { template: missing } }",
+            "policy_templates: { x: { extends: y }, y: { extends: x } }",
+            "policy_templates: { x: { accept_cdm: maybe } }",
+        ];
+        for yaml in invalid {
+            assert!(matches!(layered(yaml), Err(Error::Config(_))), "{}", yaml);
+        }
     }
 }
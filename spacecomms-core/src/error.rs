@@ -1,5 +1,6 @@
 //! SpaceComms error types
 
+use crate::protocol::ErrorCode;
 use thiserror::Error;
 
 /// SpaceComms result type
@@ -20,15 +21,42 @@ pub enum Error {
     #[error("JSON parsing error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("CBOR encoding error: {0}")]
+    Cbor(String),
+
     #[error("CDM validation error: {0}")]
     CdmValidation(String),
 
+    #[error("CCSDS message error: {0}")]
+    Ccsds(String),
+
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedVersion(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Replay rejected: {0}")]
+    Replay(String),
+
+    #[error("Propagation error: {0}")]
+    Propagation(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     #[error("Peer error: {0}")]
     Peer(String),
 
+    #[error("Refused by peer: {0}")]
+    PeerRefused(String),
+
     #[error("Storage error: {0}")]
     Storage(String),
 
@@ -38,6 +66,9 @@ pub enum Error {
     #[error("Already exists: {0}")]
     AlreadyExists(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("HTTP client error: {0}")]
     Http(#[from] reqwest::Error),
 
@@ -55,4 +86,89 @@ impl Error {
     pub fn is_validation(&self) -> bool {
         matches!(self, Error::CdmValidation(_))
     }
+
+    /// Protocol error code reported to a peer for this error
+    ///
+    /// Problems with the received message map to `INVALID_MESSAGE`; failures
+    /// on this node that the peer cannot fix map to `INTERNAL_ERROR`.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Error::Json(_)
+            | Error::Cbor(_)
+            | Error::CdmValidation(_)
+            | Error::Ccsds(_)
+            | Error::Protocol(_)
+            | Error::LimitExceeded(_)
+            | Error::Replay(_)
+            | Error::Propagation(_)
+            | Error::NotFound(_)
+            | Error::AlreadyExists(_)
+            | Error::Conflict(_) => ErrorCode::InvalidMessage,
+            Error::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            Error::Unauthorized(_) => ErrorCode::Unauthorized,
+            Error::QuotaExceeded(_) => ErrorCode::RateLimited,
+            Error::Config(_)
+            | Error::Io(_)
+            | Error::Yaml(_)
+            | Error::Peer(_)
+            | Error::PeerRefused(_)
+            | Error::Storage(_)
+            | Error::Http(_)
+            | Error::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_mapping() {
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let cases = [
+            (Error::Json(json), ErrorCode::InvalidMessage),
+            (Error::Cbor("bad".into()), ErrorCode::InvalidMessage),
+            (Error::CdmValidation("bad".into()), ErrorCode::InvalidMessage),
+            (Error::Ccsds("bad".into()), ErrorCode::InvalidMessage),
+            (Error::Protocol("bad".into()), ErrorCode::InvalidMessage),
+            (Error::LimitExceeded("big".into()), ErrorCode::InvalidMessage),
+            (Error::Replay("old".into()), ErrorCode::InvalidMessage),
+            (Error::Propagation("decayed".into()), ErrorCode::InvalidMessage),
+            (Error::NotFound("x".into()), ErrorCode::InvalidMessage),
+            (Error::AlreadyExists("x".into()), ErrorCode::InvalidMessage),
+            (Error::Conflict("x".into()), ErrorCode::InvalidMessage),
+            (Error::UnsupportedVersion("2.0.0".into()), ErrorCode::UnsupportedVersion),
+            (Error::Unauthorized("x".into()), ErrorCode::Unauthorized),
+            (Error::QuotaExceeded("x".into()), ErrorCode::RateLimited),
+            (Error::Config("x".into()), ErrorCode::InternalError),
+            (Error::Io(std::io::Error::other("x")), ErrorCode::InternalError),
+            (Error::Peer("x".into()), ErrorCode::InternalError),
+            (Error::Storage("x".into()), ErrorCode::InternalError),
+            (Error::Internal("x".into()), ErrorCode::InternalError),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.error_code(), code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_error_payload_hides_internal_detail() {
+        use crate::protocol::{ErrorPayload, INTERNAL_ERROR_MESSAGE};
+        let internal = [
+            Error::Storage("lock poisoned at /var/lib/spacecomms".into()),
+            Error::Io(std::io::Error::other("disk full on /dev/sda1")),
+            Error::Internal("task panicked".into()),
+            Error::Config("api.auth.tokens[0].secret".into()),
+        ];
+        for error in internal {
+            let payload = ErrorPayload::from_error(&error, Some("msg-1".into()));
+            assert_eq!(payload.error_code, ErrorCode::InternalError);
+            assert_eq!(payload.error_message, INTERNAL_ERROR_MESSAGE);
+            assert_eq!(payload.related_message_id.as_deref(), Some("msg-1"));
+        }
+        // What the peer can fix is still explained to it
+        let payload = ErrorPayload::from_error(&Error::CdmValidation("miss_distance_m is negative".into()), None);
+        assert_eq!(payload.error_message, "CDM validation error: miss_distance_m is negative");
+    }
 }
@@ -5,15 +5,20 @@
 //! - CDM parsing and validation
 //! - Peer session management
 //! - Routing engine
+//! - Object catalog enrichment
+//! - Orbit propagation
 //! - REST API server
 
 pub mod api;
+pub mod catalog;
 pub mod cdm;
 pub mod config;
 pub mod error;
 pub mod node;
+pub mod orbit;
 pub mod protocol;
 pub mod storage;
+pub mod telemetry;
 
 pub use config::Config;
 pub use error::{Error, Result};
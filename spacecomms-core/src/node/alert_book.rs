@@ -0,0 +1,533 @@
+//! Operator alerts for watched assets
+//!
+//! The node opens an alert for every CDM involving an asset on its
+//! watchlist. The alert's severity is the conjunction category from the
+//! node's own `protocol.severity` Pc and miss distance thresholds, whatever
+//! the originator said. Alerts follow the CDM event feed: newer versions
+//! update the figures, and withdrawal resolves the alert.
+//!
+//! Operators work an alert through `open`, `acknowledged` and `resolved`,
+//! and can assign it to a person or token. An acknowledged or resolved
+//! alert reopens when its severity rises, and a resolved alert reopens
+//! when its withdrawn CDM is announced again. Alerts are held in memory;
+//! the most recent resolved alerts are kept for review.
+//!
+//! Opening, reopening and TCA escalation of an unresolved alert are handed
+//! to the [`Notifier`](crate::node::Notifier), which records its
+//! deliveries on the alert.
+
+use crate::cdm::{conjunction_category, CdmRecord, ConjunctionCategory, RecommendedAction};
+use crate::config::SeverityConfig;
+use crate::node::{
+    rank, AlertNotice, AppState, CdmEvent, CdmEventKind, NoticeTrigger, NotificationDelivery, TenantScope, Watchlist,
+};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Resolved alerts kept for review
+const MAX_RESOLVED_ALERTS: usize = 1000;
+
+/// Notification deliveries kept per alert
+const MAX_DELIVERIES: usize = 100;
+
+/// How long the tracker waits on the event feed before checking again
+const TRACKER_POLL: Duration = Duration::from_secs(30);
+
+/// Where an alert is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+/// A conjunction involving a watched asset that needs an operator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Alert {
+    pub alert_id: String,
+    pub state: AlertState,
+    /// Category from this node's severity thresholds
+    pub severity: ConjunctionCategory,
+    pub cdm_id: String,
+    /// The watched object
+    pub asset_id: String,
+    pub asset_name: String,
+    pub other_object_id: String,
+    pub other_object_name: String,
+    pub tca: DateTime<Utc>,
+    pub collision_probability: f64,
+    pub miss_distance_m: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommended_action: Option<RecommendedAction>,
+    /// Person or token working the alert
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acknowledged_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// Who resolved the alert; absent when the node resolved it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Notices sent about the alert, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<NotificationDelivery>,
+}
+
+impl Alert {
+    fn open(cdm: &CdmRecord, watchlist: &Watchlist, severity: ConjunctionCategory, at: DateTime<Utc>) -> Self {
+        let (asset, other) = match watchlist.asset(&cdm.object1.object_id) {
+            Some(_) => (&cdm.object1, &cdm.object2),
+            None => (&cdm.object2, &cdm.object1),
+        };
+        let asset_name = watchlist
+            .asset(&asset.object_id)
+            .and_then(|watched| watched.name)
+            .unwrap_or_else(|| asset.object_name.clone());
+        Self {
+            alert_id: format!("ALERT-{}", uuid::Uuid::new_v4()),
+            state: AlertState::Open,
+            severity,
+            cdm_id: cdm.cdm_id.clone(),
+            asset_id: asset.object_id.clone(),
+            asset_name,
+            other_object_id: other.object_id.clone(),
+            other_object_name: other.object_name.clone(),
+            tca: cdm.tca,
+            collision_probability: cdm.collision_probability,
+            miss_distance_m: cdm.miss_distance_m,
+            recommended_action: cdm.recommended_action.clone(),
+            assignee: None,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            resolved_by: None,
+            resolved_at: None,
+            resolution: None,
+            created_at: at,
+            updated_at: at,
+            notifications: Vec::new(),
+        }
+    }
+
+    fn reopen(&mut self) {
+        self.state = AlertState::Open;
+        self.acknowledged_by = None;
+        self.acknowledged_at = None;
+        self.resolved_by = None;
+        self.resolved_at = None;
+        self.resolution = None;
+    }
+}
+
+/// Filters for listing alerts
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilter {
+    pub state: Option<AlertState>,
+    /// Least severe category returned
+    pub min_severity: Option<ConjunctionCategory>,
+    pub assignee: Option<String>,
+    pub cdm_id: Option<String>,
+}
+
+impl AlertFilter {
+    fn matches(&self, alert: &Alert) -> bool {
+        self.state.is_none_or(|state| alert.state == state)
+            && self.min_severity.as_ref().is_none_or(|min| rank(&alert.severity) >= rank(min))
+            && self.assignee.as_ref().is_none_or(|a| alert.assignee.as_ref() == Some(a))
+            && self.cdm_id.as_ref().is_none_or(|id| alert.cdm_id == *id)
+    }
+}
+
+/// A change an operator makes to an alert
+#[derive(Debug, Clone)]
+pub enum AlertChange {
+    Acknowledge { by: Option<String> },
+    Assign { assignee: Option<String> },
+    Resolve { by: Option<String>, resolution: Option<String> },
+}
+
+struct Entry {
+    alert: Alert,
+    /// Last version of the CDM seen, for tenant filtering
+    cdm: CdmRecord,
+    /// Resolved because the CDM was withdrawn
+    withdrawn: bool,
+}
+
+#[derive(Default)]
+struct Book {
+    entries: Vec<Entry>,
+    /// Next event to apply
+    next_seq: u64,
+}
+
+impl Book {
+    /// The latest alert for a CDM
+    fn for_cdm(&mut self, cdm_id: &str) -> Option<&mut Entry> {
+        self.entries.iter_mut().rev().find(|e| e.alert.cdm_id == cdm_id)
+    }
+
+    /// Apply one event; returns the notice it calls for, if any
+    fn apply(&mut self, event: &CdmEvent, watchlist: &Watchlist, thresholds: &SeverityConfig) -> Option<AlertNotice> {
+        match event.kind {
+            CdmEventKind::Withdrawn => {
+                let entry = self.for_cdm(&event.cdm_id)?;
+                if entry.alert.state == AlertState::Resolved {
+                    return None;
+                }
+                entry.alert.state = AlertState::Resolved;
+                entry.alert.resolved_by = None;
+                entry.alert.resolved_at = Some(event.at);
+                entry.alert.resolution = Some(format!("CDM withdrawn: {}", event.reason.as_deref().unwrap_or("unknown")));
+                entry.alert.updated_at = event.at;
+                entry.withdrawn = true;
+                debug!("Alert {} resolved: CDM {} withdrawn", entry.alert.alert_id, event.cdm_id);
+                self.prune();
+                None
+            }
+            CdmEventKind::Announced => self.observe(event.cdm.as_ref()?, watchlist, thresholds, event.at),
+            CdmEventKind::Escalated => {
+                let notice = self.observe(event.cdm.as_ref()?, watchlist, thresholds, event.at);
+                if notice.is_some() {
+                    return notice;
+                }
+                let entry = self.for_cdm(&event.cdm_id)?;
+                if entry.alert.state == AlertState::Resolved {
+                    return None;
+                }
+                Some(AlertNotice {
+                    trigger: NoticeTrigger::Escalated,
+                    alert: entry.alert.clone(),
+                    escalation: event.escalation.clone(),
+                })
+            }
+        }
+    }
+
+    /// Open or update the alert for a stored CDM; returns a notice when
+    /// the alert opens or reopens
+    fn observe(
+        &mut self,
+        cdm: &CdmRecord,
+        watchlist: &Watchlist,
+        thresholds: &SeverityConfig,
+        at: DateTime<Utc>,
+    ) -> Option<AlertNotice> {
+        let severity = conjunction_category(cdm, thresholds);
+        let Some(entry) = self.for_cdm(&cdm.cdm_id) else {
+            if !watchlist.involves(cdm) {
+                return None;
+            }
+            let alert = Alert::open(cdm, watchlist, severity, at);
+            info!(
+                "Alert {} opened: {:?} conjunction of {} with {}",
+                alert.alert_id, alert.severity, alert.asset_id, alert.other_object_id
+            );
+            self.entries.push(Entry {
+                alert: alert.clone(),
+                cdm: cdm.clone(),
+                withdrawn: false,
+            });
+            return Some(AlertNotice {
+                trigger: NoticeTrigger::Opened,
+                alert,
+                escalation: None,
+            });
+        };
+
+        let risen = rank(&severity) > rank(&entry.alert.severity);
+        let returned = entry.alert.state == AlertState::Resolved && entry.withdrawn;
+        let reopened = entry.alert.state != AlertState::Open && (risen || returned);
+        if reopened {
+            info!("Alert {} reopened: CDM {} is now {:?}", entry.alert.alert_id, cdm.cdm_id, severity);
+            entry.alert.reopen();
+        }
+        entry.alert.severity = severity;
+        entry.alert.tca = cdm.tca;
+        entry.alert.collision_probability = cdm.collision_probability;
+        entry.alert.miss_distance_m = cdm.miss_distance_m;
+        entry.alert.recommended_action = cdm.recommended_action.clone();
+        entry.alert.updated_at = at;
+        entry.cdm = cdm.clone();
+        entry.withdrawn = false;
+        reopened.then(|| AlertNotice {
+            trigger: NoticeTrigger::Reopened,
+            alert: entry.alert.clone(),
+            escalation: None,
+        })
+    }
+
+    /// Drop the oldest resolved alerts beyond the limit
+    fn prune(&mut self) {
+        let resolved = self.entries.iter().filter(|e| e.alert.state == AlertState::Resolved).count();
+        let mut excess = resolved.saturating_sub(MAX_RESOLVED_ALERTS);
+        self.entries.retain(|e| {
+            if excess > 0 && e.alert.state == AlertState::Resolved {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
+
+/// Alerts raised on this node
+#[derive(Default)]
+pub struct AlertBook {
+    book: Mutex<Book>,
+}
+
+impl AlertBook {
+    /// Next event the book will apply
+    fn cursor(&self) -> u64 {
+        self.book.lock().map_or(0, |book| book.next_seq)
+    }
+
+    /// Apply the CDM events logged since the last call
+    pub fn catch_up(&self, state: &AppState) {
+        let config = state.config.get();
+        let notices: Vec<AlertNotice> = {
+            let Ok(mut book) = self.book.lock() else {
+                return;
+            };
+            let page = state.events.page(book.next_seq);
+            if page.missed > 0 {
+                warn!("Alerts missed {} CDM events that aged out of the log", page.missed);
+            }
+            let notices = page
+                .events
+                .iter()
+                .filter_map(|event| book.apply(event, &state.watchlist, &config.protocol.severity))
+                .collect();
+            book.next_seq = page.next_seq;
+            notices
+        };
+        state.notifier.dispatch(state, notices);
+    }
+
+    /// Open alerts for active CDMs involving a watched asset that have
+    /// none, such as after the watchlist grew; returns the number opened
+    pub async fn open_for_watched(&self, state: &AppState) -> Result<usize> {
+        self.catch_up(state);
+        let cdms = state.storage.list_cdms().await?;
+        let config = state.config.get();
+        let notices: Vec<AlertNotice> = {
+            let Ok(mut book) = self.book.lock() else {
+                return Ok(0);
+            };
+            cdms.iter()
+                .filter(|cdm| state.watchlist.involves(cdm))
+                .filter_map(|cdm| match book.for_cdm(&cdm.cdm_id) {
+                    Some(_) => None,
+                    None => book.observe(cdm, &state.watchlist, &config.protocol.severity, Utc::now()),
+                })
+                .collect()
+        };
+        let opened = notices.len();
+        state.notifier.dispatch(state, notices);
+        Ok(opened)
+    }
+
+    /// Record a notification sent, or not, about an alert
+    pub fn record_delivery(&self, alert_id: &str, delivery: NotificationDelivery) {
+        let Ok(mut book) = self.book.lock() else {
+            return;
+        };
+        if let Some(entry) = book.entries.iter_mut().find(|e| e.alert.alert_id == alert_id) {
+            let notifications = &mut entry.alert.notifications;
+            notifications.push(delivery);
+            if notifications.len() > MAX_DELIVERIES {
+                notifications.remove(0);
+            }
+        }
+    }
+
+    /// Alerts the scope may see that pass the filter, ordered by TCA
+    pub fn list(&self, filter: &AlertFilter, scope: &TenantScope) -> Vec<Alert> {
+        let Ok(book) = self.book.lock() else {
+            return Vec::new();
+        };
+        let mut alerts: Vec<Alert> = book
+            .entries
+            .iter()
+            .filter(|e| scope.sees_cdm(&e.cdm) && filter.matches(&e.alert))
+            .map(|e| e.alert.clone())
+            .collect();
+        alerts.sort_by(|a, b| a.tca.cmp(&b.tca).then_with(|| a.alert_id.cmp(&b.alert_id)));
+        alerts
+    }
+
+    /// One alert, if the scope may see it
+    pub fn get(&self, alert_id: &str, scope: &TenantScope) -> Option<Alert> {
+        let book = self.book.lock().ok()?;
+        book.entries
+            .iter()
+            .find(|e| e.alert.alert_id == alert_id && scope.sees_cdm(&e.cdm))
+            .map(|e| e.alert.clone())
+    }
+
+    /// Apply an operator's change
+    ///
+    /// Fails with [`Error::NotFound`] for an alert the scope cannot see and
+    /// [`Error::Conflict`] when acknowledging or assigning a resolved alert.
+    /// Acknowledging twice and resolving twice change nothing.
+    pub fn update(&self, alert_id: &str, change: AlertChange, scope: &TenantScope) -> Result<Alert> {
+        let mut book = self
+            .book
+            .lock()
+            .map_err(|_| Error::Internal("alert book poisoned".into()))?;
+        let entry = book
+            .entries
+            .iter_mut()
+            .find(|e| e.alert.alert_id == alert_id && scope.sees_cdm(&e.cdm))
+            .ok_or_else(|| Error::NotFound(format!("Alert not found: {}", alert_id)))?;
+        let alert = &mut entry.alert;
+        let now = Utc::now();
+        match change {
+            AlertChange::Acknowledge { .. } | AlertChange::Assign { .. } if alert.state == AlertState::Resolved => {
+                return Err(Error::Conflict(format!("alert {} is resolved", alert_id)));
+            }
+            AlertChange::Acknowledge { by } => {
+                if alert.state == AlertState::Open {
+                    alert.state = AlertState::Acknowledged;
+                    alert.acknowledged_by = by;
+                    alert.acknowledged_at = Some(now);
+                    alert.updated_at = now;
+                }
+            }
+            AlertChange::Assign { assignee } => {
+                alert.assignee = assignee;
+                alert.updated_at = now;
+            }
+            AlertChange::Resolve { by, resolution } => {
+                if alert.state != AlertState::Resolved {
+                    alert.state = AlertState::Resolved;
+                    alert.resolved_by = by;
+                    alert.resolved_at = Some(now);
+                    alert.resolution = resolution;
+                    alert.updated_at = now;
+                    entry.withdrawn = false;
+                }
+            }
+        }
+        let alert = entry.alert.clone();
+        book.prune();
+        Ok(alert)
+    }
+}
+
+/// Keep the alert book in step with the CDM event feed for as long as the
+/// node runs
+pub fn spawn_alert_tracker(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        loop {
+            let since = state.alerts.cursor();
+            state.events.wait(since, TRACKER_POLL).await;
+            state.alerts.catch_up(&state);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use chrono::Duration as ChronoDuration;
+
+    #[tokio::test]
+    async fn test_alert_lifecycle() {
+        let state = test_state("node-a");
+        state.watchlist.register("12345", Some("SAT-1".into()));
+        let mut cdm = generate_demo_cdm();
+        cdm.collision_probability = 1e-6;
+        cdm.miss_distance_m = 5000.0;
+        let mut unwatched = cdm.clone();
+        unwatched.cdm_id = "CDM-OTHER".into();
+        unwatched.object1.object_id = "NORAD-11111".into();
+        unwatched.object2.object_id = "NORAD-22222".into();
+        state.events.announced(&cdm);
+        state.events.announced(&unwatched);
+        state.alerts.catch_up(&state);
+
+        let all = AlertFilter::default();
+        let scope = TenantScope::default();
+        let alerts = state.alerts.list(&all, &scope);
+        assert_eq!(alerts.len(), 1);
+        let alert = &alerts[0];
+        assert_eq!((alert.state, &alert.severity), (AlertState::Open, &ConjunctionCategory::Low));
+        assert_eq!(alert.asset_name, "SAT-1");
+        assert_eq!(alert.other_object_id, cdm.object2.object_id);
+        let id = alert.alert_id.clone();
+
+        let acknowledge = AlertChange::Acknowledge { by: Some("alice".into()) };
+        let acked = state.alerts.update(&id, acknowledge.clone(), &scope).unwrap();
+        assert_eq!(acked.state, AlertState::Acknowledged);
+        assert_eq!(acked.acknowledged_by.as_deref(), Some("alice"));
+        let assign = AlertChange::Assign { assignee: Some("bob".into()) };
+        assert_eq!(state.alerts.update(&id, assign, &scope).unwrap().assignee.as_deref(), Some("bob"));
+        assert!(state.alerts.update("ALERT-NONE", acknowledge.clone(), &scope).unwrap_err().is_not_found());
+
+        // A newer version at the same severity keeps the acknowledgement
+        cdm.tca += ChronoDuration::minutes(1);
+        state.events.announced(&cdm);
+        state.alerts.catch_up(&state);
+        assert_eq!(state.alerts.get(&id, &scope).unwrap().state, AlertState::Acknowledged);
+        // A rise in severity reopens it
+        cdm.collision_probability = 1e-3;
+        state.events.announced(&cdm);
+        state.alerts.catch_up(&state);
+        let reopened = state.alerts.get(&id, &scope).unwrap();
+        assert_eq!((reopened.state, reopened.severity), (AlertState::Open, ConjunctionCategory::High));
+        assert_eq!(reopened.assignee.as_deref(), Some("bob"));
+
+        // Withdrawal resolves it, and the CDM returning reopens it
+        state.events.withdrawn(&cdm.cdm_id, Some(&cdm), "TCA_PASSED".into());
+        state.alerts.catch_up(&state);
+        let resolved = state.alerts.get(&id, &scope).unwrap();
+        assert_eq!(resolved.state, AlertState::Resolved);
+        assert_eq!(resolved.resolution.as_deref(), Some("CDM withdrawn: TCA_PASSED"));
+        assert!(matches!(state.alerts.update(&id, acknowledge, &scope), Err(Error::Conflict(_))));
+        state.events.announced(&cdm);
+        state.alerts.catch_up(&state);
+        assert_eq!(state.alerts.get(&id, &scope).unwrap().state, AlertState::Open);
+
+        // An operator's resolution sticks while the severity holds
+        let resolve = AlertChange::Resolve {
+            by: None,
+            resolution: Some("accepted risk".into()),
+        };
+        state.alerts.update(&id, resolve, &scope).unwrap();
+        state.events.announced(&cdm);
+        state.alerts.catch_up(&state);
+        let filter = AlertFilter {
+            state: Some(AlertState::Resolved),
+            ..Default::default()
+        };
+        assert_eq!(state.alerts.list(&filter, &scope).len(), 1);
+
+        // Registering an asset opens alerts for its active CDMs
+        state.storage.store_cdm(unwatched.clone()).await.unwrap();
+        state.watchlist.register("22222", None);
+        assert_eq!(state.alerts.open_for_watched(&state).await.unwrap(), 1);
+        assert_eq!(state.alerts.open_for_watched(&state).await.unwrap(), 0);
+        let filter = AlertFilter {
+            cdm_id: Some(unwatched.cdm_id.clone()),
+            ..Default::default()
+        };
+        assert_eq!(state.alerts.list(&filter, &scope)[0].asset_id, "NORAD-22222");
+    }
+}
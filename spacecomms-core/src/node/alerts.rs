@@ -0,0 +1,408 @@
+//! TCA countdown and alert escalation
+//!
+//! A scheduler checks active CDMs against the `alerts.thresholds_hours`
+//! ladder (72h, 24h, 6h and 1h before TCA by default). When a conjunction at
+//! or above `alerts.min_category` crosses a threshold, its recommended action
+//! is raised one step (MONITOR, PREPARE, MANEUVER), the updated CDM is
+//! stored, and an `escalated` event is logged for watchers and POSTed to the
+//! configured webhooks. Each threshold fires once per CDM; a CDM first seen
+//! inside several thresholds escalates once, for the closest. Conjunctions
+//! whose [`RiskTrend`] is increasing escalate whatever their category, through
+//! their newest CDM.
+//! Escalations are local advice and are not forwarded to peers.
+//!
+//! The object catalog's capacity alerts, when it fills past
+//! `storage.object_limits.alert_percent` and when it drops back, are POSTed
+//! to the same webhooks as a [`CapacityAlert`].
+
+use crate::cdm::{
+    conjunction_category, recommended_action, CdmRecord, ConjunctionCategory, ConjunctionKey, RecommendedAction,
+    RiskTrend, TrendPoint,
+};
+use crate::config::AlertsConfig;
+use crate::node::{AppState, CdmEvent};
+use crate::storage::CapacityEvent;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Time allowed for one webhook delivery
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A conjunction crossing a TCA countdown threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Escalation {
+    /// Threshold crossed, in hours before TCA
+    pub threshold_hours: u64,
+    pub tca: DateTime<Utc>,
+    /// Seconds left to TCA when the threshold was noticed
+    pub time_to_tca_seconds: i64,
+    pub conjunction_category: ConjunctionCategory,
+    pub previous_action: RecommendedAction,
+    pub recommended_action: RecommendedAction,
+    /// Escalated because the conjunction's risk is increasing
+    #[serde(default)]
+    pub risk_increasing: bool,
+}
+
+/// Next step up from an action
+fn raise(action: &RecommendedAction) -> RecommendedAction {
+    match action {
+        RecommendedAction::Monitor => RecommendedAction::Prepare,
+        RecommendedAction::Prepare | RecommendedAction::Maneuver => RecommendedAction::Maneuver,
+    }
+}
+
+/// Severity order of a category, most severe highest
+pub(crate) fn rank(category: &ConjunctionCategory) -> u8 {
+    match category {
+        ConjunctionCategory::Low => 0,
+        ConjunctionCategory::Medium => 1,
+        ConjunctionCategory::High => 2,
+    }
+}
+
+/// Every stored CDM that `visible` keeps, withdrawn ones included, grouped
+/// by conjunction
+pub(crate) async fn trend_points(
+    state: &AppState,
+    visible: impl Fn(&CdmRecord) -> bool,
+) -> Result<HashMap<ConjunctionKey, Vec<TrendPoint>>> {
+    let bucket_seconds = state.config.get().storage.conjunction_bucket_seconds;
+    let mut groups: HashMap<ConjunctionKey, Vec<TrendPoint>> = HashMap::new();
+    for (key, cdms) in state.storage.list_conjunctions().await? {
+        let points = cdms.iter().filter(|cdm| visible(cdm)).map(|cdm| TrendPoint::new(cdm, false));
+        groups.entry(key).or_default().extend(points);
+    }
+    for withdrawn in state.storage.list_withdrawn_cdms().await? {
+        let cdm = &withdrawn.record;
+        if visible(cdm) {
+            let key = ConjunctionKey::for_cdm(cdm, bucket_seconds);
+            groups.entry(key).or_default().push(TrendPoint::new(cdm, true));
+        }
+    }
+    groups.retain(|_, points| !points.is_empty());
+    Ok(groups)
+}
+
+/// Closest threshold, in hours, that `remaining` seconds to TCA is inside
+fn crossed_threshold(remaining: i64, thresholds: &[u64]) -> Option<u64> {
+    if remaining <= 0 {
+        return None;
+    }
+    thresholds
+        .iter()
+        .copied()
+        .filter(|hours| remaining <= (*hours as i64).saturating_mul(3600))
+        .min()
+}
+
+/// Escalates active CDMs as their TCA approaches
+pub struct TcaScheduler {
+    /// Closest threshold already fired for each CDM
+    fired: Mutex<HashMap<String, u64>>,
+    http: reqwest::Client,
+}
+
+impl Default for TcaScheduler {
+    fn default() -> Self {
+        Self {
+            fired: Mutex::new(HashMap::new()),
+            http: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl TcaScheduler {
+    /// Check every active CDM once, returning the escalations made
+    pub async fn run_once(&self, state: &AppState) -> Result<Vec<Escalation>> {
+        let config = state.config.get();
+        let now = Utc::now();
+        let cdms = state.storage.list_cdms().await?;
+        self.forget_inactive(&cdms);
+        // Newest CDM of each conjunction whose risk is increasing
+        let rising: HashSet<String> = trend_points(state, |_| true)
+            .await?
+            .into_values()
+            .map(|points| RiskTrend::new(points, config.alerts.risk_increase_factor))
+            .filter(|trend| trend.risk_increasing)
+            .filter_map(|trend| trend.points.last().map(|newest| newest.cdm_id.clone()))
+            .collect();
+
+        let mut escalations = Vec::new();
+        for cdm in cdms {
+            let remaining = (cdm.tca - now).num_seconds();
+            let Some(threshold) = crossed_threshold(remaining, &config.alerts.thresholds_hours) else {
+                continue;
+            };
+            if self.already_fired(&cdm.cdm_id, threshold) {
+                continue;
+            }
+            let category = cdm
+                .conjunction_category
+                .clone()
+                .unwrap_or_else(|| conjunction_category(&cdm, &config.protocol.severity));
+            let risk_increasing = rising.contains(&cdm.cdm_id);
+            if rank(&category) < rank(&config.alerts.min_category) && !risk_increasing {
+                continue;
+            }
+
+            let previous_action = cdm
+                .recommended_action
+                .clone()
+                .unwrap_or_else(|| recommended_action(&category));
+            let escalation = Escalation {
+                threshold_hours: threshold,
+                tca: cdm.tca,
+                time_to_tca_seconds: remaining,
+                conjunction_category: category,
+                recommended_action: raise(&previous_action),
+                previous_action,
+                risk_increasing,
+            };
+            let Some(event) = self.escalate(state, &cdm.cdm_id, &escalation).await? else {
+                continue;
+            };
+            self.mark_fired(&cdm.cdm_id, threshold);
+            state.metrics.escalations.fetch_add(1, Ordering::Relaxed);
+            info!(
+                "CDM {} is {}h from TCA: {:?} -> {:?}",
+                cdm.cdm_id, threshold, escalation.previous_action, escalation.recommended_action
+            );
+            self.notify(state, &config.alerts, event);
+            escalations.push(escalation);
+        }
+        Ok(escalations)
+    }
+
+    /// Store the raised action and log the event; `None` if the CDM was
+    /// withdrawn or replaced meanwhile (the next check sees the new state)
+    async fn escalate(&self, state: &AppState, cdm_id: &str, escalation: &Escalation) -> Result<Option<CdmEvent>> {
+        let Some(current) = state.storage.get_cdm_versioned(cdm_id).await? else {
+            return Ok(None);
+        };
+        let mut cdm = current.record;
+        cdm.recommended_action = Some(escalation.recommended_action.clone());
+        let outcome = state.storage.compare_and_swap_cdm(cdm.clone(), Some(current.revision)).await?;
+        if !outcome.written() {
+            return Ok(None);
+        }
+        Ok(state.events.escalated(&cdm, escalation.clone()))
+    }
+
+    fn already_fired(&self, cdm_id: &str, threshold: u64) -> bool {
+        self.fired
+            .lock()
+            .is_ok_and(|fired| fired.get(cdm_id).is_some_and(|closest| *closest <= threshold))
+    }
+
+    fn mark_fired(&self, cdm_id: &str, threshold: u64) {
+        if let Ok(mut fired) = self.fired.lock() {
+            fired.insert(cdm_id.to_string(), threshold);
+        }
+    }
+
+    /// Drop state for CDMs no longer active
+    fn forget_inactive(&self, active: &[CdmRecord]) {
+        let ids: HashSet<&str> = active.iter().map(|cdm| cdm.cdm_id.as_str()).collect();
+        if let Ok(mut fired) = self.fired.lock() {
+            fired.retain(|id, _| ids.contains(id.as_str()));
+        }
+    }
+
+    /// POST the event to each webhook without waiting for the responses
+    fn notify(&self, state: &AppState, config: &AlertsConfig, event: CdmEvent) {
+        post_to_webhooks(state, &self.http, config, &event);
+    }
+}
+
+/// POST a body to each alert webhook without waiting for the responses
+fn post_to_webhooks<T: Serialize>(state: &AppState, http: &reqwest::Client, config: &AlertsConfig, body: &T) {
+    for url in &config.webhooks {
+        let request = http.post(url).json(body);
+        let metrics = state.metrics.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            let result = request.send().await.and_then(|resp| resp.error_for_status());
+            if let Err(e) = result {
+                metrics.webhook_failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Alert webhook {} failed: {}", url, e);
+            }
+        });
+    }
+}
+
+/// Object catalog capacity alert, as POSTed to the alert webhooks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacityAlert {
+    pub node_id: String,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: CapacityEvent,
+}
+
+/// POST the object catalog's capacity alerts, queued by the storage hook,
+/// to the alert webhooks for as long as the node runs
+pub fn spawn_capacity_alerts(state: AppState, mut events: UnboundedReceiver<CapacityEvent>) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let http = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        while let Some(event) = events.recv().await {
+            let config = state.config.get();
+            let alert = CapacityAlert {
+                node_id: config.node.id.clone(),
+                at: Utc::now(),
+                event,
+            };
+            post_to_webhooks(&state, &http, &config.alerts, &alert);
+        }
+    });
+}
+
+/// Check active CDMs on the configured interval for as long as the node runs
+pub fn spawn_tca_scheduler(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let scheduler = TcaScheduler::default();
+        loop {
+            let alerts = state.config.get().alerts.clone();
+            tokio::time::sleep(Duration::from_secs(alerts.check_interval_seconds)).await;
+            if !alerts.enabled || !state.leadership.is_leader() {
+                continue;
+            }
+            match scheduler.run_once(&state).await {
+                Ok(escalations) if escalations.is_empty() => debug!("TCA check found nothing to escalate"),
+                Ok(escalations) => debug!("TCA check escalated {} CDMs", escalations.len()),
+                Err(e) => warn!("TCA check failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use crate::node::CdmEventKind;
+    use chrono::Duration as ChronoDuration;
+
+    #[test]
+    fn test_crossed_threshold() {
+        let thresholds = [72, 24, 6, 1];
+        assert_eq!(crossed_threshold(80 * 3600, &thresholds), None);
+        assert_eq!(crossed_threshold(72 * 3600, &thresholds), Some(72));
+        assert_eq!(crossed_threshold(5 * 3600, &thresholds), Some(6));
+        assert_eq!(crossed_threshold(60, &thresholds), Some(1));
+        // Past TCA nothing escalates
+        assert_eq!(crossed_threshold(0, &thresholds), None);
+        assert_eq!(crossed_threshold(-60, &thresholds), None);
+    }
+
+    #[tokio::test]
+    async fn test_escalation() {
+        let state = test_state("node-a");
+        let (tx, mut webhook) = tokio::sync::mpsc::unbounded_channel();
+        let receiver = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |axum::Json(event): axum::Json<CdmEvent>| async move {
+                tx.send(event).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await });
+        let mut config = (*state.config.get()).clone();
+        config.alerts.webhooks = vec![hook];
+        state.config.replace(config);
+
+        let scheduler = TcaScheduler::default();
+        let mut medium = generate_demo_cdm();
+        medium.cdm_id = "CDM-MEDIUM".into();
+        medium.conjunction_category = Some(ConjunctionCategory::Medium);
+        medium.recommended_action = Some(RecommendedAction::Monitor);
+        medium.tca = Utc::now() + ChronoDuration::hours(30);
+        let mut low = medium.clone();
+        low.cdm_id = "CDM-LOW".into();
+        low.conjunction_category = Some(ConjunctionCategory::Low);
+        for cdm in [medium.clone(), low] {
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+
+        let escalations = scheduler.run_once(&state).await.unwrap();
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(escalations[0].threshold_hours, 72);
+        assert_eq!(escalations[0].recommended_action, RecommendedAction::Prepare);
+        let stored = state.storage.get_cdm("CDM-MEDIUM").await.unwrap().unwrap();
+        assert_eq!(stored.recommended_action, Some(RecommendedAction::Prepare));
+        let page = state.events.page(0);
+        assert_eq!(page.events[0].kind, CdmEventKind::Escalated);
+        assert_eq!(page.events[0].escalation.as_ref(), Some(&escalations[0]));
+        let delivered = tokio::time::timeout(std::time::Duration::from_secs(5), webhook.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivered.cdm_id, "CDM-MEDIUM");
+        assert_eq!(delivered.escalation, Some(escalations[0].clone()));
+
+        // Each threshold fires once
+        assert!(scheduler.run_once(&state).await.unwrap().is_empty());
+
+        // Several thresholds crossed at once escalate once, for the closest
+        let mut closer = stored;
+        closer.tca = Utc::now() + ChronoDuration::minutes(30);
+        state.storage.store_cdm(closer).await.unwrap();
+        let escalations = scheduler.run_once(&state).await.unwrap();
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(escalations[0].threshold_hours, 1);
+        assert_eq!(escalations[0].recommended_action, RecommendedAction::Maneuver);
+        assert!(scheduler.run_once(&state).await.unwrap().is_empty());
+        assert_eq!(state.metrics.escalations.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_escalation_on_rising_risk() {
+        let state = test_state("node-a");
+        let scheduler = TcaScheduler::default();
+        let mut first = generate_demo_cdm();
+        first.cdm_id = "CDM-1".into();
+        first.conjunction_category = Some(ConjunctionCategory::Low);
+        first.recommended_action = Some(RecommendedAction::Monitor);
+        first.collision_probability = 1e-6;
+        first.tca = Utc::now() + ChronoDuration::hours(30);
+        first.creation_date = Utc::now() - ChronoDuration::hours(2);
+        let mut second = first.clone();
+        second.cdm_id = "CDM-2".into();
+        second.collision_probability = 5e-6;
+        second.creation_date = Utc::now() - ChronoDuration::hours(1);
+        state.storage.store_cdm(first).await.unwrap();
+
+        // Below the minimum category, and no trend yet
+        assert!(scheduler.run_once(&state).await.unwrap().is_empty());
+
+        // Five times the probability: only the newest CDM escalates
+        state.storage.store_cdm(second).await.unwrap();
+        let escalations = scheduler.run_once(&state).await.unwrap();
+        assert_eq!(escalations.len(), 1);
+        assert!(escalations[0].risk_increasing);
+        let action = |id: &'static str| {
+            let state = state.clone();
+            async move { state.storage.get_cdm(id).await.unwrap().unwrap().recommended_action }
+        };
+        assert_eq!(action("CDM-2").await, Some(RecommendedAction::Prepare));
+        assert_eq!(action("CDM-1").await, Some(RecommendedAction::Monitor));
+    }
+}
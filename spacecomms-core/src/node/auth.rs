@@ -0,0 +1,562 @@
+//! API authentication and tenant scoping
+//!
+//! With `api.auth.enabled`, every API request other than health, metrics,
+//! the OpenAPI document, the dashboard page and the peer protocol endpoint
+//! must carry a bearer token from `api.auth.tokens`. GET requests need
+//! `read`, other requests `write`, and `/admin` and peer management
+//! `admin`; `admin` implies `write`, which implies `read`. The dashboard
+//! asks for a token and sends it with the API calls it makes.
+//!
+//! A token's permissions are scopes: one of those three levels, or an
+//! endpoint scope `METHOD /path` (`*` matches any method or any characters
+//! of the path) granting just the matching requests. Tokens may also hold
+//! roles, named sets of scopes: the built-in `viewer`, `operator`, `admin`
+//! and `peer-manager`, or roles defined in `api.auth.roles`. The caller's
+//! effective permissions are its own scopes plus those of its roles.
+//!
+//! Besides the configured tokens, admins may issue tokens at runtime through
+//! `/auth/tokens`. Those are kept in storage, may expire, and can be rotated
+//! or revoked without a restart.
+//!
+//! Peers do not use API tokens. The `auth_token` configured for a peer is a
+//! secret the two nodes share: it is sent to the peer, as a bearer token and
+//! in HELLO, and the peer must present it on the protocol endpoint and its
+//! gRPC streams. A node ID header or HELLO naming another peer than the
+//! credential's is refused, so a sender cannot pose as a peer it does not
+//! hold the secret of. Envelopes without a valid credential are refused
+//! with `UNAUTHORIZED` unless `protocol.require_peer_auth` is off and the
+//! sender's peer has no token.
+//!
+//! A token may be bound to one of the `api.organizations`. CDMs ingested
+//! with it record that organization as their owner; CDMs and objects from
+//! peers are assigned to the organization they are addressed to
+//! (`message_for`) or whose registered objects they involve. With
+//! `api.tenant_isolation`, read endpoints only show an organization's
+//! tokens the CDMs and objects it owns, is addressed by, or whose objects
+//! it registered. Tokens without an organization see everything.
+
+use crate::cdm::{CdmRecord, ObjectRecord};
+use crate::config::{ApiConfig, AuthConfig, OrganizationConfig, TokenConfig};
+use crate::node::{AppState, PROTOCOL_ENDPOINT};
+use crate::protocol::{wildcard_match, Envelope, MessageType};
+use crate::storage::ApiTokenRecord;
+use crate::Error;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header::AUTHORIZATION, request::Parts, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Paths served without a token
+const PUBLIC_PATHS: [&str; 5] = ["/health", "/health/live", "/health/ready", "/metrics", "/openapi.json"];
+
+/// Served to any valid token, whatever its permissions
+pub const WHOAMI_PATH: &str = "/auth/whoami";
+
+/// Token management endpoints
+pub const TOKENS_PATH: &str = "/auth/tokens";
+
+/// Roles every node knows, with their scopes
+pub const BUILTIN_ROLES: [(&str, &[&str]); 4] = [
+    ("viewer", &["read"]),
+    ("operator", &["write"]),
+    ("admin", &["admin"]),
+    ("peer-manager", &["read", "* /peers", "* /peers/*"]),
+];
+
+/// Permission levels, weakest first; each implies the ones before it
+const LEVELS: [&str; 3] = ["read", "write", "admin"];
+
+/// What a permission grants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// `read`, `write` or `admin`
+    Level(&'static str),
+    /// Requests whose method and path match; `None` matches any method
+    Endpoint { method: Option<Method>, path: String },
+}
+
+impl Scope {
+    /// Parse a permission: a level or `METHOD /path`
+    pub fn parse(permission: &str) -> Option<Self> {
+        if let Some(level) = LEVELS.iter().find(|level| **level == permission) {
+            return Some(Scope::Level(level));
+        }
+        let (method, path) = permission.split_once(' ')?;
+        let path = path.trim();
+        if !path.starts_with('/') && path != "*" {
+            return None;
+        }
+        let method = match method.to_ascii_uppercase().as_str() {
+            "*" => None,
+            "GET" => Some(Method::GET),
+            "POST" => Some(Method::POST),
+            "PUT" => Some(Method::PUT),
+            "PATCH" => Some(Method::PATCH),
+            "DELETE" => Some(Method::DELETE),
+            _ => return None,
+        };
+        Some(Scope::Endpoint {
+            method,
+            path: path.to_string(),
+        })
+    }
+
+    /// Whether the scope grants a request
+    pub fn grants(&self, method: &Method, path: &str) -> bool {
+        match self {
+            Scope::Level(level) => implies(level, required_permission(method, path)),
+            Scope::Endpoint { method: wanted, path: pattern } => {
+                wanted.as_ref().is_none_or(|m| m == method) && wildcard_match(pattern, path)
+            }
+        }
+    }
+}
+
+fn implies(held: &str, needed: &str) -> bool {
+    let rank = |level: &str| LEVELS.iter().position(|l| *l == level);
+    matches!((rank(held), rank(needed)), (Some(held), Some(needed)) if held >= needed)
+}
+
+/// Scopes of a role: built-in or defined in `api.auth.roles`
+pub fn role_scopes<'a>(auth: &'a AuthConfig, role: &str) -> Option<Vec<&'a str>> {
+    if let Some((_, scopes)) = BUILTIN_ROLES.iter().find(|(name, _)| *name == role) {
+        return Some(scopes.to_vec());
+    }
+    auth.roles
+        .iter()
+        .find(|r| r.name == role)
+        .map(|r| r.scopes.iter().map(String::as_str).collect())
+}
+
+/// The authenticated token behind a request
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub token_id: String,
+    pub organization: Option<String>,
+    pub roles: Vec<String>,
+    /// Effective scopes: the token's own plus those of its roles
+    pub permissions: Vec<String>,
+    /// When an issued token stops working
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Caller {
+    /// Caller for a configured token, with its roles expanded
+    pub fn for_token(token: &TokenConfig, auth: &AuthConfig) -> Self {
+        let mut permissions = token.permissions.clone();
+        for role in &token.roles {
+            for scope in role_scopes(auth, role).unwrap_or_default() {
+                if !permissions.iter().any(|p| p == scope) {
+                    permissions.push(scope.to_string());
+                }
+            }
+        }
+        Self {
+            token_id: token.id.clone(),
+            organization: token.organization.clone(),
+            roles: token.roles.clone(),
+            permissions,
+            expires_at: None,
+        }
+    }
+
+    /// Caller for a token issued through the API
+    pub fn for_issued(token: &ApiTokenRecord, auth: &AuthConfig) -> Self {
+        let config = TokenConfig {
+            id: token.id.clone(),
+            secret: String::new(),
+            permissions: token.permissions.clone(),
+            roles: token.roles.clone(),
+            organization: token.organization.clone(),
+        };
+        Self {
+            expires_at: token.expires_at,
+            ..Self::for_token(&config, auth)
+        }
+    }
+
+    /// Whether the token grants `permission`, directly or through a
+    /// stronger one
+    pub fn can(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| implies(p, permission))
+    }
+
+    /// Whether any of the caller's scopes grants a request
+    pub fn allows(&self, method: &Method, path: &str) -> bool {
+        self.permissions
+            .iter()
+            .filter_map(|p| Scope::parse(p))
+            .any(|scope| scope.grants(method, path))
+    }
+}
+
+/// Permission a request needs
+fn required_permission(method: &Method, path: &str) -> &'static str {
+    if path.starts_with("/admin/")
+        || path.starts_with(TOKENS_PATH)
+        || path == "/export"
+        || path == "/import"
+        || path == "/peers/export"
+        || path.starts_with("/deadletter")
+        || path.starts_with("/cdms/quarantine")
+        || (path.starts_with("/peers") && method != Method::GET)
+        || (path.starts_with("/originators") && method != Method::GET)
+    {
+        "admin"
+    } else if method == Method::GET || path == "/routing/simulate" {
+        "read"
+    } else {
+        "write"
+    }
+}
+
+fn is_public(path: &str) -> bool {
+    PUBLIC_PATHS.contains(&path)
+        || path == PROTOCOL_ENDPOINT
+        || path == "/docs"
+        || path.starts_with("/docs/")
+        || path == "/ui"
+        || path.starts_with("/ui/")
+}
+
+/// Compare secrets without revealing where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn auth_error(status: StatusCode, error: &str, message: &str) -> Response {
+    (status, Json(json!({ "error": error, "message": message }))).into_response()
+}
+
+/// Middleware checking the bearer token and recording the [`Caller`]
+pub(crate) async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.config.get();
+    let auth = &config.api.auth;
+    let path = request.uri().path();
+    if !auth.enabled || is_public(path) {
+        return next.run(request).await;
+    }
+
+    let presented = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(presented) = presented else {
+        return auth_error(StatusCode::UNAUTHORIZED, "unauthorized", "bearer token required");
+    };
+    let configured = auth
+        .tokens
+        .iter()
+        .find(|t| constant_time_eq(t.secret.as_bytes(), presented.as_bytes()));
+    let caller = match configured {
+        Some(token) => Caller::for_token(token, auth),
+        None => match issued_caller(&state, auth, presented).await {
+            Ok(caller) => caller,
+            Err(response) => return response,
+        },
+    };
+    if path != WHOAMI_PATH && !caller.allows(request.method(), path) {
+        let permission = required_permission(request.method(), path);
+        debug!("Token {} lacks {} for {} {}", caller.token_id, permission, request.method(), path);
+        let message = format!(
+            "token {} lacks the {} permission or a scope for {} {}",
+            caller.token_id,
+            permission,
+            request.method(),
+            path
+        );
+        return auth_error(StatusCode::FORBIDDEN, "forbidden", &message);
+    }
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+/// Caller for a presented secret of a token issued through the API
+async fn issued_caller(state: &AppState, auth: &AuthConfig, presented: &str) -> Result<Caller, Response> {
+    let tokens = state.storage.list_api_tokens().await.map_err(|e| {
+        auth_error(StatusCode::SERVICE_UNAVAILABLE, "storage_error", &e.to_string())
+    })?;
+    let now = Utc::now();
+    let hash = ApiTokenRecord::hash_secret(presented);
+    let Some(token) = tokens.iter().find(|t| t.matches(&hash, now)) else {
+        return Err(auth_error(StatusCode::UNAUTHORIZED, "unauthorized", "unknown token"));
+    };
+    match token.status(now) {
+        "active" => Ok(Caller::for_issued(token, auth)),
+        status => Err(auth_error(StatusCode::UNAUTHORIZED, "unauthorized", &format!("token {}", status))),
+    }
+}
+
+/// Peering secret presented with an inbound envelope: the request's bearer
+/// token or, failing that, the `auth_token` of a HELLO
+pub(crate) fn peer_credential<'a>(bearer: Option<&'a str>, envelope: &'a Envelope) -> Option<&'a str> {
+    bearer.or_else(|| {
+        (envelope.message_type == MessageType::Hello)
+            .then(|| envelope.payload.get("auth_token").and_then(|token| token.as_str()))
+            .flatten()
+    })
+}
+
+/// Peer an inbound envelope is from
+///
+/// `claimed` is the peer the transport names as the sender (the node ID
+/// header, or the source of a gRPC stream's HELLO); without one, the
+/// envelope's source is taken, unless the credential is some peer's
+/// `auth_token` and so names the sender itself. A claimed peer with a token
+/// must present it; one without may present the `discovery.template` token
+/// shared across a federation. Without a credential the claim stands only
+/// when `protocol.require_peer_auth` is off and the peer has no token.
+pub(crate) async fn authenticate_peer(
+    state: &AppState,
+    envelope: &Envelope,
+    claimed: Option<&str>,
+    credential: Option<&str>,
+) -> crate::Result<String> {
+    let config = state.config.get();
+    let peers = state.peers.read().await;
+    let sender = claimed.unwrap_or(&envelope.source_node_id);
+    let own_token = peers.get_peer(sender).and_then(|peer| peer.auth_token.as_ref());
+    let Some(credential) = credential else {
+        if config.protocol.require_peer_auth {
+            return Err(Error::Unauthorized("peering credential required".into()));
+        }
+        if own_token.is_some() {
+            return Err(Error::Unauthorized(format!("peer {} must present its peering credential", sender)));
+        }
+        return Ok(sender.to_string());
+    };
+    let presented = |token: Option<&String>| token.is_some_and(|t| constant_time_eq(t.as_bytes(), credential.as_bytes()));
+    let owner = peers.list_peers().iter().find(|peer| presented(peer.auth_token.as_ref()));
+    let federation = config.discovery.as_ref().and_then(|d| d.template.auth_token.as_ref());
+    match (claimed, owner) {
+        (None, Some(owner)) => Ok(owner.id.clone()),
+        _ if presented(own_token) => Ok(sender.to_string()),
+        _ if own_token.is_none() && presented(federation) => Ok(sender.to_string()),
+        (_, Some(owner)) => Err(Error::Unauthorized(format!(
+            "credential of peer {} presented by {}",
+            owner.id, sender
+        ))),
+        (_, None) => Err(Error::Unauthorized(format!("wrong peering credential for {}", sender))),
+    }
+}
+
+/// Organization a CDM belongs to: the first addressed by its `message_for`
+/// or registering one of its objects
+pub fn cdm_organization(api: &ApiConfig, cdm: &CdmRecord) -> Option<String> {
+    api.organizations
+        .iter()
+        .find(|org| addressed_to(org, &cdm.message_for))
+        .or_else(|| {
+            api.organizations
+                .iter()
+                .find(|org| registers(org, &cdm.object1.object_id) || registers(org, &cdm.object2.object_id))
+        })
+        .map(|org| org.id.clone())
+}
+
+/// Drop the owning organization and watchlist tag from a CDM or object
+/// state sent to peers; they describe this node's tenants and mean nothing
+/// elsewhere
+pub(crate) fn strip_local_fields(payload: &mut serde_json::Value) {
+    if let Some(fields) = payload.as_object_mut() {
+        fields.remove("organization");
+        fields.remove("involves_watched_asset");
+    }
+}
+
+/// Organization that registered an object
+pub fn object_organization(api: &ApiConfig, object_id: &str) -> Option<String> {
+    api.organizations
+        .iter()
+        .find(|org| registers(org, object_id))
+        .map(|org| org.id.clone())
+}
+
+fn addressed_to(org: &OrganizationConfig, message_for: &str) -> bool {
+    org.id.eq_ignore_ascii_case(message_for) || org.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(message_for))
+}
+
+fn registers(org: &OrganizationConfig, object_id: &str) -> bool {
+    org.objects.iter().any(|pattern| wildcard_match(pattern, object_id))
+}
+
+/// Data a request may read: everything, or one organization's share
+#[derive(Debug, Clone, Default)]
+pub struct TenantScope(Option<Arc<OrganizationConfig>>);
+
+impl TenantScope {
+    /// Scope limited to one organization
+    pub fn organization(org: OrganizationConfig) -> Self {
+        Self(Some(Arc::new(org)))
+    }
+
+    /// Whether reads are limited to one organization
+    pub fn is_scoped(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Whether the caller may see a CDM
+    pub fn sees_cdm(&self, cdm: &CdmRecord) -> bool {
+        let Some(org) = &self.0 else {
+            return true;
+        };
+        cdm.organization.as_deref() == Some(org.id.as_str())
+            || addressed_to(org, &cdm.message_for)
+            || registers(org, &cdm.object1.object_id)
+            || registers(org, &cdm.object2.object_id)
+    }
+
+    /// Whether the caller may see an object
+    pub fn sees_object(&self, object: &ObjectRecord) -> bool {
+        let Some(org) = &self.0 else {
+            return true;
+        };
+        object.organization.as_deref() == Some(org.id.as_str()) || registers(org, &object.object_id)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for TenantScope {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let config = state.config.get();
+        if !config.api.tenant_isolation {
+            return Ok(Self::default());
+        }
+        let org = parts
+            .extensions
+            .get::<Caller>()
+            .and_then(|caller| caller.organization.as_deref())
+            .and_then(|id| config.api.organization(id));
+        Ok(org.cloned().map(Self::organization).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn api() -> ApiConfig {
+        serde_yaml::from_str(
+            r#"
+organizations:
+  - id: acme
+    aliases: ["ACME Space"]
+    objects: ["NORAD-12*"]
+  - id: orbital
+    objects: ["NORAD-7*"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_permissions() {
+        let caller = |permissions: &[&str]| Caller {
+            token_id: "t".into(),
+            organization: None,
+            roles: Vec::new(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            expires_at: None,
+        };
+        assert_eq!(required_permission(&Method::GET, "/cdms"), "read");
+        assert_eq!(required_permission(&Method::POST, "/cdm"), "write");
+        assert_eq!(required_permission(&Method::POST, "/routing/simulate"), "read");
+        assert_eq!(required_permission(&Method::GET, "/peers"), "read");
+        assert_eq!(required_permission(&Method::POST, "/peers"), "admin");
+        assert_eq!(required_permission(&Method::DELETE, "/originators/OP-A/hold"), "admin");
+        assert_eq!(required_permission(&Method::GET, "/cdms/quarantine"), "admin");
+        assert_eq!(required_permission(&Method::POST, "/cdms/quarantine/CDM-1/release"), "admin");
+        assert_eq!(required_permission(&Method::POST, "/admin/reload"), "admin");
+        assert_eq!(required_permission(&Method::GET, "/export"), "admin");
+        assert_eq!(required_permission(&Method::GET, "/peers/export"), "admin");
+        assert_eq!(required_permission(&Method::GET, "/deadletter"), "admin");
+        assert_eq!(required_permission(&Method::POST, "/cdm/import"), "write");
+        assert_eq!(required_permission(&Method::GET, "/auth/tokens"), "admin");
+        assert_eq!(required_permission(&Method::GET, "/auth/whoami"), "read");
+        assert!(caller(&["admin"]).can("read"));
+        assert!(caller(&["write"]).can("read"));
+        assert!(!caller(&["write"]).can("admin"));
+        assert!(!caller(&["read"]).can("write"));
+        assert!(!caller(&[]).can("read"));
+        assert!(is_public("/health/ready") && is_public("/docs/") && is_public(PROTOCOL_ENDPOINT));
+        assert!(is_public("/ui/") && is_public("/ui/app.js"));
+        assert!(!is_public("/cdms"));
+    }
+
+    #[test]
+    fn test_roles_and_scopes() {
+        let auth: AuthConfig = serde_yaml::from_str(
+            r#"
+enabled: true
+roles:
+  - { name: ingest, scopes: ["POST /cdm", "POST /cdms/bulk"] }
+tokens:
+  - { id: pipeline, secret: s1, roles: [ingest], permissions: ["GET /cdms/*"] }
+  - { id: netops, secret: s2, roles: [peer-manager] }
+  - { id: console, secret: s3, roles: [operator, viewer] }
+"#,
+        )
+        .unwrap();
+        let caller = |i: usize| Caller::for_token(&auth.tokens[i], &auth);
+
+        let pipeline = caller(0);
+        assert_eq!(pipeline.permissions, ["GET /cdms/*", "POST /cdm", "POST /cdms/bulk"]);
+        assert!(pipeline.allows(&Method::POST, "/cdm"));
+        assert!(pipeline.allows(&Method::GET, "/cdms/CDM-1"));
+        assert!(!pipeline.allows(&Method::GET, "/cdms"));
+        assert!(!pipeline.allows(&Method::DELETE, "/cdms/CDM-1"));
+        assert!(!pipeline.can("read"));
+
+        let netops = caller(1);
+        assert!(netops.allows(&Method::POST, "/peers") && netops.allows(&Method::DELETE, "/peers/peer-b"));
+        assert!(netops.allows(&Method::GET, "/cdms"));
+        assert!(!netops.allows(&Method::POST, "/cdm") && !netops.allows(&Method::POST, "/admin/reload"));
+
+        let console = caller(2);
+        assert_eq!(console.permissions, ["write", "read"]);
+        assert!(console.allows(&Method::POST, "/cdm") && !console.allows(&Method::POST, "/peers"));
+
+        assert_eq!(Scope::parse("admin"), Some(Scope::Level("admin")));
+        assert!(Scope::parse("* /peers/*").is_some());
+        assert!(Scope::parse("FETCH /cdms").is_none() && Scope::parse("GET cdms").is_none());
+        assert!(Scope::parse("superuser").is_none());
+    }
+
+    #[test]
+    fn test_tenant_visibility() {
+        let api = api();
+        let mut cdm = generate_demo_cdm();
+        cdm.message_for = "ALL".into();
+        cdm.object1.object_id = "NORAD-55555".into();
+        cdm.object2.object_id = "NORAD-70001".into();
+        assert_eq!(cdm_organization(&api, &cdm).as_deref(), Some("orbital"));
+        // Addressing wins over object registration
+        cdm.message_for = "acme space".into();
+        assert_eq!(cdm_organization(&api, &cdm).as_deref(), Some("acme"));
+
+        let acme = TenantScope::organization(api.organization("acme").unwrap().clone());
+        let orbital = TenantScope::organization(api.organization("orbital").unwrap().clone());
+        assert!(acme.sees_cdm(&cdm) && orbital.sees_cdm(&cdm));
+        cdm.message_for = "ALL".into();
+        assert!(!acme.sees_cdm(&cdm));
+        cdm.organization = Some("acme".into());
+        assert!(acme.sees_cdm(&cdm));
+        assert!(TenantScope::default().sees_cdm(&cdm));
+        assert_eq!(object_organization(&api, "NORAD-12345").as_deref(), Some("acme"));
+        assert_eq!(object_organization(&api, "NORAD-99999"), None);
+    }
+}
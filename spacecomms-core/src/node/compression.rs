@@ -0,0 +1,139 @@
+//! gzip compression of API responses
+//!
+//! Responses of at least `compression.min_response_bytes` are gzip
+//! compressed for clients whose `Accept-Encoding` allows it. Streamed
+//! responses, such as the event streams and NDJSON exports, are passed
+//! through as they are so they keep flowing while they are produced.
+
+use crate::node::AppState;
+use axum::{
+    body::{Body, HttpBody as _},
+    extract::{Request, State},
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, VARY},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use tracing::warn;
+
+/// Whether an `Accept-Encoding` header allows gzip
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or("").trim();
+            let refused = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .any(|q| q.trim().parse::<f32>().is_ok_and(|q| q <= 0.0));
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+}
+
+fn gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len() / 4), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Middleware compressing large, fully buffered responses
+pub(crate) async fn compress_response(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let min_bytes = state.config.get().compression.min_response_bytes;
+    let accepted = min_bytes > 0 && accepts_gzip(request.headers());
+    let response = next.run(request).await;
+    if !accepted || response.headers().contains_key(CONTENT_ENCODING) {
+        return response;
+    }
+    // Streamed bodies have no exact length
+    match response.body().size_hint().exact() {
+        Some(len) if len >= min_bytes as u64 => {}
+        _ => return response,
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let compressed = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => gzip(&bytes).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let compressed = match compressed {
+        Ok(compressed) => compressed,
+        Err(e) => {
+            warn!("Could not compress response: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts.headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    Response::from_parts(parts, Body::from(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+    use axum::{middleware, routing::get, Router};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_accepts_gzip() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, value.parse().unwrap());
+            headers
+        };
+        assert!(accepts_gzip(&headers("gzip")));
+        assert!(accepts_gzip(&headers("br;q=1.0, GZIP;q=0.5")));
+        assert!(accepts_gzip(&headers("*")));
+        assert!(!accepts_gzip(&headers("gzip;q=0")));
+        assert!(!accepts_gzip(&headers("br, deflate")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_large_responses_compressed() {
+        let state = test_state("node-a");
+        let large = "x".repeat(8 * 1024);
+        let app = Router::new()
+            .route("/large", get(move || async move { large }))
+            .route("/small", get(|| async { "small" }))
+            .layer(middleware::from_fn_with_state(state.clone(), compress_response))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let get = |path: &str, accept: Option<&str>| {
+            let mut request = client.get(format!("{}{}", base, path));
+            if let Some(accept) = accept {
+                request = request.header("accept-encoding", accept);
+            }
+            request.send()
+        };
+
+        let resp = get("/large", Some("gzip")).await.unwrap();
+        assert_eq!(resp.headers()["content-encoding"], "gzip");
+        let compressed = resp.bytes().await.unwrap();
+        assert!(compressed.len() < 1024);
+        let mut text = String::new();
+        GzDecoder::new(compressed.as_ref()).read_to_string(&mut text).unwrap();
+        assert_eq!(text.len(), 8 * 1024);
+
+        // Too small, or not asked for
+        let resp = get("/small", Some("gzip")).await.unwrap();
+        assert!(resp.headers().get("content-encoding").is_none());
+        let resp = get("/large", None).await.unwrap();
+        assert!(resp.headers().get("content-encoding").is_none());
+        assert_eq!(resp.bytes().await.unwrap().len(), 8 * 1024);
+    }
+}
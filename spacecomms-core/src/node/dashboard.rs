@@ -0,0 +1,56 @@
+//! Embedded web dashboard
+//!
+//! The dashboard in the repository's `ui/` directory is built into the
+//! binary and served at `/ui/`. It shows active conjunctions by risk, the
+//! peer mesh and a live CDM event feed, all read from the node's own API,
+//! so small operators get situational awareness without Grafana.
+
+use axum::{
+    http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE},
+    response::{Html, IntoResponse, Redirect},
+};
+
+const INDEX_HTML: &str = include_str!("../../../ui/index.html");
+const APP_JS: &str = include_str!("../../../ui/app.js");
+const STYLE_CSS: &str = include_str!("../../../ui/style.css");
+
+/// Policy sent with the dashboard page: scripts and styles only from the
+/// node, no inline scripts. `connect-src` stays open because the page can be
+/// pointed at another node or a hub with `?node=` and `?hub=`.
+const CONTENT_POLICY: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; connect-src *; object-src 'none'; base-uri 'none'; frame-ancestors 'none'";
+
+/// `/ui` without the slash, so the page's relative asset paths resolve
+pub(crate) async fn dashboard_redirect() -> Redirect {
+    Redirect::permanent("/ui/")
+}
+
+pub(crate) async fn dashboard_index() -> impl IntoResponse {
+    ([(CONTENT_SECURITY_POLICY, CONTENT_POLICY)], Html(INDEX_HTML))
+}
+
+pub(crate) async fn dashboard_script() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/javascript; charset=utf-8")], APP_JS)
+}
+
+pub(crate) async fn dashboard_style() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/css; charset=utf-8")], STYLE_CSS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dashboard_assets() {
+        let index = dashboard_index().await.into_response();
+        assert!(index.headers()[CONTENT_SECURITY_POLICY].to_str().unwrap().contains("script-src 'self';"));
+        assert!(INDEX_HTML.contains("src=\"app.js\"") && INDEX_HTML.contains("href=\"style.css\""));
+        assert!(!INDEX_HTML.contains("onclick"));
+        let script = dashboard_script().await.into_response();
+        assert_eq!(script.headers()[CONTENT_TYPE], "text/javascript; charset=utf-8");
+        for endpoint in ["/conjunctions", "/peers", "/events/cdms"] {
+            assert!(APP_JS.contains(endpoint), "{}", endpoint);
+        }
+    }
+}
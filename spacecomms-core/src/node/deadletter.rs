@@ -0,0 +1,237 @@
+//! Dead-letter queue
+//!
+//! Announcements a node could not take in or pass on are kept here instead
+//! of vanishing into the log: relayed messages from peers that failed
+//! validation, were refused by a peer's policies or could not be applied,
+//! and forwards that exhausted their retries or were dropped by a full or
+//! open fan-out lane. Session messages such as HELLO are answered on the
+//! spot and never dead-lettered, nor are replays.
+//!
+//! Operators list dead letters with `GET /deadletter`, and requeue or
+//! discard them once the cause is fixed. The queue is held in memory and
+//! keeps the most recent [`MAX_DEAD_LETTERS`].
+
+use crate::protocol::{Envelope, ErrorCode, MessageType};
+use crate::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Dead letters kept; the oldest are dropped beyond this
+pub const MAX_DEAD_LETTERS: usize = 1000;
+
+/// Why a message was dead-lettered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    /// Received from a peer and failed validation
+    Invalid,
+    /// Received from a peer whose policies or quota refuse it
+    PolicyRejected,
+    /// Received and valid, but the node could not apply it
+    ProcessingFailed,
+    /// Forwarding to a peer failed on every attempt
+    RetriesExhausted,
+    /// Not queued for a peer whose fan-out queue was full or circuit open
+    Dropped,
+    /// Forwarded to a peer that never acknowledged it
+    Unacknowledged,
+}
+
+impl DeadLetterReason {
+    /// Reason for a message received from a peer and refused with `error`
+    pub fn refused(error: &Error) -> Self {
+        match error.error_code() {
+            ErrorCode::InvalidMessage | ErrorCode::UnsupportedVersion => DeadLetterReason::Invalid,
+            ErrorCode::Unauthorized | ErrorCode::RateLimited => DeadLetterReason::PolicyRejected,
+            ErrorCode::InternalError => DeadLetterReason::ProcessingFailed,
+        }
+    }
+
+    /// Whether the message was received, rather than being forwarded
+    pub fn is_inbound(&self) -> bool {
+        matches!(
+            self,
+            DeadLetterReason::Invalid | DeadLetterReason::PolicyRejected | DeadLetterReason::ProcessingFailed
+        )
+    }
+}
+
+/// A message the node gave up on
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DeadLetter {
+    pub id: String,
+    pub reason: DeadLetterReason,
+    /// The last error met
+    pub error: String,
+    /// Peer the message came from, or was being forwarded to
+    pub peer_id: String,
+    pub recorded_at: DateTime<Utc>,
+    /// Times an operator requeued it without success
+    pub requeues: u32,
+    pub envelope: Envelope,
+}
+
+/// Filters for listing dead letters
+#[derive(Debug, Clone, Default)]
+pub struct DeadLetterFilter {
+    pub reason: Option<DeadLetterReason>,
+    pub peer_id: Option<String>,
+    pub message_type: Option<MessageType>,
+}
+
+impl DeadLetterFilter {
+    fn matches(&self, letter: &DeadLetter) -> bool {
+        self.reason.is_none_or(|reason| letter.reason == reason)
+            && self.peer_id.as_ref().is_none_or(|peer| &letter.peer_id == peer)
+            && self
+                .message_type
+                .as_ref()
+                .is_none_or(|message_type| &letter.envelope.message_type == message_type)
+    }
+}
+
+/// Counters of the dead-letter queue
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct DeadLetterMetrics {
+    /// Dead letters held now
+    pub held: u64,
+    /// Messages dead-lettered since startup
+    pub recorded: u64,
+    /// Dead letters dropped to stay within the limit
+    pub evicted: u64,
+}
+
+/// Bounded, in-memory dead-letter queue, oldest first
+#[derive(Default)]
+pub struct DeadLetterQueue {
+    letters: Mutex<VecDeque<DeadLetter>>,
+    recorded: AtomicU64,
+    evicted: AtomicU64,
+}
+
+impl DeadLetterQueue {
+    fn letters(&self) -> MutexGuard<'_, VecDeque<DeadLetter>> {
+        self.letters.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn push(&self, letter: DeadLetter) {
+        let mut letters = self.letters();
+        letters.push_back(letter);
+        while letters.len() > MAX_DEAD_LETTERS {
+            letters.pop_front();
+            self.evicted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Keep a message the node gave up on, returning its dead-letter ID
+    pub fn record(&self, reason: DeadLetterReason, peer_id: &str, envelope: Envelope, error: &Error) -> String {
+        warn!(
+            "Dead-lettered {} {} ({:?}, peer {}): {}",
+            envelope.message_type, envelope.message_id, reason, peer_id, error
+        );
+        let id = uuid::Uuid::new_v4().to_string();
+        self.push(DeadLetter {
+            id: id.clone(),
+            reason,
+            error: error.to_string(),
+            peer_id: peer_id.to_string(),
+            recorded_at: Utc::now(),
+            requeues: 0,
+            envelope,
+        });
+        self.recorded.fetch_add(1, Ordering::Relaxed);
+        id
+    }
+
+    /// Keep a relayed message refused from a peer; other messages and
+    /// replays are left to the error reply
+    pub fn refused(&self, envelope: &Envelope, sender: &str, error: &Error) {
+        if envelope.message_type.is_relayed() && !matches!(error, Error::Replay(_)) {
+            self.record(DeadLetterReason::refused(error), sender, envelope.clone(), error);
+        }
+    }
+
+    /// Dead letters matching a filter, newest first
+    pub fn list(&self, filter: &DeadLetterFilter) -> Vec<DeadLetter> {
+        self.letters().iter().rev().filter(|letter| filter.matches(letter)).cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<DeadLetter> {
+        self.letters().iter().find(|letter| letter.id == id).cloned()
+    }
+
+    /// Remove a dead letter, to discard or requeue it
+    pub fn take(&self, id: &str) -> Option<DeadLetter> {
+        let mut letters = self.letters();
+        let index = letters.iter().position(|letter| letter.id == id)?;
+        letters.remove(index)
+    }
+
+    /// Put back a dead letter whose requeue failed with `error`
+    pub fn restore(&self, mut letter: DeadLetter, error: &Error) -> DeadLetter {
+        letter.requeues += 1;
+        letter.error = error.to_string();
+        self.push(letter.clone());
+        letter
+    }
+
+    pub fn metrics(&self) -> DeadLetterMetrics {
+        DeadLetterMetrics {
+            held: self.letters().len() as u64,
+            recorded: self.recorded.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announce() -> Envelope {
+        Envelope::new("node-x".into(), MessageType::CdmAnnounce, serde_json::json!({ "cdm_id": "CDM-1" }))
+    }
+
+    #[test]
+    fn test_dead_letter_queue() {
+        let queue = DeadLetterQueue::default();
+        queue.refused(&announce(), "node-x", &Error::CdmValidation("bad".into()));
+        queue.refused(&announce(), "node-x", &Error::Unauthorized("policy".into()));
+        // Session messages and replays are not kept
+        let hello = Envelope::new("node-x".into(), MessageType::Hello, serde_json::json!({}));
+        queue.refused(&hello, "node-x", &Error::Protocol("bad".into()));
+        queue.refused(&announce(), "node-x", &Error::Replay("seen".into()));
+        let lost = queue.record(DeadLetterReason::RetriesExhausted, "node-y", announce(), &Error::Peer("down".into()));
+
+        let all = queue.list(&DeadLetterFilter::default());
+        let reasons: Vec<_> = all.iter().map(|letter| letter.reason).collect();
+        assert_eq!(
+            reasons,
+            [DeadLetterReason::RetriesExhausted, DeadLetterReason::PolicyRejected, DeadLetterReason::Invalid]
+        );
+        let filter = DeadLetterFilter {
+            peer_id: Some("node-x".into()),
+            ..Default::default()
+        };
+        assert_eq!(queue.list(&filter).len(), 2);
+        assert!(!DeadLetterReason::RetriesExhausted.is_inbound());
+
+        // A failed requeue goes back with the new error
+        let letter = queue.take(&lost).unwrap();
+        assert!(queue.get(&lost).is_none());
+        let restored = queue.restore(letter, &Error::Peer("still down".into()));
+        assert_eq!((restored.requeues, restored.error.as_str()), (1, "Peer error: still down"));
+        assert_eq!(queue.get(&lost).unwrap().requeues, 1);
+
+        for _ in 0..MAX_DEAD_LETTERS {
+            queue.record(DeadLetterReason::Dropped, "node-y", announce(), &Error::Peer("queue full".into()));
+        }
+        let metrics = queue.metrics();
+        assert_eq!((metrics.held, metrics.recorded, metrics.evicted), (MAX_DEAD_LETTERS as u64, 1003, 3));
+    }
+}
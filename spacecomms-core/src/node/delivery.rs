@@ -0,0 +1,272 @@
+//! Delivery tracking of CDM announcements and withdrawals
+//!
+//! Every CDM_ANNOUNCE and CDM_WITHDRAW this node forwards is tracked per
+//! peer, from being queued to the peer's ACK, so `GET /cdms/{id}/propagation`
+//! can tell which peers have confirmed receipt of the latest one about a
+//! CDM. A newer announcement or withdrawal replaces what was tracked for its
+//! CDM. Peers that do not offer ACK are only ever `delivered`.
+//!
+//! Tracking is held in memory and covers the most recent
+//! [`MAX_TRACKED_CDMS`] CDMs.
+
+use crate::protocol::{Envelope, MessageType};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use utoipa::ToSchema;
+
+/// CDMs tracked; the oldest are forgotten beyond this
+pub const MAX_TRACKED_CDMS: usize = 10_000;
+
+/// Where delivery of an envelope to one peer stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    /// Waiting on the peer's fan-out lane
+    Queued,
+    /// Sent; the peer's ACK has not arrived
+    AwaitingAck,
+    /// Sent to a peer that does not acknowledge
+    Delivered,
+    /// The peer confirmed receipt
+    Acknowledged,
+    /// Not acknowledged after every redelivery
+    Unacknowledged,
+    /// Not queued, or every send attempt failed
+    Failed,
+}
+
+/// Delivery of an envelope to one peer
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PeerDelivery {
+    pub peer_id: String,
+    pub state: DeliveryState,
+    /// Times the envelope was sent again for want of an ACK
+    pub redeliveries: u32,
+    /// Why delivery failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Delivery of the latest announcement or withdrawal of a CDM to peers
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PropagationStatus {
+    pub cdm_id: String,
+    /// ID of the envelope tracked
+    pub message_id: String,
+    pub message_type: MessageType,
+    /// Peers that acknowledged it
+    pub confirmed: Vec<String>,
+    /// Every peer it was forwarded to, by ID
+    pub peers: Vec<PeerDelivery>,
+}
+
+struct Tracked {
+    message_id: String,
+    message_type: MessageType,
+    peers: BTreeMap<String, PeerDelivery>,
+}
+
+#[derive(Default)]
+struct Deliveries {
+    cdms: HashMap<String, Tracked>,
+    /// CDM IDs, oldest first
+    order: VecDeque<String>,
+    /// CDM ID of each tracked envelope
+    messages: HashMap<String, String>,
+}
+
+impl Deliveries {
+    fn delivery(&mut self, message_id: &str, peer_id: &str) -> Option<&mut PeerDelivery> {
+        let cdm_id = self.messages.get(message_id)?;
+        self.cdms.get_mut(cdm_id)?.peers.get_mut(peer_id)
+    }
+}
+
+/// Bounded, in-memory delivery tracking
+#[derive(Default)]
+pub struct DeliveryTracker {
+    deliveries: Mutex<Deliveries>,
+}
+
+/// ID of the CDM an announcement or withdrawal is about; None for envelopes
+/// that are not tracked
+pub fn tracked_cdm(envelope: &Envelope) -> Option<&str> {
+    if !matches!(envelope.message_type, MessageType::CdmAnnounce | MessageType::CdmWithdraw) {
+        return None;
+    }
+    envelope.payload.get("cdm_id").and_then(|id| id.as_str())
+}
+
+impl DeliveryTracker {
+    fn deliveries(&self) -> MutexGuard<'_, Deliveries> {
+        self.deliveries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Track an envelope queued for a peer, replacing an older envelope
+    /// tracked for its CDM; queueing it for the peer again starts its
+    /// redeliveries over
+    pub fn queued(&self, envelope: &Envelope, peer_id: &str) {
+        let Some(cdm_id) = tracked_cdm(envelope) else {
+            return;
+        };
+        let mut deliveries = self.deliveries();
+        let deliveries = &mut *deliveries;
+        let current = deliveries.cdms.get(cdm_id).map(|tracked| tracked.message_id.clone());
+        if current.as_deref() != Some(envelope.message_id.as_str()) {
+            match current {
+                Some(replaced) => {
+                    deliveries.messages.remove(&replaced);
+                }
+                None => {
+                    deliveries.order.push_back(cdm_id.to_string());
+                    while deliveries.order.len() > MAX_TRACKED_CDMS {
+                        if let Some(forgotten) = deliveries.order.pop_front() {
+                            if let Some(tracked) = deliveries.cdms.remove(&forgotten) {
+                                deliveries.messages.remove(&tracked.message_id);
+                            }
+                        }
+                    }
+                }
+            }
+            deliveries.messages.insert(envelope.message_id.clone(), cdm_id.to_string());
+            deliveries.cdms.insert(
+                cdm_id.to_string(),
+                Tracked {
+                    message_id: envelope.message_id.clone(),
+                    message_type: envelope.message_type.clone(),
+                    peers: BTreeMap::new(),
+                },
+            );
+        }
+        let Some(tracked) = deliveries.cdms.get_mut(cdm_id) else {
+            return;
+        };
+        let delivery = tracked.peers.entry(peer_id.to_string()).or_insert_with(|| PeerDelivery {
+            peer_id: peer_id.to_string(),
+            state: DeliveryState::Queued,
+            redeliveries: 0,
+            error: None,
+            updated_at: Utc::now(),
+        });
+        if delivery.state != DeliveryState::Acknowledged {
+            delivery.state = DeliveryState::Queued;
+            delivery.redeliveries = 0;
+            delivery.error = None;
+            delivery.updated_at = Utc::now();
+        }
+    }
+
+    /// Move a tracked delivery to `state`; an acknowledged one stays so.
+    /// Returns false when the envelope is not, or no longer, tracked.
+    pub fn update(&self, message_id: &str, peer_id: &str, state: DeliveryState, error: Option<String>) -> bool {
+        let mut deliveries = self.deliveries();
+        let Some(delivery) = deliveries.delivery(message_id, peer_id) else {
+            return false;
+        };
+        if delivery.state != DeliveryState::Acknowledged {
+            delivery.state = state;
+            delivery.error = error;
+            delivery.updated_at = Utc::now();
+        }
+        true
+    }
+
+    /// Record a peer's ACK, returning false for an envelope not tracked
+    pub fn acknowledge(&self, message_id: &str, peer_id: &str) -> bool {
+        self.update(message_id, peer_id, DeliveryState::Acknowledged, None)
+    }
+
+    /// Count a redelivery and queue the envelope again, returning false
+    /// when it is no longer tracked
+    pub fn redelivered(&self, message_id: &str, peer_id: &str) -> bool {
+        let mut deliveries = self.deliveries();
+        let Some(delivery) = deliveries.delivery(message_id, peer_id) else {
+            return false;
+        };
+        delivery.redeliveries += 1;
+        delivery.state = DeliveryState::Queued;
+        delivery.updated_at = Utc::now();
+        true
+    }
+
+    /// Delivery of an envelope to a peer, while tracked
+    pub fn delivery(&self, message_id: &str, peer_id: &str) -> Option<PeerDelivery> {
+        self.deliveries().delivery(message_id, peer_id).cloned()
+    }
+
+    /// Delivery of the latest envelope about a CDM
+    pub fn status(&self, cdm_id: &str) -> Option<PropagationStatus> {
+        let deliveries = self.deliveries();
+        let tracked = deliveries.cdms.get(cdm_id)?;
+        let peers: Vec<PeerDelivery> = tracked.peers.values().cloned().collect();
+        Some(PropagationStatus {
+            cdm_id: cdm_id.to_string(),
+            message_id: tracked.message_id.clone(),
+            message_type: tracked.message_type.clone(),
+            confirmed: peers
+                .iter()
+                .filter(|peer| peer.state == DeliveryState::Acknowledged)
+                .map(|peer| peer.peer_id.clone())
+                .collect(),
+            peers,
+        })
+    }
+
+    /// CDMs tracked now
+    pub fn len(&self) -> usize {
+        self.deliveries().cdms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deliveries().cdms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn announce(cdm_id: &str) -> Envelope {
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = cdm_id.to_string();
+        Envelope::new("node-a".into(), MessageType::CdmAnnounce, serde_json::to_value(cdm).unwrap())
+    }
+
+    #[test]
+    fn test_delivery_tracker() {
+        let tracker = DeliveryTracker::default();
+        let first = announce("CDM-1");
+        tracker.queued(&first, "node-b");
+        tracker.queued(&first, "node-c");
+        assert!(tracker.update(&first.message_id, "node-b", DeliveryState::AwaitingAck, None));
+        assert!(tracker.acknowledge(&first.message_id, "node-b"));
+        // Late failures do not undo an ACK
+        tracker.update(&first.message_id, "node-b", DeliveryState::Failed, Some("timed out".into()));
+        tracker.update(&first.message_id, "node-c", DeliveryState::AwaitingAck, None);
+        assert!(tracker.redelivered(&first.message_id, "node-c"));
+        assert!(tracker.redelivered(&first.message_id, "node-c"));
+
+        let status = tracker.status("CDM-1").unwrap();
+        assert_eq!(status.confirmed, ["node-b"]);
+        assert_eq!(status.peers.len(), 2);
+        assert_eq!((status.peers[1].state, status.peers[1].redeliveries), (DeliveryState::Queued, 2));
+
+        // A newer announcement replaces the one tracked
+        let second = announce("CDM-1");
+        tracker.queued(&second, "node-c");
+        assert!(!tracker.acknowledge(&first.message_id, "node-b"));
+        let delivery = tracker.delivery(&second.message_id, "node-c").unwrap();
+        assert_eq!((delivery.state, delivery.redeliveries), (DeliveryState::Queued, 0));
+        let status = tracker.status("CDM-1").unwrap();
+        assert_eq!((status.message_id, status.peers.len()), (second.message_id, 1));
+
+        // Only CDM announcements and withdrawals are tracked
+        let hello = Envelope::new("node-a".into(), MessageType::Hello, serde_json::json!({}));
+        tracker.queued(&hello, "node-b");
+        assert_eq!(tracker.len(), 1);
+    }
+}
@@ -0,0 +1,469 @@
+//! Peer discovery from DNS SRV records and seed addresses
+//!
+//! With `discovery` configured, the node looks for peers every refresh
+//! interval: each seed address, and the target of every SRV record under
+//! the configured names. An address is probed with HELLO to learn the node
+//! ID behind it unless its seed names one. New nodes are added as peers with
+//! the discovery template's settings and a session is started; with
+//! `remove_missing`, peers discovery added are removed once no source lists
+//! them. Peers from the configuration file or the API are never changed.
+//!
+//! SRV lookups go straight to a nameserver over UDP, retrying over TCP when
+//! the answer is truncated.
+
+use crate::config::{parse_nameserver, DiscoveryConfig, DnsDiscoveryConfig, PeerTemplate};
+use crate::node::{spawn_session, AppState, HttpTransport, PeerInfo, PeerStatus, Transport};
+use crate::protocol::{Envelope, MessageType};
+use crate::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tracing::{debug, info, warn};
+
+/// How long a nameserver has to answer
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Largest UDP answer read; longer ones come back truncated
+const MAX_UDP_RESPONSE: usize = 4096;
+
+/// One SRV record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// Host name, without the trailing dot
+    pub target: String,
+}
+
+/// What one discovery round changed
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryReport {
+    /// Peers discovery knows about after the round, this node excluded
+    pub found: usize,
+    pub added: Vec<String>,
+    /// Discovered peers whose address changed
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Lookups and probes that failed
+    pub errors: Vec<String>,
+}
+
+/// Peers found so far, carried from one round to the next
+#[derive(Default)]
+pub struct Discovery {
+    /// Node ID behind each probed address
+    probed: HashMap<String, String>,
+    /// Peers this discovery added, with their address
+    added: HashMap<String, String>,
+}
+
+impl Discovery {
+    /// Run one discovery round against the node's current configuration
+    pub async fn refresh(&mut self, state: &AppState) -> DiscoveryReport {
+        let mut report = DiscoveryReport::default();
+        let Some(config) = state.config.get().discovery.clone() else {
+            return report;
+        };
+
+        // A failed lookup must not read as every peer behind it leaving
+        let mut complete = true;
+        let mut addresses: Vec<(String, Option<String>)> = config
+            .seeds
+            .iter()
+            .map(|seed| (seed.address.trim_end_matches('/').to_string(), seed.id.clone()))
+            .collect();
+        for dns in &config.dns {
+            match resolve(dns).await {
+                Ok(found) => addresses.extend(found.into_iter().map(|address| (address, None))),
+                Err(e) => {
+                    complete = false;
+                    report.errors.push(format!("{}: {}", dns.name, e));
+                }
+            }
+        }
+
+        let local_id = state.config.get().node.id.clone();
+        let mut present = HashSet::new();
+        let mut probed = HashMap::new();
+        for (address, id) in addresses {
+            let id = match id.or_else(|| self.probed.get(&address).cloned()) {
+                Some(id) => id,
+                None => match probe(state, &address, &config.template).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        report.errors.push(format!("{}: {}", address, e));
+                        continue;
+                    }
+                },
+            };
+            probed.insert(address.clone(), id.clone());
+            if id == local_id || !present.insert(id.clone()) {
+                continue;
+            }
+            self.track(state, &config, id, address, &mut report).await;
+        }
+        self.probed = probed;
+
+        if config.remove_missing && complete {
+            let missing: Vec<String> = self.added.keys().filter(|id| !present.contains(*id)).cloned().collect();
+            for id in missing {
+                self.added.remove(&id);
+                if state.peers.write().await.remove_peer(&id) {
+                    state.fanout.remove_peer(&id);
+                    info!("Peer {} removed: no longer discovered", id);
+                    report.removed.push(id);
+                }
+            }
+        }
+        report.found = present.len();
+        report
+    }
+
+    /// Add a discovered peer, or follow it to a new address
+    async fn track(
+        &mut self,
+        state: &AppState,
+        config: &DiscoveryConfig,
+        id: String,
+        address: String,
+        report: &mut DiscoveryReport,
+    ) {
+        let mut peers = state.peers.write().await;
+        let known = peers.get_peer(&id).map(|peer| peer.address.clone());
+        match (self.added.get(&id), known) {
+            (Some(previous), Some(_)) if *previous == address => {}
+            (Some(_), Some(_)) => {
+                if let Some(peer) = peers.get_peer_mut(&id) {
+                    peer.address = address.clone();
+                    peer.status = PeerStatus::Disconnected;
+                }
+                peers.drop_link(&id);
+                state.fanout.remove_peer(&id);
+                info!("Discovered peer {} moved to {}", id, address);
+                self.added.insert(id.clone(), address);
+                report.updated.push(id);
+            }
+            // Configured or added through the API
+            (None, Some(_)) => {}
+            // New, or removed through the API since discovery added it
+            (_, None) => {
+                peers.add_peer(PeerInfo::from_config(&config.template.peer(&id, &address)));
+                drop(peers);
+                spawn_session(state.clone(), id.clone());
+                info!("Discovered peer {} at {}", id, address);
+                self.added.insert(id.clone(), address);
+                report.added.push(id);
+            }
+        }
+    }
+}
+
+/// Look for peers every refresh interval for as long as the node runs
+pub fn spawn_discovery(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut discovery = Discovery::default();
+        loop {
+            let report = discovery.refresh(&state).await;
+            for error in &report.errors {
+                warn!("Peer discovery: {}", error);
+            }
+            debug!(
+                "Peer discovery found {} peers: {} added, {} updated, {} removed",
+                report.found,
+                report.added.len(),
+                report.updated.len(),
+                report.removed.len()
+            );
+            let Some(interval) = state.config.get().discovery.as_ref().map(|d| d.refresh_interval_seconds) else {
+                return;
+            };
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
+
+/// Addresses of the nodes listed under an SRV name
+async fn resolve(dns: &DnsDiscoveryConfig) -> Result<Vec<String>> {
+    let nameserver = match &dns.nameserver {
+        Some(nameserver) => parse_nameserver(nameserver)
+            .ok_or_else(|| Error::Config(format!("invalid nameserver {}", nameserver)))?,
+        None => system_nameserver()?,
+    };
+    let mut records = lookup_srv(&dns.name, nameserver).await?;
+    records.sort_by_key(|record| (record.priority, u16::MAX - record.weight));
+    Ok(records
+        .into_iter()
+        // A target of "." means the service is not offered
+        .filter(|record| !record.target.is_empty())
+        .map(|record| format!("{}://{}:{}", dns.scheme, record.target, record.port))
+        .collect())
+}
+
+/// Node ID of whatever answers HELLO at an address
+async fn probe(state: &AppState, address: &str, template: &PeerTemplate) -> Result<String> {
+    let node_id = state.config.get().node.id.clone();
+    let mut hello = state.local_hello();
+    hello.auth_token = template.auth_token.clone();
+    let envelope = Envelope::new(node_id.clone(), MessageType::Hello, serde_json::to_value(hello)?);
+    let reply = HttpTransport::new(address, &node_id, template.auth_token.clone())
+        .send(&envelope)
+        .await?
+        .ok_or_else(|| Error::Protocol("no HELLO reply".into()))?;
+    if reply.message_type != MessageType::Hello {
+        return Err(Error::Protocol(format!("answered HELLO with {}", reply.message_type)));
+    }
+    Ok(reply.source_node_id)
+}
+
+/// First nameserver in `/etc/resolv.conf`
+fn system_nameserver() -> Result<SocketAddr> {
+    std::fs::read_to_string("/etc/resolv.conf")?
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|nameserver| parse_nameserver(nameserver.trim()))
+        .ok_or_else(|| Error::Config("no nameserver in /etc/resolv.conf; set discovery.dns nameserver".into()))
+}
+
+/// SRV records under `name`; none if the name does not exist
+pub async fn lookup_srv(name: &str, nameserver: SocketAddr) -> Result<Vec<SrvRecord>> {
+    let id: u16 = rand::random();
+    let query = srv_query(id, name)?;
+    let lookup = async {
+        let local: SocketAddr = if nameserver.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(nameserver).await?;
+        socket.send(&query).await?;
+        let mut buf = vec![0; MAX_UDP_RESPONSE];
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            // Stray datagrams for other queries are skipped
+            if buf[..len].starts_with(&id.to_be_bytes()) {
+                if let Some(records) = parse_srv_response(id, &buf[..len])? {
+                    return Ok(records);
+                }
+                break;
+            }
+        }
+
+        debug!("SRV answer for {} truncated, asking again over TCP", name);
+        let mut stream = TcpStream::connect(nameserver).await?;
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&query);
+        stream.write_all(&framed).await?;
+        let len = stream.read_u16().await? as usize;
+        let mut response = vec![0; len];
+        stream.read_exact(&mut response).await?;
+        parse_srv_response(id, &response)?.ok_or_else(|| Error::Peer("truncated DNS answer over TCP".into()))
+    };
+    tokio::time::timeout(DNS_TIMEOUT, lookup)
+        .await
+        .map_err(|_| Error::Peer(format!("no answer from nameserver {}", nameserver)))?
+}
+
+/// A recursive SRV query for `name`
+fn srv_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(64);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired; one question
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::Config(format!("invalid DNS name {}", name)));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// SRV records in a response, or `None` if it was truncated
+fn parse_srv_response(id: u16, response: &[u8]) -> Result<Option<Vec<SrvRecord>>> {
+    let malformed = || Error::Peer("malformed DNS answer".into());
+    if response.len() < 12 || u16_at(response, 0)? != id || response[2] & 0x80 == 0 {
+        return Err(malformed());
+    }
+    if response[2] & 0x02 != 0 {
+        return Ok(None);
+    }
+    match response[3] & 0x0f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(Some(Vec::new())),
+        rcode => return Err(Error::Peer(format!("DNS lookup failed with rcode {}", rcode))),
+    }
+    let questions = u16_at(response, 4)?;
+    let answers = u16_at(response, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(response, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        pos = read_name(response, pos)?.1;
+        let kind = u16_at(response, pos)?;
+        let length = u16_at(response, pos + 8)? as usize;
+        let data = pos + 10;
+        if data + length > response.len() {
+            return Err(malformed());
+        }
+        if kind == TYPE_SRV {
+            records.push(SrvRecord {
+                priority: u16_at(response, data)?,
+                weight: u16_at(response, data + 2)?,
+                port: u16_at(response, data + 4)?,
+                target: read_name(response, data + 6)?.0,
+            });
+        }
+        pos = data + length;
+    }
+    Ok(Some(records))
+}
+
+fn u16_at(message: &[u8], pos: usize) -> Result<u16> {
+    message
+        .get(pos..pos + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| Error::Peer("malformed DNS answer".into()))
+}
+
+/// A possibly compressed name and the position after it
+fn read_name(message: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let malformed = || Error::Peer("malformed DNS answer".into());
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds pointer loops
+    for _ in 0..128 {
+        let len = *message.get(pos).ok_or_else(malformed)? as usize;
+        match len {
+            0 => return Ok((labels.join("."), end.unwrap_or(pos + 1))),
+            len if len & 0xc0 == 0xc0 => {
+                let target = (u16_at(message, pos)? & 0x3fff) as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            len => {
+                let label = message.get(pos + 1..pos + 1 + len).ok_or_else(malformed)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            }
+        }
+    }
+    Err(malformed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::receive_message;
+    use crate::node::server::tests::test_state;
+    use crate::node::PROTOCOL_ENDPOINT;
+    use axum::routing::post;
+    use axum::Router;
+
+    /// An answer to `query` listing `records`, the targets compressed
+    /// against the question name
+    fn srv_answer(query: &[u8], records: &[(u16, &str)]) -> Vec<u8> {
+        let mut answer = query.to_vec();
+        answer[2] = 0x81;
+        answer[3] = 0x80;
+        answer[7] = records.len() as u8;
+        for (port, host) in records {
+            let mut target = Vec::new();
+            target.push(host.len() as u8);
+            target.extend_from_slice(host.as_bytes());
+            // The rest of the target is the question name after its service labels
+            target.extend_from_slice(&[0xc0, 12 + 1 + "_spacecomms".len() as u8 + 1 + "_tcp".len() as u8]);
+            answer.extend_from_slice(&[0xc0, 12]);
+            answer.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            answer.extend_from_slice(&CLASS_IN.to_be_bytes());
+            answer.extend_from_slice(&300u32.to_be_bytes());
+            answer.extend_from_slice(&(6 + target.len() as u16).to_be_bytes());
+            answer.extend_from_slice(&[0, 10, 0, 5]);
+            answer.extend_from_slice(&port.to_be_bytes());
+            answer.extend_from_slice(&target);
+        }
+        answer
+    }
+
+    #[tokio::test]
+    async fn test_lookup_srv() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            let (len, from) = server.recv_from(&mut buf).await.unwrap();
+            let answer = srv_answer(&buf[..len], &[(8443, "node-b"), (8443, "node-c")]);
+            server.send_to(&answer, from).await.unwrap();
+        });
+
+        let records = lookup_srv("_spacecomms._tcp.example.org", nameserver).await.unwrap();
+        let targets: Vec<_> = records.iter().map(|r| (r.target.as_str(), r.port)).collect();
+        assert_eq!(targets, [("node-b.example.org", 8443), ("node-c.example.org", 8443)]);
+
+        let query = srv_query(7, "_spacecomms._tcp.example.org").unwrap();
+        let mut missing = srv_answer(&query, &[]);
+        missing[3] = 0x83;
+        assert_eq!(parse_srv_response(7, &missing).unwrap(), Some(Vec::new()));
+        let mut truncated = srv_answer(&query, &[]);
+        truncated[2] |= 0x02;
+        assert_eq!(parse_srv_response(7, &truncated).unwrap(), None);
+        assert!(parse_srv_response(8, &missing).is_err());
+        assert!(srv_query(7, "bad..name").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_discovery_round() {
+        let remote = Router::new()
+            .route(PROTOCOL_ENDPOINT, post(receive_message))
+            .with_state(test_state("node-b"));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node_b = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, remote).await });
+
+        let state = test_state("node-a");
+        state
+            .peers
+            .write()
+            .await
+            .add_peer(PeerInfo::from_config(&serde_yaml::from_str("{ id: node-c, address: 'http://c' }").unwrap()));
+        let configure = |discovery: String| {
+            let mut config = (*state.config.get()).clone();
+            config.discovery = Some(serde_yaml::from_str(&discovery).unwrap());
+            state.config.replace(config);
+        };
+        configure(format!(
+            "{{ remove_missing: true, template: {{ policies: {{ accept_maneuver: false }} }}, seeds: [\
+             {{ address: '{}' }}, {{ address: 'http://127.0.0.1:9', id: node-x }}, {{ address: 'http://c', id: node-c }}, \
+             {{ address: 'http://127.0.0.1:1' }}] }}",
+            node_b
+        ));
+
+        let mut discovery = Discovery::default();
+        let report = discovery.refresh(&state).await;
+        assert_eq!(report.added, ["node-b", "node-x"]);
+        assert_eq!((report.found, report.errors.len()), (3, 1));
+        let peer = state.peers.read().await.get_peer("node-b").unwrap().clone();
+        assert_eq!(peer.address, node_b);
+        assert!(!peer.policies.accept_maneuver);
+
+        // A second round changes nothing; then node-x and node-c drop out
+        // of discovery, and only the discovered one is removed
+        assert!(discovery.refresh(&state).await.added.is_empty());
+        configure(format!("{{ remove_missing: true, seeds: [{{ address: '{}' }}] }}", node_b));
+        let report = discovery.refresh(&state).await;
+        assert_eq!(report.removed, ["node-x"]);
+        let peers = state.peers.read().await;
+        assert!(peers.get_peer("node-x").is_none() && peers.get_peer("node-c").is_some());
+    }
+}
@@ -0,0 +1,256 @@
+//! CDM event feed for watchers
+//!
+//! Every CDM stored or withdrawn on this node is appended to a bounded,
+//! sequence-numbered log. Clients long-poll `GET /events/cdms?since=<seq>`
+//! and receive the events at or after `since`, waiting until one arrives or
+//! the poll times out. TCA countdown escalations are logged here too.
+
+use crate::cdm::CdmRecord;
+use crate::node::Escalation;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+/// Number of events kept for watchers that fall behind
+const MAX_EVENTS: usize = 1000;
+
+/// What happened to a CDM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CdmEventKind {
+    Announced,
+    Withdrawn,
+    /// The conjunction crossed a TCA countdown threshold
+    Escalated,
+}
+
+/// One CDM event
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CdmEvent {
+    /// Position in the event log
+    pub seq: u64,
+    pub kind: CdmEventKind,
+    pub at: DateTime<Utc>,
+    pub cdm_id: String,
+    /// Pc of the CDM, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision_probability: Option<f64>,
+    /// The stored CDM (announcements and escalations)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdm: Option<CdmRecord>,
+    /// Withdrawal reason (withdrawals only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Threshold crossed and action change (escalations only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<Escalation>,
+    /// The CDM as it was when withdrawn, kept for tenant filtering
+    #[serde(skip)]
+    pub(crate) withdrawn: Option<CdmRecord>,
+}
+
+impl CdmEvent {
+    /// The CDM the event is about, when known
+    pub fn record(&self) -> Option<&CdmRecord> {
+        self.cdm.as_ref().or(self.withdrawn.as_ref())
+    }
+}
+
+/// Events returned by one poll
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CdmEventPage {
+    pub events: Vec<CdmEvent>,
+    /// Sequence number to poll from next
+    pub next_seq: u64,
+    /// Events that aged out of the log before this poll
+    #[serde(default)]
+    pub missed: u64,
+}
+
+/// Bounded, sequence-numbered log of CDM events
+pub struct CdmEventLog {
+    events: Mutex<VecDeque<CdmEvent>>,
+    head: watch::Sender<u64>,
+}
+
+impl Default for CdmEventLog {
+    fn default() -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            head: watch::channel(0).0,
+        }
+    }
+}
+
+impl CdmEventLog {
+    /// Sequence number the next event will get
+    pub fn head(&self) -> u64 {
+        *self.head.borrow()
+    }
+
+    /// Record a stored CDM
+    pub fn announced(&self, cdm: &CdmRecord) {
+        self.push(CdmEventKind::Announced, &cdm.cdm_id, Some(cdm), None, None);
+    }
+
+    /// Record a withdrawn CDM, with its last stored version when known
+    pub fn withdrawn(&self, cdm_id: &str, cdm: Option<&CdmRecord>, reason: String) {
+        self.push(CdmEventKind::Withdrawn, cdm_id, cdm, Some(reason), None);
+    }
+
+    /// Record an escalated CDM, returning the logged event
+    pub fn escalated(&self, cdm: &CdmRecord, escalation: Escalation) -> Option<CdmEvent> {
+        self.push(CdmEventKind::Escalated, &cdm.cdm_id, Some(cdm), None, Some(escalation))
+    }
+
+    fn push(
+        &self,
+        kind: CdmEventKind,
+        cdm_id: &str,
+        cdm: Option<&CdmRecord>,
+        reason: Option<String>,
+        escalation: Option<Escalation>,
+    ) -> Option<CdmEvent> {
+        let mut events = self.events.lock().ok()?;
+        let seq = self.head();
+        // A withdrawn CDM is no longer served, so it is only kept internally
+        let (cdm, withdrawn) = match kind {
+            CdmEventKind::Withdrawn => (None, cdm.cloned()),
+            _ => (cdm.cloned(), None),
+        };
+        let event = CdmEvent {
+            seq,
+            kind,
+            at: Utc::now(),
+            cdm_id: cdm_id.to_string(),
+            collision_probability: cdm.as_ref().or(withdrawn.as_ref()).map(|cdm| cdm.collision_probability),
+            cdm,
+            reason,
+            escalation,
+            withdrawn,
+        };
+        events.push_back(event.clone());
+        if events.len() > MAX_EVENTS {
+            events.pop_front();
+        }
+        self.head.send_replace(seq + 1);
+        Some(event)
+    }
+
+    /// Events at or after `since`
+    pub fn page(&self, since: u64) -> CdmEventPage {
+        let Ok(events) = self.events.lock() else {
+            return CdmEventPage {
+                events: Vec::new(),
+                next_seq: since,
+                missed: 0,
+            };
+        };
+        let head = self.head();
+        let oldest = events.front().map_or(head, |e| e.seq);
+        CdmEventPage {
+            events: events.iter().filter(|e| e.seq >= since).cloned().collect(),
+            next_seq: head,
+            missed: oldest.saturating_sub(since),
+        }
+    }
+
+    /// Wait up to `timeout` for events at or after `since`
+    pub async fn wait(&self, since: u64, timeout: Duration) -> CdmEventPage {
+        let mut head = self.head.subscribe();
+        let _ = tokio::time::timeout(timeout, head.wait_for(|head| *head > since)).await;
+        self.page(since)
+    }
+
+    /// Follow events logged from now on
+    pub fn subscribe(self: &Arc<Self>) -> CdmEventSubscription {
+        CdmEventSubscription {
+            log: self.clone(),
+            head: self.head.subscribe(),
+            next_seq: self.head(),
+            pending: VecDeque::new(),
+            missed: 0,
+        }
+    }
+}
+
+/// Events of a node's log in order, as they are logged
+pub struct CdmEventSubscription {
+    log: Arc<CdmEventLog>,
+    head: watch::Receiver<u64>,
+    next_seq: u64,
+    pending: VecDeque<CdmEvent>,
+    missed: u64,
+}
+
+impl CdmEventSubscription {
+    /// The next event, waiting until one is logged
+    pub async fn next(&mut self) -> CdmEvent {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+            let since = self.next_seq;
+            // The log holds the sender, so the wait only ends with a new event
+            let _ = self.head.wait_for(|head| *head > since).await;
+            let page = self.log.page(since);
+            self.missed += page.missed;
+            self.next_seq = page.next_seq;
+            self.pending.extend(page.events);
+        }
+    }
+
+    /// Events that aged out of the log before this subscriber read them
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[tokio::test]
+    async fn test_long_poll() {
+        let log = std::sync::Arc::new(CdmEventLog::default());
+        let cdm = generate_demo_cdm();
+
+        let empty = log.wait(0, Duration::from_millis(10)).await;
+        assert!(empty.events.is_empty());
+        assert_eq!(empty.next_seq, 0);
+
+        let waiter = tokio::spawn({
+            let log = log.clone();
+            async move { log.wait(0, Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        log.announced(&cdm);
+        let page = waiter.await.unwrap();
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].kind, CdmEventKind::Announced);
+        assert_eq!(page.next_seq, 1);
+
+        log.withdrawn(&cdm.cdm_id, Some(&cdm), "TCA_PASSED".into());
+        let page = log.page(1);
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].reason.as_deref(), Some("TCA_PASSED"));
+        assert_eq!(page.next_seq, 2);
+    }
+
+    #[test]
+    fn test_missed_events() {
+        let log = CdmEventLog::default();
+        for _ in 0..MAX_EVENTS + 5 {
+            log.withdrawn("CDM-1", None, "ERROR".into());
+        }
+        let page = log.page(0);
+        assert_eq!(page.missed, 5);
+        assert_eq!(page.events.len(), MAX_EVENTS);
+        assert_eq!(page.next_seq, (MAX_EVENTS + 5) as u64);
+    }
+}
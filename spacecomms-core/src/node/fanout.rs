@@ -0,0 +1,1115 @@
+//! Bounded fan-out of envelopes to peers
+//!
+//! Every peer gets a lane: a queue drained by one worker that keeps at most
+//! `fanout.max_in_flight_per_peer` sends running. A slow peer fills its own
+//! queue and then has new envelopes dropped, without holding up the others
+//! or growing the number of tasks. Failed or timed out sends are retried
+//! with jittered exponential backoff, and a peer whose deliveries keep
+//! failing has its circuit opened: envelopes for it are refused until the
+//! cooldown passes and a trial delivery succeeds.
+//!
+//! Each lane queues by priority class (`fanout.priority_classes`), so an
+//! emergency-screening CDM waiting behind a backlog of object states goes
+//! out with the next free send slot. After `fanout.starvation_limit` sends
+//! ahead of a waiting, less urgent class, that class gets the next one.
+//!
+//! A peer that advertises a credit window in its heartbeats is also flow
+//! controlled: each heartbeat resets its credits to the window, every send
+//! takes one, and once they run out the lane holds its queue until the next
+//! heartbeat. Peers that advertise no window get no limit.
+//!
+//! Limits are read from the running configuration on every envelope, so
+//! reloads apply to queued envelopes too.
+
+use crate::cdm::{ConjunctionCategory, ScreenType};
+use crate::config::{BatchConfig, FanoutConfig, PriorityClass, DEFAULT_PRIORITY_CLASS};
+use crate::node::{rank, SharedConfig, Transport};
+use crate::protocol::{AckPayload, Envelope, EnvelopeBatchPayload, ErrorPayload, MessageType, TimestampFormat};
+use crate::telemetry;
+use crate::{Error, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{debug, info_span, warn, Instrument, Span};
+use utoipa::ToSchema;
+
+/// Called once a delivery has succeeded or given up
+pub type DeliveryCallback = Box<dyn FnOnce(Delivery) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Outcome of delivering one envelope to one peer
+pub struct Delivery {
+    /// Result of the last attempt
+    pub result: Result<Option<Envelope>>,
+    /// Attempts made, including the first
+    pub attempts: u32,
+    /// Time from the first attempt to the outcome
+    pub elapsed: Duration,
+}
+
+/// Why an envelope was not queued for a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The peer's queue is at `fanout.queue_per_peer`
+    QueueFull,
+    /// The peer's circuit is open
+    CircuitOpen,
+}
+
+impl std::fmt::Display for Refusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Refusal::QueueFull => write!(f, "queue full"),
+            Refusal::CircuitOpen => write!(f, "circuit open"),
+        }
+    }
+}
+
+/// Circuit breaker state of a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Deliveries are attempted
+    Closed,
+    /// Envelopes are refused until the cooldown passes
+    Open,
+    /// Cooldown passed; the next failure reopens the circuit
+    HalfOpen,
+}
+
+/// Fan-out state of one peer
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LaneStatus {
+    /// Envelopes waiting for a send slot
+    pub queued: usize,
+    /// Waiting envelopes by priority class, for classes with any
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub queued_by_class: BTreeMap<String, usize>,
+    /// Sends running, including retries
+    pub in_flight: usize,
+    /// Deliveries that failed since the last success
+    pub consecutive_failures: u32,
+    pub circuit: CircuitState,
+    /// Envelopes the peer takes per heartbeat, as it last advertised
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credit_window: Option<u64>,
+    /// Sends left until the peer's next heartbeat, when it has a window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credits: Option<u64>,
+    /// Out of credits with envelopes queued
+    pub paused: bool,
+}
+
+/// Fan-out counters and current load
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FanOutMetrics {
+    /// Send attempts repeated after a failure or timeout
+    pub retries: u64,
+    /// Send attempts that hit `fanout.send_timeout_ms`
+    pub timeouts: u64,
+    /// Envelopes dropped because a peer's queue was full
+    pub dropped_queue_full: u64,
+    /// Envelopes dropped because a peer's circuit was open
+    pub dropped_circuit_open: u64,
+    /// Times a circuit opened
+    pub circuits_opened: u64,
+    /// Times a lane ran out of credits with envelopes queued
+    pub flow_control_pauses: u64,
+    /// Sends given to a less urgent class because it waited past
+    /// `fanout.starvation_limit`
+    pub starvation_promotions: u64,
+    /// ENVELOPE_BATCH requests sent
+    pub batches_sent: u64,
+    /// Envelopes sent in those batches
+    pub batched_envelopes: u64,
+    /// Envelopes waiting across all peers
+    pub queued: usize,
+    /// Sends running across all peers
+    pub in_flight: usize,
+    /// Peers whose circuit is open
+    pub open_circuits: Vec<String>,
+    /// Peers whose lane is waiting for credits
+    pub paused_peers: Vec<String>,
+}
+
+#[derive(Default)]
+struct Counters {
+    retries: AtomicU64,
+    timeouts: AtomicU64,
+    dropped_queue_full: AtomicU64,
+    dropped_circuit_open: AtomicU64,
+    circuits_opened: AtomicU64,
+    flow_control_pauses: AtomicU64,
+    starvation_promotions: AtomicU64,
+    batches_sent: AtomicU64,
+    batched_envelopes: AtomicU64,
+}
+
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+}
+
+/// Credits granted by a peer's heartbeats
+#[derive(Default)]
+struct Flow {
+    /// `None` until the peer advertises a window
+    window: Option<u64>,
+    credits: u64,
+}
+
+struct Job {
+    /// Position of its priority class in the configuration
+    class: usize,
+    envelope: Arc<Envelope>,
+    link: Arc<dyn Transport>,
+    done: DeliveryCallback,
+    /// Covers the time queued as well as the sends
+    span: Span,
+}
+
+/// Waiting jobs by priority class
+#[derive(Default)]
+struct Queues {
+    /// Most urgent class first
+    classes: Vec<VecDeque<Job>>,
+    /// Times each class had jobs waiting while another went first
+    passed: Vec<u32>,
+    /// Set once the lane is dropped; the worker stops when the queues empty
+    closed: bool,
+}
+
+impl Queues {
+    fn push(&mut self, job: Job) {
+        if self.classes.len() <= job.class {
+            self.classes.resize_with(job.class + 1, VecDeque::new);
+            self.passed.resize(job.class + 1, 0);
+        }
+        self.classes[job.class].push_back(job);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.classes.iter().all(VecDeque::is_empty)
+    }
+
+    /// Class of the next job, and whether starvation protection chose it
+    fn next_class(&self, starvation_limit: u32) -> Option<(usize, bool)> {
+        let waiting = |class: &usize| !self.classes[*class].is_empty();
+        let urgent = (0..self.classes.len()).find(waiting)?;
+        let starved = (0..self.classes.len())
+            .rev()
+            .filter(waiting)
+            .find(|class| starvation_limit > 0 && self.passed[*class] >= starvation_limit);
+        let class = starved.unwrap_or(urgent);
+        Some((class, class != urgent))
+    }
+
+    fn peek(&self, starvation_limit: u32) -> Option<&Job> {
+        let (class, _) = self.next_class(starvation_limit)?;
+        self.classes[class].front()
+    }
+
+    /// The next job, and whether starvation protection chose it
+    fn pop(&mut self, starvation_limit: u32) -> Option<(Job, bool)> {
+        let (class, promoted) = self.next_class(starvation_limit)?;
+        for other in class + 1..self.classes.len() {
+            if !self.classes[other].is_empty() {
+                self.passed[other] += 1;
+            }
+        }
+        self.passed[class] = 0;
+        let job = self.classes[class].pop_front()?;
+        Some((job, promoted))
+    }
+}
+
+#[derive(Default)]
+struct Lane {
+    queues: Mutex<Queues>,
+    job_queued: Notify,
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    slot_freed: Notify,
+    breaker: Mutex<Breaker>,
+    flow: Mutex<Flow>,
+    credits_granted: Notify,
+}
+
+impl Lane {
+    fn breaker(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        self.breaker.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn queues(&self) -> std::sync::MutexGuard<'_, Queues> {
+        self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn flow(&self) -> std::sync::MutexGuard<'_, Flow> {
+        self.flow.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn status(&self, classes: &[PriorityClass]) -> LaneStatus {
+        let queued_by_class = self
+            .queues()
+            .classes
+            .iter()
+            .enumerate()
+            .filter(|(_, jobs)| !jobs.is_empty())
+            .map(|(class, jobs)| (class_name(classes, class).to_string(), jobs.len()))
+            .collect();
+        let breaker = self.breaker();
+        let flow = self.flow();
+        let queued = self.queued.load(Ordering::Relaxed);
+        LaneStatus {
+            queued,
+            queued_by_class,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            consecutive_failures: breaker.consecutive_failures,
+            circuit: breaker.state(Instant::now()),
+            credit_window: flow.window,
+            credits: flow.window.map(|_| flow.credits),
+            paused: flow.window.is_some() && flow.credits == 0 && queued > 0,
+        }
+    }
+
+    /// Take a credit for one send, if the peer has any left
+    fn take_credit(&self) -> bool {
+        let mut flow = self.flow();
+        match flow.window {
+            None => true,
+            Some(_) if flow.credits > 0 => {
+                flow.credits -= 1;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Reset credits to a window the peer advertised, or lift the limit
+    fn grant(&self, window: Option<u64>) {
+        let mut flow = self.flow();
+        flow.window = window;
+        flow.credits = window.unwrap_or(0);
+        drop(flow);
+        self.credits_granted.notify_one();
+    }
+
+    /// Update the breaker with a delivery outcome
+    fn record(&self, peer_id: &str, delivered: bool, config: &FanoutConfig, counters: &Counters) {
+        let mut breaker = self.breaker();
+        if delivered {
+            *breaker = Breaker::default();
+            return;
+        }
+        breaker.consecutive_failures += 1;
+        if config.breaker_failures == 0 || breaker.consecutive_failures < config.breaker_failures {
+            return;
+        }
+        let now = Instant::now();
+        if breaker.state(now) != CircuitState::Open {
+            warn!(
+                "Circuit for {} opened after {} failed deliveries",
+                peer_id, breaker.consecutive_failures
+            );
+            counters.circuits_opened.fetch_add(1, Ordering::Relaxed);
+        }
+        breaker.open_until = Some(now + Duration::from_secs(config.breaker_cooldown_seconds));
+    }
+}
+
+struct LaneHandle {
+    lane: Arc<Lane>,
+}
+
+impl Drop for LaneHandle {
+    /// Let the worker stop once it has sent what is queued
+    fn drop(&mut self) {
+        self.lane.queues().closed = true;
+        self.lane.job_queued.notify_one();
+    }
+}
+
+/// Per-peer queues, concurrency limits, retries and circuit breakers
+pub struct FanOut {
+    config: SharedConfig,
+    lanes: Mutex<HashMap<String, LaneHandle>>,
+    counters: Arc<Counters>,
+}
+
+impl FanOut {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            lanes: Mutex::new(HashMap::new()),
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    fn lanes(&self) -> std::sync::MutexGuard<'_, HashMap<String, LaneHandle>> {
+        self.lanes.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queue an envelope for a peer; `done` runs once delivery succeeds or
+    /// gives up. Must be called from within the runtime.
+    pub fn submit(
+        &self,
+        peer_id: &str,
+        envelope: Arc<Envelope>,
+        link: Arc<dyn Transport>,
+        done: DeliveryCallback,
+    ) -> std::result::Result<(), Refusal> {
+        let config = self.config.get();
+        let mut lanes = self.lanes();
+        let handle = lanes
+            .entry(peer_id.to_string())
+            .or_insert_with(|| self.start_lane(peer_id));
+
+        if handle.lane.breaker().state(Instant::now()) == CircuitState::Open {
+            self.counters.dropped_circuit_open.fetch_add(1, Ordering::Relaxed);
+            return Err(Refusal::CircuitOpen);
+        }
+        if handle.lane.queued.load(Ordering::Relaxed) >= config.fanout.queue_per_peer {
+            self.counters.dropped_queue_full.fetch_add(1, Ordering::Relaxed);
+            return Err(Refusal::QueueFull);
+        }
+        handle.lane.queued.fetch_add(1, Ordering::Relaxed);
+        let class = priority_class(&config.fanout.priority_classes, &envelope);
+        let span = info_span!(
+            "forward",
+            peer = %peer_id,
+            message_type = %envelope.message_type,
+            message_id = %envelope.message_id,
+            priority = %class_name(&config.fanout.priority_classes, class),
+        );
+        handle.lane.queues().push(Job {
+            class,
+            envelope,
+            link,
+            done,
+            span,
+        });
+        handle.lane.job_queued.notify_one();
+        Ok(())
+    }
+
+    fn start_lane(&self, peer_id: &str) -> LaneHandle {
+        let lane = Arc::new(Lane::default());
+        tokio::spawn(run_lane(
+            peer_id.to_string(),
+            lane.clone(),
+            self.config.clone(),
+            self.counters.clone(),
+        ));
+        LaneHandle { lane }
+    }
+
+    /// Forget a peer's lane, circuit and credits; envelopes already queued
+    /// are still delivered
+    pub fn remove_peer(&self, peer_id: &str) {
+        if let Some(handle) = self.lanes().remove(peer_id) {
+            handle.lane.grant(None);
+        }
+    }
+
+    /// Forget a peer's lane like [`remove_peer`](Self::remove_peer), but drop
+    /// the envelopes still queued for it, returning them; their delivery
+    /// callbacks never run, and sends already under way finish
+    pub fn purge_peer(&self, peer_id: &str) -> Vec<Arc<Envelope>> {
+        let Some(handle) = self.lanes().remove(peer_id) else {
+            return Vec::new();
+        };
+        let jobs: Vec<Job> = handle.lane.queues().classes.iter_mut().flat_map(|queue| queue.drain(..)).collect();
+        handle.lane.queued.fetch_sub(jobs.len(), Ordering::Relaxed);
+        handle.lane.grant(None);
+        jobs.into_iter().map(|job| job.envelope).collect()
+    }
+
+    /// Reset a peer's credits to the window from its latest heartbeat;
+    /// `None` lifts the limit. Must be called from within the runtime.
+    pub fn grant_credits(&self, peer_id: &str, window: Option<u64>) {
+        let mut lanes = self.lanes();
+        if window.is_none() && !lanes.contains_key(peer_id) {
+            return;
+        }
+        lanes
+            .entry(peer_id.to_string())
+            .or_insert_with(|| self.start_lane(peer_id))
+            .lane
+            .grant(window);
+    }
+
+    /// Fan-out state of a peer, if anything was sent to it
+    pub fn lane_status(&self, peer_id: &str) -> Option<LaneStatus> {
+        let config = self.config.get();
+        self.lanes()
+            .get(peer_id)
+            .map(|handle| handle.lane.status(&config.fanout.priority_classes))
+    }
+
+    pub fn metrics(&self) -> FanOutMetrics {
+        let config = self.config.get();
+        let lanes = self.lanes();
+        let mut metrics = FanOutMetrics {
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            timeouts: self.counters.timeouts.load(Ordering::Relaxed),
+            dropped_queue_full: self.counters.dropped_queue_full.load(Ordering::Relaxed),
+            dropped_circuit_open: self.counters.dropped_circuit_open.load(Ordering::Relaxed),
+            circuits_opened: self.counters.circuits_opened.load(Ordering::Relaxed),
+            flow_control_pauses: self.counters.flow_control_pauses.load(Ordering::Relaxed),
+            starvation_promotions: self.counters.starvation_promotions.load(Ordering::Relaxed),
+            batches_sent: self.counters.batches_sent.load(Ordering::Relaxed),
+            batched_envelopes: self.counters.batched_envelopes.load(Ordering::Relaxed),
+            queued: 0,
+            in_flight: 0,
+            open_circuits: Vec::new(),
+            paused_peers: Vec::new(),
+        };
+        for (peer_id, handle) in lanes.iter() {
+            let status = handle.lane.status(&config.fanout.priority_classes);
+            metrics.queued += status.queued;
+            metrics.in_flight += status.in_flight;
+            if status.circuit == CircuitState::Open {
+                metrics.open_circuits.push(peer_id.clone());
+            }
+            if status.paused {
+                metrics.paused_peers.push(peer_id.clone());
+            }
+        }
+        metrics.open_circuits.sort();
+        metrics.paused_peers.sort();
+        metrics
+    }
+}
+
+/// Start queued jobs as send slots and credits free up, until the lane is
+/// dropped
+async fn run_lane(peer_id: String, lane: Arc<Lane>, config: SharedConfig, counters: Arc<Counters>) {
+    loop {
+        loop {
+            let (empty, closed) = {
+                let queues = lane.queues();
+                (queues.is_empty(), queues.closed)
+            };
+            if !empty {
+                break;
+            }
+            if closed {
+                return;
+            }
+            lane.job_queued.notified().await;
+        }
+        // The job is chosen once it can go, so the most urgent one waiting
+        // then is sent
+        while lane.in_flight.load(Ordering::Relaxed) >= config.get().fanout.max_in_flight_per_peer.max(1) {
+            lane.slot_freed.notified().await;
+        }
+        if !lane.take_credit() {
+            debug!("Forwarding to {} paused until its next heartbeat", peer_id);
+            counters.flow_control_pauses.fetch_add(1, Ordering::Relaxed);
+            while !lane.take_credit() {
+                lane.credits_granted.notified().await;
+            }
+        }
+        // Only this worker takes jobs, so the queue still has one
+        let next = lane.queues().pop(config.get().fanout.starvation_limit);
+        let Some((job, promoted)) = next else {
+            continue;
+        };
+        if promoted {
+            counters.starvation_promotions.fetch_add(1, Ordering::Relaxed);
+        }
+        lane.queued.fetch_sub(1, Ordering::Relaxed);
+        lane.in_flight.fetch_add(1, Ordering::Relaxed);
+
+        let batch = config.get().fanout.batch.clone();
+        if !job.link.accepts_batches() || batch.max_envelopes < 2 {
+            let span = job.span.clone();
+            tokio::spawn(deliver(peer_id.clone(), lane.clone(), job, config.clone(), counters.clone()).instrument(span));
+            continue;
+        }
+        let jobs = gather(&lane, job, &batch, &config, &counters).await;
+        if jobs.len() == 1 {
+            let job = jobs.into_iter().next().expect("a batch has its first job");
+            let span = job.span.clone();
+            tokio::spawn(deliver(peer_id.clone(), lane.clone(), job, config.clone(), counters.clone()).instrument(span));
+        } else {
+            let span = info_span!("forward_batch", peer = %peer_id, envelopes = jobs.len());
+            tokio::spawn(
+                deliver_batch(peer_id.clone(), lane.clone(), jobs, config.clone(), counters.clone()).instrument(span),
+            );
+        }
+    }
+}
+
+/// Jobs to send along with `first`: those waiting for the same link, in
+/// the order they would go alone, up to the batch limits. Waits up to
+/// `batch.max_delay_ms` for more to arrive.
+async fn gather(lane: &Lane, first: Job, batch: &BatchConfig, config: &SharedConfig, counters: &Counters) -> Vec<Job> {
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(batch.max_delay_ms);
+    let mut bytes = encoded_size(&first.envelope);
+    let mut jobs = vec![first];
+    while jobs.len() < batch.max_envelopes {
+        let starvation_limit = config.get().fanout.starvation_limit;
+        let fits = {
+            let queues = lane.queues();
+            queues.peek(starvation_limit).map(|next| {
+                let size = encoded_size(&next.envelope);
+                let same_link = std::ptr::addr_eq(Arc::as_ptr(&next.link), Arc::as_ptr(&jobs[0].link));
+                (same_link && bytes + size <= batch.max_bytes, size)
+            })
+        };
+        match fits {
+            Some((true, size)) if lane.take_credit() => {
+                let next = lane.queues().pop(starvation_limit);
+                let Some((job, promoted)) = next else {
+                    break;
+                };
+                if promoted {
+                    counters.starvation_promotions.fetch_add(1, Ordering::Relaxed);
+                }
+                lane.queued.fetch_sub(1, Ordering::Relaxed);
+                bytes += size;
+                jobs.push(job);
+            }
+            // Full, for another link, or out of credits: this one goes next
+            Some(_) => break,
+            None => {
+                let now = tokio::time::Instant::now();
+                if now >= deadline || tokio::time::timeout(deadline - now, lane.job_queued.notified()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    jobs
+}
+
+/// Rough encoded size of an envelope, for batch limits
+fn encoded_size(envelope: &Envelope) -> usize {
+    // Header fields, generously
+    const HEADER_BYTES: usize = 512;
+    HEADER_BYTES + envelope.payload.to_json(TimestampFormat::Auto).map_or(0, <[u8]>::len)
+}
+
+/// Send with timeout and retries, then report the outcome
+async fn deliver(peer_id: String, lane: Arc<Lane>, job: Job, config: SharedConfig, counters: Arc<Counters>) {
+    let started = Instant::now();
+    let envelope = traced(&job);
+    let (result, attempts) = send(&peer_id, &lane, job.link.as_ref(), &envelope, &config, &counters).await;
+    (job.done)(Delivery {
+        result,
+        attempts,
+        elapsed: started.elapsed(),
+    })
+    .await;
+}
+
+/// Send jobs as one ENVELOPE_BATCH, then report each one's outcome
+async fn deliver_batch(peer_id: String, lane: Arc<Lane>, jobs: Vec<Job>, config: SharedConfig, counters: Arc<Counters>) {
+    let started = Instant::now();
+    let batch = EnvelopeBatchPayload {
+        envelopes: jobs.iter().map(|job| (*traced(job)).clone()).collect(),
+    };
+    let link = jobs[0].link.clone();
+    let outcome = match serde_json::to_value(batch) {
+        Ok(payload) => {
+            let envelope = Envelope::new(config.get().node.id.clone(), MessageType::EnvelopeBatch, payload);
+            send(&peer_id, &lane, link.as_ref(), &envelope, &config, &counters).await
+        }
+        Err(e) => {
+            lane.in_flight.fetch_sub(1, Ordering::Relaxed);
+            lane.slot_freed.notify_one();
+            (Err(e.into()), 0)
+        }
+    };
+    counters.batches_sent.fetch_add(1, Ordering::Relaxed);
+    counters.batched_envelopes.fetch_add(jobs.len() as u64, Ordering::Relaxed);
+
+    // A reply lists the envelopes the peer refused, and those it acknowledges
+    let (result, attempts) = outcome;
+    let replies: Vec<Envelope> = match &result {
+        Ok(Some(reply)) if reply.message_type == MessageType::EnvelopeBatch => reply
+            .payload
+            .parse::<EnvelopeBatchPayload>()
+            .map(|reply| reply.envelopes)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let refused: HashMap<String, String> = replies
+        .iter()
+        .filter_map(|envelope| envelope.payload.parse::<ErrorPayload>().ok())
+        .filter_map(|error| {
+            let reason = format!("{:?}: {}", error.error_code, error.error_message);
+            Some((error.related_message_id?, reason))
+        })
+        .collect();
+    let mut acks: HashMap<String, Envelope> = replies
+        .into_iter()
+        .filter(|envelope| envelope.message_type == MessageType::Ack)
+        .filter_map(|envelope| Some((envelope.payload.parse::<AckPayload>().ok()?.related_message_id, envelope)))
+        .collect();
+    let failed = result.as_ref().err().map(|e| e.to_string());
+    for job in jobs {
+        let result = match (&failed, refused.get(&job.envelope.message_id)) {
+            (Some(e), _) => Err(Error::Peer(e.clone())),
+            (None, Some(reason)) => Err(Error::PeerRefused(format!(
+                "{} rejected batched {}: {}",
+                peer_id, job.envelope.message_type, reason
+            ))),
+            (None, None) => Ok(acks.remove(&job.envelope.message_id)),
+        };
+        (job.done)(Delivery {
+            result,
+            attempts,
+            elapsed: started.elapsed(),
+        })
+        .await;
+    }
+}
+
+/// A job's envelope, carrying its trace context so the next hop continues
+/// the trace from this forward
+fn traced(job: &Job) -> Arc<Envelope> {
+    match telemetry::traceparent(&job.span) {
+        Some(traceparent) => Arc::new(Envelope {
+            traceparent: Some(traceparent),
+            ..(*job.envelope).clone()
+        }),
+        None => job.envelope.clone(),
+    }
+}
+
+/// Send with timeout and retries, then update the breaker and free the
+/// send slot
+async fn send(
+    peer_id: &str,
+    lane: &Lane,
+    link: &dyn Transport,
+    envelope: &Envelope,
+    config: &SharedConfig,
+    counters: &Counters,
+) -> (Result<Option<Envelope>>, u32) {
+    let mut attempts = 0;
+    let (result, fanout) = loop {
+        let fanout = config.get().fanout.clone();
+        attempts += 1;
+        let timeout = Duration::from_millis(fanout.send_timeout_ms);
+        let result = match tokio::time::timeout(timeout, link.send(envelope)).await {
+            Ok(result) => result,
+            Err(_) => {
+                counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(Error::Peer(format!("send timed out after {}ms", fanout.send_timeout_ms)))
+            }
+        };
+        // Stop early if other deliveries opened the circuit meanwhile
+        let circuit_open = lane.breaker().state(Instant::now()) == CircuitState::Open;
+        if result.is_ok() || attempts > fanout.max_retries || circuit_open {
+            break (result, fanout);
+        }
+        counters.retries.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(backoff(&fanout, attempts)).await;
+    };
+
+    lane.record(peer_id, result.is_ok(), &fanout, counters);
+    lane.in_flight.fetch_sub(1, Ordering::Relaxed);
+    lane.slot_freed.notify_one();
+    (result, attempts)
+}
+
+/// Position of the first priority class an envelope is in;
+/// `classes.len()` when it is in none
+fn priority_class(classes: &[PriorityClass], envelope: &Envelope) -> usize {
+    classes
+        .iter()
+        .position(|class| in_class(class, envelope))
+        .unwrap_or(classes.len())
+}
+
+fn in_class(class: &PriorityClass, envelope: &Envelope) -> bool {
+    if !class.message_types.is_empty() && !class.message_types.contains(&envelope.message_type) {
+        return false;
+    }
+    if class.min_severity.is_none() && class.screen_types.is_empty() {
+        return true;
+    }
+    if envelope.message_type != MessageType::CdmAnnounce {
+        return false;
+    }
+    let severe = class.min_severity.as_ref().is_some_and(|min| {
+        envelope
+            .payload
+            .get("conjunction_category")
+            .and_then(|category| ConjunctionCategory::deserialize(category).ok())
+            .is_some_and(|category| rank(&category) >= rank(min))
+    });
+    let screened = envelope
+        .payload
+        .pointer("/screening_data/screen_type")
+        .and_then(|screen| ScreenType::deserialize(screen).ok())
+        .is_some_and(|screen| class.screen_types.contains(&screen));
+    severe || screened
+}
+
+fn class_name(classes: &[PriorityClass], class: usize) -> &str {
+    classes.get(class).map_or(DEFAULT_PRIORITY_CLASS, |class| class.name.as_str())
+}
+
+/// Delay before retry number `retry`: doubling from `retry_base_ms`, less
+/// a random amount up to half
+fn backoff(config: &FanoutConfig, retry: u32) -> Duration {
+    let full = config.retry_base_ms.saturating_mul(1 << (retry - 1).min(16));
+    let jitter = rand::thread_rng().gen_range(0..=full / 2);
+    Duration::from_millis(full - jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, PeerTransport};
+    use crate::protocol::MessageType;
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+
+    /// Link that takes `delay` per send and fails the first `failures` sends
+    struct TestLink {
+        delay: Duration,
+        failures: AtomicUsize,
+        sends: AtomicUsize,
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+        traceparents: Mutex<Vec<Option<String>>>,
+        message_ids: Mutex<Vec<String>>,
+        /// Whether ENVELOPE_BATCH may be sent; batched envelopes with the
+        /// ID `refuse` are refused in the reply
+        batching: bool,
+        batch_sizes: Mutex<Vec<usize>>,
+    }
+
+    impl TestLink {
+        fn new(delay_ms: u64, failures: usize) -> Arc<Self> {
+            Arc::new(Self {
+                delay: Duration::from_millis(delay_ms),
+                failures: AtomicUsize::new(failures),
+                sends: AtomicUsize::new(0),
+                running: AtomicUsize::new(0),
+                max_running: AtomicUsize::new(0),
+                traceparents: Mutex::new(Vec::new()),
+                message_ids: Mutex::new(Vec::new()),
+                batching: false,
+                batch_sizes: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn batching(delay_ms: u64) -> Arc<Self> {
+            let mut link = Arc::into_inner(Self::new(delay_ms, 0)).unwrap();
+            link.batching = true;
+            Arc::new(link)
+        }
+    }
+
+    #[async_trait]
+    impl Transport for TestLink {
+        fn kind(&self) -> PeerTransport {
+            PeerTransport::Http
+        }
+
+        fn accepts_batches(&self) -> bool {
+            self.batching
+        }
+
+        async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+            self.sends.fetch_add(1, Ordering::Relaxed);
+            self.traceparents.lock().unwrap().push(envelope.traceparent.clone());
+            if envelope.message_type == MessageType::EnvelopeBatch {
+                let batch: EnvelopeBatchPayload = envelope.payload.parse()?;
+                self.batch_sizes.lock().unwrap().push(batch.envelopes.len());
+                let refused: Vec<_> = batch
+                    .envelopes
+                    .iter()
+                    .filter(|inner| inner.message_id == "refuse")
+                    .map(|inner| {
+                        let error = ErrorPayload::from_error(&Error::CdmValidation("no".into()), Some(inner.message_id.clone()));
+                        Envelope::error("b".into(), error)
+                    })
+                    .collect();
+                let ids = batch.envelopes.into_iter().map(|inner| inner.message_id);
+                self.message_ids.lock().unwrap().extend(ids);
+                tokio::time::sleep(self.delay).await;
+                let reply = EnvelopeBatchPayload { envelopes: refused };
+                return Ok(Some(Envelope::new("b".into(), MessageType::EnvelopeBatch, serde_json::to_value(reply)?)));
+            }
+            self.message_ids.lock().unwrap().push(envelope.message_id.clone());
+            let running = self.running.fetch_add(1, Ordering::Relaxed) + 1;
+            self.max_running.fetch_max(running, Ordering::Relaxed);
+            tokio::time::sleep(self.delay).await;
+            self.running.fetch_sub(1, Ordering::Relaxed);
+            let failing = self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                return Err(Error::Peer("refused".into()));
+            }
+            Ok(None)
+        }
+    }
+
+    fn fan_out(yaml: &str) -> FanOut {
+        let config: Config = serde_yaml::from_str(&format!("node: {{ id: node-a }}\nserver: {{}}\nfanout: {}", yaml)).unwrap();
+        FanOut::new(SharedConfig::new(config))
+    }
+
+    fn envelope() -> Arc<Envelope> {
+        Arc::new(Envelope::new("node-a".into(), MessageType::Heartbeat, serde_json::json!({})))
+    }
+
+    /// Callback sending the number of attempts once delivery finishes
+    fn report(outcomes: &mpsc::UnboundedSender<(bool, u32)>) -> DeliveryCallback {
+        let outcomes = outcomes.clone();
+        Box::new(move |delivery| {
+            Box::pin(async move {
+                let _ = outcomes.send((delivery.result.is_ok(), delivery.attempts));
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_forward_carries_trace_context() {
+        use opentelemetry::trace::TracerProvider as _;
+        use tracing_subscriber::prelude::*;
+
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let fanout = fan_out("{}");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let link = TestLink::new(0, 0);
+
+        let request = info_span!("request");
+        request
+            .in_scope(|| fanout.submit("b", envelope(), link.clone(), report(&tx)))
+            .unwrap();
+        assert_eq!(outcomes.recv().await, Some((true, 1)));
+        let sent = link.traceparents.lock().unwrap()[0].clone().unwrap();
+        // Same trace as the request, different span
+        let request = telemetry::traceparent(&request).unwrap();
+        assert_eq!(sent[3..35], request[3..35]);
+        assert_ne!(sent, request);
+    }
+
+    #[tokio::test]
+    async fn test_slow_peer_is_isolated() {
+        let fanout = fan_out("{ max_in_flight_per_peer: 2, queue_per_peer: 3 }");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let slow = TestLink::new(200, 0);
+        let fast = TestLink::new(0, 0);
+
+        // 2 sending, 3 queued, the rest refused
+        let mut refused = 0;
+        for _ in 0..8 {
+            if fanout.submit("slow", envelope(), slow.clone(), report(&tx)).is_err() {
+                refused += 1;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(refused, 3);
+        let status = fanout.lane_status("slow").unwrap();
+        assert_eq!((status.in_flight, status.queued), (2, 3));
+
+        fanout.submit("fast", envelope(), fast.clone(), report(&tx)).unwrap();
+        assert_eq!(outcomes.recv().await, Some((true, 1)));
+        assert_eq!(fast.sends.load(Ordering::Relaxed), 1);
+        assert_eq!(slow.sends.load(Ordering::Relaxed), 2);
+
+        for _ in 0..5 {
+            outcomes.recv().await.unwrap();
+        }
+        assert_eq!(slow.max_running.load(Ordering::Relaxed), 2);
+        assert_eq!(fanout.metrics().dropped_queue_full, 3);
+        assert_eq!(fanout.metrics().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_circuit_breaker() {
+        let fanout = fan_out(
+            "{ send_timeout_ms: 50, max_retries: 2, retry_base_ms: 1, breaker_failures: 2, breaker_cooldown_seconds: 1 }",
+        );
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+
+        // Two failures, then the third attempt gets through
+        let flaky = TestLink::new(0, 2);
+        fanout.submit("flaky", envelope(), flaky, report(&tx)).unwrap();
+        assert_eq!(outcomes.recv().await, Some((true, 3)));
+
+        // A peer that never answers in time fails two deliveries in a row
+        let stuck = TestLink::new(1000, 0);
+        for _ in 0..2 {
+            fanout.submit("stuck", envelope(), stuck.clone(), report(&tx)).unwrap();
+            assert!(!outcomes.recv().await.unwrap().0);
+        }
+        let metrics = fanout.metrics();
+        assert_eq!(metrics.retries, 2 + 4);
+        assert_eq!(metrics.timeouts, 6);
+        assert_eq!(metrics.circuits_opened, 1);
+        assert_eq!(metrics.open_circuits, ["stuck"]);
+        assert_eq!(
+            fanout.submit("stuck", envelope(), stuck.clone(), report(&tx)),
+            Err(Refusal::CircuitOpen)
+        );
+
+        // After the cooldown a trial delivery is let through and closes it
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(fanout.lane_status("stuck").unwrap().circuit, CircuitState::HalfOpen);
+        fanout.submit("stuck", envelope(), TestLink::new(0, 0), report(&tx)).unwrap();
+        assert_eq!(outcomes.recv().await, Some((true, 1)));
+        let status = fanout.lane_status("stuck").unwrap();
+        assert_eq!((status.circuit, status.consecutive_failures), (CircuitState::Closed, 0));
+    }
+
+    #[tokio::test]
+    async fn test_credit_window() {
+        let fanout = fan_out("{}");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let link = TestLink::new(0, 0);
+
+        // Two credits: two sends go out, the third waits for a heartbeat
+        fanout.grant_credits("slow", Some(2));
+        for _ in 0..3 {
+            fanout.submit("slow", envelope(), link.clone(), report(&tx)).unwrap();
+        }
+        for _ in 0..2 {
+            outcomes.recv().await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(link.sends.load(Ordering::Relaxed), 2);
+        let status = fanout.lane_status("slow").unwrap();
+        assert_eq!((status.credit_window, status.credits, status.queued), (Some(2), Some(0), 1));
+        assert!(status.paused);
+        let metrics = fanout.metrics();
+        assert_eq!((metrics.paused_peers, metrics.flow_control_pauses), (vec!["slow".to_string()], 1));
+
+        fanout.grant_credits("slow", Some(2));
+        outcomes.recv().await.unwrap();
+        let status = fanout.lane_status("slow").unwrap();
+        assert_eq!((status.credits, status.paused), (Some(1), false));
+
+        // A heartbeat without a window lifts the limit
+        fanout.grant_credits("slow", None);
+        for _ in 0..3 {
+            fanout.submit("slow", envelope(), link.clone(), report(&tx)).unwrap();
+            outcomes.recv().await.unwrap();
+        }
+        assert_eq!(fanout.lane_status("slow").unwrap().credits, None);
+    }
+
+    #[tokio::test]
+    async fn test_purge_peer() {
+        let fanout = fan_out("{}");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let link = TestLink::new(0, 0);
+
+        // One credit: the first send goes out, the rest stay queued
+        fanout.grant_credits("blocked", Some(1));
+        for _ in 0..3 {
+            fanout.submit("blocked", envelope(), link.clone(), report(&tx)).unwrap();
+        }
+        outcomes.recv().await.unwrap();
+        assert_eq!(fanout.purge_peer("blocked").len(), 2);
+        assert!(fanout.lane_status("blocked").is_none());
+        assert!(fanout.purge_peer("blocked").is_empty());
+        drop(tx);
+        assert_eq!(outcomes.recv().await, None);
+        assert_eq!(link.sends.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_priority_classes() {
+        let fanout = fan_out("{ max_in_flight_per_peer: 1, starvation_limit: 2 }");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let link = TestLink::new(20, 0);
+        let submit = |id: &str, message_type: MessageType, payload: serde_json::Value| {
+            let mut envelope = Envelope::new("node-a".into(), message_type, payload);
+            envelope.message_id = id.to_string();
+            fanout.submit("b", Arc::new(envelope), link.clone(), report(&tx)).unwrap();
+        };
+        let state = || MessageType::ObjectStateAnnounce;
+        let cdm = || MessageType::CdmAnnounce;
+
+        submit("r1", state(), serde_json::json!({}));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        for id in ["r2", "r3", "r4"] {
+            submit(id, state(), serde_json::json!({}));
+        }
+        submit("c1", cdm(), serde_json::json!({ "conjunction_category": "LOW" }));
+        submit("e1", cdm(), serde_json::json!({ "conjunction_category": "HIGH" }));
+        submit("e2", cdm(), serde_json::json!({ "screening_data": { "screen_type": "EMERGENCY" } }));
+        submit("e3", cdm(), serde_json::json!({ "conjunction_category": "HIGH" }));
+        let queued = fanout.lane_status("b").unwrap().queued_by_class;
+        assert_eq!((queued["emergency"], queued["conjunction"], queued["routine"]), (3, 1, 3));
+
+        for _ in 0..8 {
+            outcomes.recv().await.unwrap();
+        }
+        // Emergencies jump the queue, but every two sends ahead of a
+        // waiting class let one of its envelopes through
+        let order = link.message_ids.lock().unwrap().clone();
+        assert_eq!(order, ["r1", "e1", "e2", "r2", "c1", "e3", "r3", "r4"]);
+        assert_eq!(fanout.metrics().starvation_promotions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_batching() {
+        let fanout = fan_out("{ max_in_flight_per_peer: 1, batch: { max_envelopes: 3 } }");
+        let (tx, mut outcomes) = mpsc::unbounded_channel();
+        let link = TestLink::batching(20);
+        let submit = |id: &str| {
+            let mut envelope = Envelope::new("node-a".into(), MessageType::CdmAnnounce, serde_json::json!({}));
+            envelope.message_id = id.to_string();
+            fanout.submit("b", Arc::new(envelope), link.clone(), report(&tx)).unwrap();
+        };
+
+        submit("m0");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        for id in ["m1", "refuse", "m3", "m4", "m5"] {
+            submit(id);
+        }
+        let mut delivered = 0;
+        for _ in 0..6 {
+            delivered += outcomes.recv().await.unwrap().0 as usize;
+        }
+        // The first goes alone; the rest wait behind it and leave together,
+        // up to three at a time
+        assert_eq!(*link.batch_sizes.lock().unwrap(), [3, 2]);
+        assert_eq!(*link.message_ids.lock().unwrap(), ["m0", "m1", "refuse", "m3", "m4", "m5"]);
+        assert_eq!(delivered, 5);
+        let metrics = fanout.metrics();
+        assert_eq!((metrics.batches_sent, metrics.batched_envelopes), (2, 5));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = FanoutConfig::default();
+        for retry in 1..=3 {
+            let full = config.retry_base_ms << (retry - 1);
+            let delay = backoff(&config, retry).as_millis() as u64;
+            assert!(full / 2 <= delay && delay <= full);
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! gRPC peer transport
+//!
+//! Peers that advertise the `GRPC_STREAM` capability accept a single
+//! bidirectional stream at `/spacecomms.v1.PeerExchange/Exchange`. Each gRPC
+//! frame carries one JSON-encoded [`Envelope`], so the stream shares message
+//! schemas with the HTTP protocol endpoint and needs no protobuf definitions.
+//!
+//! The dialing side opens the stream with a HELLO so the listener can bind
+//! the stream to the peer its `auth_token` authenticates; the listener
+//! answers on the response stream, and closes a stream that opens with
+//! anything else or fails to authenticate.
+
+use crate::config::PeerTransport;
+use crate::node::{
+    authenticate_peer, peer_credential, process_envelope, record_peer_outcome, AppState, SessionEventKind, Transport,
+};
+use crate::protocol::{Encoding, Envelope, ErrorPayload, MessageType, TimestampFormat};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::body::BoxBody;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder, Streaming};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::codegen::{http, Body, BoxFuture, Service, StdError};
+use tonic::server::NamedService;
+use tonic::Status;
+use tracing::{debug, info, warn};
+
+/// Fully qualified gRPC service name
+pub const GRPC_SERVICE_NAME: &str = "spacecomms.v1.PeerExchange";
+
+/// Path of the bidirectional envelope stream method
+pub const GRPC_EXCHANGE_PATH: &str = "/spacecomms.v1.PeerExchange/Exchange";
+
+/// Outbound envelopes buffered per stream before senders wait
+const STREAM_BUFFER: usize = 256;
+
+// ============================================================================
+// Codec
+// ============================================================================
+
+/// gRPC codec carrying JSON-encoded envelopes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvelopeCodec {
+    timestamp_format: TimestampFormat,
+}
+
+impl EnvelopeCodec {
+    /// Create a codec writing timestamps with the given profile
+    pub fn new(timestamp_format: TimestampFormat) -> Self {
+        Self { timestamp_format }
+    }
+}
+
+impl Codec for EnvelopeCodec {
+    type Encode = Envelope;
+    type Decode = Envelope;
+    type Encoder = EnvelopeCodec;
+    type Decoder = EnvelopeCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        *self
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        *self
+    }
+}
+
+impl Encoder for EnvelopeCodec {
+    type Item = Envelope;
+    type Error = Status;
+
+    fn encode(&mut self, item: Envelope, dst: &mut EncodeBuf<'_>) -> std::result::Result<(), Status> {
+        let bytes = item
+            .encode_with(Encoding::Json, self.timestamp_format)
+            .map_err(|e| Status::internal(format!("envelope encoding failed: {}", e)))?;
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+}
+
+impl Decoder for EnvelopeCodec {
+    type Item = Envelope;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> std::result::Result<Option<Envelope>, Status> {
+        serde_json::from_reader(src.reader())
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(format!("invalid envelope: {}", e)))
+    }
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// gRPC service accepting peer envelope streams
+#[derive(Clone)]
+pub struct PeerExchangeServer {
+    state: AppState,
+}
+
+impl PeerExchangeServer {
+    /// Create the service over the node's shared state
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl NamedService for PeerExchangeServer {
+    const NAME: &'static str = GRPC_SERVICE_NAME;
+}
+
+impl<B> Service<http::Request<B>> for PeerExchangeServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if req.uri().path() != GRPC_EXCHANGE_PATH {
+            return Box::pin(async { Ok(Status::unimplemented("unknown method").into_http()) });
+        }
+        if !self.state.leadership.is_leader() {
+            return Box::pin(async { Ok(Status::unavailable("this instance follows another").into_http()) });
+        }
+
+        let exchange = Exchange {
+            state: self.state.clone(),
+        };
+        // The stream is bound to a peer only after its HELLO, so replies use the node default
+        let codec = EnvelopeCodec::new(self.state.config.get().protocol.timestamp_format);
+        let max_message_size = self.state.config.get().protocol.max_envelope_bytes;
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(codec).max_decoding_message_size(max_message_size);
+            Ok(grpc.streaming(exchange, req).await)
+        })
+    }
+}
+
+/// Handler for a single inbound envelope stream
+struct Exchange {
+    state: AppState,
+}
+
+impl Service<tonic::Request<Streaming<Envelope>>> for Exchange {
+    type Response = tonic::Response<ReceiverStream<std::result::Result<Envelope, Status>>>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<Streaming<Envelope>>) -> Self::Future {
+        let state = self.state.clone();
+        Box::pin(async move {
+            let mut inbound = request.into_inner();
+            let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+
+            tokio::spawn(async move {
+                let mut peer_id: Option<String> = None;
+                loop {
+                    // A demoted instance lets go of its streams
+                    if !state.leadership.is_leader() {
+                        break;
+                    }
+                    let envelope = match inbound.message().await {
+                        Ok(Some(envelope)) => envelope,
+                        Ok(None) => break,
+                        Err(status) => {
+                            debug!("gRPC stream from {:?} ended: {}", peer_id, status);
+                            break;
+                        }
+                    };
+
+                    let message_id = envelope.message_id.clone();
+                    // Nothing is taken from a stream before its HELLO authenticates it
+                    if envelope.message_type == MessageType::Hello || peer_id.is_none() {
+                        match bind_stream(&state, &envelope, peer_id.as_deref()).await {
+                            Ok(id) => peer_id = Some(id),
+                            Err(e) => {
+                                warn!("gRPC stream from {} refused: {}", envelope.source_node_id, e);
+                                let error = Envelope::error(
+                                    state.config.get().node.id.clone(),
+                                    ErrorPayload::from_error(&e, Some(message_id)),
+                                );
+                                let _ = tx.send(Ok(error)).await;
+                                break;
+                            }
+                        }
+                    }
+                    // A blocked peer's stream is closed at its next message
+                    if let Some(id) = &peer_id {
+                        if state.peers.read().await.is_blocked(id) {
+                            info!("Closing gRPC stream from blocked node {}", id);
+                            break;
+                        }
+                    }
+
+                    let reply = match process_envelope(&state, envelope, peer_id.as_deref()).await {
+                        Ok(reply) => reply,
+                        Err(e) => {
+                            warn!("gRPC envelope from {:?} rejected: {}", peer_id, e);
+                            Some(Envelope::error(
+                                state.config.get().node.id.clone(),
+                                ErrorPayload::from_error(&e, Some(message_id)),
+                            ))
+                        }
+                    };
+                    if let Some(reply) = reply {
+                        if tx.send(Ok(reply)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                info!("gRPC stream closed: {}", peer_id.unwrap_or_else(|| "unknown".into()));
+            });
+
+            Ok(tonic::Response::new(ReceiverStream::new(rx)))
+        })
+    }
+}
+
+/// Peer a stream's HELLO authenticates as; a later HELLO must be from the
+/// peer the stream is already bound to
+async fn bind_stream(state: &AppState, envelope: &Envelope, bound: Option<&str>) -> Result<String> {
+    if envelope.message_type != MessageType::Hello {
+        return Err(Error::Unauthorized("a gRPC stream must open with HELLO".into()));
+    }
+    let credential = peer_credential(None, envelope);
+    let peer_id = authenticate_peer(state, envelope, Some(&envelope.source_node_id), credential).await?;
+    match bound {
+        Some(bound) if bound != peer_id => Err(Error::Unauthorized(format!("stream is bound to peer {}", bound))),
+        _ => Ok(peer_id),
+    }
+}
+
+/// Serve the gRPC envelope stream on an address
+pub async fn serve_grpc(state: AppState, addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    serve_grpc_with_listener(state, listener).await
+}
+
+/// Serve the gRPC envelope stream on an already bound listener
+pub async fn serve_grpc_with_listener(state: AppState, listener: TcpListener) -> Result<()> {
+    info!("gRPC peer stream listening on {}", listener.local_addr()?);
+    tonic::transport::Server::builder()
+        .add_service(PeerExchangeServer::new(state))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .map_err(|e| Error::Internal(format!("gRPC server failed: {}", e)))
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+/// gRPC transport: envelopes are written to a long-lived stream
+pub struct GrpcTransport {
+    outbound: mpsc::Sender<Envelope>,
+}
+
+impl GrpcTransport {
+    /// Open a stream to a peer, starting it with the given HELLO envelope
+    ///
+    /// Envelopes the peer sends back are processed as if received on the
+    /// protocol endpoint; the link is dropped when the stream closes.
+    pub async fn connect(
+        state: AppState,
+        peer_id: &str,
+        url: &str,
+        hello: Envelope,
+        timestamp_format: TimestampFormat,
+    ) -> Result<Self> {
+        let channel = tonic::transport::Endpoint::from_shared(url.to_string())
+            .map_err(|e| Error::Peer(format!("invalid gRPC address {}: {}", url, e)))?
+            .connect()
+            .await
+            .map_err(|e| Error::Peer(format!("gRPC connect to {} failed: {}", url, e)))?;
+
+        let mut client = tonic::client::Grpc::new(channel)
+            .max_decoding_message_size(state.config.get().protocol.max_envelope_bytes);
+        client
+            .ready()
+            .await
+            .map_err(|e| Error::Peer(format!("gRPC channel not ready: {}", e)))?;
+
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        tx.send(hello)
+            .await
+            .map_err(|_| Error::Peer("gRPC stream closed".into()))?;
+
+        let response = client
+            .streaming(
+                tonic::Request::new(ReceiverStream::new(rx)),
+                PathAndQuery::from_static(GRPC_EXCHANGE_PATH),
+                EnvelopeCodec::new(timestamp_format),
+            )
+            .await
+            .map_err(|e| Error::Peer(format!("gRPC stream to {} failed: {}", url, e)))?;
+
+        let mut inbound = response.into_inner();
+        let peer_id = peer_id.to_string();
+        tokio::spawn(async move {
+            while let Ok(Some(envelope)) = inbound.message().await {
+                match envelope.message_type {
+                    // Session replies are consumed here; the handshake was
+                    // already negotiated before the stream was opened
+                    MessageType::Hello => state.peers.write().await.update_heartbeat(&peer_id),
+                    MessageType::Error => {
+                        let message = envelope.payload.parse::<ErrorPayload>()
+                            .map(|e| format!("{:?}: {}", e.error_code, e.error_message))
+                            .unwrap_or_else(|e| e.to_string());
+                        warn!("Peer {} reported error: {}", peer_id, message);
+                        state.peers.write().await.record_error(&peer_id, SessionEventKind::PeerError, message);
+                        record_peer_outcome(&state, &peer_id, false).await;
+                    }
+                    _ => {
+                        if let Err(e) = process_envelope(&state, envelope, Some(&peer_id)).await {
+                            warn!("gRPC envelope from {} rejected: {}", peer_id, e);
+                        }
+                    }
+                }
+            }
+            info!("gRPC stream to {} closed", peer_id);
+            state.peers.write().await.drop_link(&peer_id);
+        });
+
+        Ok(Self { outbound: tx })
+    }
+}
+
+#[async_trait]
+impl Transport for GrpcTransport {
+    fn kind(&self) -> PeerTransport {
+        PeerTransport::Grpc
+    }
+
+    async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+        self.outbound
+            .send(envelope.clone())
+            .await
+            .map_err(|_| Error::Peer("gRPC stream closed".into()))?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use std::time::Duration;
+    use tonic::codec::EncodeBody;
+
+    #[tokio::test]
+    async fn test_codec_round_trip() {
+        let envelopes = [
+            Envelope::new(
+                "node-1".to_string(),
+                MessageType::CdmAnnounce,
+                serde_json::to_value(generate_demo_cdm()).unwrap(),
+            ),
+            Envelope::new("node-1".to_string(), MessageType::Heartbeat, serde_json::json!({ "sequence": 7 })),
+        ];
+        // Framed by the encoder as on a stream, and read back by the decoder
+        let source = tokio_stream::iter(envelopes.clone().map(Ok));
+        let body = EncodeBody::new_client(EnvelopeCodec::new(TimestampFormat::Millis), source, None, None);
+        let mut stream = Streaming::new_request(EnvelopeCodec::default(), body, None, None);
+        for envelope in &envelopes {
+            let decoded = stream.message().await.unwrap().unwrap();
+            assert_eq!(decoded.message_id, envelope.message_id);
+            assert_eq!(decoded.message_type, envelope.message_type);
+            assert_eq!(decoded.source_node_id, "node-1");
+        }
+        assert!(stream.message().await.unwrap().is_none());
+
+        let frame = |message: &[u8]| {
+            let mut frame = vec![0];
+            frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            frame.extend_from_slice(message);
+            axum::body::Body::from(frame)
+        };
+        let announce = serde_json::to_vec(&envelopes[0]).unwrap();
+        let mut stream = Streaming::new_request(EnvelopeCodec::default(), frame(&announce), None, None);
+        let decoded = stream.message().await.unwrap().unwrap();
+        assert_eq!(decoded.payload["cdm_id"], envelopes[0].payload["cdm_id"]);
+        let mut stream = Streaming::new_request(EnvelopeCodec::default(), frame(b"{\"message_type\": 1}"), None, None);
+        assert_eq!(stream.message().await.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_stream_delivers_cdm() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_state = test_state("node-server");
+        tokio::spawn(serve_grpc_with_listener(server_state.clone(), listener));
+
+        let client_state = test_state("node-client");
+        let hello = Envelope::new(
+            "node-client".to_string(),
+            MessageType::Hello,
+            serde_json::to_value(client_state.local_hello()).unwrap(),
+        );
+        let transport = GrpcTransport::connect(
+            client_state,
+            "node-server",
+            &format!("http://{}", addr),
+            hello,
+            TimestampFormat::Millis,
+        )
+        .await
+        .unwrap();
+
+        let cdm = generate_demo_cdm();
+        let cdm_id = cdm.cdm_id.clone();
+        let announce = Envelope::new(
+            "node-client".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(cdm).unwrap(),
+        );
+        assert!(transport.send(&announce).await.unwrap().is_none());
+
+        for _ in 0..50 {
+            if server_state.storage.get_cdm(&cdm_id).await.unwrap().is_some() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("CDM was not delivered over the gRPC stream");
+    }
+
+    #[tokio::test]
+    async fn test_stream_authenticates_hello() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server_state = test_state("node-server");
+        let mut config = (*server_state.config.get()).clone();
+        config.protocol.require_peer_auth = true;
+        server_state.config.replace(config);
+        let peer = serde_yaml::from_str("{ id: node-client, address: 'http://127.0.0.1:9', auth_token: secret }").unwrap();
+        server_state.peers.write().await.add_peer(crate::node::PeerInfo::from_config(&peer));
+        tokio::spawn(serve_grpc_with_listener(server_state.clone(), listener));
+
+        let client_state = test_state("node-client");
+        let hello = |source: &str, auth_token: &str| {
+            let mut hello = client_state.local_hello();
+            hello.auth_token = Some(auth_token.to_string());
+            Envelope::new(source.to_string(), MessageType::Hello, serde_json::to_value(hello).unwrap())
+        };
+        let announce = || {
+            Envelope::new(
+                "node-client".to_string(),
+                MessageType::CdmAnnounce,
+                serde_json::to_value(generate_demo_cdm()).unwrap(),
+            )
+        };
+        // A wrong token, another peer's name on the right token, and no HELLO
+        let openers = [hello("node-client", "guess"), hello("node-other", "secret"), announce()];
+        for opener in openers {
+            let transport = GrpcTransport::connect(client_state.clone(), "node-server", &url, opener, TimestampFormat::Millis)
+                .await
+                .unwrap();
+            let _ = transport.send(&announce()).await;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(server_state.storage.list_cdms().await.unwrap().is_empty());
+
+        let transport =
+            GrpcTransport::connect(client_state.clone(), "node-server", &url, hello("node-client", "secret"), TimestampFormat::Millis)
+                .await
+                .unwrap();
+        transport.send(&announce()).await.unwrap();
+        for _ in 0..50 {
+            if !server_state.storage.list_cdms().await.unwrap().is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("CDM was not delivered over the authenticated stream");
+    }
+}
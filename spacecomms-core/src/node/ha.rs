@@ -0,0 +1,254 @@
+//! Leader election between instances sharing a node identity
+//!
+//! With `ha` configured, every instance of the node competes for one lease
+//! in the shared storage backend and renews it every
+//! `ha.renew_interval_seconds`. The holder is the leader: it holds the peer
+//! sessions, answers peers, forwards and runs the sweeps that write. The
+//! other instances follow: they drop their peer links, answer protocol
+//! messages with `503` and report not ready, so a load balancer in front of
+//! the instances routes to the leader. When the leader stops renewing, a
+//! follower takes the lease once it expires.
+
+use crate::config::Config;
+use crate::node::AppState;
+use crate::storage::Lease;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Lease the instances of a node compete for
+pub const LEADER_LEASE: &str = "node-leader";
+
+/// Part an instance plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeadershipRole {
+    Leader,
+    Follower,
+}
+
+/// This instance's view of the election
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LeadershipStatus {
+    pub instance_id: String,
+    pub role: LeadershipRole,
+    /// Instance holding the lease, if known
+    pub leader: Option<String>,
+    /// When the leader's lease runs out unless renewed
+    pub lease_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Whether this instance leads, shared by the tasks that must only run on
+/// the leader
+#[derive(Debug)]
+pub struct Leadership {
+    leader: AtomicBool,
+    /// None without `ha`
+    status: RwLock<Option<LeadershipStatus>>,
+}
+
+impl Default for Leadership {
+    /// A lone instance, always leading
+    fn default() -> Self {
+        Self {
+            leader: AtomicBool::new(true),
+            status: RwLock::new(None),
+        }
+    }
+}
+
+impl Leadership {
+    /// Start as a follower when `ha` is configured, until the lease is won
+    pub fn for_config(config: &Config) -> Self {
+        let Some(ha) = &config.ha else {
+            return Self::default();
+        };
+        Self {
+            leader: AtomicBool::new(false),
+            status: RwLock::new(Some(LeadershipStatus {
+                instance_id: ha.instance_id.clone(),
+                role: LeadershipRole::Follower,
+                leader: None,
+                lease_expires_at: None,
+            })),
+        }
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::Acquire)
+    }
+
+    /// The election as this instance sees it; None without `ha`
+    pub fn status(&self) -> Option<LeadershipStatus> {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Take in the lease after an attempt to take or renew it, returning
+    /// the new role if it changed
+    fn observe(&self, lease: &Lease) -> Option<LeadershipRole> {
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+        let status = status.as_mut()?;
+        let role = if lease.holder == status.instance_id {
+            LeadershipRole::Leader
+        } else {
+            LeadershipRole::Follower
+        };
+        status.leader = Some(lease.holder.clone());
+        status.lease_expires_at = Some(lease.expires_at);
+        self.set_role(status, role)
+    }
+
+    /// Follow once this instance's own lease has run out without a renewal
+    fn expire(&self, now: DateTime<Utc>) -> Option<LeadershipRole> {
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+        let status = status.as_mut()?;
+        if status.role != LeadershipRole::Leader || status.lease_expires_at.is_some_and(|at| at > now) {
+            return None;
+        }
+        status.leader = None;
+        self.set_role(status, LeadershipRole::Follower)
+    }
+
+    fn set_role(&self, status: &mut LeadershipStatus, role: LeadershipRole) -> Option<LeadershipRole> {
+        self.leader.store(role == LeadershipRole::Leader, Ordering::Release);
+        (status.role != role).then(|| {
+            status.role = role;
+            role
+        })
+    }
+}
+
+/// Take part in the election for as long as the node runs
+pub fn spawn_lease_keeper(state: AppState) {
+    let Some(ha) = state.config.get().ha.clone() else {
+        return;
+    };
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let node_id = state.config.get().node.id.clone();
+        let ttl = Duration::from_secs(ha.lease_seconds);
+        let mut ticker = tokio::time::interval(Duration::from_secs(ha.renew_interval_seconds));
+        loop {
+            ticker.tick().await;
+            let change = match state.storage.acquire_lease(LEADER_LEASE, &ha.instance_id, ttl).await {
+                Ok(lease) => state.leadership.observe(&lease),
+                Err(e) => {
+                    warn!("Leader lease for node {} not renewed: {}", node_id, e);
+                    state.leadership.expire(Utc::now())
+                }
+            };
+            match change {
+                Some(LeadershipRole::Leader) => info!("Instance {} now leads node {}", ha.instance_id, node_id),
+                Some(LeadershipRole::Follower) => {
+                    let leader = state.leadership.status().and_then(|s| s.leader);
+                    warn!(
+                        "Instance {} no longer leads node {} (leader: {})",
+                        ha.instance_id,
+                        node_id,
+                        leader.as_deref().unwrap_or("unknown")
+                    );
+                    state.peers.write().await.drop_all_links();
+                }
+                None => {}
+            }
+        }
+    });
+}
+
+/// Hand the lease back so a follower takes over without waiting for it to
+/// expire
+pub async fn release_leadership(state: &AppState) {
+    let Some(ha) = state.config.get().ha.clone() else {
+        return;
+    };
+    if state.leadership.is_leader() {
+        if let Err(e) = state.storage.release_lease(LEADER_LEASE, &ha.instance_id).await {
+            warn!("Leader lease not released: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{receive_message, NodeServer, PeerManager, RoutingEngine};
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use crate::storage::{MemoryStorage, Storage};
+    use std::sync::Arc;
+    use tokio::sync::RwLock as AsyncRwLock;
+
+    fn instance(storage: &Arc<dyn Storage>, instance_id: &str) -> AppState {
+        let config: Config = serde_yaml::from_str(&format!(
+            "node: {{ id: node-a }}\nserver: {{}}\nha: {{ instance_id: {}, lease_seconds: 2, renew_interval_seconds: 1 }}",
+            instance_id
+        ))
+        .unwrap();
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        NodeServer::new(config, storage.clone(), Arc::new(AsyncRwLock::new(PeerManager::new())), routing)
+            .state()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let first = instance(&storage, "ha-1");
+        let second = instance(&storage, "ha-2");
+        spawn_lease_keeper(first.clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        spawn_lease_keeper(second.clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(first.leadership.is_leader());
+        assert!(!second.leadership.is_leader());
+        assert_eq!(second.leadership.status().unwrap().leader.as_deref(), Some("ha-1"));
+        let refused = receive_message(State(second.clone()), HeaderMap::new(), Body::from("{}")).await;
+        assert_eq!(refused.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The leader dies without releasing; the follower waits out the lease
+        first.tasks.abort_all();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(4);
+        while !second.leadership.is_leader() {
+            assert!(tokio::time::Instant::now() < deadline, "follower did not take over");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // A clean shutdown hands the lease over at once
+        release_leadership(&second).await;
+        let lease = storage.acquire_lease(LEADER_LEASE, "ha-3", Duration::from_secs(2)).await.unwrap();
+        assert_eq!(lease.holder, "ha-3");
+    }
+
+    #[test]
+    fn test_leadership_roles() {
+        let lone = Leadership::default();
+        assert!(lone.is_leader());
+        assert!(lone.status().is_none());
+
+        let config: Config = serde_yaml::from_str("node: { id: n }\nserver: {}\nha: { instance_id: a }").unwrap();
+        let leadership = Leadership::for_config(&config);
+        assert!(!leadership.is_leader());
+        let now = Utc::now();
+        let lease = |holder: &str, expires_at| Lease {
+            name: LEADER_LEASE.into(),
+            holder: holder.into(),
+            acquired_at: now,
+            expires_at,
+        };
+        let later = now + chrono::Duration::seconds(30);
+        assert_eq!(leadership.observe(&lease("a", later)), Some(LeadershipRole::Leader));
+        assert_eq!(leadership.observe(&lease("a", later)), None);
+        assert!(leadership.is_leader());
+        // Storage unreachable: still leading until the lease runs out
+        assert_eq!(leadership.expire(now), None);
+        assert_eq!(leadership.expire(later), Some(LeadershipRole::Follower));
+        assert!(!leadership.is_leader());
+        assert_eq!(leadership.observe(&lease("b", later)), None);
+        assert_eq!(leadership.status().unwrap().leader.as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,297 @@
+//! Streaming import of historical CDM archives
+//!
+//! `POST /cdm/import` takes CDMs as newline-delimited JSON, gzip compressed
+//! or not, and handles each line as it arrives, so an archive of any size
+//! passes through a fixed amount of memory. Each CDM is validated, enriched
+//! and classified as on `POST /cdm`, then stored unless the node already
+//! holds the same or a newer version. Imported CDMs are history: they are
+//! not announced to peers and raise no events or alerts.
+//!
+//! The response is NDJSON too: an [`ImportLine::Record`] for each input line
+//! as it is handled, then an [`ImportLine::Summary`].
+
+use crate::node::server::prepare_cdm;
+use crate::node::AppState;
+use crate::{Error, Result};
+use axum::body::{Body, BodyDataStream};
+use flate2::write::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Longest accepted line; a longer one ends the import
+pub const MAX_IMPORT_LINE: usize = 1024 * 1024;
+
+/// First bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// What happened to one line of an import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,
+    /// The node already holds this version of the CDM or a newer one
+    Unchanged,
+    Rejected,
+}
+
+/// Outcome for one input line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportRecord {
+    /// Line number in the (decompressed) input, from 1
+    pub line: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdm_id: Option<String>,
+    pub status: ImportStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Totals for a finished import
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportSummary {
+    pub imported: u64,
+    pub unchanged: u64,
+    pub rejected: u64,
+    /// False if the input could not be read to the end; lines after
+    /// the last reported one were not imported
+    pub complete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One line of the import response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImportLine {
+    Record(ImportRecord),
+    Summary(ImportSummary),
+}
+
+/// Splits a body, decompressing it if it is gzip, into lines
+#[derive(Default)]
+struct LineReader {
+    /// Whether the input is gzip, once its first two bytes are known
+    gzip: Option<bool>,
+    head: Vec<u8>,
+    gunzip: Option<MultiGzDecoder<Vec<u8>>>,
+    /// Decoded bytes after the last complete line
+    pending: Vec<u8>,
+}
+
+impl LineReader {
+    /// Take a chunk of the body and return the complete lines it finished
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        match self.gzip {
+            None => {
+                self.head.extend_from_slice(chunk);
+                if self.head.len() < GZIP_MAGIC.len() {
+                    return Ok(Vec::new());
+                }
+                let head = std::mem::take(&mut self.head);
+                let gzip = head.starts_with(&GZIP_MAGIC);
+                self.gzip = Some(gzip);
+                if gzip {
+                    self.gunzip = Some(MultiGzDecoder::new(Vec::new()));
+                }
+                return self.push(&head);
+            }
+            Some(true) => {
+                let gunzip = self.gunzip.as_mut().expect("gzip input has a decoder");
+                gunzip.write_all(chunk)?;
+                self.pending.append(gunzip.get_mut());
+            }
+            Some(false) => self.pending.extend_from_slice(chunk),
+        }
+        let Some(end) = self.pending.iter().rposition(|b| *b == b'\n') else {
+            if self.pending.len() > MAX_IMPORT_LINE {
+                return Err(Error::LimitExceeded(format!("line longer than {} bytes", MAX_IMPORT_LINE)));
+            }
+            return Ok(Vec::new());
+        };
+        let rest = self.pending.split_off(end + 1);
+        Ok(std::mem::replace(&mut self.pending, rest))
+    }
+
+    /// The last line, if unterminated, once the body has ended
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        if self.gzip.is_none() {
+            let head = std::mem::take(&mut self.head);
+            self.gzip = Some(false);
+            self.push(&head)?;
+        }
+        if let Some(gunzip) = self.gunzip.as_mut() {
+            gunzip.try_finish()?;
+            self.pending.append(gunzip.get_mut());
+        }
+        Ok(std::mem::take(&mut self.pending))
+    }
+}
+
+/// Non-blank lines of a request body, decompressed if it is gzip, read as
+/// they arrive
+pub(crate) struct BodyLines {
+    chunks: BodyDataStream,
+    reader: LineReader,
+    /// Complete lines read but not yet returned, from `position`
+    block: Vec<u8>,
+    position: usize,
+    /// Number of the last line returned
+    line: u64,
+    done: bool,
+}
+
+impl BodyLines {
+    pub(crate) fn new(body: Body) -> Self {
+        Self {
+            chunks: body.into_data_stream(),
+            reader: LineReader::default(),
+            block: Vec::new(),
+            position: 0,
+            line: 0,
+            done: false,
+        }
+    }
+
+    /// The next non-blank line and its number, from 1, or `None` at the end
+    pub(crate) async fn next(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            if let Some(length) = self.block[self.position..].iter().position(|b| *b == b'\n') {
+                let text = &self.block[self.position..self.position + length];
+                self.position += length + 1;
+                self.line += 1;
+                if !text.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(Some((self.line, text.to_vec())));
+                }
+                continue;
+            }
+            if self.done {
+                return Ok(None);
+            }
+            self.position = 0;
+            self.block = match self.chunks.next().await {
+                Some(Ok(chunk)) => self.reader.push(&chunk)?,
+                Some(Err(e)) => return Err(Error::Protocol(format!("reading request body: {}", e))),
+                None => {
+                    self.done = true;
+                    let mut last = self.reader.finish()?;
+                    if !last.is_empty() {
+                        last.push(b'\n');
+                    }
+                    last
+                }
+            };
+        }
+    }
+
+    /// Number of the last line returned
+    pub(crate) fn line(&self) -> u64 {
+        self.line
+    }
+}
+
+/// Import the CDMs in `body`, sending a result per line and then the
+/// summary to `results`. Stops early if the receiver goes away.
+pub async fn import_cdms(state: AppState, body: Body, organization: Option<String>, results: mpsc::Sender<ImportLine>) {
+    let mut lines = BodyLines::new(body);
+    let mut summary = ImportSummary::default();
+    loop {
+        let (line, text) = match lines.next().await {
+            Ok(Some(next)) => next,
+            Ok(None) => {
+                summary.complete = true;
+                break;
+            }
+            Err(e) => {
+                summary.error = Some(e.to_string());
+                break;
+            }
+        };
+        let record = import_line(&state, line, &text, organization.as_deref()).await;
+        match record.status {
+            ImportStatus::Imported => summary.imported += 1,
+            ImportStatus::Unchanged => summary.unchanged += 1,
+            ImportStatus::Rejected => summary.rejected += 1,
+        }
+        if results.send(ImportLine::Record(record)).await.is_err() {
+            warn!("CDM import abandoned by the client after line {}", line);
+            return;
+        }
+    }
+    info!(
+        "CDM import: {} imported, {} unchanged, {} rejected",
+        summary.imported, summary.unchanged, summary.rejected
+    );
+    if let Some(error) = &summary.error {
+        warn!("CDM import stopped after line {}: {}", lines.line(), error);
+    }
+    let _ = results.send(ImportLine::Summary(summary)).await;
+}
+
+async fn import_line(state: &AppState, line: u64, text: &[u8], organization: Option<&str>) -> ImportRecord {
+    let mut record = ImportRecord {
+        line,
+        cdm_id: None,
+        status: ImportStatus::Rejected,
+        error: None,
+    };
+    let body: serde_json::Value = match serde_json::from_slice(text) {
+        Ok(body) => body,
+        Err(e) => {
+            record.error = Some(e.to_string());
+            return record;
+        }
+    };
+    record.cdm_id = body.get("cdm_id").and_then(|v| v.as_str()).map(str::to_string);
+    let stored = match prepare_cdm(state, body, organization, &None).await {
+        Ok((cdm, _)) => state.storage.upsert_cdm_if_newer(cdm).await,
+        Err(e) => Err(e),
+    };
+    match stored {
+        Ok(outcome) if outcome.written() => record.status = ImportStatus::Imported,
+        Ok(_) => record.status = ImportStatus::Unchanged,
+        Err(e) => record.error = Some(e.to_string()),
+    }
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn read_all(reader: &mut LineReader, input: &[u8], chunk: usize) -> Vec<String> {
+        let mut text = Vec::new();
+        for piece in input.chunks(chunk) {
+            text.extend(reader.push(piece).unwrap());
+        }
+        text.extend(reader.finish().unwrap());
+        String::from_utf8(text).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_line_reader() {
+        let input = b"{\"a\":1}\n{\"b\":2}\n\n{\"c\":3}";
+        for chunk in [1, 3, 64] {
+            assert_eq!(read_all(&mut LineReader::default(), input, chunk), ["{\"a\":1}", "{\"b\":2}", "", "{\"c\":3}"]);
+        }
+
+        // Concatenated gzip members read as one stream
+        let mut gzipped = Vec::new();
+        for part in [&input[..8], &input[8..]] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(part).unwrap();
+            gzipped.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(read_all(&mut LineReader::default(), &gzipped, 5).len(), 4);
+
+        let mut reader = LineReader::default();
+        assert!(reader.push(&vec![b'x'; MAX_IMPORT_LINE + 1]).is_err());
+        let mut reader = LineReader::default();
+        assert!(reader.push(b"\x1f\x8b\x08\0garbage").and_then(|_| reader.finish()).is_err());
+    }
+}
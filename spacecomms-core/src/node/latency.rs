@@ -0,0 +1,114 @@
+//! Latency between nodes
+//!
+//! Two delays are measured. The round trip to a peer comes from heartbeats:
+//! each heartbeat echoes the last one received from the peer with how long
+//! it was held, so the peer takes the time since it sent the echoed
+//! heartbeat, less the hold, on its own clock alone. The propagation delay
+//! of a CDM is the time from its originator's envelope timestamp to its
+//! receipt here, corrected by the clock offset of a peer that originated it
+//! itself; relayed CDMs are taken as stamped, so their delay carries the
+//! originator's clock error.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use utoipa::ToSchema;
+
+/// Samples each summary is computed over
+pub const LATENCY_SAMPLES: usize = 100;
+
+/// Recent delays, in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LatencySummary {
+    pub last_ms: u64,
+    pub mean_ms: f64,
+    /// 95th percentile
+    pub p95_ms: u64,
+    pub max_ms: u64,
+    /// Samples summarized, up to 100
+    pub samples: usize,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The last [`LATENCY_SAMPLES`] delays and their summary
+#[derive(Debug, Clone, Default)]
+pub struct LatencyWindow {
+    samples: VecDeque<u64>,
+    summary: Option<LatencySummary>,
+}
+
+impl LatencyWindow {
+    pub fn add(&mut self, delay_ms: u64) {
+        self.samples.push_back(delay_ms);
+        if self.samples.len() > LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let p95 = (sorted.len() * 95).div_ceil(100).max(1) - 1;
+        self.summary = Some(LatencySummary {
+            last_ms: delay_ms,
+            mean_ms: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+            p95_ms: sorted[p95],
+            max_ms: sorted[sorted.len() - 1],
+            samples: sorted.len(),
+            updated_at: Utc::now(),
+        });
+    }
+
+    /// None until the first sample
+    pub fn summary(&self) -> Option<&LatencySummary> {
+        self.summary.as_ref()
+    }
+}
+
+/// Delays measured with one peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PeerLatency {
+    /// Heartbeat round trip, once the peer has echoed one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round_trip: Option<LatencySummary>,
+    /// Origin to receipt here, of CDMs that arrived from the peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdm_propagation: Option<LatencySummary>,
+    #[serde(skip)]
+    round_trips: LatencyWindow,
+    #[serde(skip)]
+    propagation: LatencyWindow,
+}
+
+impl PeerLatency {
+    pub(crate) fn add_round_trip(&mut self, delay_ms: u64) {
+        self.round_trips.add(delay_ms);
+        self.round_trip = self.round_trips.summary().cloned();
+    }
+
+    pub(crate) fn add_propagation(&mut self, delay_ms: u64) {
+        self.propagation.add(delay_ms);
+        self.cdm_propagation = self.propagation.summary().cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_window() {
+        let mut window = LatencyWindow::default();
+        assert!(window.summary().is_none());
+        for delay in (1..=120).rev() {
+            window.add(delay);
+        }
+        // Only the last 100 (100 down to 1) are kept
+        let summary = window.summary().unwrap();
+        assert_eq!((summary.last_ms, summary.max_ms, summary.samples), (1, 100, 100));
+        assert_eq!(summary.p95_ms, 95);
+        assert!((summary.mean_ms - 50.5).abs() < 1e-9);
+
+        let mut latency = PeerLatency::default();
+        latency.add_round_trip(40);
+        assert_eq!(latency.round_trip.as_ref().unwrap().p95_ms, 40);
+        assert!(latency.cdm_propagation.is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Request body, time and connection limits of the HTTP server
+//!
+//! Bodies are never read ahead of the handler. A request declaring a
+//! `Content-Length` over `server.max_body_bytes` is refused with `413`
+//! before its body is read; the extractors reading a body into memory
+//! (JSON, text, bytes) stop at the same limit, set as axum's
+//! `DefaultBodyLimit`, and their `413` is answered like the first.
+//! Handlers must produce a response within `server.request_timeout_seconds`
+//! or the client gets `408`. The streaming imports read their body in
+//! constant memory for as long as the upload takes, and the long-poll event
+//! feed bounds its own wait, so neither is limited. The middleware runs
+//! after authentication, so nothing is done for unauthenticated requests.
+//! Connections beyond `server.max_connections` are answered with `503` and
+//! closed.
+
+use crate::config::ServerConfig;
+use axum::{
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    serve::IncomingStream,
+    Json, Router,
+};
+use serde_json::json;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::{Service, ServiceExt};
+use tracing::warn;
+
+/// Paths that hold requests open by design
+const LONG_POLL_PREFIX: &str = "/events/";
+
+/// Routes streaming their body, of any size and for any time
+const STREAMING_PATHS: [&str; 2] = ["/cdm/import", "/import"];
+
+fn limit_error(status: StatusCode, error: &str, message: String) -> Response {
+    (status, Json(json!({ "error": error, "message": message }))).into_response()
+}
+
+/// Body size and time allowed for one request
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequestLimits {
+    /// 0 when unlimited
+    max_body_bytes: usize,
+    timeout: Option<Duration>,
+}
+
+impl RequestLimits {
+    pub(crate) fn new(server: &ServerConfig) -> Self {
+        Self {
+            max_body_bytes: server.max_body_bytes,
+            timeout: (server.request_timeout_seconds > 0).then(|| Duration::from_secs(server.request_timeout_seconds)),
+        }
+    }
+
+    /// Limit for the extractors that read a body into memory
+    pub(crate) fn body_limit(&self) -> DefaultBodyLimit {
+        match self.max_body_bytes {
+            0 => DefaultBodyLimit::disable(),
+            max => DefaultBodyLimit::max(max),
+        }
+    }
+
+    fn too_large(&self) -> Response {
+        limit_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            format!("request body exceeds the limit of {} bytes", self.max_body_bytes),
+        )
+    }
+}
+
+/// Whether a response is an extractor's plain-text rejection
+fn is_plain_text(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"))
+}
+
+/// Middleware applying [`RequestLimits`]
+pub(crate) async fn limit_request(State(limits): State<RequestLimits>, request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if STREAMING_PATHS.contains(&path) {
+        return next.run(request).await;
+    }
+    let long_poll = path.starts_with(LONG_POLL_PREFIX);
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if limits.max_body_bytes > 0 && declared.is_some_and(|len| len > limits.max_body_bytes as u64) {
+        return limits.too_large();
+    }
+
+    let limited = async move {
+        let response = next.run(request).await;
+        if response.status() == StatusCode::PAYLOAD_TOO_LARGE && is_plain_text(&response) {
+            return limits.too_large();
+        }
+        response
+    };
+    match limits.timeout.filter(|_| !long_poll) {
+        Some(timeout) => tokio::time::timeout(timeout, limited).await.unwrap_or_else(|_| {
+            limit_error(
+                StatusCode::REQUEST_TIMEOUT,
+                "request_timeout",
+                format!("request not completed within {} seconds", timeout.as_secs()),
+            )
+        }),
+        None => limited.await,
+    }
+}
+
+/// Make-service for [`axum::serve`] admitting at most a fixed number of
+/// open connections
+#[derive(Clone)]
+pub(crate) struct ConnectionLimit {
+    router: Router,
+    /// None when unlimited
+    permits: Option<Arc<Semaphore>>,
+    max_connections: usize,
+}
+
+impl ConnectionLimit {
+    pub(crate) fn new(router: Router, max_connections: usize) -> Self {
+        Self {
+            router,
+            permits: (max_connections > 0).then(|| Arc::new(Semaphore::new(max_connections))),
+            max_connections,
+        }
+    }
+}
+
+impl Service<IncomingStream<'_>> for ConnectionLimit {
+    type Response = Connection;
+    type Error = Infallible;
+    type Future = std::future::Ready<Result<Connection, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, stream: IncomingStream<'_>) -> Self::Future {
+        let permit = match &self.permits {
+            None => Ok(None),
+            Some(permits) => permits.clone().try_acquire_owned().map(|permit| Some(Arc::new(permit))),
+        };
+        if permit.is_err() {
+            warn!(
+                "Refusing connection from {}: {} connections open",
+                stream.remote_addr(),
+                self.max_connections
+            );
+        }
+        std::future::ready(Ok(Connection {
+            router: self.router.clone(),
+            permit: permit.map_err(|_| self.max_connections),
+        }))
+    }
+}
+
+/// Service for one connection, holding its permit until the connection
+/// closes
+#[derive(Clone)]
+pub(crate) struct Connection {
+    router: Router,
+    /// The connection limit when the connection was refused
+    permit: Result<Option<Arc<OwnedSemaphorePermit>>, usize>,
+}
+
+impl Service<Request> for Connection {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match self.permit {
+            Ok(_) => Box::pin(self.router.clone().oneshot(request)),
+            Err(max_connections) => {
+                let mut response = limit_error(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "too_many_connections",
+                    format!("server is at its limit of {} connections", max_connections),
+                );
+                response.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("close"));
+                Box::pin(std::future::ready(Ok(response)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::post};
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let server: ServerConfig = serde_yaml::from_str("{ max_body_bytes: 1024, request_timeout_seconds: 1, max_connections: 1 }").unwrap();
+        let limits = RequestLimits::new(&server);
+        let app = Router::new()
+            .route("/echo", post(|Json(body): Json<serde_json::Value>| async move { body.to_string().len().to_string() }))
+            .route(
+                "/slow",
+                post(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .layer(middleware::from_fn_with_state(limits, limit_request))
+            .layer(limits.body_limit());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let limited = ConnectionLimit::new(app.clone(), server.max_connections);
+        tokio::spawn(async move { axum::serve(listener, limited).await });
+
+        let client = reqwest::Client::new();
+        let text = |len: usize| serde_json::to_vec(&"x".repeat(len - 2)).unwrap();
+        let post = |path: &str, body: Vec<u8>| {
+            client.post(format!("{}{}", base, path)).header("content-type", "application/json").body(body).send()
+        };
+        let resp = post("/echo", text(1024)).await.unwrap();
+        assert_eq!(resp.text().await.unwrap(), "1024");
+
+        let resp = post("/echo", text(1025)).await.unwrap();
+        assert_eq!(resp.status(), 413);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"], "payload_too_large");
+
+        // Chunked bodies carry no length up front; the extractor stops them
+        let chunks = tokio_stream::iter([Ok::<_, std::io::Error>(text(1025))]);
+        let chunked = Request::post("/echo")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from_stream(chunks))
+            .unwrap();
+        let resp = app.clone().oneshot(chunked).await.unwrap();
+        assert_eq!(resp.status(), 413);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error["error"], "payload_too_large");
+
+        let resp = post("/slow", Vec::new()).await.unwrap();
+        assert_eq!(resp.status(), 408);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"], "request_timeout");
+
+        // The first client keeps its pooled connection open
+        let other = reqwest::Client::new();
+        let resp = other.post(format!("{}/echo", base)).body("x").send().await.unwrap();
+        assert_eq!(resp.status(), 503);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"], "too_many_connections");
+    }
+}
@@ -1,19 +1,110 @@
 //! Node module - server and session management
 
+mod alert_book;
+mod alerts;
+mod auth;
+mod compression;
+mod dashboard;
+mod deadletter;
+mod delivery;
+mod discovery;
+mod events;
+mod fanout;
+mod notifier;
+mod grpc;
+mod ha;
+mod import;
+mod latency;
+mod limits;
+mod originators;
 mod peer;
+mod peering;
+mod playback;
+mod preflight;
+mod quarantine;
+mod query;
+mod receipts;
+mod redaction;
+mod read_only;
+mod reload;
+mod replay;
+mod retention;
+mod route_simulation;
 mod routing;
+mod screening;
+mod security;
 mod server;
+mod session;
+mod shards;
+mod simulate;
+mod sla;
+mod snapshot;
+mod stats;
+mod sync;
+mod topology;
+mod trace;
+mod traffic;
+mod transport;
+mod watchlist;
 
+pub use alert_book::*;
+pub use alerts::*;
+pub use auth::*;
+pub use deadletter::*;
+pub use delivery::*;
+pub use discovery::*;
+pub use events::*;
+pub use fanout::*;
+pub use notifier::*;
+pub use grpc::*;
+pub use ha::*;
+pub use import::*;
+pub use latency::*;
+pub use originators::*;
 pub use peer::*;
+pub use peering::*;
+pub use playback::*;
+pub use preflight::*;
+pub use quarantine::*;
+pub use query::*;
+pub(crate) use receipts::*;
+pub use redaction::*;
+pub use reload::*;
+pub use replay::*;
+pub use retention::*;
+pub use route_simulation::*;
 pub use routing::*;
+pub use screening::*;
 pub use server::*;
+pub use session::*;
+pub use shards::*;
+pub use simulate::*;
+pub use sla::*;
+pub use snapshot::*;
+pub use stats::*;
+pub use sync::*;
+pub use topology::*;
+pub use trace::*;
+pub use traffic::*;
+pub use transport::*;
+pub use watchlist::*;
 
-use crate::config::Config;
-use crate::storage::{create_storage, Storage};
+use crate::cdm::CdmRecord;
+use crate::config::{Config, ConfigOverride, NodeMode};
+use crate::node::server::{announce_cdm, prepare_cdm};
+use crate::node::limits::ConnectionLimit;
+use crate::storage::{create_archive, create_storage, CapacityEvent, CapacityHook, Storage};
 use crate::Result;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::info;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info, warn};
 
 /// SpaceComms node
 pub struct Node {
@@ -21,12 +112,24 @@ pub struct Node {
     storage: Arc<dyn Storage>,
     peers: Arc<RwLock<PeerManager>>,
     routing: Arc<RoutingEngine>,
+    config_path: Option<PathBuf>,
+    config_overrides: Vec<ConfigOverride>,
+    log_level_hook: Option<LogLevelHook>,
+    /// Catalog capacity alerts queued by the storage hook until the node runs
+    capacity_events: mpsc::UnboundedReceiver<CapacityEvent>,
 }
 
 impl Node {
     /// Create a new node from configuration
     pub async fn new(config: Config) -> Result<Self> {
-        let storage = create_storage(&config.storage.storage_type);
+        // The hook runs with the catalog locked, so it only queues alerts
+        let (capacity_alerts, capacity_events) = mpsc::unbounded_channel();
+        let hook: CapacityHook = Arc::new(move |event: &CapacityEvent| {
+            if event.is_alert() {
+                let _ = capacity_alerts.send(event.clone());
+            }
+        });
+        let storage = create_storage(&config, Some(hook));
         let peers = Arc::new(RwLock::new(PeerManager::new()));
         let routing = Arc::new(RoutingEngine::new(config.clone()));
         
@@ -35,37 +138,381 @@ impl Node {
             storage,
             peers,
             routing,
+            config_path: None,
+            config_overrides: Vec::new(),
+            log_level_hook: None,
+            capacity_events,
         })
     }
 
-    /// Run the node
+    /// Re-read this file on `SIGHUP` or `POST /admin/reload`
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Layer these overrides over the file on reload, as at startup
+    pub fn with_config_overrides(mut self, overrides: Vec<ConfigOverride>) -> Self {
+        self.config_overrides = overrides;
+        self
+    }
+
+    /// Apply reloaded log levels through this hook
+    pub fn with_log_level_hook(mut self, hook: LogLevelHook) -> Self {
+        self.log_level_hook = Some(hook);
+        self
+    }
+
+    /// Run the node until its HTTP server stops
     pub async fn run(self) -> Result<()> {
+        self.start().await?.wait().await
+    }
+
+    /// Start the node in the background and return a handle to it
+    ///
+    /// The listeners are bound before anything else starts, so an address
+    /// in use fails here rather than leaving half a node running.
+    pub async fn start(self) -> Result<NodeHandle> {
         info!("Node {} starting...", self.config.node.id);
         
-        // Initialize configured peers
+        // Initialize configured peers, with the policies changed through
+        // the API in place of their configured ones, and the blocklist
         {
+            let kept = self.storage.list_peer_policies().await?;
+            let blocked = self.storage.list_blocked_nodes().await?;
             let mut peers = self.peers.write().await;
             for peer_config in &self.config.peers {
-                peers.add_peer(PeerInfo {
-                    id: peer_config.id.clone(),
-                    address: peer_config.address.clone(),
-                    status: PeerStatus::Disconnected,
-                    last_heartbeat: None,
-                    messages_sent: 0,
-                    messages_received: 0,
-                    policies: peer_config.policies.clone(),
-                });
+                peers.add_peer(PeerInfo::from_config(peer_config));
+            }
+            for (peer_id, policies) in kept {
+                if let Some(peer) = peers.get_peer_mut(&peer_id) {
+                    peer.policies = policies;
+                }
+            }
+            for node in blocked {
+                peers.block(node);
             }
         }
         
-        // Start HTTP server
+        let archive = create_archive(&self.config)?;
         let server = NodeServer::new(
             self.config.clone(),
             self.storage.clone(),
             self.peers.clone(),
             self.routing.clone(),
+        )
+        .with_archive(archive.clone())
+        .with_reloader(
+            Reloader::new(self.config_path.clone(), self.log_level_hook.clone())
+                .with_overrides(self.config_overrides.clone()),
         );
-        
-        server.run().await
+        let state = server.state().clone();
+
+        let listener = TcpListener::bind((self.config.server.host.as_str(), self.config.server.port)).await?;
+        let local_addr = listener.local_addr()?;
+        let grpc_listener = match self.config.server.grpc_port {
+            Some(grpc_port) => Some(TcpListener::bind((self.config.server.host.as_str(), grpc_port)).await?),
+            None => None,
+        };
+        let grpc_addr = grpc_listener.as_ref().map(TcpListener::local_addr).transpose()?;
+
+        #[cfg(unix)]
+        if self.config_path.is_some() {
+            spawn_reload_on_hangup(state.clone())?;
+        }
+
+        // Start gRPC peer stream listener
+        if let Some(grpc_listener) = grpc_listener {
+            state.tasks.spawn({
+                let state = state.clone();
+                async move {
+                    if let Err(e) = serve_grpc_with_listener(state, grpc_listener).await {
+                        error!("gRPC listener stopped: {}", e);
+                    }
+                }
+            });
+        }
+
+        // Establish sessions with configured peers
+        for peer_config in &self.config.peers {
+            spawn_session(state.clone(), peer_config.id.clone());
+        }
+
+        // Compete for the lead with other instances of this node
+        spawn_lease_keeper(state.clone());
+
+        // Find further peers from DNS and seed addresses
+        if self.config.discovery.is_some() {
+            spawn_discovery(state.clone());
+        }
+
+        // Move cold records into the archive; a read-only node leaves that
+        // to the node writing the shared archive
+        let read_only = self.config.node.mode == NodeMode::ReadOnly;
+        if let (Some(archive), Some(config)) = (archive.filter(|_| !read_only), &self.config.archive) {
+            spawn_archiver(state.clone(), archive, config.clone());
+        }
+
+        // Escalate conjunctions as their TCA approaches
+        spawn_tca_scheduler(state.clone());
+
+        // Pass the object catalog's capacity alerts to the alert webhooks
+        spawn_capacity_alerts(state.clone(), self.capacity_events);
+
+        // Raise alerts for conjunctions involving watched assets
+        spawn_alert_tracker(state.clone());
+
+        // Keep a history of the node's statistics for dashboards
+        spawn_stats_recorder(state.clone());
+
+        // Track each peer's service levels for peering agreement reports
+        spawn_sla_monitor(state.clone());
+
+        // Screen the object catalog for conjunctions and announce the CDMs
+        // the screening policy selects
+        if self.config.screening.is_some() && !read_only {
+            spawn_screening(state.clone());
+        }
+
+        // Generate synthetic traffic in developer mode
+        if let Some(dev) = self.config.dev.as_ref().filter(|_| !read_only) {
+            spawn_traffic_generator(
+                state.clone(),
+                Duration::from_secs(dev.traffic_interval_seconds),
+            );
+        }
+
+        // Start HTTP server
+        info!("Listening on {}", local_addr);
+        info!("Dashboard available at http://{}/ui/", local_addr);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let app = ConnectionLimit::new(server.router(), self.config.server.max_connections);
+        let http = tokio::spawn(async move {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+
+        Ok(NodeHandle {
+            state,
+            local_addr,
+            grpc_addr,
+            stop,
+            http,
+        })
+    }
+}
+
+/// A node started with [`Node::start`]
+///
+/// Dropping the handle leaves the node running; [`NodeHandle::shutdown`]
+/// stops it.
+pub struct NodeHandle {
+    state: AppState,
+    local_addr: SocketAddr,
+    grpc_addr: Option<SocketAddr>,
+    stop: oneshot::Sender<()>,
+    http: JoinHandle<std::io::Result<()>>,
+}
+
+impl NodeHandle {
+    /// Address the HTTP API and protocol endpoint listen on, with the port
+    /// the system picked when `server.port` is 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address of the gRPC peer stream listener, if one is configured
+    pub fn grpc_addr(&self) -> Option<SocketAddr> {
+        self.grpc_addr
+    }
+
+    /// Ingest a CDM as `POST /cdm` does: validate, enrich and classify it,
+    /// store it and announce it to connected peers. Returns the peers it
+    /// was forwarded to.
+    pub async fn inject_cdm(&self, cdm: CdmRecord) -> Result<Vec<String>> {
+        let (cdm, _) = prepare_cdm(&self.state, serde_json::to_value(cdm)?, None, &None).await?;
+        announce_cdm(&self.state, cdm, &None).await
+    }
+
+    /// The node's peers and their sessions
+    pub fn peer_manager(&self) -> Arc<RwLock<PeerManager>> {
+        self.state.peers.clone()
+    }
+
+    /// Follow the CDM events the node logs from now on, as served by
+    /// `GET /events/cdms`
+    pub fn subscribe(&self) -> CdmEventSubscription {
+        self.state.events.subscribe()
+    }
+
+    /// Wait until the HTTP server stops, which it only does on an error
+    pub async fn wait(self) -> Result<()> {
+        let result = join_server(self.http).await;
+        self.state.tasks.abort_all();
+        result
+    }
+
+    /// Stop the node: background tasks end at once, and the HTTP server
+    /// stops accepting connections and returns once requests in flight
+    /// are answered
+    pub async fn shutdown(self) -> Result<()> {
+        info!("Node {} shutting down", self.state.config.get().node.id);
+        self.state.tasks.abort_all();
+        release_leadership(&self.state).await;
+        let _ = self.stop.send(());
+        join_server(self.http).await
+    }
+}
+
+async fn join_server(http: JoinHandle<std::io::Result<()>>) -> Result<()> {
+    match http.await {
+        Ok(result) => Ok(result?),
+        Err(e) => Err(crate::Error::Internal(format!("HTTP server task failed: {}", e))),
+    }
+}
+
+/// Long-running tasks of a node, aborted together when it shuts down
+#[derive(Default)]
+pub struct BackgroundTasks {
+    handles: Mutex<Vec<AbortHandle>>,
+    stopped: AtomicBool,
+}
+
+impl BackgroundTasks {
+    /// Run a task until it finishes or the node shuts down; after shutdown
+    /// nothing new is started
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let Ok(mut handles) = self.handles.lock() else {
+            return;
+        };
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(tokio::spawn(task).abort_handle());
+    }
+
+    /// Abort every task and refuse new ones
+    pub fn abort_all(&self) {
+        let Ok(mut handles) = self.handles.lock() else {
+            return;
+        };
+        self.stopped.store(true, Ordering::Relaxed);
+        for handle in handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+/// Reload the configuration file whenever the process receives `SIGHUP`
+#[cfg(unix)]
+fn spawn_reload_on_hangup(state: AppState) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        while hangups.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            if let Err(e) = reload_from_file(&state).await {
+                warn!("Configuration reload failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::{generate_demo_cdm, ObjectRecord};
+    use crate::protocol::{ObjectType, StateVector};
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_embedded_node() {
+        let config: Config = serde_yaml::from_str("node: { id: node-a }\nserver: { host: 127.0.0.1, port: 0 }").unwrap();
+        let node = Node::new(config).await.unwrap().start().await.unwrap();
+        let address = node.local_addr();
+        assert_ne!(address.port(), 0);
+        let health = reqwest::get(format!("http://{}/health/live", address)).await.unwrap();
+        assert!(health.status().is_success());
+
+        let mut events = node.subscribe();
+        let cdm = generate_demo_cdm();
+        assert!(node.inject_cdm(cdm.clone()).await.unwrap().is_empty());
+        let event = events.next().await;
+        assert_eq!((event.kind, event.cdm_id.as_str()), (CdmEventKind::Announced, cdm.cdm_id.as_str()));
+        let mut invalid = cdm;
+        invalid.collision_probability = 2.0;
+        assert!(node.inject_cdm(invalid).await.is_err());
+        assert!(node.peer_manager().read().await.list_peers().is_empty());
+
+        node.shutdown().await.unwrap();
+        assert!(reqwest::get(format!("http://{}/health/live", address)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_capacity_alerts_reach_webhooks() {
+        let (tx, mut webhook) = mpsc::unbounded_channel();
+        let receiver = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |axum::Json(alert): axum::Json<CapacityAlert>| async move {
+                tx.send(alert).unwrap();
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await });
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "node: {{ id: node-a }}\nserver: {{ host: 127.0.0.1, port: 0 }}\n\
+             storage: {{ object_limits: {{ max_objects: 4, alert_percent: 50 }} }}\n\
+             alerts: {{ webhooks: ['{}'] }}",
+            hook
+        ))
+        .unwrap();
+        let node = Node::new(config).await.unwrap().start().await.unwrap();
+
+        // Filling the catalog to half raises the alert, emptying it clears it
+        for id in ["OBJ-1", "OBJ-2"] {
+            let object = ObjectRecord {
+                object_id: id.to_string(),
+                object_name: id.to_string(),
+                object_type: ObjectType::Debris,
+                owner_operator: None,
+                rcs_size: None,
+                epoch: Utc::now(),
+                state_vector: StateVector {
+                    reference_frame: "TEME".to_string(),
+                    epoch: None,
+                    x_km: 7000.0,
+                    y_km: 0.0,
+                    z_km: 0.0,
+                    vx_km_s: 0.0,
+                    vy_km_s: 7.5,
+                    vz_km_s: 0.0,
+                },
+                covariance: None,
+                source_node: "peer-b".to_string(),
+                last_updated: Utc::now(),
+                organization: None,
+            };
+            node.state.storage.store_object(object).await.unwrap();
+        }
+        let raised = tokio::time::timeout(Duration::from_secs(5), webhook.recv()).await.unwrap().unwrap();
+        assert_eq!(raised.node_id, "node-a");
+        assert_eq!(raised.event, CapacityEvent::HighWater { tracked: 2, max_objects: 4 });
+        node.state.storage.withdraw_object("OBJ-2").await.unwrap();
+        let cleared = tokio::time::timeout(Duration::from_secs(5), webhook.recv()).await.unwrap().unwrap();
+        assert_eq!(cleared.event, CapacityEvent::Recovered { tracked: 1, max_objects: 4 });
+
+        node.shutdown().await.unwrap();
     }
 }
@@ -0,0 +1,498 @@
+//! Alert notifications by email, Slack and webhook
+//!
+//! When an alert opens or reopens, and when an alerted conjunction crosses a
+//! TCA countdown threshold, the notifier sends a notice to every channel in
+//! `notifications.channels` whose `min_severity` the alert meets. Each
+//! channel is rate limited to `max_per_hour` notices over any hour. Every
+//! attempt, whether delivered, failed or rate limited, is recorded on the
+//! alert. Failed deliveries are not retried.
+//!
+//! Channels implement [`NotificationChannel`]; the configured ones are built
+//! per notice, so a configuration reload takes effect for the next alert.
+
+use crate::config::{ChannelConfig, ChannelKind, SmtpChannelConfig};
+use crate::node::{rank, Alert, AppState, Escalation};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+/// Time allowed for one delivery
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Window the per-channel rate limit counts over
+const RATE_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Why a notice was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeTrigger {
+    Opened,
+    Reopened,
+    /// The conjunction crossed a TCA countdown threshold
+    Escalated,
+}
+
+/// What a channel is sent
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AlertNotice {
+    pub trigger: NoticeTrigger,
+    pub alert: Alert,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<Escalation>,
+}
+
+impl AlertNotice {
+    /// One-line summary, used as the email subject
+    pub fn subject(&self) -> String {
+        let alert = &self.alert;
+        let prefix = match (&self.trigger, &self.escalation) {
+            (NoticeTrigger::Escalated, Some(escalation)) => format!("{}h to TCA: ", escalation.threshold_hours),
+            (NoticeTrigger::Reopened, _) => "Reopened: ".to_string(),
+            _ => String::new(),
+        };
+        format!(
+            "[SpaceComms] {}{:?} conjunction: {} with {}",
+            prefix, alert.severity, alert.asset_name, alert.other_object_name
+        )
+    }
+
+    /// Plain-text description of the alert
+    pub fn text(&self) -> String {
+        let alert = &self.alert;
+        let mut lines = vec![
+            format!("Asset: {} ({})", alert.asset_name, alert.asset_id),
+            format!("Other object: {} ({})", alert.other_object_name, alert.other_object_id),
+            format!("TCA: {}", alert.tca.to_rfc3339()),
+            format!("Collision probability: {:.2e}", alert.collision_probability),
+            format!("Miss distance: {:.0} m", alert.miss_distance_m),
+        ];
+        if let Some(action) = &alert.recommended_action {
+            lines.push(format!("Recommended action: {:?}", action).to_uppercase());
+        }
+        if let Some(escalation) = &self.escalation {
+            lines.push(format!(
+                "Escalated from {:?} to {:?}{}",
+                escalation.previous_action,
+                escalation.recommended_action,
+                if escalation.risk_increasing { " (risk increasing)" } else { "" }
+            ));
+        }
+        lines.push(format!("Alert: {} ({:?})", alert.alert_id, alert.state));
+        lines.push(format!("CDM: {}", alert.cdm_id));
+        lines.join("\n")
+    }
+}
+
+/// Outcome of sending a notice to one channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Delivered,
+    Failed,
+    /// Not sent: the channel reached its `max_per_hour`
+    RateLimited,
+}
+
+/// A notice sent, or not, to one channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NotificationDelivery {
+    pub channel: String,
+    pub trigger: NoticeTrigger,
+    pub status: DeliveryStatus,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A way of delivering notices
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    /// Deliver one notice
+    async fn deliver(&self, notice: &AlertNotice) -> Result<()>;
+}
+
+/// Slack incoming webhook
+pub struct SlackChannel {
+    http: reqwest::Client,
+    webhook_url: String,
+}
+
+#[async_trait]
+impl NotificationChannel for SlackChannel {
+    async fn deliver(&self, notice: &AlertNotice) -> Result<()> {
+        let body = serde_json::json!({ "text": format!("*{}*\n{}", notice.subject(), notice.text()) });
+        self.http
+            .post(&self.webhook_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// JSON POST of the [`AlertNotice`]
+pub struct WebhookChannel {
+    http: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    async fn deliver(&self, notice: &AlertNotice) -> Result<()> {
+        self.http.post(&self.url).json(notice).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Email through an SMTP relay, over plain SMTP
+pub struct SmtpChannel {
+    config: SmtpChannelConfig,
+    /// Name given in EHLO
+    hello: String,
+}
+
+impl SmtpChannel {
+    async fn send_mail(&self, notice: &AlertNotice) -> Result<()> {
+        let stream = TcpStream::connect((self.config.host.as_str(), self.config.port)).await?;
+        let (read, mut write) = stream.into_split();
+        let mut smtp = BufReader::new(read);
+        smtp_reply(&mut smtp, 2).await?;
+        smtp_command(&mut write, &mut smtp, &format!("EHLO {}", self.hello), 2).await?;
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            let credentials = base64::engine::general_purpose::STANDARD.encode(format!("\0{}\0{}", username, password));
+            smtp_command(&mut write, &mut smtp, &format!("AUTH PLAIN {}", credentials), 2).await?;
+        }
+        smtp_command(&mut write, &mut smtp, &format!("MAIL FROM:<{}>", self.config.from), 2).await?;
+        for to in &self.config.to {
+            smtp_command(&mut write, &mut smtp, &format!("RCPT TO:<{}>", to), 2).await?;
+        }
+        smtp_command(&mut write, &mut smtp, "DATA", 3).await?;
+        write.write_all(self.message(notice).as_bytes()).await?;
+        smtp_command(&mut write, &mut smtp, ".", 2).await?;
+        // The message is accepted; a failed goodbye does not matter
+        let _ = smtp_command(&mut write, &mut smtp, "QUIT", 2).await;
+        Ok(())
+    }
+
+    /// Headers and dot-stuffed body, CRLF line endings
+    fn message(&self, notice: &AlertNotice) -> String {
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.config.from,
+            self.config.to.join(", "),
+            notice.subject(),
+            Utc::now().to_rfc2822()
+        );
+        for line in notice.text().lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for SmtpChannel {
+    async fn deliver(&self, notice: &AlertNotice) -> Result<()> {
+        self.send_mail(notice).await
+    }
+}
+
+/// Send an SMTP command and check the reply's class (2 or 3)
+async fn smtp_command<R, W>(write: &mut W, read: &mut R, command: &str, class: u16) -> Result<String>
+where
+    R: AsyncBufReadExt + Unpin,
+    W: AsyncWriteExt + Unpin,
+{
+    write.write_all(format!("{}\r\n", command).as_bytes()).await?;
+    smtp_reply(read, class).await
+}
+
+/// Read a possibly multi-line SMTP reply and check its class
+async fn smtp_reply<R: AsyncBufReadExt + Unpin>(read: &mut R, class: u16) -> Result<String> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if read.read_line(&mut line).await? == 0 {
+            return Err(Error::Protocol("SMTP server closed the connection".into()));
+        }
+        reply.push_str(&line);
+        // "250-" continues a reply, "250 " ends it
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    let code: u16 = reply.get(..3).and_then(|c| c.parse().ok()).unwrap_or(0);
+    if code / 100 != class {
+        return Err(Error::Protocol(format!("SMTP server answered {}", reply.trim_end())));
+    }
+    Ok(reply)
+}
+
+/// The channel a configuration entry describes
+pub fn notification_channel(config: &ChannelConfig, http: &reqwest::Client, node_id: &str) -> Box<dyn NotificationChannel> {
+    match &config.kind {
+        ChannelKind::Smtp(smtp) => Box::new(SmtpChannel {
+            config: smtp.clone(),
+            hello: node_id.to_string(),
+        }),
+        ChannelKind::Slack { webhook_url } => Box::new(SlackChannel {
+            http: http.clone(),
+            webhook_url: webhook_url.clone(),
+        }),
+        ChannelKind::Webhook { url } => Box::new(WebhookChannel {
+            http: http.clone(),
+            url: url.clone(),
+        }),
+    }
+}
+
+/// Whether a channel takes a notice
+fn wants(channel: &ChannelConfig, notice: &AlertNotice) -> bool {
+    (notice.trigger != NoticeTrigger::Escalated || channel.escalations)
+        && rank(&notice.alert.severity) >= rank(&channel.min_severity)
+}
+
+/// Sends alert notices to the configured channels
+pub struct Notifier {
+    http: reqwest::Client,
+    /// Recent send times per channel, for rate limiting
+    sent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Notifier {
+    /// Send notices to every channel that takes them, without waiting for
+    /// the deliveries; outcomes are recorded on the alerts
+    pub fn dispatch(&self, state: &AppState, notices: Vec<AlertNotice>) {
+        let config = state.config.get();
+        for notice in notices {
+            for channel in config.notifications.channels.iter().filter(|c| wants(c, &notice)) {
+                let mut delivery = NotificationDelivery {
+                    channel: channel.id.clone(),
+                    trigger: notice.trigger,
+                    status: DeliveryStatus::RateLimited,
+                    at: Utc::now(),
+                    error: None,
+                };
+                if !self.admit(&channel.id, channel.max_per_hour) {
+                    debug!("Channel {} is rate limited; alert {} not sent", channel.id, notice.alert.alert_id);
+                    state.metrics.notifications_rate_limited.fetch_add(1, Ordering::Relaxed);
+                    state.alerts.record_delivery(&notice.alert.alert_id, delivery);
+                    continue;
+                }
+                let sender = notification_channel(channel, &self.http, &config.node.id);
+                let state = state.clone();
+                let notice = notice.clone();
+                tokio::spawn(async move {
+                    let result = tokio::time::timeout(DELIVERY_TIMEOUT, sender.deliver(&notice))
+                        .await
+                        .unwrap_or_else(|_| Err(Error::Protocol("delivery timed out".into())));
+                    delivery.at = Utc::now();
+                    match result {
+                        Ok(()) => {
+                            delivery.status = DeliveryStatus::Delivered;
+                            state.metrics.notifications_sent.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            warn!("Notification of alert {} to {} failed: {}", notice.alert.alert_id, delivery.channel, e);
+                            delivery.status = DeliveryStatus::Failed;
+                            delivery.error = Some(e.to_string());
+                            state.metrics.notification_failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    state.alerts.record_delivery(&notice.alert.alert_id, delivery);
+                });
+            }
+        }
+    }
+
+    /// Take a send slot from a channel's hourly allowance
+    fn admit(&self, channel: &str, max_per_hour: u32) -> bool {
+        let Ok(mut sent) = self.sent.lock() else {
+            return false;
+        };
+        let now = Instant::now();
+        let times = sent.entry(channel.to_string()).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW) {
+            times.pop_front();
+        }
+        if times.len() >= max_per_hour as usize {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use crate::node::AlertFilter;
+    use crate::node::TenantScope;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// SMTP server accepting one message and passing on its data
+    async fn fake_smtp(messages: mpsc::UnboundedSender<String>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 relay ready\r\n").await.unwrap();
+            let mut data: Option<String> = None;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                if let Some(message) = data.as_mut() {
+                    if line == "." {
+                        messages.send(data.take().unwrap()).unwrap();
+                        write.write_all(b"250 queued\r\n").await.unwrap();
+                    } else {
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    continue;
+                }
+                let reply: &[u8] = match line.split(' ').next().unwrap() {
+                    "EHLO" => b"250-relay\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => b"235 accepted\r\n",
+                    "DATA" => {
+                        data = Some(String::new());
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => b"221 bye\r\n",
+                    _ => b"250 ok\r\n",
+                };
+                write.write_all(reply).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_notify_channels() {
+        let state = test_state("node-a");
+        let (tx, mut posts) = mpsc::unbounded_channel();
+        let receiver = axum::Router::new().route(
+            "/:channel",
+            axum::routing::post(
+                move |axum::extract::Path(channel): axum::extract::Path<String>,
+                      axum::Json(body): axum::Json<serde_json::Value>| async move {
+                    tx.send((channel, body)).unwrap();
+                },
+            ),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await });
+        let (mail_tx, mut mail) = mpsc::unbounded_channel();
+        let smtp_port = fake_smtp(mail_tx).await;
+
+        let channels = format!(
+            "[{{ id: mail, type: smtp, host: 127.0.0.1, port: {}, from: node@example.com, to: [ops@example.com], \
+             username: ops, password: secret }}, \
+             {{ id: slack, type: slack, webhook_url: '{}/slack', min_severity: MEDIUM, max_per_hour: 1 }}, \
+             {{ id: hook, type: webhook, url: '{}/hook', min_severity: LOW }}, \
+             {{ id: quiet, type: webhook, url: '{}/quiet', min_severity: LOW, escalations: false }}]",
+            smtp_port, base, base, base
+        );
+        let mut config = (*state.config.get()).clone();
+        config.notifications.channels = serde_yaml::from_str(&channels).unwrap();
+        state.config.replace(config);
+        state.watchlist.register("12345", None);
+
+        // A MEDIUM alert reaches Slack and the LOW-threshold webhooks, not email
+        let mut cdm = generate_demo_cdm();
+        cdm.collision_probability = 5e-5;
+        cdm.miss_distance_m = 5000.0;
+        state.events.announced(&cdm);
+        state.alerts.catch_up(&state);
+        let mut received: Vec<String> = Vec::new();
+        for _ in 0..3 {
+            let (channel, body) = posts.recv().await.unwrap();
+            if channel != "slack" {
+                assert_eq!(body["trigger"], "opened");
+                assert_eq!(body["alert"]["severity"], "MEDIUM");
+            }
+            received.push(channel);
+        }
+        received.sort();
+        assert_eq!(received, ["hook", "quiet", "slack"]);
+
+        // A HIGH conjunction also reaches email, but Slack has used its
+        // hourly allowance
+        let mut high = cdm.clone();
+        high.cdm_id = "CDM-HIGH".into();
+        high.collision_probability = 1e-3;
+        state.events.announced(&high);
+        state.alerts.catch_up(&state);
+        let message = mail.recv().await.unwrap();
+        assert!(message.contains("Subject: [SpaceComms] High conjunction"));
+        assert!(message.contains("CDM: CDM-HIGH"));
+
+        let scope = TenantScope::default();
+        let filter = AlertFilter {
+            cdm_id: Some("CDM-HIGH".into()),
+            ..Default::default()
+        };
+        let mut deliveries = Vec::new();
+        for _ in 0..50 {
+            deliveries = state.alerts.list(&filter, &scope)[0].notifications.clone();
+            if deliveries.len() == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let status = |channel: &str| deliveries.iter().find(|d| d.channel == channel).map(|d| d.status);
+        assert_eq!(status("mail"), Some(DeliveryStatus::Delivered));
+        assert_eq!(status("slack"), Some(DeliveryStatus::RateLimited));
+        assert_eq!(state.metrics.notifications_rate_limited.load(Ordering::Relaxed), 1);
+
+        // Escalations skip channels that opted out
+        let escalation = Escalation {
+            threshold_hours: 6,
+            tca: high.tca,
+            time_to_tca_seconds: 3600,
+            conjunction_category: crate::cdm::ConjunctionCategory::High,
+            previous_action: crate::cdm::RecommendedAction::Prepare,
+            recommended_action: crate::cdm::RecommendedAction::Maneuver,
+            risk_increasing: false,
+        };
+        while posts.try_recv().is_ok() {}
+        state.events.escalated(&high, escalation);
+        state.alerts.catch_up(&state);
+        let (channel, body) = posts.recv().await.unwrap();
+        assert_eq!((channel.as_str(), &body["trigger"]), ("hook", &serde_json::json!("escalated")));
+        assert_eq!(body["escalation"]["threshold_hours"], 6);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(posts.try_recv().is_err());
+    }
+}
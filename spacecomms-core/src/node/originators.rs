@@ -0,0 +1,377 @@
+//! Per-originator ingest quotas and anomaly detection
+//!
+//! Every CDM taken by `POST /cdm` or from a peer is counted against its
+//! `originator`. An originator over `ingest_quotas.max_cdms_per_minute` has
+//! the rest of that minute's CDMs refused. Two anomalies flag an originator:
+//! a rate spike, a minute with `spike_factor` times its usual rate, and a
+//! payload storm, more than `max_duplicate_payloads` copies of one payload in
+//! a minute whatever their CDM IDs. A flagged originator is held: its CDMs
+//! are refused for `throttle_seconds`, or under `quarantine` until an
+//! operator releases it. The counts are kept in memory and start over when
+//! the node restarts.
+
+use crate::cdm::CdmRecord;
+use crate::config::{AnomalyAction, IngestQuotaConfig};
+use crate::{Error, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+/// Minutes of counts an originator's usual rate is taken over
+pub const BASELINE_MINUTES: i64 = 60;
+
+/// Minutes an originator must have been seen before it can spike
+const MIN_BASELINE_MINUTES: i64 = 10;
+
+/// What an originator was flagged for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// A minute far above the originator's usual rate
+    RateSpike,
+    /// Many copies of one payload within a minute
+    PayloadStorm,
+}
+
+/// An anomaly holding back an originator's CDMs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OriginatorAnomaly {
+    pub kind: AnomalyKind,
+    pub detected_at: DateTime<Utc>,
+    pub detail: String,
+    pub action: AnomalyAction,
+    /// When the CDMs are taken again; unset under quarantine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl OriginatorAnomaly {
+    fn holds(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_none_or(|until| until > now)
+    }
+}
+
+/// One originator's ingest as served by `GET /originators`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OriginatorStatus {
+    pub originator: String,
+    /// CDMs accepted in the current minute
+    pub cdms_this_minute: u32,
+    /// Mean CDMs accepted per minute over the last hour
+    pub baseline_per_minute: f64,
+    pub accepted: u64,
+    /// CDMs refused for the quota or while held
+    pub refused: u64,
+    /// Anomalies flagged since the node started
+    pub anomalies: u64,
+    /// The anomaly holding the originator back, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held: Option<OriginatorAnomaly>,
+}
+
+/// Whether a CDM may be ingested
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission {
+    Accepted,
+    /// The originator reached its quota for this minute
+    OverQuota,
+    /// The originator is held; `new` when this CDM flagged it
+    Held { anomaly: OriginatorAnomaly, new: bool },
+}
+
+impl Admission {
+    /// The error a refused CDM is answered with
+    pub fn into_result(self, originator: &str, config: &IngestQuotaConfig) -> Result<()> {
+        match self {
+            Admission::Accepted => Ok(()),
+            Admission::OverQuota => Err(Error::QuotaExceeded(format!(
+                "originator {} is over its quota of {} CDMs per minute",
+                originator, config.max_cdms_per_minute
+            ))),
+            Admission::Held { anomaly, .. } => Err(Error::QuotaExceeded(format!(
+                "originator {} is held for {}",
+                originator, anomaly.detail
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct OriginatorRecord {
+    /// Accepted counts of recent minutes, oldest first, the current last
+    minutes: VecDeque<(i64, u32)>,
+    first_minute: i64,
+    /// Copies of each payload in the current minute
+    payloads: HashMap<u64, u32>,
+    accepted: u64,
+    refused: u64,
+    anomalies: u64,
+    held: Option<OriginatorAnomaly>,
+}
+
+impl OriginatorRecord {
+    fn roll(&mut self, minute: i64) {
+        if self.minutes.back().is_none_or(|(m, _)| *m != minute) {
+            self.minutes.push_back((minute, 0));
+            self.payloads.clear();
+        }
+        while self.minutes.front().is_some_and(|(m, _)| *m <= minute - BASELINE_MINUTES) {
+            self.minutes.pop_front();
+        }
+    }
+
+    fn this_minute(&self, minute: i64) -> u32 {
+        match self.minutes.back() {
+            Some((m, count)) if *m == minute => *count,
+            _ => 0,
+        }
+    }
+
+    /// Mean accepted per minute before the current one, and the minutes
+    /// it is taken over
+    fn baseline(&self, minute: i64) -> (f64, i64) {
+        let span = (minute - self.first_minute).min(BASELINE_MINUTES - 1);
+        if span <= 0 {
+            return (0.0, 0);
+        }
+        let total: u64 = self
+            .minutes
+            .iter()
+            .filter(|(m, _)| *m < minute && *m >= minute - span)
+            .map(|(_, count)| u64::from(*count))
+            .sum();
+        (total as f64 / span as f64, span)
+    }
+}
+
+/// Fingerprint of what a CDM says, leaving out its ID and creation time
+fn payload_hash(cdm: &CdmRecord) -> u64 {
+    let mut payload = serde_json::to_value(cdm).unwrap_or_default();
+    if let Some(fields) = payload.as_object_mut() {
+        fields.remove("cdm_id");
+        fields.remove("creation_date");
+    }
+    let mut hasher = DefaultHasher::new();
+    payload.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Ingest counts and holds of every originator seen
+#[derive(Debug, Default)]
+pub struct OriginatorGuard {
+    originators: Mutex<HashMap<String, OriginatorRecord>>,
+}
+
+impl OriginatorGuard {
+    /// Count a CDM against its originator, flagging the originator if the
+    /// CDM makes for an anomaly
+    pub fn admit(&self, cdm: &CdmRecord, config: &IngestQuotaConfig, now: DateTime<Utc>) -> Admission {
+        let Ok(mut originators) = self.originators.lock() else {
+            return Admission::Accepted;
+        };
+        let minute = now.timestamp().div_euclid(60);
+        // Originators silent for the whole baseline are forgotten
+        originators.retain(|_, record| {
+            record.held.is_some() || record.minutes.back().is_some_and(|(m, _)| *m > minute - BASELINE_MINUTES)
+        });
+        let record = originators.entry(cdm.originator.clone()).or_insert_with(|| OriginatorRecord {
+            first_minute: minute,
+            ..Default::default()
+        });
+        record.roll(minute);
+
+        if let Some(anomaly) = &record.held {
+            if anomaly.holds(now) {
+                record.refused += 1;
+                return Admission::Held {
+                    anomaly: anomaly.clone(),
+                    new: false,
+                };
+            }
+            record.held = None;
+        }
+
+        let count = record.this_minute(minute);
+        if config.max_cdms_per_minute > 0 && count >= config.max_cdms_per_minute {
+            record.refused += 1;
+            return Admission::OverQuota;
+        }
+
+        let hash = payload_hash(cdm);
+        let copies = record.payloads.get(&hash).copied().unwrap_or(0) + 1;
+        let (baseline, span) = record.baseline(minute);
+        let flagged = if config.max_duplicate_payloads > 0 && copies > config.max_duplicate_payloads {
+            Some((
+                AnomalyKind::PayloadStorm,
+                format!("a payload storm: {} copies of one payload within a minute", copies),
+            ))
+        } else if config.spike_factor > 0.0
+            && span >= MIN_BASELINE_MINUTES
+            && count + 1 >= config.spike_min_cdms
+            && f64::from(count + 1) >= config.spike_factor * baseline.max(1.0)
+        {
+            Some((
+                AnomalyKind::RateSpike,
+                format!(
+                    "a rate spike: {} CDMs within a minute against {:.1} per minute usually",
+                    count + 1,
+                    baseline
+                ),
+            ))
+        } else {
+            None
+        };
+        if let Some((kind, detail)) = flagged {
+            let anomaly = OriginatorAnomaly {
+                kind,
+                detected_at: now,
+                detail,
+                action: config.action,
+                until: (config.action == AnomalyAction::Throttle)
+                    .then(|| now + ChronoDuration::seconds(config.throttle_seconds as i64)),
+            };
+            record.held = Some(anomaly.clone());
+            record.anomalies += 1;
+            record.refused += 1;
+            return Admission::Held { anomaly, new: true };
+        }
+
+        record.payloads.insert(hash, copies);
+        if let Some((_, count)) = record.minutes.back_mut() {
+            *count += 1;
+        }
+        record.accepted += 1;
+        Admission::Accepted
+    }
+
+    /// Every originator seen in the last hour, or held, by name
+    pub fn list(&self, now: DateTime<Utc>) -> Vec<OriginatorStatus> {
+        let Ok(originators) = self.originators.lock() else {
+            return Vec::new();
+        };
+        let minute = now.timestamp().div_euclid(60);
+        let mut list: Vec<OriginatorStatus> = originators
+            .iter()
+            .map(|(originator, record)| OriginatorStatus {
+                originator: originator.clone(),
+                cdms_this_minute: record.this_minute(minute),
+                baseline_per_minute: record.baseline(minute).0,
+                accepted: record.accepted,
+                refused: record.refused,
+                anomalies: record.anomalies,
+                held: record.held.clone().filter(|anomaly| anomaly.holds(now)),
+            })
+            .collect();
+        list.sort_by(|a, b| a.originator.cmp(&b.originator));
+        list
+    }
+
+    /// Take an originator's CDMs again, returning the anomaly that held it
+    pub fn release(&self, originator: &str, now: DateTime<Utc>) -> Option<OriginatorAnomaly> {
+        let mut originators = self.originators.lock().ok()?;
+        let record = originators.get_mut(originator)?;
+        record.held.take().filter(|anomaly| anomaly.holds(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    /// A CDM from `originator` that differs from others only by `miss_distance_m`
+    fn cdm(originator: &str, miss_distance_m: f64) -> CdmRecord {
+        thread_local! {
+            static BASE: CdmRecord = generate_demo_cdm();
+        }
+        let mut cdm = BASE.with(CdmRecord::clone);
+        cdm.cdm_id = uuid::Uuid::new_v4().to_string();
+        cdm.originator = originator.to_string();
+        cdm.miss_distance_m = miss_distance_m;
+        cdm
+    }
+
+    #[test]
+    fn test_quota_and_payload_storm() {
+        let guard = OriginatorGuard::default();
+        let now = Utc::now();
+        let config = IngestQuotaConfig {
+            max_cdms_per_minute: 5,
+            ..Default::default()
+        };
+        for i in 0..5 {
+            assert_eq!(guard.admit(&cdm("OP-A", i as f64), &config, now), Admission::Accepted);
+        }
+        assert_eq!(guard.admit(&cdm("OP-A", 9.0), &config, now), Admission::OverQuota);
+        // Another originator has its own quota, and the next minute a fresh one
+        assert_eq!(guard.admit(&cdm("OP-B", 9.0), &config, now), Admission::Accepted);
+        let later = now + ChronoDuration::seconds(60);
+        assert_eq!(guard.admit(&cdm("OP-A", 9.0), &config, later), Admission::Accepted);
+
+        // The same payload under new IDs, one copy too many
+        let config = IngestQuotaConfig {
+            max_duplicate_payloads: 3,
+            ..Default::default()
+        };
+        for _ in 0..3 {
+            assert_eq!(guard.admit(&cdm("OP-C", 100.0), &config, now), Admission::Accepted);
+        }
+        let Admission::Held { anomaly, new: true } = guard.admit(&cdm("OP-C", 100.0), &config, now) else {
+            panic!("storm not flagged");
+        };
+        assert_eq!(anomaly.kind, AnomalyKind::PayloadStorm);
+        assert_eq!(anomaly.until, Some(now + ChronoDuration::seconds(300)));
+        // Held: even different payloads are refused until the throttle ends
+        assert!(matches!(guard.admit(&cdm("OP-C", 1.0), &config, now), Admission::Held { new: false, .. }));
+        let after = now + ChronoDuration::seconds(301);
+        assert_eq!(guard.admit(&cdm("OP-C", 1.0), &config, after), Admission::Accepted);
+
+        let status = guard.list(after).into_iter().find(|s| s.originator == "OP-C").unwrap();
+        assert_eq!((status.accepted, status.refused, status.anomalies), (4, 2, 1));
+        assert!(status.held.is_none());
+    }
+
+    #[test]
+    fn test_rate_spike_quarantine() {
+        let guard = OriginatorGuard::default();
+        let config = IngestQuotaConfig {
+            spike_factor: 5.0,
+            spike_min_cdms: 20,
+            action: AnomalyAction::Quarantine,
+            ..Default::default()
+        };
+        let start = Utc::now();
+        let mut distance = 0.0;
+        let mut send = |at: DateTime<Utc>| {
+            distance += 1.0;
+            guard.admit(&cdm("OP-A", distance), &config, at)
+        };
+        // Four CDMs a minute for a quarter of an hour
+        for minute in 0..15 {
+            for _ in 0..4 {
+                assert_eq!(send(start + ChronoDuration::minutes(minute)), Admission::Accepted);
+            }
+        }
+        // Then a burst: the twentieth CDM in a minute is five times usual
+        let burst = start + ChronoDuration::minutes(15);
+        for _ in 0..19 {
+            assert_eq!(send(burst), Admission::Accepted);
+        }
+        let Admission::Held { anomaly, new: true } = send(burst) else {
+            panic!("spike not flagged");
+        };
+        assert_eq!((anomaly.kind, anomaly.until), (AnomalyKind::RateSpike, None));
+
+        // Quarantine lasts until released
+        let much_later = burst + ChronoDuration::days(1);
+        assert!(matches!(send(much_later), Admission::Held { new: false, .. }));
+        assert!(guard.list(much_later)[0].held.is_some());
+        assert_eq!(guard.release("OP-A", much_later).map(|a| a.kind), Some(AnomalyKind::RateSpike));
+        assert!(guard.release("OP-A", much_later).is_none());
+        assert_eq!(send(much_later), Admission::Accepted);
+    }
+}
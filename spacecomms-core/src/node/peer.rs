@@ -1,20 +1,45 @@
 //! Peer management
 
-use crate::config::PeerPolicies;
+use crate::config::{PeerConfig, PeerHealthConfig, PeerPolicies, PeerTransport};
+use crate::node::{LatencySummary, LatencyWindow, PeerLatency, Transport};
+use crate::protocol::{
+    initial_sequence, ClockOffsetEstimator, Encoding, Envelope, HeartbeatEcho, Interests, MessageType, SequenceWindow,
+    TimestampFormat,
+};
+use crate::storage::BlockedNode;
+use crate::Result;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Session events kept per peer
+pub const MAX_SESSION_EVENTS: usize = 50;
+
+/// Heartbeats sent to a peer that an echo may still refer to
+const HEARTBEATS_REMEMBERED: usize = 8;
 
 /// Peer connection status
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum PeerStatus {
     Connected,
     Connecting,
     Disconnected,
+    /// Shut down for maintenance, by an operator here or at the peer
+    #[serde(rename = "admin_down")]
+    AdminDown,
+    /// On this node's blocklist
+    Blocked,
 }
 
 /// Peer information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PeerInfo {
     /// Peer identifier
     pub id: String,
@@ -34,31 +59,352 @@ pub struct PeerInfo {
     
     /// Messages received from peer
     pub messages_received: u64,
-    
-    /// Routing policies
+
+    /// Requested session transport
+    pub transport: PeerTransport,
+
+    /// Requested envelope encoding
+    pub encoding: Encoding,
+
+    /// Timestamp profile override
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_format: Option<TimestampFormat>,
+
+    /// Authentication token presented to this peer
     #[serde(skip)]
+    pub auth_token: Option<String>,
+    
+    /// Routing policies, as configured or changed through the API
+    #[serde(default)]
     pub policies: PeerPolicies,
 }
 
+impl PeerInfo {
+    /// A disconnected peer as configured
+    pub fn from_config(config: &PeerConfig) -> Self {
+        Self {
+            id: config.id.clone(),
+            address: config.address.clone(),
+            status: PeerStatus::Disconnected,
+            last_heartbeat: None,
+            messages_sent: 0,
+            messages_received: 0,
+            transport: config.transport,
+            encoding: config.encoding,
+            timestamp_format: config.timestamp_format,
+            auth_token: config.auth_token.clone(),
+            policies: config.policies.clone(),
+        }
+    }
+}
+
+/// Something that happened to a peer session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEventKind {
+    /// Outbound handshake completed and a link was attached
+    Connected,
+    /// Outbound handshake failed
+    HandshakeFailed,
+    /// The peer sent us a HELLO
+    HelloReceived,
+    /// The link was dropped
+    Disconnected,
+    /// Sending an envelope failed
+    SendFailed,
+    /// The peer reported an ERROR
+    PeerError,
+    /// The peer sent an INTEREST_UPDATE
+    InterestsUpdated,
+    /// The peer's error rate put it in quarantine
+    Quarantined,
+    /// An operator released the peer from quarantine
+    Released,
+    /// The peer's clock offset passed `protocol.clock_skew.warn_seconds`
+    ClockSkewed,
+    /// An operator shut the session down
+    Disabled,
+    /// An operator brought the session back up
+    Enabled,
+    /// The peer announced it shut the session down
+    PeerAdminDown,
+    /// An operator blocked the peer
+    Blocked,
+    /// An operator took the peer off the blocklist
+    Unblocked,
+}
+
+/// Timestamped session event
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionEvent {
+    pub at: DateTime<Utc>,
+    pub kind: SessionEventKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Most recent failure involving a peer
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PeerError {
+    pub at: DateTime<Utc>,
+    pub message: String,
+}
+
+/// How a peer's recent exchanges went, and whether it is quarantined
+///
+/// Relayed messages received from the peer, ERRORs it sends and forwards it
+/// answers are counted; connection failures are left to the circuit
+/// breaker.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PeerHealth {
+    /// Share of counted exchanges without an error, 0 to 100
+    pub score: f64,
+    /// Exchanges counted, up to `peer_health.window`
+    pub samples: usize,
+    /// Counted exchanges that ended in an error
+    pub errors: usize,
+    /// Nothing is taken from or forwarded to the peer before this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_until: Option<DateTime<Utc>>,
+    /// Times the peer was quarantined
+    #[serde(default)]
+    pub quarantines: u64,
+    #[serde(skip)]
+    outcomes: VecDeque<bool>,
+}
+
+impl Default for PeerHealth {
+    fn default() -> Self {
+        Self {
+            score: 100.0,
+            samples: 0,
+            errors: 0,
+            quarantined_until: None,
+            quarantines: 0,
+            outcomes: VecDeque::new(),
+        }
+    }
+}
+
+impl PeerHealth {
+    pub fn is_quarantined(&self, now: DateTime<Utc>) -> bool {
+        self.quarantined_until.is_some_and(|until| until > now)
+    }
+
+    /// Count an exchange, returning true when it puts the peer in
+    /// quarantine. Nothing is counted during a quarantine, and the count
+    /// starts over once it ends.
+    fn record(&mut self, ok: bool, config: &PeerHealthConfig, now: DateTime<Utc>) -> bool {
+        if let Some(until) = self.quarantined_until {
+            if until > now {
+                return false;
+            }
+            self.quarantined_until = None;
+            self.outcomes.clear();
+        }
+        self.outcomes.push_back(ok);
+        while self.outcomes.len() > config.window {
+            self.outcomes.pop_front();
+        }
+        self.samples = self.outcomes.len();
+        self.errors = self.outcomes.iter().filter(|ok| !**ok).count();
+        self.score = 100.0 * (self.samples - self.errors) as f64 / self.samples as f64;
+
+        let error_rate = self.errors as f64 / self.samples as f64;
+        if config.quarantine_seconds == 0 || self.samples < config.min_samples || error_rate <= config.max_error_rate {
+            return false;
+        }
+        self.quarantined_until = Some(now + chrono::Duration::seconds(config.quarantine_seconds as i64));
+        self.quarantines += 1;
+        true
+    }
+
+    /// End a quarantine and start counting over
+    fn release(&mut self) {
+        *self = Self {
+            quarantines: self.quarantines,
+            ..Self::default()
+        };
+    }
+}
+
+/// Administrative shutdown of a peering
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AdminDown {
+    /// Whether the peer shut the session down rather than this node
+    pub by_peer: bool,
+    pub since: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Estimated offset of a peer's clock from this node's
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClockSkew {
+    /// Median of recent samples in milliseconds; positive when the peer's
+    /// clock is ahead
+    pub offset_ms: i64,
+    /// Samples the estimate is based on
+    pub samples: usize,
+    pub updated_at: DateTime<Utc>,
+    /// Whether the offset exceeds `protocol.clock_skew.warn_seconds`
+    pub exceeds_warning: bool,
+}
+
+/// Session details for one peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PeerSession {
+    /// Protocol version agreed in the last handshake
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+
+    /// Capabilities the peer advertised in its last HELLO that the session
+    /// uses
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+
+    /// Capabilities the peer advertised that the session does not use:
+    /// newer than the agreed version, or disabled on this node
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub withheld_capabilities: Vec<String>,
+
+    /// Objects the peer wants announcements about; absent means all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interests: Option<Interests>,
+
+    /// Base URL the peer advertised in its last HELLO
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advertised_address: Option<String>,
+
+    /// When the current link was established
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connected_since: Option<DateTime<Utc>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<PeerError>,
+
+    /// Envelopes sent, by message type
+    #[serde(default)]
+    pub sent: BTreeMap<String, u64>,
+
+    /// Envelopes received, by message type
+    #[serde(default)]
+    pub received: BTreeMap<String, u64>,
+
+    /// Last session events, oldest first
+    #[serde(default)]
+    #[schema(value_type = Vec<SessionEvent>)]
+    pub events: VecDeque<SessionEvent>,
+
+    #[serde(default)]
+    pub health: PeerHealth,
+
+    /// Estimated clock offset, once the peer has been heard from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<ClockSkew>,
+
+    #[serde(default)]
+    pub latency: PeerLatency,
+
+    /// Set while the session is shut down for maintenance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_down: Option<AdminDown>,
+}
+
+impl PeerSession {
+    fn push_event(&mut self, kind: SessionEventKind, detail: Option<String>) {
+        self.events.push_back(SessionEvent {
+            at: Utc::now(),
+            kind,
+            detail,
+        });
+        if self.events.len() > MAX_SESSION_EVENTS {
+            self.events.pop_front();
+        }
+    }
+}
+
+/// Link wrapper numbering envelopes and counting sends that have not
+/// completed yet
+struct TrackedLink {
+    inner: Arc<dyn Transport>,
+    in_flight: Arc<AtomicUsize>,
+    next_sequence: AtomicU64,
+}
+
+#[async_trait]
+impl Transport for TrackedLink {
+    fn kind(&self) -> PeerTransport {
+        self.inner.kind()
+    }
+
+    fn accepts_batches(&self) -> bool {
+        self.inner.accepts_batches()
+    }
+
+    async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+        let mut envelope = envelope.clone();
+        envelope.sequence = Some(self.next_sequence.fetch_add(1, Ordering::Relaxed));
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let result = self.inner.send(&envelope).await;
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+}
+
 /// Peer manager
 pub struct PeerManager {
     peers: Vec<PeerInfo>,
+    links: HashMap<String, Arc<dyn Transport>>,
+    sessions: HashMap<String, PeerSession>,
+    in_flight: HashMap<String, Arc<AtomicUsize>>,
+    sequences: HashMap<String, SequenceWindow>,
+    clocks: HashMap<String, ClockOffsetEstimator>,
+    /// Recent heartbeats sent to each peer, by sequence
+    heartbeats_sent: HashMap<String, VecDeque<(u64, Instant)>>,
+    /// Last heartbeat received from each peer, not yet echoed
+    heartbeats_heard: HashMap<String, (u64, Instant)>,
+    /// CDM propagation delays over every peer
+    propagation: LatencyWindow,
+    /// Nodes blocked by an operator, peers or not
+    blocked: HashMap<String, BlockedNode>,
 }
 
 impl PeerManager {
     /// Create a new peer manager
     pub fn new() -> Self {
-        Self { peers: Vec::new() }
+        Self {
+            peers: Vec::new(),
+            links: HashMap::new(),
+            sessions: HashMap::new(),
+            in_flight: HashMap::new(),
+            sequences: HashMap::new(),
+            clocks: HashMap::new(),
+            heartbeats_sent: HashMap::new(),
+            heartbeats_heard: HashMap::new(),
+            propagation: LatencyWindow::default(),
+            blocked: HashMap::new(),
+        }
     }
 
-    /// Add a peer
-    pub fn add_peer(&mut self, peer: PeerInfo) {
+    /// Add a peer, returning true if it was not already known
+    pub fn add_peer(&mut self, peer: PeerInfo) -> bool {
         // Check if peer already exists
         if let Some(existing) = self.peers.iter_mut().find(|p| p.id == peer.id) {
             existing.address = peer.address;
+            existing.transport = peer.transport;
+            existing.encoding = peer.encoding;
+            existing.timestamp_format = peer.timestamp_format;
+            existing.auth_token = peer.auth_token;
             existing.policies = peer.policies;
+            false
         } else {
+            let id = peer.id.clone();
             self.peers.push(peer);
+            if self.is_blocked(&id) {
+                self.set_peer_status(&id, PeerStatus::Blocked);
+            }
+            true
         }
     }
 
@@ -66,9 +412,54 @@ impl PeerManager {
     pub fn remove_peer(&mut self, id: &str) -> bool {
         let len_before = self.peers.len();
         self.peers.retain(|p| p.id != id);
+        self.links.remove(id);
+        self.sessions.remove(id);
+        self.in_flight.remove(id);
+        self.sequences.remove(id);
+        self.clocks.remove(id);
+        self.heartbeats_sent.remove(id);
+        self.heartbeats_heard.remove(id);
         self.peers.len() < len_before
     }
 
+    /// Attach an established transport link to a peer
+    pub fn set_link(&mut self, id: &str, link: Arc<dyn Transport>) {
+        let in_flight = self.in_flight.entry(id.to_string()).or_default().clone();
+        let link = TrackedLink {
+            inner: link,
+            in_flight,
+            next_sequence: AtomicU64::new(initial_sequence()),
+        };
+        self.links.insert(id.to_string(), Arc::new(link));
+        if let Some(session) = self.session_mut(id) {
+            session.connected_since = Some(Utc::now());
+        }
+    }
+
+    /// Get the established transport link for a peer
+    pub fn link(&self, id: &str) -> Option<Arc<dyn Transport>> {
+        self.links.get(id).cloned()
+    }
+
+    /// Drop a peer's transport link and mark it disconnected
+    pub fn drop_link(&mut self, id: &str) {
+        if self.links.remove(id).is_some() {
+            if let Some(session) = self.session_mut(id) {
+                session.connected_since = None;
+                session.push_event(SessionEventKind::Disconnected, None);
+            }
+        }
+        self.set_peer_status(id, PeerStatus::Disconnected);
+    }
+
+    /// Drop every peer's transport link
+    pub fn drop_all_links(&mut self) {
+        let ids: Vec<String> = self.links.keys().cloned().collect();
+        for id in ids {
+            self.drop_link(&id);
+        }
+    }
+
     /// Get a peer by ID
     pub fn get_peer(&self, id: &str) -> Option<&PeerInfo> {
         self.peers.iter().find(|p| p.id == id)
@@ -94,29 +485,371 @@ impl PeerManager {
         self.peers.len()
     }
 
-    /// Update peer status
+    /// Update peer status; a blocked peer, or one shut down for
+    /// maintenance, stays down
     pub fn set_peer_status(&mut self, id: &str, status: PeerStatus) {
+        let status = if self.is_blocked(id) {
+            PeerStatus::Blocked
+        } else if self.admin_down(id).is_some() {
+            PeerStatus::AdminDown
+        } else {
+            status
+        };
         if let Some(peer) = self.get_peer_mut(id) {
             peer.status = status;
         }
     }
 
     /// Record message sent
-    pub fn record_sent(&mut self, id: &str) {
+    pub fn record_sent(&mut self, id: &str, message_type: &MessageType) {
         if let Some(peer) = self.get_peer_mut(id) {
             peer.messages_sent += 1;
         }
+        if let Some(session) = self.session_mut(id) {
+            *session.sent.entry(message_type.to_string()).or_default() += 1;
+        }
     }
 
     /// Record message received
-    pub fn record_received(&mut self, id: &str) {
+    pub fn record_received(&mut self, id: &str, message_type: &MessageType) {
         if let Some(peer) = self.get_peer_mut(id) {
             peer.messages_received += 1;
         }
+        if let Some(session) = self.session_mut(id) {
+            *session.received.entry(message_type.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Record the outcome of a HELLO exchange
+    pub fn record_handshake(&mut self, id: &str, protocol_version: String, capabilities: Vec<String>) {
+        if let Some(session) = self.session_mut(id) {
+            session.protocol_version = Some(protocol_version);
+            session.capabilities = capabilities;
+        }
+    }
+
+    /// Record the capabilities a handshake left out of the session
+    pub fn record_withheld_capabilities(&mut self, id: &str, capabilities: Vec<String>) {
+        if let Some(session) = self.session_mut(id) {
+            session.withheld_capabilities = capabilities;
+        }
+    }
+
+    /// Remember the address a peer advertised, warning when it is not the
+    /// one this node is configured to reach it at
+    pub fn record_advertised_address(&mut self, id: &str, address: Option<String>) {
+        let configured = match self.get_peer(id) {
+            Some(peer) => peer.address.trim_end_matches('/').to_string(),
+            None => return,
+        };
+        if let Some(advertised) = address.as_deref().filter(|a| a.trim_end_matches('/') != configured) {
+            warn!("Peer {} advertises {} but is configured at {}", id, advertised, configured);
+        }
+        if let Some(session) = self.session_mut(id) {
+            session.advertised_address = address;
+        }
+    }
+
+    /// Replace the interests a peer advertised
+    pub fn record_interests(&mut self, id: &str, interests: Option<Interests>) {
+        if let Some(session) = self.session_mut(id) {
+            session.interests = interests;
+        }
+    }
+
+    /// Objects a peer wants announcements about; `None` means all
+    pub fn interests(&self, id: &str) -> Option<&Interests> {
+        self.sessions.get(id)?.interests.as_ref()
+    }
+
+    /// Append a session event
+    pub fn record_event(&mut self, id: &str, kind: SessionEventKind, detail: Option<String>) {
+        if let Some(session) = self.session_mut(id) {
+            session.push_event(kind, detail);
+        }
+    }
+
+    /// Append a session event and remember it as the peer's last error
+    pub fn record_error(&mut self, id: &str, kind: SessionEventKind, message: String) {
+        if let Some(session) = self.session_mut(id) {
+            session.last_error = Some(PeerError {
+                at: Utc::now(),
+                message: message.clone(),
+            });
+            session.push_event(kind, Some(message));
+        }
+    }
+
+    /// Count an exchange with a peer towards its health score, returning
+    /// true when it puts the peer in quarantine
+    pub fn record_outcome(&mut self, id: &str, ok: bool, config: &PeerHealthConfig) -> bool {
+        let Some(session) = self.session_mut(id) else {
+            return false;
+        };
+        if !session.health.record(ok, config, Utc::now()) {
+            return false;
+        }
+        let health = &session.health;
+        let detail = format!(
+            "{} of the last {} exchanges failed; quarantined for {} s",
+            health.errors, health.samples, config.quarantine_seconds
+        );
+        session.push_event(SessionEventKind::Quarantined, Some(detail));
+        true
+    }
+
+    /// Whether a peer is in quarantine
+    pub fn is_quarantined(&self, id: &str) -> bool {
+        self.sessions
+            .get(id)
+            .is_some_and(|session| session.health.is_quarantined(Utc::now()))
+    }
+
+    /// Lift a peer's quarantine, if it has one, and reset its score
+    pub fn release_quarantine(&mut self, id: &str) -> Option<PeerHealth> {
+        let session = self.session_mut(id)?;
+        if session.health.is_quarantined(Utc::now()) {
+            session.push_event(SessionEventKind::Released, None);
+        }
+        session.health.release();
+        Some(session.health.clone())
+    }
+
+    /// Shut a peer's session down for maintenance, keeping its
+    /// configuration; false for an unknown peer
+    pub fn disable(&mut self, id: &str, reason: Option<String>) -> bool {
+        let Some(session) = self.session_mut(id) else {
+            return false;
+        };
+        if session.admin_down.as_ref().is_none_or(|down| down.by_peer) {
+            session.admin_down = Some(AdminDown {
+                by_peer: false,
+                since: Utc::now(),
+                reason: reason.clone(),
+            });
+            session.push_event(SessionEventKind::Disabled, reason);
+        }
+        self.drop_link(id);
+        true
+    }
+
+    /// Bring a peer's session back up after [`disable`](Self::disable);
+    /// false for an unknown peer
+    pub fn enable(&mut self, id: &str) -> bool {
+        let Some(session) = self.session_mut(id) else {
+            return false;
+        };
+        if session.admin_down.take().is_some() {
+            session.push_event(SessionEventKind::Enabled, None);
+        }
+        self.set_peer_status(id, PeerStatus::Disconnected);
+        true
+    }
+
+    /// Whether an operator here shut a peer's session down
+    pub fn is_disabled(&self, id: &str) -> bool {
+        self.admin_down(id).is_some_and(|down| !down.by_peer)
+    }
+
+    /// Take note of a peer announcing it shut the session down, dropping
+    /// the link until it says otherwise
+    pub fn record_peer_admin_down(&mut self, id: &str, reason: Option<String>) {
+        let Some(session) = self.session_mut(id) else {
+            return;
+        };
+        if session.admin_down.is_none() {
+            session.admin_down = Some(AdminDown {
+                by_peer: true,
+                since: Utc::now(),
+                reason: reason.clone(),
+            });
+            session.push_event(SessionEventKind::PeerAdminDown, reason);
+        }
+        self.drop_link(id);
+    }
+
+    /// Block a node, dropping the link if it is a peer; blocking it again
+    /// keeps the first block's time and takes the new reason
+    pub fn block(&mut self, node: BlockedNode) -> BlockedNode {
+        let id = node.node_id.clone();
+        let node = match self.blocked.remove(&id) {
+            Some(earlier) => BlockedNode { since: earlier.since, ..node },
+            None => {
+                if let Some(session) = self.session_mut(&id) {
+                    session.push_event(SessionEventKind::Blocked, node.reason.clone());
+                }
+                node
+            }
+        };
+        self.blocked.insert(id.clone(), node.clone());
+        self.drop_link(&id);
+        node
+    }
+
+    /// Take a node off the blocklist; a peer reconnects within a heartbeat
+    /// interval
+    pub fn unblock(&mut self, id: &str) -> Option<BlockedNode> {
+        let node = self.blocked.remove(id)?;
+        if let Some(session) = self.session_mut(id) {
+            session.push_event(SessionEventKind::Unblocked, None);
+        }
+        self.set_peer_status(id, PeerStatus::Disconnected);
+        Some(node)
+    }
+
+    pub fn is_blocked(&self, id: &str) -> bool {
+        self.blocked.contains_key(id)
+    }
+
+    /// Blocked nodes, by ID
+    pub fn blocked_nodes(&self) -> Vec<BlockedNode> {
+        let mut nodes: Vec<_> = self.blocked.values().cloned().collect();
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        nodes
+    }
+
+    /// The blocked node an envelope comes from, if any: the peer that sent
+    /// it, the node that originated it, or a node its provenance passed
+    /// through
+    pub fn blocked_in<'a>(&self, envelope: &'a Envelope, sender: &'a str) -> Option<&'a str> {
+        if self.blocked.is_empty() {
+            return None;
+        }
+        [sender, envelope.source_node_id.as_str()]
+            .into_iter()
+            .chain(envelope.provenance.iter().map(|hop| hop.node_id.as_str()))
+            .find(|id| self.blocked.contains_key(*id))
+    }
+
+    /// A peer's administrative shutdown, by either side
+    pub fn admin_down(&self, id: &str) -> Option<&AdminDown> {
+        self.sessions.get(id)?.admin_down.as_ref()
+    }
+
+    /// Session details for a peer
+    pub fn session(&self, id: &str) -> Option<PeerSession> {
+        self.get_peer(id)?;
+        Some(self.sessions.get(id).cloned().unwrap_or_default())
     }
 
-    /// Update heartbeat
+    /// Envelopes handed to a peer's link that have not been delivered yet
+    pub fn queue_depth(&self, id: &str) -> usize {
+        self.in_flight.get(id).map_or(0, |n| n.load(Ordering::Relaxed))
+    }
+
+    /// Accept a link sequence number from a peer once; numbers from
+    /// unknown senders are not tracked
+    pub fn check_sequence(&mut self, id: &str, sequence: u64) -> Result<()> {
+        if self.get_peer(id).is_none() {
+            return Ok(());
+        }
+        self.sequences.entry(id.to_string()).or_default().check(sequence)
+    }
+
+    /// Forget a peer's sequence numbers when it starts a new session, so a
+    /// peer whose clock stepped back is not locked out
+    pub fn reset_sequence(&mut self, id: &str) {
+        self.sequences.remove(id);
+    }
+
+    /// Add a clock offset sample for a peer and update its estimate,
+    /// warning when the offset first passes `warn_ms`
+    pub fn record_clock_sample(&mut self, id: &str, offset_ms: i64, warn_ms: u64) {
+        if self.get_peer(id).is_none() {
+            return;
+        }
+        let estimator = self.clocks.entry(id.to_string()).or_default();
+        estimator.add(offset_ms);
+        let (Some(offset_ms), samples) = (estimator.offset_ms(), estimator.samples()) else {
+            return;
+        };
+        let exceeds_warning = offset_ms.unsigned_abs() > warn_ms;
+        let Some(session) = self.session_mut(id) else {
+            return;
+        };
+        let warned = session.clock.as_ref().is_some_and(|clock| clock.exceeds_warning);
+        if exceeds_warning && !warned {
+            let detail = format!("clock offset {:+.1}s", offset_ms as f64 / 1000.0);
+            warn!("Peer {} {}", id, detail);
+            session.push_event(SessionEventKind::ClockSkewed, Some(detail));
+        }
+        session.clock = Some(ClockSkew {
+            offset_ms,
+            samples,
+            updated_at: Utc::now(),
+            exceeds_warning,
+        });
+    }
+
+    /// A peer's estimated clock offset in milliseconds
+    pub fn clock_offset(&self, id: &str) -> Option<i64> {
+        self.clocks.get(id)?.offset_ms()
+    }
+
+    /// Remember when a heartbeat went out, for its echo to be timed
+    pub fn record_heartbeat_sent(&mut self, id: &str, sequence: u64) {
+        if self.get_peer(id).is_none() {
+            return;
+        }
+        let sent = self.heartbeats_sent.entry(id.to_string()).or_default();
+        sent.push_back((sequence, Instant::now()));
+        if sent.len() > HEARTBEATS_REMEMBERED {
+            sent.pop_front();
+        }
+    }
+
+    /// The peer's last heartbeat to hand back in the next one sent to it,
+    /// once only
+    pub fn take_heartbeat_echo(&mut self, id: &str) -> Option<HeartbeatEcho> {
+        let (sequence, heard_at) = self.heartbeats_heard.remove(id)?;
+        Some(HeartbeatEcho {
+            sequence,
+            held_ms: heard_at.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Take in a heartbeat from a peer, timing the round trip of the one it
+    /// echoes; returns the round trip in milliseconds
+    pub fn record_heartbeat(&mut self, id: &str, sequence: u64, echo: Option<HeartbeatEcho>) -> Option<u64> {
+        self.get_peer(id)?;
+        self.heartbeats_heard.insert(id.to_string(), (sequence, Instant::now()));
+        let echo = echo?;
+        let sent = self.heartbeats_sent.get_mut(id)?;
+        let position = sent.iter().position(|(sequence, _)| *sequence == echo.sequence)?;
+        let (_, sent_at) = sent.remove(position)?;
+        let round_trip_ms = (sent_at.elapsed().as_millis() as u64).saturating_sub(echo.held_ms);
+        self.session_mut(id)?.latency.add_round_trip(round_trip_ms);
+        Some(round_trip_ms)
+    }
+
+    /// Count the delay between a CDM's origin and its arrival from a peer
+    pub fn record_propagation(&mut self, id: &str, delay_ms: u64) {
+        self.propagation.add(delay_ms);
+        if let Some(session) = self.session_mut(id) {
+            session.latency.add_propagation(delay_ms);
+        }
+    }
+
+    /// CDM propagation delays over every peer
+    pub fn cdm_propagation(&self) -> Option<LatencySummary> {
+        self.propagation.summary().cloned()
+    }
+
+    fn session_mut(&mut self, id: &str) -> Option<&mut PeerSession> {
+        self.get_peer(id)?;
+        Some(self.sessions.entry(id.to_string()).or_default())
+    }
+
+    /// Update heartbeat; hearing from a peer that had shut the session
+    /// down means it is back, while peers disabled or blocked here stay
+    /// down
     pub fn update_heartbeat(&mut self, id: &str) {
+        if self.is_disabled(id) || self.is_blocked(id) {
+            return;
+        }
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.admin_down = None;
+        }
         if let Some(peer) = self.get_peer_mut(id) {
             peer.last_heartbeat = Some(Utc::now());
             peer.status = PeerStatus::Connected;
@@ -142,6 +875,10 @@ mod tests {
             last_heartbeat: None,
             messages_sent: 0,
             messages_received: 0,
+            transport: PeerTransport::Http,
+            encoding: Encoding::Json,
+            timestamp_format: None,
+            auth_token: None,
             policies: PeerPolicies::default(),
         }
     }
@@ -149,7 +886,8 @@ mod tests {
     #[test]
     fn test_add_peer() {
         let mut mgr = PeerManager::new();
-        mgr.add_peer(test_peer());
+        assert!(mgr.add_peer(test_peer()));
+        assert!(!mgr.add_peer(test_peer()));
         assert_eq!(mgr.total_count(), 1);
     }
 
@@ -171,4 +909,213 @@ mod tests {
         assert_eq!(peer.status, PeerStatus::Connected);
         assert!(peer.last_heartbeat.is_some());
     }
+
+    #[test]
+    fn test_admin_down() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(test_peer());
+        mgr.set_link("peer-1", Arc::new(SlowLink));
+        mgr.update_heartbeat("peer-1");
+        assert!(mgr.disable("peer-1", Some("maintenance".into())));
+        assert!(!mgr.disable("peer-2", None));
+        assert!(mgr.is_disabled("peer-1") && mgr.link("peer-1").is_none());
+
+        // Nothing brings a disabled peer up but enabling it
+        mgr.update_heartbeat("peer-1");
+        mgr.set_peer_status("peer-1", PeerStatus::Connected);
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::AdminDown);
+        assert!(mgr.enable("peer-1"));
+        assert!(!mgr.is_disabled("peer-1"));
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::Disconnected);
+
+        // A peer's own shutdown lasts until it is heard from again
+        mgr.record_peer_admin_down("peer-1", None);
+        let session = mgr.session("peer-1").unwrap();
+        assert!(session.admin_down.is_some_and(|down| down.by_peer));
+        assert!(!mgr.is_disabled("peer-1"));
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::AdminDown);
+        mgr.update_heartbeat("peer-1");
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::Connected);
+        let kinds: Vec<_> = mgr.session("peer-1").unwrap().events.iter().map(|e| e.kind).collect();
+        assert!(kinds.contains(&SessionEventKind::Disabled) && kinds.contains(&SessionEventKind::PeerAdminDown));
+    }
+
+    #[test]
+    fn test_blocklist() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(test_peer());
+        mgr.set_link("peer-1", Arc::new(SlowLink));
+        mgr.update_heartbeat("peer-1");
+        let block = |id: &str, reason: &str| BlockedNode {
+            node_id: id.to_string(),
+            since: Utc::now(),
+            reason: Some(reason.to_string()),
+        };
+        let first = mgr.block(block("peer-1", "compromised"));
+        assert!(mgr.is_blocked("peer-1") && mgr.link("peer-1").is_none());
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::Blocked);
+        let again = mgr.block(block("peer-1", "still compromised"));
+        assert_eq!(again.since, first.since);
+        assert_eq!(again.reason.as_deref(), Some("still compromised"));
+
+        // Nothing brings a blocked peer up but unblocking it
+        mgr.update_heartbeat("peer-1");
+        mgr.set_peer_status("peer-1", PeerStatus::Connected);
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::Blocked);
+
+        // Messages from, originated by or relayed through a blocked node
+        mgr.block(block("node-far", "malfunctioning"));
+        let mut envelope = Envelope::new("node-origin".into(), MessageType::CdmAnnounce, serde_json::json!({}));
+        assert_eq!(mgr.blocked_in(&envelope, "peer-1"), Some("peer-1"));
+        assert_eq!(mgr.blocked_in(&envelope, "peer-2"), None);
+        envelope.provenance.push(crate::protocol::ProvenanceHop {
+            node_id: "node-far".into(),
+            received_at: Utc::now(),
+            payload_sha256: None,
+            signature: None,
+        });
+        assert_eq!(mgr.blocked_in(&envelope, "peer-2"), Some("node-far"));
+        assert_eq!(mgr.blocked_nodes().len(), 2);
+
+        assert!(mgr.unblock("peer-1").is_some());
+        assert!(mgr.unblock("peer-1").is_none());
+        assert_eq!(mgr.get_peer("peer-1").unwrap().status, PeerStatus::Disconnected);
+        let kinds: Vec<_> = mgr.session("peer-1").unwrap().events.iter().map(|e| e.kind).collect();
+        assert!(kinds.contains(&SessionEventKind::Blocked) && kinds.contains(&SessionEventKind::Unblocked));
+    }
+
+    struct SlowLink;
+
+    #[async_trait]
+    impl Transport for SlowLink {
+        fn kind(&self) -> PeerTransport {
+            PeerTransport::Http
+        }
+
+        async fn send(&self, _envelope: &Envelope) -> Result<Option<Envelope>> {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(None)
+        }
+    }
+
+    /// Link remembering the sequence numbers it was asked to send
+    #[derive(Default)]
+    struct RecordingLink(std::sync::Mutex<Vec<Option<u64>>>);
+
+    #[async_trait]
+    impl Transport for RecordingLink {
+        fn kind(&self) -> PeerTransport {
+            PeerTransport::Http
+        }
+
+        async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+            self.0.lock().unwrap().push(envelope.sequence);
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_sequences() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(test_peer());
+        let recorder = Arc::new(RecordingLink::default());
+        mgr.set_link("peer-1", recorder.clone());
+
+        let link = mgr.link("peer-1").unwrap();
+        let envelope = Envelope::new("node".into(), MessageType::Heartbeat, serde_json::json!({}));
+        link.send(&envelope).await.unwrap();
+        link.send(&envelope).await.unwrap();
+        let sent = recorder.0.lock().unwrap().clone();
+        assert_eq!(sent[1], sent[0].map(|s| s + 1));
+        assert!(envelope.sequence.is_none());
+
+        // Received numbers are accepted once per known peer
+        mgr.check_sequence("peer-1", 7).unwrap();
+        assert!(mgr.check_sequence("peer-1", 7).is_err());
+        assert!(mgr.check_sequence("peer-2", 7).is_ok());
+        assert!(mgr.check_sequence("peer-2", 7).is_ok());
+        mgr.reset_sequence("peer-1");
+        mgr.check_sequence("peer-1", 7).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_tracking() {
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(test_peer());
+        mgr.set_link("peer-1", Arc::new(SlowLink));
+        mgr.record_handshake("peer-1", "1.0".into(), vec!["CDM".into()]);
+        mgr.record_sent("peer-1", &MessageType::CdmAnnounce);
+        mgr.record_sent("peer-1", &MessageType::CdmAnnounce);
+        mgr.record_received("peer-1", &MessageType::Heartbeat);
+        mgr.record_error("peer-1", SessionEventKind::SendFailed, "timeout".into());
+        // Unknown peers are ignored
+        mgr.record_sent("peer-2", &MessageType::Hello);
+        assert!(mgr.session("peer-2").is_none());
+
+        let link = mgr.link("peer-1").unwrap();
+        let envelope = Envelope::new("node".into(), MessageType::Heartbeat, serde_json::json!({}));
+        let send = tokio::spawn(async move { link.send(&envelope).await });
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert_eq!(mgr.queue_depth("peer-1"), 1);
+        send.await.unwrap().unwrap();
+        assert_eq!(mgr.queue_depth("peer-1"), 0);
+
+        mgr.drop_link("peer-1");
+        let session = mgr.session("peer-1").unwrap();
+        assert_eq!(session.protocol_version.as_deref(), Some("1.0"));
+        assert_eq!(session.sent["CDM_ANNOUNCE"], 2);
+        assert_eq!(session.received["HEARTBEAT"], 1);
+        assert_eq!(session.last_error.unwrap().message, "timeout");
+        assert!(session.connected_since.is_none());
+        let kinds: Vec<_> = session.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [SessionEventKind::SendFailed, SessionEventKind::Disconnected]);
+
+        for _ in 0..MAX_SESSION_EVENTS + 10 {
+            mgr.record_event("peer-1", SessionEventKind::HelloReceived, None);
+        }
+        assert_eq!(mgr.session("peer-1").unwrap().events.len(), MAX_SESSION_EVENTS);
+    }
+
+    #[test]
+    fn test_peer_health() {
+        let config = PeerHealthConfig {
+            window: 10,
+            min_samples: 4,
+            max_error_rate: 0.5,
+            quarantine_seconds: 60,
+        };
+        let mut mgr = PeerManager::new();
+        mgr.add_peer(test_peer());
+        for ok in [true, false, false] {
+            assert!(!mgr.record_outcome("peer-1", ok, &config));
+        }
+        // Two thirds failed, but too few exchanges to judge
+        let health = mgr.session("peer-1").unwrap().health;
+        assert_eq!((health.samples, health.errors), (3, 2));
+        assert!((health.score - 100.0 / 3.0).abs() < 1e-9);
+        assert!(!mgr.is_quarantined("peer-1"));
+
+        assert!(mgr.record_outcome("peer-1", false, &config));
+        assert!(mgr.is_quarantined("peer-1"));
+        // Nothing counts while quarantined
+        assert!(!mgr.record_outcome("peer-1", false, &config));
+        let session = mgr.session("peer-1").unwrap();
+        assert_eq!((session.health.samples, session.health.quarantines), (4, 1));
+        assert_eq!(session.events.back().unwrap().kind, SessionEventKind::Quarantined);
+
+        let health = mgr.release_quarantine("peer-1").unwrap();
+        assert!(!mgr.is_quarantined("peer-1"));
+        assert_eq!((health.score, health.samples, health.quarantines), (100.0, 0, 1));
+        assert!(mgr.release_quarantine("peer-2").is_none());
+
+        // Disabled quarantine still keeps the score
+        let scoring_only = PeerHealthConfig {
+            quarantine_seconds: 0,
+            ..config
+        };
+        for _ in 0..10 {
+            assert!(!mgr.record_outcome("peer-1", false, &scoring_only));
+        }
+        assert_eq!(mgr.session("peer-1").unwrap().health.score, 0.0);
+    }
 }
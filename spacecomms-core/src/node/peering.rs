@@ -0,0 +1,251 @@
+//! Peering configuration export and import
+//!
+//! `GET /peers/export` writes the node's peer list, each peer with its
+//! connection settings and policies, as one YAML or JSON document.
+//! `POST /peers/import` applies such a document to another node, which is
+//! how operators keep redundant exchange nodes peering alike. Peers are
+//! written as in the `peers` configuration section.
+//!
+//! Auth tokens are left out of exports unless asked for. An imported peer
+//! without one keeps the token it has. Imported policies are kept like
+//! those set through `PATCH /peers/:id/policies`.
+
+use crate::config::PeerConfig;
+use crate::node::{spawn_session, AppState, PeerInfo, PeerStatus};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Value of [`PeeringDocument::format`]
+pub const PEERING_FORMAT: &str = "spacecomms-peers";
+
+/// Peering document layout version this build writes and reads
+pub const PEERING_VERSION: u32 = 1;
+
+/// A node's peers as exported
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PeeringDocument {
+    pub format: String,
+    pub version: u32,
+    /// Node the peers were exported from
+    pub node_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub peers: Vec<PeerConfig>,
+}
+
+/// Outcome of `POST /peers/import`, by peer ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PeeringImportReport {
+    /// Node the document was exported from
+    pub source_node_id: String,
+    pub added: Vec<String>,
+    /// Peers whose settings or policies changed
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// Peers the document does not list, removed because of `prune`
+    pub removed: Vec<String>,
+    /// Entries naming this node, which does not peer with itself
+    pub skipped: Vec<String>,
+}
+
+/// The node's peers as a peering document
+pub async fn export_peering(state: &AppState, include_secrets: bool) -> PeeringDocument {
+    let peers = state
+        .peers
+        .read()
+        .await
+        .list_peers()
+        .iter()
+        .map(|peer| PeerConfig {
+            id: peer.id.clone(),
+            address: peer.address.clone(),
+            auth_token: peer.auth_token.clone().filter(|_| include_secrets),
+            transport: peer.transport,
+            encoding: peer.encoding,
+            timestamp_format: peer.timestamp_format,
+            group: None,
+            policies: peer.policies.clone(),
+        })
+        .collect();
+    PeeringDocument {
+        format: PEERING_FORMAT.to_string(),
+        version: PEERING_VERSION,
+        node_id: state.config.get().node.id.clone(),
+        exported_at: Utc::now(),
+        peers,
+    }
+}
+
+/// Check a document before any of it is applied
+fn validate(document: &PeeringDocument) -> Result<()> {
+    if document.format != PEERING_FORMAT || document.version != PEERING_VERSION {
+        return Err(Error::Protocol(format!(
+            "unsupported peering document {} version {} (this node reads {} version {})",
+            document.format, document.version, PEERING_FORMAT, PEERING_VERSION
+        )));
+    }
+    let mut ids = HashSet::new();
+    for peer in &document.peers {
+        if peer.id.is_empty() || peer.address.is_empty() {
+            return Err(Error::Protocol("every peer needs an id and an address".into()));
+        }
+        if !ids.insert(peer.id.as_str()) {
+            return Err(Error::Protocol(format!("peer {} is listed twice", peer.id)));
+        }
+        if let Some(blocked) = peer.policies.block_message_types.iter().find(|t| !t.is_relayed()) {
+            return Err(Error::Protocol(format!(
+                "peer {}: {} is not relayed between peers and cannot be blocked",
+                peer.id, blocked
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Apply a peering document: add the peers the node lacks and bring the
+/// others to the document's settings; with `prune`, also remove the peers
+/// it does not list
+///
+/// The whole document is checked before anything changes.
+pub async fn import_peering(state: &AppState, document: PeeringDocument, prune: bool) -> Result<PeeringImportReport> {
+    validate(&document)?;
+    let node_id = state.config.get().node.id.clone();
+    let mut report = PeeringImportReport {
+        source_node_id: document.node_id.clone(),
+        ..Default::default()
+    };
+    let mut sessions = Vec::new();
+    let mut peers = state.peers.write().await;
+
+    if prune {
+        let listed: HashSet<&str> = document.peers.iter().map(|p| p.id.as_str()).collect();
+        let unlisted: Vec<String> = peers
+            .list_peers()
+            .iter()
+            .filter(|p| !listed.contains(p.id.as_str()))
+            .map(|p| p.id.clone())
+            .collect();
+        for id in unlisted {
+            peers.remove_peer(&id);
+            state.fanout.remove_peer(&id);
+            if let Err(e) = state.storage.remove_peer_policies(&id).await {
+                warn!("Could not remove policies kept for peer {}: {}", id, e);
+            }
+            report.removed.push(id);
+        }
+    }
+
+    for peer in document.peers {
+        if peer.id == node_id {
+            report.skipped.push(peer.id);
+            continue;
+        }
+        // Stored first, so the peer never runs on policies a restart would lose
+        let Some(info) = peers.get_peer_mut(&peer.id) else {
+            state.storage.store_peer_policies(&peer.id, &peer.policies).await?;
+            peers.add_peer(PeerInfo::from_config(&peer));
+            sessions.push(peer.id.clone());
+            report.added.push(peer.id);
+            continue;
+        };
+
+        let auth_token = peer.auth_token.clone().or_else(|| info.auth_token.clone());
+        let reconnect = info.address != peer.address
+            || info.transport != peer.transport
+            || info.encoding != peer.encoding
+            || info.timestamp_format != peer.timestamp_format
+            || info.auth_token != auth_token;
+        let policies_changed = info.policies != peer.policies;
+        if !reconnect && !policies_changed {
+            report.unchanged.push(peer.id);
+            continue;
+        }
+        if policies_changed {
+            state.storage.store_peer_policies(&peer.id, &peer.policies).await?;
+        }
+        info.address = peer.address;
+        info.transport = peer.transport;
+        info.encoding = peer.encoding;
+        info.timestamp_format = peer.timestamp_format;
+        info.auth_token = auth_token;
+        info.policies = peer.policies;
+        // Policy changes apply in place; anything else needs a new session
+        if reconnect && info.status != PeerStatus::Blocked {
+            info.status = PeerStatus::Disconnected;
+            peers.drop_link(&peer.id);
+            state.fanout.remove_peer(&peer.id);
+        }
+        report.updated.push(peer.id);
+    }
+    drop(peers);
+
+    for peer_id in sessions {
+        spawn_session(state.clone(), peer_id);
+    }
+    info!(
+        "Peering from {} imported: {} added, {} updated, {} unchanged, {} removed",
+        report.source_node_id,
+        report.added.len(),
+        report.updated.len(),
+        report.unchanged.len(),
+        report.removed.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+
+    fn peer(yaml: &str) -> PeerConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_peering_round_trip() {
+        let source = test_state("node-a");
+        {
+            let mut peers = source.peers.write().await;
+            peers.add_peer(PeerInfo::from_config(&peer(
+                "{ id: node-x, address: 'http://x', auth_token: secret, policies: { forward_cdm: false } }",
+            )));
+            peers.add_peer(PeerInfo::from_config(&peer("{ id: node-b, address: 'http://b' }")));
+        }
+        let document = export_peering(&source, false).await;
+        assert_eq!(document.peers.len(), 2);
+        assert!(!serde_json::to_string(&document).unwrap().contains("secret"));
+        assert!(serde_yaml::to_string(&export_peering(&source, true).await).unwrap().contains("secret"));
+
+        // The redundant node skips itself and keeps the token it has
+        let target = test_state("node-b");
+        target.peers.write().await.add_peer(PeerInfo::from_config(&peer(
+            "{ id: node-x, address: 'http://x', auth_token: kept }",
+        )));
+        target.peers.write().await.add_peer(PeerInfo::from_config(&peer("{ id: node-old, address: 'http://old' }")));
+        let yaml = serde_yaml::to_string(&document).unwrap();
+        let report = import_peering(&target, serde_yaml::from_str(&yaml).unwrap(), false).await.unwrap();
+        assert_eq!((report.updated, report.skipped), (vec!["node-x".to_string()], vec!["node-b".to_string()]));
+        let peers = target.peers.read().await;
+        let imported = peers.get_peer("node-x").unwrap();
+        assert!(!imported.policies.forward_cdm);
+        assert_eq!(imported.auth_token.as_deref(), Some("kept"));
+        drop(peers);
+        let kept = target.storage.list_peer_policies().await.unwrap();
+        assert!(kept.iter().any(|(id, policies)| id == "node-x" && !policies.forward_cdm));
+
+        let report = import_peering(&target, document.clone(), true).await.unwrap();
+        assert_eq!((report.unchanged, report.removed), (vec!["node-x".to_string()], vec!["node-old".to_string()]));
+        assert!(target.peers.read().await.get_peer("node-old").is_none());
+
+        let mut twice = document.clone();
+        twice.peers.push(twice.peers[0].clone());
+        assert!(import_peering(&target, twice, false).await.is_err());
+        let mut newer = document;
+        newer.version = PEERING_VERSION + 1;
+        assert!(import_peering(&target, newer, false).await.is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Timed playback of recorded traffic into a running node
+//!
+//! `spacecomms simulate` sends a message log, in the format `replay` reads,
+//! to a node's protocol endpoint as the peers that sent it. The recorded
+//! gaps between envelope timestamps are kept, or scaled by a speed factor,
+//! so a debris-event surge can be rehearsed against a staging node and its
+//! routing policies watched under load before going live.
+//!
+//! Envelopes are sent with a fresh message ID and timestamp and without a
+//! link sequence number, so the node's replay protection takes them as live
+//! traffic; payloads go as recorded. Each sender greets the node with HELLO
+//! before its first message.
+
+use crate::node::{RecordedMessage, NODE_ID_HEADER, PROTOCOL_ENDPOINT};
+use crate::protocol::{Encoding, Envelope, ErrorCode, ErrorPayload, HelloPayload, MessageType};
+use crate::{Error, Result};
+use chrono::Utc;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Rejections listed in a report; later ones are only counted
+const MAX_LISTED_REJECTIONS: usize = 100;
+
+/// How to play a log back
+#[derive(Debug, Clone)]
+pub struct PlaybackOptions {
+    /// Playback rate relative to the recording: 2.0 halves every gap, 0.0
+    /// sends without pausing
+    pub speed: f64,
+    /// Longest pause between two messages, after scaling
+    pub max_gap: Option<Duration>,
+    /// Send every message as this peer instead of its recorded sender
+    pub as_peer: Option<String>,
+    /// Bearer token for requests, also presented in HELLO
+    pub auth_token: Option<String>,
+    pub encoding: Encoding,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            max_gap: None,
+            as_peer: None,
+            auth_token: None,
+            encoding: Encoding::Json,
+        }
+    }
+}
+
+/// A message the node refused
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackRejection {
+    /// Position in the log, from 0
+    pub index: usize,
+    pub message_type: MessageType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    pub reason: String,
+}
+
+/// Outcome of a playback
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlaybackReport {
+    pub target: String,
+    /// Messages from the log sent, not counting greetings
+    pub sent: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    /// The first rejections, in log order
+    pub rejections: Vec<PlaybackRejection>,
+    pub elapsed_ms: u64,
+    /// Slowest response from the node
+    pub max_latency_ms: u64,
+    pub mean_latency_ms: f64,
+    /// Furthest playback fell behind the recorded schedule, because the
+    /// node answered slower than messages were due
+    pub max_lag_ms: u64,
+}
+
+impl PlaybackReport {
+    pub fn success(&self) -> bool {
+        self.rejected == 0
+    }
+}
+
+/// When each message falls due after playback starts, from the recorded
+/// timestamps
+fn schedule(messages: &[RecordedMessage], options: &PlaybackOptions) -> Vec<Duration> {
+    let mut offset = Duration::ZERO;
+    let mut offsets = Vec::with_capacity(messages.len());
+    for (index, message) in messages.iter().enumerate() {
+        if index > 0 && options.speed > 0.0 {
+            let recorded = message.envelope.timestamp - messages[index - 1].envelope.timestamp;
+            let seconds = recorded.num_milliseconds().max(0) as f64 / 1000.0 / options.speed;
+            let gap = Duration::from_secs_f64(seconds);
+            offset += options.max_gap.map_or(gap, |max| gap.min(max));
+        }
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Plays a message log into a node
+pub struct Playback {
+    client: reqwest::Client,
+    target: String,
+    endpoint: String,
+    options: PlaybackOptions,
+}
+
+impl Playback {
+    /// Playback into the node at a base address
+    pub fn new(target: &str, options: PlaybackOptions) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            target: target.to_string(),
+            endpoint: format!("{}{}", target.trim_end_matches('/'), PROTOCOL_ENDPOINT),
+            options,
+        }
+    }
+
+    /// Send every message on its schedule and report how the node answered
+    pub async fn run(&self, messages: &[RecordedMessage]) -> PlaybackReport {
+        let mut report = PlaybackReport {
+            target: self.target.clone(),
+            ..Default::default()
+        };
+        let offsets = schedule(messages, &self.options);
+        let mut greeted = HashSet::new();
+        let mut total_latency = Duration::ZERO;
+        let started = Instant::now();
+        for (index, (message, offset)) in messages.iter().zip(offsets).enumerate() {
+            let due = started + offset;
+            let now = Instant::now();
+            if due > now {
+                tokio::time::sleep(due - now).await;
+            } else {
+                report.max_lag_ms = report.max_lag_ms.max((now - due).as_millis() as u64);
+            }
+
+            let sender = self
+                .options
+                .as_peer
+                .as_deref()
+                .or(message.from.as_deref())
+                .unwrap_or(&message.envelope.source_node_id)
+                .to_string();
+            if message.envelope.message_type != MessageType::Hello && greeted.insert(sender.clone()) {
+                if let Err(e) = self.greet(&sender).await {
+                    warn!("HELLO as {} failed: {}", sender, e);
+                }
+            }
+
+            let mut envelope = message.envelope.clone();
+            envelope.message_id = uuid::Uuid::new_v4().to_string();
+            envelope.timestamp = Utc::now();
+            envelope.sequence = None;
+            if self.options.as_peer.is_some() {
+                envelope.source_node_id = sender.clone();
+            }
+            let sent_at = Instant::now();
+            let outcome = self.send(&sender, &envelope).await;
+            let latency = sent_at.elapsed();
+            total_latency += latency;
+            report.max_latency_ms = report.max_latency_ms.max(latency.as_millis() as u64);
+            report.sent += 1;
+            match outcome {
+                Ok(()) => report.accepted += 1,
+                Err(mut rejection) => {
+                    debug!("Message {} rejected: {}", index, rejection.reason);
+                    report.rejected += 1;
+                    if report.rejections.len() < MAX_LISTED_REJECTIONS {
+                        rejection.index = index;
+                        rejection.message_type = envelope.message_type.clone();
+                        report.rejections.push(rejection);
+                    }
+                }
+            }
+        }
+        report.elapsed_ms = started.elapsed().as_millis() as u64;
+        if report.sent > 0 {
+            report.mean_latency_ms = total_latency.as_secs_f64() * 1000.0 / report.sent as f64;
+        }
+        info!(
+            "Played {} messages into {} in {} ms: {} accepted, {} rejected",
+            report.sent, self.target, report.elapsed_ms, report.accepted, report.rejected
+        );
+        report
+    }
+
+    /// HELLO as a recorded sender, so the node knows its session
+    async fn greet(&self, sender: &str) -> Result<()> {
+        let hello = HelloPayload {
+            node_name: format!("{} (playback)", sender),
+            auth_token: self.options.auth_token.clone(),
+            ..Default::default()
+        };
+        let envelope = Envelope::new(sender.to_string(), MessageType::Hello, serde_json::to_value(hello)?);
+        self.send(sender, &envelope)
+            .await
+            .map_err(|rejection| Error::Peer(rejection.reason))
+    }
+
+    async fn send(&self, sender: &str, envelope: &Envelope) -> std::result::Result<(), PlaybackRejection> {
+        let rejection = |status: Option<u16>, error_code: Option<ErrorCode>, reason: String| PlaybackRejection {
+            index: 0,
+            message_type: envelope.message_type.clone(),
+            status,
+            error_code,
+            reason,
+        };
+        let encoding = self.options.encoding;
+        let body = envelope
+            .encode(encoding)
+            .map_err(|e| rejection(None, None, format!("could not encode envelope: {}", e)))?;
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(NODE_ID_HEADER, sender)
+            .header(CONTENT_TYPE, encoding.content_type())
+            .body(body);
+        if let Some(token) = &self.options.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| rejection(None, None, format!("request failed: {}", e)))?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let encoding = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_content_type)
+            .unwrap_or(Encoding::Json);
+        let body = resp.bytes().await.unwrap_or_default();
+        let error = Envelope::decode(&body, encoding)
+            .ok()
+            .and_then(|reply| reply.payload.parse::<ErrorPayload>().ok());
+        Err(match error {
+            Some(error) => rejection(Some(status.as_u16()), Some(error.error_code), error.error_message),
+            None => rejection(Some(status.as_u16()), None, format!("HTTP {}", status)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::receive_message;
+    use crate::node::server::tests::test_state;
+    use crate::protocol::CdmWithdrawPayload;
+    use axum::routing::post;
+    use axum::Router;
+
+    fn recorded(from: &str, message_type: MessageType, payload: serde_json::Value, after_ms: i64) -> RecordedMessage {
+        let mut envelope = Envelope::new(from.to_string(), message_type, payload);
+        envelope.timestamp = "2024-01-15T12:00:00Z".parse::<chrono::DateTime<Utc>>().unwrap()
+            + chrono::Duration::milliseconds(after_ms);
+        envelope.sequence = Some(7);
+        RecordedMessage { from: None, envelope }
+    }
+
+    #[tokio::test]
+    async fn test_playback_into_node() {
+        let state = test_state("node-a");
+        for id in ["partner", "other"] {
+            let peer: crate::config::PeerConfig =
+                serde_yaml::from_str(&format!("{{ id: {}, address: 'http://127.0.0.1:9' }}", id)).unwrap();
+            state.peers.write().await.add_peer(crate::node::PeerInfo::from_config(&peer));
+        }
+        let app = Router::new()
+            .route(PROTOCOL_ENDPOINT, post(receive_message))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let cdm = generate_demo_cdm();
+        let withdraw = CdmWithdrawPayload {
+            cdm_id: cdm.cdm_id.clone(),
+            reason: crate::protocol::CdmWithdrawReason::TcaPassed,
+            superseded_by: None,
+            effective_time: Utc::now(),
+        };
+        let messages = vec![
+            recorded("partner", MessageType::CdmAnnounce, serde_json::to_value(&cdm).unwrap(), 0),
+            recorded("partner", MessageType::CdmAnnounce, serde_json::json!({ "cdm_id": "CDM-BAD" }), 400),
+            recorded("other", MessageType::CdmWithdraw, serde_json::to_value(&withdraw).unwrap(), 800),
+        ];
+
+        // Recorded 800 ms apart end to end, played four times as fast
+        let options = PlaybackOptions {
+            speed: 4.0,
+            ..Default::default()
+        };
+        assert_eq!(schedule(&messages, &options)[2], Duration::from_millis(200));
+        let capped = PlaybackOptions {
+            max_gap: Some(Duration::from_millis(50)),
+            ..options.clone()
+        };
+        assert_eq!(schedule(&messages, &capped)[2], Duration::from_millis(100));
+
+        let report = Playback::new(&target, options).run(&messages).await;
+        assert!(report.elapsed_ms >= 200);
+        assert_eq!((report.sent, report.accepted, report.rejected), (3, 2, 1));
+        assert_eq!(report.rejections[0].index, 1);
+        assert_eq!(report.rejections[0].error_code, Some(ErrorCode::InvalidMessage));
+        assert!(!report.success());
+
+        // Each sender said HELLO, and the CDM came and went
+        let peers = state.peers.read().await;
+        for id in ["partner", "other"] {
+            assert!(peers.session(id).unwrap().protocol_version.is_some());
+        }
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_none());
+        assert_eq!(state.events.head(), 2);
+    }
+}
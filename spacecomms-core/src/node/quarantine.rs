@@ -0,0 +1,171 @@
+//! Quarantine of suspicious CDMs
+//!
+//! A CDM failing a validation rule with action `quarantine`, such as one
+//! with a stale `creation_date`, a poor quality score or an unknown
+//! originator, is neither accepted nor rejected. It is held here instead of
+//! being stored, and is not announced to watchers or passed on to peers.
+//!
+//! Admins list held CDMs with `GET /cdms/quarantine`, then release each one,
+//! which stores and propagates it as if just taken in, or discard it. A CDM
+//! quarantined again under the same ID replaces the one held. The quarantine
+//! is held in memory and keeps the most recent [`MAX_QUARANTINED`].
+
+use crate::cdm::{CdmRecord, RuleAction, RuleViolation};
+use crate::protocol::Envelope;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// CDMs held; the oldest are dropped beyond this
+pub const MAX_QUARANTINED: usize = 1000;
+
+/// A CDM held for an admin's decision
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuarantinedCdm {
+    pub cdm: CdmRecord,
+    /// Rules the CDM failed with action `quarantine`
+    pub violations: Vec<RuleViolation>,
+    /// Peer the CDM came from; unset when ingested through the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
+    pub quarantined_at: DateTime<Utc>,
+    /// The announcement as received or generated, passed on at release
+    #[serde(skip)]
+    pub(crate) envelope: Option<Envelope>,
+}
+
+impl QuarantinedCdm {
+    /// Hold `cdm` if it failed a quarantining rule, along with the peer and
+    /// announcement it came in, if any
+    pub(crate) fn for_violations(
+        cdm: &CdmRecord,
+        violations: &[RuleViolation],
+        peer_id: Option<&str>,
+        envelope: Option<&Envelope>,
+    ) -> Option<Self> {
+        let violations: Vec<RuleViolation> = violations
+            .iter()
+            .filter(|violation| violation.action == RuleAction::Quarantine)
+            .cloned()
+            .collect();
+        if violations.is_empty() {
+            return None;
+        }
+        Some(Self {
+            cdm: cdm.clone(),
+            violations,
+            peer_id: peer_id.map(str::to_string),
+            quarantined_at: Utc::now(),
+            envelope: envelope.cloned(),
+        })
+    }
+
+    /// Rules failed, for the log
+    pub fn reasons(&self) -> String {
+        let reasons: Vec<String> = self.violations.iter().map(|v| format!("{}: {}", v.rule, v.message)).collect();
+        reasons.join("; ")
+    }
+}
+
+/// Bounded, in-memory quarantine, oldest first
+#[derive(Default)]
+pub struct Quarantine {
+    cdms: Mutex<VecDeque<QuarantinedCdm>>,
+    evicted: AtomicU64,
+}
+
+impl Quarantine {
+    fn cdms(&self) -> MutexGuard<'_, VecDeque<QuarantinedCdm>> {
+        self.cdms.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Hold a CDM, replacing any held under its ID
+    pub fn hold(&self, held: QuarantinedCdm) {
+        let mut cdms = self.cdms();
+        cdms.retain(|cdm| cdm.cdm.cdm_id != held.cdm.cdm_id);
+        cdms.push_back(held);
+        while cdms.len() > MAX_QUARANTINED {
+            if let Some(dropped) = cdms.pop_front() {
+                warn!("Quarantine full; dropped CDM {}", dropped.cdm.cdm_id);
+            }
+            self.evicted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Held CDMs, newest first
+    pub fn list(&self) -> Vec<QuarantinedCdm> {
+        self.cdms().iter().rev().cloned().collect()
+    }
+
+    /// Remove a held CDM, to release or discard it
+    pub fn take(&self, cdm_id: &str) -> Option<QuarantinedCdm> {
+        let mut cdms = self.cdms();
+        let index = cdms.iter().position(|cdm| cdm.cdm.cdm_id == cdm_id)?;
+        cdms.remove(index)
+    }
+
+    /// CDMs held now
+    pub fn len(&self) -> usize {
+        self.cdms().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cdms().is_empty()
+    }
+
+    /// CDMs dropped to stay within the limit
+    pub fn evicted(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn violation(action: RuleAction) -> RuleViolation {
+        RuleViolation {
+            rule: "fresh".into(),
+            field: "age_seconds".into(),
+            action,
+            message: "age_seconds is 90000, not lte 86400".into(),
+        }
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let cdm = generate_demo_cdm();
+        assert!(QuarantinedCdm::for_violations(&cdm, &[violation(RuleAction::Warn)], None, None).is_none());
+        let held = QuarantinedCdm::for_violations(
+            &cdm,
+            &[violation(RuleAction::Warn), violation(RuleAction::Quarantine)],
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(held.violations.len(), 1);
+        assert!(held.peer_id.is_none());
+
+        let quarantine = Quarantine::default();
+        quarantine.hold(held.clone());
+        quarantine.hold(held);
+        assert_eq!(quarantine.len(), 1);
+        for _ in 0..MAX_QUARANTINED {
+            let other = generate_demo_cdm();
+            let mut other = QuarantinedCdm::for_violations(&other, &[violation(RuleAction::Quarantine)], None, None).unwrap();
+            other.cdm.cdm_id = uuid::Uuid::new_v4().to_string();
+            quarantine.hold(other);
+        }
+        assert_eq!((quarantine.len(), quarantine.evicted()), (MAX_QUARANTINED, 1));
+        assert!(quarantine.take(&cdm.cdm_id).is_none());
+
+        let newest = quarantine.list()[0].cdm.cdm_id.clone();
+        assert!(quarantine.take(&newest).is_some());
+        assert!(quarantine.take(&newest).is_none());
+    }
+}
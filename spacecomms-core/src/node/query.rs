@@ -0,0 +1,305 @@
+//! Pulling CDMs from peers
+//!
+//! Besides push propagation, a node can ask a peer for the CDMs it holds
+//! that match a filter by sending CDM_REQUEST. The peer answers with a
+//! CDM_RESPONSE carrying the matching active CDMs, ordered by TCA and capped
+//! at its `protocol.max_query_results`. Queries always travel over HTTP, so
+//! the response comes back as the reply whichever transport the session
+//! uses, and the responder applies its redaction policy for the requester.
+//! Pulled CDMs are stored as if announced but are not relayed: the
+//! pull model is for catching up after an outage and for leaf nodes that
+//! only want CDMs about their own assets.
+
+use crate::cdm::{canonicalize_cdm, check_quality_floor, check_rules, classify, parse_cdm, CdmRecord};
+use crate::config::PeerPolicies;
+use crate::node::{cdm_organization, redact_cdm, strip_local_fields, AppState, HttpTransport, PeerStatus, Transport};
+use crate::protocol::{
+    CdmQuery, CdmRequestPayload, CdmResponsePayload, Encoding, Envelope, MessageType, CAPABILITY_CDM_QUERY,
+    CAPABILITY_ENCODING_CBOR,
+};
+use crate::{Error, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use tracing::{debug, info};
+use utoipa::ToSchema;
+
+/// Outcome of pulling CDMs from a peer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CdmQueryReport {
+    pub peer_id: String,
+    pub request_id: String,
+    /// CDMs in the response
+    pub received: usize,
+    /// New or newer than the version held here
+    pub stored: usize,
+    /// Already held at the same or a newer version
+    pub skipped: usize,
+    /// Failed validation
+    pub rejected: usize,
+    /// The peer had more matches than it returned
+    pub truncated: bool,
+}
+
+/// Whether a CDM passes a query's filters
+pub fn query_matches(query: &CdmQuery, cdm: &CdmRecord) -> bool {
+    (query.cdm_ids.is_empty() || query.cdm_ids.contains(&cdm.cdm_id))
+        && (query.object_ids.is_empty()
+        || query
+            .object_ids
+            .iter()
+            .any(|id| *id == cdm.object1.object_id || *id == cdm.object2.object_id))
+        && query.tca_from.is_none_or(|from| cdm.tca >= from)
+        && query.tca_to.is_none_or(|to| cdm.tca < to)
+}
+
+/// Policies of a peer this node answers queries from
+pub(crate) async fn query_policies(state: &AppState, sender: &str) -> Result<PeerPolicies> {
+    let policies = state.peers.read().await.get_peer(sender).map(|peer| peer.policies.clone());
+    policies
+        .filter(|p| p.serve_cdm_queries)
+        .ok_or_else(|| Error::Unauthorized(format!("CDM queries not served to {}", sender)))
+}
+
+/// Answer a peer's CDM_REQUEST with a CDM_RESPONSE
+pub(crate) async fn answer_cdm_request(state: &AppState, envelope: &Envelope, sender: &str) -> Result<Envelope> {
+    let policies = query_policies(state, sender).await?;
+
+    let request: CdmRequestPayload = envelope.payload.parse()?;
+    let config = state.config.get();
+    let limit = request
+        .query
+        .limit
+        .unwrap_or(usize::MAX)
+        .min(config.protocol.max_query_results);
+    let mut matching: Vec<CdmRecord> = state
+        .storage
+        .list_cdms()
+        .await?
+        .into_iter()
+        .filter(|cdm| query_matches(&request.query, cdm))
+        .collect();
+    matching.sort_by(|a, b| a.tca.cmp(&b.tca).then_with(|| a.cdm_id.cmp(&b.cdm_id)));
+    let truncated = matching.len() > limit;
+    matching.truncate(limit);
+    debug!("CDM query {} from {} matched {} CDMs", request.request_id, sender, matching.len());
+
+    let mut cdms: Vec<serde_json::Value> = matching.iter().map(serde_json::to_value).collect::<std::result::Result<_, _>>()?;
+    for cdm in &mut cdms {
+        strip_local_fields(cdm);
+        redact_cdm(cdm, &policies.redact);
+    }
+    let response = CdmResponsePayload {
+        request_id: request.request_id,
+        cdms,
+        truncated,
+    };
+    Ok(Envelope::new(
+        config.node.id.clone(),
+        MessageType::CdmResponse,
+        serde_json::to_value(response)?,
+    ))
+}
+
+/// Ask a connected peer for CDMs matching `query` and store the results
+///
+/// Fails with [`Error::NotFound`] for an unknown peer, [`Error::Protocol`]
+/// when the peer is not connected or does not offer CDM queries, and
+/// [`Error::PeerRefused`] or [`Error::Peer`] when the peer refuses the query
+/// or answers badly.
+pub async fn query_peer(state: &AppState, peer_id: &str, query: CdmQuery) -> Result<CdmQueryReport> {
+    let transport = reply_transport(state, peer_id, CAPABILITY_CDM_QUERY).await?;
+    let request = CdmRequestPayload {
+        request_id: uuid::Uuid::new_v4().to_string(),
+        query,
+    };
+    let envelope = Envelope::new(
+        state.config.get().node.id.clone(),
+        MessageType::CdmRequest,
+        serde_json::to_value(&request)?,
+    );
+    state.peers.write().await.record_sent(peer_id, &MessageType::CdmRequest);
+    let reply = transport
+        .send(&envelope)
+        .await?
+        .ok_or_else(|| Error::Peer(format!("{} did not answer CDM_REQUEST", peer_id)))?;
+    if reply.message_type != MessageType::CdmResponse {
+        return Err(Error::Peer(format!("{} answered CDM_REQUEST with {}", peer_id, reply.message_type)));
+    }
+    state.peers.write().await.record_received(peer_id, &MessageType::CdmResponse);
+    let response: CdmResponsePayload = reply.payload.parse()?;
+    if response.request_id != request.request_id {
+        return Err(Error::Peer(format!(
+            "{} answered request {} instead of {}",
+            peer_id, response.request_id, request.request_id
+        )));
+    }
+
+    let mut report = CdmQueryReport {
+        peer_id: peer_id.to_string(),
+        request_id: request.request_id,
+        received: response.cdms.len(),
+        truncated: response.truncated,
+        ..Default::default()
+    };
+    for value in response.cdms {
+        let config = state.config.get();
+        let parsed = parse_cdm(value).and_then(|mut cdm| {
+            canonicalize_cdm(&mut cdm, &config.identifiers.aliases);
+            check_quality_floor(&cdm, config.protocol.min_data_quality)?;
+            for warning in check_rules(&cdm, &config.validation.rules, Utc::now())? {
+                debug!("Pulled CDM {} from {} failed rule {}: {}", cdm.cdm_id, peer_id, warning.rule, warning.message);
+            }
+            Ok(cdm)
+        });
+        let mut cdm = match parsed {
+            Ok(cdm) => cdm,
+            Err(e) => {
+                debug!("Pulled CDM from {} rejected: {}", peer_id, e);
+                report.rejected += 1;
+                continue;
+            }
+        };
+        if let Some(catalog) = &state.catalog {
+            catalog.enrich_cdm(&mut cdm).await;
+        }
+        classify(&mut cdm, &config.protocol.severity);
+        cdm.organization = cdm_organization(&config.api, &cdm);
+        cdm.involves_watched_asset = state.watchlist.involves(&cdm);
+        if state.storage.upsert_cdm_if_newer(cdm.clone()).await?.written() {
+            state.events.announced(&cdm);
+            state.metrics.cdms_announced.fetch_add(1, Ordering::Relaxed);
+            report.stored += 1;
+        } else {
+            report.skipped += 1;
+        }
+    }
+    info!(
+        "Pulled {} CDMs from {} ({} stored, {} already held, {} rejected)",
+        report.received, peer_id, report.stored, report.skipped, report.rejected
+    );
+    Ok(report)
+}
+
+/// An HTTP transport to a connected peer offering `capability`, for
+/// exchanges answered in the reply whichever transport the session uses
+pub(crate) async fn reply_transport(state: &AppState, peer_id: &str, capability: &str) -> Result<HttpTransport> {
+    let timestamp_format = state.timestamp_format_for(Some(peer_id)).await;
+    let peers = state.peers.read().await;
+    let peer = peers
+        .get_peer(peer_id)
+        .ok_or_else(|| Error::NotFound(format!("Peer not found: {}", peer_id)))?;
+    if peer.status != PeerStatus::Connected {
+        return Err(Error::Protocol(format!("peer {} is not connected", peer_id)));
+    }
+    let capabilities = peers.session(peer_id).unwrap_or_default().capabilities;
+    if !capabilities.iter().any(|c| c == capability) {
+        return Err(Error::Protocol(format!("peer {} does not offer {}", peer_id, capability)));
+    }
+    let encoding = match peer.encoding {
+        Encoding::Cbor if capabilities.iter().any(|c| c == CAPABILITY_ENCODING_CBOR) => Encoding::Cbor,
+        _ => Encoding::Json,
+    };
+    Ok(HttpTransport::new(&peer.address, &state.config.get().node.id, peer.auth_token.clone())
+        .with_encoding(encoding)
+        .with_timestamp_format(timestamp_format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::config::PeerConfig;
+    use crate::node::server::receive_message;
+    use crate::node::server::tests::test_state;
+    use crate::node::{PeerInfo, PROTOCOL_ENDPOINT};
+    use crate::protocol::HelloPayload;
+    use chrono::Duration;
+
+    fn peer(id: &str, address: &str) -> PeerInfo {
+        let config: PeerConfig = serde_yaml::from_str(&format!("id: {}\naddress: {}", id, address)).unwrap();
+        PeerInfo::from_config(&config)
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let cdm = generate_demo_cdm();
+        assert!(query_matches(&CdmQuery::default(), &cdm));
+        let involving = |id: &str| CdmQuery {
+            object_ids: vec!["OTHER".into(), id.to_string()],
+            ..Default::default()
+        };
+        assert!(query_matches(&involving(&cdm.object2.object_id), &cdm));
+        assert!(!query_matches(&involving("NORAD-0"), &cdm));
+
+        let window = CdmQuery {
+            tca_from: Some(cdm.tca - Duration::hours(1)),
+            tca_to: Some(cdm.tca),
+            ..Default::default()
+        };
+        // The window excludes its end
+        assert!(!query_matches(&window, &cdm));
+        let window = CdmQuery {
+            tca_to: Some(cdm.tca + Duration::seconds(1)),
+            ..window
+        };
+        assert!(query_matches(&window, &cdm));
+    }
+
+    #[tokio::test]
+    async fn test_pull_from_peer() {
+        // Node B holds three CDMs, one of them about another object
+        let remote = test_state("node-b");
+        remote.peers.write().await.add_peer(peer("node-a", "http://127.0.0.1:1"));
+        let first = generate_demo_cdm();
+        let mut second = first.clone();
+        second.cdm_id = "CDM-SECOND".into();
+        second.tca = first.tca + Duration::hours(2);
+        let mut unrelated = first.clone();
+        unrelated.cdm_id = "CDM-UNRELATED".into();
+        unrelated.object1.object_id = "NORAD-11111".into();
+        unrelated.object2.object_id = "NORAD-22222".into();
+        for cdm in [first.clone(), second.clone(), unrelated] {
+            remote.storage.store_cdm(cdm).await.unwrap();
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new()
+            .route(PROTOCOL_ENDPOINT, axum::routing::post(receive_message))
+            .with_state(remote.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // Node A is connected to B, which advertised CDM_QUERY
+        let local = test_state("node-a");
+        {
+            let mut peers = local.peers.write().await;
+            peers.add_peer(peer("node-b", &address));
+            peers.set_peer_status("node-b", PeerStatus::Connected);
+            peers.record_handshake("node-b", "1.0".into(), HelloPayload::default().capabilities);
+        }
+        let query = CdmQuery {
+            object_ids: vec![first.object1.object_id.clone()],
+            limit: Some(1),
+            ..Default::default()
+        };
+        let report = query_peer(&local, "node-b", query.clone()).await.unwrap();
+        assert_eq!((report.received, report.stored, report.truncated), (1, 1, true));
+        // Ordered by TCA: the earlier conjunction comes first
+        assert!(local.storage.get_cdm(&first.cdm_id).await.unwrap().is_some());
+        assert_eq!(local.events.page(0).events.len(), 1);
+
+        let query = CdmQuery { limit: None, ..query };
+        let report = query_peer(&local, "node-b", query.clone()).await.unwrap();
+        assert_eq!((report.received, report.stored, report.skipped), (2, 1, 1));
+        assert!(!report.truncated);
+        assert_eq!(local.storage.cdm_count().await.unwrap(), 2);
+
+        // B stops serving A
+        remote.peers.write().await.get_peer_mut("node-a").unwrap().policies.serve_cdm_queries = false;
+        assert!(matches!(query_peer(&local, "node-b", query.clone()).await, Err(Error::PeerRefused(_))));
+
+        assert!(query_peer(&local, "node-c", query.clone()).await.unwrap_err().is_not_found());
+        local.peers.write().await.set_peer_status("node-b", PeerStatus::Disconnected);
+        assert!(matches!(query_peer(&local, "node-b", query).await, Err(Error::Protocol(_))));
+    }
+}
@@ -0,0 +1,68 @@
+//! Refusal of writes on read-only nodes
+//!
+//! A node with `node.mode: read-only` serves queries and streams but must
+//! never put data into the mesh. Every request other than `GET` is refused
+//! with `403 read_only`, including peer protocol messages, except for the
+//! node's own bookkeeping: tokens, the watchlist, alerts and reloads, and
+//! routing simulations, which change nothing. New write endpoints are
+//! therefore refused unless added here.
+
+use crate::config::NodeMode;
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use super::server::AppState;
+
+/// Paths a read-only node still takes writes on; none reach storage or peers
+const LOCAL_WRITE_PREFIXES: [&str; 5] = ["/auth/", "/watchlist", "/alerts/", "/admin/", "/routing/simulate"];
+
+/// Whether a read-only node takes the request
+pub(crate) fn allowed_when_read_only(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || LOCAL_WRITE_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Middleware refusing ingestion on read-only nodes
+pub(crate) async fn refuse_writes(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if state.config.get().node.mode == NodeMode::ReadOnly
+        && !allowed_when_read_only(request.method(), request.uri().path())
+    {
+        let message = format!(
+            "{} {} is not available on a read-only node",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, Json(json!({ "error": "read_only", "message": message }))).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::PROTOCOL_ENDPOINT;
+
+    #[test]
+    fn test_allowed_when_read_only() {
+        assert!(allowed_when_read_only(&Method::GET, "/cdms"));
+        assert!(allowed_when_read_only(&Method::GET, "/events/cdms"));
+        assert!(allowed_when_read_only(&Method::POST, "/watchlist"));
+        assert!(allowed_when_read_only(&Method::POST, "/alerts/a-1/acknowledge"));
+        assert!(allowed_when_read_only(&Method::POST, "/auth/tokens"));
+        assert!(allowed_when_read_only(&Method::POST, "/routing/simulate"));
+        assert!(!allowed_when_read_only(&Method::POST, "/cdm"));
+        assert!(!allowed_when_read_only(&Method::POST, "/cdms/bulk"));
+        assert!(!allowed_when_read_only(&Method::DELETE, "/cdms/CDM-1"));
+        assert!(!allowed_when_read_only(&Method::POST, "/objects/omm"));
+        assert!(!allowed_when_read_only(&Method::POST, "/maneuvers"));
+        assert!(!allowed_when_read_only(&Method::POST, "/peers"));
+        assert!(!allowed_when_read_only(&Method::POST, "/import"));
+        assert!(!allowed_when_read_only(&Method::POST, PROTOCOL_ENDPOINT));
+    }
+}
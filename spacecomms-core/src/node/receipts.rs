@@ -0,0 +1,157 @@
+//! Delivery receipts back to a CDM's originator
+//!
+//! A node taking in a CDM_ANNOUNCE from a peer sends a DELIVERY_RECEIPT
+//! toward the CDM's originator when the CDM involves an object on its
+//! watchlist, so the originator learns the CDM reached an operator who
+//! cares about it. The receipt goes straight to the originator when the two
+//! are peers, otherwise to the node before this one in the CDM's
+//! provenance, and each node on the way passes it on the same way until the
+//! receipt's TTL, from `protocol.ttl`, runs out. Receipts only go to peers offering
+//! DELIVERY_RECEIPT; one with nowhere to go is dropped.
+//!
+//! The originator records the latest receipt from each receiving node on
+//! the CDM, for `GET /cdms/{id}/receipts`, up to
+//! [`MAX_RECEIPTS_PER_CDM`](crate::storage::MAX_RECEIPTS_PER_CDM) nodes and
+//! within the memory budget. Receipts are sent unless
+//! `protocol.delivery.receipts` is off.
+
+use crate::cdm::CdmRecord;
+use crate::node::{clamp_ttl, AppState};
+use crate::protocol::{DeliveryReceiptPayload, Envelope, MessageType, CAPABILITY_DELIVERY_RECEIPT};
+use crate::{Error, Result};
+use chrono::Utc;
+use std::sync::atomic::Ordering;
+use tracing::{debug, info, warn};
+
+/// Send a receipt for a CDM announcement taken in from a peer, if the CDM
+/// involves a watched object
+pub(crate) async fn send_receipt(state: &AppState, envelope: &Envelope) {
+    let config = state.config.get();
+    if !config.protocol.delivery.receipts || envelope.source_node_id == config.node.id {
+        return;
+    }
+    let Ok(cdm) = envelope.payload.parse::<CdmRecord>() else {
+        return;
+    };
+    let matched_object_ids: Vec<String> = [&cdm.object1.object_id, &cdm.object2.object_id]
+        .into_iter()
+        .filter(|id| state.watchlist.watches(id))
+        .cloned()
+        .collect();
+    if matched_object_ids.is_empty() {
+        return;
+    }
+    let receipt = DeliveryReceiptPayload {
+        cdm_id: cdm.cdm_id,
+        related_message_id: envelope.message_id.clone(),
+        originator_node_id: envelope.source_node_id.clone(),
+        receiving_node_id: config.node.id.clone(),
+        matched_object_ids,
+        hop_count: envelope.hop_count,
+        received_at: Utc::now(),
+    };
+    let payload = match serde_json::to_value(&receipt) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Could not encode the receipt for CDM {}: {}", receipt.cdm_id, e);
+            return;
+        }
+    };
+    let mut reply = Envelope::new(config.node.id.clone(), MessageType::DeliveryReceipt, payload);
+    reply.ttl = config.protocol.ttl.for_message_type(&MessageType::DeliveryReceipt);
+    if route_receipt(state, reply, &receipt, None).await {
+        state.metrics.receipts_sent.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Take in a receipt from a peer: record it on the CDM when this node
+/// originated the CDM, otherwise pass it on toward the originator
+pub(crate) async fn receive_receipt(state: &AppState, envelope: &Envelope, sender: &str) -> Result<()> {
+    let receipt: DeliveryReceiptPayload = envelope.payload.parse()?;
+    if receipt.receiving_node_id != envelope.source_node_id {
+        return Err(Error::Protocol(format!(
+            "receipt from {} names {} as the receiving node",
+            envelope.source_node_id, receipt.receiving_node_id
+        )));
+    }
+    if state.storage.has_seen_message(&envelope.message_id).await? {
+        debug!("Duplicate receipt {} from {}", envelope.message_id, sender);
+        return Ok(());
+    }
+    state.storage.mark_message_seen(&envelope.message_id).await?;
+
+    if receipt.originator_node_id != state.config.get().node.id {
+        match envelope.forwarded() {
+            Some(mut forwarded) => {
+                clamp_ttl(state, &*state.peers.read().await, &mut forwarded, sender);
+                route_receipt(state, forwarded, &receipt, Some(sender)).await;
+            }
+            None => debug!("Receipt for CDM {} dropped: TTL exhausted", receipt.cdm_id),
+        }
+        return Ok(());
+    }
+    let cdm_id = receipt.cdm_id.clone();
+    let receiving_node_id = receipt.receiving_node_id.clone();
+    match state.storage.store_delivery_receipt(&cdm_id, receipt).await {
+        Ok(()) => {
+            info!("CDM {} reached {}, which watches one of its objects", cdm_id, receiving_node_id);
+            state.metrics.receipts_received.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+        Err(e) if e.is_not_found() => {
+            debug!("Receipt from {} for CDM {} no longer held", receiving_node_id, cdm_id);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Send a receipt one hop toward its originator, never back to `sender`,
+/// returning false when no peer can take it
+async fn route_receipt(
+    state: &AppState,
+    envelope: Envelope,
+    receipt: &DeliveryReceiptPayload,
+    sender: Option<&str>,
+) -> bool {
+    if !state.leadership.is_leader() {
+        return false;
+    }
+    let node_id = state.config.get().node.id.clone();
+    let mut hops = vec![receipt.originator_node_id.clone()];
+    if let Ok(Some(chain)) = state.storage.get_cdm_provenance(&receipt.cdm_id).await {
+        let previous = chain.iter().rposition(|hop| hop.node_id == node_id).and_then(|i| i.checked_sub(1));
+        hops.extend(previous.map(|i| chain[i].node_id.clone()));
+    }
+
+    let peers = state.peers.read().await;
+    let next = hops.into_iter().filter(|id| Some(id.as_str()) != sender).find_map(|id| {
+        let offered = peers
+            .session(&id)
+            .is_some_and(|s| s.capabilities.iter().any(|c| c == CAPABILITY_DELIVERY_RECEIPT));
+        if !offered || peers.is_quarantined(&id) {
+            return None;
+        }
+        peers.link(&id).map(|link| (id, link))
+    });
+    drop(peers);
+    let Some((peer_id, link)) = next else {
+        debug!(
+            "Receipt for CDM {} dropped: no route to {}",
+            receipt.cdm_id, receipt.originator_node_id
+        );
+        return false;
+    };
+
+    let task_state = state.clone();
+    state.tasks.spawn(async move {
+        match link.send(&envelope).await {
+            Ok(_) => {
+                task_state.metrics.messages_sent.fetch_add(1, Ordering::Relaxed);
+                task_state.peers.write().await.record_sent(&peer_id, &MessageType::DeliveryReceipt);
+            }
+            Err(e) => warn!("Sending the receipt {} to {} failed: {}", envelope.message_id, peer_id, e),
+        }
+    });
+    true
+}
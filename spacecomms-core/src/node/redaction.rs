@@ -0,0 +1,104 @@
+//! Per-peer redaction of forwarded data
+//!
+//! A peer's `policies.redact` strips or coarsens commercially sensitive
+//! fields in the CDMs and object states sent to it: covariance is dropped,
+//! state vectors are rounded and owner/operator names are replaced. The
+//! forwarding pipeline and CDM query answers work on a copy per peer, so
+//! stored records and what other peers receive are unchanged.
+
+use crate::config::RedactionPolicy;
+use crate::protocol::{Envelope, MessageType};
+use serde_json::Value;
+
+/// Replacement for anonymized owner/operator names
+pub const REDACTED_OWNER: &str = "REDACTED";
+
+const POSITION_FIELDS: [&str; 3] = ["x_km", "y_km", "z_km"];
+const VELOCITY_FIELDS: [&str; 3] = ["vx_km_s", "vy_km_s", "vz_km_s"];
+
+/// Copy of `envelope` with the policy applied to its payload; other message
+/// types are returned unchanged
+pub fn redact_envelope(envelope: &Envelope, policy: &RedactionPolicy) -> Envelope {
+    let mut redacted = envelope.clone();
+    match envelope.message_type {
+        MessageType::CdmAnnounce => redact_cdm(redacted.payload.make_mut(), policy),
+        MessageType::ObjectStateAnnounce => redact_object(redacted.payload.make_mut(), policy, "covariance"),
+        _ => {}
+    }
+    redacted
+}
+
+/// Apply the policy to a CDM in its JSON form
+pub fn redact_cdm(cdm: &mut Value, policy: &RedactionPolicy) {
+    for side in ["object1", "object2"] {
+        if let Some(object) = cdm.get_mut(side) {
+            redact_object(object, policy, "covariance_rtm");
+        }
+    }
+}
+
+fn redact_object(object: &mut Value, policy: &RedactionPolicy, covariance_field: &str) {
+    let Some(object) = object.as_object_mut() else {
+        return;
+    };
+    if policy.drop_covariance {
+        object.remove(covariance_field);
+    }
+    if policy.anonymize_owner && object.get("owner_operator").is_some_and(|o| !o.is_null()) {
+        object.insert("owner_operator".to_string(), Value::from(REDACTED_OWNER));
+    }
+    if let Some(state_vector) = object.get_mut("state_vector") {
+        round_fields(state_vector, &POSITION_FIELDS, policy.position_resolution_km);
+        round_fields(state_vector, &VELOCITY_FIELDS, policy.velocity_resolution_km_s);
+    }
+}
+
+fn round_fields(state_vector: &mut Value, fields: &[&str], resolution: Option<f64>) {
+    let Some(resolution) = resolution else {
+        return;
+    };
+    for field in fields {
+        if let Some(value) = state_vector.get_mut(*field) {
+            if let Some(rounded) = value.as_f64().map(|v| (v / resolution).round() * resolution) {
+                *value = Value::from(rounded);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[test]
+    fn test_redact_cdm() {
+        let cdm = generate_demo_cdm();
+        let envelope = Envelope::new(
+            "node-a".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(&cdm).unwrap(),
+        );
+        assert_eq!(redact_envelope(&envelope, &RedactionPolicy::default()).payload, envelope.payload);
+
+        let policy = RedactionPolicy {
+            drop_covariance: true,
+            position_resolution_km: Some(10.0),
+            velocity_resolution_km_s: None,
+            anonymize_owner: true,
+        };
+        let redacted = redact_envelope(&envelope, &policy);
+        assert_eq!(redacted.message_id, envelope.message_id);
+        let object1 = &redacted.payload["object1"];
+        assert!(object1.get("covariance_rtm").is_none());
+        assert_eq!(object1["owner_operator"], REDACTED_OWNER);
+        let x = object1["state_vector"]["x_km"].as_f64().unwrap();
+        assert_eq!(x % 10.0, 0.0);
+        assert!((x - cdm.object1.state_vector.x_km).abs() <= 5.0);
+        assert_eq!(object1["state_vector"]["vx_km_s"], envelope.payload["object1"]["state_vector"]["vx_km_s"]);
+        // Still a valid CDM
+        assert!(crate::cdm::parse_cdm(redacted.payload.into_value()).is_ok());
+        // The original is untouched
+        assert!(envelope.payload["object1"].get("covariance_rtm").is_some());
+    }
+}
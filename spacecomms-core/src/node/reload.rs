@@ -0,0 +1,464 @@
+//! Configuration hot reload
+//!
+//! `SIGHUP` or `POST /admin/reload` re-reads the configuration file and
+//! applies the settings that can change in place: peers, the logging level,
+//! protocol limits, interests and object catalog limits. Unchanged peers
+//! keep their sessions and stored data is untouched. Settings that need a restart keep
+//! their running values and are listed in the [`ReloadReport`].
+
+use crate::config::{Config, ConfigOverride, PeerConfig, PeerPolicies};
+use crate::node::{advertise_interests, spawn_session, AppState, PeerInfo, PeerStatus};
+use crate::storage::object_limits;
+use crate::{Error, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn, Level};
+use utoipa::ToSchema;
+
+/// Applies a new log level to the running subscriber
+pub type LogLevelHook = Arc<dyn Fn(Level) -> Result<()> + Send + Sync>;
+
+/// Effective configuration, replaced as a whole on reload
+#[derive(Clone)]
+pub struct SharedConfig(Arc<RwLock<Arc<Config>>>);
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// Snapshot of the current configuration
+    pub fn get(&self) -> Arc<Config> {
+        match self.0.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub(crate) fn replace(&self, config: Config) {
+        match self.0.write() {
+            Ok(mut current) => *current = Arc::new(config),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(config),
+        }
+    }
+}
+
+/// Where reloads read from and how they reach the logger
+#[derive(Default)]
+pub struct Reloader {
+    path: Option<PathBuf>,
+    overrides: Vec<ConfigOverride>,
+    log_level_hook: Option<LogLevelHook>,
+    lock: tokio::sync::Mutex<()>,
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl Reloader {
+    pub fn new(path: Option<PathBuf>, log_level_hook: Option<LogLevelHook>) -> Self {
+        Self {
+            path,
+            overrides: Vec::new(),
+            log_level_hook,
+            lock: tokio::sync::Mutex::new(()),
+            last_error: Default::default(),
+        }
+    }
+
+    /// Why the most recent reload failed, if it did
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().ok().and_then(|e| e.clone())
+    }
+
+    fn set_last_error(&self, error: Option<String>) {
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = error;
+        }
+    }
+
+    /// Layer these `--set` overrides over the file on every reload
+    pub fn with_overrides(mut self, overrides: Vec<ConfigOverride>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+}
+
+/// What a reload changed
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ReloadReport {
+    pub peers_added: Vec<String>,
+    pub peers_removed: Vec<String>,
+    pub peers_updated: Vec<String>,
+    /// Settings applied in place
+    pub applied: Vec<String>,
+    /// Changed settings that keep their running values until a restart
+    pub restart_required: Vec<String>,
+}
+
+/// Re-read the configuration file and apply it
+pub async fn reload_from_file(state: &AppState) -> Result<ReloadReport> {
+    let reloader = &state.reloader;
+    let Some(path) = &reloader.path else {
+        return Err(Error::Config("node was not started from a configuration file".into()));
+    };
+
+    let _reloading = reloader.lock.lock().await;
+    let result = match Config::load_with(path, &reloader.overrides) {
+        Ok(config) => apply_config(state, config).await,
+        Err(e) => Err(e),
+    };
+    reloader.set_last_error(result.as_ref().err().map(|e| e.to_string()));
+    result
+}
+
+fn changed<T: Serialize>(current: &T, new: &T) -> bool {
+    serde_json::to_value(current).ok() != serde_json::to_value(new).ok()
+}
+
+/// Apply a validated configuration to a running node
+pub async fn apply_config(state: &AppState, new: Config) -> Result<ReloadReport> {
+    let current = state.config.get();
+    let mut effective = (*current).clone();
+    let mut report = ReloadReport::default();
+
+    let fixed = [
+        ("node", changed(&current.node, &new.node)),
+        ("server", changed(&current.server, &new.server)),
+        ("api", changed(&current.api, &new.api)),
+        ("storage.type", current.storage.storage_type != new.storage.storage_type),
+        ("storage.file_path", current.storage.file_path != new.storage.file_path),
+        ("storage.memory", changed(&current.storage.memory, &new.storage.memory)),
+        (
+            "storage.conjunction_bucket_seconds",
+            current.storage.conjunction_bucket_seconds != new.storage.conjunction_bucket_seconds,
+        ),
+        (
+            "storage.cdm_history_limit",
+            current.storage.cdm_history_limit != new.storage.cdm_history_limit,
+        ),
+        (
+            "storage.object_history_limit",
+            current.storage.object_history_limit != new.storage.object_history_limit,
+        ),
+        ("storage.encryption", changed(&current.storage.encryption, &new.storage.encryption)),
+        ("logging.format", current.logging.format != new.logging.format),
+        (
+            "protocol.heartbeat_interval_seconds",
+            current.protocol.heartbeat_interval_seconds != new.protocol.heartbeat_interval_seconds,
+        ),
+        (
+            "protocol.session_timeout_seconds",
+            current.protocol.session_timeout_seconds != new.protocol.session_timeout_seconds,
+        ),
+        ("catalog", changed(&current.catalog, &new.catalog)),
+        ("dev", changed(&current.dev, &new.dev)),
+        ("archive", changed(&current.archive, &new.archive)),
+        ("discovery", changed(&current.discovery, &new.discovery)),
+        ("telemetry", changed(&current.telemetry, &new.telemetry)),
+        ("ha", changed(&current.ha, &new.ha)),
+        ("pc", changed(&current.pc, &new.pc)),
+    ];
+    for (setting, differs) in fixed {
+        if differs {
+            warn!("Configuration reload: {} changed, restart to apply", setting);
+            report.restart_required.push(setting.to_string());
+        }
+    }
+
+    // The log level goes first: if the hook fails nothing has changed yet
+    if new.logging.level != current.logging.level {
+        if let Some(hook) = &state.reloader.log_level_hook {
+            hook(new.logging_level())?;
+        }
+        effective.logging.level = new.logging.level.clone();
+        report.applied.push("logging.level".to_string());
+    }
+
+    if new.protocol.version != current.protocol.version {
+        report.applied.push("protocol.version".to_string());
+    }
+    if new.protocol.disabled_features != current.protocol.disabled_features {
+        report.applied.push("protocol.disabled_features".to_string());
+    }
+    if new.protocol.max_hop_count != current.protocol.max_hop_count {
+        state.routing.set_max_hop_count(new.protocol.max_hop_count);
+        report.applied.push("protocol.max_hop_count".to_string());
+    }
+    if changed(&current.protocol.ttl, &new.protocol.ttl) {
+        report.applied.push("protocol.ttl".to_string());
+    }
+    if new.protocol.max_envelope_bytes != current.protocol.max_envelope_bytes {
+        report.applied.push("protocol.max_envelope_bytes".to_string());
+    }
+    if new.protocol.max_payload_depth != current.protocol.max_payload_depth {
+        report.applied.push("protocol.max_payload_depth".to_string());
+    }
+    if new.protocol.max_message_age_seconds != current.protocol.max_message_age_seconds {
+        report.applied.push("protocol.max_message_age_seconds".to_string());
+    }
+    if new.protocol.max_clock_skew_seconds != current.protocol.max_clock_skew_seconds {
+        report.applied.push("protocol.max_clock_skew_seconds".to_string());
+    }
+    if changed(&current.protocol.clock_skew, &new.protocol.clock_skew) {
+        report.applied.push("protocol.clock_skew".to_string());
+    }
+    if new.protocol.max_query_results != current.protocol.max_query_results {
+        report.applied.push("protocol.max_query_results".to_string());
+    }
+    if changed(&current.protocol.sync, &new.protocol.sync) {
+        report.applied.push("protocol.sync".to_string());
+    }
+    if changed(&current.protocol.delivery, &new.protocol.delivery) {
+        report.applied.push("protocol.delivery".to_string());
+    }
+    if new.protocol.receive_window != current.protocol.receive_window {
+        report.applied.push("protocol.receive_window".to_string());
+    }
+    if new.protocol.timestamp_format != current.protocol.timestamp_format {
+        report.applied.push("protocol.timestamp_format".to_string());
+    }
+    if changed(&current.protocol.severity, &new.protocol.severity) {
+        report.applied.push("protocol.severity".to_string());
+    }
+    if new.protocol.min_data_quality != current.protocol.min_data_quality {
+        report.applied.push("protocol.min_data_quality".to_string());
+    }
+    effective.protocol.version = new.protocol.version.clone();
+    effective.protocol.disabled_features = new.protocol.disabled_features.clone();
+    effective.protocol.max_hop_count = new.protocol.max_hop_count;
+    effective.protocol.ttl = new.protocol.ttl.clone();
+    effective.protocol.max_envelope_bytes = new.protocol.max_envelope_bytes;
+    effective.protocol.max_payload_depth = new.protocol.max_payload_depth;
+    effective.protocol.max_message_age_seconds = new.protocol.max_message_age_seconds;
+    effective.protocol.max_clock_skew_seconds = new.protocol.max_clock_skew_seconds;
+    effective.protocol.clock_skew = new.protocol.clock_skew.clone();
+    effective.protocol.max_query_results = new.protocol.max_query_results;
+    effective.protocol.sync = new.protocol.sync.clone();
+    effective.protocol.delivery = new.protocol.delivery.clone();
+    effective.protocol.receive_window = new.protocol.receive_window;
+    effective.protocol.timestamp_format = new.protocol.timestamp_format;
+    effective.protocol.severity = new.protocol.severity.clone();
+    effective.protocol.min_data_quality = new.protocol.min_data_quality;
+
+    if changed(&current.fusion, &new.fusion) {
+        effective.fusion = new.fusion.clone();
+        report.applied.push("fusion".to_string());
+    }
+
+    if changed(&current.fanout, &new.fanout) {
+        effective.fanout = new.fanout.clone();
+        report.applied.push("fanout".to_string());
+    }
+
+    // Response thresholds apply at once, payload thresholds to new sessions
+    if changed(&current.compression, &new.compression) {
+        effective.compression = new.compression.clone();
+        report.applied.push("compression".to_string());
+    }
+
+    if changed(&current.peer_health, &new.peer_health) {
+        effective.peer_health = new.peer_health.clone();
+        report.applied.push("peer_health".to_string());
+    }
+
+    if changed(&current.ingest_quotas, &new.ingest_quotas) {
+        effective.ingest_quotas = new.ingest_quotas.clone();
+        report.applied.push("ingest_quotas".to_string());
+    }
+
+    if changed(&current.validation, &new.validation) {
+        effective.validation = new.validation.clone();
+        report.applied.push("validation".to_string());
+    }
+    if changed(&current.stats, &new.stats) {
+        effective.stats = new.stats.clone();
+        report.applied.push("stats".to_string());
+    }
+
+    if changed(&current.provenance, &new.provenance) {
+        effective.provenance = new.provenance.clone();
+        report.applied.push("provenance".to_string());
+    }
+
+    if changed(&current.alerts, &new.alerts) {
+        effective.alerts = new.alerts.clone();
+        report.applied.push("alerts".to_string());
+    }
+
+    if changed(&current.notifications, &new.notifications) {
+        effective.notifications = new.notifications.clone();
+        report.applied.push("notifications".to_string());
+    }
+
+    if changed(&current.readiness, &new.readiness) {
+        effective.readiness = new.readiness.clone();
+        report.applied.push("readiness".to_string());
+    }
+
+    if changed(&current.storage.object_limits, &new.storage.object_limits) {
+        effective.storage.object_limits = new.storage.object_limits.clone();
+        state.storage.set_object_limits(object_limits(&effective)).await?;
+        report.applied.push("storage.object_limits".to_string());
+    }
+
+    if new.storage.idempotency_ttl_seconds != current.storage.idempotency_ttl_seconds {
+        effective.storage.idempotency_ttl_seconds = new.storage.idempotency_ttl_seconds;
+        report.applied.push("storage.idempotency_ttl_seconds".to_string());
+    }
+
+    let interests_changed = changed(&current.interests, &new.interests);
+    if interests_changed {
+        effective.interests = new.interests.clone();
+        report.applied.push("interests".to_string());
+    }
+
+    // Groups and templates were resolved into each peer's policies at load
+    apply_peers(state, &current.peers, &new.peers, &mut report).await;
+    effective.peers = new.peers;
+    effective.policy_templates = new.policy_templates;
+    effective.peer_groups = new.peer_groups;
+
+    state.config.replace(effective);
+    if interests_changed {
+        advertise_interests(state).await;
+    }
+    info!(
+        "Configuration reloaded: {} peers added, {} removed, {} updated; applied [{}]",
+        report.peers_added.len(),
+        report.peers_removed.len(),
+        report.peers_updated.len(),
+        report.applied.join(", ")
+    );
+    Ok(report)
+}
+
+/// Reconcile configured peers; peers added through the API are left alone
+async fn apply_peers(state: &AppState, current: &[PeerConfig], new: &[PeerConfig], report: &mut ReloadReport) {
+    let mut sessions = Vec::new();
+    // Policies changed through the API outlast the configured ones
+    let kept: HashMap<String, PeerPolicies> = match state.storage.list_peer_policies().await {
+        Ok(kept) => kept.into_iter().collect(),
+        Err(e) => {
+            warn!("Could not read kept peer policies: {}", e);
+            HashMap::new()
+        }
+    };
+    let mut peers = state.peers.write().await;
+
+    for peer in current {
+        if !new.iter().any(|p| p.id == peer.id) && peers.remove_peer(&peer.id) {
+            state.fanout.remove_peer(&peer.id);
+            if let Err(e) = state.storage.remove_peer_policies(&peer.id).await {
+                warn!("Could not remove policies kept for peer {}: {}", peer.id, e);
+            }
+            info!("Peer {} removed by configuration reload", peer.id);
+            report.peers_removed.push(peer.id.clone());
+        }
+    }
+
+    for peer in new {
+        let previous = current.iter().find(|p| p.id == peer.id);
+        if previous.is_some_and(|p| !changed(p, peer)) {
+            continue;
+        }
+
+        let Some(info) = peers.get_peer_mut(&peer.id) else {
+            peers.add_peer(PeerInfo::from_config(peer));
+            info!("Peer {} added by configuration reload", peer.id);
+            report.peers_added.push(peer.id.clone());
+            sessions.push(peer.id.clone());
+            continue;
+        };
+
+        // Policy changes apply in place; anything else needs a new session
+        let reconnect = info.address != peer.address
+            || info.transport != peer.transport
+            || info.encoding != peer.encoding
+            || info.timestamp_format != peer.timestamp_format
+            || info.auth_token != peer.auth_token;
+        info.address = peer.address.clone();
+        info.transport = peer.transport;
+        info.encoding = peer.encoding;
+        info.timestamp_format = peer.timestamp_format;
+        info.auth_token = peer.auth_token.clone();
+        info.policies = kept.get(&peer.id).unwrap_or(&peer.policies).clone();
+        if reconnect {
+            info.status = PeerStatus::Disconnected;
+            peers.drop_link(&peer.id);
+            state.fanout.remove_peer(&peer.id);
+        }
+        info!("Peer {} updated by configuration reload (reconnect: {})", peer.id, reconnect);
+        report.peers_updated.push(peer.id.clone());
+    }
+    drop(peers);
+
+    for peer_id in sessions {
+        spawn_session(state.clone(), peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(&format!("node: {{ id: node-a }}\n{}", yaml)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_apply_config() {
+        let state = test_state("node-a");
+        state.config.replace(config(
+            "server: {}\npeers:\n  - { id: keep, address: 'http://keep' }\n  - { id: gone, address: 'http://gone' }\n  - { id: move, address: 'http://old' }",
+        ));
+        {
+            let mut peers = state.peers.write().await;
+            for peer in &state.config.get().peers {
+                peers.add_peer(PeerInfo::from_config(peer));
+                peers.set_peer_status(&peer.id, PeerStatus::Connected);
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let hook: LogLevelHook = Arc::new(move |level| {
+            assert_eq!(level, Level::DEBUG);
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+        let mut state = state;
+        state.reloader = Arc::new(Reloader::new(None, Some(hook)));
+
+        let new = config(
+            "server: { port: 9999 }\nlogging: { level: debug }\nprotocol: { max_hop_count: 3 }\npeers:\n  - { id: keep, address: 'http://keep' }\n  - { id: move, address: 'http://new' }\n  - { id: fresh, address: 'http://fresh' }",
+        );
+        let report = apply_config(&state, new).await.unwrap();
+
+        assert_eq!(report.peers_added, ["fresh"]);
+        assert_eq!(report.peers_removed, ["gone"]);
+        assert_eq!(report.peers_updated, ["move"]);
+        assert_eq!(report.applied, ["logging.level", "protocol.max_hop_count"]);
+        assert_eq!(report.restart_required, ["server"]);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let peers = state.peers.read().await;
+        assert_eq!(peers.get_peer("keep").unwrap().status, PeerStatus::Connected);
+        assert_eq!(peers.get_peer("move").unwrap().status, PeerStatus::Disconnected);
+        assert!(peers.get_peer("gone").is_none());
+
+        // Restart-only settings keep their running values
+        let effective = state.config.get();
+        assert_eq!(effective.server.port, 8080);
+        assert_eq!(effective.protocol.max_hop_count, 3);
+        assert_eq!(effective.peers.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_reload_requires_config_file() {
+        let state = test_state("node-a");
+        assert!(matches!(reload_from_file(&state).await, Err(Error::Config(_))));
+    }
+}
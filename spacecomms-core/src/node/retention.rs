@@ -0,0 +1,179 @@
+//! Retention sweeps moving cold records into the archive
+//!
+//! Each sweep archives CDMs withdrawn since the last one, expires CDMs whose
+//! TCA is long past and drops object states that have not been updated, so
+//! the hot store only holds what is still relevant. Records are written to
+//! the archive before they leave the hot store.
+
+use crate::config::ArchiveConfig;
+use crate::node::AppState;
+use crate::storage::{ArchiveEntry, ArchiveReason, FileArchive};
+use crate::{Error, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Records moved by one sweep
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RetentionReport {
+    pub cdms_withdrawn: usize,
+    pub cdms_expired: usize,
+    pub objects_stale: usize,
+}
+
+/// Append entries off the async executor
+async fn write(archive: &Arc<FileArchive>, entries: Vec<ArchiveEntry>) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let archive = archive.clone();
+    tokio::task::spawn_blocking(move || archive.append(&entries))
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))?
+}
+
+/// Run one retention sweep
+pub async fn run_retention(state: &AppState, archive: &Arc<FileArchive>, config: &ArchiveConfig) -> Result<RetentionReport> {
+    let now = Utc::now();
+    let mut report = RetentionReport::default();
+
+    // CDMs whose conjunction is long over
+    let expiry = now - ChronoDuration::hours(config.cdm_expiry_hours as i64);
+    let expired: Vec<_> = state.storage.list_cdms().await?.into_iter().filter(|cdm| cdm.tca < expiry).collect();
+    write(
+        archive,
+        expired.iter().map(|cdm| ArchiveEntry::cdm(cdm.clone(), ArchiveReason::Expired)).collect(),
+    )
+    .await?;
+    let mut expired_ids = HashSet::new();
+    for cdm in expired {
+        match state.storage.withdraw_cdm(&cdm.cdm_id).await {
+            Ok(()) => {}
+            // Withdrawn concurrently; archived below as withdrawn
+            Err(e) if e.is_not_found() => continue,
+            Err(e) => return Err(e),
+        }
+        state.events.withdrawn(&cdm.cdm_id, Some(&cdm), "expired".to_string());
+        expired_ids.insert(cdm.cdm_id);
+        report.cdms_expired += 1;
+    }
+
+    // Withdrawn CDMs, dated by their withdrawal; those just expired are
+    // already archived
+    let withdrawn = state.storage.list_withdrawn_cdms().await?;
+    let entries: Vec<ArchiveEntry> = withdrawn
+        .iter()
+        .filter(|w| !expired_ids.contains(&w.record.cdm_id))
+        .map(|w| ArchiveEntry {
+            archived_at: w.withdrawn_at,
+            ..ArchiveEntry::cdm(w.record.clone(), ArchiveReason::Withdrawn)
+        })
+        .collect();
+    report.cdms_withdrawn = entries.len();
+    write(archive, entries).await?;
+    state.storage.discard_withdrawn_cdms(&withdrawn).await?;
+
+    // Object states nobody has refreshed
+    let stale_after = now - ChronoDuration::hours(config.object_stale_hours as i64);
+    let stale: Vec<_> = state
+        .storage
+        .list_objects()
+        .await?
+        .into_iter()
+        .filter(|obj| obj.last_updated < stale_after)
+        .collect();
+    write(
+        archive,
+        stale.iter().map(|obj| ArchiveEntry::object(obj.clone(), ArchiveReason::Stale)).collect(),
+    )
+    .await?;
+    for obj in stale {
+        match state.storage.withdraw_object(&obj.object_id).await {
+            Ok(()) => report.objects_stale += 1,
+            Err(e) if e.is_not_found() => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Sweep on the configured interval for as long as the node runs
+pub fn spawn_archiver(state: AppState, archive: Arc<FileArchive>, config: ArchiveConfig) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_seconds));
+        info!("Archiving to {} every {}s", config.directory, config.interval_seconds);
+        loop {
+            ticker.tick().await;
+            if !state.leadership.is_leader() {
+                continue;
+            }
+            match run_retention(&state, &archive, &config).await {
+                Ok(report) if report == RetentionReport::default() => debug!("Retention sweep found nothing to archive"),
+                Ok(report) => info!(
+                    "Archived {} withdrawn CDMs, {} expired CDMs and {} stale objects",
+                    report.cdms_withdrawn, report.cdms_expired, report.objects_stale
+                ),
+                Err(e) => warn!("Retention sweep failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use crate::storage::{ArchiveKind, ArchiveQuery};
+
+    #[tokio::test]
+    async fn test_retention_sweep() {
+        let state = test_state("node-a");
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(FileArchive::new(dir.path(), true));
+        let config = ArchiveConfig {
+            directory: dir.path().display().to_string(),
+            interval_seconds: 60,
+            cdm_expiry_hours: 24,
+            object_stale_hours: 168,
+            compress: true,
+        };
+
+        let mut current = generate_demo_cdm();
+        current.tca = Utc::now() + ChronoDuration::hours(6);
+        let mut past = current.clone();
+        past.cdm_id = "CDM-PAST".into();
+        past.tca = Utc::now() - ChronoDuration::days(3);
+        let mut withdrawn = current.clone();
+        withdrawn.cdm_id = "CDM-WITHDRAWN".into();
+        for cdm in [current.clone(), past, withdrawn] {
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+        state.storage.withdraw_cdm("CDM-WITHDRAWN").await.unwrap();
+
+        let report = run_retention(&state, &archive, &config).await.unwrap();
+        assert_eq!((report.cdms_withdrawn, report.cdms_expired, report.objects_stale), (1, 1, 0));
+        assert_eq!(state.storage.cdm_count().await.unwrap(), 1);
+        assert!(state.storage.get_cdm(&current.cdm_id).await.unwrap().is_some());
+        // Moved out of the hot store
+        assert!(state.storage.list_withdrawn_cdms().await.unwrap().is_empty());
+
+        let query = ArchiveQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        let page = archive.query(ArchiveKind::Cdm, &query).unwrap();
+        let mut reasons: Vec<_> = page.entries.iter().map(|e| e.reason).collect();
+        reasons.sort_by_key(|r| format!("{:?}", r));
+        assert_eq!(reasons, [ArchiveReason::Expired, ArchiveReason::Withdrawn]);
+
+        // Nothing left to move
+        let report = run_retention(&state, &archive, &config).await.unwrap();
+        assert_eq!(report, RetentionReport::default());
+    }
+}
@@ -0,0 +1,389 @@
+//! Routing simulation
+//!
+//! `POST /routing/simulate` answers "what would this node do with this
+//! message": take it in, hold it in quarantine or refuse it, which of its
+//! configured policies come into play, and which peers it would be
+//! forwarded to. The message goes through the checks a message from a peer,
+//! or a CDM ingested through the API, goes through, against the node's
+//! current configuration and peer sessions, but nothing is stored or sent.
+//!
+//! Checks that depend on the node's recent traffic, such as ingest quotas
+//! and originator rate spikes, are not simulated.
+
+use crate::cdm::{check_quality_floor, evaluate_rules, score_covariance_quality, CdmRecord, RuleAction};
+use crate::config::PeerPolicies;
+use crate::node::{check_envelope, clamp_ttl, exclusion, AppState, Exclusion, PeerStatus, RoutingDecision};
+use crate::protocol::{Envelope, MessageType};
+use crate::{Error, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A message to route: an envelope, or a CDM to route as a CDM_ANNOUNCE
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RoutingSimulationRequest {
+    /// Envelope as it would arrive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<Envelope>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdm: Option<CdmRecord>,
+    /// Peer the message arrives from. Unset, an envelope arrives from its
+    /// source node unless that is this node, and a CDM is ingested here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_peer: Option<String>,
+}
+
+/// What the node would do with a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingOutcome {
+    /// Take it in and forward it to at least one peer
+    Forward,
+    /// Take it in without forwarding it
+    Accept,
+    /// Hold it in quarantine
+    Quarantine,
+    /// Drop it, having seen it already
+    Duplicate,
+    Reject,
+}
+
+/// A configured policy that applies to the message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MatchedRule {
+    /// Setting, such as `peers.node-b.policies.accept_cdm` or
+    /// `validation.rules.fresh`
+    pub rule: String,
+    /// What it does to the message
+    pub effect: String,
+}
+
+/// Whether a connected peer would be sent the message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PeerRoute {
+    pub peer_id: String,
+    pub forwarded: bool,
+    /// Sent with the peer's `policies.redact` applied
+    #[serde(default)]
+    pub redacted: bool,
+    /// TTL the peer would receive the message with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// Why it would not be sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excluded_by: Option<String>,
+}
+
+/// How the node would route a message
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoutingSimulation {
+    pub message_id: String,
+    pub message_type: MessageType,
+    /// Peer the message arrives from; unset when originated here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_peer: Option<String>,
+    pub outcome: RoutingOutcome,
+    /// Why it would be refused, held or not forwarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Policies that apply, in the order checked
+    pub rules: Vec<MatchedRule>,
+    /// Peers it would be forwarded to
+    pub forward_to: Vec<String>,
+    /// Connected peers considered, other than the sender
+    pub peers: Vec<PeerRoute>,
+}
+
+impl RoutingSimulation {
+    fn rule(&mut self, rule: impl Into<String>, effect: impl Into<String>) {
+        self.rules.push(MatchedRule {
+            rule: rule.into(),
+            effect: effect.into(),
+        });
+    }
+
+    fn decided(mut self, outcome: RoutingOutcome, reason: impl Into<String>) -> Self {
+        self.outcome = outcome;
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// Route a message as the node would, without storing or sending it
+///
+/// Fails with [`Error::Protocol`] unless exactly one of an envelope and a
+/// CDM is given.
+pub async fn simulate_routing(state: &AppState, request: RoutingSimulationRequest) -> Result<RoutingSimulation> {
+    let config = state.config.get();
+    let (mut envelope, from_peer) = match (request.envelope, request.cdm) {
+        (Some(envelope), None) => {
+            let source = (envelope.source_node_id != config.node.id).then(|| envelope.source_node_id.clone());
+            let from_peer = request.from_peer.or(source);
+            (envelope, from_peer)
+        }
+        (None, Some(cdm)) => {
+            let source = request.from_peer.clone().unwrap_or_else(|| config.node.id.clone());
+            let envelope = Envelope::new(source, MessageType::CdmAnnounce, serde_json::to_value(&cdm)?);
+            (envelope, request.from_peer)
+        }
+        _ => return Err(Error::Protocol("give either an envelope or a CDM".to_string())),
+    };
+    let mut simulation = RoutingSimulation {
+        message_id: envelope.message_id.clone(),
+        message_type: envelope.message_type.clone(),
+        from_peer: from_peer.clone(),
+        outcome: RoutingOutcome::Accept,
+        reason: None,
+        rules: Vec::new(),
+        forward_to: Vec::new(),
+        peers: Vec::new(),
+    };
+
+    let sender = from_peer.clone().unwrap_or_else(|| envelope.source_node_id.clone());
+    if let Some(node) = state.peers.read().await.blocked_in(&envelope, &sender) {
+        simulation.rule("blocklist", format!("refuses messages from or through node {}", node));
+        return Ok(simulation.decided(RoutingOutcome::Reject, format!("node {} is blocked", node)));
+    }
+    if let Err(e) = check_envelope(&mut envelope, &state.envelope_limits()) {
+        return Ok(simulation.decided(RoutingOutcome::Reject, e.to_string()));
+    }
+    let message_type = envelope.message_type.clone();
+    if !message_type.is_relayed() {
+        return Ok(simulation.decided(RoutingOutcome::Accept, format!("{} is never forwarded", message_type)));
+    }
+
+    if from_peer.is_some() && state.storage.has_seen_message(&envelope.message_id).await? {
+        return Ok(simulation.decided(RoutingOutcome::Duplicate, "message already seen"));
+    }
+    let peers = state.peers.read().await;
+    if let Some(sender) = &from_peer {
+        if peers.is_quarantined(sender) {
+            simulation.rule("peer_health", format!("refuses messages from quarantined peer {}", sender));
+            return Ok(simulation.decided(RoutingOutcome::Reject, format!("peer {} is quarantined", sender)));
+        }
+        if let Some(peer) = peers.get_peer(sender) {
+            if !state.routing.should_forward_to_peer(&message_type, &peer.policies) {
+                let policy = refusing_policy(&message_type, &peer.policies);
+                simulation.rule(format!("peers.{}.policies.{}", sender, policy), "refuses the message type");
+                let reason = format!("{} not accepted from peer {}", message_type, sender);
+                return Ok(simulation.decided(RoutingOutcome::Reject, reason));
+            }
+        }
+    }
+
+    if message_type == MessageType::CdmAnnounce {
+        let mut cdm: CdmRecord = envelope.payload.parse()?;
+        score_covariance_quality(&mut cdm);
+        if let Err(e) = check_quality_floor(&cdm, config.protocol.min_data_quality) {
+            simulation.rule("protocol.min_data_quality", "rejects the CDM");
+            return Ok(simulation.decided(RoutingOutcome::Reject, e.to_string()));
+        }
+        let violations = evaluate_rules(&cdm, &config.validation.rules, Utc::now());
+        for violation in &violations {
+            let effect = match violation.action {
+                RuleAction::Reject => "rejects",
+                RuleAction::Warn => "warns",
+                RuleAction::Quarantine => "quarantines",
+            };
+            simulation.rule(
+                format!("validation.rules.{}", violation.rule),
+                format!("{}: {}", effect, violation.message),
+            );
+        }
+        for (action, outcome) in [(RuleAction::Reject, RoutingOutcome::Reject), (RuleAction::Quarantine, RoutingOutcome::Quarantine)] {
+            if let Some(violation) = violations.iter().find(|violation| violation.action == action) {
+                let reason = format!("rule {}: {}", violation.rule, violation.message);
+                return Ok(simulation.decided(outcome, reason));
+            }
+        }
+    }
+
+    let connected: Vec<String> = peers
+        .list_peers()
+        .iter()
+        .filter(|p| p.status == PeerStatus::Connected && Some(&p.id) != from_peer.as_ref())
+        .map(|p| p.id.clone())
+        .collect();
+    // Relayed messages are passed on as the routing engine decides;
+    // messages originated here go to every connected peer
+    let (envelope, peer_ids) = match &from_peer {
+        Some(sender) => {
+            let decision = state.routing.decide(
+                &message_type,
+                &envelope.source_node_id,
+                envelope.hop_count,
+                envelope.ttl,
+                &connected,
+            );
+            let peer_ids = match decision {
+                RoutingDecision::Reject { reason } => {
+                    if envelope.hop_count > config.protocol.max_hop_count {
+                        simulation.rule("protocol.max_hop_count", "refuses the message");
+                    }
+                    return Ok(simulation.decided(RoutingOutcome::Reject, reason));
+                }
+                RoutingDecision::Accept => Vec::new(),
+                RoutingDecision::AcceptAndForward { peer_ids } => peer_ids,
+            };
+            let Some(mut forwarded) = envelope.forwarded().filter(|_| !peer_ids.is_empty()) else {
+                let reason = if envelope.ttl == 0 { "TTL exhausted" } else { "no connected peer to forward to" };
+                return Ok(simulation.decided(RoutingOutcome::Accept, reason));
+            };
+            let forward_cdm = peers.get_peer(sender).map(|p| p.policies.forward_cdm).unwrap_or(true);
+            if matches!(message_type, MessageType::CdmAnnounce | MessageType::CdmWithdraw) && !forward_cdm {
+                simulation.rule(format!("peers.{}.policies.forward_cdm", sender), "keeps the CDM from being passed on");
+                return Ok(simulation.decided(RoutingOutcome::Accept, format!("CDMs from {} are not passed on", sender)));
+            }
+            let ttl = forwarded.ttl;
+            clamp_ttl(state, &peers, &mut forwarded, sender);
+            if forwarded.ttl < ttl {
+                let type_ttl = config.protocol.ttl.for_message_type(&message_type);
+                let rule = if forwarded.ttl == type_ttl {
+                    "protocol.ttl".to_string()
+                } else {
+                    format!("peers.{}.policies.max_ttl", sender)
+                };
+                simulation.rule(rule, format!("lowers the TTL to {}", forwarded.ttl));
+            }
+            (forwarded, peer_ids)
+        }
+        None => {
+            envelope.ttl = config.protocol.ttl.for_message_type(&message_type);
+            (envelope, connected.clone())
+        }
+    };
+    if !state.leadership.is_leader() {
+        simulation.rule("ha", "standby nodes forward nothing");
+        return Ok(simulation.decided(RoutingOutcome::Accept, "this node is on standby"));
+    }
+
+    for id in connected {
+        let excluded_by = if !peer_ids.contains(&id) {
+            Some("originator".to_string())
+        } else {
+            exclusion(state, &peers, &envelope, &id).map(|excluded| match excluded {
+                Exclusion::Policy => {
+                    let policies = peers.get_peer(&id).map(|p| p.policies.clone()).unwrap_or_default();
+                    let rule = format!("peers.{}.policies.{}", id, refusing_policy(&message_type, &policies));
+                    simulation.rule(rule.clone(), "refuses the message type");
+                    rule
+                }
+                Exclusion::Unknown => "unknown_peer".to_string(),
+                Exclusion::Blocked => "blocklist".to_string(),
+                Exclusion::Quarantined => "peer_health".to_string(),
+                Exclusion::Interests => "interests".to_string(),
+                Exclusion::NoLink => "no_session".to_string(),
+            })
+        };
+        let forwarded = excluded_by.is_none();
+        let policies = peers.get_peer(&id).map(|p| &p.policies);
+        let redacted = forwarded && policies.is_some_and(|p| !p.redact.is_empty());
+        if redacted {
+            simulation.rule(format!("peers.{}.policies.redact", id), "sends a redacted copy");
+        }
+        let max_ttl = policies.and_then(|p| p.max_ttl).filter(|max| *max < envelope.ttl);
+        if let Some(max) = max_ttl.filter(|_| forwarded) {
+            simulation.rule(format!("peers.{}.policies.max_ttl", id), format!("lowers the TTL to {}", max));
+        }
+        let ttl = forwarded.then(|| max_ttl.unwrap_or(envelope.ttl));
+        if forwarded {
+            simulation.forward_to.push(id.clone());
+        }
+        simulation.peers.push(PeerRoute {
+            peer_id: id,
+            forwarded,
+            redacted,
+            ttl,
+            excluded_by,
+        });
+    }
+    Ok(if simulation.forward_to.is_empty() {
+        simulation.decided(RoutingOutcome::Accept, "no connected peer takes it")
+    } else {
+        simulation.outcome = RoutingOutcome::Forward;
+        simulation
+    })
+}
+
+/// The peer policy refusing a relayed message type
+fn refusing_policy(message_type: &MessageType, policies: &PeerPolicies) -> &'static str {
+    if policies.block_message_types.contains(message_type) {
+        return "block_message_types";
+    }
+    match message_type {
+        MessageType::CdmAnnounce | MessageType::CdmWithdraw => "accept_cdm",
+        MessageType::ObjectStateAnnounce | MessageType::ObjectStateWithdraw => "accept_object_state",
+        _ => "accept_maneuver",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use crate::node::{HttpTransport, PeerInfo};
+    use crate::protocol::DEFAULT_TTL;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_simulate_routing() {
+        let state = test_state("node-local");
+        {
+            let mut peers = state.peers.write().await;
+            for (id, policies) in [
+                ("node-a", "max_ttl: 2"),
+                ("node-b", "accept_cdm: false"),
+                ("node-c", "redact: { drop_covariance: true }"),
+                ("node-d", ""),
+            ] {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1', policies: {{ {} }} }}", id, policies);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                if id != "node-d" {
+                    peers.set_link(id, Arc::new(HttpTransport::new("http://127.0.0.1:1", "node-local", None)));
+                }
+            }
+        }
+        let cdm = generate_demo_cdm();
+        let ingest = RoutingSimulationRequest {
+            cdm: Some(cdm.clone()),
+            ..Default::default()
+        };
+        let simulation = simulate_routing(&state, ingest).await.unwrap();
+        assert_eq!(simulation.outcome, RoutingOutcome::Forward);
+        assert_eq!(simulation.forward_to, ["node-a", "node-c"]);
+        let excluded: Vec<Option<&str>> = simulation.peers.iter().map(|p| p.excluded_by.as_deref()).collect();
+        assert_eq!(excluded, [None, Some("peers.node-b.policies.accept_cdm"), None, Some("no_session")]);
+        assert!(simulation.peers[2].redacted);
+        let ttls: Vec<Option<u32>> = simulation.peers.iter().map(|p| p.ttl).collect();
+        assert_eq!(ttls, [Some(2), None, Some(DEFAULT_TTL), None]);
+        assert!(simulation.rules.iter().any(|r| r.rule == "peers.node-c.policies.redact"));
+        // Nothing was stored or marked seen
+        assert_eq!(state.storage.cdm_count().await.unwrap(), 0);
+        assert!(!state.storage.has_seen_message(&simulation.message_id).await.unwrap());
+
+        // Node B's policies refuse CDMs from it too
+        let from_b = RoutingSimulationRequest {
+            cdm: Some(cdm.clone()),
+            from_peer: Some("node-b".into()),
+            ..Default::default()
+        };
+        let simulation = simulate_routing(&state, from_b).await.unwrap();
+        assert_eq!(simulation.outcome, RoutingOutcome::Reject);
+        assert_eq!(simulation.rules[0].rule, "peers.node-b.policies.accept_cdm");
+
+        // A relayed envelope out of TTL is only taken in
+        let mut envelope = Envelope::new("node-a".into(), MessageType::CdmAnnounce, serde_json::to_value(&cdm).unwrap());
+        envelope.ttl = 0;
+        let relayed = RoutingSimulationRequest {
+            envelope: Some(envelope),
+            ..Default::default()
+        };
+        let simulation = simulate_routing(&state, relayed).await.unwrap();
+        assert_eq!(simulation.from_peer.as_deref(), Some("node-a"));
+        assert_eq!((simulation.outcome, simulation.reason.as_deref()), (RoutingOutcome::Accept, Some("TTL exhausted")));
+
+        assert!(simulate_routing(&state, RoutingSimulationRequest::default()).await.is_err());
+    }
+}
@@ -1,7 +1,9 @@
 //! Routing engine
 
-use crate::config::Config;
-use crate::protocol::MessageType;
+use crate::config::{Config, PeerPolicies};
+use crate::protocol::{Envelope, Interests, MessageType};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Routing decision
 #[derive(Debug, Clone)]
@@ -17,7 +19,7 @@ pub enum RoutingDecision {
 /// Routing engine
 pub struct RoutingEngine {
     node_id: String,
-    max_hop_count: u32,
+    max_hop_count: AtomicU32,
 }
 
 impl RoutingEngine {
@@ -25,10 +27,15 @@ impl RoutingEngine {
     pub fn new(config: Config) -> Self {
         Self {
             node_id: config.node.id,
-            max_hop_count: config.protocol.max_hop_count,
+            max_hop_count: AtomicU32::new(config.protocol.max_hop_count),
         }
     }
 
+    /// Change the hop limit of a running node
+    pub fn set_max_hop_count(&self, max_hop_count: u32) {
+        self.max_hop_count.store(max_hop_count, Ordering::Relaxed);
+    }
+
     /// Decide how to route a message
     pub fn decide(
         &self,
@@ -46,7 +53,7 @@ impl RoutingEngine {
         }
 
         // Check hop count limit
-        if hop_count > self.max_hop_count {
+        if hop_count > self.max_hop_count.load(Ordering::Relaxed) {
             return RoutingDecision::Reject {
                 reason: "Max hop count exceeded".to_string(),
             };
@@ -59,8 +66,22 @@ impl RoutingEngine {
 
         // Determine which peers to forward to
         match message_type {
-            MessageType::Hello | MessageType::Heartbeat | MessageType::Error => {
-                // Don't forward session messages
+            MessageType::Hello
+            | MessageType::Heartbeat
+            | MessageType::Error
+            | MessageType::CdmRequest
+            | MessageType::CdmResponse
+            | MessageType::InterestUpdate
+            | MessageType::EnvelopeBatch
+            | MessageType::SyncDigest
+            | MessageType::Ack
+            | MessageType::DeliveryReceipt
+            | MessageType::ScreeningRequest
+            | MessageType::ScreeningResponse => {
+                // Don't forward session messages, queries, batches,
+                // screening work or receipts; a batch's envelopes are
+                // routed one by one, and receipts back toward their
+                // originator
                 RoutingDecision::Accept
             }
             MessageType::CdmAnnounce
@@ -85,19 +106,37 @@ impl RoutingEngine {
         }
     }
 
-    /// Check if a peer should receive a message type
-    pub fn should_forward_to_peer(
-        &self,
-        message_type: &MessageType,
-        accept_cdm: bool,
-        accept_object_state: bool,
-        accept_maneuver: bool,
-    ) -> bool {
-        match message_type {
-            MessageType::CdmAnnounce | MessageType::CdmWithdraw => accept_cdm,
-            MessageType::ObjectStateAnnounce | MessageType::ObjectStateWithdraw => accept_object_state,
-            MessageType::ManeuverIntent | MessageType::ManeuverStatus => accept_maneuver,
+    /// Check if a peer's policies let a message type through, to or from it
+    pub fn should_forward_to_peer(&self, message_type: &MessageType, policies: &PeerPolicies) -> bool {
+        let accepted = match message_type {
+            MessageType::CdmAnnounce | MessageType::CdmWithdraw => policies.accept_cdm,
+            MessageType::ObjectStateAnnounce | MessageType::ObjectStateWithdraw => policies.accept_object_state,
+            MessageType::ManeuverIntent | MessageType::ManeuverStatus => policies.accept_maneuver,
             _ => false,
+        };
+        accepted && !policies.block_message_types.contains(message_type)
+    }
+
+    /// Check if a peer with these interests wants an envelope
+    ///
+    /// Only CDM and object state announcements are filtered: a CDM is wanted
+    /// when either object is. Withdrawals carry no object details and always
+    /// pass, as does everything for a peer that advertised no interests.
+    pub fn matches_interests(&self, envelope: &Envelope, interests: Option<&Interests>) -> bool {
+        let Some(interests) = interests.filter(|i| !i.is_all()) else {
+            return true;
+        };
+        let wants = |object: &Value| {
+            let id = object.get("object_id").and_then(Value::as_str).unwrap_or_default();
+            let owner = object.get("owner_operator").and_then(Value::as_str);
+            interests.wants_object(id, owner)
+        };
+        match envelope.message_type {
+            MessageType::CdmAnnounce => ["object1", "object2"]
+                .iter()
+                .any(|side| envelope.payload.get(side).is_some_and(wants)),
+            MessageType::ObjectStateAnnounce => wants(&envelope.payload),
+            _ => true,
         }
     }
 }
@@ -112,13 +151,37 @@ mod tests {
             node: NodeConfig {
                 id: "node-1".to_string(),
                 name: "Test Node".to_string(),
+                mode: Default::default(),
             },
             server: ServerConfig::default(),
             api: ApiConfig::default(),
             peers: vec![],
+            policy_templates: Default::default(),
+            peer_groups: Default::default(),
             storage: StorageConfig::default(),
             logging: LoggingConfig::default(),
             protocol: ProtocolConfig::default(),
+            catalog: None,
+            dev: None,
+            pc: Default::default(),
+            readiness: Default::default(),
+            fusion: Default::default(),
+            fanout: Default::default(),
+            alerts: Default::default(),
+            notifications: Default::default(),
+            interests: Default::default(),
+            archive: None,
+            discovery: None,
+            compression: Default::default(),
+            peer_health: Default::default(),
+            ingest_quotas: Default::default(),
+            provenance: Default::default(),
+            stats: Default::default(),
+            validation: Default::default(),
+            identifiers: Default::default(),
+            telemetry: None,
+            ha: None,
+            screening: None,
         }
     }
 
@@ -155,6 +218,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_matches_interests() {
+        let engine = RoutingEngine::new(test_config());
+        let cdm = Envelope::new(
+            "node-2".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(crate::cdm::generate_demo_cdm()).unwrap(),
+        );
+        let object2 = cdm.payload["object2"]["object_id"].as_str().unwrap().to_string();
+        let interests = |patterns: &[&str]| Interests {
+            object_ids: patterns.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        };
+
+        assert!(engine.matches_interests(&cdm, None));
+        assert!(engine.matches_interests(&cdm, Some(&Interests::default())));
+        assert!(engine.matches_interests(&cdm, Some(&interests(&[&object2]))));
+        assert!(!engine.matches_interests(&cdm, Some(&interests(&["NORAD-0*"]))));
+
+        let withdraw = Envelope::new(
+            "node-2".to_string(),
+            MessageType::CdmWithdraw,
+            serde_json::json!({ "cdm_id": "CDM-1" }),
+        );
+        assert!(engine.matches_interests(&withdraw, Some(&interests(&["NORAD-0*"]))));
+    }
+
     #[test]
     fn test_no_forward_hello() {
         let engine = RoutingEngine::new(test_config());
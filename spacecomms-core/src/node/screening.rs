@@ -0,0 +1,377 @@
+//! Conjunction screening of the object catalog
+//!
+//! With a `screening` section configured, the node screens its object
+//! catalog on a timer and announces a CDM, as its own originator, for each
+//! close approach the policy of its orbital regime selects: inside the
+//! regime's screening volume, and with a Pc at or above `min_pc` or a miss
+//! distance at or below `max_miss_distance_m`. Pairs with a CDM are screened
+//! again every `emergency.interval_seconds` once their TCA is within
+//! `emergency.within_hours`; those CDMs are marked EMERGENCY, and a pair the
+//! re-screen no longer triggers on stops being re-screened.
+//! The shards of either kind of run are shared with
+//! `screening.worker_nodes`, as described in [`screen_shared`].
+
+use crate::cdm::{CdmObject, CdmRecord, ObjectRecord, PcMethods, RelativeState, ScreenType, ScreeningData};
+use crate::config::ScreeningConfig;
+use crate::node::{publish, screen_shared, AppState};
+use crate::orbit::{relative_rtn, CloseApproach, OrbitalRegime};
+use crate::protocol::{Envelope, MessageType, ObjectType, StateVector};
+use crate::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// How often a node without screening configured, or not leading, checks again
+const IDLE_CHECK: Duration = Duration::from_secs(60);
+
+/// The CDM a close approach becomes under the screening policy, if any
+///
+/// A payload is made the first object when only one of the two is one;
+/// Pc is computed with the node's default method when both objects carry a
+/// covariance, and is reported as 0 otherwise.
+pub fn screening_cdm(
+    approach: &CloseApproach,
+    objects: [&ObjectRecord; 2],
+    config: &ScreeningConfig,
+    methods: &PcMethods,
+    originator: &str,
+    screen_type: ScreenType,
+) -> Option<CdmRecord> {
+    let mut sides = [(objects[0], &approach.state1), (objects[1], &approach.state2)];
+    if sides[0].0.object_type != ObjectType::Payload && sides[1].0.object_type == ObjectType::Payload {
+        sides.swap(0, 1);
+    }
+    let policy = config.regimes.get(OrbitalRegime::of(approach));
+    let ([r, t, n], velocity) = relative_rtn(sides[0].1, sides[1].1)?;
+    if !policy.volume.contains([r, t, n]) {
+        return None;
+    }
+
+    let created = Utc::now();
+    let mut cdm = CdmRecord {
+        cdm_id: format!("CDM-{}-{}", created.format("%Y%m%d"), &Uuid::new_v4().simple().to_string()[..8].to_uppercase()),
+        creation_date: created,
+        originator: originator.to_string(),
+        message_for: sides[0].0.owner_operator.clone().unwrap_or_else(|| "UNKNOWN".to_string()),
+        tca: approach.tca,
+        miss_distance_m: approach.miss_distance_km * 1000.0,
+        collision_probability: 0.0,
+        object1: cdm_object(sides[0].0, sides[0].1),
+        object2: cdm_object(sides[1].0, sides[1].1),
+        relative_state: Some(RelativeState {
+            relative_position_r_m: r * 1000.0,
+            relative_position_t_m: t * 1000.0,
+            relative_position_n_m: n * 1000.0,
+            relative_velocity_r_m_s: velocity[0] * 1000.0,
+            relative_velocity_t_m_s: velocity[1] * 1000.0,
+            relative_velocity_n_m_s: velocity[2] * 1000.0,
+        }),
+        screening_data: Some(ScreeningData {
+            screen_type,
+            screen_volume_shape: Some("ELLIPSOID".to_string()),
+            hard_body_radius_m: Some(config.hard_body_radius_m),
+        }),
+        data_quality_score: None,
+        conjunction_category: None,
+        recommended_action: None,
+        organization: None,
+        involves_watched_asset: false,
+    };
+    let pc = methods.compute(&cdm, None).ok().map(|result| result.pc);
+    if !(pc.is_some_and(|pc| pc >= policy.min_pc) || cdm.miss_distance_m <= policy.max_miss_distance_m) {
+        return None;
+    }
+    cdm.collision_probability = pc.unwrap_or(0.0);
+    Some(cdm)
+}
+
+fn cdm_object(object: &ObjectRecord, state_vector: &StateVector) -> CdmObject {
+    CdmObject {
+        object_id: object.object_id.clone(),
+        object_name: object.object_name.clone(),
+        object_type: object.object_type.clone(),
+        owner_operator: object.owner_operator.clone(),
+        maneuverable: false,
+        rcs_size: object.rcs_size,
+        state_vector: state_vector.clone(),
+        covariance_rtm: object.covariance.clone(),
+    }
+}
+
+/// Object IDs of a pair in a fixed order
+fn pair(a: &str, b: &str) -> (String, String) {
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    (a.to_string(), b.to_string())
+}
+
+/// Screens the object catalog and announces the CDMs the policy selects
+#[derive(Default)]
+pub struct ScreeningScheduler {
+    /// Earliest upcoming TCA with a CDM for each pair of object IDs
+    tracked: Mutex<BTreeMap<(String, String), DateTime<Utc>>>,
+}
+
+impl ScreeningScheduler {
+    /// Screen the whole catalog over the next `window_hours`, returning the
+    /// CDMs announced
+    pub async fn routine(&self, state: &AppState, config: &ScreeningConfig) -> Result<Vec<CdmRecord>> {
+        let objects = state.storage.list_objects().await?;
+        let window = chrono::Duration::hours(config.window_hours as i64);
+        self.run(state, config, objects, window, ScreenType::Routine, None).await
+    }
+
+    /// Screen again the pairs with a CDM whose TCA is within
+    /// `emergency.within_hours`, returning the CDMs announced
+    pub async fn emergency(&self, state: &AppState, config: &ScreeningConfig) -> Result<Vec<CdmRecord>> {
+        let now = Utc::now();
+        let window = chrono::Duration::hours(config.emergency.within_hours as i64);
+        let due: BTreeSet<(String, String)> = match self.tracked.lock() {
+            Ok(mut tracked) => {
+                tracked.retain(|_, tca| *tca > now);
+                tracked.iter().filter(|(_, tca)| **tca <= now + window).map(|(pair, _)| pair.clone()).collect()
+            }
+            Err(_) => return Ok(Vec::new()),
+        };
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: BTreeSet<&str> = due.iter().flat_map(|(a, b)| [a.as_str(), b.as_str()]).collect();
+        let mut objects = Vec::new();
+        for id in ids {
+            objects.extend(state.storage.get_object(id).await?);
+        }
+        let cdms = self.run(state, config, objects, window, ScreenType::Emergency, Some(&due)).await?;
+
+        let triggered: BTreeSet<(String, String)> = cdms
+            .iter()
+            .map(|cdm| pair(&cdm.object1.object_id, &cdm.object2.object_id))
+            .collect();
+        if let Ok(mut tracked) = self.tracked.lock() {
+            for cleared in due.difference(&triggered) {
+                info!("Conjunction of {} and {} no longer triggers a CDM", cleared.0, cleared.1);
+                tracked.remove(cleared);
+            }
+        }
+        Ok(cdms)
+    }
+
+    /// Screen `objects`, keeping only the pairs in `only` when given
+    async fn run(
+        &self,
+        state: &AppState,
+        config: &ScreeningConfig,
+        objects: Vec<ObjectRecord>,
+        window: chrono::Duration,
+        screen_type: ScreenType,
+        only: Option<&BTreeSet<(String, String)>>,
+    ) -> Result<Vec<CdmRecord>> {
+        let started = Instant::now();
+        let options = config.options(window);
+        let screening = screen_shared(state, &config.worker_nodes, &objects, Utc::now(), &options).await;
+        for (object_id, reason) in &screening.skipped {
+            debug!("Screening skipped {}: {}", object_id, reason);
+        }
+
+        let by_id: HashMap<&str, &ObjectRecord> = objects.iter().map(|o| (o.object_id.as_str(), o)).collect();
+        let node_id = state.config.get().node.id.clone();
+        let mut announced = Vec::new();
+        for approach in &screening.approaches {
+            let key = pair(&approach.object1_id, &approach.object2_id);
+            if only.is_some_and(|only| !only.contains(&key)) {
+                continue;
+            }
+            let (Some(object1), Some(object2)) =
+                (by_id.get(approach.object1_id.as_str()), by_id.get(approach.object2_id.as_str()))
+            else {
+                continue;
+            };
+            let objects = [*object1, *object2];
+            let Some(cdm) = screening_cdm(approach, objects, config, &state.pc_methods, &node_id, screen_type.clone())
+            else {
+                continue;
+            };
+            let envelope = Envelope::new(node_id.clone(), MessageType::CdmAnnounce, serde_json::to_value(&cdm)?);
+            if publish(state, envelope).await {
+                if let Ok(mut tracked) = self.tracked.lock() {
+                    tracked.entry(key).and_modify(|tca| *tca = (*tca).min(cdm.tca)).or_insert(cdm.tca);
+                }
+                announced.push(cdm);
+            }
+        }
+        info!(
+            "{:?} screening of {} objects ({} bins, {} shards, {} pairs) found {} close approaches and announced {} CDMs in {:?}",
+            screen_type,
+            screening.objects,
+            screening.bins,
+            screening.shards,
+            screening.pairs,
+            screening.approaches.len(),
+            announced.len(),
+            started.elapsed()
+        );
+        Ok(announced)
+    }
+}
+
+/// Screen the catalog while a `screening` section is configured and this
+/// instance leads: routinely every `interval_seconds`, and the pairs nearing
+/// TCA every `emergency.interval_seconds` in between
+pub fn spawn_screening(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let scheduler = ScreeningScheduler::default();
+        let mut last_routine: Option<Instant> = None;
+        loop {
+            let config = state.config.get().screening.clone();
+            let Some(config) = config.filter(|_| state.leadership.is_leader()) else {
+                tokio::time::sleep(IDLE_CHECK).await;
+                continue;
+            };
+            let routine_due = last_routine.is_none_or(|at| at.elapsed() >= Duration::from_secs(config.interval_seconds));
+            let result = if routine_due {
+                last_routine = Some(Instant::now());
+                scheduler.routine(&state, &config).await
+            } else {
+                scheduler.emergency(&state, &config).await
+            };
+            if let Err(e) = result {
+                warn!("Screening failed: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(config.emergency.interval_seconds)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+    use crate::orbit::{propagate, PropagationModel, EARTH_MU_KM3_S2};
+    use crate::protocol::CovarianceRtn;
+
+    /// Circular orbit of `radius_km` through +x at `epoch`
+    fn circular(radius_km: f64, inclination_deg: f64, epoch: DateTime<Utc>) -> StateVector {
+        let v = (EARTH_MU_KM3_S2 / radius_km).sqrt();
+        let (s, c) = inclination_deg.to_radians().sin_cos();
+        StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(epoch),
+            x_km: radius_km,
+            y_km: 0.0,
+            z_km: 0.0,
+            vx_km_s: 0.0,
+            vy_km_s: v * c,
+            vz_km_s: v * s,
+        }
+    }
+
+    fn object(id: &str, object_type: ObjectType, state_vector: StateVector) -> ObjectRecord {
+        ObjectRecord {
+            object_id: id.to_string(),
+            object_name: id.to_string(),
+            object_type,
+            owner_operator: None,
+            rcs_size: None,
+            epoch: state_vector.epoch.unwrap(),
+            state_vector,
+            covariance: None,
+            source_node: "node-a".to_string(),
+            last_updated: Utc::now(),
+            organization: None,
+        }
+    }
+
+    /// Equatorial debris and a polar payload crossing over +x at `tca`,
+    /// `miss_km` apart radially
+    fn approach(miss_km: f64, tca: DateTime<Utc>) -> CloseApproach {
+        CloseApproach {
+            object1_id: "DEB-1".to_string(),
+            object2_id: "SAT-1".to_string(),
+            tca,
+            miss_distance_km: miss_km,
+            relative_speed_km_s: 10.7,
+            state1: circular(7000.0 + miss_km, 0.0, tca),
+            state2: circular(7000.0, 90.0, tca),
+        }
+    }
+
+    fn covariance(variance_km2: f64) -> CovarianceRtn {
+        serde_json::from_value(serde_json::json!({ "cr_r": variance_km2, "ct_t": variance_km2, "cn_n": variance_km2 }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_screening_cdm_policy() {
+        let tca = Utc::now() + chrono::Duration::hours(6);
+        let config: ScreeningConfig = serde_yaml::from_str("{}").unwrap();
+        let methods = PcMethods::default();
+        let mut debris = object("DEB-1", ObjectType::Debris, circular(7000.0, 0.0, tca));
+        let mut payload = object("SAT-1", ObjectType::Payload, circular(7000.0, 90.0, tca));
+        let cdm_for = |miss_km: f64, debris: &ObjectRecord, payload: &ObjectRecord| {
+            screening_cdm(&approach(miss_km, tca), [debris, payload], &config, &methods, "node-a", ScreenType::Routine)
+        };
+
+        // Within the LEO miss distance trigger; the payload comes first
+        let cdm = cdm_for(0.5, &debris, &payload).unwrap();
+        assert_eq!((cdm.object1.object_id.as_str(), cdm.object2.object_id.as_str()), ("SAT-1", "DEB-1"));
+        assert_eq!((cdm.originator.as_str(), cdm.tca, cdm.miss_distance_m), ("node-a", tca, 500.0));
+        assert_eq!(cdm.collision_probability, 0.0);
+        let relative = cdm.relative_state.as_ref().unwrap();
+        assert!((relative.relative_position_r_m - 500.0).abs() < 1e-6);
+        assert!(relative.relative_position_t_m.abs() < 1e-6 && relative.relative_position_n_m.abs() < 1e-6);
+        assert!(crate::cdm::validate_cdm(&cdm).is_ok());
+
+        // Outside the LEO volume's 2 km radial extent
+        assert!(cdm_for(3.0, &debris, &payload).is_none());
+
+        // Inside the volume but beyond the miss distance trigger: only a Pc
+        // at or above min_pc produces a CDM
+        assert!(cdm_for(1.5, &debris, &payload).is_none());
+        debris.covariance = Some(covariance(1.0));
+        payload.covariance = Some(covariance(1.0));
+        let cdm = cdm_for(1.5, &debris, &payload).unwrap();
+        assert!(cdm.collision_probability >= 1e-7, "{}", cdm.collision_probability);
+        debris.covariance = Some(covariance(1e-4));
+        payload.covariance = Some(covariance(1e-4));
+        assert!(cdm_for(1.5, &debris, &payload).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_routine_and_emergency_screening() {
+        let state = test_state("node-screen");
+        let mut config = (*state.config.get()).clone();
+        config.screening = Some(serde_yaml::from_str("{ window_hours: 1, workers: 2 }").unwrap());
+        state.config.replace(config);
+        let config = state.config.get().screening.clone().unwrap();
+
+        let now = Utc::now();
+        let tca = now + chrono::Duration::seconds(1000);
+        let arriving = |at_tca: StateVector| propagate(&at_tca, tca, now, PropagationModel::J2).unwrap();
+        state.storage.store_object(object("DEB-1", ObjectType::Debris, arriving(circular(7000.4, 0.0, tca)))).await.unwrap();
+        state.storage.store_object(object("SAT-1", ObjectType::Payload, arriving(circular(7000.0, 90.0, tca)))).await.unwrap();
+        state.storage.store_object(object("SAT-GEO", ObjectType::Payload, circular(42164.0, 0.0, now))).await.unwrap();
+
+        let scheduler = ScreeningScheduler::default();
+        let routine = scheduler.routine(&state, &config).await.unwrap();
+        assert_eq!(routine.len(), 1, "{:?}", routine);
+        let cdm = state.storage.get_cdm(&routine[0].cdm_id).await.unwrap().unwrap();
+        assert_eq!((cdm.object1.object_id.as_str(), cdm.originator.as_str()), ("SAT-1", "node-screen"));
+        assert!((cdm.tca - tca).num_milliseconds().abs() < 100);
+        assert!((cdm.miss_distance_m - 400.0).abs() < 1.0, "{}", cdm.miss_distance_m);
+        assert_eq!(cdm.screening_data.unwrap().screen_type, ScreenType::Routine);
+
+        // The pair is within a day of TCA, so it is re-screened
+        let emergency = scheduler.emergency(&state, &config).await.unwrap();
+        assert_eq!(emergency.len(), 1);
+        assert_eq!(emergency[0].screening_data.as_ref().unwrap().screen_type, ScreenType::Emergency);
+
+        // Once the debris has moved away the pair is dropped
+        state.storage.store_object(object("DEB-1", ObjectType::Debris, circular(7300.0, 0.0, now))).await.unwrap();
+        assert!(scheduler.emergency(&state, &config).await.unwrap().is_empty());
+        assert!(scheduler.tracked.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Browser-facing protections: CORS and security headers
+//!
+//! Cross-origin calls are refused unless `api.cors.allowed_origins` lists
+//! the caller's origin. Every response also carries the usual hardening
+//! headers unless `api.security_headers.enabled` is off, plus a
+//! `Content-Security-Policy` and `Strict-Transport-Security` when
+//! configured. Headers a handler set itself are left as they are.
+
+use crate::config::{ApiConfig, SecurityHeadersConfig};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use std::str::FromStr;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::server::IDEMPOTENT_REPLAY_HEADER;
+use super::trace::TRACE_HEADER;
+
+/// CORS layer for the API; entries were checked when the configuration
+/// was loaded
+pub(crate) fn cors_layer(api: &ApiConfig) -> CorsLayer {
+    let cors = &api.cors;
+    let origins = if cors.allowed_origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(cors.allowed_origins.iter().filter_map(|o| HeaderValue::from_str(o).ok()))
+    };
+    let methods: Vec<Method> = cors.allowed_methods.iter().filter_map(|m| Method::from_str(m).ok()).collect();
+    let headers: Vec<HeaderName> = cors.allowed_headers.iter().filter_map(|h| HeaderName::from_str(h).ok()).collect();
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .expose_headers([
+            HeaderName::from_static(IDEMPOTENT_REPLAY_HEADER),
+            HeaderName::from_static(TRACE_HEADER),
+        ])
+        .max_age(Duration::from_secs(cors.max_age_seconds))
+}
+
+/// Headers added to every response
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SecurityHeaders(Vec<(HeaderName, HeaderValue)>);
+
+impl SecurityHeaders {
+    pub(crate) fn new(config: &SecurityHeadersConfig) -> Self {
+        let mut headers = Vec::new();
+        if config.enabled {
+            headers.push((header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")));
+            headers.push((header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")));
+            headers.push((header::REFERRER_POLICY, HeaderValue::from_static("no-referrer")));
+        }
+        if let Some(policy) = config.content_security_policy.as_deref().and_then(|p| HeaderValue::from_str(p).ok()) {
+            headers.push((header::CONTENT_SECURITY_POLICY, policy));
+        }
+        if let Some(max_age) = config.hsts_max_age_seconds {
+            let value = HeaderValue::from_str(&format!("max-age={}", max_age)).expect("digits are a valid header");
+            headers.push((header::STRICT_TRANSPORT_SECURITY, value));
+        }
+        Self(headers)
+    }
+}
+
+/// Middleware adding [`SecurityHeaders`]
+pub(crate) async fn add_security_headers(
+    State(headers): State<SecurityHeaders>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    for (name, value) in &headers.0 {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+
+    #[tokio::test]
+    async fn test_cors_and_security_headers() {
+        let api: ApiConfig = serde_yaml::from_str(
+            "{ cors: { allowed_origins: ['https://ops.example.org'] }, \
+             security_headers: { content_security_policy: \"default-src 'self'\", hsts_max_age_seconds: 31536000 } }",
+        )
+        .unwrap();
+        let app = Router::new()
+            .route("/cdms", get(|| async { "[]" }))
+            .layer(middleware::from_fn_with_state(SecurityHeaders::new(&api.security_headers), add_security_headers))
+            .layer(cors_layer(&api));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+
+        let resp = client.get(format!("{}/cdms", base)).header("origin", "https://ops.example.org").send().await.unwrap();
+        assert_eq!(resp.headers()["access-control-allow-origin"], "https://ops.example.org");
+        assert_eq!(resp.headers()["x-content-type-options"], "nosniff");
+        assert_eq!(resp.headers()["x-frame-options"], "DENY");
+        assert_eq!(resp.headers()["content-security-policy"], "default-src 'self'");
+        assert_eq!(resp.headers()["strict-transport-security"], "max-age=31536000");
+
+        // Preflight for an authenticated call
+        let resp = client
+            .request(reqwest::Method::OPTIONS, format!("{}/cdms", base))
+            .header("origin", "https://ops.example.org")
+            .header("access-control-request-method", "DELETE")
+            .header("access-control-request-headers", "authorization")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.headers()["access-control-allow-methods"].to_str().unwrap().contains("DELETE"));
+        assert!(resp.headers()["access-control-allow-headers"].to_str().unwrap().contains("authorization"));
+
+        let resp = client.get(format!("{}/cdms", base)).header("origin", "https://evil.example.com").send().await.unwrap();
+        assert!(resp.headers().get("access-control-allow-origin").is_none());
+    }
+}
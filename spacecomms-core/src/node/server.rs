@@ -1,35 +1,126 @@
 //! HTTP server for SpaceComms node
 
-use crate::cdm::{parse_cdm, CdmRecord};
-use crate::config::Config;
-use crate::node::{PeerInfo, PeerManager, PeerStatus, RoutingEngine};
-use crate::storage::Storage;
-use crate::Result;
+use crate::catalog::{create_catalog, resolve_object, CatalogCache, ResolvedObject};
+use crate::cdm::{
+    canonicalize_cdm, check_object_id, check_quality_floor, check_rules, classify, conjunction_id, normalize_units, parse_omm, parse_opm, RuleViolation, score_covariance_quality, validate_cdm, CdmRecord, ConjunctionCategory, ConjunctionCdm, ConjunctionSummary, ObjectRecord, PcMethods,
+    PcResult, RecommendedAction, RiskTrend,
+};
+use crate::config::{Config, DeliveryGuarantee, NodeMode, PeerPolicies, RedactionPolicy};
+use crate::node::compression::compress_response;
+use crate::node::dashboard::{dashboard_index, dashboard_redirect, dashboard_script, dashboard_style};
+use crate::node::limits::{limit_request, ConnectionLimit, RequestLimits};
+use crate::node::read_only::refuse_writes;
+use crate::node::security::{add_security_headers, cors_layer, SecurityHeaders};
+use crate::node::{
+    answer_cdm_request, answer_screening_request, answer_sync_digest, simulate_routing, RoutingSimulation, RoutingSimulationRequest, build_topology, object_sources, ObjectSources, Topology, receive_receipt, send_receipt, authenticate, authenticate_peer, peer_credential, Leadership, LeadershipRole, LeadershipStatus, PeerHealth, BackgroundTasks, DeadLetter, DeadLetterFilter, DeadLetterMetrics, DeadLetterQueue, DeadLetterReason, DeliveryState, DeliveryTracker, PropagationStatus, tracked_cdm, cdm_organization, object_organization, query_peer, sync_with_peer, SyncReport, strip_local_fields, redact_envelope, reload_from_file, parse_range, spawn_session, CdmQueryReport, CdmEventLog, import_cdms, ImportLine, export_snapshot, import_snapshot, SnapshotImportReport, export_peering, import_peering, PeeringDocument, PeeringImportReport, CdmEventPage, DeliveryCallback, FanOut, FanOutMetrics, LaneStatus, PeerInfo, PeerManager, PeerSession, PeerStatus,
+    PipelineTrace, ReloadReport, Reloader, RoutingDecision, RoutingEngine, SessionEventKind, SharedConfig,
+    StageOutcome, TenantScope, Quarantine, QuarantinedCdm, Admission, OriginatorAnomaly, OriginatorGuard, OriginatorStatus, Alert, AlertBook, AlertChange, Notifier, trend_points, LatencySummary, SlaReport, SlaTracker, SLA_RETENTION_DAYS, AlertFilter, AlertState, TraceStore, Tracer, Transport, Caller, WatchedAsset, Watchlist, role_scopes, Scope, TOKENS_PATH, WHOAMI_PATH, norad_number, retag_cdms, NODE_ID_HEADER, PROTOCOL_ENDPOINT, TRACE_HEADER,
+};
+use crate::orbit::{predict, Prediction, PropagationModel};
+use crate::protocol::{
+    check_timestamp, correct_timestamp, parse_timestamp, AckPayload, CdmQuery, negotiate_version, validate_envelope, CdmWithdrawPayload, Encoding, InterestUpdatePayload, EnvelopeBatchPayload, EnvelopeLimits, Envelope, ErrorCode, ErrorPayload, HeartbeatPayload, HelloPayload,
+    ManeuverIntentPayload, ManeuverStatusPayload, ManeuverStatusType, ManeuverType, MessageType,
+    ObjectStateAnnouncePayload, ObjectStateWithdrawPayload, StateVector, TimestampFormat, VersionNegotiationResult, WithdrawReason,
+    CAPABILITY_ACK, CAPABILITY_GRPC_STREAM, CAPABILITY_INTERESTS, CAPABILITY_PAYLOAD_GZIP, CAPABILITY_SIGNING, DeliveryReceiptPayload, attest, verify_hop, ProvenanceHop,
+    allows, downgrade, parse_version,
+};
+use crate::storage::{
+    IdempotencyClaim, IdempotentResponse, ArchiveEntry, ArchiveKind, ArchiveReason, ObjectStateChange, StateDiff, ArchivePage, ArchiveQuery, ApiTokenRecord, BlockedNode, FileArchive, Footprint, MemoryBudget, MemoryUsage, ObjectCapacity, QueueCharge, StatMetric, StatSample, Storage,
+};
+use crate::telemetry;
+use crate::{Error, Result};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{delete, get, post},
-    Json, Router,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE}, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post},
+    Extension, Json, Router,
 };
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tower_http::trace::TraceLayer;
-use tower_http::cors::{CorsLayer, Any};
-use tracing::info;
+use tokio_stream::StreamExt;
+use tower_http::trace::{DefaultMakeSpan, TraceLayer};
+use tracing::{debug, info, info_span, warn, Instrument, Level};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
 
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
-    config: Config,
-    storage: Arc<dyn Storage>,
-    peers: Arc<RwLock<PeerManager>>,
-    routing: Arc<RoutingEngine>,
-    start_time: chrono::DateTime<Utc>,
-    metrics: Arc<Metrics>,
+    pub(crate) config: SharedConfig,
+    pub(crate) storage: Arc<dyn Storage>,
+    pub(crate) peers: Arc<RwLock<PeerManager>>,
+    pub(crate) routing: Arc<RoutingEngine>,
+    pub(crate) start_time: chrono::DateTime<Utc>,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) catalog: Option<Arc<CatalogCache>>,
+    pub(crate) pc_methods: Arc<PcMethods>,
+    pub(crate) traces: Arc<TraceStore>,
+    pub(crate) memory: Arc<MemoryBudget>,
+    pub(crate) events: Arc<CdmEventLog>,
+    pub(crate) reloader: Arc<Reloader>,
+    pub(crate) archive: Option<Arc<FileArchive>>,
+    pub(crate) fanout: Arc<FanOut>,
+    pub(crate) watchlist: Arc<Watchlist>,
+    pub(crate) alerts: Arc<AlertBook>,
+    pub(crate) notifier: Arc<Notifier>,
+    pub(crate) tasks: Arc<BackgroundTasks>,
+    pub(crate) dead_letters: Arc<DeadLetterQueue>,
+    pub(crate) leadership: Arc<Leadership>,
+    pub(crate) sla: Arc<SlaTracker>,
+    pub(crate) originators: Arc<OriginatorGuard>,
+    pub(crate) quarantine: Arc<Quarantine>,
+    pub(crate) deliveries: Arc<DeliveryTracker>,
+}
+
+impl AppState {
+    /// HELLO payload describing this node
+    ///
+    /// Only features of `protocol.version` that are not disabled are offered.
+    pub(crate) fn local_hello(&self) -> HelloPayload {
+        let config = self.config.get();
+        let protocol = &config.protocol;
+        let mut hello = HelloPayload {
+            node_name: config.node.name.clone(),
+            protocol_version: protocol.version.clone(),
+            advertise_address: config.server.advertise_address.clone(),
+            ..Default::default()
+        };
+        hello.supported_versions.retain(|v| parse_version(v) <= parse_version(&protocol.version));
+        if let Some(grpc_port) = config.server.grpc_port {
+            hello.capabilities.push(CAPABILITY_GRPC_STREAM.to_string());
+            hello.grpc_port = Some(grpc_port);
+        }
+        hello.capabilities.retain(|c| allows(c, &protocol.version, &protocol.disabled_features));
+        if !config.interests.is_all() && hello.has_capability(CAPABILITY_INTERESTS) {
+            hello.interests = Some(config.interests.clone());
+        }
+        hello
+    }
+
+    /// Limits applied to envelopes received from peers
+    pub(crate) fn envelope_limits(&self) -> EnvelopeLimits {
+        EnvelopeLimits {
+            max_envelope_bytes: self.config.get().protocol.max_envelope_bytes,
+            max_payload_depth: self.config.get().protocol.max_payload_depth,
+        }
+    }
+
+    /// Timestamp profile for envelopes sent to a peer
+    pub(crate) async fn timestamp_format_for(&self, peer_id: Option<&str>) -> TimestampFormat {
+        let peers = self.peers.read().await;
+        peer_id
+            .and_then(|id| peers.get_peer(id))
+            .and_then(|peer| peer.timestamp_format)
+            .unwrap_or(self.config.get().protocol.timestamp_format)
+    }
 }
 
 /// Metrics counters
@@ -39,6 +130,24 @@ pub struct Metrics {
     pub messages_sent: AtomicU64,
     pub messages_received: AtomicU64,
     pub errors: AtomicU64,
+    pub replays_rejected: AtomicU64,
+    pub escalations: AtomicU64,
+    pub webhook_failures: AtomicU64,
+    pub notifications_sent: AtomicU64,
+    pub notification_failures: AtomicU64,
+    pub notifications_rate_limited: AtomicU64,
+    pub peers_quarantined: AtomicU64,
+    pub originator_anomalies: AtomicU64,
+    pub originator_refusals: AtomicU64,
+    pub cdms_quarantined: AtomicU64,
+    pub sync_rounds: AtomicU64,
+    pub sync_cdms_fetched: AtomicU64,
+    pub acks_received: AtomicU64,
+    pub redeliveries: AtomicU64,
+    pub deliveries_unacknowledged: AtomicU64,
+    pub receipts_sent: AtomicU64,
+    pub receipts_received: AtomicU64,
+    pub messages_blocked: AtomicU64,
 }
 
 impl Default for Metrics {
@@ -49,6 +158,24 @@ impl Default for Metrics {
             messages_sent: AtomicU64::new(0),
             messages_received: AtomicU64::new(0),
             errors: AtomicU64::new(0),
+            replays_rejected: AtomicU64::new(0),
+            escalations: AtomicU64::new(0),
+            webhook_failures: AtomicU64::new(0),
+            notifications_sent: AtomicU64::new(0),
+            notification_failures: AtomicU64::new(0),
+            notifications_rate_limited: AtomicU64::new(0),
+            peers_quarantined: AtomicU64::new(0),
+            originator_anomalies: AtomicU64::new(0),
+            originator_refusals: AtomicU64::new(0),
+            cdms_quarantined: AtomicU64::new(0),
+            sync_rounds: AtomicU64::new(0),
+            sync_cdms_fetched: AtomicU64::new(0),
+            acks_received: AtomicU64::new(0),
+            redeliveries: AtomicU64::new(0),
+            deliveries_unacknowledged: AtomicU64::new(0),
+            receipts_sent: AtomicU64::new(0),
+            receipts_received: AtomicU64::new(0),
+            messages_blocked: AtomicU64::new(0),
         }
     }
 }
@@ -66,9 +193,28 @@ impl NodeServer {
         peers: Arc<RwLock<PeerManager>>,
         routing: Arc<RoutingEngine>,
     ) -> Self {
+        let shared = SharedConfig::new(config.clone());
         Self {
             state: AppState {
-                config,
+                catalog: create_catalog(&config),
+                archive: None,
+                pc_methods: Arc::new(PcMethods::new(&config.pc.method).unwrap_or_default()),
+                traces: Arc::new(TraceStore::default()),
+                memory: storage.memory_budget().unwrap_or_default(),
+                events: Arc::new(CdmEventLog::default()),
+                reloader: Arc::new(Reloader::default()),
+                fanout: Arc::new(FanOut::new(shared.clone())),
+                watchlist: Arc::new(Watchlist::default()),
+                alerts: Arc::new(AlertBook::default()),
+                notifier: Arc::new(Notifier::default()),
+                tasks: Arc::new(BackgroundTasks::default()),
+                dead_letters: Arc::new(DeadLetterQueue::default()),
+                leadership: Arc::new(Leadership::for_config(&config)),
+                sla: Arc::new(SlaTracker::default()),
+                originators: Arc::new(OriginatorGuard::default()),
+                quarantine: Arc::new(Quarantine::default()),
+                deliveries: Arc::new(DeliveryTracker::default()),
+                config: shared,
                 storage,
                 peers,
                 routing,
@@ -78,92 +224,614 @@ impl NodeServer {
         }
     }
 
-    /// Run the server
-    pub async fn run(self) -> Result<()> {
-        // CORS layer for UI development
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any);
+    /// Take configuration reloads from a file and apply log level changes
+    pub fn with_reloader(mut self, reloader: Reloader) -> Self {
+        self.state.reloader = Arc::new(reloader);
+        self
+    }
+
+    /// Serve archive queries from `archive`, see [`crate::storage::create_archive`]
+    pub fn with_archive(mut self, archive: Option<Arc<FileArchive>>) -> Self {
+        self.state.archive = archive;
+        self
+    }
+
+    /// Shared state used by the HTTP handlers
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
 
-        let app = Router::new()
+    /// HTTP routes of the node API and protocol endpoint
+    pub fn router(&self) -> Router {
+        let config = self.state.config.get();
+        let limits = RequestLimits::new(&config.server);
+        Router::new()
             .route("/health", get(health))
+            .route("/health/live", get(liveness))
+            .route("/ui", get(dashboard_redirect))
+            .route("/ui/", get(dashboard_index))
+            .route("/ui/app.js", get(dashboard_script))
+            .route("/ui/style.css", get(dashboard_style))
+            .route("/health/ready", get(readiness))
             .route("/metrics", get(metrics))
+            .route("/stats/history", get(stats_history))
+            .route(WHOAMI_PATH, get(whoami))
+            .route(TOKENS_PATH, get(list_tokens))
+            .route(TOKENS_PATH, post(issue_token))
+            .route("/auth/tokens/:id", delete(revoke_token))
+            .route("/auth/tokens/:id/rotate", post(rotate_token))
             .route("/cdm", post(ingest_cdm))
             .route("/cdms", get(list_cdms))
+            .route("/cdms", delete(purge_cdms))
+            .route("/cdms/bulk", post(ingest_cdms_bulk))
+            .route("/cdm/import", post(import_cdm_archive))
             .route("/cdms/:id", get(get_cdm))
             .route("/cdms/:id", delete(withdraw_cdm))
+            .route("/cdms/:id/pc", get(compare_pc))
+            .route("/cdms/:id/pc", post(recompute_pc))
+            .route("/cdms/:id/trace", get(get_cdm_trace))
+            .route("/cdms/:id/provenance", get(get_cdm_provenance))
+            .route("/cdms/:id/propagation", get(get_cdm_propagation))
+            .route("/cdms/:id/receipts", get(get_cdm_receipts))
+            .route("/cdms/quarantine", get(list_quarantined_cdms))
+            .route("/cdms/quarantine/:id", delete(discard_quarantined_cdm))
+            .route("/cdms/quarantine/:id/release", post(release_quarantined_cdm))
+            .route("/conjunctions", get(list_conjunctions))
+            .route("/conjunctions/upcoming", get(upcoming_conjunctions))
+            .route("/conjunctions/:id/trend", get(conjunction_trend))
+            .route("/events/cdms", get(cdm_events))
+            .route("/archive/cdms", get(archived_cdms))
+            .route("/archive/objects", get(archived_objects))
             .route("/objects", get(list_objects))
+            .route("/objects/omm", post(ingest_omm))
+            .route("/objects/:id", delete(withdraw_object))
+            .route("/objects/:id/cdms", get(object_cdm_history))
+            .route("/objects/:id/state", get(object_state))
+            .route("/objects/:id/history", get(object_state_history))
+            .route("/catalog/resolve", get(resolve_catalog_object))
             .route("/peers", get(list_peers))
             .route("/peers", post(add_peer))
+            .route("/peers/:id", get(get_peer_detail))
             .route("/peers/:id", delete(remove_peer))
+            .route("/peers/:id/cdm-query", post(query_peer_cdms))
+            .route("/peers/:id/sync", post(sync_peer))
+            .route("/routing/simulate", post(simulate_route))
+            .route("/network/topology", get(network_topology))
+            .route("/network/objects/:id/sources", get(network_object_sources))
+            .route("/peers/:id/quarantine", delete(release_peer))
+            .route("/peers/:id/disable", post(disable_peer))
+            .route("/peers/:id/enable", post(enable_peer))
+            .route("/peers/:id/block", post(block_peer))
+            .route("/peers/:id/block", delete(unblock_peer))
+            .route("/peers/blocked", get(list_blocked_nodes))
+            .route("/peers/export", get(export_peers))
+            .route("/peers/import", post(import_peers))
+            .route("/peers/:id/sla", get(peer_sla))
+            .route("/peers/:id/policies", patch(update_peer_policies))
+            .route("/originators", get(list_originators))
+            .route("/originators/:id/hold", delete(release_originator))
+            .route("/watchlist", get(list_watchlist))
+            .route("/watchlist", post(register_assets))
+            .route("/watchlist/:id", delete(unregister_asset))
+            .route("/alerts", get(list_alerts))
+            .route("/alerts/:id", get(get_alert))
+            .route("/alerts/:id/acknowledge", post(acknowledge_alert))
+            .route("/alerts/:id/assign", post(assign_alert))
+            .route("/alerts/:id/resolve", post(resolve_alert))
             .route("/maneuvers", post(announce_maneuver))
-            .layer(cors)
-            .layer(TraceLayer::new_for_http())
-            .with_state(self.state.clone());
+            .route("/deadletter", get(list_dead_letters))
+            .route("/deadletter/:id", get(get_dead_letter))
+            .route("/deadletter/:id", delete(discard_dead_letter))
+            .route("/deadletter/:id/requeue", post(requeue_dead_letter))
+            .route("/admin/reload", post(reload_config))
+            .route("/export", get(export_node))
+            .route("/import", post(import_node))
+            .route(PROTOCOL_ENDPOINT, post(receive_message))
+            .merge(SwaggerUi::new("/docs").url("/openapi.json", openapi()))
+            .layer(middleware::from_fn_with_state(self.state.clone(), refuse_writes))
+            .layer(middleware::from_fn_with_state(limits, limit_request))
+            .layer(limits.body_limit())
+            .layer(middleware::from_fn_with_state(self.state.clone(), authenticate))
+            .layer(middleware::from_fn_with_state(self.state.clone(), compress_response))
+            .layer(cors_layer(&config.api))
+            .layer(middleware::from_fn_with_state(SecurityHeaders::new(&config.api.security_headers), add_security_headers))
+            .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::new().level(Level::INFO)))
+            .with_state(self.state.clone())
+    }
 
-        let addr = format!("{}:{}", self.state.config.server.host, self.state.config.server.port);
+    /// Run the server
+    pub async fn run(self) -> Result<()> {
+        let app = self.router();
+        let addr = format!("{}:{}", self.state.config.get().server.host, self.state.config.get().server.port);
         info!("Listening on {}", addr);
         info!("Dashboard available at http://{}/ui/", addr);
 
         let listener = tokio::net::TcpListener::bind(&addr).await?;
-        axum::serve(listener, app).await?;
+        let max_connections = self.state.config.get().server.max_connections;
+        axum::serve(listener, ConnectionLimit::new(app, max_connections)).await?;
 
         Ok(())
     }
 }
 
+// ============================================================================
+// OpenAPI document
+// ============================================================================
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "SpaceComms Node API",
+        description = "REST API of a SpaceComms node and its peer protocol endpoint"
+    ),
+    paths(
+        health,
+        liveness,
+        readiness,
+        metrics,
+        stats_history,
+        whoami,
+        list_tokens,
+        issue_token,
+        revoke_token,
+        rotate_token,
+        ingest_cdm,
+        ingest_cdms_bulk,
+        import_cdm_archive,
+        list_cdms,
+        purge_cdms,
+        get_cdm,
+        withdraw_cdm,
+        compare_pc,
+        recompute_pc,
+        get_cdm_trace,
+        get_cdm_provenance,
+        get_cdm_propagation,
+        get_cdm_receipts,
+        list_quarantined_cdms,
+        release_quarantined_cdm,
+        discard_quarantined_cdm,
+        list_conjunctions,
+        upcoming_conjunctions,
+        conjunction_trend,
+        cdm_events,
+        archived_cdms,
+        archived_objects,
+        list_objects,
+        ingest_omm,
+        withdraw_object,
+        object_cdm_history,
+        object_state,
+        object_state_history,
+        resolve_catalog_object,
+        list_peers,
+        add_peer,
+        get_peer_detail,
+        peer_sla,
+        remove_peer,
+        query_peer_cdms,
+        sync_peer,
+        simulate_route,
+        network_topology,
+        network_object_sources,
+        release_peer,
+        disable_peer,
+        enable_peer,
+        block_peer,
+        unblock_peer,
+        list_blocked_nodes,
+        update_peer_policies,
+        export_peers,
+        import_peers,
+        list_originators,
+        release_originator,
+        list_watchlist,
+        register_assets,
+        unregister_asset,
+        list_alerts,
+        get_alert,
+        acknowledge_alert,
+        assign_alert,
+        resolve_alert,
+        announce_maneuver,
+        list_dead_letters,
+        get_dead_letter,
+        discard_dead_letter,
+        requeue_dead_letter,
+        reload_config,
+        export_node,
+        import_node,
+        receive_message,
+    ),
+    tags(
+        (name = "health", description = "Liveness, readiness and counters"),
+        (name = "auth", description = "The caller's token and permissions"),
+        (name = "cdms", description = "CDM ingestion, lookup and withdrawal"),
+        (name = "conjunctions", description = "CDMs grouped by conjunction"),
+        (name = "events", description = "CDM change feed"),
+        (name = "archive", description = "Records moved out of the hot store"),
+        (name = "objects", description = "Tracked space objects"),
+        (name = "catalog", description = "External object catalog"),
+        (name = "peers", description = "Peer management"),
+        (name = "routing", description = "What the node would do with a message"),
+        (name = "network", description = "The mesh beyond this node's peers"),
+        (name = "originators", description = "Per-originator ingest quotas and holds"),
+        (name = "watchlist", description = "Assets this node's operator owns"),
+        (name = "alerts", description = "Conjunctions of watched assets awaiting an operator"),
+        (name = "maneuvers", description = "Maneuver announcements"),
+        (name = "deadletter", description = "Messages the node could not take in or pass on"),
+        (name = "admin", description = "Node administration"),
+        (name = "protocol", description = "Node-to-node envelopes"),
+    )
+)]
+struct ApiDoc;
+
+/// OpenAPI document for the node's HTTP API, served at `/openapi.json`
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
 // ============================================================================
 // Response types
 // ============================================================================
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct HealthResponse {
     status: String,
     node_id: String,
+    /// `full` or `read-only`
+    mode: NodeMode,
     uptime_seconds: i64,
     peers: PeerStats,
     objects_tracked: usize,
     cdms_active: usize,
+    memory: MemoryUsage,
+    /// This instance's part in leader election, with `ha` configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ha: Option<LeadershipStatus>,
     version: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+struct LivenessResponse {
+    status: String,
+    uptime_seconds: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReadinessResponse {
+    /// `ready` or `not_ready`
+    status: String,
+    checks: Vec<ReadinessCheck>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReadinessCheck {
+    name: String,
+    ok: bool,
+    detail: String,
+}
+
+#[derive(Serialize, ToSchema)]
 struct PeerStats {
     connected: usize,
     total: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 struct CdmIngestResponse {
     cdm_id: String,
     status: String,
     propagated_to: Vec<String>,
+    /// Validation rules the CDM failed with action `warn` or `quarantine`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<RuleViolation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<PipelineTrace>,
+}
+
+/// Most CDMs accepted in one bulk ingest
+const MAX_BULK_CDMS: usize = 1000;
+
+#[derive(Deserialize, ToSchema)]
+struct BulkIngestRequest {
+    /// Validated one by one, so a malformed CDM only fails its own entry
+    #[schema(value_type = Vec<CdmRecord>)]
+    cdms: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct BulkIngestResponse {
+    accepted: usize,
+    rejected: usize,
+    results: Vec<BulkIngestResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct BulkIngestResult {
+    /// Position in the request
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cdm_id: Option<String>,
+    status: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    propagated_to: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<RuleViolation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorResponse>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PurgeQuery {
+    /// Withdraw every CDM from this originator
+    originator: Option<String>,
+    #[serde(default = "default_purge_reason")]
+    reason: String,
+}
+
+fn default_purge_reason() -> String {
+    "purged".to_string()
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PurgeResponse {
+    originator: String,
+    reason: String,
+    withdrawn: usize,
+    cdm_ids: Vec<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventsQuery {
+    /// First sequence number wanted; only new events when omitted
+    since: Option<u64>,
+    /// Seconds to wait for an event before returning an empty page
+    #[serde(default = "default_events_timeout")]
+    timeout_seconds: u64,
+    /// Only events about CDMs involving a watched asset
+    #[serde(default)]
+    watched: bool,
+}
+
+fn default_events_timeout() -> u64 {
+    30
+}
+
+/// Upper bound on how long one events poll is held open
+const MAX_EVENTS_TIMEOUT_SECONDS: u64 = 60;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ArchiveParams {
+    /// Archived at or after
+    since: Option<chrono::DateTime<Utc>>,
+    /// Archived before
+    until: Option<chrono::DateTime<Utc>>,
+    object_id: Option<String>,
+    #[serde(default = "default_archive_limit")]
+    limit: usize,
+}
+
+fn default_archive_limit() -> usize {
+    1000
+}
+
+/// Largest page one archive query returns
+const MAX_ARCHIVE_RESULTS: usize = 10_000;
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CdmListQuery {
+    /// Only CDMs involving this object (either side), in any form it is
+    /// canonicalized from
+    object_id: Option<String>,
+    /// Only CDMs from this originator
+    originator: Option<String>,
+    /// Only CDMs at or above this collision probability
+    min_probability: Option<f64>,
+    /// Only CDMs that do (`true`) or do not (`false`) involve a watched asset
+    watched: Option<bool>,
+}
+
+impl CdmListQuery {
+    fn matches(&self, cdm: &CdmRecord) -> bool {
+        self.object_id
+            .as_deref()
+            .is_none_or(|id| cdm.object1.object_id == id || cdm.object2.object_id == id)
+            && self.originator.as_deref().is_none_or(|o| cdm.originator == o)
+            && self.min_probability.is_none_or(|pc| cdm.collision_probability >= pc)
+            && self.watched.is_none_or(|watched| cdm.involves_watched_asset == watched)
+    }
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AlertListQuery {
+    /// Only alerts in this state
+    state: Option<AlertState>,
+    /// Only alerts at or above this severity
+    min_severity: Option<ConjunctionCategory>,
+    /// Only alerts assigned to this person or token
+    assignee: Option<String>,
+    /// Only the alert for this CDM
+    cdm_id: Option<String>,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeadLetterQuery {
+    reason: Option<DeadLetterReason>,
+    /// Peer the message came from or was bound for
+    peer_id: Option<String>,
+    message_type: Option<MessageType>,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ObjectHistoryQuery {
+    /// Only states with an epoch at or after this
+    from: Option<String>,
+    /// Only states with an epoch at or before this
+    to: Option<String>,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ObjectStateQuery {
+    /// Epoch to predict the state at; now when omitted
+    at: Option<String>,
+    /// Force model, `j2` by default
+    #[serde(default)]
+    #[param(inline)]
+    model: PropagationModel,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CatalogResolveQuery {
+    /// Object name, NORAD catalog number or international designator
+    query: String,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IngestQuery {
+    /// Capture a pipeline trace for this CDM
+    #[serde(default)]
+    trace: bool,
+}
+
+#[derive(Serialize, ToSchema)]
 struct CdmListResponse {
     cdms: Vec<CdmSummary>,
     total: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct CdmSummary {
     cdm_id: String,
+    /// Conjunction the CDM belongs to, as `GET /conjunctions` lists it
+    conjunction_id: String,
     tca: chrono::DateTime<Utc>,
     miss_distance_m: f64,
     collision_probability: f64,
     object1_id: String,
     object2_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conjunction_category: Option<ConjunctionCategory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommended_action: Option<RecommendedAction>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    involves_watched_asset: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ConjunctionListResponse {
+    conjunctions: Vec<ConjunctionSummary>,
+    total: usize,
+}
+
+/// Order of upcoming conjunctions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum UpcomingOrder {
+    /// Soonest TCA first, the riskier first on ties
+    #[default]
+    Tca,
+    /// Highest category first, then highest probability, then soonest
+    Risk,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct UpcomingQuery {
+    /// How far ahead to look, such as `6h` or `3d`; `24h` by default
+    window: Option<String>,
+    /// `tca` (default) or `risk`
+    #[serde(default)]
+    #[param(inline)]
+    sort: UpcomingOrder,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct UpcomingConjunction {
+    #[serde(flatten)]
+    summary: ConjunctionSummary,
+    /// TCA of the latest CDM
+    tca: chrono::DateTime<Utc>,
+    seconds_to_tca: i64,
+    /// Time to TCA for display, such as `5h 12m`
+    countdown: String,
+    /// Fused figure when there is one, otherwise the latest CDM's
+    collision_probability: f64,
+    miss_distance_m: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conjunction_category: Option<ConjunctionCategory>,
+    /// Watched assets on either side
+    watched_assets: Vec<WatchedAsset>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct UpcomingConjunctionsResponse {
+    generated_at: chrono::DateTime<Utc>,
+    /// End of the window
+    until: chrono::DateTime<Utc>,
+    conjunctions: Vec<UpcomingConjunction>,
+    total: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ConjunctionTrendResponse {
+    conjunction_id: String,
+    /// `object1/object2@bucket`
+    conjunction_key: String,
+    trend: RiskTrend,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SlaQuery {
+    /// How far back to report, such as `24h` or `30d`; `30d` by default and
+    /// at most `31d`
+    window: Option<String>,
+}
+
+/// Time to TCA as `2d 3h`, `5h 12m` or `7m`
+fn countdown(seconds: i64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PcComparisonResponse {
+    cdm_id: String,
+    reported_pc: f64,
+    default_method: String,
+    results: Vec<PcResult>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct PcRequest {
+    #[serde(default)]
+    method: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ObjectListResponse {
     objects: Vec<ObjectSummary>,
     total: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ObjectSummary {
     object_id: String,
     object_name: String,
@@ -171,46 +839,325 @@ struct ObjectSummary {
     last_updated: chrono::DateTime<Utc>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+struct ObjectCdmHistoryResponse {
+    object_id: String,
+    cdms: Vec<ObjectCdmEntry>,
+    active: usize,
+    withdrawn: usize,
+    total: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ObjectStateHistoryResponse {
+    object_id: String,
+    /// Oldest version first
+    states: Vec<ObjectStateVersion>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ObjectStateVersion {
+    #[serde(flatten)]
+    state: ObjectStateChange,
+    /// Difference from the previous version, if still kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<StateDiff>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ObjectStateResponse {
+    object_id: String,
+    object_name: String,
+    #[serde(flatten)]
+    prediction: Prediction,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ObjectCdmEntry {
+    /// "active" or "withdrawn"
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withdrawn_at: Option<chrono::DateTime<Utc>>,
+    /// The object on the other side of the conjunction
+    other_object_id: String,
+    other_object_name: String,
+    #[serde(flatten)]
+    cdm: ConjunctionCdm,
+}
+
+impl ObjectCdmEntry {
+    fn new(object_id: &str, cdm: &CdmRecord, withdrawn_at: Option<chrono::DateTime<Utc>>) -> Self {
+        let other = if cdm.object1.object_id == object_id { &cdm.object2 } else { &cdm.object1 };
+        Self {
+            status: if withdrawn_at.is_some() { "withdrawn" } else { "active" }.to_string(),
+            withdrawn_at,
+            other_object_id: other.object_id.clone(),
+            other_object_name: other.object_name.clone(),
+            cdm: cdm.into(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 struct PeerListResponse {
     peers: Vec<PeerInfo>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, ToSchema)]
+struct PeerDetailResponse {
+    #[serde(flatten)]
+    peer: PeerInfo,
+    session: PeerSessionView,
+}
+
+#[derive(Serialize, ToSchema)]
+struct PeerSessionView {
+    #[serde(flatten)]
+    session: PeerSession,
+    /// Seconds since the current link was established
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime_seconds: Option<u64>,
+    /// Envelopes handed to the link and not yet delivered
+    queue_depth: usize,
+    /// Forwarding queue and circuit breaker, once anything was forwarded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fanout: Option<LaneStatus>,
+}
+
+#[derive(Deserialize, ToSchema)]
 struct AddPeerRequest {
     peer_id: String,
     address: String,
     #[serde(default)]
     auth_token: Option<String>,
+    #[serde(default)]
+    transport: crate::config::PeerTransport,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    timestamp_format: Option<TimestampFormat>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct BlockPeerRequest {
+    /// Why the node is blocked, kept with the block
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BlockPeerResponse {
+    blocked: BlockedNode,
+    /// Envelopes queued for the peer that were dropped
+    purged: usize,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct DisablePeerRequest {
+    /// Note passed on to the peer, such as a maintenance window
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Policy changes for a peer; fields left out keep their value
+#[derive(Debug, Default, Deserialize, ToSchema)]
+struct UpdatePoliciesRequest {
+    #[serde(default)]
+    accept_cdm: Option<bool>,
+    #[serde(default)]
+    accept_object_state: Option<bool>,
+    #[serde(default)]
+    accept_maneuver: Option<bool>,
+    #[serde(default)]
+    forward_cdm: Option<bool>,
+    #[serde(default)]
+    serve_cdm_queries: Option<bool>,
+    #[serde(default)]
+    serve_screening: Option<bool>,
+    /// Replaces the blocked message types; an empty list clears them
+    #[serde(default)]
+    block_message_types: Option<Vec<MessageType>>,
+    #[serde(default)]
+    redact: Option<RedactionPolicy>,
+    /// Caps the TTL of envelopes to and from the peer; null removes the cap
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<u32>)]
+    max_ttl: Option<Option<u32>>,
+}
+
+/// Deserialize a field that is present, even as null, into `Some`
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl UpdatePoliciesRequest {
+    fn apply(self, policies: &mut PeerPolicies) {
+        let flags = [
+            (self.accept_cdm, &mut policies.accept_cdm),
+            (self.accept_object_state, &mut policies.accept_object_state),
+            (self.accept_maneuver, &mut policies.accept_maneuver),
+            (self.forward_cdm, &mut policies.forward_cdm),
+            (self.serve_cdm_queries, &mut policies.serve_cdm_queries),
+            (self.serve_screening, &mut policies.serve_screening),
+        ];
+        for (change, flag) in flags {
+            if let Some(value) = change {
+                *flag = value;
+            }
+        }
+        if let Some(types) = self.block_message_types {
+            policies.block_message_types = types;
+        }
+        if let Some(redact) = self.redact {
+            policies.redact = redact;
+        }
+        if let Some(max_ttl) = self.max_ttl {
+            policies.max_ttl = max_ttl;
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct AddPeerResponse {
     peer_id: String,
     status: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct RemovePeerResponse {
     peer_id: String,
     status: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct WithdrawCdmRequest {
     reason: String,
     #[serde(default)]
     superseded_by: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct WithdrawResponse {
     cdm_id: String,
     status: String,
     reason: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+struct WithdrawObjectRequest {
+    reason: WithdrawReason,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct WithdrawObjectResponse {
+    object_id: String,
+    status: String,
+    reason: WithdrawReason,
+    propagated_to: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct OmmIngestResponse {
+    accepted: usize,
+    rejected: usize,
+    results: Vec<OmmIngestResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct OmmIngestResult {
+    /// Position of the element set in the document
+    index: usize,
+    object_id: String,
+    status: String,
+    /// TEME state from SGP4 at the element epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_vector: Option<StateVector>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    propagated_to: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct WatchlistResponse {
+    assets: Vec<WatchedAsset>,
+    total: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RegisterAssetsRequest {
+    assets: Vec<AssetRegistration>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AssetRegistration {
+    /// NORAD catalog number, with or without the `NORAD-` prefix, or an
+    /// alias of one from `identifiers.aliases`
+    norad_id: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct WatchlistUpdateResponse {
+    /// Catalog numbers newly added or removed
+    changed: Vec<String>,
+    /// Active CDMs whose watched tag changed
+    retagged: usize,
+    /// Assets now on the watchlist
+    total: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AlertListResponse {
+    alerts: Vec<Alert>,
+    total: usize,
+    /// Alerts not yet acknowledged or resolved
+    open: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeadLetterListResponse {
+    total: usize,
+    /// Newest first
+    dead_letters: Vec<DeadLetter>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RequeueResponse {
+    id: String,
+    message_id: String,
+    /// Peers the message was queued for
+    forwarded_to: Vec<String>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct AcknowledgeAlertRequest {
+    /// Who acknowledged; the API token when omitted
+    #[serde(default)]
+    by: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AssignAlertRequest {
+    /// Person or token to assign; `null` unassigns
+    assignee: Option<String>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct ResolveAlertRequest {
+    /// Who resolved; the API token when omitted
+    #[serde(default)]
+    by: Option<String>,
+    #[serde(default)]
+    resolution: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
 struct ManeuverRequest {
     object_id: String,
     #[serde(default)]
@@ -220,20 +1167,39 @@ struct ManeuverRequest {
     maneuver_type: String,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct OpmManeuverQuery {
+    /// Object to announce the maneuver for; the OPM's `OBJECT_ID` when omitted
+    object_id: Option<String>,
+    related_cdm_id: Option<String>,
+    /// `COLLISION_AVOIDANCE` when `related_cdm_id` is set, `OTHER` otherwise
+    #[param(value_type = Option<String>)]
+    maneuver_type: Option<ManeuverType>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 struct ManeuverResponse {
     maneuver_id: String,
     status: String,
     propagated_to: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 struct ErrorResponse {
     error: String,
     message: String,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+struct TracedErrorResponse {
+    #[serde(flatten)]
+    error: ErrorResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<PipelineTrace>,
+}
+
+#[derive(Serialize, ToSchema)]
 struct MetricsResponse {
     active_peers: usize,
     cdms_announced: u64,
@@ -241,13 +1207,89 @@ struct MetricsResponse {
     messages_sent: u64,
     messages_received: u64,
     errors: u64,
+    /// Envelopes refused as stale, from the future or replayed on a link
+    replays_rejected: u64,
+    /// CDMs escalated by the TCA countdown
+    escalations: u64,
+    /// Escalation webhook deliveries that failed
+    webhook_failures: u64,
+    /// Alert notifications delivered to a channel
+    notifications_sent: u64,
+    /// Alert notifications a channel failed to take
+    notification_failures: u64,
+    /// Alert notifications held back by a channel's hourly limit
+    notifications_rate_limited: u64,
+    /// Times a peer was quarantined for its error rate
+    peers_quarantined: u64,
+    /// Times an originator was held for an ingest anomaly
+    originator_anomalies: u64,
+    /// CDMs refused for their originator's quota or hold
+    originator_refusals: u64,
+    /// CDMs quarantined by a validation rule
+    cdms_quarantined: u64,
+    /// CDMs held in quarantine now
+    quarantine_held: usize,
+    /// Digest exchanges completed with peers
+    sync_rounds: u64,
+    /// CDMs stored after a digest exchange found them missing
+    sync_cdms_fetched: u64,
+    /// ACKs received from peers for forwarded CDM announcements and withdrawals
+    acks_received: u64,
+    /// Envelopes sent to a peer again for want of its ACK
+    redeliveries: u64,
+    /// Envelopes dead-lettered unacknowledged after every redelivery
+    deliveries_unacknowledged: u64,
+    /// DELIVERY_RECEIPTs sent for CDMs involving a watched object
+    receipts_sent: u64,
+    /// DELIVERY_RECEIPTs recorded on CDMs this node originated
+    receipts_received: u64,
+    /// Envelopes refused for coming from, or through, a blocked node
+    messages_blocked: u64,
+    /// Origin to receipt of the last 100 CDMs received from peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cdm_propagation: Option<LatencySummary>,
+    /// Mean heartbeat round trip to each peer that has echoed one
+    peer_round_trip_ms: BTreeMap<String, f64>,
     uptime_seconds: i64,
+    object_catalog: ObjectCapacity,
+    memory: MemoryUsage,
+    fanout: FanOutMetrics,
+    dead_letters: DeadLetterMetrics,
+}
+
+/// Query parameters for the statistics history
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsHistoryQuery {
+    /// Metric to return samples of
+    metric: StatMetric,
+    /// How far back to look, such as `30m`, `24h` or `7d` (default `1h`)
+    range: Option<String>,
+}
+
+/// Samples of one metric over a range
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsHistoryResponse {
+    metric: StatMetric,
+    range_seconds: i64,
+    /// Seconds between samples, 0 when recording is disabled
+    sample_interval_seconds: u64,
+    /// Oldest first
+    samples: Vec<StatSample>,
 }
 
 // ============================================================================
 // Handlers
 // ============================================================================
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "Node status", body = HealthResponse),
+    )
+)]
 async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
     let peers = state.peers.read().await;
     let cdm_count = state.storage.cdm_count().await.unwrap_or(0);
@@ -256,7 +1298,8 @@ async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
 
     Json(HealthResponse {
         status: "healthy".to_string(),
-        node_id: state.config.node.id.clone(),
+        node_id: state.config.get().node.id.clone(),
+        mode: state.config.get().node.mode,
         uptime_seconds: uptime.num_seconds(),
         peers: PeerStats {
             connected: peers.connected_count(),
@@ -264,13 +1307,114 @@ async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
         },
         objects_tracked: object_count,
         cdms_active: cdm_count,
+        memory: state.memory.usage(),
+        ha: state.leadership.status(),
         version: env!("CARGO_PKG_VERSION").to_string(),
     })
 }
 
+/// Liveness probe: answers whenever the server is serving requests
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses(
+        (status = 200, description = "Serving requests", body = LivenessResponse),
+    )
+)]
+async fn liveness(State(state): State<AppState>) -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "alive".to_string(),
+        uptime_seconds: (Utc::now() - state.start_time).num_seconds(),
+    })
+}
+
+/// Readiness probe: 503 until the configured criteria are met
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready", body = ReadinessResponse),
+        (status = 503, description = "Not ready", body = ReadinessResponse),
+    )
+)]
+async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let criteria = state.config.get().readiness.clone();
+    let mut checks = Vec::new();
+
+    if criteria.check_storage {
+        let (ok, detail) = match state.storage.cdm_count().await {
+            Ok(count) => (true, format!("{} CDMs stored", count)),
+            Err(e) => (false, e.to_string()),
+        };
+        checks.push(ReadinessCheck {
+            name: "storage".to_string(),
+            ok,
+            detail,
+        });
+    }
+
+    if criteria.min_connected_peers > 0 {
+        let connected = state.peers.read().await.connected_count();
+        checks.push(ReadinessCheck {
+            name: "peers".to_string(),
+            ok: connected >= criteria.min_connected_peers,
+            detail: format!("{} of {} required peers connected", connected, criteria.min_connected_peers),
+        });
+    }
+
+    if criteria.check_config {
+        let last_error = state.reloader.last_error();
+        checks.push(ReadinessCheck {
+            name: "config".to_string(),
+            ok: last_error.is_none(),
+            detail: last_error.map_or("configuration valid".to_string(), |e| format!("last reload failed: {}", e)),
+        });
+    }
+
+    // Followers stay out of the load balancer until they lead
+    if let Some(ha) = state.leadership.status() {
+        let detail = match (&ha.role, &ha.leader) {
+            (LeadershipRole::Leader, _) => format!("instance {} leads", ha.instance_id),
+            (LeadershipRole::Follower, Some(leader)) => format!("instance {} follows {}", ha.instance_id, leader),
+            (LeadershipRole::Follower, None) => format!("instance {} follows, no leader known", ha.instance_id),
+        };
+        checks.push(ReadinessCheck {
+            name: "leader".to_string(),
+            ok: ha.role == LeadershipRole::Leader,
+            detail,
+        });
+    }
+
+    let ready = checks.iter().all(|c| c.ok);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = ReadinessResponse {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        checks,
+    };
+    (status, Json(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Counters", body = MetricsResponse),
+    )
+)]
 async fn metrics(State(state): State<AppState>) -> Json<MetricsResponse> {
     let peers = state.peers.read().await;
     let uptime = Utc::now() - state.start_time;
+    let peer_round_trip_ms = peers
+        .list_peers()
+        .iter()
+        .filter_map(|peer| {
+            let round_trip = peers.session(&peer.id)?.latency.round_trip?;
+            Some((peer.id.clone(), round_trip.mean_ms))
+        })
+        .collect();
 
     Json(MetricsResponse {
         active_peers: peers.connected_count(),
@@ -279,124 +1423,461 @@ async fn metrics(State(state): State<AppState>) -> Json<MetricsResponse> {
         messages_sent: state.metrics.messages_sent.load(Ordering::Relaxed),
         messages_received: state.metrics.messages_received.load(Ordering::Relaxed),
         errors: state.metrics.errors.load(Ordering::Relaxed),
+        replays_rejected: state.metrics.replays_rejected.load(Ordering::Relaxed),
+        escalations: state.metrics.escalations.load(Ordering::Relaxed),
+        webhook_failures: state.metrics.webhook_failures.load(Ordering::Relaxed),
+        notifications_sent: state.metrics.notifications_sent.load(Ordering::Relaxed),
+        notification_failures: state.metrics.notification_failures.load(Ordering::Relaxed),
+        notifications_rate_limited: state.metrics.notifications_rate_limited.load(Ordering::Relaxed),
+        peers_quarantined: state.metrics.peers_quarantined.load(Ordering::Relaxed),
+        originator_anomalies: state.metrics.originator_anomalies.load(Ordering::Relaxed),
+        originator_refusals: state.metrics.originator_refusals.load(Ordering::Relaxed),
+        cdms_quarantined: state.metrics.cdms_quarantined.load(Ordering::Relaxed),
+        quarantine_held: state.quarantine.len(),
+        sync_rounds: state.metrics.sync_rounds.load(Ordering::Relaxed),
+        sync_cdms_fetched: state.metrics.sync_cdms_fetched.load(Ordering::Relaxed),
+        acks_received: state.metrics.acks_received.load(Ordering::Relaxed),
+        redeliveries: state.metrics.redeliveries.load(Ordering::Relaxed),
+        deliveries_unacknowledged: state.metrics.deliveries_unacknowledged.load(Ordering::Relaxed),
+        receipts_sent: state.metrics.receipts_sent.load(Ordering::Relaxed),
+        receipts_received: state.metrics.receipts_received.load(Ordering::Relaxed),
+        messages_blocked: state.metrics.messages_blocked.load(Ordering::Relaxed),
+        cdm_propagation: peers.cdm_propagation(),
+        peer_round_trip_ms,
         uptime_seconds: uptime.num_seconds(),
+        object_catalog: state.storage.object_capacity().await.unwrap_or_default(),
+        memory: state.memory.usage(),
+        fanout: state.fanout.metrics(),
+        dead_letters: state.dead_letters.metrics(),
     })
 }
 
-async fn ingest_cdm(
-    State(state): State<AppState>,
-    Json(body): Json<serde_json::Value>,
-) -> std::result::Result<(StatusCode, Json<CdmIngestResponse>), (StatusCode, Json<ErrorResponse>)> {
-    // Parse and validate CDM
-    let cdm = parse_cdm(body).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "validation_failed".to_string(),
-                message: e.to_string(),
-            }),
-        )
-    })?;
+#[derive(Debug, Serialize, ToSchema)]
+struct WhoamiResponse {
+    /// False when the node does not check tokens
+    authenticated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organization: Option<String>,
+    roles: Vec<String>,
+    /// Effective scopes: the token's own plus those of its roles
+    permissions: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<chrono::DateTime<Utc>>,
+}
 
-    let cdm_id = cdm.cdm_id.clone();
-    info!("CDM received: {}", cdm_id);
-    info!("  TCA: {}", cdm.tca);
-    info!("  Miss distance: {}m", cdm.miss_distance_m);
-    info!("  Collision probability: {}", cdm.collision_probability);
+/// A token as listed; secrets are never shown after issue
+#[derive(Debug, Serialize, ToSchema)]
+struct TokenInfo {
+    id: String,
+    /// `config` for tokens from `api.auth.tokens`, `issued` otherwise
+    source: String,
+    /// `active`, `expired` or `revoked`
+    status: String,
+    permissions: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<chrono::DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issued_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<chrono::DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<chrono::DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotated_at: Option<chrono::DateTime<Utc>>,
+    /// End of the grace period of the secret replaced by the last rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_secret_expires_at: Option<chrono::DateTime<Utc>>,
+}
 
-    // Store CDM
-    state.storage.store_cdm(cdm).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "storage_error".to_string(),
-                message: e.to_string(),
-            }),
-        )
-    })?;
+impl TokenInfo {
+    fn issued(token: &ApiTokenRecord) -> Self {
+        let now = Utc::now();
+        Self {
+            id: token.id.clone(),
+            source: "issued".to_string(),
+            status: token.status(now).to_string(),
+            permissions: token.permissions.clone(),
+            roles: token.roles.clone(),
+            organization: token.organization.clone(),
+            created_at: Some(token.created_at),
+            issued_by: token.issued_by.clone(),
+            expires_at: token.expires_at,
+            revoked_at: token.revoked_at,
+            rotated_at: token.rotated_at,
+            previous_secret_expires_at: token.previous_secret_expires_at.filter(|at| *at > now),
+        }
+    }
+}
 
-    // Get peers for propagation (in real implementation, would forward)
-    let peers = state.peers.read().await;
-    let propagated_to: Vec<String> = peers
-        .list_peers()
+#[derive(Serialize, ToSchema)]
+struct TokenListResponse {
+    tokens: Vec<TokenInfo>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct IssueTokenRequest {
+    /// Generated when omitted
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    permissions: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    organization: Option<String>,
+    /// Lifetime; the token never expires when omitted
+    #[serde(default)]
+    expires_in_seconds: Option<u64>,
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+struct RotateTokenRequest {
+    /// How long the replaced secret keeps working
+    #[serde(default)]
+    grace_seconds: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct IssuedTokenResponse {
+    token: TokenInfo,
+    /// Shown only in this response; store it now
+    secret: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/whoami",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The caller's token, roles and effective permissions", body = WhoamiResponse),
+        (status = 401, description = "Missing or unknown token", body = ErrorResponse),
+    )
+)]
+async fn whoami(caller: Option<Extension<Caller>>) -> Json<WhoamiResponse> {
+    Json(match caller {
+        Some(Extension(caller)) => WhoamiResponse {
+            authenticated: true,
+            token_id: Some(caller.token_id),
+            organization: caller.organization,
+            roles: caller.roles,
+            permissions: caller.permissions,
+            expires_at: caller.expires_at,
+        },
+        // Without authentication every request is allowed
+        None => WhoamiResponse {
+            authenticated: false,
+            token_id: None,
+            organization: None,
+            roles: Vec::new(),
+            permissions: vec!["admin".to_string()],
+            expires_at: None,
+        },
+    })
+}
+
+/// Longest lifetime or grace period accepted for issued tokens (10 years)
+const MAX_TOKEN_SECONDS: u64 = 10 * 365 * 86400;
+
+fn token_error(status: StatusCode, error: &str, message: String) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message,
+        }),
+    )
+}
+
+/// Issuing and rotating hand out credentials, so they need the `admin`
+/// level itself; an endpoint scope on `/auth/tokens` is not enough
+fn require_admin(caller: &Option<Extension<Caller>>) -> std::result::Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match caller {
+        Some(Extension(caller)) if !caller.can("admin") => Err(token_error(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            format!("token {} lacks the admin permission", caller.token_id),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Random secret for an issued token
+fn generate_secret() -> Result<String> {
+    use base64::Engine;
+    use ring::rand::SecureRandom;
+    let mut bytes = [0u8; 32];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| Error::Internal("no randomness for token secret".to_string()))?;
+    Ok(format!("sct_{}", base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+fn token_lifetime(seconds: u64) -> std::result::Result<chrono::Duration, (StatusCode, Json<ErrorResponse>)> {
+    if seconds > MAX_TOKEN_SECONDS {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "validation_failed",
+            format!("{} seconds is longer than the maximum of {}", seconds, MAX_TOKEN_SECONDS),
+        ));
+    }
+    Ok(chrono::Duration::seconds(seconds as i64))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/tokens",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Configured and issued tokens, without secrets", body = TokenListResponse),
+    )
+)]
+async fn list_tokens(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<TokenListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let config = state.config.get();
+    let mut tokens: Vec<TokenInfo> = config
+        .api
+        .auth
+        .tokens
         .iter()
-        .filter(|p| p.status == PeerStatus::Connected)
-        .map(|p| p.id.clone())
+        .map(|token| TokenInfo {
+            id: token.id.clone(),
+            source: "config".to_string(),
+            status: "active".to_string(),
+            permissions: token.permissions.clone(),
+            roles: token.roles.clone(),
+            organization: token.organization.clone(),
+            created_at: None,
+            issued_by: None,
+            expires_at: None,
+            revoked_at: None,
+            rotated_at: None,
+            previous_secret_expires_at: None,
+        })
         .collect();
+    let issued = state
+        .storage
+        .list_api_tokens()
+        .await
+        .map_err(|e| token_error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string()))?;
+    tokens.extend(issued.iter().map(TokenInfo::issued));
+    Ok(Json(TokenListResponse { tokens }))
+}
 
-    info!("CDM accepted, would forward to {} peers", propagated_to.len());
+#[utoipa::path(
+    post,
+    path = "/auth/tokens",
+    tag = "auth",
+    request_body = IssueTokenRequest,
+    responses(
+        (status = 201, description = "Token issued; the secret is shown only here", body = IssuedTokenResponse),
+        (status = 400, description = "Unknown role or organization, or malformed permission", body = ErrorResponse),
+        (status = 403, description = "Caller lacks the admin level", body = ErrorResponse),
+        (status = 409, description = "Token ID in use", body = ErrorResponse),
+    )
+)]
+async fn issue_token(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Json(body): Json<IssueTokenRequest>,
+) -> std::result::Result<(StatusCode, Json<IssuedTokenResponse>), (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&caller)?;
+    let invalid = |message: String| token_error(StatusCode::BAD_REQUEST, "validation_failed", message);
+    let config = state.config.get();
+    let auth = &config.api.auth;
 
-    // Update metrics
-    state.metrics.cdms_announced.fetch_add(1, Ordering::Relaxed);
+    if body.permissions.is_empty() && body.roles.is_empty() {
+        return Err(invalid("a token needs at least one permission or role".to_string()));
+    }
+    if let Some(permission) = body.permissions.iter().find(|p| Scope::parse(p).is_none()) {
+        return Err(invalid(format!("invalid permission {:?}", permission)));
+    }
+    if let Some(role) = body.roles.iter().find(|r| role_scopes(auth, r).is_none()) {
+        return Err(invalid(format!("unknown role {}", role)));
+    }
+    if let Some(org) = body.organization.as_deref().filter(|org| config.api.organization(org).is_none()) {
+        return Err(invalid(format!("unknown organization {}", org)));
+    }
+    let id = match body.id {
+        Some(id) => {
+            let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+            if !valid {
+                return Err(invalid("token IDs use letters, digits, '.', '_' and '-'".to_string()));
+            }
+            id
+        }
+        None => format!("tok-{}", &uuid::Uuid::new_v4().simple().to_string()[..12]),
+    };
+    let storage_error = |e: Error| token_error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string());
+    let stored = state.storage.get_api_token(&id).await.map_err(storage_error)?;
+    if stored.is_some() || auth.tokens.iter().any(|t| t.id == id) {
+        return Err(token_error(StatusCode::CONFLICT, "already_exists", format!("token {} already exists", id)));
+    }
 
+    let now = Utc::now();
+    let expires_at = match body.expires_in_seconds {
+        Some(seconds) => Some(now + token_lifetime(seconds)?),
+        None => None,
+    };
+    let secret = generate_secret().map_err(storage_error)?;
+    let token = ApiTokenRecord {
+        id,
+        secret_hash: ApiTokenRecord::hash_secret(&secret),
+        permissions: body.permissions,
+        roles: body.roles,
+        organization: body.organization,
+        created_at: now,
+        issued_by: caller.map(|Extension(caller)| caller.token_id),
+        expires_at,
+        revoked_at: None,
+        rotated_at: None,
+        previous_secret_hash: None,
+        previous_secret_expires_at: None,
+    };
+    state.storage.store_api_token(token.clone()).await.map_err(storage_error)?;
+    info!("API token {} issued by {}", token.id, token.issued_by.as_deref().unwrap_or("unauthenticated caller"));
     Ok((
         StatusCode::CREATED,
-        Json(CdmIngestResponse {
-            cdm_id,
-            status: "accepted".to_string(),
-            propagated_to,
+        Json(IssuedTokenResponse {
+            token: TokenInfo::issued(&token),
+            secret,
         }),
     ))
 }
 
-async fn list_cdms(State(state): State<AppState>) -> Json<CdmListResponse> {
-    let cdms = state.storage.list_cdms().await.unwrap_or_default();
-    let summaries: Vec<CdmSummary> = cdms
-        .iter()
-        .map(|c| CdmSummary {
-            cdm_id: c.cdm_id.clone(),
-            tca: c.tca,
-            miss_distance_m: c.miss_distance_m,
-            collision_probability: c.collision_probability,
-            object1_id: c.object1.object_id.clone(),
-            object2_id: c.object2.object_id.clone(),
-        })
-        .collect();
-
-    Json(CdmListResponse {
-        total: summaries.len(),
-        cdms: summaries,
-    })
+/// An issued token, refusing configured and unknown IDs
+async fn stored_token(
+    state: &AppState,
+    id: &str,
+) -> std::result::Result<ApiTokenRecord, (StatusCode, Json<ErrorResponse>)> {
+    if state.config.get().api.auth.tokens.iter().any(|t| t.id == id) {
+        return Err(token_error(
+            StatusCode::CONFLICT,
+            "config_token",
+            format!("token {} is defined in the configuration; remove it there", id),
+        ));
+    }
+    match state.storage.get_api_token(id).await {
+        Ok(Some(token)) => Ok(token),
+        Ok(None) => Err(token_error(StatusCode::NOT_FOUND, "not_found", format!("Token not found: {}", id))),
+        Err(e) => Err(token_error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string())),
+    }
 }
 
-async fn get_cdm(
+#[utoipa::path(
+    delete,
+    path = "/auth/tokens/{id}",
+    tag = "auth",
+    params(("id" = String, Path, description = "Token ID")),
+    responses(
+        (status = 200, description = "Token revoked", body = TokenInfo),
+        (status = 404, description = "Unknown token", body = ErrorResponse),
+        (status = 409, description = "Token comes from the configuration", body = ErrorResponse),
+    )
+)]
+async fn revoke_token(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> std::result::Result<Json<CdmRecord>, (StatusCode, Json<ErrorResponse>)> {
-    match state.storage.get_cdm(&id).await {
-        Ok(Some(cdm)) => Ok(Json(cdm)),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "not_found".to_string(),
-                message: format!("CDM not found: {}", id),
-            }),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "storage_error".to_string(),
-                message: e.to_string(),
-            }),
-        )),
+) -> std::result::Result<Json<TokenInfo>, (StatusCode, Json<ErrorResponse>)> {
+    let mut token = stored_token(&state, &id).await?;
+    if token.revoked_at.is_none() {
+        token.revoked_at = Some(Utc::now());
+        state
+            .storage
+            .store_api_token(token.clone())
+            .await
+            .map_err(|e| token_error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string()))?;
+        info!("API token {} revoked", id);
     }
+    Ok(Json(TokenInfo::issued(&token)))
 }
 
-async fn withdraw_cdm(
+#[utoipa::path(
+    post,
+    path = "/auth/tokens/{id}/rotate",
+    tag = "auth",
+    params(("id" = String, Path, description = "Token ID")),
+    request_body = RotateTokenRequest,
+    responses(
+        (status = 200, description = "New secret; the old one works until the grace period ends", body = IssuedTokenResponse),
+        (status = 403, description = "Caller lacks the admin level", body = ErrorResponse),
+        (status = 404, description = "Unknown token", body = ErrorResponse),
+        (status = 409, description = "Token comes from the configuration, or is revoked or expired", body = ErrorResponse),
+    )
+)]
+async fn rotate_token(
     State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
     Path(id): Path<String>,
-    Json(body): Json<WithdrawCdmRequest>,
-) -> std::result::Result<Json<WithdrawResponse>, (StatusCode, Json<ErrorResponse>)> {
-    state.storage.withdraw_cdm(&id).await.map_err(|e| {
-        if e.is_not_found() {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: "not_found".to_string(),
-                    message: format!("CDM not found: {}", id),
-                }),
-            )
-        } else {
+    body: Option<Json<RotateTokenRequest>>,
+) -> std::result::Result<Json<IssuedTokenResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&caller)?;
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    let mut token = stored_token(&state, &id).await?;
+    let now = Utc::now();
+    let status = token.status(now);
+    if status != "active" {
+        return Err(token_error(StatusCode::CONFLICT, "token_inactive", format!("token {} is {}", id, status)));
+    }
+    let grace = token_lifetime(body.grace_seconds)?;
+
+    let secret = generate_secret()
+        .map_err(|e| token_error(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", e.to_string()))?;
+    let previous = std::mem::replace(&mut token.secret_hash, ApiTokenRecord::hash_secret(&secret));
+    token.rotated_at = Some(now);
+    (token.previous_secret_hash, token.previous_secret_expires_at) = if grace.is_zero() {
+        (None, None)
+    } else {
+        (Some(previous), Some(now + grace))
+    };
+    state
+        .storage
+        .store_api_token(token.clone())
+        .await
+        .map_err(|e| token_error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string()))?;
+    info!("API token {} rotated ({}s grace)", id, body.grace_seconds);
+    Ok(Json(IssuedTokenResponse {
+        token: TokenInfo::issued(&token),
+        secret,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/history",
+    tag = "health",
+    params(StatsHistoryQuery),
+    responses(
+        (status = 200, description = "Samples of the metric", body = StatsHistoryResponse),
+        (status = 400, description = "Invalid range", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn stats_history(
+    State(state): State<AppState>,
+    Query(query): Query<StatsHistoryQuery>,
+) -> std::result::Result<Json<StatsHistoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let range = query.range.as_deref().unwrap_or("1h");
+    let Some(duration) = parse_range(range) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "validation_failed".to_string(),
+                message: format!("invalid range {:?}: expected a number followed by s, m, h or d", range),
+            }),
+        ));
+    };
+    let samples = state
+        .storage
+        .stats_history(query.metric, Utc::now() - duration)
+        .await
+        .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
@@ -404,121 +1885,7048 @@ async fn withdraw_cdm(
                     message: e.to_string(),
                 }),
             )
-        }
-    })?;
+        })?;
+    Ok(Json(StatsHistoryResponse {
+        metric: query.metric,
+        range_seconds: duration.num_seconds(),
+        sample_interval_seconds: state.config.get().stats.sample_interval_seconds,
+        samples,
+    }))
+}
 
-    info!("CDM withdrawn: {} (reason: {})", id, body.reason);
+/// Whether the client asked for a pipeline trace via header
+fn trace_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(TRACE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1")
+}
 
-    Ok(Json(WithdrawResponse {
-        cdm_id: id,
-        status: "withdrawn".to_string(),
-        reason: body.reason,
-    }))
+/// Record a stage's result in the trace, if tracing
+fn trace_result<T>(tracer: &Option<Tracer>, stage: &str, started: Instant, result: &Result<T>) {
+    if let Some(tracer) = tracer {
+        let (outcome, detail) = match result {
+            Ok(_) => (StageOutcome::Ok, None),
+            Err(e) => (StageOutcome::Rejected, Some(e.to_string())),
+        };
+        tracer.record_timed(stage, outcome, detail, started.elapsed());
+    }
 }
 
-async fn list_objects(State(state): State<AppState>) -> Json<ObjectListResponse> {
-    let objects = state.storage.list_objects().await.unwrap_or_default();
-    let summaries: Vec<ObjectSummary> = objects
-        .iter()
-        .map(|o| ObjectSummary {
-            object_id: o.object_id.clone(),
-            object_name: o.object_name.clone(),
-            object_type: format!("{:?}", o.object_type),
-            last_updated: o.last_updated,
-        })
-        .collect();
+/// Header naming a client-chosen key that makes retried ingests safe
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
 
-    Json(ObjectListResponse {
-        total: summaries.len(),
-        objects: summaries,
-    })
+/// Header set on responses replayed for a repeated idempotency key
+pub const IDEMPOTENT_REPLAY_HEADER: &str = "idempotent-replayed";
+
+/// Longest idempotency key accepted
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Idempotency key of a request, if it sent a valid one
+fn idempotency_key(headers: &HeaderMap) -> std::result::Result<Option<String>, ErrorResponse> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => Ok(Some(key.to_string())),
+        _ => Err(ErrorResponse {
+            error: "invalid_idempotency_key".to_string(),
+            message: format!("{} must be 1 to {} visible ASCII characters", IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN),
+        }),
+    }
 }
 
-async fn list_peers(State(state): State<AppState>) -> Json<PeerListResponse> {
-    let peers = state.peers.read().await;
-    Json(PeerListResponse {
-        peers: peers.list_peers().to_vec(),
-    })
+/// Fingerprint of a request body, telling retries from key reuse
+fn body_fingerprint(body: &serde_json::Value) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    body.to_string().hash(&mut hasher);
+    hasher.finish()
 }
 
-async fn add_peer(
+#[utoipa::path(
+    post,
+    path = "/cdm",
+    tag = "cdms",
+    params(
+        IngestQuery,
+        ("idempotency-key" = Option<String>, Header, description = "Client-chosen key; a retry with the same key and body gets the original response without the CDM being forwarded again"),
+    ),
+    request_body = CdmRecord,
+    responses(
+        (status = 201, description = "CDM stored and propagated", body = CdmIngestResponse),
+        (status = 202, description = "CDM quarantined by a validation rule, awaiting an admin", body = CdmIngestResponse),
+        (status = 400, description = "Invalid CDM or idempotency key", body = TracedErrorResponse),
+        (status = 409, description = "A request with this idempotency key is still running", body = ErrorResponse),
+        (status = 422, description = "Idempotency key already used with a different body", body = ErrorResponse),
+        (status = 429, description = "Originator over its quota or held for an anomaly", body = TracedErrorResponse),
+        (status = 507, description = "Memory budget exhausted", body = TracedErrorResponse),
+        (status = 500, description = "Storage failure", body = TracedErrorResponse),
+    )
+)]
+async fn ingest_cdm(
     State(state): State<AppState>,
-    Json(body): Json<AddPeerRequest>,
-) -> (StatusCode, Json<AddPeerResponse>) {
-    let mut peers = state.peers.write().await;
-    
-    peers.add_peer(PeerInfo {
-        id: body.peer_id.clone(),
-        address: body.address,
-        status: PeerStatus::Connecting,
-        last_heartbeat: None,
-        messages_sent: 0,
-        messages_received: 0,
-        policies: Default::default(),
-    });
+    Query(query): Query<IngestQuery>,
+    caller: Option<Extension<Caller>>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let organization = caller.and_then(|Extension(caller)| caller.organization);
+    let organization = organization.as_deref();
+    let key = match idempotency_key(&headers) {
+        Ok(Some(key)) => key,
+        Ok(None) => return ingest_cdm_once(&state, &query, &headers, organization, body).await.into_response(),
+        Err(error) => return (StatusCode::BAD_REQUEST, Json(error)).into_response(),
+    };
 
-    info!("Peer added: {}", body.peer_id);
+    let ttl = Duration::from_secs(state.config.get().storage.idempotency_ttl_seconds);
+    let conflict = |status: StatusCode, error: &str, message: &str| {
+        let error = ErrorResponse {
+            error: error.to_string(),
+            message: format!("{}: {}", message, key),
+        };
+        (status, Json(error)).into_response()
+    };
+    match state.storage.claim_idempotency_key(&key, body_fingerprint(&body), ttl).await {
+        Ok(IdempotencyClaim::Claimed) => {}
+        Ok(IdempotencyClaim::Completed(response)) => {
+            debug!("Replaying response for idempotency key {}", key);
+            let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+            return (status, [(IDEMPOTENT_REPLAY_HEADER, "true")], Json(response.body)).into_response();
+        }
+        Ok(IdempotencyClaim::InProgress) => {
+            return conflict(StatusCode::CONFLICT, "idempotency_key_in_use", "a request with this key is still running")
+        }
+        Ok(IdempotencyClaim::Mismatch) => {
+            return conflict(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "idempotency_key_reused",
+                "key already used with a different body",
+            )
+        }
+        Err(e) => return conflict(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", &e.to_string()),
+    }
 
-    (
-        StatusCode::CREATED,
-        Json(AddPeerResponse {
-            peer_id: body.peer_id,
-            status: "connecting".to_string(),
-        }),
-    )
+    let result = ingest_cdm_once(&state, &query, &headers, organization, body).await;
+    // Server-side failures are not remembered, so a retry runs again
+    let recorded = match &result {
+        Ok((status, Json(response))) => serde_json::to_value(response).ok().map(|body| (*status, body)),
+        Err((status, Json(error))) if status.is_client_error() => {
+            serde_json::to_value(error).ok().map(|body| (*status, body))
+        }
+        Err(_) => None,
+    };
+    let recorded = recorded.map(|(status, body)| IdempotentResponse {
+        status: status.as_u16(),
+        body,
+    });
+    if let Err(e) = state.storage.complete_idempotency_key(&key, recorded).await {
+        warn!("Failed to record response for idempotency key {}: {}", key, e);
+    }
+    result.into_response()
 }
 
-async fn remove_peer(
-    State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> std::result::Result<Json<RemovePeerResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let mut peers = state.peers.write().await;
-    
-    if peers.remove_peer(&id) {
-        info!("Peer removed: {}", id);
-        Ok(Json(RemovePeerResponse {
-            peer_id: id,
-            status: "removed".to_string(),
-        }))
-    } else {
-        Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "not_found".to_string(),
-                message: format!("Peer not found: {}", id),
+/// Ingest one CDM from `POST /cdm`, with its trace if requested
+async fn ingest_cdm_once(
+    state: &AppState,
+    query: &IngestQuery,
+    headers: &HeaderMap,
+    organization: Option<&str>,
+    body: serde_json::Value,
+) -> std::result::Result<(StatusCode, Json<CdmIngestResponse>), (StatusCode, Json<TracedErrorResponse>)> {
+    let tracer = (query.trace || trace_requested(headers)).then(Tracer::new);
+    let accepted = accept_cdm(state, body, organization, &tracer).await.map_err(|(status, error)| {
+        (
+            status,
+            Json(TracedErrorResponse {
+                error,
+                trace: tracer.as_ref().map(Tracer::snapshot),
             }),
-        ))
+        )
+    })?;
+
+    if let Some(tracer) = &tracer {
+        state.traces.insert(&accepted.cdm_id, tracer.clone());
     }
+
+    Ok((
+        if accepted.quarantined { StatusCode::ACCEPTED } else { StatusCode::CREATED },
+        Json(CdmIngestResponse {
+            status: accepted.status().to_string(),
+            cdm_id: accepted.cdm_id,
+            propagated_to: accepted.propagated_to,
+            warnings: accepted.warnings,
+            trace: tracer.as_ref().map(Tracer::snapshot),
+        }),
+    ))
 }
 
-async fn announce_maneuver(
-    State(state): State<AppState>,
-    Json(body): Json<ManeuverRequest>,
-) -> (StatusCode, Json<ManeuverResponse>) {
-    let maneuver_id = format!("MNVR-{}-{}", 
-        Utc::now().format("%Y%m%d"),
-        &uuid::Uuid::new_v4().to_string()[..8].to_uppercase()
-    );
+/// Parse, validate, enrich and classify a submitted CDM, ready to store
+///
+/// The CDM belongs to `organization` when submitted with an organization's
+/// token. Also returns the warnings raised by the node's validation rules.
+pub(crate) async fn prepare_cdm(
+    state: &AppState,
+    mut body: serde_json::Value,
+    organization: Option<&str>,
+    tracer: &Option<Tracer>,
+) -> Result<(CdmRecord, Vec<RuleViolation>)> {
+    let started = Instant::now();
+    let parsed = normalize_units(&mut body)
+        .and_then(|()| serde_json::from_value::<CdmRecord>(body).map_err(Error::from));
+    trace_result(tracer, "parse", started, &parsed);
+    let mut cdm = parsed?;
+    if let Some(tracer) = &tracer {
+        tracer.set_cdm_id(&cdm.cdm_id);
+    }
 
-    info!("Maneuver intent announced: {}", maneuver_id);
-    info!("  Object: {}", body.object_id);
-    info!("  Planned start: {}", body.planned_start);
-    info!("  Type: {}", body.maneuver_type);
+    let config = state.config.get();
+    canonicalize_cdm(&mut cdm, &config.identifiers.aliases);
+    let started = Instant::now();
+    let validated = validate_cdm(&cdm).and_then(|()| {
+        score_covariance_quality(&mut cdm);
+        check_quality_floor(&cdm, config.protocol.min_data_quality)?;
+        check_rules(&cdm, &config.validation.rules, Utc::now())
+    });
+    trace_result(tracer, "validate", started, &validated);
+    let warnings = validated?;
 
-    let peers = state.peers.read().await;
-    let propagated_to: Vec<String> = peers
-        .list_peers()
-        .iter()
-        .filter(|p| p.status == PeerStatus::Connected)
-        .map(|p| p.id.clone())
-        .collect();
+    match &state.catalog {
+        Some(catalog) => {
+            let started = Instant::now();
+            catalog.enrich_cdm(&mut cdm).await;
+            trace_result(tracer, "enrich", started, &Ok(()));
+        }
+        None => {
+            if let Some(tracer) = &tracer {
+                tracer.record("enrich", StageOutcome::Skipped, Some("no catalog configured".into()));
+            }
+        }
+    }
 
-    (
-        StatusCode::CREATED,
-        Json(ManeuverResponse {
-            maneuver_id,
-            status: "announced".to_string(),
-            propagated_to,
+    classify(&mut cdm, &config.protocol.severity);
+    cdm.organization = organization.map(str::to_string).or_else(|| cdm_organization(&config.api, &cdm));
+    cdm.involves_watched_asset = state.watchlist.involves(&cdm);
+    Ok((cdm, warnings))
+}
+
+/// A CDM stored and announced, or quarantined, by [`accept_cdm`]
+struct AcceptedCdm {
+    cdm_id: String,
+    /// Peers it was announced to
+    propagated_to: Vec<String>,
+    warnings: Vec<RuleViolation>,
+    quarantined: bool,
+}
+
+impl AcceptedCdm {
+    fn status(&self) -> &'static str {
+        if self.quarantined {
+            "quarantined"
+        } else {
+            "accepted"
+        }
+    }
+}
+
+/// Parse, validate, enrich, store and announce one CDM, or quarantine it
+/// when it fails a quarantining rule
+///
+/// The CDM belongs to `organization` when ingested with an organization's
+/// token.
+async fn accept_cdm(
+    state: &AppState,
+    body: serde_json::Value,
+    organization: Option<&str>,
+    tracer: &Option<Tracer>,
+) -> std::result::Result<AcceptedCdm, (StatusCode, ErrorResponse)> {
+    let fail = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            ErrorResponse {
+                error: error.to_string(),
+                message,
+            },
+        )
+    };
+
+    let (cdm, warnings) = prepare_cdm(state, body, organization, tracer)
+        .await
+        .map_err(|e| fail(StatusCode::BAD_REQUEST, "validation_failed", e.to_string()))?;
+    admit_originator(state, &cdm).map_err(|e| fail(StatusCode::TOO_MANY_REQUESTS, "rate_limited", e.to_string()))?;
+    let cdm_id = cdm.cdm_id.clone();
+    if let Some(held) = QuarantinedCdm::for_violations(&cdm, &warnings, None, None) {
+        if let Some(tracer) = &tracer {
+            tracer.record("quarantine", StageOutcome::Skipped, Some(held.reasons()));
+        }
+        quarantine_cdm(state, held);
+        return Ok(AcceptedCdm {
+            cdm_id,
+            propagated_to: Vec::new(),
+            warnings,
+            quarantined: true,
+        });
+    }
+    let propagated_to = announce_cdm(state, cdm, tracer).await.map_err(|e| match e {
+        Error::QuotaExceeded(_) => fail(StatusCode::INSUFFICIENT_STORAGE, "quota_exceeded", e.to_string()),
+        Error::Json(_) => fail(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", e.to_string()),
+        e => fail(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string()),
+    })?;
+    Ok(AcceptedCdm {
+        cdm_id,
+        propagated_to,
+        warnings,
+        quarantined: false,
+    })
+}
+
+/// Hold a CDM for an admin's decision
+fn quarantine_cdm(state: &AppState, held: QuarantinedCdm) {
+    let source = held.peer_id.as_deref().unwrap_or("the API");
+    warn!("CDM {} from {} quarantined: {}", held.cdm.cdm_id, source, held.reasons());
+    state.metrics.cdms_quarantined.fetch_add(1, Ordering::Relaxed);
+    state.quarantine.hold(held);
+}
+
+/// Count a CDM against its originator's quota, refusing it over the quota
+/// or while the originator is held for an anomaly
+pub(crate) fn admit_originator(state: &AppState, cdm: &CdmRecord) -> Result<()> {
+    let config = state.config.get();
+    let admission = state.originators.admit(cdm, &config.ingest_quotas, Utc::now());
+    if let Admission::Held { anomaly, new: true } = &admission {
+        state.metrics.originator_anomalies.fetch_add(1, Ordering::Relaxed);
+        match anomaly.until {
+            Some(until) => warn!("Originator {} throttled until {}: {}", cdm.originator, until, anomaly.detail),
+            None => warn!("Originator {} quarantined until released: {}", cdm.originator, anomaly.detail),
+        }
+    }
+    if admission != Admission::Accepted {
+        state.metrics.originator_refusals.fetch_add(1, Ordering::Relaxed);
+    }
+    admission.into_result(&cdm.originator, &config.ingest_quotas)
+}
+
+/// Store a prepared CDM, log its event and announce it to connected peers,
+/// returning the peers it was forwarded to
+pub(crate) async fn announce_cdm(state: &AppState, cdm: CdmRecord, tracer: &Option<Tracer>) -> Result<Vec<String>> {
+    let cdm_id = cdm.cdm_id.clone();
+    info!("CDM received: {}", cdm_id);
+    info!("  TCA: {}", cdm.tca);
+    info!("  Miss distance: {}m", cdm.miss_distance_m);
+    info!("  Collision probability: {}", cdm.collision_probability);
+
+    if let Some(tracer) = &tracer {
+        let detail = match state.storage.get_cdm(&cdm_id).await {
+            Ok(Some(_)) => "replaces stored CDM with the same ID",
+            Ok(None) => "new CDM",
+            Err(_) => "storage lookup failed",
+        };
+        tracer.record("dedup", StageOutcome::Ok, Some(detail.to_string()));
+    }
+
+    let mut payload = serde_json::to_value(&cdm)?;
+    strip_local_fields(&mut payload);
+    let config = state.config.get();
+    let mut envelope = Envelope::new(config.node.id.clone(), MessageType::CdmAnnounce, payload);
+    attest(&mut envelope.provenance, &cdm_id, &envelope.payload, &config.node.id, config.provenance.signer().as_ref());
+
+    // Store CDM
+    let announced = cdm.clone();
+    let started = Instant::now();
+    let stored = state.storage.store_cdm(cdm).await;
+    trace_result(tracer, "store", started, &stored);
+    stored?;
+    state.storage.store_cdm_provenance(&cdm_id, envelope.provenance.clone()).await?;
+    state.events.announced(&announced);
+
+    // Announce to connected peers
+    let propagated_to = originate_traced(state, envelope, tracer.as_ref()).await;
+
+    info!("CDM accepted, forwarding to {} peers", propagated_to.len());
+
+    // Update metrics
+    state.metrics.cdms_announced.fetch_add(1, Ordering::Relaxed);
+
+    Ok(propagated_to)
+}
+
+#[utoipa::path(
+    post,
+    path = "/cdms/bulk",
+    tag = "cdms",
+    request_body = BulkIngestRequest,
+    responses(
+        (status = 200, description = "Per-CDM results", body = BulkIngestResponse),
+        (status = 413, description = "Too many CDMs", body = ErrorResponse),
+    )
+)]
+async fn ingest_cdms_bulk(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Json(body): Json<BulkIngestRequest>,
+) -> std::result::Result<Json<BulkIngestResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if body.cdms.len() > MAX_BULK_CDMS {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ErrorResponse {
+                error: "limit_exceeded".to_string(),
+                message: format!("{} CDMs in one request (max {})", body.cdms.len(), MAX_BULK_CDMS),
+            }),
+        ));
+    }
+
+    let organization = caller.and_then(|Extension(caller)| caller.organization);
+
+    // Each CDM stands alone; one bad record does not reject the batch
+    let mut results = Vec::with_capacity(body.cdms.len());
+    for (index, cdm) in body.cdms.into_iter().enumerate() {
+        let cdm_id = cdm.get("cdm_id").and_then(|v| v.as_str()).map(str::to_string);
+        results.push(match accept_cdm(&state, cdm, organization.as_deref(), &None).await {
+            Ok(accepted) => BulkIngestResult {
+                index,
+                status: accepted.status().to_string(),
+                cdm_id: Some(accepted.cdm_id),
+                propagated_to: accepted.propagated_to,
+                warnings: accepted.warnings,
+                error: None,
+            },
+            Err((_, error)) => BulkIngestResult {
+                index,
+                cdm_id,
+                status: "rejected".to_string(),
+                propagated_to: Vec::new(),
+                warnings: Vec::new(),
+                error: Some(error),
+            },
+        });
+    }
+
+    let accepted = results.iter().filter(|r| r.error.is_none()).count();
+    info!("Bulk ingest: {} accepted, {} rejected", accepted, results.len() - accepted);
+    Ok(Json(BulkIngestResponse {
+        accepted,
+        rejected: results.len() - accepted,
+        results,
+    }))
+}
+
+/// Import results buffered ahead of a slow reader before the import pauses
+const IMPORT_RESULT_BACKLOG: usize = 256;
+
+#[utoipa::path(
+    post,
+    path = "/cdm/import",
+    tag = "cdms",
+    request_body(
+        content = String,
+        description = "One CDM per line (NDJSON), optionally gzip compressed",
+        content_type = "application/x-ndjson"
+    ),
+    responses(
+        (status = 200, description = "One result per line as it is imported, then a summary (NDJSON)", body = ImportLine, content_type = "application/x-ndjson"),
+    )
+)]
+async fn import_cdm_archive(State(state): State<AppState>, caller: Option<Extension<Caller>>, body: Body) -> Response {
+    let organization = caller.and_then(|Extension(caller)| caller.organization);
+    let (tx, rx) = tokio::sync::mpsc::channel(IMPORT_RESULT_BACKLOG);
+    tokio::spawn(import_cdms(state, body, organization, tx));
+    let lines = tokio_stream::wrappers::ReceiverStream::new(rx).map(|line| {
+        let mut json = serde_json::to_vec(&line)?;
+        json.push(b'\n');
+        Ok::<_, serde_json::Error>(json)
+    });
+    ([(CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}/trace",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Pipeline trace", body = PipelineTrace),
+        (status = 404, description = "No trace kept", body = ErrorResponse),
+    )
+)]
+async fn get_cdm_trace(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PipelineTrace>, (StatusCode, Json<ErrorResponse>)> {
+    let visible = !scope.is_scoped()
+        || matches!(state.storage.get_cdm(&id).await, Ok(Some(cdm)) if scope.sees_cdm(&cdm));
+    state.traces.get(&id).filter(|_| visible).map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("No trace recorded for CDM: {}", id),
+            }),
+        )
+    })
+}
+
+/// A provenance hop and whether its signature checks out
+#[derive(Serialize, ToSchema)]
+struct ProvenanceHopView {
+    #[serde(flatten)]
+    hop: ProvenanceHop,
+    /// Whether the signature verifies against the node's trusted key over
+    /// the payload this node received; absent when the node has no trusted
+    /// key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verified: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ProvenanceResponse {
+    cdm_id: String,
+    /// Nodes the CDM passed through, originator first and this node last
+    hops: Vec<ProvenanceHopView>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}/provenance",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Path the CDM took to this node", body = ProvenanceResponse),
+        (status = 404, description = "No provenance kept", body = ErrorResponse),
+    )
+)]
+async fn get_cdm_provenance(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ProvenanceResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let visible = !scope.is_scoped()
+        || matches!(state.storage.get_cdm(&id).await, Ok(Some(cdm)) if scope.sees_cdm(&cdm));
+    let chain = match state.storage.get_cdm_provenance(&id).await {
+        Ok(Some(chain)) if visible => chain,
+        Ok(_) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "not_found".to_string(),
+                    message: format!("No provenance kept for CDM: {}", id),
+                }),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "storage_error".to_string(),
+                    message: e.to_string(),
+                }),
+            ))
+        }
+    };
+
+    let config = state.config.get();
+    let own_key = config.provenance.signer().map(|signer| signer.public_key());
+    // Signed hops must cover the payload this node recorded on receipt
+    let received = chain
+        .last()
+        .filter(|hop| hop.node_id == config.node.id)
+        .and_then(|hop| hop.payload_sha256.as_deref())
+        .unwrap_or_default();
+    let hops = chain
+        .iter()
+        .enumerate()
+        .map(|(index, hop)| {
+            let key = match config.provenance.trusted_keys.get(&hop.node_id) {
+                Some(key) => Some(key.as_str()),
+                None if hop.node_id == config.node.id => own_key.as_deref(),
+                None => None,
+            };
+            let previous = index.checked_sub(1).map(|i| &chain[i]);
+            ProvenanceHopView {
+                verified: key.map(|key| verify_hop(key, &id, received, hop, previous)),
+                hop: hop.clone(),
+            }
+        })
+        .collect();
+    Ok(Json(ProvenanceResponse { cdm_id: id, hops }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}/propagation",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Delivery of the CDM's latest announcement or withdrawal to peers", body = PropagationStatus),
+        (status = 404, description = "Not forwarded to any peer, or no longer tracked", body = ErrorResponse),
+    )
+)]
+async fn get_cdm_propagation(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PropagationStatus>, (StatusCode, Json<ErrorResponse>)> {
+    let visible = !scope.is_scoped()
+        || matches!(state.storage.get_cdm(&id).await, Ok(Some(cdm)) if scope.sees_cdm(&cdm));
+    state.deliveries.status(&id).filter(|_| visible).map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("No deliveries tracked for CDM: {}", id),
+            }),
+        )
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReceiptsResponse {
+    cdm_id: String,
+    /// Latest receipt from each node the CDM reached whose watchlist
+    /// matched, oldest first
+    receipts: Vec<DeliveryReceiptPayload>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}/receipts",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Delivery receipts recorded on a CDM this node holds", body = ReceiptsResponse),
+        (status = 404, description = "CDM not found", body = ErrorResponse),
+    )
+)]
+async fn get_cdm_receipts(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ReceiptsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let storage_error = |e: Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    };
+    match state.storage.get_cdm(&id).await {
+        Ok(Some(cdm)) if scope.sees_cdm(&cdm) => {}
+        Ok(_) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "not_found".to_string(),
+                    message: format!("CDM not found: {}", id),
+                }),
+            ))
+        }
+        Err(e) => return Err(storage_error(e)),
+    }
+    let receipts = state.storage.get_delivery_receipts(&id).await.map_err(storage_error)?;
+    Ok(Json(ReceiptsResponse { cdm_id: id, receipts }))
+}
+
+#[derive(Serialize, ToSchema)]
+struct QuarantineListResponse {
+    total: usize,
+    /// Newest first
+    cdms: Vec<QuarantinedCdm>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct QuarantineReleaseResponse {
+    cdm_id: String,
+    /// Peers the CDM was passed on to
+    propagated_to: Vec<String>,
+}
+
+fn quarantined_not_found(id: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "not_found".to_string(),
+            message: format!("CDM not quarantined: {}", id),
         }),
     )
 }
+
+#[utoipa::path(
+    get,
+    path = "/cdms/quarantine",
+    tag = "cdms",
+    responses(
+        (status = 200, description = "Quarantined CDMs, newest first", body = QuarantineListResponse),
+    )
+)]
+async fn list_quarantined_cdms(State(state): State<AppState>) -> Json<QuarantineListResponse> {
+    let cdms = state.quarantine.list();
+    Json(QuarantineListResponse { total: cdms.len(), cdms })
+}
+
+#[utoipa::path(
+    post,
+    path = "/cdms/quarantine/{id}/release",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "CDM stored and propagated", body = QuarantineReleaseResponse),
+        (status = 404, description = "CDM not quarantined", body = ErrorResponse),
+        (status = 409, description = "Release failed; the CDM stays quarantined", body = ErrorResponse),
+    )
+)]
+async fn release_quarantined_cdm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<QuarantineReleaseResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let held = state.quarantine.take(&id).ok_or_else(|| quarantined_not_found(&id))?;
+    match release(&state, &held).await {
+        Ok(propagated_to) => {
+            info!("Quarantined CDM {} released", id);
+            Ok(Json(QuarantineReleaseResponse { cdm_id: id, propagated_to }))
+        }
+        Err(e) => {
+            warn!("Release of quarantined CDM {} failed: {}", id, e);
+            state.quarantine.hold(held);
+            Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: "release_failed".to_string(),
+                    message: e.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Store a quarantined CDM and pass it on as it would have been: relayed
+/// under the sending peer's policies, or announced from this node
+async fn release(state: &AppState, held: &QuarantinedCdm) -> Result<Vec<String>> {
+    let Some(envelope) = &held.envelope else {
+        return announce_cdm(state, held.cdm.clone(), &None).await;
+    };
+    store_received_cdm(state, held.cdm.clone(), envelope).await?;
+    let Some(peer_id) = &held.peer_id else {
+        return Ok(originate(state, envelope.clone()).await);
+    };
+    let forward = state.peers.read().await.get_peer(peer_id).map(|p| p.policies.forward_cdm).unwrap_or(true);
+    Ok(if forward {
+        relay(state, envelope, peer_id).await
+    } else {
+        Vec::new()
+    })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/cdms/quarantine/{id}",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Quarantined CDM discarded", body = QuarantinedCdm),
+        (status = 404, description = "CDM not quarantined", body = ErrorResponse),
+    )
+)]
+async fn discard_quarantined_cdm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<QuarantinedCdm>, (StatusCode, Json<ErrorResponse>)> {
+    let held = state.quarantine.take(&id).ok_or_else(|| quarantined_not_found(&id))?;
+    info!("Quarantined CDM {} discarded", id);
+    Ok(Json(held))
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms",
+    tag = "cdms",
+    params(CdmListQuery),
+    responses(
+        (status = 200, description = "Active CDMs", body = CdmListResponse),
+    )
+)]
+async fn list_cdms(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(mut query): Query<CdmListQuery>,
+) -> Json<CdmListResponse> {
+    let cdms = state.storage.list_cdms().await.unwrap_or_default();
+    let config = state.config.get();
+    let bucket_seconds = config.storage.conjunction_bucket_seconds;
+    query.object_id = query.object_id.map(|id| config.identifiers.canonical(&id));
+    let summaries: Vec<CdmSummary> = cdms
+        .iter()
+        .filter(|c| scope.sees_cdm(c) && query.matches(c))
+        .map(|c| CdmSummary {
+            cdm_id: c.cdm_id.clone(),
+            conjunction_id: conjunction_id(&c.object1.object_id, &c.object2.object_id, c.tca, bucket_seconds),
+            tca: c.tca,
+            miss_distance_m: c.miss_distance_m,
+            collision_probability: c.collision_probability,
+            object1_id: c.object1.object_id.clone(),
+            object2_id: c.object2.object_id.clone(),
+            conjunction_category: c.conjunction_category.clone(),
+            recommended_action: c.recommended_action.clone(),
+            involves_watched_asset: c.involves_watched_asset,
+        })
+        .collect();
+
+    Json(CdmListResponse {
+        total: summaries.len(),
+        cdms: summaries,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/conjunctions",
+    tag = "conjunctions",
+    responses(
+        (status = 200, description = "Conjunctions, soonest first", body = ConjunctionListResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn list_conjunctions(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> std::result::Result<Json<ConjunctionListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let groups = state.storage.list_conjunctions().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })?;
+    let config = state.config.get();
+    let mut conjunctions: Vec<ConjunctionSummary> = groups
+        .into_iter()
+        .filter_map(|(key, mut cdms)| {
+            cdms.retain(|cdm| scope.sees_cdm(cdm));
+            ConjunctionSummary::new(key, &cdms, &config.fusion, &config.protocol.severity)
+        })
+        .collect();
+    // Soonest first
+    conjunctions.sort_by(|a, b| {
+        a.tca_bucket
+            .cmp(&b.tca_bucket)
+            .then_with(|| a.conjunction_key.cmp(&b.conjunction_key))
+    });
+    Ok(Json(ConjunctionListResponse {
+        total: conjunctions.len(),
+        conjunctions,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/conjunctions/upcoming",
+    tag = "conjunctions",
+    params(UpcomingQuery),
+    responses(
+        (status = 200, description = "Conjunctions with TCA inside the window", body = UpcomingConjunctionsResponse),
+        (status = 400, description = "Invalid window", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn upcoming_conjunctions(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<UpcomingQuery>,
+) -> std::result::Result<Json<UpcomingConjunctionsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let window = query.window.as_deref().unwrap_or("24h");
+    let Some(span) = parse_range(window) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "validation_failed".to_string(),
+                message: format!("invalid window {:?}: expected a number followed by s, m, h or d", window),
+            }),
+        ));
+    };
+    let groups = state.storage.list_conjunctions().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })?;
+
+    let config = state.config.get();
+    let now = Utc::now();
+    let until = now + span;
+    let mut conjunctions: Vec<UpcomingConjunction> = groups
+        .into_iter()
+        .filter_map(|(key, mut cdms)| {
+            cdms.retain(|cdm| scope.sees_cdm(cdm));
+            ConjunctionSummary::new(key, &cdms, &config.fusion, &config.protocol.severity)
+        })
+        .filter(|summary| summary.latest.tca >= now && summary.latest.tca <= until)
+        .map(|summary| {
+            let seconds_to_tca = (summary.latest.tca - now).num_seconds();
+            let (collision_probability, miss_distance_m, conjunction_category) = match &summary.fused {
+                Some(fused) => (fused.collision_probability, fused.miss_distance_m, Some(fused.conjunction_category.clone())),
+                None => (
+                    summary.latest.collision_probability,
+                    summary.latest.miss_distance_m,
+                    summary.latest.conjunction_category.clone(),
+                ),
+            };
+            let watched_assets = [&summary.object1_id, &summary.object2_id]
+                .into_iter()
+                .filter_map(|id| state.watchlist.asset(id))
+                .collect();
+            UpcomingConjunction {
+                tca: summary.latest.tca,
+                seconds_to_tca,
+                countdown: countdown(seconds_to_tca),
+                collision_probability,
+                miss_distance_m,
+                conjunction_category,
+                watched_assets,
+                summary,
+            }
+        })
+        .collect();
+
+    let rank = |c: &UpcomingConjunction| match c.conjunction_category {
+        Some(ConjunctionCategory::High) => 0,
+        Some(ConjunctionCategory::Medium) => 1,
+        Some(ConjunctionCategory::Low) => 2,
+        None => 3,
+    };
+    let soonest = |a: &UpcomingConjunction, b: &UpcomingConjunction| a.tca.cmp(&b.tca);
+    let riskiest = |a: &UpcomingConjunction, b: &UpcomingConjunction| {
+        rank(a).cmp(&rank(b)).then_with(|| b.collision_probability.total_cmp(&a.collision_probability))
+    };
+    conjunctions.sort_by(|a, b| {
+        match query.sort {
+            UpcomingOrder::Tca => soonest(a, b).then_with(|| riskiest(a, b)),
+            UpcomingOrder::Risk => riskiest(a, b).then_with(|| soonest(a, b)),
+        }
+        .then_with(|| a.summary.conjunction_key.cmp(&b.summary.conjunction_key))
+    });
+
+    Ok(Json(UpcomingConjunctionsResponse {
+        generated_at: now,
+        until,
+        total: conjunctions.len(),
+        conjunctions,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/conjunctions/{id}/trend",
+    tag = "conjunctions",
+    params(("id" = String, Path, description = "Conjunction ID")),
+    responses(
+        (status = 200, description = "Every CDM of the conjunction and which way its risk is moving", body = ConjunctionTrendResponse),
+        (status = 404, description = "Unknown conjunction", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn conjunction_trend(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ConjunctionTrendResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let groups = trend_points(&state, |cdm| scope.sees_cdm(cdm)).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })?;
+    let Some((key, points)) = groups.into_iter().find(|(key, _)| key.id() == id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Conjunction {} not found", id),
+            }),
+        ));
+    };
+    let factor = state.config.get().alerts.risk_increase_factor;
+    Ok(Json(ConjunctionTrendResponse {
+        conjunction_id: id,
+        conjunction_key: key.to_string(),
+        trend: RiskTrend::new(points, factor),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "CDM", body = CdmRecord),
+        (status = 404, description = "Unknown CDM", body = ErrorResponse),
+    )
+)]
+async fn get_cdm(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<CdmRecord>, (StatusCode, Json<ErrorResponse>)> {
+    match state.storage.get_cdm(&id).await {
+        Ok(Some(cdm)) if scope.sees_cdm(&cdm) => Ok(Json(cdm)),
+        Ok(_) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("CDM not found: {}", id),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Map a Pc computation failure to an API error
+fn pc_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, error) = match &e {
+        Error::NotFound(_) => (StatusCode::BAD_REQUEST, "unknown_method"),
+        Error::CdmValidation(_) => (StatusCode::UNPROCESSABLE_ENTITY, "insufficient_data"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message: e.to_string(),
+        }),
+    )
+}
+
+/// Run a Pc computation for a stored CDM off the async executor
+async fn with_stored_cdm<T: Send + 'static>(
+    state: &AppState,
+    scope: &TenantScope,
+    id: &str,
+    compute: impl FnOnce(&PcMethods, &CdmRecord) -> Result<T> + Send + 'static,
+) -> std::result::Result<(CdmRecord, T), (StatusCode, Json<ErrorResponse>)> {
+    let cdm = match state.storage.get_cdm(id).await {
+        Ok(Some(cdm)) if scope.sees_cdm(&cdm) => cdm,
+        Ok(_) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "not_found".to_string(),
+                    message: format!("CDM not found: {}", id),
+                }),
+            ))
+        }
+        Err(e) => return Err(pc_error(e)),
+    };
+    let methods = state.pc_methods.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = compute(&methods, &cdm);
+        result.map(|r| (cdm, r))
+    })
+    .await
+    .map_err(|e| pc_error(Error::Internal(e.to_string())))?
+    .map_err(pc_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/cdms/{id}/pc",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")),
+    responses(
+        (status = 200, description = "Pc from every method", body = PcComparisonResponse),
+        (status = 404, description = "Unknown CDM", body = ErrorResponse),
+        (status = 422, description = "CDM lacks the data needed", body = ErrorResponse),
+    )
+)]
+async fn compare_pc(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PcComparisonResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let (cdm, results) = with_stored_cdm(&state, &scope, &id, |methods, cdm| methods.compare(cdm)).await?;
+    Ok(Json(PcComparisonResponse {
+        cdm_id: cdm.cdm_id,
+        reported_pc: cdm.collision_probability,
+        default_method: state.pc_methods.default_method().to_string(),
+        results,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/cdms/{id}/pc",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")), request_body(content = Option<PcRequest>),
+    responses(
+        (status = 200, description = "Recomputed Pc", body = PcResult),
+        (status = 400, description = "Unknown method", body = ErrorResponse),
+        (status = 404, description = "Unknown CDM", body = ErrorResponse),
+        (status = 422, description = "CDM lacks the data needed", body = ErrorResponse),
+    )
+)]
+async fn recompute_pc(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+    body: Option<Json<PcRequest>>,
+) -> std::result::Result<Json<PcResult>, (StatusCode, Json<ErrorResponse>)> {
+    let method = body.unwrap_or_default().0.method;
+    let (cdm, result) =
+        with_stored_cdm(&state, &scope, &id, move |methods, cdm| methods.compute(cdm, method.as_deref())).await?;
+    info!("Pc for {} recomputed with {}: {:e}", cdm.cdm_id, result.method, result.pc);
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/cdms/{id}",
+    tag = "cdms",
+    params(("id" = String, Path, description = "CDM ID")), request_body = WithdrawCdmRequest,
+    responses(
+        (status = 200, description = "CDM withdrawn", body = WithdrawResponse),
+        (status = 404, description = "Unknown CDM", body = ErrorResponse),
+    )
+)]
+async fn withdraw_cdm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<WithdrawCdmRequest>,
+) -> std::result::Result<Json<WithdrawResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let stored = state.storage.get_cdm(&id).await.ok().flatten();
+    state.storage.withdraw_cdm(&id).await.map_err(|e| {
+        if e.is_not_found() {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "not_found".to_string(),
+                    message: format!("CDM not found: {}", id),
+                }),
+            )
+        } else {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "storage_error".to_string(),
+                    message: e.to_string(),
+                }),
+            )
+        }
+    })?;
+
+    match &body.superseded_by {
+        Some(replacement) => info!("CDM withdrawn: {} (reason: {}, superseded by {})", id, body.reason, replacement),
+        None => info!("CDM withdrawn: {} (reason: {})", id, body.reason),
+    }
+    state.events.withdrawn(&id, stored.as_ref(), body.reason.clone());
+
+    Ok(Json(WithdrawResponse {
+        cdm_id: id,
+        status: "withdrawn".to_string(),
+        reason: body.reason,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/cdms",
+    tag = "cdms",
+    params(PurgeQuery),
+    responses(
+        (status = 200, description = "CDMs withdrawn", body = PurgeResponse),
+        (status = 400, description = "Missing originator", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn purge_cdms(
+    State(state): State<AppState>,
+    Query(query): Query<PurgeQuery>,
+) -> std::result::Result<Json<PurgeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let storage_error = |e: Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    };
+    // Refuse to purge everything by accident
+    let Some(originator) = query.originator.filter(|o| !o.is_empty()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "validation_failed".to_string(),
+                message: "originator query parameter is required".to_string(),
+            }),
+        ));
+    };
+
+    let mut cdm_ids = Vec::new();
+    for cdm in state.storage.list_cdms().await.map_err(storage_error)? {
+        if cdm.originator != originator {
+            continue;
+        }
+        match state.storage.withdraw_cdm(&cdm.cdm_id).await {
+            Ok(()) => {}
+            // Withdrawn concurrently
+            Err(e) if e.is_not_found() => continue,
+            Err(e) => return Err(storage_error(e)),
+        }
+        state.events.withdrawn(&cdm.cdm_id, Some(&cdm), query.reason.clone());
+        cdm_ids.push(cdm.cdm_id);
+    }
+
+    info!("Purged {} CDMs from {} (reason: {})", cdm_ids.len(), originator, query.reason);
+    Ok(Json(PurgeResponse {
+        originator,
+        reason: query.reason,
+        withdrawn: cdm_ids.len(),
+        cdm_ids,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/objects/{id}",
+    tag = "objects",
+    params(("id" = String, Path, description = "Object ID")), request_body = WithdrawObjectRequest,
+    responses(
+        (status = 200, description = "Object withdrawn", body = WithdrawObjectResponse),
+        (status = 404, description = "Unknown object", body = ErrorResponse),
+    )
+)]
+async fn withdraw_object(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<WithdrawObjectRequest>,
+) -> std::result::Result<Json<WithdrawObjectResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let id = state.config.get().identifiers.canonical(&id);
+    state.storage.withdraw_object(&id).await.map_err(|e| {
+        if e.is_not_found() {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "not_found".to_string(),
+                    message: format!("Object not found: {}", id),
+                }),
+            )
+        } else {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "storage_error".to_string(),
+                    message: e.to_string(),
+                }),
+            )
+        }
+    })?;
+    info!("Object withdrawn: {} (reason: {:?})", id, body.reason);
+
+    // Tell peers so they drop the object too
+    let payload = ObjectStateWithdrawPayload {
+        object_id: id.clone(),
+        reason: body.reason.clone(),
+        effective_time: Utc::now(),
+    };
+    let propagated_to = match serde_json::to_value(payload) {
+        Ok(payload) => {
+            let envelope = Envelope::new(state.config.get().node.id.clone(), MessageType::ObjectStateWithdraw, payload);
+            originate(&state, envelope).await
+        }
+        Err(e) => {
+            warn!("Failed to encode withdrawal of {}: {}", id, e);
+            Vec::new()
+        }
+    };
+
+    Ok(Json(WithdrawObjectResponse {
+        object_id: id,
+        status: "withdrawn".to_string(),
+        reason: body.reason,
+        propagated_to,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/objects/omm",
+    tag = "objects",
+    request_body(
+        content = String,
+        description = "CCSDS OMM in JSON (one message or an array) or KVN, SGP4 mean elements only",
+        content_type = "text/plain"
+    ),
+    responses(
+        (status = 200, description = "Per element set results", body = OmmIngestResponse),
+        (status = 400, description = "Document could not be parsed", body = ErrorResponse),
+    )
+)]
+async fn ingest_omm(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    body: String,
+) -> std::result::Result<Json<OmmIngestResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let records = parse_omm(&body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "invalid_omm".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })?;
+    let organization = caller.and_then(|Extension(caller)| caller.organization);
+    let config = state.config.get();
+
+    // Each element set stands alone, as in bulk CDM ingest
+    let mut results = Vec::with_capacity(records.len());
+    for (index, record) in records.into_iter().enumerate() {
+        let object_id = record.tracking_id();
+        let stored = async {
+            let mut object = record.to_object(&config.node.id)?;
+            object.object_id = config.identifiers.canonical(&object.object_id);
+            check_object_id(&object.object_id).map_err(|reason| Error::Protocol(format!("object_id {}", reason)))?;
+            object.organization = object_organization(&config.api, &object.object_id).or(organization.clone());
+            if let Some(catalog) = &state.catalog {
+                catalog.enrich_object(&mut object).await;
+            }
+            state.storage.store_object(object.clone()).await?;
+            Ok::<_, Error>(object)
+        }
+        .await;
+        let object = match stored {
+            Ok(object) => object,
+            Err(e) => {
+                let error = match e {
+                    Error::Propagation(_) => "propagation_failed",
+                    Error::Protocol(_) => "invalid_object_id",
+                    Error::QuotaExceeded(_) => "quota_exceeded",
+                    _ => "storage_error",
+                };
+                results.push(OmmIngestResult {
+                    index,
+                    object_id,
+                    status: "rejected".to_string(),
+                    state_vector: None,
+                    propagated_to: Vec::new(),
+                    error: Some(ErrorResponse {
+                        error: error.to_string(),
+                        message: e.to_string(),
+                    }),
+                });
+                continue;
+            }
+        };
+
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("international_designator".to_string(), record.object_id.clone().into());
+        metadata.insert("mean_element_theory".to_string(), "SGP4".into());
+        if let Some(bstar) = record.bstar {
+            metadata.insert("bstar".to_string(), bstar.into());
+        }
+        let payload = ObjectStateAnnouncePayload {
+            object_id: object.object_id.clone(),
+            object_name: object.object_name.clone(),
+            object_type: object.object_type.clone(),
+            owner_operator: object.owner_operator.clone(),
+            epoch: object.epoch,
+            state_vector: object.state_vector.clone(),
+            covariance: None,
+            metadata,
+        };
+        let propagated_to = match serde_json::to_value(payload) {
+            Ok(payload) => {
+                let envelope = Envelope::new(config.node.id.clone(), MessageType::ObjectStateAnnounce, payload);
+                originate(&state, envelope).await
+            }
+            Err(e) => {
+                warn!("Failed to encode announcement of {}: {}", object_id, e);
+                Vec::new()
+            }
+        };
+        results.push(OmmIngestResult {
+            index,
+            object_id,
+            status: "accepted".to_string(),
+            state_vector: Some(object.state_vector),
+            propagated_to,
+            error: None,
+        });
+    }
+
+    let accepted = results.iter().filter(|r| r.error.is_none()).count();
+    info!("OMM ingest: {} accepted, {} rejected", accepted, results.len() - accepted);
+    Ok(Json(OmmIngestResponse {
+        accepted,
+        rejected: results.len() - accepted,
+        results,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/events/cdms",
+    tag = "events",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Events after `since`", body = CdmEventPage),
+    )
+)]
+async fn cdm_events(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<EventsQuery>,
+) -> Json<CdmEventPage> {
+    let since = query.since.unwrap_or_else(|| state.events.head());
+    let timeout = Duration::from_secs(query.timeout_seconds.min(MAX_EVENTS_TIMEOUT_SECONDS));
+    let mut page = state.events.wait(since, timeout).await;
+    page.events.retain(|event| {
+        let record = event.record();
+        (!scope.is_scoped() || record.is_some_and(|cdm| scope.sees_cdm(cdm)))
+            && (!query.watched || record.is_some_and(|cdm| state.watchlist.involves(cdm)))
+    });
+    Json(page)
+}
+
+#[utoipa::path(
+    get,
+    path = "/archive/cdms",
+    tag = "archive",
+    params(ArchiveParams),
+    responses(
+        (status = 200, description = "Archived CDMs, oldest first", body = ArchivePage),
+        (status = 403, description = "Organization token", body = ErrorResponse),
+        (status = 404, description = "Archiving not configured", body = ErrorResponse),
+    )
+)]
+async fn archived_cdms(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(params): Query<ArchiveParams>,
+) -> std::result::Result<Json<ArchivePage>, (StatusCode, Json<ErrorResponse>)> {
+    query_archive(&state, &scope, ArchiveKind::Cdm, params).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/archive/objects",
+    tag = "archive",
+    params(ArchiveParams),
+    responses(
+        (status = 200, description = "Archived object states, oldest first", body = ArchivePage),
+        (status = 403, description = "Organization token", body = ErrorResponse),
+        (status = 404, description = "Archiving not configured", body = ErrorResponse),
+    )
+)]
+async fn archived_objects(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(params): Query<ArchiveParams>,
+) -> std::result::Result<Json<ArchivePage>, (StatusCode, Json<ErrorResponse>)> {
+    query_archive(&state, &scope, ArchiveKind::Object, params).await
+}
+
+/// Read the archive off the async executor
+///
+/// The archive is operator data: tenant-scoped tokens are refused.
+async fn query_archive(
+    state: &AppState,
+    scope: &TenantScope,
+    kind: ArchiveKind,
+    params: ArchiveParams,
+) -> std::result::Result<Json<ArchivePage>, (StatusCode, Json<ErrorResponse>)> {
+    if scope.is_scoped() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "forbidden".to_string(),
+                message: "the archive is not available to organization tokens".to_string(),
+            }),
+        ));
+    }
+    let Some(archive) = state.archive.clone() else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "archive_disabled".to_string(),
+                message: "archiving is not configured on this node".to_string(),
+            }),
+        ));
+    };
+    let query = ArchiveQuery {
+        since: params.since,
+        until: params.until,
+        object_id: params.object_id,
+        limit: params.limit.min(MAX_ARCHIVE_RESULTS),
+    };
+    tokio::task::spawn_blocking(move || archive.query(kind, &query))
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))
+        .and_then(|result| result)
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "storage_error".to_string(),
+                    message: e.to_string(),
+                }),
+            )
+        })
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Reload applied", body = ReloadReport),
+        (status = 400, description = "Configuration rejected", body = ErrorResponse),
+    )
+)]
+async fn reload_config(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ReloadReport>, (StatusCode, Json<ErrorResponse>)> {
+    reload_from_file(&state).await.map(Json).map_err(|e| {
+        warn!("Configuration reload failed: {}", e);
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "reload_failed".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    /// Gzip compress the snapshot
+    #[serde(default)]
+    gzip: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "admin",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Snapshot of the node's peers, watchlist, objects and CDMs (NDJSON)", body = String, content_type = "application/x-ndjson"),
+        (status = 500, description = "Storage could not be read", body = ErrorResponse),
+    )
+)]
+async fn export_node(State(state): State<AppState>, Query(query): Query<ExportQuery>) -> Response {
+    let snapshot = match export_snapshot(&state).await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            let error = ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            };
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response();
+        }
+    };
+    info!("Exporting snapshot of {} records", snapshot.len());
+    let disposition = format!(
+        "attachment; filename=\"{}-{}.ndjson{}\"",
+        state.config.get().node.id,
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        if query.gzip { ".gz" } else { "" }
+    );
+    let lines = snapshot.into_iter().map(|record| {
+        let mut json = serde_json::to_vec(&record)?;
+        json.push(b'\n');
+        Ok::<_, serde_json::Error>(json)
+    });
+    if !query.gzip {
+        let headers = [(CONTENT_TYPE, "application/x-ndjson".to_string()), (CONTENT_DISPOSITION, disposition)];
+        return (headers, Body::from_stream(tokio_stream::iter(lines))).into_response();
+    }
+    match gzip_lines(lines) {
+        Ok(bytes) => ([(CONTENT_TYPE, "application/gzip".to_string()), (CONTENT_DISPOSITION, disposition)], bytes).into_response(),
+        Err(e) => {
+            let error = ErrorResponse {
+                error: "internal_error".to_string(),
+                message: e.to_string(),
+            };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response()
+        }
+    }
+}
+
+/// Compress NDJSON lines into one gzip member
+fn gzip_lines(lines: impl Iterator<Item = serde_json::Result<Vec<u8>>>) -> Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    for line in lines {
+        gzip.write_all(&line?)?;
+    }
+    Ok(gzip.finish()?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "admin",
+    request_body(
+        content = String,
+        description = "Snapshot from GET /export, optionally gzip compressed",
+        content_type = "application/x-ndjson"
+    ),
+    responses(
+        (status = 200, description = "Snapshot restored", body = SnapshotImportReport),
+        (status = 400, description = "Not a snapshot, an unsupported version, or damaged", body = ErrorResponse),
+    )
+)]
+async fn import_node(
+    State(state): State<AppState>,
+    body: Body,
+) -> std::result::Result<Json<SnapshotImportReport>, (StatusCode, Json<ErrorResponse>)> {
+    import_snapshot(&state, body).await.map(Json).map_err(|e| {
+        warn!("Snapshot import failed: {}", e);
+        let (status, error) = match e {
+            Error::Protocol(_) | Error::Io(_) | Error::LimitExceeded(_) => (StatusCode::BAD_REQUEST, "invalid_snapshot"),
+            Error::QuotaExceeded(_) => (StatusCode::INSUFFICIENT_STORAGE, "quota_exceeded"),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "storage_error"),
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/objects",
+    tag = "objects",
+    responses(
+        (status = 200, description = "Tracked objects", body = ObjectListResponse),
+    )
+)]
+async fn list_objects(State(state): State<AppState>, scope: TenantScope) -> Json<ObjectListResponse> {
+    let objects = state.storage.list_objects().await.unwrap_or_default();
+    let summaries: Vec<ObjectSummary> = objects
+        .iter()
+        .filter(|o| scope.sees_object(o))
+        .map(|o| ObjectSummary {
+            object_id: o.object_id.clone(),
+            object_name: o.object_name.clone(),
+            object_type: format!("{:?}", o.object_type),
+            last_updated: o.last_updated,
+        })
+        .collect();
+
+    Json(ObjectListResponse {
+        total: summaries.len(),
+        objects: summaries,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/objects/{id}/cdms",
+    tag = "objects",
+    params(("id" = String, Path, description = "Object ID")),
+    responses(
+        (status = 200, description = "CDMs involving the object, by TCA", body = ObjectCdmHistoryResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn object_cdm_history(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ObjectCdmHistoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let id = state.config.get().identifiers.canonical(&id);
+    let storage_error = |e: Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    };
+    let involves =
+        |cdm: &CdmRecord| (cdm.object1.object_id == id || cdm.object2.object_id == id) && scope.sees_cdm(cdm);
+
+    let mut cdms: Vec<ObjectCdmEntry> = state
+        .storage
+        .list_cdms()
+        .await
+        .map_err(storage_error)?
+        .iter()
+        .filter(|cdm| involves(cdm))
+        .map(|cdm| ObjectCdmEntry::new(&id, cdm, None))
+        .collect();
+    let active = cdms.len();
+    cdms.extend(
+        state
+            .storage
+            .list_withdrawn_cdms()
+            .await
+            .map_err(storage_error)?
+            .iter()
+            .filter(|w| involves(&w.record))
+            .map(|w| ObjectCdmEntry::new(&id, &w.record, Some(w.withdrawn_at))),
+    );
+    cdms.sort_by(|a, b| {
+        a.cdm.tca
+            .cmp(&b.cdm.tca)
+            .then_with(|| a.cdm.creation_date.cmp(&b.cdm.creation_date))
+            .then_with(|| a.cdm.cdm_id.cmp(&b.cdm.cdm_id))
+    });
+
+    Ok(Json(ObjectCdmHistoryResponse {
+        object_id: id,
+        active,
+        withdrawn: cdms.len() - active,
+        total: cdms.len(),
+        cdms,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/objects/{id}/state",
+    tag = "objects",
+    params(("id" = String, Path, description = "Object ID"), ObjectStateQuery),
+    responses(
+        (status = 200, description = "Predicted state with accuracy caveats", body = ObjectStateResponse),
+        (status = 400, description = "Unparseable epoch", body = ErrorResponse),
+        (status = 404, description = "Unknown object", body = ErrorResponse),
+        (status = 422, description = "State cannot be propagated to the epoch", body = ErrorResponse),
+    )
+)]
+async fn object_state(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+    Query(query): Query<ObjectStateQuery>,
+) -> std::result::Result<Json<ObjectStateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let id = state.config.get().identifiers.canonical(&id);
+    let error = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message,
+            }),
+        )
+    };
+    let at = match query.at.as_deref() {
+        Some(at) => parse_timestamp(at).map_err(|e| error(StatusCode::BAD_REQUEST, "invalid_epoch", e.to_string()))?,
+        None => Utc::now(),
+    };
+    let object = match state.storage.get_object(&id).await {
+        Ok(Some(object)) if scope.sees_object(&object) => object,
+        Ok(_) => return Err(error(StatusCode::NOT_FOUND, "not_found", format!("Object not found: {}", id))),
+        Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string())),
+    };
+    let prediction = predict(&object, at, query.model)
+        .map_err(|e| error(StatusCode::UNPROCESSABLE_ENTITY, "propagation_failed", e.to_string()))?;
+    Ok(Json(ObjectStateResponse {
+        object_id: object.object_id,
+        object_name: object.object_name,
+        prediction,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/objects/{id}/history",
+    tag = "objects",
+    params(("id" = String, Path, description = "Object ID"), ObjectHistoryQuery),
+    responses(
+        (status = 200, description = "Versions of the object's state with their differences", body = ObjectStateHistoryResponse),
+        (status = 400, description = "Unparseable or inverted range", body = ErrorResponse),
+        (status = 404, description = "Unknown object", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn object_state_history(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+    Query(query): Query<ObjectHistoryQuery>,
+) -> std::result::Result<Json<ObjectStateHistoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let id = state.config.get().identifiers.canonical(&id);
+    let error = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message,
+            }),
+        )
+    };
+    let bound = |at: Option<&str>| {
+        at.map(parse_timestamp)
+            .transpose()
+            .map_err(|e| error(StatusCode::BAD_REQUEST, "invalid_epoch", e.to_string()))
+    };
+    let (from, to) = (bound(query.from.as_deref())?, bound(query.to.as_deref())?);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(error(StatusCode::BAD_REQUEST, "invalid_range", "from is after to".to_string()));
+        }
+    }
+    let storage_error = |e: Error| error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string());
+    match state.storage.get_object(&id).await.map_err(storage_error)? {
+        Some(object) if scope.sees_object(&object) => {}
+        _ => return Err(error(StatusCode::NOT_FOUND, "not_found", format!("Object not found: {}", id))),
+    }
+
+    let history = state.storage.object_state_history(&id).await.map_err(storage_error)?;
+    let in_range = |s: &ObjectStateChange| from.is_none_or(|from| s.epoch >= from) && to.is_none_or(|to| s.epoch <= to);
+    let states = history
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| in_range(s))
+        .map(|(i, s)| ObjectStateVersion {
+            state: s.clone(),
+            diff: i.checked_sub(1).map(|previous| StateDiff::between(&history[previous], s)),
+        })
+        .collect();
+    Ok(Json(ObjectStateHistoryResponse { object_id: id, states }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/catalog/resolve",
+    tag = "catalog",
+    params(CatalogResolveQuery),
+    responses(
+        (status = 200, description = "Catalog entry the query names, with the IDs it is also known by", body = ResolvedObject),
+        (status = 404, description = "No catalog configured, or the catalog does not know the object", body = ErrorResponse),
+    )
+)]
+async fn resolve_catalog_object(
+    State(state): State<AppState>,
+    Query(query): Query<CatalogResolveQuery>,
+) -> std::result::Result<Json<ResolvedObject>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(catalog) = &state.catalog else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "catalog_disabled".to_string(),
+                message: "no object catalog is configured".to_string(),
+            }),
+        ));
+    };
+    let identifiers = state.config.get().identifiers.clone();
+    match resolve_object(catalog, &identifiers, &query.query).await {
+        Some(resolved) => Ok(Json(resolved)),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("catalog has no object matching {}", query.query),
+            }),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers",
+    tag = "peers",
+    responses(
+        (status = 200, description = "Configured peers", body = PeerListResponse),
+    )
+)]
+async fn list_peers(State(state): State<AppState>) -> Json<PeerListResponse> {
+    let peers = state.peers.read().await;
+    Json(PeerListResponse {
+        peers: peers.list_peers().to_vec(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers/{id}",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    responses(
+        (status = 200, description = "Peer and session details", body = PeerDetailResponse),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn get_peer_detail(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PeerDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let peers = state.peers.read().await;
+    let (Some(peer), Some(session)) = (peers.get_peer(&id), peers.session(&id)) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        ));
+    };
+    let uptime_seconds = session
+        .connected_since
+        .map(|since| (Utc::now() - since).num_seconds().max(0) as u64);
+    Ok(Json(PeerDetailResponse {
+        peer: peer.clone(),
+        session: PeerSessionView {
+            session,
+            uptime_seconds,
+            queue_depth: peers.queue_depth(&id),
+            fanout: state.fanout.lane_status(&id),
+        },
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers/{id}/sla",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID"), SlaQuery),
+    responses(
+        (status = 200, description = "The peer's service levels over the window", body = SlaReport),
+        (status = 400, description = "Invalid window", body = ErrorResponse),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn peer_sla(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<SlaQuery>,
+) -> std::result::Result<Json<SlaReport>, (StatusCode, Json<ErrorResponse>)> {
+    let window = query.window.as_deref().unwrap_or("30d");
+    let Some(span) = parse_range(window).filter(|span| *span <= chrono::Duration::days(SLA_RETENTION_DAYS)) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "validation_failed".to_string(),
+                message: format!(
+                    "invalid window {:?}: expected a number followed by s, m, h or d, up to {}d",
+                    window, SLA_RETENTION_DAYS
+                ),
+            }),
+        ));
+    };
+    let now = Utc::now();
+    let known = state.peers.read().await.get_peer(&id).is_some();
+    // A peer added since the last check has an empty record
+    let report = known.then(|| {
+        state.sla.report(&id, now - span, now).unwrap_or_else(|| SlaReport {
+            peer_id: id.clone(),
+            from: now - span,
+            to: now,
+            observed_seconds: 0,
+            connected_seconds: 0,
+            uptime_percent: None,
+            heartbeat_checks: 0,
+            heartbeats_missed: 0,
+            forwards: 0,
+            forward_failures: 0,
+            mean_forward_latency_ms: None,
+            messages_received: 0,
+            messages_rejected: 0,
+            rejected_percent: None,
+        })
+    });
+    report.map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        )
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers",
+    tag = "peers",
+    request_body = AddPeerRequest,
+    responses(
+        (status = 201, description = "Peer added", body = AddPeerResponse),
+    )
+)]
+async fn add_peer(
+    State(state): State<AppState>,
+    Json(body): Json<AddPeerRequest>,
+) -> (StatusCode, Json<AddPeerResponse>) {
+    let mut peers = state.peers.write().await;
+    
+    let added = peers.add_peer(PeerInfo {
+        id: body.peer_id.clone(),
+        address: body.address,
+        status: PeerStatus::Connecting,
+        last_heartbeat: None,
+        messages_sent: 0,
+        messages_received: 0,
+        transport: body.transport,
+        encoding: body.encoding,
+        timestamp_format: body.timestamp_format,
+        auth_token: body.auth_token,
+        policies: Default::default(),
+    });
+    drop(peers);
+
+    if added {
+        spawn_session(state.clone(), body.peer_id.clone());
+    }
+
+    info!("Peer added: {}", body.peer_id);
+
+    (
+        StatusCode::CREATED,
+        Json(AddPeerResponse {
+            peer_id: body.peer_id,
+            status: "connecting".to_string(),
+        }),
+    )
+}
+
+#[utoipa::path(
+    delete,
+    path = "/peers/{id}",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    responses(
+        (status = 200, description = "Peer removed", body = RemovePeerResponse),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn remove_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<RemovePeerResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let mut peers = state.peers.write().await;
+    
+    if peers.remove_peer(&id) {
+        state.fanout.remove_peer(&id);
+        if let Err(e) = state.storage.remove_peer_policies(&id).await {
+            warn!("Could not remove policies kept for peer {}: {}", id, e);
+        }
+        info!("Peer removed: {}", id);
+        Ok(Json(RemovePeerResponse {
+            peer_id: id,
+            status: "removed".to_string(),
+        }))
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        ))
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/peers/{id}/quarantine",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    responses(
+        (status = 200, description = "Peer released, with its score reset", body = PeerHealth),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn release_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PeerHealth>, (StatusCode, Json<ErrorResponse>)> {
+    let health = state.peers.write().await.release_quarantine(&id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        )
+    })?;
+    info!("Peer {} released from quarantine", id);
+    Ok(Json(health))
+}
+
+#[utoipa::path(
+    get,
+    path = "/originators",
+    tag = "originators",
+    responses(
+        (status = 200, description = "Originators seen in the last hour or held, by name", body = Vec<OriginatorStatus>),
+    )
+)]
+async fn list_originators(State(state): State<AppState>) -> Json<Vec<OriginatorStatus>> {
+    Json(state.originators.list(Utc::now()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/originators/{id}/hold",
+    tag = "originators",
+    params(("id" = String, Path, description = "Originator")),
+    responses(
+        (status = 200, description = "Originator released; the anomaly that held it", body = OriginatorAnomaly),
+        (status = 404, description = "Originator not held", body = ErrorResponse),
+    )
+)]
+async fn release_originator(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<OriginatorAnomaly>, (StatusCode, Json<ErrorResponse>)> {
+    let anomaly = state.originators.release(&id, Utc::now()).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Originator not held: {}", id),
+            }),
+        )
+    })?;
+    info!("Originator {} released", id);
+    Ok(Json(anomaly))
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/{id}/disable",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    request_body(content = DisablePeerRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Session shut down, configuration kept", body = PeerInfo),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn disable_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    body: Option<Json<DisablePeerRequest>>,
+) -> std::result::Result<Json<PeerInfo>, (StatusCode, Json<ErrorResponse>)> {
+    let Json(body) = body.unwrap_or_default();
+    let (link, peer) = {
+        let mut peers = state.peers.write().await;
+        let link = peers.link(&id);
+        let peer = peers.disable(&id, body.reason.clone()).then(|| peers.get_peer(&id).cloned()).flatten();
+        (link, peer)
+    };
+    let peer = peer.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        )
+    })?;
+    state.fanout.remove_peer(&id);
+    info!("Peer {} administratively shut down", id);
+
+    // Tell the peer over the old link that this is maintenance, not a fault
+    if let Some(link) = link {
+        let mut hello = state.local_hello();
+        hello.admin_down = true;
+        hello.admin_down_reason = body.reason;
+        let sent = match serde_json::to_value(hello) {
+            Ok(payload) => {
+                let envelope = Envelope::new(state.config.get().node.id.clone(), MessageType::Hello, payload);
+                link.send(&envelope).await.map(|_| ())
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = sent {
+            warn!("Could not tell {} about the shutdown: {}", id, e);
+        }
+    }
+    Ok(Json(peer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/{id}/enable",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    responses(
+        (status = 200, description = "Session brought back up; it reconnects within a heartbeat interval", body = PeerInfo),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+    )
+)]
+async fn enable_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PeerInfo>, (StatusCode, Json<ErrorResponse>)> {
+    let mut peers = state.peers.write().await;
+    let peer = peers.enable(&id).then(|| peers.get_peer(&id).cloned()).flatten();
+    let peer = peer.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Peer not found: {}", id),
+            }),
+        )
+    })?;
+    info!("Peer {} enabled", id);
+    Ok(Json(peer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/{id}/block",
+    tag = "peers",
+    params(("id" = String, Path, description = "Node ID, a peer's or any other node's")),
+    request_body(content = BlockPeerRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Node blocked, its session dropped and its queued envelopes purged", body = BlockPeerResponse),
+        (status = 500, description = "The block could not be stored", body = ErrorResponse),
+    )
+)]
+async fn block_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    body: Option<Json<BlockPeerRequest>>,
+) -> std::result::Result<Json<BlockPeerResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Json(body) = body.unwrap_or_default();
+    let node = BlockedNode {
+        node_id: id.clone(),
+        since: Utc::now(),
+        reason: body.reason,
+    };
+    // Blocked before it is stored, so nothing more gets through meanwhile
+    let blocked = state.peers.write().await.block(node);
+    state.storage.store_blocked_node(&blocked).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: format!("Could not store the block: {}", e),
+            }),
+        )
+    })?;
+    let purged = state.fanout.purge_peer(&id);
+    for envelope in &purged {
+        state
+            .deliveries
+            .update(&envelope.message_id, &id, DeliveryState::Failed, Some("peer blocked".to_string()));
+    }
+    warn!("Node {} blocked, {} queued envelopes purged", id, purged.len());
+    Ok(Json(BlockPeerResponse {
+        blocked,
+        purged: purged.len(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/peers/{id}/block",
+    tag = "peers",
+    params(("id" = String, Path, description = "Node ID")),
+    responses(
+        (status = 200, description = "Node unblocked; a peer reconnects within a heartbeat interval", body = BlockedNode),
+        (status = 404, description = "Node not blocked", body = ErrorResponse),
+        (status = 500, description = "The block could not be removed from storage", body = ErrorResponse),
+    )
+)]
+async fn unblock_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<BlockedNode>, (StatusCode, Json<ErrorResponse>)> {
+    let node = state.peers.write().await.unblock(&id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Node not blocked: {}", id),
+            }),
+        )
+    })?;
+    state.storage.remove_blocked_node(&id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: format!("Could not remove the stored block: {}", e),
+            }),
+        )
+    })?;
+    info!("Node {} unblocked", id);
+    Ok(Json(node))
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers/blocked",
+    tag = "peers",
+    responses(
+        (status = 200, description = "Blocked nodes, by ID", body = Vec<BlockedNode>),
+    )
+)]
+async fn list_blocked_nodes(State(state): State<AppState>) -> Json<Vec<BlockedNode>> {
+    Json(state.peers.read().await.blocked_nodes())
+}
+
+#[utoipa::path(
+    patch,
+    path = "/peers/{id}/policies",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    request_body = UpdatePoliciesRequest,
+    responses(
+        (status = 200, description = "Policies changed and kept", body = PeerInfo),
+        (status = 400, description = "A blocked type is not a relayed message type", body = ErrorResponse),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+        (status = 500, description = "The policies could not be stored", body = ErrorResponse),
+    )
+)]
+async fn update_peer_policies(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdatePoliciesRequest>,
+) -> std::result::Result<Json<PeerInfo>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message,
+            }),
+        )
+    };
+    if let Some(blocked) = body
+        .block_message_types
+        .iter()
+        .flatten()
+        .find(|message_type| !message_type.is_relayed())
+    {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "validation_failed",
+            format!("{} is not relayed between peers and cannot be blocked", blocked),
+        ));
+    }
+
+    let mut peers = state.peers.write().await;
+    let Some(peer) = peers.get_peer_mut(&id) else {
+        return Err(error(StatusCode::NOT_FOUND, "not_found", format!("Peer not found: {}", id)));
+    };
+    let mut policies = peer.policies.clone();
+    body.apply(&mut policies);
+    // Stored first, so the peer never runs on policies a restart would lose
+    state.storage.store_peer_policies(&id, &policies).await.map_err(|e| {
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "storage_error",
+            format!("Could not store policies: {}", e),
+        )
+    })?;
+    peer.policies = policies;
+    info!("Policies of peer {} changed: {:?}", id, peer.policies);
+    Ok(Json(peer.clone()))
+}
+
+/// Document format of `GET /peers/export`
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum PeeringFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PeerExportQuery {
+    /// `yaml` (default) or `json`
+    #[serde(default)]
+    #[param(inline)]
+    format: PeeringFormat,
+    /// Include the peers' auth tokens
+    #[serde(default)]
+    include_secrets: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PeerImportQuery {
+    /// Also remove the peers the document does not list
+    #[serde(default)]
+    prune: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers/export",
+    tag = "peers",
+    params(PeerExportQuery),
+    responses(
+        (status = 200, description = "The node's peers with their settings and policies", content((PeeringDocument = "application/yaml"), (PeeringDocument = "application/json"))),
+        (status = 403, description = "Secrets asked for by a caller without the admin level", body = ErrorResponse),
+    )
+)]
+async fn export_peers(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Query(query): Query<PeerExportQuery>,
+) -> Response {
+    if query.include_secrets {
+        if let Err(rejection) = require_admin(&caller) {
+            return rejection.into_response();
+        }
+    }
+    let document = export_peering(&state, query.include_secrets).await;
+    info!("Exporting {} peers (secrets: {})", document.peers.len(), query.include_secrets);
+    match query.format {
+        PeeringFormat::Json => Json(document).into_response(),
+        PeeringFormat::Yaml => match serde_yaml::to_string(&document) {
+            Ok(yaml) => ([(CONTENT_TYPE, "application/yaml")], yaml).into_response(),
+            Err(e) => {
+                let error = ErrorResponse {
+                    error: "internal_error".to_string(),
+                    message: e.to_string(),
+                };
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response()
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/import",
+    tag = "peers",
+    params(PeerImportQuery),
+    request_body(
+        content((PeeringDocument = "application/yaml"), (PeeringDocument = "application/json")),
+        description = "Document from GET /peers/export, as YAML or JSON"
+    ),
+    responses(
+        (status = 200, description = "Peers added, updated and removed", body = PeeringImportReport),
+        (status = 400, description = "Not a peering document, an unsupported version, or an invalid peer", body = ErrorResponse),
+        (status = 500, description = "Policies could not be stored", body = ErrorResponse),
+    )
+)]
+async fn import_peers(
+    State(state): State<AppState>,
+    Query(query): Query<PeerImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> std::result::Result<Json<PeeringImportReport>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message,
+            }),
+        )
+    };
+    let json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let document: PeeringDocument = if json {
+        serde_json::from_slice(&body).map_err(|e| error(StatusCode::BAD_REQUEST, "invalid_document", e.to_string()))?
+    } else {
+        serde_yaml::from_slice(&body).map_err(|e| error(StatusCode::BAD_REQUEST, "invalid_document", e.to_string()))?
+    };
+    import_peering(&state, document, query.prune).await.map(Json).map_err(|e| {
+        warn!("Peering import failed: {}", e);
+        match e {
+            Error::Protocol(message) => error(StatusCode::BAD_REQUEST, "invalid_document", message),
+            e => error(StatusCode::INTERNAL_SERVER_ERROR, "storage_error", e.to_string()),
+        }
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/{id}/cdm-query",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    request_body = CdmQuery,
+    responses(
+        (status = 200, description = "CDMs pulled from the peer", body = CdmQueryReport),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+        (status = 409, description = "Peer not connected or does not answer CDM queries", body = ErrorResponse),
+        (status = 502, description = "The peer failed or refused the query", body = ErrorResponse),
+    )
+)]
+async fn query_peer_cdms(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(query): Json<CdmQuery>,
+) -> std::result::Result<Json<CdmQueryReport>, (StatusCode, Json<ErrorResponse>)> {
+    query_peer(&state, &id, query).await.map(Json).map_err(|e| {
+        warn!("CDM query to {} failed: {}", id, e);
+        peer_exchange_error(e)
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/{id}/sync",
+    tag = "peers",
+    params(("id" = String, Path, description = "Peer ID")),
+    responses(
+        (status = 200, description = "Digests compared and missing CDMs pulled", body = SyncReport),
+        (status = 404, description = "Unknown peer", body = ErrorResponse),
+        (status = 409, description = "Peer not connected or does not answer SYNC_DIGEST", body = ErrorResponse),
+        (status = 502, description = "The peer failed or refused the exchange", body = ErrorResponse),
+    )
+)]
+async fn sync_peer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<SyncReport>, (StatusCode, Json<ErrorResponse>)> {
+    sync_with_peer(&state, &id).await.map(Json).map_err(|e| {
+        warn!("Sync with {} failed: {}", id, e);
+        peer_exchange_error(e)
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/routing/simulate",
+    tag = "routing",
+    request_body = RoutingSimulationRequest,
+    responses(
+        (status = 200, description = "Routing the node would apply; nothing is stored or sent", body = RoutingSimulation),
+        (status = 400, description = "Neither or both of an envelope and a CDM, or a malformed CDM", body = ErrorResponse),
+    )
+)]
+async fn simulate_route(
+    State(state): State<AppState>,
+    Json(request): Json<RoutingSimulationRequest>,
+) -> std::result::Result<Json<RoutingSimulation>, (StatusCode, Json<ErrorResponse>)> {
+    simulate_routing(&state, request).await.map(Json).map_err(|e| {
+        let (status, error) = match &e {
+            Error::Storage(_) => (StatusCode::INTERNAL_SERVER_ERROR, "storage_error"),
+            _ => (StatusCode::BAD_REQUEST, "validation_failed"),
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })
+}
+
+/// Rendering of `GET /network/topology`
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum TopologyFormat {
+    #[default]
+    Json,
+    /// Graphviz DOT
+    Dot,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TopologyQuery {
+    /// `json` (default) or `dot`
+    #[serde(default)]
+    #[param(inline)]
+    format: TopologyFormat,
+}
+
+#[utoipa::path(
+    get,
+    path = "/network/topology",
+    tag = "network",
+    params(TopologyQuery),
+    responses(
+        (status = 200, description = "Nodes and links of the mesh seen from this node, as JSON or Graphviz DOT", content(
+            (Topology = "application/json"),
+            (String = "text/vnd.graphviz"),
+        )),
+        (status = 500, description = "Storage could not be read", body = ErrorResponse),
+    )
+)]
+async fn network_topology(State(state): State<AppState>, Query(query): Query<TopologyQuery>) -> Response {
+    let topology = match build_topology(&state).await {
+        Ok(topology) => topology,
+        Err(e) => {
+            let error = ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            };
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response();
+        }
+    };
+    match query.format {
+        TopologyFormat::Json => Json(topology).into_response(),
+        TopologyFormat::Dot => ([(CONTENT_TYPE, "text/vnd.graphviz")], topology.to_dot()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/network/objects/{id}/sources",
+    tag = "network",
+    params(("id" = String, Path, description = "Object ID")),
+    responses(
+        (status = 200, description = "Nodes announcing the object, and peers interested in it", body = ObjectSources),
+        (status = 404, description = "Nothing stored about the object", body = ErrorResponse),
+        (status = 500, description = "Storage could not be read", body = ErrorResponse),
+    )
+)]
+async fn network_object_sources(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ObjectSources>, (StatusCode, Json<ErrorResponse>)> {
+    let id = state.config.get().identifiers.canonical(&id);
+    object_sources(&state, &scope, &id).await.map(Json).map_err(|e| {
+        let (status, error) = if e.is_not_found() {
+            (StatusCode::NOT_FOUND, "not_found")
+        } else {
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage_error")
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })
+}
+
+/// Map a failed query or sync with a peer to a response
+fn peer_exchange_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, error) = match &e {
+        Error::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+        Error::Protocol(_) => (StatusCode::CONFLICT, "peer_unavailable"),
+        _ => (StatusCode::BAD_GATEWAY, "peer_error"),
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message: e.to_string(),
+        }),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/watchlist",
+    tag = "watchlist",
+    responses(
+        (status = 200, description = "Watched assets by catalog number", body = WatchlistResponse),
+    )
+)]
+async fn list_watchlist(State(state): State<AppState>) -> Json<WatchlistResponse> {
+    let assets = state.watchlist.list();
+    Json(WatchlistResponse {
+        total: assets.len(),
+        assets,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/watchlist",
+    tag = "watchlist",
+    request_body = RegisterAssetsRequest,
+    responses(
+        (status = 200, description = "Assets registered and active CDMs re-tagged", body = WatchlistUpdateResponse),
+        (status = 400, description = "Not a NORAD catalog number", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn register_assets(
+    State(state): State<AppState>,
+    Json(body): Json<RegisterAssetsRequest>,
+) -> std::result::Result<Json<WatchlistUpdateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Aliases, such as an international designator, stand for the asset;
+    // nothing is registered unless every ID is valid
+    let config = state.config.get();
+    let assets: Vec<_> = body
+        .assets
+        .into_iter()
+        .map(|asset| (config.identifiers.canonical(&asset.norad_id), asset))
+        .collect();
+    let invalid: Vec<&str> = assets
+        .iter()
+        .filter(|(id, _)| norad_number(id).is_none())
+        .map(|(_, asset)| asset.norad_id.as_str())
+        .collect();
+    if !invalid.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "validation_failed".to_string(),
+                message: format!("not NORAD catalog numbers: {}", invalid.join(", ")),
+            }),
+        ));
+    }
+
+    let mut changed = Vec::new();
+    for (id, asset) in assets {
+        if state.watchlist.register(&id, asset.name) == Some(true) {
+            changed.extend(norad_number(&id));
+        }
+    }
+    info!("Watchlist: registered {} assets", changed.len());
+    watchlist_updated(&state, changed).await.map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/watchlist/{id}",
+    tag = "watchlist",
+    params(("id" = String, Path, description = "NORAD catalog number, or an alias of one")),
+    responses(
+        (status = 200, description = "Asset removed and active CDMs re-tagged", body = WatchlistUpdateResponse),
+        (status = 404, description = "Asset not on the watchlist", body = ErrorResponse),
+        (status = 500, description = "Storage failure", body = ErrorResponse),
+    )
+)]
+async fn unregister_asset(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<WatchlistUpdateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let canonical = state.config.get().identifiers.canonical(&id);
+    if !state.watchlist.remove(&canonical) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Asset not on the watchlist: {}", id),
+            }),
+        ));
+    }
+    info!("Watchlist: removed {}", id);
+    watchlist_updated(&state, norad_number(&canonical).into_iter().collect()).await.map(Json)
+}
+
+/// Re-tag active CDMs after the watchlist changed
+async fn watchlist_updated(
+    state: &AppState,
+    changed: Vec<String>,
+) -> std::result::Result<WatchlistUpdateResponse, (StatusCode, Json<ErrorResponse>)> {
+    let retagged = retag_cdms(state).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "storage_error".to_string(),
+                message: e.to_string(),
+            }),
+        )
+    })?;
+    if let Err(e) = state.alerts.open_for_watched(state).await {
+        warn!("Could not open alerts for newly watched assets: {}", e);
+    }
+    Ok(WatchlistUpdateResponse {
+        changed,
+        retagged,
+        total: state.watchlist.list().len(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/alerts",
+    tag = "alerts",
+    params(AlertListQuery),
+    responses(
+        (status = 200, description = "Alerts ordered by TCA", body = AlertListResponse),
+    )
+)]
+async fn list_alerts(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<AlertListQuery>,
+) -> Json<AlertListResponse> {
+    state.alerts.catch_up(&state);
+    let filter = AlertFilter {
+        state: query.state,
+        min_severity: query.min_severity,
+        assignee: query.assignee,
+        cdm_id: query.cdm_id,
+    };
+    let alerts = state.alerts.list(&filter, &scope);
+    Json(AlertListResponse {
+        total: alerts.len(),
+        open: alerts.iter().filter(|a| a.state == AlertState::Open).count(),
+        alerts,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/alerts/{id}",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    responses(
+        (status = 200, description = "The alert", body = Alert),
+        (status = 404, description = "Alert not found", body = ErrorResponse),
+    )
+)]
+async fn get_alert(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<Alert>, (StatusCode, Json<ErrorResponse>)> {
+    state.alerts.catch_up(&state);
+    state
+        .alerts
+        .get(&id, &scope)
+        .map(Json)
+        .ok_or_else(|| alert_error(Error::NotFound(format!("Alert not found: {}", id))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/alerts/{id}/acknowledge",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    request_body(content = AcknowledgeAlertRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Alert acknowledged", body = Alert),
+        (status = 404, description = "Alert not found", body = ErrorResponse),
+        (status = 409, description = "Alert already resolved", body = ErrorResponse),
+    )
+)]
+async fn acknowledge_alert(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+    body: Option<Json<AcknowledgeAlertRequest>>,
+) -> std::result::Result<Json<Alert>, (StatusCode, Json<ErrorResponse>)> {
+    let Json(body) = body.unwrap_or_default();
+    let by = body.by.or_else(|| caller.map(|Extension(caller)| caller.token_id));
+    update_alert(&state, &id, AlertChange::Acknowledge { by }, &scope)
+}
+
+#[utoipa::path(
+    post,
+    path = "/alerts/{id}/assign",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    request_body = AssignAlertRequest,
+    responses(
+        (status = 200, description = "Alert assigned", body = Alert),
+        (status = 404, description = "Alert not found", body = ErrorResponse),
+        (status = 409, description = "Alert already resolved", body = ErrorResponse),
+    )
+)]
+async fn assign_alert(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<String>,
+    Json(body): Json<AssignAlertRequest>,
+) -> std::result::Result<Json<Alert>, (StatusCode, Json<ErrorResponse>)> {
+    update_alert(&state, &id, AlertChange::Assign { assignee: body.assignee }, &scope)
+}
+
+#[utoipa::path(
+    post,
+    path = "/alerts/{id}/resolve",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    request_body(content = ResolveAlertRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Alert resolved", body = Alert),
+        (status = 404, description = "Alert not found", body = ErrorResponse),
+    )
+)]
+async fn resolve_alert(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<String>,
+    body: Option<Json<ResolveAlertRequest>>,
+) -> std::result::Result<Json<Alert>, (StatusCode, Json<ErrorResponse>)> {
+    let Json(body) = body.unwrap_or_default();
+    let by = body.by.or_else(|| caller.map(|Extension(caller)| caller.token_id));
+    let change = AlertChange::Resolve {
+        by,
+        resolution: body.resolution,
+    };
+    update_alert(&state, &id, change, &scope)
+}
+
+fn update_alert(
+    state: &AppState,
+    id: &str,
+    change: AlertChange,
+    scope: &TenantScope,
+) -> std::result::Result<Json<Alert>, (StatusCode, Json<ErrorResponse>)> {
+    state.alerts.catch_up(state);
+    let alert = state.alerts.update(id, change.clone(), scope).map_err(alert_error)?;
+    info!("Alert {} updated: {:?}", id, change);
+    Ok(Json(alert))
+}
+
+fn alert_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, error) = match e {
+        Error::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+        Error::Conflict(_) => (StatusCode::CONFLICT, "alert_resolved"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message: e.to_string(),
+        }),
+    )
+}
+
+fn dead_letter_not_found(id: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "not_found".to_string(),
+            message: format!("Dead letter not found: {}", id),
+        }),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/deadletter",
+    tag = "deadletter",
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "Dead letters, newest first", body = DeadLetterListResponse),
+    )
+)]
+async fn list_dead_letters(
+    State(state): State<AppState>,
+    Query(query): Query<DeadLetterQuery>,
+) -> Json<DeadLetterListResponse> {
+    let filter = DeadLetterFilter {
+        reason: query.reason,
+        peer_id: query.peer_id,
+        message_type: query.message_type,
+    };
+    let dead_letters = state.dead_letters.list(&filter);
+    Json(DeadLetterListResponse {
+        total: dead_letters.len(),
+        dead_letters,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/deadletter/{id}",
+    tag = "deadletter",
+    params(("id" = String, Path, description = "Dead letter ID")),
+    responses(
+        (status = 200, description = "The dead letter", body = DeadLetter),
+        (status = 404, description = "Dead letter not found", body = ErrorResponse),
+    )
+)]
+async fn get_dead_letter(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<DeadLetter>, (StatusCode, Json<ErrorResponse>)> {
+    state.dead_letters.get(&id).map(Json).ok_or_else(|| dead_letter_not_found(&id))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/deadletter/{id}",
+    tag = "deadletter",
+    params(("id" = String, Path, description = "Dead letter ID")),
+    responses(
+        (status = 200, description = "Dead letter discarded", body = DeadLetter),
+        (status = 404, description = "Dead letter not found", body = ErrorResponse),
+    )
+)]
+async fn discard_dead_letter(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<DeadLetter>, (StatusCode, Json<ErrorResponse>)> {
+    let letter = state.dead_letters.take(&id).ok_or_else(|| dead_letter_not_found(&id))?;
+    info!("Dead letter {} ({}) discarded", id, letter.envelope.message_id);
+    Ok(Json(letter))
+}
+
+#[utoipa::path(
+    post,
+    path = "/deadletter/{id}/requeue",
+    tag = "deadletter",
+    params(("id" = String, Path, description = "Dead letter ID")),
+    responses(
+        (status = 200, description = "Message taken in or queued for its peer again", body = RequeueResponse),
+        (status = 404, description = "Dead letter not found", body = ErrorResponse),
+        (status = 409, description = "Requeue failed; the dead letter is kept with the new error", body = ErrorResponse),
+    )
+)]
+async fn requeue_dead_letter(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<RequeueResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let letter = state.dead_letters.take(&id).ok_or_else(|| dead_letter_not_found(&id))?;
+    match requeue(&state, &letter).await {
+        Ok(forwarded_to) => {
+            info!("Dead letter {} ({}) requeued", id, letter.envelope.message_id);
+            Ok(Json(RequeueResponse {
+                id,
+                message_id: letter.envelope.message_id,
+                forwarded_to,
+            }))
+        }
+        Err(e) => {
+            let letter = state.dead_letters.restore(letter, &e);
+            warn!("Requeue of dead letter {} failed: {}", id, letter.error);
+            Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: "requeue_failed".to_string(),
+                    message: e.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Send a dead letter through again: a refused message is validated and
+/// applied as if just received from its peer, and a failed forward is
+/// queued for its peer once more
+async fn requeue(state: &AppState, letter: &DeadLetter) -> Result<Vec<String>> {
+    let mut envelope = letter.envelope.clone();
+    if letter.reason.is_inbound() {
+        check_envelope(&mut envelope, &state.envelope_limits())?;
+        return accept_relayed(state, &envelope, &letter.peer_id).await;
+    }
+    let link = state
+        .peers
+        .read()
+        .await
+        .link(&letter.peer_id)
+        .ok_or_else(|| Error::Peer(format!("no link to {}", letter.peer_id)))?;
+    let queued = Arc::new(state.memory.charge_queue(envelope.footprint()));
+    let envelope = Arc::new(envelope);
+    let done = delivery_report(state, &letter.peer_id, &envelope, None, queued);
+    state.deliveries.queued(&envelope, &letter.peer_id);
+    state
+        .fanout
+        .submit(&letter.peer_id, envelope, link, done)
+        .map_err(|refusal| Error::Peer(format!("not queued for {}: {}", letter.peer_id, refusal)))?;
+    Ok(vec![letter.peer_id.clone()])
+}
+
+#[utoipa::path(
+    post,
+    path = "/maneuvers",
+    tag = "maneuvers",
+    params(OpmManeuverQuery),
+    request_body(
+        description = "Maneuver as JSON, or a CCSDS OPM in KVN with one maneuver block and the post-maneuver state",
+        content(
+            (ManeuverRequest = "application/json"),
+            (String = "text/plain"),
+        )
+    ),
+    responses(
+        (status = 201, description = "Maneuver announced", body = ManeuverResponse),
+        (status = 400, description = "Malformed request or unusable OPM", body = ErrorResponse),
+    )
+)]
+async fn announce_maneuver(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<OpmManeuverQuery>,
+    body: Bytes,
+) -> std::result::Result<(StatusCode, Json<ManeuverResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let maneuver_id = format!("MNVR-{}-{}", 
+        Utc::now().format("%Y%m%d"),
+        &uuid::Uuid::new_v4().to_string()[..8].to_uppercase()
+    );
+    let fail = |status: StatusCode, error: &str, message: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                message,
+            }),
+        )
+    };
+
+    // JSON requests name the maneuver directly; anything else is an OPM
+    let json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let intent = if json {
+        let Json(body) = Json::<ManeuverRequest>::from_bytes(&body)
+            .map_err(|rejection| fail(rejection.status(), "validation_failed", rejection.body_text()))?;
+        ManeuverIntentPayload {
+            maneuver_id: maneuver_id.clone(),
+            object_id: body.object_id,
+            related_cdm_id: body.related_cdm_id,
+            planned_start: body.planned_start,
+            planned_duration_s: body.planned_duration_s,
+            maneuver_type: serde_json::from_value(serde_json::Value::String(body.maneuver_type))
+                .unwrap_or(ManeuverType::Other),
+            delta_v: None,
+            predicted_post_maneuver_state: None,
+        }
+    } else {
+        let maneuver_type = query.maneuver_type.unwrap_or(if query.related_cdm_id.is_some() {
+            ManeuverType::CollisionAvoidance
+        } else {
+            ManeuverType::Other
+        });
+        std::str::from_utf8(&body)
+            .map_err(|e| Error::Ccsds(format!("OPM is not UTF-8: {}", e)))
+            .and_then(parse_opm)
+            .and_then(|opm| opm.to_intent(maneuver_id.clone(), query.object_id, query.related_cdm_id, maneuver_type))
+            .map_err(|e| fail(StatusCode::BAD_REQUEST, "invalid_opm", e.to_string()))?
+    };
+
+    info!("Maneuver intent announced: {}", maneuver_id);
+    info!("  Object: {}", intent.object_id);
+    info!("  Planned start: {}", intent.planned_start);
+    info!("  Type: {:?}", intent.maneuver_type);
+    let propagated_to = match serde_json::to_value(&intent) {
+        Ok(payload) => {
+            let envelope = Envelope::new(state.config.get().node.id.clone(), MessageType::ManeuverIntent, payload);
+            originate(&state, envelope).await
+        }
+        Err(e) => {
+            warn!("Failed to encode maneuver intent {}: {}", maneuver_id, e);
+            Vec::new()
+        }
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ManeuverResponse {
+            maneuver_id,
+            status: "announced".to_string(),
+            propagated_to,
+        }),
+    ))
+}
+
+// ============================================================================
+// Protocol processing
+// ============================================================================
+
+#[utoipa::path(
+    post,
+    path = "/spacecomms/v1/messages",
+    tag = "protocol",
+    request_body(
+        description = "Protocol envelope",
+        content((Envelope = "application/json"), (Envelope = "application/cbor"))
+    ),
+    params(
+        ("x-spacecomms-node-id" = Option<String>, Header, description = "Sending node ID"),
+        ("Authorization" = Option<String>, Header, description = "`Bearer` and the peering secret, the sender's `auth_token` here"),
+    ),
+    responses(
+        (status = 200, description = "Reply envelope, e.g. HEARTBEAT_ACK", body = Envelope),
+        (status = 202, description = "Envelope accepted with no reply"),
+        (status = 400, description = "Invalid message (ERROR envelope)", body = Envelope),
+        (status = 403, description = "Unauthorized, including a missing or wrong peering credential (ERROR envelope)", body = Envelope),
+        (status = 413, description = "Envelope or payload too large (ERROR envelope)", body = Envelope),
+        (status = 415, description = "Unsupported content type (ERROR envelope)", body = Envelope),
+        (status = 429, description = "Rate limited (ERROR envelope)", body = Envelope),
+        (status = 503, description = "Instance follows another (ERROR envelope)", body = Envelope),
+    )
+)]
+pub(crate) async fn receive_message(State(state): State<AppState>, headers: HeaderMap, body: Body) -> Response {
+    let from_peer = headers
+        .get(NODE_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let timestamp_format = state.timestamp_format_for(from_peer.as_deref()).await;
+
+    // Requests without a Content-Type are treated as JSON
+    let encoding = match headers.get(CONTENT_TYPE) {
+        None => Some(Encoding::Json),
+        Some(v) => v.to_str().ok().and_then(Encoding::from_content_type),
+    };
+    let Some(encoding) = encoding else {
+        let error = Error::Protocol("envelopes must be application/json or application/cbor".to_string());
+        return protocol_error(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE, &error, None, Encoding::Json, timestamp_format);
+    };
+
+    if !state.leadership.is_leader() {
+        let error = Error::Peer("this instance follows; peers are served by the leading instance".to_string());
+        return protocol_error(&state, StatusCode::SERVICE_UNAVAILABLE, &error, None, encoding, timestamp_format);
+    }
+
+    // Stop reading at the size limit instead of buffering arbitrarily large bodies
+    let limits = state.envelope_limits();
+    let body = match axum::body::to_bytes(body, limits.max_envelope_bytes).await {
+        Ok(body) => body,
+        Err(_) => {
+            let error = Error::LimitExceeded(format!(
+                "envelope body exceeds {} bytes",
+                limits.max_envelope_bytes
+            ));
+            return protocol_error(&state, StatusCode::PAYLOAD_TOO_LARGE, &error, None, encoding, timestamp_format);
+        }
+    };
+
+    let envelope = match Envelope::decode(&body, encoding) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            let related = related_message_id(&body, encoding);
+            return protocol_error(&state, StatusCode::BAD_REQUEST, &e, related, encoding, timestamp_format);
+        }
+    };
+    let message_id = envelope.message_id.clone();
+
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    let credential = peer_credential(bearer, &envelope);
+    let sender = match authenticate_peer(&state, &envelope, from_peer.as_deref(), credential).await {
+        Ok(sender) => sender,
+        Err(e) => {
+            let status = protocol_error_status(&e);
+            return protocol_error(&state, status, &e, Some(message_id), encoding, timestamp_format);
+        }
+    };
+
+    let result = match process_envelope(&state, envelope, Some(&sender)).await {
+        Ok(Some(reply)) => encode_reply(&state, &reply, Some(&sender), encoding, timestamp_format)
+            .await
+            .map(Some),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(Some(reply)) => (StatusCode::OK, [(CONTENT_TYPE, encoding.content_type())], reply).into_response(),
+        Ok(None) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => {
+            let status = protocol_error_status(&e);
+            protocol_error(&state, status, &e, Some(message_id), encoding, timestamp_format)
+        }
+    }
+}
+
+/// Encode a reply on the protocol endpoint, compressing a large payload
+/// for senders that offer PAYLOAD_GZIP
+async fn encode_reply(
+    state: &AppState,
+    reply: &Envelope,
+    from_peer: Option<&str>,
+    encoding: Encoding,
+    timestamp_format: TimestampFormat,
+) -> Result<Vec<u8>> {
+    let min_bytes = state.config.get().compression.min_payload_bytes;
+    if let Some(peer_id) = from_peer.filter(|_| min_bytes > 0) {
+        if reply.payload.to_json(timestamp_format)?.len() >= min_bytes {
+            let accepts = state
+                .peers
+                .read()
+                .await
+                .session(peer_id)
+                .is_some_and(|s| s.capabilities.iter().any(|c| c == CAPABILITY_PAYLOAD_GZIP));
+            if accepts {
+                return reply.compressed(timestamp_format)?.encode_with(encoding, timestamp_format);
+            }
+        }
+    }
+    reply.encode_with(encoding, timestamp_format)
+}
+
+/// HTTP status accompanying an ERROR envelope on the protocol endpoint
+fn protocol_error_status(error: &Error) -> StatusCode {
+    if let Error::LimitExceeded(_) = error {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+    match error.error_code() {
+        ErrorCode::InvalidMessage | ErrorCode::UnsupportedVersion => StatusCode::BAD_REQUEST,
+        ErrorCode::Unauthorized => StatusCode::FORBIDDEN,
+        ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Answer a protocol request with an ERROR envelope
+fn protocol_error(
+    state: &AppState,
+    status: StatusCode,
+    error: &Error,
+    related_message_id: Option<String>,
+    encoding: Encoding,
+    timestamp_format: TimestampFormat,
+) -> Response {
+    state.metrics.errors.fetch_add(1, Ordering::Relaxed);
+    warn!("Rejected protocol message {:?}: {}", related_message_id, error);
+
+    let envelope = Envelope::error(
+        state.config.get().node.id.clone(),
+        ErrorPayload::from_error(error, related_message_id),
+    );
+    match envelope.encode_with(encoding, timestamp_format) {
+        Ok(body) => (status, [(CONTENT_TYPE, encoding.content_type())], body).into_response(),
+        Err(_) => status.into_response(),
+    }
+}
+
+/// Best-effort message ID from a body that is not a valid envelope
+fn related_message_id(body: &[u8], encoding: Encoding) -> Option<String> {
+    let value: serde_json::Value = match encoding {
+        Encoding::Json => serde_json::from_slice(body).ok()?,
+        Encoding::Cbor => ciborium::from_reader(body).ok()?,
+    };
+    value.get("message_id")?.as_str().map(str::to_string)
+}
+
+/// Process an envelope received from a peer, on any transport
+///
+/// `from_peer` identifies the previous hop when the transport knows it;
+/// otherwise the envelope's source node is assumed to be the sender.
+/// Returns the reply envelope for session messages that expect one.
+pub(crate) async fn process_envelope(
+    state: &AppState,
+    envelope: Envelope,
+    from_peer: Option<&str>,
+) -> Result<Option<Envelope>> {
+    process_envelope_routed(state, envelope, from_peer)
+        .await
+        .map(|(reply, _)| reply)
+}
+
+/// Process a received envelope, also returning the peers it was relayed to
+pub(crate) async fn process_envelope_routed(
+    state: &AppState,
+    envelope: Envelope,
+    from_peer: Option<&str>,
+) -> Result<(Option<Envelope>, Vec<String>)> {
+    let sender = from_peer.unwrap_or(&envelope.source_node_id).to_string();
+    let span = info_span!(
+        "receive",
+        peer = %sender,
+        message_type = %envelope.message_type,
+        message_id = %envelope.message_id,
+    );
+    telemetry::continue_trace(&span, envelope.traceparent.as_deref());
+    // Checked before anything else so a blocked node's traffic leaves no
+    // trace, and kept out of the relaying peer's health
+    if let Some(node) = state.peers.read().await.blocked_in(&envelope, &sender) {
+        state.metrics.messages_blocked.fetch_add(1, Ordering::Relaxed);
+        return Err(Error::Unauthorized(format!("node {} is blocked", node)));
+    }
+    let relayed = envelope.message_type.is_relayed();
+    let result = handle_envelope(state, envelope, sender.clone()).instrument(span).await;
+    state.sla.record_received(&sender, result.is_ok());
+    // Relayed messages count towards the sender's health, unless this node
+    // was at fault or held back an originator the sender only relays;
+    // ERRORs are counted as they are handled
+    if relayed {
+        match &result {
+            Ok(_) => record_peer_outcome(state, &sender, true).await,
+            Err(e) if !matches!(e.error_code(), ErrorCode::InternalError | ErrorCode::RateLimited) => {
+                record_peer_outcome(state, &sender, false).await
+            }
+            Err(_) => {}
+        }
+    }
+    result
+}
+
+/// Count an exchange towards a peer's health score, quarantining the peer
+/// when its error rate is too high
+pub(crate) async fn record_peer_outcome(state: &AppState, peer_id: &str, ok: bool) {
+    let config = state.config.get();
+    if state.peers.write().await.record_outcome(peer_id, ok, &config.peer_health) {
+        state.metrics.peers_quarantined.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Peer {} quarantined for {} s: too many errors",
+            peer_id, config.peer_health.quarantine_seconds
+        );
+    }
+}
+
+async fn handle_envelope(
+    state: &AppState,
+    mut envelope: Envelope,
+    sender: String,
+) -> Result<(Option<Envelope>, Vec<String>)> {
+    state.metrics.messages_received.fetch_add(1, Ordering::Relaxed);
+    state.peers.write().await.record_received(&sender, &envelope.message_type);
+    if let Err(e) = check_envelope(&mut envelope, &state.envelope_limits()) {
+        state.dead_letters.refused(&envelope, &sender, &e);
+        return Err(e);
+    }
+    check_replay(state, &envelope, &sender).await?;
+
+    // A peer shut down here only learns why, from the answer to its HELLO
+    let disabled = state
+        .peers
+        .read()
+        .await
+        .admin_down(&sender)
+        .filter(|down| !down.by_peer)
+        .map(|down| down.reason.clone());
+    if let Some(reason) = disabled {
+        if envelope.message_type == MessageType::Hello {
+            let mut local = state.local_hello();
+            local.admin_down = true;
+            local.admin_down_reason = reason;
+            let reply = Envelope::new(
+                state.config.get().node.id.clone(),
+                MessageType::Hello,
+                serde_json::to_value(local)?,
+            );
+            return Ok((Some(reply), Vec::new()));
+        }
+        return Err(Error::Unauthorized(format!(
+            "peering with {} is administratively down",
+            sender
+        )));
+    }
+
+    match envelope.message_type {
+        MessageType::Hello => {
+            let mut remote: HelloPayload = envelope.payload.parse()?;
+            if remote.admin_down {
+                info!("Peer {} shut the session down for maintenance", sender);
+                state.peers.write().await.record_peer_admin_down(&sender, remote.admin_down_reason);
+                state.fanout.remove_peer(&sender);
+                return Ok((None, Vec::new()));
+            }
+            let local = state.local_hello();
+            let version = match negotiate_version(&local, &remote) {
+                VersionNegotiationResult::Compatible(version) => version,
+                VersionNegotiationResult::Incompatible { reason, .. } => {
+                    let mut peers = state.peers.write().await;
+                    peers.record_error(&sender, SessionEventKind::HandshakeFailed, reason.clone());
+                    return Err(Error::UnsupportedVersion(reason));
+                }
+            };
+            info!("HELLO from {} ({})", sender, remote.node_name);
+            let withheld = downgrade(&mut remote, &version, &state.config.get().protocol.disabled_features);
+            if !withheld.is_empty() {
+                info!("Session with {} on protocol {} without {}", sender, version, withheld.join(", "));
+            }
+            let mut peers = state.peers.write().await;
+            peers.update_heartbeat(&sender);
+            peers.reset_sequence(&sender);
+            record_clock_sample(state, &mut peers, &envelope, &sender);
+            peers.record_interests(&sender, remote.interests);
+            peers.record_handshake(&sender, version.clone(), remote.capabilities);
+            peers.record_withheld_capabilities(&sender, withheld);
+            peers.record_advertised_address(&sender, remote.advertise_address);
+            peers.record_event(&sender, SessionEventKind::HelloReceived, Some(format!("protocol {}", version)));
+            drop(peers);
+            let reply = Envelope::new(
+                state.config.get().node.id.clone(),
+                MessageType::Hello,
+                serde_json::to_value(local)?,
+            );
+            Ok((Some(reply), Vec::new()))
+        }
+        MessageType::Heartbeat => {
+            let heartbeat: HeartbeatPayload = envelope.payload.parse()?;
+            let mut peers = state.peers.write().await;
+            peers.update_heartbeat(&sender);
+            record_clock_sample(state, &mut peers, &envelope, &sender);
+            if envelope.source_node_id == sender {
+                peers.record_heartbeat(&sender, heartbeat.sequence, heartbeat.echo);
+            }
+            if peers.get_peer(&sender).is_some() {
+                state.fanout.grant_credits(&sender, heartbeat.credit_window);
+            }
+            Ok((None, Vec::new()))
+        }
+        MessageType::CdmRequest => {
+            let reply = answer_cdm_request(state, &envelope, &sender).await?;
+            Ok((Some(reply), Vec::new()))
+        }
+        MessageType::CdmResponse => Err(Error::Protocol(
+            "CDM_RESPONSE is only accepted as the reply to a CDM_REQUEST".to_string(),
+        )),
+        MessageType::SyncDigest => {
+            let reply = answer_sync_digest(state, &envelope, &sender).await?;
+            Ok((Some(reply), Vec::new()))
+        }
+        MessageType::ScreeningRequest => {
+            let reply = answer_screening_request(state, &envelope, &sender).await?;
+            Ok((Some(reply), Vec::new()))
+        }
+        MessageType::ScreeningResponse => Err(Error::Protocol(
+            "SCREENING_RESPONSE is only accepted as the reply to a SCREENING_REQUEST".to_string(),
+        )),
+        MessageType::Ack => {
+            let ack: AckPayload = envelope.payload.parse()?;
+            acknowledged(state, &ack.related_message_id, &sender);
+            Ok((None, Vec::new()))
+        }
+        MessageType::DeliveryReceipt => {
+            receive_receipt(state, &envelope, &sender).await?;
+            Ok((None, Vec::new()))
+        }
+        MessageType::InterestUpdate => {
+            let update: InterestUpdatePayload = envelope.payload.parse()?;
+            info!("Interests updated by {}", sender);
+            let mut peers = state.peers.write().await;
+            let all = update.interests.is_all();
+            peers.record_interests(&sender, (!all).then_some(update.interests));
+            let detail = if all { "all objects" } else { "filtered" };
+            peers.record_event(&sender, SessionEventKind::InterestsUpdated, Some(detail.to_string()));
+            Ok((None, Vec::new()))
+        }
+        MessageType::EnvelopeBatch => {
+            let batch: EnvelopeBatchPayload = envelope.payload.parse()?;
+            receive_batch(state, batch, &sender).await
+        }
+        MessageType::Error => {
+            let error: ErrorPayload = envelope.payload.parse()?;
+            warn!("Peer {} reported {:?}: {}", sender, error.error_code, error.error_message);
+            state.peers.write().await.record_error(
+                &sender,
+                SessionEventKind::PeerError,
+                format!("{:?}: {}", error.error_code, error.error_message),
+            );
+            record_peer_outcome(state, &sender, false).await;
+            Ok((None, Vec::new()))
+        }
+        _ => {
+            // A duplicate is acknowledged again: the sender may have missed
+            // the first ACK
+            if state.storage.has_seen_message(&envelope.message_id).await? {
+                debug!("Duplicate message {} from {}", envelope.message_id, sender);
+                return Ok((ack_for(state, &envelope, &sender).await, Vec::new()));
+            }
+            state.storage.mark_message_seen(&envelope.message_id).await?;
+
+            let result = accept_relayed(state, &envelope, &sender).await;
+            if let Err(e) = &result {
+                state.dead_letters.refused(&envelope, &sender, e);
+            }
+            let forwarded_to = result?;
+            Ok((ack_for(state, &envelope, &sender).await, forwarded_to))
+        }
+    }
+}
+
+/// Inflate a received envelope's payload, holding it to the size limit
+/// once inflated, and validate the envelope
+pub(crate) fn check_envelope(envelope: &mut Envelope, limits: &EnvelopeLimits) -> Result<()> {
+    envelope.inflate(limits.max_envelope_bytes)?;
+    validate_envelope(envelope, limits)
+}
+
+/// Apply a relayed message from a peer whose policies accept it, and pass
+/// it on, returning the peers it was forwarded to
+async fn accept_relayed(state: &AppState, envelope: &Envelope, sender: &str) -> Result<Vec<String>> {
+    let peers = state.peers.read().await;
+    if peers.is_quarantined(sender) {
+        return Err(Error::Unauthorized(format!("peer {} is quarantined", sender)));
+    }
+    let policies = peers.get_peer(sender).map(|p| p.policies.clone());
+    drop(peers);
+    if let Some(policies) = &policies {
+        if !state.routing.should_forward_to_peer(&envelope.message_type, policies) {
+            return Err(Error::Unauthorized(format!(
+                "{} not accepted from peer {}",
+                envelope.message_type, sender
+            )));
+        }
+    }
+
+    // This node joins a CDM's provenance before storing and relaying it
+    let attested;
+    let envelope = if envelope.message_type == MessageType::CdmAnnounce {
+        let config = state.config.get();
+        let cdm_id = envelope.payload.get("cdm_id").and_then(|id| id.as_str()).unwrap_or_default();
+        let mut copy = envelope.clone();
+        attest(&mut copy.provenance, cdm_id, &copy.payload, &config.node.id, config.provenance.signer().as_ref());
+        attested = copy;
+        &attested
+    } else {
+        envelope
+    };
+    if !apply_announcement(state, envelope, Some(sender)).await? {
+        return Ok(Vec::new());
+    }
+    if envelope.message_type == MessageType::CdmAnnounce {
+        record_propagation(state, envelope, sender).await;
+        send_receipt(state, envelope).await;
+    }
+
+    let forward = match envelope.message_type {
+        MessageType::CdmAnnounce | MessageType::CdmWithdraw => policies.map(|p| p.forward_cdm).unwrap_or(true),
+        _ => true,
+    };
+    Ok(if forward {
+        relay(state, envelope, sender).await
+    } else {
+        Vec::new()
+    })
+}
+
+/// Handle each envelope of a batch as if it had arrived alone, replying
+/// with an ERROR envelope for each one refused and an ACK for each one
+/// acknowledged
+async fn receive_batch(
+    state: &AppState,
+    batch: EnvelopeBatchPayload,
+    sender: &str,
+) -> Result<(Option<Envelope>, Vec<String>)> {
+    let node_id = state.config.get().node.id.clone();
+    let mut replies = Vec::new();
+    let mut forwarded_to: Vec<String> = Vec::new();
+    for envelope in batch.envelopes {
+        let message_id = envelope.message_id.clone();
+        let result = if envelope.message_type.is_relayed() {
+            Box::pin(process_envelope_routed(state, envelope, Some(sender))).await
+        } else {
+            Err(Error::Protocol(format!("{} cannot be batched", envelope.message_type)))
+        };
+        match result {
+            Ok((ack, peers)) => {
+                replies.extend(ack);
+                for peer in peers {
+                    if !forwarded_to.contains(&peer) {
+                        forwarded_to.push(peer);
+                    }
+                }
+            }
+            Err(e) => {
+                state.metrics.errors.fetch_add(1, Ordering::Relaxed);
+                warn!("Rejected batched message {} from {}: {}", message_id, sender, e);
+                replies.push(Envelope::error(node_id.clone(), ErrorPayload::from_error(&e, Some(message_id))));
+            }
+        }
+    }
+    if replies.is_empty() {
+        return Ok((None, forwarded_to));
+    }
+    let reply = EnvelopeBatchPayload { envelopes: replies };
+    let reply = Envelope::new(node_id, MessageType::EnvelopeBatch, serde_json::to_value(reply)?);
+    Ok((Some(reply), forwarded_to))
+}
+
+/// Take the age of an accepted HELLO or heartbeat as a one-way sample of
+/// the sender's clock offset; the transit time counts against the peer
+fn record_clock_sample(state: &AppState, peers: &mut PeerManager, envelope: &Envelope, sender: &str) {
+    if envelope.source_node_id != sender {
+        return;
+    }
+    let offset_ms = (envelope.timestamp - Utc::now()).num_milliseconds();
+    peers.record_clock_sample(sender, offset_ms, state.config.get().protocol.clock_skew.warn_seconds * 1000);
+}
+
+/// Count the time from a CDM's origin to its arrival from `sender`
+///
+/// A CDM the sender originated is timed on its clock offset, when known;
+/// a relayed one is timed from its originator's timestamp as it is.
+async fn record_propagation(state: &AppState, envelope: &Envelope, sender: &str) {
+    let skew = state.config.get().protocol.clock_skew.clone();
+    let mut peers = state.peers.write().await;
+    let offset_ms = if skew.correct && envelope.source_node_id == sender {
+        peers.clock_offset(sender)
+    } else {
+        None
+    };
+    let origin = match offset_ms {
+        Some(offset_ms) => correct_timestamp(envelope.timestamp, offset_ms, skew.max_correction_seconds),
+        None => envelope.timestamp,
+    };
+    let delay_ms = (Utc::now() - origin).num_milliseconds().max(0) as u64;
+    peers.record_propagation(sender, delay_ms);
+}
+
+/// Reject an envelope outside the accepted age window, or whose link
+/// sequence number was already received from the sender
+///
+/// Envelopes the sender originated are judged on its clock offset, when
+/// known; relayed ones carry another node's timestamp and are judged as
+/// they are.
+async fn check_replay(state: &AppState, envelope: &Envelope, sender: &str) -> Result<()> {
+    let protocol = &state.config.get().protocol;
+    let skew = &protocol.clock_skew;
+    let offset_ms = if skew.correct && envelope.source_node_id == sender {
+        state.peers.read().await.clock_offset(sender)
+    } else {
+        None
+    };
+    let timestamp = match offset_ms {
+        Some(offset_ms) => correct_timestamp(envelope.timestamp, offset_ms, skew.max_correction_seconds),
+        None => envelope.timestamp,
+    };
+    let mut result = check_timestamp(
+        timestamp,
+        Utc::now(),
+        protocol.max_message_age_seconds,
+        protocol.max_clock_skew_seconds,
+    );
+    if let (Ok(()), Some(sequence)) = (&result, envelope.sequence) {
+        result = state.peers.write().await.check_sequence(sender, sequence);
+    }
+    if result.is_err() {
+        state.metrics.replays_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+/// Apply an announcement or withdrawal to local storage, returning false
+/// when a CDM was quarantined instead; `sender` is the peer it came from
+pub(crate) async fn apply_announcement(state: &AppState, envelope: &Envelope, sender: Option<&str>) -> Result<bool> {
+    let payload = &envelope.payload;
+    match envelope.message_type {
+        MessageType::CdmAnnounce => {
+            let mut cdm: CdmRecord = payload.parse()?;
+            let config = state.config.get();
+            canonicalize_cdm(&mut cdm, &config.identifiers.aliases);
+            validate_cdm(&cdm)?;
+            admit_originator(state, &cdm)?;
+            score_covariance_quality(&mut cdm);
+            check_quality_floor(&cdm, config.protocol.min_data_quality)?;
+            let warnings = check_rules(&cdm, &config.validation.rules, Utc::now())?;
+            for warning in &warnings {
+                warn!("CDM {} from {} failed rule {}: {}", cdm.cdm_id, envelope.source_node_id, warning.rule, warning.message);
+            }
+            if let Some(catalog) = &state.catalog {
+                catalog.enrich_cdm(&mut cdm).await;
+            }
+            classify(&mut cdm, &config.protocol.severity);
+            cdm.organization = cdm_organization(&config.api, &cdm);
+            cdm.involves_watched_asset = state.watchlist.involves(&cdm);
+            if let Some(held) = QuarantinedCdm::for_violations(&cdm, &warnings, sender, Some(envelope)) {
+                quarantine_cdm(state, held);
+                return Ok(false);
+            }
+            info!("CDM {} received from {}", cdm.cdm_id, envelope.source_node_id);
+            store_received_cdm(state, cdm, envelope).await?;
+        }
+        MessageType::CdmWithdraw => {
+            let withdraw: CdmWithdrawPayload = payload.parse()?;
+            let stored = state.storage.get_cdm(&withdraw.cdm_id).await?;
+            match state.storage.withdraw_cdm(&withdraw.cdm_id).await {
+                Ok(()) => {
+                    info!("CDM {} withdrawn by {} ({:?})", withdraw.cdm_id, envelope.source_node_id, withdraw.reason);
+                    let reason = serde_json::to_value(&withdraw.reason)?;
+                    state.events.withdrawn(&withdraw.cdm_id, stored.as_ref(), reason.as_str().unwrap_or_default().to_string());
+                    state.metrics.cdms_withdrawn.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) if e.is_not_found() => debug!("Withdrawal for unknown CDM {}", withdraw.cdm_id),
+                Err(e) => return Err(e),
+            }
+        }
+        MessageType::ObjectStateAnnounce => {
+            let announce: ObjectStateAnnouncePayload = payload.parse()?;
+            let config = state.config.get();
+            let object_id = config.identifiers.canonical(&announce.object_id);
+            check_object_id(&object_id).map_err(|reason| Error::Protocol(format!("object_id {}", reason)))?;
+            let mut object = ObjectRecord {
+                organization: object_organization(&config.api, &object_id),
+                object_id,
+                object_name: announce.object_name,
+                object_type: announce.object_type,
+                owner_operator: announce.owner_operator,
+                rcs_size: None,
+                epoch: announce.epoch,
+                state_vector: announce.state_vector,
+                covariance: announce.covariance,
+                source_node: envelope.source_node_id.clone(),
+                last_updated: Utc::now(),
+            };
+            if let Some(catalog) = &state.catalog {
+                catalog.enrich_object(&mut object).await;
+            }
+            state.storage.store_object(object).await?;
+        }
+        MessageType::ObjectStateWithdraw => {
+            let withdraw: ObjectStateWithdrawPayload = payload.parse()?;
+            let object_id = state.config.get().identifiers.canonical(&withdraw.object_id);
+            match state.storage.withdraw_object(&object_id).await {
+                Ok(()) => info!("Object {} withdrawn ({:?})", object_id, withdraw.reason),
+                Err(e) if e.is_not_found() => debug!("Withdrawal for unknown object {}", object_id),
+                Err(e) => return Err(e),
+            }
+        }
+        MessageType::ManeuverIntent => {
+            let intent: ManeuverIntentPayload = payload.parse()?;
+            info!("Maneuver intent {} for {} from {}", intent.maneuver_id, intent.object_id, envelope.source_node_id);
+        }
+        MessageType::ManeuverStatus => {
+            let status: ManeuverStatusPayload = payload.parse()?;
+            info!("Maneuver {} is {:?}", status.maneuver_id, status.status);
+            if status.status == ManeuverStatusType::Completed {
+                apply_completed_maneuver(state, status, &envelope.source_node_id).await?;
+            }
+        }
+        MessageType::Hello
+        | MessageType::Heartbeat
+        | MessageType::Error
+        | MessageType::CdmRequest
+        | MessageType::CdmResponse
+        | MessageType::InterestUpdate
+        | MessageType::EnvelopeBatch
+        | MessageType::SyncDigest
+        | MessageType::Ack
+        | MessageType::DeliveryReceipt
+        | MessageType::ScreeningRequest
+        | MessageType::ScreeningResponse => {}
+    }
+    Ok(true)
+}
+
+/// Store a CDM received in `envelope`, with the envelope's provenance
+async fn store_received_cdm(state: &AppState, cdm: CdmRecord, envelope: &Envelope) -> Result<()> {
+    state.storage.store_cdm(cdm.clone()).await?;
+    state.storage.store_cdm_provenance(&cdm.cdm_id, envelope.provenance.clone()).await?;
+    state.events.announced(&cdm);
+    state.metrics.cdms_announced.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Move a tracked object to the state a completed maneuver left it in,
+/// archiving the state it replaces
+async fn apply_completed_maneuver(state: &AppState, status: ManeuverStatusPayload, source_node: &str) -> Result<()> {
+    let Some(post) = status.post_maneuver_state else {
+        debug!("Maneuver {} completed without a post-maneuver state", status.maneuver_id);
+        return Ok(());
+    };
+    let object_id = state.config.get().identifiers.canonical(&status.object_id);
+    let Some(current) = state.storage.get_object(&object_id).await? else {
+        debug!("Maneuver {} completed for untracked object {}", status.maneuver_id, status.object_id);
+        return Ok(());
+    };
+    // Without its own epoch the state holds from the end of the burn
+    let burn_end = status.actual_start.map(|start| {
+        start + chrono::Duration::milliseconds((status.actual_duration_s.unwrap_or(0.0) * 1000.0) as i64)
+    });
+    let Some(epoch) = post.epoch.or(burn_end) else {
+        warn!("Maneuver {} completed with an undated post-maneuver state", status.maneuver_id);
+        return Ok(());
+    };
+    if current.epoch >= epoch {
+        debug!(
+            "Object {} already has a state at {}, not before maneuver {}",
+            current.object_id, current.epoch, status.maneuver_id
+        );
+        return Ok(());
+    }
+
+    let updated = ObjectRecord {
+        epoch,
+        state_vector: post,
+        // The covariance described the orbit before the burn
+        covariance: None,
+        source_node: source_node.to_string(),
+        last_updated: Utc::now(),
+        ..current.clone()
+    };
+    state.storage.store_maneuvered_object(updated, &status.maneuver_id).await?;
+    info!("Object {} moved to its state after maneuver {}", current.object_id, status.maneuver_id);
+
+    if let Some(archive) = state.archive.clone() {
+        let entry = ArchiveEntry::object(current, ArchiveReason::Maneuvered);
+        tokio::task::spawn_blocking(move || archive.append(&[entry]))
+            .await
+            .map_err(|e| Error::Internal(e.to_string()))??;
+    }
+    Ok(())
+}
+
+/// Send a locally originated envelope to every connected peer that accepts it
+pub(crate) async fn originate(state: &AppState, envelope: Envelope) -> Vec<String> {
+    originate_traced(state, envelope, None).await
+}
+
+/// Like [`originate`], recording the routing decision and per-peer outcomes
+async fn originate_traced(state: &AppState, mut envelope: Envelope, tracer: Option<&Tracer>) -> Vec<String> {
+    envelope.ttl = state.config.get().protocol.ttl.for_message_type(&envelope.message_type);
+    if let Err(e) = state.storage.mark_message_seen(&envelope.message_id).await {
+        warn!("Failed to record message {}: {}", envelope.message_id, e);
+    }
+
+    let peers = state.peers.read().await;
+    let peer_ids: Vec<String> = peers
+        .list_peers()
+        .iter()
+        .filter(|p| p.status == PeerStatus::Connected)
+        .map(|p| p.id.clone())
+        .collect();
+    let targets = select_targets(state, &peers, &envelope, &peer_ids);
+    drop(peers);
+
+    if let Some(tracer) = tracer {
+        let selected: Vec<&str> = targets.iter().map(|t| t.peer_id.as_str()).collect();
+        let excluded: Vec<&str> = peer_ids
+            .iter()
+            .map(String::as_str)
+            .filter(|id| !selected.contains(id))
+            .collect();
+        tracer.set_message_id(&envelope.message_id);
+        tracer.record(
+            "route",
+            StageOutcome::Ok,
+            Some(format!(
+                "{} connected peers; forwarding to [{}]; excluded by policy or interests [{}]",
+                peer_ids.len(),
+                selected.join(", "),
+                excluded.join(", ")
+            )),
+        );
+    }
+
+    dispatch(state, envelope, targets, tracer.cloned())
+}
+
+/// Forward a received envelope according to the routing engine's decision
+async fn relay(state: &AppState, envelope: &Envelope, sender: &str) -> Vec<String> {
+    let peers = state.peers.read().await;
+    let peer_ids: Vec<String> = peers
+        .list_peers()
+        .iter()
+        .filter(|p| p.status == PeerStatus::Connected && p.id != sender)
+        .map(|p| p.id.clone())
+        .collect();
+
+    let decision = state.routing.decide(
+        &envelope.message_type,
+        &envelope.source_node_id,
+        envelope.hop_count,
+        envelope.ttl,
+        &peer_ids,
+    );
+    let (RoutingDecision::AcceptAndForward { peer_ids }, Some(mut forwarded)) = (decision, envelope.forwarded()) else {
+        return Vec::new();
+    };
+    clamp_ttl(state, &peers, &mut forwarded, sender);
+
+    let targets = select_targets(state, &peers, &forwarded, &peer_ids);
+    drop(peers);
+
+    dispatch(state, forwarded, targets, None)
+}
+
+/// Lower the TTL of an envelope being passed on to its message type's TTL
+/// and to the `max_ttl` of the peer it came from
+pub(crate) fn clamp_ttl(state: &AppState, peers: &PeerManager, envelope: &mut Envelope, sender: &str) {
+    let type_ttl = state.config.get().protocol.ttl.for_message_type(&envelope.message_type);
+    let peer_ttl = peers.get_peer(sender).and_then(|p| p.policies.max_ttl);
+    envelope.ttl = envelope.ttl.min(type_ttl).min(peer_ttl.unwrap_or(u32::MAX));
+}
+
+/// A peer chosen to receive an envelope
+struct Target {
+    peer_id: String,
+    link: Arc<dyn Transport>,
+    redact: RedactionPolicy,
+    max_ttl: Option<u32>,
+    /// The session does not carry signed provenance
+    strip_provenance: bool,
+}
+
+/// Resolve peer IDs to links, keeping peers whose policies accept the
+/// message type and whose interests cover the envelope
+fn select_targets(
+    state: &AppState,
+    peers: &PeerManager,
+    envelope: &Envelope,
+    peer_ids: &[String],
+) -> Vec<Target> {
+    if !state.leadership.is_leader() {
+        return Vec::new();
+    }
+    peer_ids
+        .iter()
+        .filter(|id| exclusion(state, peers, envelope, id).is_none())
+        .filter_map(|id| {
+            let peer = peers.get_peer(id)?;
+            peers.link(id).map(|link| Target {
+                peer_id: id.clone(),
+                link,
+                redact: peer.policies.redact.clone(),
+                max_ttl: peer.policies.max_ttl,
+                strip_provenance: !envelope.provenance.is_empty()
+                    && peers.session(id).is_some_and(|s| {
+                        s.protocol_version.is_some() && !s.capabilities.iter().any(|c| c == CAPABILITY_SIGNING)
+                    }),
+            })
+        })
+        .collect()
+}
+
+/// Why a peer is not sent an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exclusion {
+    /// Not a configured peer
+    Unknown,
+    /// On the blocklist
+    Blocked,
+    /// The peer's policies refuse the message type
+    Policy,
+    Quarantined,
+    /// Outside the interests the peer advertised
+    Interests,
+    /// No session link to send on
+    NoLink,
+}
+
+/// What keeps a peer from being sent an envelope, if anything
+pub(crate) fn exclusion(state: &AppState, peers: &PeerManager, envelope: &Envelope, id: &str) -> Option<Exclusion> {
+    let Some(peer) = peers.get_peer(id) else {
+        return Some(Exclusion::Unknown);
+    };
+    if peers.is_blocked(id) {
+        Some(Exclusion::Blocked)
+    } else if !state.routing.should_forward_to_peer(&envelope.message_type, &peer.policies) {
+        Some(Exclusion::Policy)
+    } else if peers.is_quarantined(id) {
+        Some(Exclusion::Quarantined)
+    } else if !state.routing.matches_interests(envelope, peers.interests(id)) {
+        Some(Exclusion::Interests)
+    } else if peers.link(id).is_none() {
+        Some(Exclusion::NoLink)
+    } else {
+        None
+    }
+}
+
+/// Queue an envelope on each target's fan-out lane, returning the peers it
+/// was queued for
+fn dispatch(state: &AppState, envelope: Envelope, targets: Vec<Target>, tracer: Option<Tracer>) -> Vec<String> {
+    // Held by every delivery; the queue charge is released with the last
+    let queued = Arc::new(state.memory.charge_queue(envelope.footprint()));
+    let original = Arc::new(envelope);
+    targets
+        .into_iter()
+        .filter_map(|Target { peer_id, link, redact, max_ttl, strip_provenance }| {
+            let stage = format!("forward:{}", peer_id);
+            // Redacted, TTL-clamped and downgraded copies go to this peer
+            // only; the original stays as stored
+            let clamped = max_ttl.filter(|max| *max < original.ttl);
+            let envelope = if redact.is_empty() && clamped.is_none() && !strip_provenance {
+                original.clone()
+            } else {
+                let mut copy = redact_envelope(&original, &redact);
+                copy.ttl = clamped.unwrap_or(copy.ttl);
+                if strip_provenance {
+                    copy.provenance.clear();
+                }
+                Arc::new(copy)
+            };
+            if let Some(tracer) = &tracer {
+                let redacted = if redact.is_empty() { "" } else { ", redacted" };
+                let ttl = clamped.map(|ttl| format!(", TTL {}", ttl)).unwrap_or_default();
+                tracer.record(
+                    stage.clone(),
+                    StageOutcome::Pending,
+                    Some(format!("queued via {:?}{}{}", link.kind(), redacted, ttl)),
+                );
+            }
+            let done = delivery_report(state, &peer_id, &envelope, tracer.clone(), queued.clone());
+            state.deliveries.queued(&envelope, &peer_id);
+            match state.fanout.submit(&peer_id, envelope.clone(), link, done) {
+                Ok(()) => Some(peer_id),
+                Err(refusal) => {
+                    warn!("Not forwarding {} to {}: {}", envelope.message_type, peer_id, refusal);
+                    if let Some(tracer) = &tracer {
+                        tracer.record(stage, StageOutcome::Rejected, Some(refusal.to_string()));
+                    }
+                    let error = Error::Peer(format!("not queued: {}", refusal));
+                    state.deliveries.update(&envelope.message_id, &peer_id, DeliveryState::Failed, Some(error.to_string()));
+                    state.dead_letters.record(DeadLetterReason::Dropped, &peer_id, (*envelope).clone(), &error);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Record a finished delivery in the metrics, the peer's session, the
+/// delivery tracking and the trace
+fn delivery_report(
+    state: &AppState,
+    peer_id: &str,
+    envelope: &Arc<Envelope>,
+    tracer: Option<Tracer>,
+    queued: Arc<QueueCharge>,
+) -> DeliveryCallback {
+    let state = state.clone();
+    let id = peer_id.to_string();
+    let envelope = envelope.clone();
+    let message_type = envelope.message_type.clone();
+    Box::new(move |delivery| {
+        Box::pin(async move {
+            state.sla.record_forward(&id, delivery.elapsed, delivery.result.is_ok());
+            match &delivery.result {
+                Ok(reply) => {
+                    state.metrics.messages_sent.fetch_add(1, Ordering::Relaxed);
+                    state.peers.write().await.record_sent(&id, &message_type);
+                    record_peer_outcome(&state, &id, true).await;
+                    track_delivery(&state, &id, &envelope, reply.as_ref()).await;
+                }
+                Err(e) => {
+                    state.metrics.errors.fetch_add(1, Ordering::Relaxed);
+                    warn!("Forwarding {} to {} failed after {} attempts: {}", message_type, id, delivery.attempts, e);
+                    state.peers.write().await.record_error(
+                        &id,
+                        SessionEventKind::SendFailed,
+                        format!("{}: {}", message_type, e),
+                    );
+                    if matches!(e, Error::PeerRefused(_)) {
+                        record_peer_outcome(&state, &id, false).await;
+                    }
+                    state.deliveries.update(&envelope.message_id, &id, DeliveryState::Failed, Some(e.to_string()));
+                    let envelope = Arc::unwrap_or_clone(envelope);
+                    state.dead_letters.record(DeadLetterReason::RetriesExhausted, &id, envelope, e);
+                }
+            }
+            if let Some(tracer) = tracer {
+                let retried = (delivery.attempts > 1).then(|| format!("{} attempts", delivery.attempts));
+                let (outcome, detail) = match delivery.result {
+                    Ok(_) => (StageOutcome::Ok, retried),
+                    Err(e) => (StageOutcome::Failed, Some(format!("{} ({} attempts)", e, delivery.attempts))),
+                };
+                tracer.record_timed(format!("forward:{}", id), outcome, detail, delivery.elapsed);
+            }
+            drop(queued);
+        })
+    })
+}
+
+/// Whether a peer acknowledges CDM announcements and withdrawals
+async fn acknowledges(state: &AppState, peer_id: &str) -> bool {
+    state
+        .peers
+        .read()
+        .await
+        .session(peer_id)
+        .is_some_and(|s| s.capabilities.iter().any(|c| c == CAPABILITY_ACK))
+}
+
+/// ACK for a CDM announcement or withdrawal taken in from a peer that
+/// acknowledges them
+async fn ack_for(state: &AppState, envelope: &Envelope, sender: &str) -> Option<Envelope> {
+    tracked_cdm(envelope)?;
+    if !acknowledges(state, sender).await {
+        return None;
+    }
+    let ack = AckPayload {
+        related_message_id: envelope.message_id.clone(),
+    };
+    let payload = serde_json::to_value(ack).ok()?;
+    Some(Envelope::new(state.config.get().node.id.clone(), MessageType::Ack, payload))
+}
+
+/// Record a peer's ACK of a forwarded envelope
+fn acknowledged(state: &AppState, message_id: &str, peer_id: &str) {
+    state.metrics.acks_received.fetch_add(1, Ordering::Relaxed);
+    if !state.deliveries.acknowledge(message_id, peer_id) {
+        debug!("ACK from {} for untracked message {}", peer_id, message_id);
+    }
+}
+
+/// Track a forwarded announcement or withdrawal once sent: acknowledged in
+/// the reply, awaiting the peer's ACK, or delivered to a peer that does not
+/// acknowledge. Under at-least-once delivery, an ACK still missing after
+/// `protocol.delivery.ack_timeout_seconds` has the envelope sent again.
+async fn track_delivery(state: &AppState, peer_id: &str, envelope: &Arc<Envelope>, reply: Option<&Envelope>) {
+    if tracked_cdm(envelope).is_none() {
+        return;
+    }
+    if let Some(reply) = reply.filter(|reply| reply.message_type == MessageType::Ack) {
+        match reply.payload.parse::<AckPayload>() {
+            Ok(ack) => acknowledged(state, &ack.related_message_id, peer_id),
+            Err(e) => warn!("Malformed ACK from {}: {}", peer_id, e),
+        }
+        return;
+    }
+    if !acknowledges(state, peer_id).await {
+        state.deliveries.update(&envelope.message_id, peer_id, DeliveryState::Delivered, None);
+        return;
+    }
+    if !state.deliveries.update(&envelope.message_id, peer_id, DeliveryState::AwaitingAck, None) {
+        return;
+    }
+    let delivery = state.config.get().protocol.delivery.clone();
+    if delivery.guarantee != DeliveryGuarantee::AtLeastOnce {
+        return;
+    }
+    let task_state = state.clone();
+    let peer_id = peer_id.to_string();
+    let envelope = envelope.clone();
+    state.tasks.spawn(async move {
+        tokio::time::sleep(Duration::from_secs(delivery.ack_timeout_seconds)).await;
+        redeliver_unacknowledged(&task_state, &peer_id, envelope).await;
+    });
+}
+
+/// Send an envelope a peer has not acknowledged again, or dead-letter it
+/// once out of redeliveries
+async fn redeliver_unacknowledged(state: &AppState, peer_id: &str, envelope: Arc<Envelope>) {
+    let message_id = &envelope.message_id;
+    let Some(delivery) = state
+        .deliveries
+        .delivery(message_id, peer_id)
+        .filter(|delivery| delivery.state == DeliveryState::AwaitingAck)
+    else {
+        return;
+    };
+    if state.peers.read().await.is_blocked(peer_id) {
+        state.deliveries.update(message_id, peer_id, DeliveryState::Failed, Some("peer blocked".to_string()));
+        return;
+    }
+    let max_redeliveries = state.config.get().protocol.delivery.max_redeliveries;
+    let link = state.peers.read().await.link(peer_id);
+    let link = match link {
+        Some(link) if delivery.redeliveries < max_redeliveries => link,
+        Some(_) => {
+            let error = Error::Peer(format!("not acknowledged after {} redeliveries", delivery.redeliveries));
+            return give_up_unacknowledged(state, peer_id, &envelope, &error);
+        }
+        None => {
+            let error = Error::Peer(format!("not acknowledged, and no link to {}", peer_id));
+            return give_up_unacknowledged(state, peer_id, &envelope, &error);
+        }
+    };
+
+    debug!("Redelivering {} {} to {}: not acknowledged", envelope.message_type, message_id, peer_id);
+    state.deliveries.redelivered(message_id, peer_id);
+    state.metrics.redeliveries.fetch_add(1, Ordering::Relaxed);
+    let queued = Arc::new(state.memory.charge_queue(envelope.footprint()));
+    let done = delivery_report(state, peer_id, &envelope, None, queued);
+    if let Err(refusal) = state.fanout.submit(peer_id, envelope.clone(), link, done) {
+        let error = Error::Peer(format!("not queued: {}", refusal));
+        state.deliveries.update(message_id, peer_id, DeliveryState::Failed, Some(error.to_string()));
+        state.dead_letters.record(DeadLetterReason::Dropped, peer_id, (*envelope).clone(), &error);
+    }
+}
+
+fn give_up_unacknowledged(state: &AppState, peer_id: &str, envelope: &Envelope, error: &Error) {
+    state.deliveries.update(&envelope.message_id, peer_id, DeliveryState::Unacknowledged, Some(error.to_string()));
+    state.metrics.deliveries_unacknowledged.fetch_add(1, Ordering::Relaxed);
+    state.dead_letters.record(DeadLetterReason::Unacknowledged, peer_id, envelope.clone(), error);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::catalog::{CatalogEntry, CatalogMatch, CatalogProvider, CatalogSearch};
+    use crate::cdm::generate_demo_cdm;
+    use crate::protocol::{
+        CdmRequestPayload, CdmResponsePayload, Interests, ObjectType, PayloadEncoding, CAPABILITY_BATCHING, DEFAULT_TTL,
+        MAX_INFLATED_BYTES,
+    };
+    use crate::node::{CdmEventKind, HttpTransport, PeerManager, REDACTED_OWNER};
+    use crate::storage::MemoryStorage;
+
+    /// Application state for a node with default configuration
+    pub(crate) fn test_state(node_id: &str) -> AppState {
+        // Test peers send as whatever node they like; see test_peer_authentication
+        let config: Config = serde_yaml::from_str(&format!(
+            "node: {{ id: {} }}\nserver: {{}}\nprotocol: {{ require_peer_auth: false }}",
+            node_id
+        ))
+        .unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        NodeServer::new(config, storage, Arc::new(RwLock::new(PeerManager::new())), routing)
+            .state()
+            .clone()
+    }
+
+    async fn send(state: &AppState, content_type: &str, from: &str, body: Vec<u8>) -> (StatusCode, Option<Envelope>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        headers.insert(NODE_ID_HEADER, from.parse().unwrap());
+
+        let resp = receive_message(State(state.clone()), headers, Body::from(body)).await;
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        // Unsupported content types are answered in JSON
+        let encoding = Encoding::from_content_type(content_type).unwrap_or_default();
+        let envelope = (!body.is_empty()).then(|| Envelope::decode(&body, encoding).unwrap());
+        (status, envelope)
+    }
+
+    fn error_payload(envelope: Option<Envelope>) -> ErrorPayload {
+        let envelope = envelope.expect("ERROR envelope");
+        assert_eq!(envelope.message_type, MessageType::Error);
+        envelope.payload.parse().unwrap()
+    }
+
+    fn cdm_envelope() -> Envelope {
+        Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(generate_demo_cdm()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_accepts_valid_announcement() {
+        let state = test_state("node-local");
+        let body = serde_json::to_vec(&cdm_envelope()).unwrap();
+        let (status, reply) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(reply.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cdm_provenance() {
+        use crate::protocol::ProvenanceSigner;
+        let state = test_state("node-local");
+        let remote_seed = ProvenanceSigner::generate_seed();
+        let remote = ProvenanceSigner::from_seed(&remote_seed).unwrap();
+        let mut config = (*state.config.get()).clone();
+        config.provenance.signing_key = Some(ProvenanceSigner::generate_seed());
+        config.provenance.trusted_keys.insert("node-remote".into(), remote.public_key());
+        state.config.replace(config);
+
+        let mut envelope = cdm_envelope();
+        let cdm_id = envelope.payload["cdm_id"].as_str().unwrap().to_string();
+        attest(&mut envelope.provenance, &cdm_id, &envelope.payload, "node-origin", None);
+        attest(&mut envelope.provenance, &cdm_id, &envelope.payload, "node-remote", Some(&remote));
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let provenance = |id: String| get_cdm_provenance(State(state.clone()), TenantScope::default(), Path(id));
+        let Json(path) = provenance(cdm_id.clone()).await.unwrap();
+        let hops: Vec<_> = path.hops.iter().map(|h| (h.hop.node_id.as_str(), h.verified)).collect();
+        assert_eq!(hops, [("node-origin", None), ("node-remote", Some(true)), ("node-local", Some(true))]);
+
+        // A payload changed after a hop signed it fails that hop
+        let mut changed = cdm_envelope();
+        let changed_id = changed.payload["cdm_id"].as_str().unwrap().to_string();
+        attest(&mut changed.provenance, &changed_id, &changed.payload, "node-remote", Some(&remote));
+        changed.payload.make_mut()["miss_distance_m"] = serde_json::json!(4321.0);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&changed).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let Json(path) = provenance(changed_id).await.unwrap();
+        let hops: Vec<_> = path.hops.iter().map(|h| (h.hop.node_id.as_str(), h.verified)).collect();
+        assert_eq!(hops, [("node-remote", Some(false)), ("node-local", Some(true))]);
+
+        // A locally ingested CDM starts a new chain
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = "CDM-LOCAL".into();
+        announce_cdm(&state, cdm, &None).await.unwrap();
+        let Json(path) = provenance("CDM-LOCAL".into()).await.unwrap();
+        assert_eq!(path.hops.len(), 1);
+        assert_eq!(path.hops[0].hop.node_id, "node-local");
+        assert!(provenance("CDM-NONE".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_message_error() {
+        let state = test_state("node-local");
+        let mut envelope = serde_json::to_value(cdm_envelope()).unwrap();
+        envelope["payload"]["tca"] = "not a time".into();
+        envelope["message_id"] = "msg-bad-1".into();
+
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = error_payload(reply);
+        assert_eq!(error.error_code, ErrorCode::InvalidMessage);
+        assert_eq!(error.related_message_id.as_deref(), Some("msg-bad-1"));
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_envelope_error() {
+        let state = test_state("node-local");
+        let body = br#"{"message_id": "msg-bad-2", "message_type": "CDM_ANNOUNCE"}"#.to_vec();
+        let (status, reply) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = error_payload(reply);
+        assert_eq!(error.error_code, ErrorCode::InvalidMessage);
+        assert_eq!(error.related_message_id.as_deref(), Some("msg-bad-2"));
+
+        let (status, reply) = send(&state, "application/cbor", "node-remote", vec![0xff, 0x00]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_payload(reply).related_message_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_media_type_error() {
+        let state = test_state("node-local");
+        let (status, reply) = send(&state, "text/plain", "node-remote", b"hello".to_vec()).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::InvalidMessage);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_version_error() {
+        let state = test_state("node-local");
+        let hello = HelloPayload {
+            protocol_version: "2.0.0".to_string(),
+            ..Default::default()
+        };
+        let envelope = Envelope::new("node-remote".to_string(), MessageType::Hello, serde_json::to_value(hello).unwrap());
+        let message_id = envelope.message_id.clone();
+
+        let (status, reply) = send(&state, "application/cbor", "node-remote", envelope.to_cbor().unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = error_payload(reply);
+        assert_eq!(error.error_code, ErrorCode::UnsupportedVersion);
+        assert_eq!(error.related_message_id, Some(message_id));
+    }
+
+    #[tokio::test]
+    async fn test_policy_rejection_error() {
+        let state = test_state("node-local");
+        state.peers.write().await.add_peer(PeerInfo {
+            id: "node-remote".to_string(),
+            address: "http://localhost:1".to_string(),
+            status: PeerStatus::Connected,
+            last_heartbeat: None,
+            messages_sent: 0,
+            messages_received: 0,
+            transport: Default::default(),
+            encoding: Encoding::Json,
+            timestamp_format: None,
+            auth_token: None,
+            policies: PeerPolicies {
+                accept_cdm: false,
+                ..Default::default()
+            },
+        });
+
+        let body = serde_json::to_vec(&cdm_envelope()).unwrap();
+        let (status, reply) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters() {
+        let state = test_state("node-local");
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9', policies: { accept_cdm: false } }")
+                .unwrap();
+        state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+
+        let refused = cdm_envelope();
+        let cdm: CdmRecord = refused.payload.parse().unwrap();
+        send(&state, "application/json", "node-remote", serde_json::to_vec(&refused).unwrap()).await;
+        let invalid = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::json!({ "cdm_id": "CDM-BAD" }),
+        );
+        send(&state, "application/json", "node-other", serde_json::to_vec(&invalid).unwrap()).await;
+
+        let Json(list) = list_dead_letters(State(state.clone()), Query(DeadLetterQuery::default())).await;
+        assert_eq!(list.total, 2);
+        assert_eq!(list.dead_letters[0].reason, DeadLetterReason::Invalid);
+        let policy = &list.dead_letters[1];
+        assert_eq!((policy.reason, policy.peer_id.as_str()), (DeadLetterReason::PolicyRejected, "node-remote"));
+        let query = DeadLetterQuery {
+            reason: Some(DeadLetterReason::PolicyRejected),
+            ..Default::default()
+        };
+        assert_eq!(list_dead_letters(State(state.clone()), Query(query)).await.total, 1);
+
+        // Still refused, so kept with the count of attempts
+        let requeued = requeue_dead_letter(State(state.clone()), Path(policy.id.clone())).await;
+        assert_eq!(requeued.err().unwrap().0, StatusCode::CONFLICT);
+        assert_eq!(state.dead_letters.get(&policy.id).unwrap().requeues, 1);
+
+        // Accepted once the policy allows it
+        state.peers.write().await.get_peer_mut("node-remote").unwrap().policies.accept_cdm = true;
+        let Json(requeued) = requeue_dead_letter(State(state.clone()), Path(policy.id.clone())).await.unwrap();
+        assert_eq!(requeued.message_id, refused.message_id);
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_some());
+        assert!(state.dead_letters.get(&policy.id).is_none());
+
+        let invalid_id = list.dead_letters[0].id.clone();
+        assert!(discard_dead_letter(State(state.clone()), Path(invalid_id.clone())).await.is_ok());
+        let missing = discard_dead_letter(State(state.clone()), Path(invalid_id)).await;
+        assert_eq!(missing.err().unwrap().0, StatusCode::NOT_FOUND);
+        assert_eq!(state.dead_letters.metrics().held, 0);
+    }
+
+    #[tokio::test]
+    async fn test_peer_quarantine() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.peer_health = crate::config::PeerHealthConfig {
+            window: 10,
+            min_samples: 2,
+            max_error_rate: 0.5,
+            quarantine_seconds: 60,
+        };
+        state.config.replace(config);
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9' }").unwrap();
+        state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+
+        let invalid = || {
+            let envelope = Envelope::new(
+                "node-remote".to_string(),
+                MessageType::CdmAnnounce,
+                serde_json::json!({ "cdm_id": "CDM-BAD" }),
+            );
+            serde_json::to_vec(&envelope).unwrap()
+        };
+        let valid = || serde_json::to_vec(&cdm_envelope()).unwrap();
+        let (status, _) = send(&state, "application/json", "node-remote", valid()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        send(&state, "application/json", "node-remote", invalid()).await;
+        assert!(!state.peers.read().await.is_quarantined("node-remote"));
+        // The peer's own ERROR tips it over half
+        let error = Envelope::error(
+            "node-remote".to_string(),
+            ErrorPayload::from_error(&Error::Protocol("confused".into()), None),
+        );
+        send(&state, "application/json", "node-remote", serde_json::to_vec(&error).unwrap()).await;
+        assert!(state.peers.read().await.is_quarantined("node-remote"));
+        assert_eq!(state.metrics.peers_quarantined.load(Ordering::Relaxed), 1);
+
+        let (status, reply) = send(&state, "application/json", "node-remote", valid()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(error_payload(reply).error_message.contains("quarantined"));
+        let health = state.peers.read().await.session("node-remote").unwrap().health;
+        assert_eq!((health.samples, health.errors, health.quarantines), (3, 2, 1));
+
+        let Json(health) = release_peer(State(state.clone()), Path("node-remote".into())).await.unwrap();
+        assert_eq!((health.score, health.samples), (100.0, 0));
+        let (status, _) = send(&state, "application/json", "node-remote", valid()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(release_peer(State(state.clone()), Path("node-x".into())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_originator_hold() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.ingest_quotas.max_duplicate_payloads = 2;
+        config.peer_health.min_samples = 1;
+        state.config.replace(config);
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9' }").unwrap();
+        state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+
+        // A provider repeating one CDM under new IDs, relayed by a peer
+        let storm = generate_demo_cdm();
+        let copy = || {
+            let mut cdm = storm.clone();
+            cdm.cdm_id = uuid::Uuid::new_v4().to_string();
+            let envelope = Envelope::new("node-remote".to_string(), MessageType::CdmAnnounce, serde_json::to_value(cdm).unwrap());
+            serde_json::to_vec(&envelope).unwrap()
+        };
+        for _ in 0..2 {
+            let (status, _) = send(&state, "application/json", "node-remote", copy()).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+        }
+        let (status, reply) = send(&state, "application/json", "node-remote", copy()).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(error_payload(reply).error_message.contains("payload storm"));
+        assert_eq!(state.metrics.originator_anomalies.load(Ordering::Relaxed), 1);
+        // The relaying peer is not blamed
+        assert!(!state.peers.read().await.is_quarantined("node-remote"));
+
+        // Held on every path until released
+        let mut other = generate_demo_cdm();
+        other.originator = storm.originator.clone();
+        let resp = ingest_cdm(
+            State(state.clone()),
+            Query(IngestQuery::default()),
+            None,
+            HeaderMap::new(),
+            Json(serde_json::to_value(&other).unwrap()),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(state.metrics.originator_refusals.load(Ordering::Relaxed), 2);
+
+        let Json(list) = list_originators(State(state.clone())).await;
+        assert_eq!(list[0].held.as_ref().map(|a| a.kind), Some(crate::node::AnomalyKind::PayloadStorm));
+        let Json(anomaly) = release_originator(State(state.clone()), Path(storm.originator.clone())).await.unwrap();
+        assert_eq!(anomaly.kind, crate::node::AnomalyKind::PayloadStorm);
+        assert!(release_originator(State(state.clone()), Path(storm.originator.clone())).await.is_err());
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm_envelope()).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_peer_admin_down() {
+        let state = test_state("node-local");
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9' }").unwrap();
+        state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+        let hello = |admin_down: bool| {
+            let hello = HelloPayload {
+                admin_down,
+                ..Default::default()
+            };
+            let envelope = Envelope::new("node-remote".to_string(), MessageType::Hello, serde_json::to_value(hello).unwrap());
+            serde_json::to_vec(&envelope).unwrap()
+        };
+
+        let body = Json(DisablePeerRequest {
+            reason: Some("rack move".into()),
+        });
+        let Json(peer) = disable_peer(State(state.clone()), Path("node-remote".into()), Some(body)).await.unwrap();
+        assert_eq!(peer.status, PeerStatus::AdminDown);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm_envelope()).unwrap()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        // The peer's HELLO is answered with the shutdown, not a session
+        let (status, reply) = send(&state, "application/json", "node-remote", hello(false)).await;
+        assert_eq!(status, StatusCode::OK);
+        let reply: HelloPayload = reply.unwrap().payload.parse().unwrap();
+        assert!(reply.admin_down);
+        assert_eq!(reply.admin_down_reason.as_deref(), Some("rack move"));
+        assert_eq!(state.peers.read().await.get_peer("node-remote").unwrap().status, PeerStatus::AdminDown);
+
+        let Json(peer) = enable_peer(State(state.clone()), Path("node-remote".into())).await.unwrap();
+        assert_eq!(peer.status, PeerStatus::Disconnected);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm_envelope()).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        // The peer's own shutdown, until it says hello again
+        send(&state, "application/json", "node-remote", hello(true)).await;
+        let session = state.peers.read().await.session("node-remote").unwrap();
+        assert!(session.admin_down.is_some_and(|down| down.by_peer));
+        send(&state, "application/json", "node-remote", hello(false)).await;
+        assert_eq!(state.peers.read().await.get_peer("node-remote").unwrap().status, PeerStatus::Connected);
+
+        assert!(disable_peer(State(state.clone()), Path("node-x".into()), None).await.is_err());
+        assert!(enable_peer(State(state.clone()), Path("node-x".into())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_authentication() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.protocol.require_peer_auth = true;
+        state.config.replace(config);
+        for (id, token) in [("node-a", "secret-a"), ("node-b", "secret-b")] {
+            let peer: crate::config::PeerConfig =
+                serde_yaml::from_str(&format!("{{ id: {}, address: 'http://127.0.0.1:9', auth_token: {} }}", id, token))
+                    .unwrap();
+            state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+        }
+        let send_as = |from: Option<&'static str>, token: Option<&'static str>, envelope: Envelope| {
+            let state = state.clone();
+            async move {
+                let mut headers = HeaderMap::new();
+                headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+                if let Some(from) = from {
+                    headers.insert(NODE_ID_HEADER, from.parse().unwrap());
+                }
+                if let Some(token) = token {
+                    headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+                }
+                let body = Body::from(serde_json::to_vec(&envelope).unwrap());
+                let resp = receive_message(State(state), headers, body).await;
+                let status = resp.status();
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+                (status, (!body.is_empty()).then(|| Envelope::decode(&body, Encoding::Json).unwrap()))
+            }
+        };
+        let hello = |source: &str, auth_token: Option<&str>, admin_down: bool| {
+            let hello = HelloPayload {
+                auth_token: auth_token.map(str::to_string),
+                admin_down,
+                ..Default::default()
+            };
+            Envelope::new(source.to_string(), MessageType::Hello, serde_json::to_value(hello).unwrap())
+        };
+
+        // A wrong token, a spoofed node ID header and no credential at all
+        let (status, reply) = send_as(Some("node-a"), Some("guess"), cdm_envelope()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::Unauthorized);
+        let (status, _) = send_as(Some("node-a"), Some("secret-b"), cdm_envelope()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(Some("node-a"), None, hello("node-a", None, true)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(Some("node-a"), None, hello("node-a", Some("secret-b"), true)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_ne!(state.peers.read().await.get_peer("node-a").unwrap().status, PeerStatus::AdminDown);
+        assert_eq!(state.storage.list_cdms().await.unwrap().len(), 0);
+
+        // The credential names the sender, with or without the header
+        let (status, reply) = send_as(None, None, hello("node-a", Some("secret-a"), false)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reply.unwrap().message_type, MessageType::Hello);
+        let (status, _) = send_as(None, Some("secret-b"), cdm_envelope()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(state.peers.read().await.get_peer("node-b").unwrap().messages_received, 1);
+
+        // A federation member not peered with yet presents the discovery token
+        let mut config = (*state.config.get()).clone();
+        config.discovery = Some(serde_yaml::from_str("{ template: { auth_token: federation } }").unwrap());
+        state.config.replace(config);
+        let (status, _) = send_as(Some("node-new"), Some("federation"), hello("node-new", None, false)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(Some("node-a"), Some("federation"), hello("node-a", None, false)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Without require_peer_auth, only peers without a token may go without
+        let mut config = (*state.config.get()).clone();
+        config.protocol.require_peer_auth = false;
+        state.config.replace(config);
+        let (status, _) = send_as(Some("node-a"), None, hello("node-a", None, false)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(Some("node-x"), None, hello("node-x", None, false)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_block_peer() {
+        let state = test_state("node-local");
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9' }").unwrap();
+        state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+
+        let body = Json(BlockPeerRequest {
+            reason: Some("compromised key".into()),
+        });
+        let Json(blocked) = block_peer(State(state.clone()), Path("node-remote".into()), Some(body)).await.unwrap();
+        assert_eq!((blocked.blocked.node_id.as_str(), blocked.purged), ("node-remote", 0));
+        assert_eq!(state.peers.read().await.get_peer("node-remote").unwrap().status, PeerStatus::Blocked);
+        assert_eq!(state.storage.list_blocked_nodes().await.unwrap().len(), 1);
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm_envelope()).unwrap()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(error_payload(reply).error_message.contains("blocked"));
+        // Not held against the peer's health
+        assert_eq!(state.peers.read().await.session("node-remote").unwrap().health.samples, 0);
+
+        // Whatever path its messages take, and for nodes that are not peers
+        let Json(far) = block_peer(State(state.clone()), Path("node-far".into()), None).await.unwrap();
+        assert!(far.blocked.reason.is_none());
+        let mut relayed = cdm_envelope();
+        relayed.source_node_id = "node-other".into();
+        relayed.provenance.push(ProvenanceHop {
+            node_id: "node-far".into(),
+            received_at: Utc::now(),
+            payload_sha256: None,
+            signature: None,
+        });
+        let (status, _) = send(&state, "application/json", "node-other", serde_json::to_vec(&relayed).unwrap()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(state.metrics.messages_blocked.load(Ordering::Relaxed), 2);
+        let Json(listed) = list_blocked_nodes(State(state.clone())).await;
+        assert_eq!(listed.iter().map(|n| n.node_id.as_str()).collect::<Vec<_>>(), ["node-far", "node-remote"]);
+
+        let Json(unblocked) = unblock_peer(State(state.clone()), Path("node-remote".into())).await.unwrap();
+        assert_eq!(unblocked.reason.as_deref(), Some("compromised key"));
+        assert_eq!(state.peers.read().await.get_peer("node-remote").unwrap().status, PeerStatus::Disconnected);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm_envelope()).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let (status, _) = unblock_peer(State(state.clone()), Path("node-remote".into())).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(state.storage.list_blocked_nodes().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_import_peers() {
+        let source = test_state("node-a");
+        let peer: crate::config::PeerConfig =
+            serde_yaml::from_str("{ id: node-x, address: 'http://127.0.0.1:9', policies: { accept_maneuver: false } }").unwrap();
+        source.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+        let query = |format| PeerExportQuery {
+            format,
+            include_secrets: false,
+        };
+        let resp = export_peers(State(source.clone()), None, Query(query(PeeringFormat::Yaml))).await;
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/yaml");
+        let yaml = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let resp = export_peers(State(source.clone()), None, Query(query(PeeringFormat::Json))).await;
+        let json = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+
+        let target = test_state("node-b");
+        let import = |content_type: &str, body: Bytes| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+            import_peers(State(target.clone()), Query(PeerImportQuery { prune: false }), headers, body)
+        };
+        let Json(report) = import("application/yaml", yaml).await.unwrap();
+        assert_eq!((report.source_node_id.as_str(), report.added.as_slice()), ("node-a", &["node-x".to_string()][..]));
+        assert!(!target.peers.read().await.get_peer("node-x").unwrap().policies.accept_maneuver);
+        let Json(report) = import("application/json", json).await.unwrap();
+        assert_eq!(report.unchanged, ["node-x"]);
+
+        let (status, Json(error)) = import("application/json", Bytes::from("peers: []")).await.unwrap_err();
+        assert_eq!((status, error.error.as_str()), (StatusCode::BAD_REQUEST, "invalid_document"));
+        let wrong = "{ format: spacecomms-snapshot, version: 1, node_id: a, exported_at: '2026-01-01T00:00:00Z', peers: [] }";
+        let (status, _) = import("application/yaml", Bytes::from(wrong)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_update_peer_policies() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.peers = vec![serde_yaml::from_str("{ id: node-remote, address: 'http://127.0.0.1:9' }").unwrap()];
+        state.config.replace(config.clone());
+        state.peers.write().await.add_peer(PeerInfo::from_config(&config.peers[0]));
+
+        let update = |body: serde_json::Value| {
+            update_peer_policies(
+                State(state.clone()),
+                Path("node-remote".into()),
+                Json(serde_json::from_value(body).unwrap()),
+            )
+        };
+        let Json(peer) = update(serde_json::json!({ "block_message_types": ["CDM_ANNOUNCE"] })).await.unwrap();
+        assert_eq!(peer.policies.block_message_types, [MessageType::CdmAnnounce]);
+        assert!(peer.policies.accept_cdm);
+        let valid = serde_json::to_vec(&cdm_envelope()).unwrap();
+        let (status, _) = send(&state, "application/json", "node-remote", valid).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Session messages cannot be blocked, and unknown peers are refused
+        let (status, _) = update(serde_json::json!({ "block_message_types": ["HELLO"] })).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = update_peer_policies(State(state.clone()), Path("node-x".into()), Json(Default::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A TTL cap is kept until set to null
+        let Json(peer) = update(serde_json::json!({ "max_ttl": 2 })).await.unwrap();
+        assert_eq!(peer.policies.max_ttl, Some(2));
+        let Json(peer) = update(serde_json::json!({ "forward_cdm": true })).await.unwrap();
+        assert_eq!(peer.policies.max_ttl, Some(2));
+        let Json(peer) = update(serde_json::json!({ "max_ttl": null })).await.unwrap();
+        assert_eq!(peer.policies.max_ttl, None);
+
+        // Listed with the peer, and kept over a configuration reload
+        let Json(peer) = update(serde_json::json!({ "accept_maneuver": false })).await.unwrap();
+        let listed = serde_json::to_value(peer).unwrap();
+        assert_eq!(listed["policies"]["accept_maneuver"], false);
+        config.peers[0].address = "http://127.0.0.1:10".into();
+        crate::node::apply_config(&state, config).await.unwrap();
+        let peers = state.peers.read().await;
+        let policies = &peers.get_peer("node-remote").unwrap().policies;
+        assert!(!policies.accept_maneuver);
+        assert_eq!(policies.block_message_types, [MessageType::CdmAnnounce]);
+    }
+
+    #[tokio::test]
+    async fn test_cdm_query_messages() {
+        let state = test_state("node-local");
+        let request = CdmRequestPayload {
+            request_id: "q-1".to_string(),
+            query: Default::default(),
+        };
+        let envelope = Envelope::new(
+            "node-unknown".to_string(),
+            MessageType::CdmRequest,
+            serde_json::to_value(request).unwrap(),
+        );
+        let (status, reply) = send(&state, "application/json", "node-unknown", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::Unauthorized);
+
+        // Responses only travel as the reply to a request
+        let response = CdmResponsePayload {
+            request_id: "q-1".to_string(),
+            cdms: vec![],
+            truncated: false,
+        };
+        let envelope = Envelope::new(
+            "node-unknown".to_string(),
+            MessageType::CdmResponse,
+            serde_json::to_value(response).unwrap(),
+        );
+        let (status, reply) = send(&state, "application/json", "node-unknown", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::InvalidMessage);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_payloads() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.compression.min_payload_bytes = 256;
+        config.protocol.max_envelope_bytes = 64 * 1024;
+        state.config.replace(config);
+        {
+            let mut peers = state.peers.write().await;
+            peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(
+                "{ id: node-remote, address: 'http://localhost:1' }",
+            ).unwrap()));
+            peers.record_handshake("node-remote", "1.0.0".into(), vec![CAPABILITY_PAYLOAD_GZIP.into()]);
+        }
+
+        // A compressed announcement is inflated and handled as usual
+        let cdm = generate_demo_cdm();
+        let announce = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(&cdm).unwrap(),
+        );
+        let body = announce.compressed(TimestampFormat::Auto).unwrap().encode(Encoding::Json).unwrap();
+        let (status, _) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_some());
+
+        // Large replies go back compressed to a peer that offers it
+        let request = CdmRequestPayload {
+            request_id: "q-1".to_string(),
+            query: Default::default(),
+        };
+        let envelope = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmRequest,
+            serde_json::to_value(request).unwrap(),
+        );
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut reply = reply.unwrap();
+        assert_eq!(reply.payload_encoding, Some(PayloadEncoding::Gzip));
+        reply.inflate(MAX_INFLATED_BYTES).unwrap();
+        let response: CdmResponsePayload = reply.payload.parse().unwrap();
+        assert_eq!(response.cdms[0]["cdm_id"], cdm.cdm_id);
+
+        // Compression does not get a payload past the size limit
+        let padded = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::json!({ "padding": "x".repeat(128 * 1024) }),
+        );
+        let body = padded.compressed(TimestampFormat::Auto).unwrap().encode(Encoding::Json).unwrap();
+        assert!(body.len() < 64 * 1024);
+        let (status, _) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_interest_filtering() {
+        let state = test_state("node-local");
+        {
+            let mut peers = state.peers.write().await;
+            for id in ["node-all", "node-picky"] {
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&format!(
+                    "{{ id: {}, address: 'http://127.0.0.1:1' }}",
+                    id
+                )).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.set_link(id, Arc::new(HttpTransport::new("http://127.0.0.1:1", "node-local", None)));
+            }
+        }
+
+        // node-picky narrows its interests mid-session
+        let update = InterestUpdatePayload {
+            interests: Interests {
+                owners: vec!["Other Operator".to_string()],
+                ..Default::default()
+            },
+        };
+        let envelope = Envelope::new(
+            "node-picky".to_string(),
+            MessageType::InterestUpdate,
+            serde_json::to_value(update).unwrap(),
+        );
+        let (status, _) = send(&state, "application/json", "node-picky", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(state.peers.read().await.interests("node-picky").is_some());
+
+        let cdm = Envelope::new(
+            "node-local".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(generate_demo_cdm()).unwrap(),
+        );
+        assert_eq!(originate(&state, cdm.clone()).await, ["node-all"]);
+        let withdraw = Envelope::new(
+            "node-local".to_string(),
+            MessageType::CdmWithdraw,
+            serde_json::to_value(CdmWithdrawPayload {
+                cdm_id: "CDM-1".to_string(),
+                reason: crate::protocol::CdmWithdrawReason::Error,
+                superseded_by: None,
+                effective_time: Utc::now(),
+            })
+            .unwrap(),
+        );
+        assert_eq!(originate(&state, withdraw).await.len(), 2);
+
+        // Back to everything
+        let envelope = Envelope::new(
+            "node-picky".to_string(),
+            MessageType::InterestUpdate,
+            serde_json::to_value(InterestUpdatePayload { interests: Interests::default() }).unwrap(),
+        );
+        send(&state, "application/json", "node-picky", serde_json::to_vec(&envelope).unwrap()).await;
+        let cdm = Envelope { message_id: "msg-again".to_string(), ..cdm };
+        assert_eq!(originate(&state, cdm).await.len(), 2);
+    }
+
+    /// Link handing each envelope it sends to a channel
+    struct ChannelLink(tokio::sync::mpsc::UnboundedSender<Envelope>);
+
+    #[async_trait::async_trait]
+    impl Transport for ChannelLink {
+        fn kind(&self) -> crate::config::PeerTransport {
+            crate::config::PeerTransport::Http
+        }
+
+        async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+            let _ = self.0.send(envelope.clone());
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_redaction() {
+        let state = test_state("node-local");
+        let (tx, mut sent) = tokio::sync::mpsc::unbounded_channel();
+        {
+            let mut peers = state.peers.write().await;
+            let partner = "drop_covariance: true, anonymize_owner: true, position_resolution_km: 1.0";
+            for (id, redact) in [("node-src", ""), ("node-open", ""), ("node-partner", partner)] {
+                let config = format!(
+                    "{{ id: {}, address: 'http://127.0.0.1:1', policies: {{ redact: {{ {} }} }} }}",
+                    id, redact
+                );
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.set_link(id, Arc::new(ChannelLink(tx.clone())));
+            }
+        }
+
+        let envelope = Envelope {
+            source_node_id: "node-src".to_string(),
+            ..cdm_envelope()
+        };
+        let (status, _) = send(&state, "application/json", "node-src", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let envelope = tokio::time::timeout(std::time::Duration::from_secs(5), sent.recv()).await.unwrap().unwrap();
+            received.push(envelope.payload["object1"].clone());
+        }
+        let (open, partner): (Vec<_>, Vec<_>) = received.into_iter().partition(|o| o.get("covariance_rtm").is_some());
+        assert_eq!((open.len(), partner.len()), (1, 1));
+        assert_eq!(partner[0]["owner_operator"], REDACTED_OWNER);
+        assert_ne!(open[0]["owner_operator"], REDACTED_OWNER);
+
+        // The stored original keeps every field
+        let cdm_id = envelope.payload["cdm_id"].as_str().unwrap();
+        let stored = state.storage.get_cdm(cdm_id).await.unwrap().unwrap();
+        assert!(stored.object1.covariance_rtm.is_some());
+        assert_eq!(serde_json::to_value(&stored.object1.state_vector).unwrap(), open[0]["state_vector"]);
+    }
+
+    #[tokio::test]
+    async fn test_ttl_overrides() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.protocol.ttl.message_types.insert(MessageType::CdmAnnounce, 6);
+        state.config.replace(config);
+        let (tx, mut sent) = tokio::sync::mpsc::unbounded_channel();
+        {
+            let mut peers = state.peers.write().await;
+            for (id, policies) in [("node-src", ""), ("node-open", ""), ("node-near", "max_ttl: 1")] {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1', policies: {{ {} }} }}", id, policies);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.set_link(id, Arc::new(ChannelLink(tx.clone())));
+            }
+        }
+        async fn ttls(sent: &mut tokio::sync::mpsc::UnboundedReceiver<Envelope>, count: usize) -> Vec<u32> {
+            let mut ttls = Vec::new();
+            for _ in 0..count {
+                let envelope = tokio::time::timeout(std::time::Duration::from_secs(5), sent.recv()).await.unwrap().unwrap();
+                ttls.push(envelope.ttl);
+            }
+            ttls.sort();
+            ttls
+        }
+
+        // Forwarded CDMs leave with no more than the CDM_ANNOUNCE TTL, and
+        // no more than a peer's max_ttl
+        let envelope = Envelope {
+            source_node_id: "node-src".to_string(),
+            ..cdm_envelope()
+        };
+        assert_eq!(envelope.ttl, DEFAULT_TTL);
+        let (status, _) = send(&state, "application/json", "node-src", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(ttls(&mut sent, 2).await, [1, 6]);
+
+        // Envelopes from a peer are passed on with no more than its max_ttl
+        let envelope = Envelope {
+            source_node_id: "node-near".to_string(),
+            ..cdm_envelope()
+        };
+        let (status, _) = send(&state, "application/json", "node-near", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(ttls(&mut sent, 2).await, [1, 1]);
+
+        // Originated CDMs start with the CDM_ANNOUNCE TTL
+        let envelope = Envelope {
+            source_node_id: "node-local".to_string(),
+            ..cdm_envelope()
+        };
+        assert_eq!(originate(&state, envelope).await.len(), 3);
+        assert_eq!(ttls(&mut sent, 3).await, [1, 6, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_version_mesh() {
+        let state = test_state("node-local");
+        let (old_tx, mut old_sent) = tokio::sync::mpsc::unbounded_channel();
+        let (new_tx, mut new_sent) = tokio::sync::mpsc::unbounded_channel();
+        {
+            let mut peers = state.peers.write().await;
+            for (id, tx) in [("node-old", old_tx), ("node-new", new_tx)] {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1' }}", id);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.set_link(id, Arc::new(ChannelLink(tx)));
+            }
+        }
+        let hello = |id: &'static str, hello: HelloPayload| {
+            let envelope = Envelope::new(id.to_string(), MessageType::Hello, serde_json::to_value(hello).unwrap());
+            send(&state, "application/json", id, serde_json::to_vec(&envelope).unwrap())
+        };
+        let old = HelloPayload {
+            protocol_version: "1.0".to_string(),
+            supported_versions: vec!["1.0".to_string()],
+            ..Default::default()
+        };
+        let (status, reply) = hello("node-old", old.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let reply: HelloPayload = reply.unwrap().payload.parse().unwrap();
+        assert_eq!(reply.protocol_version, "1.1");
+        hello("node-new", HelloPayload::default()).await;
+        {
+            let peers = state.peers.read().await;
+            let session = peers.session("node-old").unwrap();
+            assert_eq!(session.protocol_version.as_deref(), Some("1.0"));
+            assert!(session.withheld_capabilities.contains(&CAPABILITY_SIGNING.to_string()));
+            assert!(!session.capabilities.contains(&CAPABILITY_ACK.to_string()));
+            let session = peers.session("node-new").unwrap();
+            assert_eq!(session.protocol_version.as_deref(), Some("1.1"));
+            assert!(session.withheld_capabilities.is_empty());
+        }
+        async fn next(rx: &mut tokio::sync::mpsc::UnboundedReceiver<Envelope>) -> Envelope {
+            tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+        }
+
+        // A CDM from the 1.1 peer reaches the 1.0 peer without its provenance,
+        let mut envelope = Envelope {
+            source_node_id: "node-new".to_string(),
+            ..cdm_envelope()
+        };
+        let cdm_id = envelope.payload["cdm_id"].as_str().unwrap().to_string();
+        attest(&mut envelope.provenance, &cdm_id, &envelope.payload, "node-new", None);
+        // and is acknowledged, which the 1.0 peer's is not
+        let (status, _) = send(&state, "application/json", "node-new", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let forwarded = next(&mut old_sent).await;
+        assert_eq!(forwarded.payload["cdm_id"], cdm_id.as_str());
+        assert!(forwarded.provenance.is_empty());
+
+        // A base CDM from the 1.0 peer reaches the 1.1 peer, which gets the
+        // chain this node starts
+        let envelope = Envelope {
+            source_node_id: "node-old".to_string(),
+            ..cdm_envelope()
+        };
+        let (status, _) = send(&state, "application/json", "node-old", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let forwarded = next(&mut new_sent).await;
+        assert_eq!(forwarded.message_id, envelope.message_id);
+        assert_eq!(forwarded.provenance.len(), 1);
+
+        // Pinned to 1.0, the node keeps even 1.1 peers on 1.0
+        let mut config = (*state.config.get()).clone();
+        config.protocol.version = "1.0".to_string();
+        state.config.replace(config);
+        let (_, reply) = hello("node-new", HelloPayload::default()).await;
+        let reply: HelloPayload = reply.unwrap().payload.parse().unwrap();
+        assert_eq!((reply.protocol_version.as_str(), reply.supported_versions.as_slice()), ("1.0", ["1.0".to_string()].as_slice()));
+        assert!(!reply.has_capability(CAPABILITY_BATCHING));
+        let peers = state.peers.read().await;
+        assert_eq!(peers.session("node-new").unwrap().protocol_version.as_deref(), Some("1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_acknowledged_delivery() {
+        // Node B acknowledges over HTTP; node C offers ACK but never sends one
+        let remote = test_state("node-b");
+        {
+            let mut peers = remote.peers.write().await;
+            let config = "{ id: node-local, address: 'http://127.0.0.1:1' }";
+            peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(config).unwrap()));
+            peers.record_handshake("node-local", "1.0".into(), HelloPayload::default().capabilities);
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new()
+            .route(PROTOCOL_ENDPOINT, post(receive_message))
+            .with_state(remote.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.protocol.delivery.ack_timeout_seconds = 1;
+        config.protocol.delivery.max_redeliveries = 1;
+        state.config.replace(config);
+        let (tx, mut sent) = tokio::sync::mpsc::unbounded_channel();
+        {
+            let mut peers = state.peers.write().await;
+            for (id, peer_address) in [("node-b", address.as_str()), ("node-c", "http://127.0.0.1:1")] {
+                let config = format!("{{ id: {}, address: '{}' }}", id, peer_address);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.record_handshake(id, "1.0".into(), HelloPayload::default().capabilities);
+            }
+            peers.set_link("node-b", Arc::new(HttpTransport::new(&address, "node-local", None)));
+            peers.set_link("node-c", Arc::new(ChannelLink(tx)));
+        }
+
+        let envelope = Envelope::new(
+            "node-local".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(generate_demo_cdm()).unwrap(),
+        );
+        let cdm_id = envelope.payload["cdm_id"].as_str().unwrap().to_string();
+        assert_eq!(originate(&state, envelope.clone()).await.len(), 2);
+
+        // Node C is sent the announcement once more, then gives up on it
+        for _ in 0..2 {
+            let resent = tokio::time::timeout(Duration::from_secs(5), sent.recv()).await.unwrap().unwrap();
+            assert_eq!(resent.message_id, envelope.message_id);
+        }
+        let propagation = || get_cdm_propagation(State(state.clone()), TenantScope::default(), Path(cdm_id.clone()));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let status = loop {
+            let Json(status) = propagation().await.unwrap();
+            if status.peers.iter().any(|p| p.state == DeliveryState::Unacknowledged) || Instant::now() > deadline {
+                break status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert_eq!(status.confirmed, ["node-b"]);
+        assert_eq!((status.peers[1].state, status.peers[1].redeliveries), (DeliveryState::Unacknowledged, 1));
+        let filter = DeadLetterFilter {
+            reason: Some(DeadLetterReason::Unacknowledged),
+            ..Default::default()
+        };
+        assert_eq!(state.dead_letters.list(&filter)[0].peer_id, "node-c");
+        assert_eq!(state.metrics.redeliveries.load(Ordering::Relaxed), 1);
+
+        // A redelivered duplicate is acknowledged again
+        let (status, reply) = send(&remote, "application/json", "node-local", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let ack: AckPayload = reply.unwrap().payload.parse().unwrap();
+        assert_eq!(ack.related_message_id, envelope.message_id);
+        let unknown = get_cdm_propagation(State(state.clone()), TenantScope::default(), Path("CDM-NONE".into())).await;
+        assert!(unknown.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_receipt() {
+        // node-origin -> node-relay -> node-watcher, which watches object 1
+        let cdm = generate_demo_cdm();
+        let origin = test_state("node-origin");
+        origin.storage.store_cdm(cdm.clone()).await.unwrap();
+        let relay = test_state("node-relay");
+        let watcher = test_state("node-watcher");
+        watcher.watchlist.register(&norad_number(&cdm.object1.object_id).unwrap(), None);
+        let (relay_tx, mut relay_sent) = tokio::sync::mpsc::unbounded_channel();
+        let (watcher_tx, mut watcher_sent) = tokio::sync::mpsc::unbounded_channel();
+        for (state, links) in [
+            (&relay, vec![("node-origin", relay_tx.clone()), ("node-watcher", relay_tx)]),
+            (&watcher, vec![("node-relay", watcher_tx)]),
+        ] {
+            let mut peers = state.peers.write().await;
+            for (id, tx) in links {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1' }}", id);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+                peers.set_peer_status(id, PeerStatus::Connected);
+                peers.record_handshake(id, "1.0".into(), HelloPayload::default().capabilities);
+                peers.set_link(id, Arc::new(ChannelLink(tx)));
+            }
+        }
+        async fn next(rx: &mut tokio::sync::mpsc::UnboundedReceiver<Envelope>) -> Envelope {
+            tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+        }
+
+        let announce = Envelope::new("node-origin".into(), MessageType::CdmAnnounce, serde_json::to_value(&cdm).unwrap());
+        let body = serde_json::to_vec(&announce).unwrap();
+        assert!(send(&relay, "application/json", "node-origin", body).await.0.is_success());
+        let relayed = next(&mut relay_sent).await;
+        assert_eq!(relayed.message_type, MessageType::CdmAnnounce);
+
+        // The watcher is not the originator's peer: the receipt goes back
+        // along the provenance
+        let body = serde_json::to_vec(&relayed).unwrap();
+        assert!(send(&watcher, "application/json", "node-relay", body).await.0.is_success());
+        let receipt = next(&mut watcher_sent).await;
+        assert_eq!(receipt.message_type, MessageType::DeliveryReceipt);
+        assert_eq!(watcher.metrics.receipts_sent.load(Ordering::Relaxed), 1);
+
+        // The relay passes it on to the originator, which records it
+        let body = serde_json::to_vec(&receipt).unwrap();
+        assert_eq!(send(&relay, "application/json", "node-watcher", body).await.0, StatusCode::ACCEPTED);
+        let passed_on = next(&mut relay_sent).await;
+        assert_eq!((passed_on.message_id.as_str(), passed_on.hop_count), (receipt.message_id.as_str(), 1));
+        let body = serde_json::to_vec(&passed_on).unwrap();
+        assert_eq!(send(&origin, "application/json", "node-relay", body).await.0, StatusCode::ACCEPTED);
+        let receipts = || get_cdm_receipts(State(origin.clone()), TenantScope::default(), Path(cdm.cdm_id.clone()));
+        let Json(response) = receipts().await.unwrap();
+        assert_eq!(response.receipts.len(), 1);
+        let recorded = &response.receipts[0];
+        assert_eq!((recorded.receiving_node_id.as_str(), recorded.hop_count), ("node-watcher", 1));
+        assert_eq!(recorded.matched_object_ids, std::slice::from_ref(&cdm.object1.object_id));
+        assert_eq!(origin.metrics.receipts_received.load(Ordering::Relaxed), 1);
+
+        // A receipt naming another node than its sender is refused
+        let mut forged = passed_on.clone();
+        forged.message_id = uuid::Uuid::new_v4().to_string();
+        forged.source_node_id = "node-relay".into();
+        let body = serde_json::to_vec(&forged).unwrap();
+        assert_eq!(send(&origin, "application/json", "node-relay", body).await.0, StatusCode::BAD_REQUEST);
+        let unknown = get_cdm_receipts(State(origin.clone()), TenantScope::default(), Path("CDM-NONE".into())).await;
+        assert!(matches!(unknown, Err((StatusCode::NOT_FOUND, _))));
+    }
+
+    #[tokio::test]
+    async fn test_envelope_batch() {
+        let state = test_state("node-local");
+        let cdm = generate_demo_cdm();
+        let good = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(&cdm).unwrap(),
+        );
+        let bad = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::json!({ "cdm_id": "CDM-BAD" }),
+        );
+        let hello = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::Hello,
+            serde_json::to_value(HelloPayload::default()).unwrap(),
+        );
+        let batch = EnvelopeBatchPayload {
+            envelopes: vec![good, bad.clone(), hello.clone()],
+        };
+        let batch = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::EnvelopeBatch,
+            serde_json::to_value(batch).unwrap(),
+        );
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&batch).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // The CDM is taken; the reply names the two refused envelopes
+        let reply = reply.unwrap();
+        assert_eq!(reply.message_type, MessageType::EnvelopeBatch);
+        let refused: Vec<_> = reply
+            .payload
+            .parse::<EnvelopeBatchPayload>()
+            .unwrap()
+            .envelopes
+            .into_iter()
+            .map(|envelope| error_payload(Some(envelope)).related_message_id.unwrap())
+            .collect();
+        assert_eq!(refused, [bad.message_id, hello.message_id]);
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_replay_rejection() {
+        let state = test_state("node-local");
+        state.peers.write().await.add_peer(PeerInfo::from_config(&serde_yaml::from_str(
+            "{ id: node-remote, address: 'http://localhost:1' }",
+        ).unwrap()));
+
+        let mut stale = cdm_envelope();
+        stale.timestamp = Utc::now() - chrono::Duration::hours(2);
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&stale).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::InvalidMessage);
+
+        let mut future = cdm_envelope();
+        future.timestamp = Utc::now() + chrono::Duration::hours(1);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&future).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A link sequence number is accepted once, even on a new message ID
+        let mut first = cdm_envelope();
+        first.sequence = Some(42);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&first).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let mut replayed = cdm_envelope();
+        replayed.sequence = Some(42);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&replayed).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(state.metrics.replays_rejected.load(Ordering::Relaxed), 3);
+
+        // A new session starts a new window
+        let hello = Envelope::new(
+            "node-remote".to_string(),
+            MessageType::Hello,
+            serde_json::to_value(HelloPayload::default()).unwrap(),
+        );
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&hello).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&replayed).unwrap()).await;
+        // Answered with an ACK, which the peer offered in its HELLO
+        assert_eq!((status, reply.map(|r| r.message_type)), (StatusCode::OK, Some(MessageType::Ack)));
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew_correction() {
+        let state = test_state("node-local");
+        state.peers.write().await.add_peer(PeerInfo::from_config(&serde_yaml::from_str(
+            "{ id: node-remote, address: 'http://localhost:1' }",
+        ).unwrap()));
+        let ahead = |mut envelope: Envelope| {
+            envelope.timestamp = Utc::now() + chrono::Duration::minutes(10);
+            serde_json::to_vec(&envelope).unwrap()
+        };
+        let heartbeat = || {
+            let payload = serde_json::json!({ "sequence": 1 });
+            Envelope::new("node-remote".to_string(), MessageType::Heartbeat, payload)
+        };
+
+        // Ten minutes ahead is past max_clock_skew_seconds until the offset is known
+        let (status, _) = send(&state, "application/json", "node-remote", ahead(cdm_envelope())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // As measured by our own HELLO exchange
+        state.peers.write().await.record_clock_sample("node-remote", 600_000, 5000);
+        let (status, _) = send(&state, "application/json", "node-remote", ahead(cdm_envelope())).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let (status, _) = send(&state, "application/json", "node-remote", ahead(heartbeat())).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let session = state.peers.read().await.session("node-remote").unwrap();
+        let clock = session.clock.unwrap();
+        assert_eq!(clock.samples, 2);
+        assert!(clock.exceeds_warning && (clock.offset_ms - 600_000).abs() < 1000);
+        let warnings = session.events.iter().filter(|e| e.kind == SessionEventKind::ClockSkewed).count();
+        assert_eq!(warnings, 1);
+
+        // Relayed envelopes carry the originator's timestamp and are not corrected
+        let mut relayed = cdm_envelope();
+        relayed.source_node_id = "node-far".to_string();
+        let (status, _) = send(&state, "application/json", "node-remote", ahead(relayed)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut config = (*state.config.get()).clone();
+        config.protocol.clock_skew.correct = false;
+        state.config.replace(config);
+        let (status, _) = send(&state, "application/json", "node-remote", ahead(cdm_envelope())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_latency_measurement() {
+        let state = test_state("node-local");
+        state.peers.write().await.add_peer(PeerInfo::from_config(&serde_yaml::from_str(
+            "{ id: node-remote, address: 'http://localhost:1' }",
+        ).unwrap()));
+        state.peers.write().await.record_heartbeat_sent("node-remote", 7);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let payload = serde_json::json!({ "sequence": 3, "echo": { "sequence": 7, "held_ms": 10 } });
+        let heartbeat = Envelope::new("node-remote".to_string(), MessageType::Heartbeat, payload);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&heartbeat).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        // Our next heartbeat hands back theirs
+        let echo = state.peers.write().await.take_heartbeat_echo("node-remote").unwrap();
+        assert_eq!(echo.sequence, 3);
+
+        let mut cdm = cdm_envelope();
+        cdm.timestamp = Utc::now() - chrono::Duration::seconds(2);
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&cdm).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let latency = state.peers.read().await.session("node-remote").unwrap().latency;
+        let round_trip = latency.round_trip.unwrap();
+        assert_eq!(round_trip.samples, 1);
+        assert!((20..1000).contains(&round_trip.last_ms));
+        let propagation = latency.cdm_propagation.unwrap();
+        assert!((2000..3000).contains(&propagation.last_ms));
+        let Json(metrics) = metrics(State(state)).await;
+        assert_eq!(metrics.peer_round_trip_ms["node-remote"], round_trip.mean_ms);
+        assert_eq!(metrics.cdm_propagation.unwrap().samples, 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_envelope_error() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.protocol.max_envelope_bytes = 512;
+        state.config.replace(config);
+
+        let body = serde_json::to_vec(&cdm_envelope()).unwrap();
+        assert!(body.len() > 512);
+        let (status, reply) = send(&state, "application/json", "node-remote", body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_payload(reply).error_code, ErrorCode::InvalidMessage);
+    }
+
+    #[tokio::test]
+    async fn test_object_quota_error() {
+        let mut state = test_state("node-local");
+        state.storage = Arc::new(MemoryStorage::with_object_limits(crate::config::ObjectLimitsConfig {
+            max_objects_per_source: Some(1),
+            ..Default::default()
+        }));
+
+        for (i, expected) in [(1, StatusCode::ACCEPTED), (2, StatusCode::TOO_MANY_REQUESTS)] {
+            let announce = Envelope::new(
+                "node-remote".to_string(),
+                MessageType::ObjectStateAnnounce,
+                serde_json::json!({
+                    "object_id": format!("FAKE-{}", i),
+                    "object_name": "FAKE",
+                    "object_type": "DEBRIS",
+                    "epoch": "2024-01-15T12:00:00Z",
+                    "state_vector": {
+                        "reference_frame": "TEME",
+                        "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                        "vx_km_s": 0.0, "vy_km_s": 7.5, "vz_km_s": 0.0
+                    }
+                }),
+            );
+            let (status, reply) = send(&state, "application/json", "node-remote", serde_json::to_vec(&announce).unwrap()).await;
+            assert_eq!(status, expected);
+            if status != StatusCode::ACCEPTED {
+                assert_eq!(error_payload(reply).error_code, ErrorCode::RateLimited);
+            }
+        }
+        assert_eq!(state.storage.object_capacity().await.unwrap().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pc_endpoints() {
+        let state = test_state("node-local");
+        let cdm = generate_demo_cdm();
+        let id = cdm.cdm_id.clone();
+        state.storage.store_cdm(cdm.clone()).await.unwrap();
+
+        let Json(comparison) = compare_pc(State(state.clone()), TenantScope::default(), Path(id.clone())).await.unwrap();
+        assert_eq!(comparison.default_method, "foster");
+        assert_eq!(comparison.results.len(), PcMethods::BUILTIN.len());
+
+        let request = PcRequest {
+            method: Some("chan".to_string()),
+        };
+        let Json(result) = recompute_pc(State(state.clone()), TenantScope::default(), Path(id.clone()), Some(Json(request))).await.unwrap();
+        assert_eq!(result.method, "chan");
+        let Json(result) = recompute_pc(State(state.clone()), TenantScope::default(), Path(id.clone()), None).await.unwrap();
+        assert_eq!(result.method, "foster");
+
+        let request = PcRequest {
+            method: Some("magic".to_string()),
+        };
+        let (status, _) = recompute_pc(State(state.clone()), TenantScope::default(), Path(id), Some(Json(request))).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut bare = cdm;
+        bare.cdm_id = "CDM-BARE".to_string();
+        bare.relative_state = None;
+        state.storage.store_cdm(bare).await.unwrap();
+        let (status, _) = compare_pc(State(state.clone()), TenantScope::default(), Path("CDM-BARE".to_string())).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = compare_pc(State(state), TenantScope::default(), Path("CDM-NONE".to_string())).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_trace() {
+        let state = test_state("node-a");
+        let cdm = generate_demo_cdm();
+        let id = cdm.cdm_id.clone();
+        let body = serde_json::to_value(&cdm).unwrap();
+
+        // Untraced ingest records nothing
+        let (_, Json(resp)) = ingest_cdm_once(&state, &IngestQuery::default(), &HeaderMap::new(), None, body.clone())
+            .await
+            .unwrap();
+        assert!(resp.trace.is_none());
+        assert!(get_cdm_trace(State(state.clone()), TenantScope::default(), Path(id.clone())).await.is_err());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACE_HEADER, "true".parse().unwrap());
+        let (status, Json(resp)) = ingest_cdm_once(&state, &IngestQuery::default(), &headers, None, body)
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        let trace = resp.trace.unwrap();
+        let stages: Vec<&str> = trace.stages.iter().map(|s| s.stage.as_str()).collect();
+        assert_eq!(stages, ["parse", "validate", "enrich", "dedup", "store", "route"]);
+        assert_eq!(trace.stages[3].detail.as_deref(), Some("replaces stored CDM with the same ID"));
+
+        let Json(stored) = get_cdm_trace(State(state.clone()), TenantScope::default(), Path(id.clone())).await.unwrap();
+        assert_eq!(stored.cdm_id.as_deref(), Some(id.as_str()));
+
+        // Rejections carry the trace up to the failing stage
+        let (status, Json(err)) = ingest_cdm_once(
+            &state,
+            &IngestQuery { trace: true },
+            &HeaderMap::new(),
+            None,
+            serde_json::json!({ "cdm_id": "bad" }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let trace = err.trace.unwrap();
+        assert_eq!(trace.stages.len(), 1);
+        assert_eq!(trace.stages[0].outcome, StageOutcome::Rejected);
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_ingest() {
+        let state = test_state("node-a");
+        let cdm = generate_demo_cdm();
+        let body = serde_json::to_value(&cdm).unwrap();
+        let ingest = |key: &str, body: serde_json::Value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IDEMPOTENCY_KEY_HEADER, key.parse().unwrap());
+            let state = state.clone();
+            async move {
+                let resp = ingest_cdm(State(state), Query(IngestQuery::default()), None, headers, Json(body)).await;
+                let status = resp.status();
+                let replayed = resp.headers().contains_key(IDEMPOTENT_REPLAY_HEADER);
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+                (status, replayed, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, replayed, first) = ingest("retry-1", body.clone()).await;
+        assert_eq!((status, replayed), (StatusCode::CREATED, false));
+        let (status, replayed, second) = ingest("retry-1", body.clone()).await;
+        assert_eq!((status, replayed), (StatusCode::CREATED, true));
+        assert_eq!(first, second);
+        // Announced once
+        assert_eq!(state.metrics.cdms_announced.load(Ordering::Relaxed), 1);
+
+        let mut changed = body.clone();
+        changed["miss_distance_m"] = serde_json::json!(1.0);
+        let (status, _, error) = ingest("retry-1", changed).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"], "idempotency_key_reused");
+
+        // Rejections are replayed too
+        let (status, _, _) = ingest("retry-2", serde_json::json!({ "cdm_id": "bad" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, replayed, _) = ingest("retry-2", serde_json::json!({ "cdm_id": "bad" })).await;
+        assert_eq!((status, replayed), (StatusCode::BAD_REQUEST, true));
+
+        let (status, _, error) = ingest("", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["error"], "invalid_idempotency_key");
+    }
+
+    #[tokio::test]
+    async fn test_cdm_events() {
+        let state = test_state("node-a");
+        let cdm = generate_demo_cdm();
+        let body = serde_json::to_value(&cdm).unwrap();
+        let (status, _) = ingest_cdm_once(&state, &IngestQuery::default(), &HeaderMap::new(), None, body)
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        let Json(withdrawn) = withdraw_cdm(
+            State(state.clone()),
+            Path(cdm.cdm_id.clone()),
+            Json(WithdrawCdmRequest {
+                reason: "TCA_PASSED".to_string(),
+                superseded_by: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(withdrawn.status, "withdrawn");
+
+        let query = EventsQuery {
+            since: Some(0),
+            timeout_seconds: 0,
+            watched: false,
+        };
+        let Json(page) = cdm_events(State(state.clone()), TenantScope::default(), Query(query)).await;
+        let kinds: Vec<CdmEventKind> = page.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [CdmEventKind::Announced, CdmEventKind::Withdrawn]);
+        assert_eq!(page.events[1].collision_probability, Some(cdm.collision_probability));
+        assert_eq!(page.next_seq, 2);
+
+        // Without a cursor only new events are returned
+        let query = EventsQuery {
+            since: None,
+            timeout_seconds: 0,
+            watched: false,
+        };
+        let Json(page) = cdm_events(State(state), TenantScope::default(), Query(query)).await;
+        assert!(page.events.is_empty());
+        assert_eq!(page.next_seq, 2);
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let mut state = test_state("node-a");
+        let (status, Json(body)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.status, "ready");
+
+        let mut config = (*state.config.get()).clone();
+        config.readiness.min_connected_peers = 1;
+        state.config.replace(config);
+        let (status, Json(body)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.checks.iter().any(|c| c.name == "peers" && !c.ok));
+
+        {
+            let mut peers = state.peers.write().await;
+            peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str("{ id: node-b, address: 'http://b' }").unwrap()));
+            peers.set_peer_status("node-b", PeerStatus::Connected);
+        }
+        assert_eq!(readiness(State(state.clone())).await.0, StatusCode::OK);
+
+        // A failed reload keeps the node live but not ready
+        state.reloader = Arc::new(Reloader::new(Some("/nonexistent/config.yaml".into()), None));
+        assert!(crate::node::reload_from_file(&state).await.is_err());
+        let (status, Json(body)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.checks.iter().any(|c| c.name == "config" && !c.ok));
+        assert_eq!(liveness(State(state)).await.0.status, "alive");
+    }
+
+    #[tokio::test]
+    async fn test_peer_detail() {
+        let state = test_state("node-a");
+        let missing = get_peer_detail(State(state.clone()), Path("node-b".into())).await;
+        assert_eq!(missing.err().unwrap().0, StatusCode::NOT_FOUND);
+
+        state
+            .peers
+            .write()
+            .await
+            .add_peer(PeerInfo::from_config(&serde_yaml::from_str("{ id: node-b, address: 'http://b' }").unwrap()));
+        let mut hello = state.local_hello();
+        hello.advertise_address = Some("https://b.example.org:8443".into());
+        let envelope = Envelope::new("node-b".into(), MessageType::Hello, serde_json::to_value(hello).unwrap());
+        process_envelope(&state, envelope, None).await.unwrap();
+
+        let Json(detail) = get_peer_detail(State(state), Path("node-b".into())).await.unwrap();
+        let body = serde_json::to_value(detail).unwrap();
+        assert_eq!(body["id"], "node-b");
+        assert_eq!(body["session"]["advertised_address"], "https://b.example.org:8443");
+        assert_eq!(body["session"]["protocol_version"], "1.1");
+        assert_eq!(body["session"]["received"]["HELLO"], 1);
+        assert_eq!(body["session"]["events"][0]["kind"], "hello_received");
+        assert_eq!(body["session"]["queue_depth"], 0);
+        assert!(body["session"].get("uptime_seconds").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_cdm_archive() {
+        use crate::node::ImportStatus;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let state = test_state("node-a");
+        let mut older = generate_demo_cdm();
+        older.cdm_id = "CDM-IMPORT-1".into();
+        older.creation_date -= chrono::Duration::days(1);
+        let mut newer = older.clone();
+        newer.creation_date += chrono::Duration::hours(1);
+        let mut ndjson = String::new();
+        for line in [
+            serde_json::to_string(&newer).unwrap(),
+            String::new(),
+            r#"{ "cdm_id": "CDM-BAD" }"#.to_string(),
+            serde_json::to_string(&older).unwrap(),
+        ] {
+            ndjson.push_str(&line);
+            ndjson.push('\n');
+        }
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(ndjson.as_bytes()).unwrap();
+
+        let response = import_cdm_archive(State(state.clone()), None, Body::from(gzip.finish().unwrap())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<ImportLine> = body.split(|b| *b == b'\n').filter(|l| !l.is_empty()).map(|l| serde_json::from_slice(l).unwrap()).collect();
+        let statuses: Vec<_> = lines
+            .iter()
+            .filter_map(|l| match l {
+                ImportLine::Record(r) => Some((r.line, r.status)),
+                ImportLine::Summary(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [(1, ImportStatus::Imported), (3, ImportStatus::Rejected), (4, ImportStatus::Unchanged)]
+        );
+        let ImportLine::Summary(summary) = lines.last().unwrap() else {
+            panic!("import ended without a summary");
+        };
+        assert_eq!((summary.imported, summary.unchanged, summary.rejected, summary.complete), (1, 1, 1, true));
+
+        // Stored and classified, but not announced
+        let stored = state.storage.get_cdm("CDM-IMPORT-1").await.unwrap().unwrap();
+        assert_eq!(stored.creation_date, newer.creation_date);
+        assert!(stored.conjunction_category.is_some());
+        assert_eq!(state.events.head(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_operations() {
+        let state = test_state("node-a");
+        let cdms: Vec<serde_json::Value> = ["OP-A", "OP-A", "OP-B"]
+            .iter()
+            .enumerate()
+            .map(|(i, originator)| {
+                let mut cdm = generate_demo_cdm();
+                cdm.cdm_id = format!("CDM-BULK-{}", i);
+                cdm.originator = originator.to_string();
+                cdm.conjunction_category = None;
+                cdm.recommended_action = None;
+                serde_json::to_value(cdm).unwrap()
+            })
+            .chain([serde_json::json!({ "cdm_id": "CDM-BAD" })])
+            .collect();
+        let Json(ingested) = ingest_cdms_bulk(State(state.clone()), None, Json(BulkIngestRequest { cdms }))
+            .await
+            .unwrap();
+        assert_eq!((ingested.accepted, ingested.rejected), (3, 1));
+        assert_eq!(ingested.results[3].cdm_id.as_deref(), Some("CDM-BAD"));
+        assert_eq!(ingested.results[3].error.as_ref().unwrap().error, "validation_failed");
+
+        let missing = PurgeQuery {
+            originator: None,
+            reason: default_purge_reason(),
+        };
+        let (status, _) = purge_cdms(State(state.clone()), Query(missing)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let query = PurgeQuery {
+            originator: Some("OP-A".into()),
+            reason: "bad_batch".into(),
+        };
+        let Json(purged) = purge_cdms(State(state.clone()), Query(query)).await.unwrap();
+        assert_eq!(purged.withdrawn, 2);
+        let Json(remaining) = list_cdms(State(state.clone()), TenantScope::default(), Query(CdmListQuery::default())).await;
+        assert_eq!(remaining.total, 1);
+        // Classified on ingest
+        assert!(remaining.cdms[0].conjunction_category.is_some());
+        assert!(remaining.cdms[0].recommended_action.is_some());
+        let filtered = CdmListQuery {
+            min_probability: Some(1.0),
+            ..Default::default()
+        };
+        let Json(filtered) = list_cdms(State(state.clone()), TenantScope::default(), Query(filtered)).await;
+        assert_eq!(filtered.total, 0);
+
+        let announce = Envelope::new(
+            "node-b".into(),
+            MessageType::ObjectStateAnnounce,
+            serde_json::json!({
+                "object_id": "DEBRIS-1",
+                "object_name": "DEBRIS",
+                "object_type": "DEBRIS",
+                "epoch": "2024-01-15T12:00:00Z",
+                "state_vector": {
+                    "reference_frame": "TEME",
+                    "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                    "vx_km_s": 0.0, "vy_km_s": 7.5, "vz_km_s": 0.0
+                }
+            }),
+        );
+        process_envelope(&state, announce, None).await.unwrap();
+        let request = || {
+            Json(WithdrawObjectRequest {
+                reason: WithdrawReason::Decayed,
+            })
+        };
+        let Json(withdrawn) = withdraw_object(State(state.clone()), Path("DEBRIS-1".into()), request())
+            .await
+            .unwrap();
+        assert_eq!(withdrawn.reason, WithdrawReason::Decayed);
+        assert_eq!(state.storage.object_count().await.unwrap(), 0);
+        let (status, _) = withdraw_object(State(state), Path("DEBRIS-1".into()), request()).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_object_cdm_history() {
+        let state = test_state("node-a");
+        let first = generate_demo_cdm();
+        let mut earlier = first.clone();
+        earlier.cdm_id = format!("{}-EARLY", first.cdm_id);
+        earlier.tca -= chrono::Duration::hours(1);
+        let mut unrelated = first.clone();
+        unrelated.cdm_id = format!("{}-OTHER", first.cdm_id);
+        unrelated.object1.object_id = "NORAD-11111".into();
+        for cdm in [first.clone(), earlier.clone(), unrelated] {
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+        state.storage.withdraw_cdm(&earlier.cdm_id).await.unwrap();
+
+        let object_id = first.object1.object_id.clone();
+        let Json(history) = object_cdm_history(State(state), TenantScope::default(), Path(object_id)).await.unwrap();
+        assert_eq!((history.active, history.withdrawn, history.total), (1, 1, 2));
+        // Ordered by TCA, withdrawn CDMs included
+        assert_eq!(history.cdms[0].cdm.cdm_id, earlier.cdm_id);
+        assert_eq!(history.cdms[0].status, "withdrawn");
+        assert!(history.cdms[0].withdrawn_at.is_some());
+        assert_eq!(history.cdms[1].status, "active");
+        assert_eq!(history.cdms[1].other_object_id, first.object2.object_id);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_omm() {
+        let state = test_state("node-a");
+        let omm = serde_json::json!([
+            {
+                "OBJECT_NAME": "VANGUARD 1", "OBJECT_ID": "1958-002B", "NORAD_CAT_ID": 5,
+                "EPOCH": "2000-06-27T18:50:19.733568", "MEAN_MOTION": 10.82419157, "ECCENTRICITY": 0.1859667,
+                "INCLINATION": 34.2682, "RA_OF_ASC_NODE": 348.7242, "ARG_OF_PERICENTER": 331.7664,
+                "MEAN_ANOMALY": 19.3264, "BSTAR": 0.000028098
+            },
+            {
+                "OBJECT_NAME": "DECAYED", "OBJECT_ID": "2024-001A", "NORAD_CAT_ID": 99990,
+                "EPOCH": "2024-01-15T12:00:00", "MEAN_MOTION": 18.0, "ECCENTRICITY": 0.0,
+                "INCLINATION": 51.6, "RA_OF_ASC_NODE": 0.0, "ARG_OF_PERICENTER": 0.0, "MEAN_ANOMALY": 0.0
+            }
+        ]);
+        let Json(response) = ingest_omm(State(state.clone()), None, omm.to_string()).await.unwrap();
+        assert_eq!((response.accepted, response.rejected), (1, 1));
+        assert_eq!(response.results[1].error.as_ref().unwrap().error, "propagation_failed");
+
+        let stored = state.storage.get_object("NORAD-5").await.unwrap().unwrap();
+        assert_eq!(stored.state_vector.reference_frame, "TEME");
+        assert!((stored.state_vector.x_km - 7022.465).abs() < 1e-2);
+        assert!(state.storage.get_object("NORAD-99990").await.unwrap().is_none());
+
+        let (status, Json(body)) = ingest_omm(State(state), None, "OBJECT_NAME VANGUARD".to_string()).await.unwrap_err();
+        assert_eq!((status, body.error.as_str()), (StatusCode::BAD_REQUEST, "invalid_omm"));
+    }
+
+    #[tokio::test]
+    async fn test_announce_maneuver_opm() {
+        let state = test_state("node-a");
+        let opm = "\
+CCSDS_OPM_VERS = 2.0
+OBJECT_NAME = SAT-ALPHA
+OBJECT_ID = 2020-001A
+CENTER_NAME = EARTH
+REF_FRAME = GCRF
+TIME_SYSTEM = UTC
+EPOCH = 2024-01-16T06:10:00
+X = 7000.0
+Y = 0.0
+Z = 0.0
+X_DOT = 0.0
+Y_DOT = 7.5465
+Z_DOT = 0.0
+MAN_EPOCH_IGNITION = 2024-01-16T06:00:00
+MAN_DURATION = 30.0
+MAN_REF_FRAME = RTN
+MAN_DV_1 = 0.0
+MAN_DV_2 = 0.0005
+MAN_DV_3 = 0.0
+";
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
+        let query = |cdm: Option<&str>| {
+            Query(OpmManeuverQuery {
+                object_id: Some("NORAD-45000".to_string()),
+                related_cdm_id: cdm.map(str::to_string),
+                maneuver_type: None,
+            })
+        };
+        let (status, Json(response)) =
+            announce_maneuver(State(state.clone()), headers.clone(), query(Some("CDM-1")), Bytes::from(opm)).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(response.status, "announced");
+
+        let early = opm.replace("EPOCH = 2024-01-16T06:10:00", "EPOCH = 2024-01-16T05:00:00");
+        let (status, Json(body)) = announce_maneuver(State(state.clone()), headers, query(None), Bytes::from(early)).await.unwrap_err();
+        assert_eq!((status, body.error.as_str()), (StatusCode::BAD_REQUEST, "invalid_opm"));
+
+        let mut json = HeaderMap::new();
+        json.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let (status, Json(body)) = announce_maneuver(State(state), json, query(None), Bytes::from("{}")).await.unwrap_err();
+        assert_eq!((status, body.error.as_str()), (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed"));
+    }
+
+    #[tokio::test]
+    async fn test_completed_maneuver_moves_object() {
+        let mut state = test_state("node-a");
+        let dir = tempfile::tempdir().unwrap();
+        state.archive = Some(Arc::new(FileArchive::new(dir.path(), false)));
+        let state_vector = |epoch: &str, vy: f64| {
+            serde_json::json!({
+                "reference_frame": "TEME", "epoch": epoch,
+                "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                "vx_km_s": 0.0, "vy_km_s": vy, "vz_km_s": 0.0
+            })
+        };
+        let announce = Envelope::new(
+            "node-b".into(),
+            MessageType::ObjectStateAnnounce,
+            serde_json::json!({
+                "object_id": "SAT-1",
+                "object_name": "SAT",
+                "object_type": "PAYLOAD",
+                "epoch": "2024-01-16T06:00:00Z",
+                "state_vector": state_vector("2024-01-16T06:00:00Z", 7.5),
+            }),
+        );
+        process_envelope(&state, announce, None).await.unwrap();
+        let completed = |maneuver_id: &str, epoch: &str| {
+            Envelope::new(
+                "node-b".into(),
+                MessageType::ManeuverStatus,
+                serde_json::json!({
+                    "maneuver_id": maneuver_id,
+                    "object_id": "SAT-1",
+                    "status": "COMPLETED",
+                    "post_maneuver_state": state_vector(epoch, 7.6),
+                }),
+            )
+        };
+        process_envelope(&state, completed("MNVR-1", "2024-01-16T06:15:00Z"), None).await.unwrap();
+        // A report older than the current state changes nothing
+        process_envelope(&state, completed("MNVR-0", "2024-01-16T05:00:00Z"), None).await.unwrap();
+
+        let object = state.storage.get_object("SAT-1").await.unwrap().unwrap();
+        assert_eq!(object.epoch.to_rfc3339(), "2024-01-16T06:15:00+00:00");
+        assert_eq!(object.state_vector.vy_km_s, 7.6);
+        assert_eq!(object.object_name, "SAT");
+        let history = |id: &str, from: Option<&str>, to: Option<&str>| {
+            let query = ObjectHistoryQuery {
+                from: from.map(str::to_string),
+                to: to.map(str::to_string),
+            };
+            object_state_history(State(state.clone()), TenantScope::default(), Path(id.to_string()), Query(query))
+        };
+        let Json(all) = history("SAT-1", None, None).await.unwrap();
+        let linked: Vec<Option<&str>> = all.states.iter().map(|s| s.state.maneuver_id.as_deref()).collect();
+        assert_eq!(linked, [None, Some("MNVR-1")]);
+        assert!(all.states[0].diff.is_none());
+
+        let page = state.archive.as_ref().unwrap().query(ArchiveKind::Object, &ArchiveQuery { limit: 10, ..Default::default() }).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].reason, ArchiveReason::Maneuvered);
+
+        let (status, _) = history("SAT-2", None, None).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_object_state_history_range() {
+        let state = test_state("node-a");
+        let object: ObjectRecord = serde_json::from_value(serde_json::json!({
+            "object_id": "SAT-1",
+            "object_name": "SAT",
+            "object_type": "PAYLOAD",
+            "epoch": "2024-01-16T06:00:00Z",
+            "state_vector": {
+                "reference_frame": "TEME",
+                "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                "vx_km_s": 0.0, "vy_km_s": 7.546, "vz_km_s": 0.0
+            },
+            "source_node": "node-b",
+            "last_updated": "2024-01-16T06:00:00Z"
+        }))
+        .unwrap();
+        for hours in 0..3 {
+            let at = object.epoch + chrono::Duration::hours(hours);
+            let mut state_vector = crate::orbit::propagate(&object.state_vector, object.epoch, at, PropagationModel::J2).unwrap();
+            state_vector.vy_km_s += 0.001 * hours as f64;
+            let update = ObjectRecord { epoch: at, state_vector, ..object.clone() };
+            state.storage.store_object(update).await.unwrap();
+        }
+        let history = |from: Option<&str>, to: Option<&str>| {
+            let query = ObjectHistoryQuery {
+                from: from.map(str::to_string),
+                to: to.map(str::to_string),
+            };
+            object_state_history(State(state.clone()), TenantScope::default(), Path("SAT-1".into()), Query(query))
+        };
+
+        let Json(window) = history(Some("2024-01-16T06:30:00Z"), Some("2024-01-16T07:30:00Z")).await.unwrap();
+        assert_eq!(window.states.len(), 1);
+        assert_eq!(window.states[0].state.version, 2);
+        // The diff still compares with the version before, outside the window
+        let diff = window.states[0].diff.as_ref().unwrap();
+        assert_eq!((diff.from_version, diff.epoch_delta_seconds), (1, 3600.0));
+        assert!((diff.velocity_residual_m_s.unwrap() - 1.0).abs() < 1e-6);
+
+        let (status, Json(body)) = history(Some("2024-01-16T08:00:00Z"), Some("2024-01-16T06:00:00Z")).await.unwrap_err();
+        assert_eq!((status, body.error.as_str()), (StatusCode::BAD_REQUEST, "invalid_range"));
+        let (status, _) = history(Some("yesterday"), None).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_object_state() {
+        let state = test_state("node-a");
+        let object: ObjectRecord = serde_json::from_value(serde_json::json!({
+            "object_id": "SAT-1",
+            "object_name": "SAT",
+            "object_type": "PAYLOAD",
+            "epoch": "2024-01-15T12:00:00Z",
+            "state_vector": {
+                "reference_frame": "TEME",
+                "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                "vx_km_s": 0.0, "vy_km_s": 7.546, "vz_km_s": 0.0
+            },
+            "source_node": "node-b",
+            "last_updated": "2024-01-15T12:00:00Z"
+        }))
+        .unwrap();
+        state.storage.store_object(object).await.unwrap();
+        let query = |at: &str| {
+            Query(ObjectStateQuery {
+                at: Some(at.to_string()),
+                model: PropagationModel::J2,
+            })
+        };
+        let call = |at: &str| object_state(State(state.clone()), TenantScope::default(), Path("SAT-1".into()), query(at));
+
+        let Json(predicted) = call("2024-01-15T12:30:00Z").await.unwrap();
+        assert_eq!(predicted.prediction.span_seconds, 1800.0);
+        assert!(predicted.prediction.state_vector.y_km > 6000.0);
+        let body = serde_json::to_value(&predicted).unwrap();
+        assert_eq!(body["accuracy"], "nominal");
+        assert_eq!(body["state_vector"]["epoch"], "2024-01-15T12:30:00Z");
+        assert!(body["caveats"][0].as_str().unwrap().contains("drag"));
+
+        let (status, Json(body)) = call("yesterday").await.unwrap_err();
+        assert_eq!((status, body.error.as_str()), (StatusCode::BAD_REQUEST, "invalid_epoch"));
+        let (status, _) = call("2025-01-15T12:00:00Z").await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = object_state(State(state.clone()), TenantScope::default(), Path("SAT-2".into()), query("2024-01-15T12:00:00Z"))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_archive_query() {
+        let params = || ArchiveParams {
+            since: None,
+            until: None,
+            object_id: Some(generate_demo_cdm().object1.object_id),
+            limit: default_archive_limit(),
+        };
+        let mut state = test_state("node-a");
+        let (status, _) = archived_cdms(State(state.clone()), TenantScope::default(), Query(params())).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(FileArchive::new(dir.path(), true));
+        archive
+            .append(&[crate::storage::ArchiveEntry::cdm(
+                generate_demo_cdm(),
+                crate::storage::ArchiveReason::Withdrawn,
+            )])
+            .unwrap();
+        state.archive = Some(archive);
+        let Json(page) = archived_cdms(State(state.clone()), TenantScope::default(), Query(params())).await.unwrap();
+        assert_eq!(page.entries.len(), 1);
+        let Json(page) = archived_objects(State(state), TenantScope::default(), Query(params())).await.unwrap();
+        assert!(page.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_conjunctions() {
+        let state = test_state("node-a");
+        let first = generate_demo_cdm();
+        let mut second = first.clone();
+        second.cdm_id = format!("{}-B", first.cdm_id);
+        second.originator = "OTHER-PROVIDER".into();
+        std::mem::swap(&mut second.object1, &mut second.object2);
+        for cdm in [first, second] {
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+
+        let Json(list) = list_conjunctions(State(state), TenantScope::default()).await.unwrap();
+        assert_eq!(list.total, 1);
+        let body = serde_json::to_value(&list.conjunctions[0]).unwrap();
+        assert_eq!(body["cdm_count"], 2);
+        assert_eq!(body["disagreement"]["providers"], 2);
+        assert_eq!(body["fused"]["contributors"].as_array().unwrap().len(), 2);
+        assert!(body["conjunction_key"].as_str().unwrap().contains('@'));
+        let id = body["conjunction_id"].as_str().unwrap().to_string();
+        let cdm = generate_demo_cdm();
+        let bucket = crate::config::StorageConfig::default().conjunction_bucket_seconds;
+        assert_eq!(id, conjunction_id(&cdm.object2.object_id, &cdm.object1.object_id, cdm.tca, bucket));
+    }
+
+    #[tokio::test]
+    async fn test_upcoming_conjunctions() {
+        let state = test_state("node-a");
+        let now = Utc::now();
+        for (id, secondary, hours, pc) in [("SOON", "1", 2, 1e-6), ("RISKY", "2", 10, 1e-3), ("LATER", "3", 30, 1e-4), ("PAST", "4", -1, 1e-3)] {
+            let mut cdm = generate_demo_cdm();
+            cdm.cdm_id = id.into();
+            cdm.object2.object_id = format!("NORAD-9000{}", secondary);
+            cdm.tca = now + chrono::Duration::hours(hours) + chrono::Duration::minutes(1);
+            cdm.collision_probability = pc;
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+        state.watchlist.register("NORAD-90002", Some("OURSAT".into()));
+        let upcoming = |window: Option<&str>, sort: UpcomingOrder| {
+            let query = UpcomingQuery {
+                window: window.map(str::to_string),
+                sort,
+            };
+            upcoming_conjunctions(State(state.clone()), TenantScope::default(), Query(query))
+        };
+        let ids = |list: &UpcomingConjunctionsResponse| -> Vec<String> {
+            list.conjunctions.iter().map(|c| c.summary.latest.cdm_id.clone()).collect()
+        };
+
+        let Json(day) = upcoming(None, UpcomingOrder::Tca).await.unwrap();
+        assert_eq!(ids(&day), ["SOON", "RISKY"]);
+        assert_eq!(day.conjunctions[0].countdown, "2h 0m");
+        assert!(day.conjunctions[0].watched_assets.is_empty());
+        assert_eq!(day.conjunctions[1].watched_assets[0].name.as_deref(), Some("OURSAT"));
+        let Json(by_risk) = upcoming(None, UpcomingOrder::Risk).await.unwrap();
+        assert_eq!(ids(&by_risk), ["RISKY", "SOON"]);
+        let Json(two_days) = upcoming(Some("2d"), UpcomingOrder::Tca).await.unwrap();
+        assert_eq!(ids(&two_days), ["SOON", "RISKY", "LATER"]);
+        assert_eq!(two_days.conjunctions[2].countdown, "1d 6h");
+
+        let (status, _) = upcoming(Some("soon"), UpcomingOrder::Tca).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_conjunction_trend() {
+        let state = test_state("node-a");
+        let base = generate_demo_cdm();
+        for (id, pc, hours_ago) in [("CDM-1", 1e-5, 3), ("CDM-2", 2e-5, 2), ("CDM-3", 8e-5, 1)] {
+            let mut cdm = base.clone();
+            cdm.cdm_id = id.into();
+            cdm.collision_probability = pc;
+            cdm.creation_date = Utc::now() - chrono::Duration::hours(hours_ago);
+            state.storage.store_cdm(cdm).await.unwrap();
+        }
+        state.storage.withdraw_cdm("CDM-1").await.unwrap();
+        let bucket = state.config.get().storage.conjunction_bucket_seconds;
+        let id = conjunction_id(&base.object1.object_id, &base.object2.object_id, base.tca, bucket);
+
+        let Json(trend) = conjunction_trend(State(state.clone()), TenantScope::default(), Path(id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(trend.conjunction_id, id);
+        let points: Vec<(&str, bool)> = trend.trend.points.iter().map(|p| (p.cdm_id.as_str(), p.withdrawn)).collect();
+        assert_eq!(points, [("CDM-1", true), ("CDM-2", false), ("CDM-3", false)]);
+        assert_eq!(trend.trend.direction, crate::cdm::TrendDirection::Increasing);
+        assert_eq!(trend.trend.compared_with.as_deref(), Some("CDM-2"));
+        assert!(trend.trend.risk_increasing);
+
+        let (status, _) = conjunction_trend(State(state), TenantScope::default(), Path("cj-unknown".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_watchlist() {
+        let state = test_state("node-a");
+        let watched = generate_demo_cdm();
+        let mut other = generate_demo_cdm();
+        other.cdm_id = "CDM-OTHER".into();
+        other.object1.object_id = "NORAD-11111".into();
+        other.object2.object_id = "NORAD-22222".into();
+        for cdm in [&watched, &other] {
+            let body = serde_json::to_value(cdm).unwrap();
+            accept_cdm(&state, body, None, &None).await.unwrap();
+        }
+        assert!(!state.storage.get_cdm(&watched.cdm_id).await.unwrap().unwrap().involves_watched_asset);
+
+        let register = |ids: &[&str]| {
+            Json(RegisterAssetsRequest {
+                assets: ids
+                    .iter()
+                    .map(|id| AssetRegistration {
+                        norad_id: id.to_string(),
+                        name: None,
+                    })
+                    .collect(),
+            })
+        };
+        let (status, _) = register_assets(State(state.clone()), register(&["12345", "ISS"])).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(state.watchlist.list().len(), 0);
+        let Json(update) = register_assets(State(state.clone()), register(&["NORAD-12345"])).await.unwrap();
+        assert_eq!((update.changed, update.retagged, update.total), (vec!["12345".to_string()], 1, 1));
+
+        let only_watched = CdmListQuery {
+            watched: Some(true),
+            ..Default::default()
+        };
+        let Json(list) = list_cdms(State(state.clone()), TenantScope::default(), Query(only_watched)).await;
+        assert_eq!(list.total, 1);
+        assert!(list.cdms[0].involves_watched_asset);
+        assert_eq!(list.cdms[0].cdm_id, watched.cdm_id);
+
+        // New CDMs are tagged on ingest
+        let mut newer = other.clone();
+        newer.cdm_id = "CDM-NEWER".into();
+        newer.object2.object_id = "NORAD-12345".into();
+        let body = serde_json::to_value(&newer).unwrap();
+        accept_cdm(&state, body, None, &None).await.unwrap();
+        assert!(state.storage.get_cdm("CDM-NEWER").await.unwrap().unwrap().involves_watched_asset);
+
+        let query = EventsQuery {
+            since: Some(0),
+            timeout_seconds: 0,
+            watched: true,
+        };
+        let Json(page) = cdm_events(State(state.clone()), TenantScope::default(), Query(query)).await;
+        let ids: Vec<&str> = page.events.iter().map(|e| e.cdm_id.as_str()).collect();
+        assert_eq!(ids, [watched.cdm_id.as_str(), "CDM-NEWER"]);
+
+        let Json(update) = unregister_asset(State(state.clone()), Path("12345".into())).await.unwrap();
+        assert_eq!((update.retagged, update.total), (2, 0));
+        let (status, _) = unregister_asset(State(state.clone()), Path("12345".into())).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_alerts() {
+        let state = test_state("node-a");
+        let cdm = generate_demo_cdm();
+        accept_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap();
+        let all = || Query(AlertListQuery::default());
+        let Json(list) = list_alerts(State(state.clone()), TenantScope::default(), all()).await;
+        assert_eq!(list.total, 0);
+
+        // Watching an asset opens alerts for its active CDMs
+        let request = Json(RegisterAssetsRequest {
+            assets: vec![AssetRegistration {
+                norad_id: "12345".into(),
+                name: None,
+            }],
+        });
+        let Json(update) = register_assets(State(state.clone()), request).await.unwrap();
+        assert_eq!(update.retagged, 1);
+        let Json(list) = list_alerts(State(state.clone()), TenantScope::default(), all()).await;
+        assert_eq!((list.total, list.open), (1, 1));
+        let id = list.alerts[0].alert_id.clone();
+
+        let caller = Caller {
+            token_id: "ops-console".into(),
+            organization: None,
+            roles: Vec::new(),
+            permissions: vec!["write".into()],
+            expires_at: None,
+        };
+        let Json(alert) = acknowledge_alert(
+            State(state.clone()),
+            TenantScope::default(),
+            Some(Extension(caller)),
+            Path(id.clone()),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(alert.acknowledged_by.as_deref(), Some("ops-console"));
+        let assign = Json(AssignAlertRequest {
+            assignee: Some("alice".into()),
+        });
+        let Json(alert) = assign_alert(State(state.clone()), TenantScope::default(), Path(id.clone()), assign).await.unwrap();
+        assert_eq!(alert.assignee.as_deref(), Some("alice"));
+        let query = AlertListQuery {
+            assignee: Some("alice".into()),
+            state: Some(AlertState::Acknowledged),
+            ..Default::default()
+        };
+        let Json(list) = list_alerts(State(state.clone()), TenantScope::default(), Query(query)).await;
+        assert_eq!((list.total, list.open), (1, 0));
+
+        // Withdrawing the CDM resolves the alert
+        let body = WithdrawCdmRequest {
+            reason: "TCA_PASSED".into(),
+            superseded_by: None,
+        };
+        let Json(withdrawn) = withdraw_cdm(State(state.clone()), Path(cdm.cdm_id.clone()), Json(body)).await.unwrap();
+        assert_eq!(withdrawn.status, "withdrawn");
+        let Json(alert) = get_alert(State(state.clone()), TenantScope::default(), Path(id.clone())).await.unwrap();
+        assert_eq!(alert.state, AlertState::Resolved);
+        let (status, Json(error)) = assign_alert(
+            State(state.clone()),
+            TenantScope::default(),
+            Path(id.clone()),
+            Json(AssignAlertRequest { assignee: None }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!((status, error.error.as_str()), (StatusCode::CONFLICT, "alert_resolved"));
+        let (status, _) = get_alert(State(state.clone()), TenantScope::default(), Path("ALERT-NONE".into())).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+node: { id: node-a }
+server: {}
+api:
+  auth:
+    enabled: true
+    tokens:
+      - { id: operator, secret: op-secret, permissions: [admin] }
+      - { id: acme, secret: acme-secret, permissions: [write], organization: acme }
+      - { id: orbital, secret: orbital-secret, permissions: [read], organization: orbital }
+      - { id: netops, secret: netops-secret, roles: [peer-manager] }
+      - { id: pipeline, secret: pipeline-secret, permissions: ["POST /cdm"] }
+  organizations:
+    - { id: acme, aliases: [ACME Space] }
+    - { id: orbital, objects: ["NORAD-7*"] }
+  tenant_isolation: true
+"#,
+        )
+        .unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        let server = NodeServer::new(config, storage, Arc::new(RwLock::new(PeerManager::new())), routing);
+        let state = server.state().clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let http = reqwest::Client::new();
+        let get = |path: &str, token: Option<&str>| {
+            let request = http.get(format!("{}{}", address, path));
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        };
+        assert_eq!(get("/health", None).send().await.unwrap().status().as_u16(), 200);
+        assert_eq!(get("/cdms", None).send().await.unwrap().status().as_u16(), 401);
+        assert_eq!(get("/cdms", Some("guess")).send().await.unwrap().status().as_u16(), 401);
+
+        // ACME ingests its own CDM; the operator ingests one involving an
+        // object Orbital registered
+        let mut own = generate_demo_cdm();
+        own.cdm_id = "CDM-ACME".into();
+        let mut registered = generate_demo_cdm();
+        registered.cdm_id = "CDM-ORBITAL".into();
+        registered.object2.object_id = "NORAD-70001".into();
+        let post = |cdm: &CdmRecord, token: &str| http.post(format!("{}/cdm", address)).bearer_auth(token).json(cdm);
+        assert_eq!(post(&own, "acme-secret").send().await.unwrap().status().as_u16(), 201);
+        assert_eq!(post(&registered, "op-secret").send().await.unwrap().status().as_u16(), 201);
+        assert_eq!(post(&registered, "orbital-secret").send().await.unwrap().status().as_u16(), 403);
+        let stored = state.storage.get_cdm("CDM-ACME").await.unwrap().unwrap();
+        assert_eq!(stored.organization.as_deref(), Some("acme"));
+        let stored = state.storage.get_cdm("CDM-ORBITAL").await.unwrap().unwrap();
+        assert_eq!(stored.organization.as_deref(), Some("orbital"));
+
+        let listed = |token: &'static str| {
+            let request = get("/cdms", Some(token));
+            async move {
+                let list: serde_json::Value = request.send().await.unwrap().json().await.unwrap();
+                let cdms = list["cdms"].as_array().unwrap().iter();
+                let mut ids: Vec<String> = cdms.map(|c| c["cdm_id"].as_str().unwrap().to_string()).collect();
+                ids.sort();
+                ids
+            }
+        };
+        assert_eq!(listed("acme-secret").await, vec!["CDM-ACME"]);
+        assert_eq!(listed("orbital-secret").await, vec!["CDM-ORBITAL"]);
+        assert_eq!(listed("op-secret").await, vec!["CDM-ACME", "CDM-ORBITAL"]);
+        let status = |path: &str, token: &str| get(path, Some(token)).send();
+        assert_eq!(status("/cdms/CDM-ORBITAL", "acme-secret").await.unwrap().status().as_u16(), 404);
+        assert_eq!(status("/cdms/CDM-ORBITAL", "orbital-secret").await.unwrap().status().as_u16(), 200);
+        assert_eq!(status("/archive/cdms", "acme-secret").await.unwrap().status().as_u16(), 403);
+
+        // A CDM addressed to an alias is visible to that organization
+        let mut addressed = generate_demo_cdm();
+        addressed.message_for = "acme space".into();
+        apply_announcement(&state, &Envelope::new("node-b".into(), MessageType::CdmAnnounce, serde_json::to_value(&addressed).unwrap()), None)
+            .await
+            .unwrap();
+        assert_eq!(listed("acme-secret").await.len(), 2);
+
+        let events: CdmEventPage = get("/events/cdms?since=0&timeout_seconds=0", Some("orbital-secret"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(events.events.len(), 1);
+        assert_eq!(events.events[0].cdm_id, "CDM-ORBITAL");
+
+        // Roles and endpoint scopes
+        let whoami: serde_json::Value = get("/auth/whoami", Some("netops-secret")).send().await.unwrap().json().await.unwrap();
+        assert_eq!(whoami["roles"], serde_json::json!(["peer-manager"]));
+        assert_eq!(whoami["permissions"], serde_json::json!(["read", "* /peers", "* /peers/*"]));
+        let removed = http.delete(format!("{}/peers/unknown", address)).bearer_auth("netops-secret").send().await.unwrap();
+        assert_eq!(removed.status().as_u16(), 404);
+        let reload = http.post(format!("{}/admin/reload", address)).bearer_auth("netops-secret").send().await.unwrap();
+        assert_eq!(reload.status().as_u16(), 403);
+        // Peer secrets are only exported to admins
+        assert_eq!(status("/peers/export", "netops-secret").await.unwrap().status().as_u16(), 200);
+        let export = status("/peers/export?include_secrets=true", "netops-secret").await.unwrap();
+        assert_eq!(export.status().as_u16(), 403);
+        let export = status("/peers/export?include_secrets=true", "op-secret").await.unwrap();
+        assert_eq!(export.status().as_u16(), 200);
+        assert_eq!(status("/cdms", "pipeline-secret").await.unwrap().status().as_u16(), 403);
+        let whoami = get("/auth/whoami", Some("pipeline-secret")).send().await.unwrap();
+        assert_eq!(whoami.status().as_u16(), 200);
+        assert_eq!(get("/auth/whoami", None).send().await.unwrap().status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_request_limits_on_node_routes() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let config: Config = serde_yaml::from_str(
+            r#"
+node: { id: node-a }
+server: { max_body_bytes: 3145728, request_timeout_seconds: 1 }
+api:
+  auth:
+    enabled: true
+    tokens:
+      - { id: operator, secret: op-secret, permissions: [admin] }
+"#,
+        )
+        .unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        let server = NodeServer::new(config, storage, Arc::new(RwLock::new(PeerManager::new())), routing);
+        let state = server.state().clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let padded = |len: usize| {
+            let mut body = serde_json::to_vec(&generate_demo_cdm()).unwrap();
+            body.resize(len, b' ');
+            body
+        };
+        let http = reqwest::Client::new();
+        let post = |body: Vec<u8>, token: Option<&str>| {
+            let request = http.post(format!("http://{}/cdm", addr)).header("content-type", "application/json").body(body);
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        };
+        // Over axum's own 2 MiB default, under the configured limit
+        let resp = post(padded(2_500_000), Some("op-secret")).send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 201);
+        let resp = post(padded(3_500_000), Some("op-secret")).send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 413);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"], "payload_too_large");
+        // Authentication comes first
+        let resp = post(padded(3_500_000), None).send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+
+        // The streaming import takes a larger body, uploaded slower than the timeout
+        let mut archive = Vec::new();
+        for _ in 0..4 {
+            archive.extend(padded(900_000));
+            archive.push(b'\n');
+        }
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let head = format!(
+            "POST /cdm/import HTTP/1.1\r\nHost: node\r\nAuthorization: Bearer op-secret\r\n\
+             Content-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            archive.len()
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let (first, rest) = archive.split_at(archive.len() / 2);
+        stream.write_all(first).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        stream.write_all(rest).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains(r#""imported":4"#) && response.contains(r#""complete":true"#), "{}", response);
+        assert_eq!(state.storage.list_cdms().await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_api_token_lifecycle() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+node: { id: node-a }
+server: {}
+api:
+  auth:
+    enabled: true
+    tokens:
+      - { id: operator, secret: op-secret, permissions: [admin] }
+      - { id: tokens-only, secret: scoped-secret, permissions: ["* /auth/tokens", "* /auth/tokens/*"] }
+  organizations:
+    - { id: acme }
+"#,
+        )
+        .unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        let server = NodeServer::new(config, storage.clone(), Arc::new(RwLock::new(PeerManager::new())), routing);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let http = reqwest::Client::new();
+        let url = |path: &str| format!("{}{}", address, path);
+        let whoami = |secret: &str| http.get(url("/auth/whoami")).bearer_auth(secret).send();
+        let issue = |token: &str, body: serde_json::Value| http.post(url("/auth/tokens")).bearer_auth(token).json(&body).send();
+        let rotate = |id: &str, grace: u64| {
+            http.post(url(&format!("/auth/tokens/{}/rotate", id)))
+                .bearer_auth("op-secret")
+                .json(&serde_json::json!({ "grace_seconds": grace }))
+                .send()
+        };
+
+        let issued = issue("op-secret", serde_json::json!({"id": "ingest", "roles": ["operator"], "organization": "acme", "expires_in_seconds": 3600}))
+            .await
+            .unwrap();
+        assert_eq!(issued.status().as_u16(), 201);
+        let issued: serde_json::Value = issued.json().await.unwrap();
+        let secret = issued["secret"].as_str().unwrap().to_string();
+        assert!(secret.starts_with("sct_"));
+        assert_eq!(issued["token"]["issued_by"], "operator");
+        let me: serde_json::Value = whoami(&secret).await.unwrap().json().await.unwrap();
+        assert_eq!(me["token_id"], "ingest");
+        assert_eq!(me["organization"], "acme");
+        assert!(me["expires_at"].is_string());
+
+        // Clashing IDs, unknown roles and scope-only callers are refused
+        let clash = issue("op-secret", serde_json::json!({"id": "operator", "permissions": ["read"]})).await.unwrap();
+        assert_eq!(clash.status().as_u16(), 409);
+        let unknown = issue("op-secret", serde_json::json!({"roles": ["pilot"]})).await.unwrap();
+        assert_eq!(unknown.status().as_u16(), 400);
+        let scoped = issue("scoped-secret", serde_json::json!({"permissions": ["admin"]})).await.unwrap();
+        assert_eq!(scoped.status().as_u16(), 403);
+
+        // Rotation with a grace period keeps the old secret working
+        let rotated: serde_json::Value = rotate("ingest", 300).await.unwrap().json().await.unwrap();
+        let new_secret = rotated["secret"].as_str().unwrap().to_string();
+        assert_ne!(new_secret, secret);
+        assert!(rotated["token"]["previous_secret_expires_at"].is_string());
+        assert_eq!(whoami(&secret).await.unwrap().status().as_u16(), 200);
+        assert_eq!(whoami(&new_secret).await.unwrap().status().as_u16(), 200);
+
+        // Rotation without one retires the replaced secret at once
+        let rotated: serde_json::Value = rotate("ingest", 0).await.unwrap().json().await.unwrap();
+        let newest = rotated["secret"].as_str().unwrap().to_string();
+        assert!(rotated["token"]["previous_secret_expires_at"].is_null());
+        assert!(storage.get_api_token("ingest").await.unwrap().unwrap().previous_secret_hash.is_none());
+        assert_eq!(whoami(&newest).await.unwrap().status().as_u16(), 200);
+        assert_eq!(whoami(&new_secret).await.unwrap().status().as_u16(), 401);
+        assert_eq!(whoami(&secret).await.unwrap().status().as_u16(), 401);
+
+        let revoke = |id: &str| http.delete(url(&format!("/auth/tokens/{}", id))).bearer_auth("op-secret").send();
+        let revoked: serde_json::Value = revoke("ingest").await.unwrap().json().await.unwrap();
+        assert_eq!(revoked["status"], "revoked");
+        let refused = whoami(&newest).await.unwrap();
+        assert_eq!(refused.status().as_u16(), 401);
+        assert_eq!(refused.json::<serde_json::Value>().await.unwrap()["message"], "token revoked");
+        assert_eq!(whoami(&secret).await.unwrap().status().as_u16(), 401);
+        assert_eq!(revoke("ingest").await.unwrap().status().as_u16(), 200);
+        assert_eq!(revoke("operator").await.unwrap().status().as_u16(), 409);
+        assert_eq!(revoke("missing").await.unwrap().status().as_u16(), 404);
+
+        // Revoked and expired tokens cannot be rotated back to life
+        let inactive = rotate("ingest", 0).await.unwrap();
+        assert_eq!(inactive.status().as_u16(), 409);
+        assert_eq!(inactive.json::<serde_json::Value>().await.unwrap()["error"], "token_inactive");
+        let issued: serde_json::Value = issue("op-secret", serde_json::json!({"id": "short", "permissions": ["read"], "expires_in_seconds": 60}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let short_secret = issued["secret"].as_str().unwrap().to_string();
+        let mut short = storage.get_api_token("short").await.unwrap().unwrap();
+        short.expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        storage.store_api_token(short).await.unwrap();
+        let refused = whoami(&short_secret).await.unwrap();
+        assert_eq!(refused.status().as_u16(), 401);
+        assert_eq!(refused.json::<serde_json::Value>().await.unwrap()["message"], "token expired");
+        assert_eq!(rotate("short", 0).await.unwrap().status().as_u16(), 409);
+
+        let listed: serde_json::Value = http.get(url("/auth/tokens")).bearer_auth("op-secret").send().await.unwrap().json().await.unwrap();
+        let sources: Vec<(&str, &str)> = listed["tokens"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| (t["id"].as_str().unwrap(), t["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![("operator", "active"), ("tokens-only", "active"), ("ingest", "revoked"), ("short", "expired")]
+        );
+        assert!(!listed.to_string().contains("secret_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_node() {
+        let config: Config = serde_yaml::from_str("node: { id: replica, mode: read-only }\nserver: {}").unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        // Written by the operational node sharing the backend
+        storage.store_cdm(generate_demo_cdm()).await.unwrap();
+        let routing = Arc::new(RoutingEngine::new(config.clone()));
+        let server = NodeServer::new(config, storage, Arc::new(RwLock::new(PeerManager::new())), routing);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let http = reqwest::Client::new();
+
+        let health: serde_json::Value = http.get(format!("{}/health", address)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(health["mode"], "read-only");
+        let list: serde_json::Value = http.get(format!("{}/cdms", address)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(list["cdms"].as_array().unwrap().len(), 1);
+
+        let resp = http.post(format!("{}/cdm", address)).json(&generate_demo_cdm()).send().await.unwrap();
+        assert_eq!(resp.status(), 403);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"], "read_only");
+        let resp = http.post(format!("{}{}", address, PROTOCOL_ENDPOINT)).body("{}").send().await.unwrap();
+        assert_eq!(resp.status(), 403);
+        let resp = http.delete(format!("{}/cdms/{}", address, generate_demo_cdm().cdm_id)).send().await.unwrap();
+        assert_eq!(resp.status(), 403);
+
+        // Local bookkeeping still works
+        let resp = http
+            .post(format!("{}/watchlist", address))
+            .json(&serde_json::json!({ "assets": [{ "norad_id": "25544" }] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[test]
+    fn test_openapi_document() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        for (path, methods) in [
+            ("/health", &["get"][..]),
+            ("/health/live", &["get"]),
+            ("/health/ready", &["get"]),
+            ("/metrics", &["get"]),
+            ("/auth/whoami", &["get"]),
+            ("/auth/tokens", &["get", "post"]),
+            ("/auth/tokens/{id}", &["delete"]),
+            ("/auth/tokens/{id}/rotate", &["post"]),
+            ("/cdm", &["post"]),
+            ("/cdms", &["get", "delete"]),
+            ("/cdms/bulk", &["post"]),
+            ("/cdm/import", &["post"]),
+            ("/export", &["get"]),
+            ("/import", &["post"]),
+            ("/cdms/{id}", &["get", "delete"]),
+            ("/cdms/{id}/pc", &["get", "post"]),
+            ("/cdms/{id}/trace", &["get"]),
+            ("/cdms/{id}/propagation", &["get"]),
+            ("/cdms/{id}/receipts", &["get"]),
+            ("/cdms/quarantine", &["get"]),
+            ("/cdms/quarantine/{id}", &["delete"]),
+            ("/cdms/quarantine/{id}/release", &["post"]),
+            ("/conjunctions", &["get"]),
+            ("/conjunctions/upcoming", &["get"]),
+            ("/conjunctions/{id}/trend", &["get"]),
+            ("/events/cdms", &["get"]),
+            ("/archive/cdms", &["get"]),
+            ("/archive/objects", &["get"]),
+            ("/objects", &["get"]),
+            ("/objects/omm", &["post"]),
+            ("/objects/{id}", &["delete"]),
+            ("/objects/{id}/cdms", &["get"]),
+            ("/objects/{id}/state", &["get"]),
+            ("/objects/{id}/history", &["get"]),
+            ("/catalog/resolve", &["get"]),
+            ("/peers", &["get", "post"]),
+            ("/peers/{id}", &["get", "delete"]),
+            ("/peers/{id}/sla", &["get"]),
+            ("/peers/{id}/disable", &["post"]),
+            ("/peers/{id}/enable", &["post"]),
+            ("/peers/{id}/block", &["post", "delete"]),
+            ("/peers/export", &["get"]),
+            ("/peers/import", &["post"]),
+            ("/peers/blocked", &["get"]),
+            ("/peers/{id}/cdm-query", &["post"]),
+            ("/peers/{id}/sync", &["post"]),
+            ("/routing/simulate", &["post"]),
+            ("/network/topology", &["get"]),
+            ("/network/objects/{id}/sources", &["get"]),
+            ("/originators", &["get"]),
+            ("/originators/{id}/hold", &["delete"]),
+            ("/watchlist", &["get", "post"]),
+            ("/watchlist/{id}", &["delete"]),
+            ("/alerts", &["get"]),
+            ("/alerts/{id}", &["get"]),
+            ("/alerts/{id}/acknowledge", &["post"]),
+            ("/alerts/{id}/assign", &["post"]),
+            ("/alerts/{id}/resolve", &["post"]),
+            ("/maneuvers", &["post"]),
+            ("/admin/reload", &["post"]),
+            (PROTOCOL_ENDPOINT, &["post"]),
+        ] {
+            for method in methods {
+                assert!(paths[path].get(*method).is_some(), "{} {} undocumented", method, path);
+            }
+        }
+
+        // Every referenced schema is defined
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let text = spec.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(schemas.contains_key(name), "missing schema {}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_history_endpoint() {
+        let state = test_state("node-local");
+        let at = Utc::now() - chrono::Duration::minutes(10);
+        let samples = [(StatMetric::PeersConnected, 3.0)];
+        state.storage.record_stats(at, &samples, at - chrono::Duration::hours(1)).await.unwrap();
+
+        let history = |metric: StatMetric, range: &str| {
+            stats_history(
+                State(state.clone()),
+                Query(StatsHistoryQuery { metric, range: Some(range.to_string()) }),
+            )
+        };
+        let Json(response) = history(StatMetric::PeersConnected, "1h").await.unwrap();
+        assert_eq!(response.range_seconds, 3600);
+        assert_eq!(response.samples.len(), 1);
+        assert_eq!(response.samples[0].value, 3.0);
+        assert!(history(StatMetric::PeersConnected, "5m").await.unwrap().0.samples.is_empty());
+        assert!(history(StatMetric::QueueDepth, "1h").await.unwrap().0.samples.is_empty());
+
+        let (status, _) = history(StatMetric::PeersConnected, "soon").await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_quality_floor() {
+        let state = test_state("node-local");
+        let mut cdm = generate_demo_cdm();
+        cdm.data_quality_score = Some(0.8);
+        cdm.object2.covariance_rtm.as_mut().unwrap().cn_n = -1.0e-4;
+        let body = serde_json::to_value(&cdm).unwrap();
+
+        // Kept with a lowered score
+        let (prepared, _) = prepare_cdm(&state, body.clone(), None, &None).await.unwrap();
+        assert!(prepared.data_quality_score.unwrap() < 0.1);
+
+        let mut config = (*state.config.get()).clone();
+        config.protocol.min_data_quality = 0.5;
+        state.config.replace(config);
+        let err = prepare_cdm(&state, body, None, &None).await.unwrap_err();
+        assert!(matches!(err, Error::CdmValidation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_canonical_object_ids() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.identifiers.aliases.insert("98067A".into(), "25544".into());
+        state.config.replace(config);
+
+        let mut cdm = generate_demo_cdm();
+        cdm.object1.object_id = "012345".into();
+        cdm.object2.object_id = "1998-067A".into();
+        let (prepared, _) = prepare_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap();
+        assert_eq!(
+            (prepared.object1.object_id.as_str(), prepared.object2.object_id.as_str()),
+            ("NORAD-12345", "NORAD-25544")
+        );
+
+        // Peers' CDMs too, stored and found under any form of the ID
+        cdm.object2.object_id = "norad 25544".into();
+        let envelope = Envelope::new("node-remote".to_string(), MessageType::CdmAnnounce, serde_json::to_value(&cdm).unwrap());
+        let (status, _) = send(&state, "application/json", "node-remote", serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let stored = state.storage.get_cdm(&cdm.cdm_id).await.unwrap().unwrap();
+        assert_eq!(stored.object2.object_id, "NORAD-25544");
+        let query = CdmListQuery {
+            object_id: Some("98-067A".into()),
+            ..Default::default()
+        };
+        let Json(list) = list_cdms(State(state.clone()), TenantScope::default(), Query(query)).await;
+        assert_eq!(list.total, 1);
+
+        // IDs with control characters, or too long, are refused on every path
+        for bad in ["SAT\nB".to_string(), "SAT\u{1b}[2J".to_string(), "X".repeat(65)] {
+            let mut cdm = generate_demo_cdm();
+            cdm.object1.object_id = bad.clone();
+            let err = prepare_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap_err();
+            assert!(matches!(err, Error::CdmValidation(_)), "{:?}", bad);
+            let envelope = Envelope::new("node-remote".to_string(), MessageType::CdmAnnounce, serde_json::to_value(&cdm).unwrap());
+            assert!(apply_announcement(&state, &envelope, Some("node-remote")).await.is_err());
+
+            let announce = ObjectStateAnnouncePayload {
+                object_id: bad.clone(),
+                object_name: "SAT".into(),
+                object_type: ObjectType::Payload,
+                owner_operator: None,
+                epoch: Utc::now(),
+                state_vector: cdm.object2.state_vector.clone(),
+                covariance: None,
+                metadata: Default::default(),
+            };
+            let envelope = Envelope::new("node-remote".to_string(), MessageType::ObjectStateAnnounce, serde_json::to_value(&announce).unwrap());
+            assert!(apply_announcement(&state, &envelope, Some("node-remote")).await.is_err());
+            assert!(state.storage.get_object(&bad).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_catalog_object() {
+        struct Catalog;
+
+        #[async_trait::async_trait]
+        impl CatalogProvider for Catalog {
+            async fn lookup(&self, object_id: &str) -> Result<Option<CatalogEntry>> {
+                Ok((object_id == "NORAD-25544").then(|| CatalogEntry {
+                    object_id: object_id.to_string(),
+                    object_name: "ISS (ZARYA)".to_string(),
+                    object_type: ObjectType::Payload,
+                    owner: Some("ISS".to_string()),
+                    rcs_size: None,
+                    international_designator: Some("1998-067A".to_string()),
+                }))
+            }
+
+            async fn search(&self, by: CatalogSearch, value: &str) -> Result<Option<CatalogEntry>> {
+                match by {
+                    CatalogSearch::Name if value.eq_ignore_ascii_case("ISS (ZARYA)") => self.lookup("NORAD-25544").await,
+                    _ => Ok(None),
+                }
+            }
+        }
+
+        let mut state = test_state("node-local");
+        let resolve = |state: &AppState, query: &str| {
+            let query = CatalogResolveQuery { query: query.into() };
+            resolve_catalog_object(State(state.clone()), Query(query))
+        };
+        let (status, Json(error)) = resolve(&state, "25544").await.unwrap_err();
+        assert_eq!((status, error.error.as_str()), (StatusCode::NOT_FOUND, "catalog_disabled"));
+
+        state.catalog = Some(Arc::new(CatalogCache::new(Arc::new(Catalog), Duration::from_secs(60), 10)));
+        let mut config = (*state.config.get()).clone();
+        config.identifiers.aliases.insert("ZARYA".into(), "25544".into());
+        state.config.replace(config);
+
+        let Json(resolved) = resolve(&state, "025544").await.unwrap();
+        assert_eq!((resolved.object_id.as_str(), resolved.matched_by), ("NORAD-25544", CatalogMatch::CatalogNumber));
+        assert_eq!(resolved.aliases, ["25544", "1998-067A", "ISS (ZARYA)", "ZARYA"]);
+        let Json(resolved) = resolve(&state, "iss (zarya)").await.unwrap();
+        assert_eq!(resolved.matched_by, CatalogMatch::Name);
+        let Json(resolved) = resolve(&state, "zarya").await.unwrap();
+        assert_eq!(resolved.matched_by, CatalogMatch::Alias);
+        let (status, Json(error)) = resolve(&state, "STARLINK-1234").await.unwrap_err();
+        assert_eq!((status, error.error.as_str()), (StatusCode::NOT_FOUND, "not_found"));
+
+        // A CDM naming the object is stored under its catalog number
+        let mut cdm = generate_demo_cdm();
+        cdm.object2.object_id = "ISS (ZARYA)".into();
+        let (prepared, _) = prepare_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap();
+        assert_eq!(prepared.object2.object_id, "NORAD-25544");
+    }
+
+    #[tokio::test]
+    async fn test_ingest_rule_warnings() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.validation.rules = serde_yaml::from_str(
+            "[{ name: low-pc, field: collision_probability, operator: lt, threshold: 0.5, action: warn },
+              { field: originator, operator: ne, threshold: BLOCKED }]",
+        )
+        .unwrap();
+        state.config.replace(config);
+
+        let mut cdm = generate_demo_cdm();
+        cdm.collision_probability = 0.6;
+        let accepted = accept_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap();
+        assert_eq!(accepted.warnings.len(), 1);
+        assert_eq!(accepted.warnings[0].rule, "low-pc");
+
+        cdm.cdm_id = "CDM-BLOCKED".into();
+        cdm.originator = "BLOCKED".into();
+        let (status, error) = accept_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("originator ne"), "{}", error.message);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_cdms() {
+        let state = test_state("node-local");
+        let mut config = (*state.config.get()).clone();
+        config.validation.rules = serde_yaml::from_str(
+            "[{ name: known-originator, field: originator, operator: ne, threshold: UNKNOWN, action: quarantine }]",
+        )
+        .unwrap();
+        state.config.replace(config);
+
+        // Held from the API, and stored only once released
+        let mut cdm = generate_demo_cdm();
+        cdm.originator = "UNKNOWN".into();
+        let accepted = accept_cdm(&state, serde_json::to_value(&cdm).unwrap(), None, &None).await.unwrap();
+        assert_eq!(accepted.status(), "quarantined");
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_none());
+        let Json(list) = list_quarantined_cdms(State(state.clone())).await;
+        assert_eq!(list.total, 1);
+        assert_eq!(list.cdms[0].violations[0].rule, "known-originator");
+        let Json(released) = release_quarantined_cdm(State(state.clone()), Path(cdm.cdm_id.clone())).await.unwrap();
+        assert_eq!(released.cdm_id, cdm.cdm_id);
+        assert!(state.storage.get_cdm(&cdm.cdm_id).await.unwrap().is_some());
+        assert!(release_quarantined_cdm(State(state.clone()), Path(cdm.cdm_id.clone())).await.is_err());
+
+        // Held from a peer, and dropped when discarded
+        let mut relayed = generate_demo_cdm();
+        relayed.originator = "UNKNOWN".into();
+        let envelope = Envelope::new("node-b".into(), MessageType::CdmAnnounce, serde_json::to_value(&relayed).unwrap());
+        assert!(accept_relayed(&state, &envelope, "node-b").await.unwrap().is_empty());
+        assert!(state.storage.get_cdm(&relayed.cdm_id).await.unwrap().is_none());
+        assert_eq!(state.quarantine.list()[0].peer_id.as_deref(), Some("node-b"));
+        let Json(discarded) = discard_quarantined_cdm(State(state.clone()), Path(relayed.cdm_id.clone())).await.unwrap();
+        assert_eq!(discarded.cdm.cdm_id, relayed.cdm_id);
+        assert!(state.quarantine.is_empty());
+        assert!(state.storage.get_cdm(&relayed.cdm_id).await.unwrap().is_none());
+        assert_eq!(state.metrics.cdms_quarantined.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,257 @@
+//! Peer session establishment and keepalive
+
+use crate::config::PeerTransport;
+use crate::node::{spawn_sync, AppState, GrpcTransport, HttpTransport, SessionEventKind, Transport};
+use crate::protocol::{
+    downgrade, negotiate_version, round_trip_offset, Encoding, Envelope, HeartbeatPayload, HelloPayload, InterestUpdatePayload, MessageType,
+    VersionNegotiationResult, CAPABILITY_BATCHING, CAPABILITY_ENCODING_CBOR, CAPABILITY_GRPC_STREAM, CAPABILITY_INTERESTS, CAPABILITY_PAYLOAD_GZIP,
+};
+use crate::{Error, Result};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Spawn the session task for a peer
+///
+/// The task performs the HELLO handshake (retrying every heartbeat interval
+/// until it succeeds), then sends heartbeats over the established link and
+/// syncs with the peer every `protocol.sync.interval_seconds`, starting as
+/// soon as the session is up. It idles while the peer is disabled, and
+/// exits once the peer is removed from the peer manager or the node shuts
+/// down.
+pub fn spawn_session(state: AppState, peer_id: String) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let period = Duration::from_secs(state.config.get().protocol.heartbeat_interval_seconds.max(1));
+        let mut interval = tokio::time::interval(period);
+        let mut sequence = 0u64;
+        let mut last_sync: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            let link = {
+                let peers = state.peers.read().await;
+                if peers.get_peer(&peer_id).is_none() {
+                    info!("Session for removed peer {} stopped", peer_id);
+                    return;
+                }
+                // Disabling or blocking the peer dropped its link; it stays
+                // idle until enabled or unblocked
+                if peers.is_disabled(&peer_id) || peers.is_blocked(&peer_id) {
+                    continue;
+                }
+                peers.link(&peer_id)
+            };
+
+            // Only the leading instance of the node talks to peers
+            if !state.leadership.is_leader() {
+                if link.is_some() {
+                    state.peers.write().await.drop_link(&peer_id);
+                }
+                continue;
+            }
+
+            let Some(link) = link else {
+                last_sync = None;
+                if let Err(e) = connect(&state, &peer_id).await {
+                    warn!("Session with {} not established: {}", peer_id, e);
+                    state.peers.write().await.record_error(&peer_id, SessionEventKind::HandshakeFailed, e.to_string());
+                }
+                continue;
+            };
+
+            sequence += 1;
+            let echo = state.peers.write().await.take_heartbeat_echo(&peer_id);
+            let heartbeat = HeartbeatPayload {
+                sequence,
+                objects_tracked: state.storage.object_count().await.ok().map(|n| n as u64),
+                cdms_active: state.storage.cdm_count().await.ok().map(|n| n as u64),
+                credit_window: Some(state.config.get().protocol.receive_window).filter(|window| *window > 0),
+                echo,
+            };
+            let envelope = match serde_json::to_value(heartbeat) {
+                Ok(payload) => Envelope::new(state.config.get().node.id.clone(), MessageType::Heartbeat, payload),
+                Err(e) => {
+                    warn!("Failed to encode heartbeat: {}", e);
+                    continue;
+                }
+            };
+
+            state.peers.write().await.record_heartbeat_sent(&peer_id, sequence);
+            if let Err(e) = link.send(&envelope).await {
+                warn!("Heartbeat to {} failed, dropping session: {}", peer_id, e);
+                let mut peers = state.peers.write().await;
+                peers.record_error(&peer_id, SessionEventKind::SendFailed, format!("HEARTBEAT: {}", e));
+                peers.drop_link(&peer_id);
+            } else {
+                state.peers.write().await.record_sent(&peer_id, &MessageType::Heartbeat);
+                let interval = state.config.get().protocol.sync.interval_seconds;
+                if interval > 0 && last_sync.is_none_or(|at| at.elapsed() >= Duration::from_secs(interval)) {
+                    last_sync = Some(Instant::now());
+                    spawn_sync(&state, &peer_id);
+                }
+            }
+        }
+    });
+}
+
+/// Perform the HELLO handshake with a peer and attach a transport link
+async fn connect(state: &AppState, peer_id: &str) -> Result<()> {
+    let timestamp_format = state.timestamp_format_for(Some(peer_id)).await;
+    let (address, transport, encoding, auth_token) = {
+        let peers = state.peers.read().await;
+        let peer = peers
+            .get_peer(peer_id)
+            .ok_or_else(|| Error::NotFound(format!("Peer not found: {}", peer_id)))?;
+        (peer.address.clone(), peer.transport, peer.encoding, peer.auth_token.clone())
+    };
+
+    let mut local = state.local_hello();
+    local.auth_token = auth_token.clone();
+    let hello = Envelope::new(
+        state.config.get().node.id.clone(),
+        MessageType::Hello,
+        serde_json::to_value(&local)?,
+    );
+
+    // The handshake always runs over HTTP so transports can be negotiated
+    let http = HttpTransport::new(&address, &state.config.get().node.id, auth_token).with_timestamp_format(timestamp_format);
+    let sent = chrono::Utc::now();
+    let reply = http
+        .send(&hello)
+        .await?
+        .ok_or_else(|| Error::Protocol(format!("{} did not answer HELLO", peer_id)))?;
+    let clock_offset_ms = round_trip_offset(sent, reply.timestamp, chrono::Utc::now());
+    if reply.message_type != MessageType::Hello {
+        return Err(Error::Protocol(format!(
+            "{} answered HELLO with {}",
+            peer_id, reply.message_type
+        )));
+    }
+    let mut remote: HelloPayload = reply.payload.parse()?;
+    // Asked again each interval, so the session returns after maintenance
+    if remote.admin_down {
+        state.peers.write().await.record_peer_admin_down(peer_id, remote.admin_down_reason);
+        return Ok(());
+    }
+
+    let version = match negotiate_version(&local, &remote) {
+        VersionNegotiationResult::Compatible(version) => version,
+        VersionNegotiationResult::Incompatible { reason, .. } => return Err(Error::UnsupportedVersion(reason)),
+    };
+    // Features newer than the agreed version, or disabled here, fall back
+    // for this session; HELLO was sent before either side knew
+    let withheld = downgrade(&mut remote, &version, &state.config.get().protocol.disabled_features);
+    if !withheld.is_empty() {
+        info!("Session with {} on protocol {} without {}", peer_id, version, withheld.join(", "));
+    }
+
+    // The handshake is always JSON; later envelopes use CBOR when both sides agree
+    let http = match encoding {
+        Encoding::Cbor if remote.has_capability(CAPABILITY_ENCODING_CBOR) => http.with_encoding(Encoding::Cbor),
+        Encoding::Cbor => {
+            warn!("Peer {} does not offer {}, using JSON", peer_id, CAPABILITY_ENCODING_CBOR);
+            http
+        }
+        Encoding::Json => http,
+    };
+    // Whether to batch is decided per send, from `fanout.batch`
+    let http = if remote.has_capability(CAPABILITY_BATCHING) {
+        http.with_batching()
+    } else {
+        http
+    };
+    let min_payload_bytes = state.config.get().compression.min_payload_bytes;
+    let http = if min_payload_bytes > 0 && remote.has_capability(CAPABILITY_PAYLOAD_GZIP) {
+        http.with_compression(min_payload_bytes)
+    } else {
+        http
+    };
+
+    let link: Arc<dyn Transport> = match (transport, remote.grpc_port) {
+        (PeerTransport::Grpc, Some(port)) if remote.has_capability(CAPABILITY_GRPC_STREAM) => {
+            let url = grpc_url(&address, port)?;
+            Arc::new(GrpcTransport::connect(state.clone(), peer_id, &url, hello, timestamp_format).await?)
+        }
+        (PeerTransport::Grpc, _) => {
+            warn!("Peer {} does not offer {}, falling back to HTTP", peer_id, CAPABILITY_GRPC_STREAM);
+            Arc::new(http)
+        }
+        (PeerTransport::Http, _) => Arc::new(http),
+    };
+
+    let kind = link.kind();
+    info!("Session established with {} over {:?}", peer_id, kind);
+    // A fresh session gets a fresh circuit
+    state.fanout.remove_peer(peer_id);
+    let mut peers = state.peers.write().await;
+    peers.set_link(peer_id, link);
+    peers.update_heartbeat(peer_id);
+    peers.record_sent(peer_id, &MessageType::Hello);
+    peers.record_received(peer_id, &MessageType::Hello);
+    peers.record_interests(peer_id, remote.interests);
+    peers.record_handshake(peer_id, version.clone(), remote.capabilities);
+    peers.record_withheld_capabilities(peer_id, withheld);
+    peers.record_advertised_address(peer_id, remote.advertise_address);
+    peers.record_clock_sample(peer_id, clock_offset_ms, state.config.get().protocol.clock_skew.warn_seconds * 1000);
+    peers.record_event(peer_id, SessionEventKind::Connected, Some(format!("protocol {} over {:?}", version, kind)));
+    Ok(())
+}
+
+/// Send this node's interests to every connected peer that accepts
+/// INTEREST_UPDATE, after they change on reload
+pub(crate) async fn advertise_interests(state: &AppState) {
+    let interests = state.config.get().interests.clone();
+    let payload = match serde_json::to_value(InterestUpdatePayload { interests }) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Failed to encode interests: {}", e);
+            return;
+        }
+    };
+    let envelope = Envelope::new(state.config.get().node.id.clone(), MessageType::InterestUpdate, payload);
+
+    let peers = state.peers.read().await;
+    for peer in peers.list_peers() {
+        let accepts = peers
+            .session(&peer.id)
+            .is_some_and(|s| s.capabilities.iter().any(|c| c == CAPABILITY_INTERESTS));
+        let Some(link) = peers.link(&peer.id).filter(|_| accepts) else {
+            continue;
+        };
+        let (state, envelope, peer_id) = (state.clone(), envelope.clone(), peer.id.clone());
+        tokio::spawn(async move {
+            let result = link.send(&envelope).await;
+            let mut peers = state.peers.write().await;
+            match result {
+                Ok(_) => peers.record_sent(&peer_id, &MessageType::InterestUpdate),
+                Err(e) => {
+                    warn!("INTEREST_UPDATE to {} failed: {}", peer_id, e);
+                    peers.record_error(&peer_id, SessionEventKind::SendFailed, format!("INTEREST_UPDATE: {}", e));
+                }
+            }
+        });
+    }
+}
+
+/// Derive the gRPC endpoint from a peer's HTTP address and advertised port
+fn grpc_url(address: &str, port: u16) -> Result<String> {
+    let mut url = reqwest::Url::parse(address)
+        .map_err(|e| Error::Peer(format!("invalid peer address {}: {}", address, e)))?;
+    url.set_port(Some(port))
+        .map_err(|_| Error::Peer(format!("cannot set port on {}", address)))?;
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grpc_url() {
+        assert_eq!(grpc_url("http://peer.example.com:8080", 9090).unwrap(), "http://peer.example.com:9090");
+        assert_eq!(grpc_url("https://10.0.0.5:8443/", 9443).unwrap(), "https://10.0.0.5:9443");
+        assert!(grpc_url("not a url", 9090).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+//! Screening shards shared with peers
+//!
+//! Screening a catalog of tens of thousands of objects can be more work than
+//! one node should take on. With `screening.worker_nodes` set, a node plans
+//! each run as it would alone, propagating the catalog to the window start
+//! and partitioning it into shards, then splits the shards, each kept whole,
+//! into parts of about equal pair count: one kept here and one for every
+//! listed peer that is connected and offers SCREENING. A peer's part goes to
+//! it in SCREENING_REQUESTs of at most [`MAX_SCREENING_PAIRS`] pairs and
+//! [`MAX_SCREENING_OBJECTS`] objects, each carrying only the objects its
+//! pairs involve, with their states at the window start. The peer screens
+//! the pairs as this node would and answers with the close approaches
+//! found in a SCREENING_RESPONSE. Like CDM queries, requests always travel
+//! over HTTP with the answer as the reply, so each must be screened within
+//! the peer's `server.request_timeout_seconds`. Whatever part of its share
+//! a peer fails or refuses to screen is screened here, so a run always
+//! covers every shard.
+//!
+//! A node answers SCREENING_REQUEST only from peers whose `serve_screening`
+//! policy is on, which it is not by default, and screens on its own worker
+//! tasks.
+
+use crate::cdm::ObjectRecord;
+use crate::node::{reply_transport, AppState, HttpTransport, Transport};
+use crate::orbit::{screen_pairs, PairScreening, Screening, ScreeningOptions, ScreeningPlan};
+use crate::protocol::{
+    Envelope, MessageType, ScreeningRequestPayload, ScreeningResponsePayload, CAPABILITY_SCREENING,
+    MAX_SCREENING_OBJECTS, MAX_SCREENING_PAIRS,
+};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+/// Screen `objects` over the window starting at `start`, sharing the shards
+/// with those of `worker_nodes` that are connected and offer SCREENING
+pub async fn screen_shared(
+    state: &AppState,
+    worker_nodes: &[String],
+    objects: &[ObjectRecord],
+    start: DateTime<Utc>,
+    options: &ScreeningOptions,
+) -> Screening {
+    let plan = ScreeningPlan::new(objects, start, options);
+    let mut peers = Vec::new();
+    if plan.pairs() > 0 {
+        for peer_id in worker_nodes {
+            match reply_transport(state, peer_id, CAPABILITY_SCREENING).await {
+                Ok(transport) => peers.push((peer_id.clone(), transport)),
+                Err(e) => debug!("Screening without {}: {}", peer_id, e),
+            }
+        }
+    }
+
+    let mut parts = plan.split(peers.len() + 1).into_iter();
+    let local = parts.next().unwrap_or_default();
+    let plan = Arc::new(plan);
+    let mut shared = JoinSet::new();
+    for ((peer_id, transport), pairs) in peers.into_iter().zip(parts) {
+        let (state, plan, options) = (state.clone(), plan.clone(), options.clone());
+        shared.spawn(async move { screen_on_peer(&state, &peer_id, &transport, &plan, pairs, &options).await });
+    }
+    let mut found = vec![screen_pairs(plan.objects.clone(), local, start, options).await];
+    while let Some(done) = shared.join_next().await {
+        let (screened, unscreened) = match done {
+            Ok(done) => done,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+        found.push(screened);
+        if !unscreened.is_empty() {
+            found.push(screen_pairs(plan.objects.clone(), unscreened, start, options).await);
+        }
+    }
+    let plan = Arc::try_unwrap(plan).unwrap_or_else(|plan| (*plan).clone());
+    plan.finish(found, options)
+}
+
+/// Screen `pairs` of the plan on a peer, returning what it found and the
+/// pairs it left unscreened by failing
+async fn screen_on_peer(
+    state: &AppState,
+    peer_id: &str,
+    transport: &HttpTransport,
+    plan: &ScreeningPlan,
+    pairs: Vec<(usize, usize)>,
+    options: &ScreeningOptions,
+) -> (PairScreening, Vec<(usize, usize)>) {
+    let mut screened = PairScreening::default();
+    let mut done = 0;
+    for batch in request_batches(&pairs) {
+        match request_screening(state, peer_id, transport, plan, batch, options).await {
+            Ok(response) => {
+                screened.approaches.extend(response.approaches);
+                screened.skipped.extend(response.skipped);
+                done += batch.len();
+            }
+            Err(e) => {
+                let rest = pairs[done..].to_vec();
+                warn!("Screening {} pairs here instead of on {}: {}", rest.len(), peer_id, e);
+                return (screened, rest);
+            }
+        }
+    }
+    (screened, Vec::new())
+}
+
+/// Consecutive runs of `pairs` within the pair and object limits of one
+/// SCREENING_REQUEST
+fn request_batches(pairs: &[(usize, usize)]) -> Vec<&[(usize, usize)]> {
+    let mut batches = Vec::new();
+    let mut from = 0;
+    let mut objects = HashSet::new();
+    for (n, &(i, j)) in pairs.iter().enumerate() {
+        let added = [i, j].iter().filter(|index| !objects.contains(*index)).count();
+        if n - from == MAX_SCREENING_PAIRS || objects.len() + added > MAX_SCREENING_OBJECTS {
+            batches.push(&pairs[from..n]);
+            from = n;
+            objects.clear();
+        }
+        objects.extend([i, j]);
+    }
+    if from < pairs.len() {
+        batches.push(&pairs[from..]);
+    }
+    batches
+}
+
+/// Send one SCREENING_REQUEST for `pairs` of the plan and check the answer
+async fn request_screening(
+    state: &AppState,
+    peer_id: &str,
+    transport: &HttpTransport,
+    plan: &ScreeningPlan,
+    pairs: &[(usize, usize)],
+    options: &ScreeningOptions,
+) -> Result<ScreeningResponsePayload> {
+    // Only the objects the pairs involve, renumbered in catalog order
+    let mut renumbered: BTreeMap<usize, usize> = pairs.iter().flat_map(|&(i, j)| [(i, 0), (j, 0)]).collect();
+    for (n, index) in renumbered.values_mut().enumerate() {
+        *index = n;
+    }
+    let request = ScreeningRequestPayload {
+        request_id: uuid::Uuid::new_v4().to_string(),
+        start: plan.start,
+        window_seconds: options.window.num_seconds().max(1) as u64,
+        step_seconds: options.step_seconds,
+        threshold_km: options.threshold_km,
+        model: options.model,
+        objects: renumbered.keys().map(|&i| plan.objects[i].clone()).collect(),
+        pairs: pairs.iter().map(|(i, j)| (renumbered[i], renumbered[j])).collect(),
+    };
+    let envelope = Envelope::new(
+        state.config.get().node.id.clone(),
+        MessageType::ScreeningRequest,
+        serde_json::to_value(&request)?,
+    );
+    state.peers.write().await.record_sent(peer_id, &MessageType::ScreeningRequest);
+    let reply = transport
+        .send(&envelope)
+        .await?
+        .ok_or_else(|| Error::Peer(format!("{} did not answer SCREENING_REQUEST", peer_id)))?;
+    if reply.message_type != MessageType::ScreeningResponse {
+        return Err(Error::Peer(format!("{} answered SCREENING_REQUEST with {}", peer_id, reply.message_type)));
+    }
+    state.peers.write().await.record_received(peer_id, &MessageType::ScreeningResponse);
+    let response: ScreeningResponsePayload = reply.payload.parse()?;
+    if response.request_id != request.request_id {
+        return Err(Error::Peer(format!(
+            "{} answered request {} instead of {}",
+            peer_id, response.request_id, request.request_id
+        )));
+    }
+    Ok(response)
+}
+
+/// Answer a peer's SCREENING_REQUEST with a SCREENING_RESPONSE
+pub(crate) async fn answer_screening_request(state: &AppState, envelope: &Envelope, sender: &str) -> Result<Envelope> {
+    let serves = state.peers.read().await.get_peer(sender).is_some_and(|peer| peer.policies.serve_screening);
+    if !serves {
+        return Err(Error::Unauthorized(format!("screening not served to {}", sender)));
+    }
+    let request: ScreeningRequestPayload = envelope.payload.parse()?;
+    request.check()?;
+
+    let config = state.config.get();
+    let defaults = ScreeningOptions::default();
+    let options = ScreeningOptions {
+        threshold_km: request.threshold_km,
+        window: chrono::Duration::seconds(request.window_seconds as i64),
+        step_seconds: request.step_seconds,
+        workers: config.screening.as_ref().map(|s| s.workers).filter(|&w| w > 0).unwrap_or(defaults.workers),
+        model: request.model,
+        ..defaults
+    };
+    let started = Instant::now();
+    let pairs = request.pairs.len();
+    let screened = screen_pairs(request.objects, request.pairs, request.start, &options).await;
+    debug!(
+        "Screened {} pairs for {} and found {} close approaches in {:?}",
+        pairs,
+        sender,
+        screened.approaches.len(),
+        started.elapsed()
+    );
+    let response = ScreeningResponsePayload {
+        request_id: request.request_id,
+        approaches: screened.approaches,
+        skipped: screened.skipped,
+    };
+    Ok(Envelope::new(
+        config.node.id.clone(),
+        MessageType::ScreeningResponse,
+        serde_json::to_value(response)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PeerConfig;
+    use crate::node::server::receive_message;
+    use crate::node::server::tests::test_state;
+    use crate::node::{PeerInfo, PeerStatus, PROTOCOL_ENDPOINT};
+    use crate::orbit::{propagate, PropagationModel, EARTH_MU_KM3_S2};
+    use crate::protocol::{HelloPayload, ObjectType, StateVector};
+    use chrono::Duration;
+
+    fn peer(id: &str, address: &str) -> PeerInfo {
+        let config: PeerConfig = serde_yaml::from_str(&format!("id: {}\naddress: {}", id, address)).unwrap();
+        PeerInfo::from_config(&config)
+    }
+
+    /// Object on a circular orbit of `radius_km` passing over +x at `tca`,
+    /// with its state given at `start`
+    fn crossing(id: &str, radius_km: f64, inclination_deg: f64, tca: DateTime<Utc>, start: DateTime<Utc>) -> ObjectRecord {
+        let v = (EARTH_MU_KM3_S2 / radius_km).sqrt();
+        let (s, c) = inclination_deg.to_radians().sin_cos();
+        let at_tca = StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(tca),
+            x_km: radius_km,
+            y_km: 0.0,
+            z_km: 0.0,
+            vx_km_s: 0.0,
+            vy_km_s: v * c,
+            vz_km_s: v * s,
+        };
+        let state_vector = propagate(&at_tca, tca, start, PropagationModel::J2).unwrap();
+        ObjectRecord {
+            object_id: id.to_string(),
+            object_name: id.to_string(),
+            object_type: ObjectType::Payload,
+            owner_operator: None,
+            rcs_size: None,
+            epoch: start,
+            state_vector,
+            covariance: None,
+            source_node: "node-a".to_string(),
+            last_updated: Utc::now(),
+            organization: None,
+        }
+    }
+
+    #[test]
+    fn test_request_batches() {
+        // Every pair of 150 objects: split on the pair limit
+        let dense: Vec<(usize, usize)> = (0..150).flat_map(|i| (i + 1..150).map(move |j| (i, j))).collect();
+        let batches = request_batches(&dense);
+        assert_eq!(batches.iter().map(|b| b.len()).collect::<Vec<_>>(), [MAX_SCREENING_PAIRS, 1175]);
+
+        // Disjoint pairs: split on the object limit
+        let disjoint: Vec<(usize, usize)> = (0..MAX_SCREENING_OBJECTS).map(|n| (2 * n, 2 * n + 1)).collect();
+        let batches = request_batches(&disjoint);
+        assert_eq!(batches.iter().map(|b| b.len()).collect::<Vec<_>>(), [MAX_SCREENING_OBJECTS / 2; 2]);
+        assert!(request_batches(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_screen_shared_with_peer() {
+        let remote = test_state("node-b");
+        remote.peers.write().await.add_peer(peer("node-a", "http://127.0.0.1:1"));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new()
+            .route(PROTOCOL_ENDPOINT, axum::routing::post(receive_message))
+            .with_state(remote.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let local = test_state("node-a");
+        {
+            let mut peers = local.peers.write().await;
+            peers.add_peer(peer("node-b", &address));
+            peers.set_peer_status("node-b", PeerStatus::Connected);
+            peers.record_handshake("node-b", "1.1".into(), HelloPayload::default().capabilities);
+        }
+
+        // Two crossings at altitudes far apart, so in separate shards
+        let start = Utc::now();
+        let (low_tca, high_tca) = (start + Duration::seconds(1000), start + Duration::seconds(2000));
+        let objects = vec![
+            crossing("LOW-EQ", 7000.5, 0.0, low_tca, start),
+            crossing("LOW-POLAR", 7000.0, 90.0, low_tca, start),
+            crossing("HIGH-EQ", 7800.3, 0.0, high_tca, start),
+            crossing("HIGH-POLAR", 7800.0, 90.0, high_tca, start),
+        ];
+        let options = ScreeningOptions {
+            threshold_km: 2.0,
+            window: Duration::hours(1),
+            workers: 2,
+            ..Default::default()
+        };
+        let workers = ["node-b".to_string()];
+        let alone = screen_shared(&local, &[], &objects, start, &options).await;
+        let screening = screen_shared(&local, &workers, &objects, start, &options).await;
+        assert_eq!((screening.shards, screening.pairs, screening.objects), (2, 2, 4));
+        assert_eq!(screening.approaches.len(), 2, "{:?}", screening.approaches);
+        for (shared, own) in screening.approaches.iter().zip(&alone.approaches) {
+            assert_eq!((&shared.object1_id, &shared.object2_id), (&own.object1_id, &own.object2_id));
+            assert!((shared.tca - own.tca).num_milliseconds().abs() <= 1);
+            assert!((shared.miss_distance_km - own.miss_distance_km).abs() < 1e-6);
+        }
+        // One shard was screened on node B
+        assert_eq!(remote.peers.read().await.get_peer("node-a").unwrap().messages_received, 1);
+
+        // B screens only for peers it serves; its part falls back here
+        remote.peers.write().await.get_peer_mut("node-a").unwrap().policies.serve_screening = false;
+        let plan = ScreeningPlan::new(&objects, start, &options);
+        let transport = reply_transport(&local, "node-b", CAPABILITY_SCREENING).await.unwrap();
+        let refused = request_screening(&local, "node-b", &transport, &plan, &plan.shards[0], &options).await;
+        assert!(matches!(refused, Err(Error::PeerRefused(_))), "{:?}", refused.map(|r| r.approaches));
+        let screening = screen_shared(&local, &workers, &objects, start, &options).await;
+        assert_eq!(screening.approaches.len(), 2);
+    }
+}
@@ -0,0 +1,748 @@
+//! Scripted peer simulation
+//!
+//! `spacecomms simulate-peer` plays the part of a remote node against a
+//! target: it performs HELLO, sends a scripted sequence of announcements,
+//! withdrawals and heartbeats with optional fault injection, and checks each
+//! response against the scenario's expectations. Envelopes are built from the
+//! same protocol types the node uses, so scenarios stay in step with the
+//! implementation.
+
+use crate::cdm::generate_synthetic_cdm;
+use crate::node::{NODE_ID_HEADER, PROTOCOL_ENDPOINT};
+use crate::protocol::{
+    negotiate_version, CdmWithdrawPayload, CdmWithdrawReason, Encoding, Envelope, ErrorCode, ErrorPayload,
+    HeartbeatPayload, HelloPayload, MessageType, ObjectStateAnnouncePayload, ObjectType, StateVector,
+    TimestampFormat, VersionNegotiationResult,
+};
+use crate::{Error, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// A scripted conversation with a target node
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    /// Node ID the simulated peer presents
+    pub node_id: String,
+
+    /// Node name sent in HELLO
+    #[serde(default = "default_node_name")]
+    pub node_name: String,
+
+    /// Bearer token sent with every request
+    #[serde(default)]
+    pub auth_token: Option<String>,
+
+    /// Envelope encoding for requests
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// Timestamp profile for outbound envelopes
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+
+    /// Stop at the first step that fails its expectations
+    #[serde(default)]
+    pub stop_on_failure: bool,
+
+    pub steps: Vec<Step>,
+}
+
+fn default_node_name() -> String {
+    "SpaceComms Peer Simulator".to_string()
+}
+
+impl Scenario {
+    /// Load a scenario from a YAML file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_yaml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse and validate a scenario
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let scenario: Scenario = serde_yaml::from_str(yaml)?;
+        if scenario.node_id.is_empty() {
+            return Err(Error::Config("scenario node_id must not be empty".into()));
+        }
+        if scenario.steps.is_empty() {
+            return Err(Error::Config("scenario has no steps".into()));
+        }
+        if let Some(index) = scenario.steps.iter().position(|s| s.repeat == 0) {
+            return Err(Error::Config(format!("step {}: repeat must be at least 1", index + 1)));
+        }
+        Ok(scenario)
+    }
+}
+
+/// One scripted message, optionally repeated
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    /// Label used in the report (defaults to the action)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Pause before the first send
+    #[serde(default)]
+    pub delay_ms: u64,
+
+    /// Number of times to send the message
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+
+    /// Pause between repetitions
+    #[serde(default)]
+    pub interval_ms: u64,
+
+    /// What to send
+    pub send: Action,
+
+    /// Faults applied to the envelope before sending
+    #[serde(default)]
+    pub inject: Fault,
+
+    /// Expected response; a successful status when omitted
+    #[serde(default)]
+    pub expect: Option<Expectation>,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+/// Message a step sends
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// HELLO with this node's versions and capabilities unless overridden
+    Hello {
+        #[serde(default)]
+        protocol_version: Option<String>,
+        #[serde(default)]
+        supported_versions: Option<Vec<String>>,
+        #[serde(default)]
+        capabilities: Option<Vec<String>>,
+    },
+    /// HEARTBEAT with an increasing sequence number
+    Heartbeat,
+    /// CDM_ANNOUNCE of a synthetic CDM, or of `cdm` verbatim
+    CdmAnnounce {
+        #[serde(default)]
+        cdm_id: Option<String>,
+        #[serde(default = "default_collision_probability")]
+        collision_probability: f64,
+        #[serde(default = "default_tca_hours")]
+        tca_hours: f64,
+        #[serde(default = "default_miss_distance")]
+        miss_distance_m: f64,
+        #[serde(default)]
+        cdm: Option<serde_json::Value>,
+    },
+    /// CDM_WITHDRAW, of the last announced CDM unless `cdm_id` is given
+    CdmWithdraw {
+        #[serde(default)]
+        cdm_id: Option<String>,
+        #[serde(default = "default_withdraw_reason")]
+        reason: CdmWithdrawReason,
+        #[serde(default)]
+        superseded_by: Option<String>,
+    },
+    /// OBJECT_STATE_ANNOUNCE with a nominal LEO state vector
+    ObjectAnnounce {
+        object_id: String,
+        #[serde(default)]
+        object_name: Option<String>,
+        #[serde(default = "default_object_type")]
+        object_type: ObjectType,
+    },
+    /// Any message type with a literal payload
+    Raw {
+        message_type: MessageType,
+        payload: serde_json::Value,
+    },
+}
+
+fn default_collision_probability() -> f64 {
+    1.0e-4
+}
+
+fn default_tca_hours() -> f64 {
+    24.0
+}
+
+fn default_miss_distance() -> f64 {
+    150.0
+}
+
+fn default_withdraw_reason() -> CdmWithdrawReason {
+    CdmWithdrawReason::TcaPassed
+}
+
+fn default_object_type() -> ObjectType {
+    ObjectType::Payload
+}
+
+impl Action {
+    fn label(&self) -> &'static str {
+        match self {
+            Action::Hello { .. } => "hello",
+            Action::Heartbeat => "heartbeat",
+            Action::CdmAnnounce { .. } => "cdm_announce",
+            Action::CdmWithdraw { .. } => "cdm_withdraw",
+            Action::ObjectAnnounce { .. } => "object_announce",
+            Action::Raw { .. } => "raw",
+        }
+    }
+}
+
+/// Deliberate protocol violations
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Fault {
+    /// Envelope protocol version to claim
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+
+    /// Envelope source node to claim
+    #[serde(default)]
+    pub source_node_id: Option<String>,
+
+    #[serde(default)]
+    pub hop_count: Option<u32>,
+
+    #[serde(default)]
+    pub ttl: Option<u32>,
+
+    /// Reuse the previous envelope's message ID
+    #[serde(default)]
+    pub replay: bool,
+
+    /// Top-level payload fields to delete
+    #[serde(default)]
+    pub remove_fields: Vec<String>,
+
+    /// Pad the payload with this many bytes of filler
+    #[serde(default)]
+    pub pad_bytes: Option<usize>,
+
+    /// Send only the first half of the encoded envelope
+    #[serde(default)]
+    pub truncate: bool,
+
+    /// Content-Type header to send instead of the encoding's
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+/// Expected response to a step
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Expectation {
+    /// HTTP status code
+    #[serde(default)]
+    pub status: Option<u16>,
+
+    /// Message type of the reply envelope
+    #[serde(default)]
+    pub reply: Option<MessageType>,
+
+    /// Error code of an ERROR reply
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Outcome of one sent message
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    /// 1-based step number in the scenario
+    pub step: usize,
+    pub name: String,
+    pub message_type: MessageType,
+    pub message_id: String,
+    /// HTTP status, if the request completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<MessageType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    pub elapsed_ms: u64,
+    /// Unmet expectations; empty if the step passed
+    pub failures: Vec<String>,
+}
+
+impl StepResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Outcome of a scenario run
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    pub target: String,
+    /// Node ID from the target's HELLO reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_node_id: Option<String>,
+    pub passed: usize,
+    pub failed: usize,
+    pub steps: Vec<StepResult>,
+}
+
+impl SimulationReport {
+    /// Whether every step met its expectations
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Response to one request, as far as it got
+#[derive(Default)]
+struct Response {
+    status: Option<u16>,
+    reply: Option<Envelope>,
+    transport_error: Option<String>,
+}
+
+/// Runs a scenario against a target node
+pub struct PeerSimulator {
+    client: reqwest::Client,
+    target: String,
+    endpoint: String,
+    scenario: Scenario,
+    heartbeat_sequence: u64,
+    last_message_id: Option<String>,
+    last_cdm_id: Option<String>,
+    remote_node_id: Option<String>,
+}
+
+impl PeerSimulator {
+    /// Create a simulator for a target node's base address
+    pub fn new(target: &str, scenario: Scenario) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            target: target.to_string(),
+            endpoint: format!("{}{}", target.trim_end_matches('/'), PROTOCOL_ENDPOINT),
+            scenario,
+            heartbeat_sequence: 0,
+            last_message_id: None,
+            last_cdm_id: None,
+            remote_node_id: None,
+        }
+    }
+
+    /// Run every step and report the outcome
+    pub async fn run(mut self) -> SimulationReport {
+        let mut results = Vec::new();
+        let steps = std::mem::take(&mut self.scenario.steps);
+
+        'steps: for (index, step) in steps.iter().enumerate() {
+            tokio::time::sleep(Duration::from_millis(step.delay_ms)).await;
+            for attempt in 0..step.repeat {
+                if attempt > 0 {
+                    tokio::time::sleep(Duration::from_millis(step.interval_ms)).await;
+                }
+                let mut name = step.name.clone().unwrap_or_else(|| step.send.label().to_string());
+                if step.repeat > 1 {
+                    name = format!("{} #{}", name, attempt + 1);
+                }
+
+                let result = self.run_step(index + 1, name, step).await;
+                if result.passed() {
+                    info!("Step {} {}: passed", result.step, result.name);
+                } else {
+                    warn!("Step {} {}: {}", result.step, result.name, result.failures.join("; "));
+                }
+                let failed = !result.passed();
+                results.push(result);
+                if failed && self.scenario.stop_on_failure {
+                    break 'steps;
+                }
+            }
+        }
+
+        let passed = results.iter().filter(|r| r.passed()).count();
+        SimulationReport {
+            target: self.target,
+            remote_node_id: self.remote_node_id,
+            passed,
+            failed: results.len() - passed,
+            steps: results,
+        }
+    }
+
+    async fn run_step(&mut self, number: usize, name: String, step: &Step) -> StepResult {
+        let started = Instant::now();
+        let mut result = StepResult {
+            step: number,
+            name,
+            message_type: MessageType::Error,
+            message_id: String::new(),
+            status: None,
+            reply: None,
+            error_code: None,
+            error_message: None,
+            elapsed_ms: 0,
+            failures: Vec::new(),
+        };
+
+        let envelope = match self.build(&step.send) {
+            Ok(envelope) => self.inject(envelope, &step.inject),
+            Err(e) => {
+                result.failures.push(format!("could not build message: {}", e));
+                return result;
+            }
+        };
+        result.message_type = envelope.message_type.clone();
+        result.message_id = envelope.message_id.clone();
+        self.last_message_id = Some(envelope.message_id.clone());
+
+        let response = self.send(&envelope, &step.inject).await;
+        result.elapsed_ms = started.elapsed().as_millis() as u64;
+        result.status = response.status;
+        if let Some(error) = response.transport_error {
+            result.failures.push(error);
+            return result;
+        }
+
+        if let Some(reply) = &response.reply {
+            result.reply = Some(reply.message_type.clone());
+            match reply.message_type {
+                MessageType::Error => {
+                    if let Ok(error) = reply.payload.parse::<ErrorPayload>() {
+                        result.error_code = Some(error.error_code);
+                        result.error_message = Some(error.error_message);
+                    }
+                }
+                MessageType::Hello => self.check_hello(reply, &step.send, &mut result.failures),
+                _ => {}
+            }
+        }
+
+        check_expectation(step, &mut result);
+        result
+    }
+
+    /// Build the envelope for an action
+    fn build(&mut self, action: &Action) -> Result<Envelope> {
+        let node_id = self.scenario.node_id.clone();
+        let (message_type, payload) = match action {
+            Action::Hello { .. } => (MessageType::Hello, serde_json::to_value(self.local_hello(action))?),
+            Action::Heartbeat => {
+                self.heartbeat_sequence += 1;
+                let heartbeat = HeartbeatPayload {
+                    sequence: self.heartbeat_sequence,
+                    objects_tracked: None,
+                    cdms_active: None,
+                    credit_window: None,
+                    echo: None,
+                };
+                (MessageType::Heartbeat, serde_json::to_value(heartbeat)?)
+            }
+            Action::CdmAnnounce {
+                cdm_id,
+                collision_probability,
+                tca_hours,
+                miss_distance_m,
+                cdm,
+            } => {
+                let mut payload = match cdm {
+                    Some(cdm) => cdm.clone(),
+                    None => {
+                        let tca = Utc::now() + ChronoDuration::seconds((tca_hours * 3600.0) as i64);
+                        let mut cdm = generate_synthetic_cdm(
+                            "NORAD-SIM-1",
+                            "SIM-PAYLOAD",
+                            "NORAD-SIM-2",
+                            "SIM-DEBRIS",
+                            tca,
+                            *miss_distance_m,
+                            *collision_probability,
+                        );
+                        cdm.originator = node_id.clone();
+                        serde_json::to_value(cdm)?
+                    }
+                };
+                if let (Some(id), Some(fields)) = (cdm_id, payload.as_object_mut()) {
+                    fields.insert("cdm_id".into(), id.clone().into());
+                }
+                self.last_cdm_id = payload.get("cdm_id").and_then(|v| v.as_str()).map(str::to_string);
+                (MessageType::CdmAnnounce, payload)
+            }
+            Action::CdmWithdraw {
+                cdm_id,
+                reason,
+                superseded_by,
+            } => {
+                let Some(cdm_id) = cdm_id.clone().or_else(|| self.last_cdm_id.clone()) else {
+                    return Err(Error::Config("cdm_withdraw needs a cdm_id when no CDM was announced".into()));
+                };
+                let withdraw = CdmWithdrawPayload {
+                    cdm_id,
+                    reason: reason.clone(),
+                    superseded_by: superseded_by.clone(),
+                    effective_time: Utc::now(),
+                };
+                (MessageType::CdmWithdraw, serde_json::to_value(withdraw)?)
+            }
+            Action::ObjectAnnounce {
+                object_id,
+                object_name,
+                object_type,
+            } => {
+                let now = Utc::now();
+                let object = ObjectStateAnnouncePayload {
+                    object_id: object_id.clone(),
+                    object_name: object_name.clone().unwrap_or_else(|| object_id.clone()),
+                    object_type: object_type.clone(),
+                    owner_operator: Some(node_id.clone()),
+                    epoch: now,
+                    state_vector: StateVector {
+                        reference_frame: "TEME".to_string(),
+                        epoch: Some(now),
+                        x_km: 6778.0,
+                        y_km: 0.0,
+                        z_km: 0.0,
+                        vx_km_s: 0.0,
+                        vy_km_s: 7.67,
+                        vz_km_s: 0.0,
+                    },
+                    covariance: None,
+                    metadata: Default::default(),
+                };
+                (MessageType::ObjectStateAnnounce, serde_json::to_value(object)?)
+            }
+            Action::Raw { message_type, payload } => (message_type.clone(), payload.clone()),
+        };
+        Ok(Envelope::new(node_id, message_type, payload))
+    }
+
+    fn local_hello(&self, action: &Action) -> HelloPayload {
+        let mut hello = HelloPayload {
+            node_name: self.scenario.node_name.clone(),
+            auth_token: self.scenario.auth_token.clone(),
+            ..Default::default()
+        };
+        if let Action::Hello {
+            protocol_version,
+            supported_versions,
+            capabilities,
+        } = action
+        {
+            if let Some(version) = protocol_version {
+                hello.protocol_version = version.clone();
+            }
+            if let Some(versions) = supported_versions {
+                hello.supported_versions = versions.clone();
+            }
+            if let Some(capabilities) = capabilities {
+                hello.capabilities = capabilities.clone();
+            }
+        }
+        hello
+    }
+
+    /// Apply a step's faults to an envelope
+    fn inject(&self, mut envelope: Envelope, fault: &Fault) -> Envelope {
+        if let Some(version) = &fault.protocol_version {
+            envelope.protocol_version = version.clone();
+        }
+        if let Some(source) = &fault.source_node_id {
+            envelope.source_node_id = source.clone();
+        }
+        if let Some(hop_count) = fault.hop_count {
+            envelope.hop_count = hop_count;
+        }
+        if let Some(ttl) = fault.ttl {
+            envelope.ttl = ttl;
+        }
+        if fault.replay {
+            if let Some(previous) = &self.last_message_id {
+                envelope.message_id = previous.clone();
+            }
+        }
+        if let Some(fields) = envelope.payload.make_mut().as_object_mut() {
+            for field in &fault.remove_fields {
+                fields.remove(field);
+            }
+            if let Some(pad) = fault.pad_bytes {
+                fields.insert("padding".into(), "x".repeat(pad).into());
+            }
+        }
+        envelope
+    }
+
+    async fn send(&self, envelope: &Envelope, fault: &Fault) -> Response {
+        let encoding = self.scenario.encoding;
+        let mut body = match envelope.encode_with(encoding, self.scenario.timestamp_format) {
+            Ok(body) => body,
+            Err(e) => {
+                return Response {
+                    transport_error: Some(format!("could not encode envelope: {}", e)),
+                    ..Default::default()
+                }
+            }
+        };
+        if fault.truncate {
+            body.truncate(body.len() / 2);
+        }
+
+        let content_type = fault.content_type.as_deref().unwrap_or(encoding.content_type());
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(NODE_ID_HEADER, &self.scenario.node_id)
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+        if let Some(token) = &self.scenario.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let resp = match request.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                return Response {
+                    transport_error: Some(format!("request failed: {}", e)),
+                    ..Default::default()
+                }
+            }
+        };
+        let status = resp.status().as_u16();
+        let encoding = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_content_type)
+            .unwrap_or(Encoding::Json);
+        let body = resp.bytes().await.unwrap_or_default();
+        Response {
+            status: Some(status),
+            reply: (!body.is_empty()).then(|| Envelope::decode(&body, encoding).ok()).flatten(),
+            transport_error: None,
+        }
+    }
+
+    /// Check the target's HELLO reply against what this peer offered
+    fn check_hello(&mut self, reply: &Envelope, action: &Action, failures: &mut Vec<String>) {
+        self.remote_node_id = Some(reply.source_node_id.clone());
+        let remote = match reply.payload.parse::<HelloPayload>() {
+            Ok(remote) => remote,
+            Err(e) => {
+                failures.push(format!("malformed HELLO reply: {}", e));
+                return;
+            }
+        };
+        if let VersionNegotiationResult::Incompatible { reason, .. } =
+            negotiate_version(&self.local_hello(action), &remote)
+        {
+            failures.push(format!("HELLO reply is incompatible: {}", reason));
+        }
+    }
+}
+
+/// Compare a step's response with what the scenario expects
+fn check_expectation(step: &Step, result: &mut StepResult) {
+    let Some(expect) = &step.expect else {
+        if !result.status.is_some_and(|s| (200..300).contains(&s)) {
+            result.failures.push(format!(
+                "expected a successful status, got {}{}",
+                describe_status(result.status),
+                result.error_message.as_deref().map(|m| format!(" ({})", m)).unwrap_or_default()
+            ));
+        }
+        if matches!(step.send, Action::Hello { .. }) && result.reply != Some(MessageType::Hello) {
+            result.failures.push("expected a HELLO reply".to_string());
+        }
+        return;
+    };
+
+    if let Some(status) = expect.status {
+        if result.status != Some(status) {
+            result
+                .failures
+                .push(format!("expected status {}, got {}", status, describe_status(result.status)));
+        }
+    }
+    if let Some(reply) = &expect.reply {
+        if result.reply.as_ref() != Some(reply) {
+            let got = result.reply.as_ref().map_or("no reply".to_string(), |r| r.to_string());
+            result.failures.push(format!("expected a {} reply, got {}", reply, got));
+        }
+    }
+    if let Some(code) = &expect.error_code {
+        if result.error_code.as_ref() != Some(code) {
+            result
+                .failures
+                .push(format!("expected error {:?}, got {:?}", code, result.error_code));
+        }
+    }
+}
+
+fn describe_status(status: Option<u16>) -> String {
+    status.map_or("no response".to_string(), |s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+    use crate::node::receive_message;
+    use axum::routing::post;
+    use axum::Router;
+
+    async fn serve() -> String {
+        let app = Router::new()
+            .route(PROTOCOL_ENDPOINT, post(receive_message))
+            .with_state(test_state("node-a"));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        address
+    }
+
+    #[test]
+    fn test_example_scenario_parses() {
+        let scenario = Scenario::from_yaml(include_str!("../../../examples/simulate-peer.yaml")).unwrap();
+        assert!(scenario.steps.iter().any(|s| matches!(s.send, Action::Hello { .. })));
+        assert!(Scenario::from_yaml("node_id: sim\nsteps: []").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scenario_against_node() {
+        let target = serve().await;
+        let scenario = Scenario::from_yaml(
+            r#"
+node_id: partner
+steps:
+  - send: { type: hello }
+  - send: { type: heartbeat }
+    repeat: 2
+  - send: { type: cdm_announce, cdm_id: CDM-SIM-1 }
+    expect: { status: 202 }
+  - send: { type: cdm_withdraw }
+  - name: bad version
+    send: { type: hello, protocol_version: "9.0", supported_versions: ["9.0"] }
+    expect: { status: 400, reply: ERROR, error_code: UNSUPPORTED_VERSION }
+  - name: truncated
+    send: { type: heartbeat }
+    inject: { truncate: true }
+    expect: { status: 400, error_code: INVALID_MESSAGE }
+  - name: wrong expectation
+    send: { type: raw, message_type: CDM_ANNOUNCE, payload: { cdm_id: CDM-BROKEN } }
+"#,
+        )
+        .unwrap();
+
+        let report = PeerSimulator::new(&target, scenario).run().await;
+        assert_eq!(report.remote_node_id.as_deref(), Some("node-a"));
+        assert_eq!(report.steps.len(), 8);
+        assert_eq!(report.steps[3].message_type, MessageType::CdmAnnounce);
+        assert_eq!(report.steps[4].status, Some(202));
+        let failed: Vec<_> = report.steps.iter().filter(|s| !s.passed()).map(|s| s.name.as_str()).collect();
+        assert_eq!(failed, ["wrong expectation"]);
+        assert_eq!(report.steps[7].error_code, Some(ErrorCode::InvalidMessage));
+        assert!(!report.success());
+    }
+}
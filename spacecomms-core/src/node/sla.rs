@@ -0,0 +1,246 @@
+//! Service levels of each peer over rolling windows
+//!
+//! Peering agreements promise availability and timeliness, so each peer's
+//! record is kept in hourly buckets for [`SLA_RETENTION_DAYS`] days: time
+//! connected, heartbeat checks and misses, forwards with their latency, and
+//! envelopes received and rejected. Every heartbeat interval the leading
+//! instance checks each configured peer; a connected peer misses a check when
+//! nothing was heard from it for two intervals. The record is kept in memory
+//! and starts over when the node restarts or the peer is removed.
+
+use crate::node::{AppState, PeerStatus};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Days of hourly buckets kept per peer
+pub const SLA_RETENTION_DAYS: i64 = 31;
+
+const BUCKET_SECONDS: i64 = 3600;
+
+/// One hour of a peer's record
+#[derive(Debug, Clone, Default)]
+struct SlaBucket {
+    start: i64,
+    observed_seconds: u64,
+    connected_seconds: u64,
+    heartbeat_checks: u64,
+    heartbeats_missed: u64,
+    forwards: u64,
+    forward_failures: u64,
+    forward_latency_ms: u64,
+    received: u64,
+    rejected: u64,
+}
+
+/// A peer's service levels over a window
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SlaReport {
+    pub peer_id: String,
+    /// Start of the window, rounded down to the hour
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Seconds the peer was checked within the window
+    pub observed_seconds: u64,
+    pub connected_seconds: u64,
+    /// Share of the observed time connected, 0 to 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_percent: Option<f64>,
+    /// Heartbeat checks made while connected
+    pub heartbeat_checks: u64,
+    /// Checks with nothing heard from the peer for two intervals
+    pub heartbeats_missed: u64,
+    /// Envelopes forwarded to the peer, delivered or given up on
+    pub forwards: u64,
+    pub forward_failures: u64,
+    /// Mean time from the first attempt to delivery, retries included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_forward_latency_ms: Option<f64>,
+    pub messages_received: u64,
+    /// Received envelopes answered with an error
+    pub messages_rejected: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected_percent: Option<f64>,
+}
+
+/// Hourly service level buckets of every peer
+#[derive(Debug, Default)]
+pub struct SlaTracker {
+    peers: Mutex<HashMap<String, VecDeque<SlaBucket>>>,
+}
+
+impl SlaTracker {
+    /// Change the current bucket of a peer already being checked
+    fn update(&self, peer_id: &str, now: DateTime<Utc>, change: impl FnOnce(&mut SlaBucket)) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(buckets) = peers.get_mut(peer_id) else {
+            return;
+        };
+        let start = now.timestamp().div_euclid(BUCKET_SECONDS) * BUCKET_SECONDS;
+        if buckets.back().is_none_or(|bucket| bucket.start != start) {
+            buckets.push_back(SlaBucket {
+                start,
+                ..SlaBucket::default()
+            });
+        }
+        let oldest = start - SLA_RETENTION_DAYS * 24 * BUCKET_SECONDS;
+        while buckets.front().is_some_and(|bucket| bucket.start <= oldest) {
+            buckets.pop_front();
+        }
+        if let Some(bucket) = buckets.back_mut() {
+            change(bucket);
+        }
+    }
+
+    /// Record a check of every configured peer, forgetting peers no longer
+    /// configured; each entry is a peer, whether it is connected and whether
+    /// it missed a heartbeat
+    pub fn check(&self, peers: &[(String, bool, bool)], seconds: u64, now: DateTime<Utc>) {
+        {
+            let mut tracked = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+            tracked.retain(|id, _| peers.iter().any(|(peer_id, _, _)| peer_id == id));
+            for (peer_id, _, _) in peers {
+                tracked.entry(peer_id.clone()).or_default();
+            }
+        }
+        for (peer_id, connected, missed) in peers {
+            self.update(peer_id, now, |bucket| {
+                bucket.observed_seconds += seconds;
+                if *connected {
+                    bucket.connected_seconds += seconds;
+                    bucket.heartbeat_checks += 1;
+                    bucket.heartbeats_missed += u64::from(*missed);
+                }
+            });
+        }
+    }
+
+    /// Record a finished forward to a peer
+    pub fn record_forward(&self, peer_id: &str, elapsed: Duration, delivered: bool) {
+        self.update(peer_id, Utc::now(), |bucket| {
+            bucket.forwards += 1;
+            bucket.forward_failures += u64::from(!delivered);
+            bucket.forward_latency_ms += elapsed.as_millis() as u64;
+        });
+    }
+
+    /// Record an envelope received from a peer
+    pub fn record_received(&self, peer_id: &str, accepted: bool) {
+        self.update(peer_id, Utc::now(), |bucket| {
+            bucket.received += 1;
+            bucket.rejected += u64::from(!accepted);
+        });
+    }
+
+    /// A peer's service levels since `from`; None for a peer never checked
+    pub fn report(&self, peer_id: &str, from: DateTime<Utc>, now: DateTime<Utc>) -> Option<SlaReport> {
+        let peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let buckets = peers.get(peer_id)?;
+        let start = from.timestamp().div_euclid(BUCKET_SECONDS) * BUCKET_SECONDS;
+        let mut total = SlaBucket::default();
+        for bucket in buckets.iter().filter(|bucket| bucket.start >= start) {
+            total.observed_seconds += bucket.observed_seconds;
+            total.connected_seconds += bucket.connected_seconds;
+            total.heartbeat_checks += bucket.heartbeat_checks;
+            total.heartbeats_missed += bucket.heartbeats_missed;
+            total.forwards += bucket.forwards;
+            total.forward_failures += bucket.forward_failures;
+            total.forward_latency_ms += bucket.forward_latency_ms;
+            total.received += bucket.received;
+            total.rejected += bucket.rejected;
+        }
+        let share = |part: u64, whole: u64| (whole > 0).then(|| 100.0 * part as f64 / whole as f64);
+        Some(SlaReport {
+            peer_id: peer_id.to_string(),
+            from: Utc.timestamp_opt(start, 0).single().unwrap_or(from),
+            to: now,
+            observed_seconds: total.observed_seconds,
+            connected_seconds: total.connected_seconds,
+            uptime_percent: share(total.connected_seconds, total.observed_seconds),
+            heartbeat_checks: total.heartbeat_checks,
+            heartbeats_missed: total.heartbeats_missed,
+            forwards: total.forwards,
+            forward_failures: total.forward_failures,
+            mean_forward_latency_ms: (total.forwards > 0)
+                .then(|| total.forward_latency_ms as f64 / total.forwards as f64),
+            messages_received: total.received,
+            messages_rejected: total.rejected,
+            rejected_percent: share(total.rejected, total.received),
+        })
+    }
+}
+
+/// Check every peer each heartbeat interval for as long as the node runs
+pub fn spawn_sla_monitor(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut last = tokio::time::Instant::now();
+        loop {
+            let interval = state.config.get().protocol.heartbeat_interval_seconds.max(1);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let seconds = last.elapsed().as_secs();
+            last = tokio::time::Instant::now();
+            // Followers hold no peer sessions, so only the leader's view counts
+            if !state.leadership.is_leader() {
+                continue;
+            }
+            let now = Utc::now();
+            let silent_after = ChronoDuration::seconds(2 * interval as i64);
+            let checks: Vec<(String, bool, bool)> = state
+                .peers
+                .read()
+                .await
+                .list_peers()
+                .iter()
+                .map(|peer| {
+                    let connected = peer.status == PeerStatus::Connected;
+                    let missed = peer.last_heartbeat.is_none_or(|at| now - at > silent_after);
+                    (peer.id.clone(), connected, connected && missed)
+                })
+                .collect();
+            state.sla.check(&checks, seconds, now);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sla_report() {
+        let tracker = SlaTracker::default();
+        let hour = Utc::now().timestamp().div_euclid(BUCKET_SECONDS) * BUCKET_SECONDS;
+        let start = Utc.timestamp_opt(hour, 0).unwrap() - ChronoDuration::hours(3);
+        // Unchecked peers are not recorded
+        tracker.record_received("peer-a", true);
+        assert!(tracker.report("peer-a", start, start).is_none());
+
+        tracker.check(&[("peer-a".into(), true, false), ("peer-b".into(), false, false)], 30, start);
+        tracker.check(&[("peer-a".into(), true, true)], 30, start + ChronoDuration::minutes(1));
+        tracker.check(&[("peer-a".into(), false, false)], 60, start + ChronoDuration::hours(2));
+        // peer-b was dropped from the configuration
+        assert!(tracker.report("peer-b", start, start).is_none());
+        tracker.record_forward("peer-a", Duration::from_millis(100), true);
+        tracker.record_forward("peer-a", Duration::from_millis(300), false);
+        tracker.record_received("peer-a", true);
+        tracker.record_received("peer-a", false);
+
+        let now = Utc::now();
+        let report = tracker.report("peer-a", start, now).unwrap();
+        assert_eq!((report.observed_seconds, report.connected_seconds), (120, 60));
+        assert_eq!(report.uptime_percent, Some(50.0));
+        assert_eq!((report.heartbeat_checks, report.heartbeats_missed), (2, 1));
+        assert_eq!((report.forwards, report.forward_failures), (2, 1));
+        assert_eq!(report.mean_forward_latency_ms, Some(200.0));
+        assert_eq!(report.rejected_percent, Some(50.0));
+
+        // A later window leaves out the first hour
+        let later = tracker.report("peer-a", start + ChronoDuration::minutes(90), now).unwrap();
+        assert_eq!(later.from, start + ChronoDuration::hours(1));
+        assert_eq!((later.observed_seconds, later.uptime_percent), (60, Some(0.0)));
+    }
+}
@@ -0,0 +1,371 @@
+//! Portable node snapshots
+//!
+//! `GET /export` writes the node's state as an NDJSON bundle: a header line,
+//! then one line per peer, watched asset, object and active CDM, then an end
+//! line with the counts. Each object line is followed by a line per state in
+//! the object's history, maneuver links included. `POST /import` restores such a bundle, gzip
+//! compressed or not, into any node whatever its storage backend, which is
+//! how a node moves between backends or a test environment is seeded.
+//!
+//! Peers are exported as configuration without their auth tokens. Maneuver
+//! intents are relayed, not stored, so a snapshot has none.
+
+use crate::cdm::{CdmRecord, ObjectRecord};
+use crate::config::PeerConfig;
+use crate::node::import::BodyLines;
+use crate::node::{spawn_session, AppState, PeerInfo, WatchedAsset};
+use crate::storage::ObjectStateChange;
+use crate::{Error, Result};
+use axum::body::Body;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+/// Value of [`SnapshotHeader::format`]
+pub const SNAPSHOT_FORMAT: &str = "spacecomms-snapshot";
+
+/// Snapshot layout version this build writes; it also reads version 1,
+/// which has no object history
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// First line of a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub format: String,
+    pub version: u32,
+    /// Node the snapshot was taken from
+    pub node_id: String,
+    pub exported_at: DateTime<Utc>,
+}
+
+/// What a snapshot holds, or what an import restored
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotCounts {
+    pub peers: u64,
+    pub watched_assets: u64,
+    pub objects: u64,
+    /// Object history entries
+    #[serde(default)]
+    pub object_states: u64,
+    pub cdms: u64,
+}
+
+/// A state from an object's history, following the object's line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectStateRecord {
+    pub object_id: String,
+    #[serde(flatten)]
+    pub state: ObjectStateChange,
+}
+
+/// One line of a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SnapshotRecord {
+    Header(SnapshotHeader),
+    Peer(Box<PeerConfig>),
+    WatchedAsset(WatchedAsset),
+    Object(Box<ObjectRecord>),
+    ObjectState(Box<ObjectStateRecord>),
+    Cdm(Box<CdmRecord>),
+    /// Last line; a snapshot without it was cut short
+    End(SnapshotCounts),
+}
+
+/// Outcome of `POST /import`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotImportReport {
+    /// Node the snapshot was taken from
+    pub source_node_id: String,
+    pub exported_at: DateTime<Utc>,
+    /// Records written; peers already known and CDMs the node holds a
+    /// same or newer version of are not counted
+    pub restored: SnapshotCounts,
+    /// Records left as they were
+    pub skipped: u64,
+}
+
+/// The node's state as snapshot lines, header first
+pub async fn export_snapshot(state: &AppState) -> Result<Vec<SnapshotRecord>> {
+    let mut records = vec![SnapshotRecord::Header(SnapshotHeader {
+        format: SNAPSHOT_FORMAT.to_string(),
+        version: SNAPSHOT_VERSION,
+        node_id: state.config.get().node.id.clone(),
+        exported_at: Utc::now(),
+    })];
+    let mut counts = SnapshotCounts::default();
+    for peer in state.peers.read().await.list_peers() {
+        records.push(SnapshotRecord::Peer(Box::new(PeerConfig {
+            id: peer.id.clone(),
+            address: peer.address.clone(),
+            auth_token: None,
+            transport: peer.transport,
+            encoding: peer.encoding,
+            timestamp_format: peer.timestamp_format,
+            group: None,
+            policies: peer.policies.clone(),
+        })));
+        counts.peers += 1;
+    }
+    for asset in state.watchlist.list() {
+        records.push(SnapshotRecord::WatchedAsset(asset));
+        counts.watched_assets += 1;
+    }
+    for object in state.storage.list_objects().await? {
+        let history = state.storage.object_state_history(&object.object_id).await?;
+        let object_id = object.object_id.clone();
+        records.push(SnapshotRecord::Object(Box::new(object)));
+        counts.objects += 1;
+        for change in history {
+            records.push(SnapshotRecord::ObjectState(Box::new(ObjectStateRecord {
+                object_id: object_id.clone(),
+                state: change,
+            })));
+            counts.object_states += 1;
+        }
+    }
+    for cdm in state.storage.list_cdms().await? {
+        records.push(SnapshotRecord::Cdm(Box::new(cdm)));
+        counts.cdms += 1;
+    }
+    records.push(SnapshotRecord::End(counts));
+    Ok(records)
+}
+
+/// Restore a snapshot read from `body`
+///
+/// Records are applied as they are read, so a snapshot that turns out to be
+/// damaged part way leaves the records before the damage restored. An
+/// object's history replaces the one this node keeps once the object's last
+/// history line is read. Importing the same snapshot again is safe.
+pub async fn import_snapshot(state: &AppState, body: Body) -> Result<SnapshotImportReport> {
+    let mut lines = BodyLines::new(body);
+    let invalid = |line: u64, message: String| Error::Protocol(format!("snapshot line {}: {}", line, message));
+    let read = |line: u64, text: &[u8]| serde_json::from_slice::<SnapshotRecord>(text).map_err(|e| invalid(line, e.to_string()));
+
+    let header = match lines.next().await? {
+        Some((line, text)) => match read(line, &text)? {
+            SnapshotRecord::Header(header) => header,
+            _ => return Err(invalid(line, "expected the snapshot header".into())),
+        },
+        None => return Err(Error::Protocol("empty snapshot".into())),
+    };
+    if header.format != SNAPSHOT_FORMAT || !(1..=SNAPSHOT_VERSION).contains(&header.version) {
+        return Err(Error::Protocol(format!(
+            "unsupported snapshot {} version {} (this node reads {} version {})",
+            header.format, header.version, SNAPSHOT_FORMAT, SNAPSHOT_VERSION
+        )));
+    }
+
+    let mut report = SnapshotImportReport {
+        source_node_id: header.node_id,
+        exported_at: header.exported_at,
+        restored: SnapshotCounts::default(),
+        skipped: 0,
+    };
+    let mut seen = SnapshotCounts::default();
+    // History of the object last read, restored when its lines end
+    let mut history: Option<(String, Vec<ObjectStateChange>)> = None;
+    let expected = loop {
+        let Some((line, text)) = lines.next().await? else {
+            return Err(Error::Protocol(format!("snapshot ends at line {} without its end record", lines.line())));
+        };
+        let record = read(line, &text)?;
+        if !matches!(record, SnapshotRecord::ObjectState(_)) {
+            if let Some((object_id, states)) = history.take().filter(|(_, states)| !states.is_empty()) {
+                state.storage.restore_object_history(&object_id, states).await?;
+            }
+        }
+        let restored = match record {
+            SnapshotRecord::Header(_) => return Err(invalid(line, "second header".into())),
+            SnapshotRecord::End(counts) => break counts,
+            SnapshotRecord::Peer(peer) => {
+                seen.peers += 1;
+                let added = state.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+                if added {
+                    spawn_session(state.clone(), peer.id.clone());
+                    report.restored.peers += 1;
+                }
+                added
+            }
+            SnapshotRecord::WatchedAsset(asset) => {
+                seen.watched_assets += 1;
+                let added = state.watchlist.register(&asset.norad_id, asset.name) == Some(true);
+                if added {
+                    report.restored.watched_assets += 1;
+                }
+                added
+            }
+            SnapshotRecord::Object(object) => {
+                seen.objects += 1;
+                history = Some((object.object_id.clone(), Vec::new()));
+                state.storage.store_object(*object).await?;
+                report.restored.objects += 1;
+                true
+            }
+            SnapshotRecord::ObjectState(record) => {
+                seen.object_states += 1;
+                match &mut history {
+                    Some((object_id, states)) if *object_id == record.object_id => states.push(record.state),
+                    _ => return Err(invalid(line, format!("history of {} does not follow its object", record.object_id))),
+                }
+                report.restored.object_states += 1;
+                true
+            }
+            SnapshotRecord::Cdm(mut cdm) => {
+                seen.cdms += 1;
+                // The tag follows this node's watchlist, not the source's
+                cdm.involves_watched_asset = state.watchlist.involves(&cdm);
+                let written = state.storage.upsert_cdm_if_newer(*cdm).await?.written();
+                if written {
+                    report.restored.cdms += 1;
+                }
+                written
+            }
+        };
+        if !restored {
+            report.skipped += 1;
+        }
+    };
+    if seen != expected {
+        return Err(Error::Protocol(format!(
+            "snapshot holds {:?} but its end record lists {:?}",
+            seen, expected
+        )));
+    }
+    info!(
+        "Snapshot from {} restored: {} peers, {} watched assets, {} objects ({} states), {} CDMs ({} skipped)",
+        report.source_node_id,
+        report.restored.peers,
+        report.restored.watched_assets,
+        report.restored.objects,
+        report.restored.object_states,
+        report.restored.cdms,
+        report.skipped
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+
+    fn bundle(records: &[SnapshotRecord]) -> Body {
+        let mut ndjson = String::new();
+        for record in records {
+            ndjson.push_str(&serde_json::to_string(record).unwrap());
+            ndjson.push('\n');
+        }
+        Body::from(ndjson)
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let source = test_state("node-a");
+        let peer: PeerConfig = serde_yaml::from_str("{ id: node-x, address: 'http://127.0.0.1:9', auth_token: secret }").unwrap();
+        source.peers.write().await.add_peer(PeerInfo::from_config(&peer));
+        let mut cdm = generate_demo_cdm();
+        cdm.involves_watched_asset = true;
+        source.storage.store_cdm(cdm.clone()).await.unwrap();
+        source.watchlist.register("12345", Some("SAT-A".into()));
+        let snapshot = export_snapshot(&source).await.unwrap();
+        let SnapshotRecord::End(counts) = snapshot.last().unwrap() else {
+            panic!("snapshot without an end record");
+        };
+        assert_eq!((counts.peers, counts.watched_assets, counts.cdms), (1, 1, 1));
+        assert_eq!((counts.objects, counts.object_states), (0, 0));
+        let exported = serde_json::to_string(&snapshot).unwrap();
+        assert!(!exported.contains("secret"));
+
+        let target = test_state("node-b");
+        let report = import_snapshot(&target, bundle(&snapshot)).await.unwrap();
+        assert_eq!(report.source_node_id, "node-a");
+        assert_eq!(report.restored, SnapshotCounts { peers: 1, watched_assets: 1, cdms: 1, ..Default::default() });
+        assert_eq!(report.skipped, 0);
+        assert_eq!(target.peers.read().await.get_peer("node-x").unwrap().address, "http://127.0.0.1:9");
+        let restored = target.storage.get_cdm(&cdm.cdm_id).await.unwrap().unwrap();
+        assert_eq!(restored.creation_date, cdm.creation_date);
+        assert!(restored.involves_watched_asset);
+        assert_eq!(target.watchlist.list()[0].name.as_deref(), Some("SAT-A"));
+
+        // Restoring again changes nothing
+        let again = import_snapshot(&target, bundle(&snapshot)).await.unwrap();
+        assert_eq!((again.restored.cdms, again.skipped), (0, 3));
+
+        // A cut-off snapshot is refused once the cut is found
+        let cut = import_snapshot(&test_state("node-c"), bundle(&snapshot[..snapshot.len() - 1])).await;
+        assert!(cut.unwrap_err().to_string().contains("without its end record"));
+        let mut newer = snapshot.clone();
+        if let SnapshotRecord::Header(header) = &mut newer[0] {
+            header.version = SNAPSHOT_VERSION + 1;
+        }
+        assert!(import_snapshot(&test_state("node-c"), bundle(&newer)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_object_history() {
+        let source = test_state("node-a");
+        let object: ObjectRecord = serde_json::from_value(serde_json::json!({
+            "object_id": "SAT-1",
+            "object_name": "SAT",
+            "object_type": "PAYLOAD",
+            "epoch": "2024-01-15T12:00:00Z",
+            "state_vector": {
+                "reference_frame": "TEME",
+                "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                "vx_km_s": 0.0, "vy_km_s": 7.546, "vz_km_s": 0.0
+            },
+            "source_node": "node-b",
+            "last_updated": "2024-01-15T12:00:00Z"
+        }))
+        .unwrap();
+        source.storage.store_object(object.clone()).await.unwrap();
+        let mut maneuvered = object.clone();
+        maneuvered.epoch += chrono::Duration::hours(1);
+        maneuvered.state_vector.vy_km_s += 0.01;
+        source.storage.store_maneuvered_object(maneuvered.clone(), "MNV-1").await.unwrap();
+        let mut later = maneuvered.clone();
+        later.epoch += chrono::Duration::hours(1);
+        source.storage.store_object(later).await.unwrap();
+        let expected = source.storage.object_state_history("SAT-1").await.unwrap();
+        assert_eq!(expected.len(), 3);
+
+        let snapshot = export_snapshot(&source).await.unwrap();
+        let SnapshotRecord::End(counts) = snapshot.last().unwrap() else {
+            panic!("snapshot without an end record");
+        };
+        assert_eq!((counts.objects, counts.object_states), (1, 3));
+
+        // Versions and the maneuver link survive, and importing again keeps them
+        let target = test_state("node-b");
+        for _ in 0..2 {
+            let report = import_snapshot(&target, bundle(&snapshot)).await.unwrap();
+            assert_eq!((report.restored.objects, report.restored.object_states), (1, 3));
+            let restored = target.storage.object_state_history("SAT-1").await.unwrap();
+            assert_eq!(serde_json::to_value(&restored).unwrap(), serde_json::to_value(&expected).unwrap());
+            assert_eq!(restored[1].maneuver_id.as_deref(), Some("MNV-1"));
+        }
+
+        // Version 1 snapshots, without history, are still read
+        let mut v1: Vec<SnapshotRecord> = snapshot
+            .iter()
+            .filter(|record| !matches!(record, SnapshotRecord::ObjectState(_)))
+            .cloned()
+            .collect();
+        if let SnapshotRecord::Header(header) = &mut v1[0] {
+            header.version = 1;
+        }
+        let v1: Vec<String> = v1.iter().map(|record| serde_json::to_string(record).unwrap().replace(",\"object_states\":3", "")).collect();
+        let report = import_snapshot(&test_state("node-c"), Body::from(v1.join("\n") + "\n")).await.unwrap();
+        assert_eq!((report.restored.objects, report.restored.object_states), (1, 0));
+
+        // History lines must follow their object
+        let mut stray = snapshot.clone();
+        stray.swap(1, 2);
+        let err = import_snapshot(&test_state("node-d"), bundle(&stray)).await.unwrap_err();
+        assert!(err.to_string().contains("does not follow its object"), "{}", err);
+    }
+}
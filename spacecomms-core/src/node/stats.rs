@@ -0,0 +1,157 @@
+//! Statistics history recorder
+//!
+//! Every `stats.sample_interval_seconds` the node samples its counters and
+//! gauges into storage. Counters are turned into per-minute rates over the
+//! interval since the previous sample; gauges are taken as they are.
+
+use crate::node::AppState;
+use crate::storage::StatMetric;
+use crate::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Counter readings a rate is taken from
+#[derive(Clone, Copy)]
+struct Counters {
+    taken: Instant,
+    cdms_announced: u64,
+    messages_received: u64,
+    messages_sent: u64,
+    errors: u64,
+}
+
+/// Takes samples, remembering the last counters for rates
+#[derive(Default)]
+pub struct StatsSampler {
+    previous: Option<Counters>,
+}
+
+impl StatsSampler {
+    /// Sample every metric; rates are left out of the first sample
+    pub async fn sample(&mut self, state: &AppState) -> Vec<(StatMetric, f64)> {
+        let metrics = &state.metrics;
+        let now = Counters {
+            taken: Instant::now(),
+            cdms_announced: metrics.cdms_announced.load(Ordering::Relaxed),
+            messages_received: metrics.messages_received.load(Ordering::Relaxed),
+            messages_sent: metrics.messages_sent.load(Ordering::Relaxed),
+            errors: metrics.errors.load(Ordering::Relaxed),
+        };
+        let (connected, known) = {
+            let peers = state.peers.read().await;
+            (peers.connected_count(), peers.list_peers().len())
+        };
+        let mut samples = vec![
+            (StatMetric::CdmsActive, state.storage.cdm_count().await.unwrap_or(0) as f64),
+            (StatMetric::PeersConnected, connected as f64),
+            (StatMetric::PeersKnown, known as f64),
+            (StatMetric::QueueDepth, state.fanout.metrics().queued as f64),
+            (StatMetric::DeadLetters, state.dead_letters.metrics().held as f64),
+        ];
+        if let Some(previous) = self.previous {
+            let minutes = (now.taken - previous.taken).as_secs_f64() / 60.0;
+            if minutes > 0.0 {
+                let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / minutes;
+                samples.extend([
+                    (StatMetric::CdmIngestRate, rate(now.cdms_announced, previous.cdms_announced)),
+                    (StatMetric::MessagesReceivedRate, rate(now.messages_received, previous.messages_received)),
+                    (StatMetric::MessagesSentRate, rate(now.messages_sent, previous.messages_sent)),
+                    (StatMetric::ErrorRate, rate(now.errors, previous.errors)),
+                ]);
+            }
+        }
+        self.previous = Some(now);
+        samples
+    }
+
+    /// Sample and store, forgetting samples past the retention
+    pub async fn record(&mut self, state: &AppState) -> Result<()> {
+        let samples = self.sample(state).await;
+        let retention = ChronoDuration::hours(state.config.get().stats.retention_hours as i64);
+        let at = Utc::now();
+        state.storage.record_stats(at, &samples, at - retention).await
+    }
+}
+
+/// Parse a history range such as `90s`, `30m`, `24h` or `7d`
+pub fn parse_range(range: &str) -> Option<ChronoDuration> {
+    let range = range.trim();
+    let (count, unit) = range.split_at(range.char_indices().last()?.0);
+    let count: i64 = count.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    if count <= 0 {
+        return None;
+    }
+    count.checked_mul(seconds).and_then(ChronoDuration::try_seconds)
+}
+
+/// Record statistics for as long as the node runs
+pub fn spawn_stats_recorder(state: AppState) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut sampler = StatsSampler::default();
+        loop {
+            let interval = state.config.get().stats.sample_interval_seconds;
+            if interval == 0 {
+                // Disabled; look again after a reload might have enabled it
+                sampler = StatsSampler::default();
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+            if let Err(e) = sampler.record(&state).await {
+                warn!("Could not record statistics: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("90s").unwrap(), ChronoDuration::seconds(90));
+        assert_eq!(parse_range("24h").unwrap(), ChronoDuration::hours(24));
+        assert_eq!(parse_range("7d").unwrap(), ChronoDuration::days(7));
+        for invalid in ["", "h", "0m", "-1h", "5w", "1.5h", "5µ"] {
+            assert!(parse_range(invalid).is_none(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_history() {
+        let state = test_state("node-local");
+        let mut sampler = StatsSampler::default();
+        sampler.record(&state).await.unwrap();
+        state.metrics.cdms_announced.fetch_add(6, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sampler.record(&state).await.unwrap();
+
+        let since = Utc::now() - ChronoDuration::hours(1);
+        let active = state.storage.stats_history(StatMetric::CdmsActive, since).await.unwrap();
+        assert_eq!(active.len(), 2);
+        // No rate until there is an interval to take it over
+        let ingest = state.storage.stats_history(StatMetric::CdmIngestRate, since).await.unwrap();
+        assert_eq!(ingest.len(), 1);
+        assert!(ingest[0].value > 6.0);
+        assert!(state.storage.stats_history(StatMetric::CdmIngestRate, Utc::now()).await.unwrap().is_empty());
+
+        // Samples past the retention are dropped as new ones come in
+        let later = Utc::now() + ChronoDuration::hours(25);
+        let samples = [(StatMetric::CdmsActive, 1.0)];
+        state.storage.record_stats(later, &samples, later - ChronoDuration::hours(24)).await.unwrap();
+        let active = state.storage.stats_history(StatMetric::CdmsActive, since).await.unwrap();
+        assert_eq!(active.len(), 1);
+    }
+}
@@ -0,0 +1,331 @@
+//! Differential sync with peers
+//!
+//! Catching up by pulling every CDM a peer holds wastes bandwidth when the
+//! two nodes mostly agree. Instead, as soon as a session is up and every
+//! `protocol.sync.interval_seconds` after, a node sends each peer offering
+//! SYNC_DIGEST a two-level hash tree of its active CDMs: a root over a fixed
+//! number of bucket checksums. Equal roots end the exchange. Otherwise the
+//! peer lists its CDMs in the buckets whose checksums differ, and the node
+//! pulls with a CDM_REQUEST naming only those it lacks or holds an older
+//! version of. Pulled CDMs are stored as for any CDM query.
+//!
+//! Each side pulls on its own rounds, so CDMs held only here reach the peer
+//! when it syncs. CDMs withdrawn here and still kept in history, and CDMs
+//! held in quarantine, are not pulled again.
+
+use crate::cdm::CdmRecord;
+use crate::node::{query_peer, query_policies, reply_transport, AppState, CdmQueryReport, Transport};
+use crate::protocol::{CdmQuery, DigestEntry, Envelope, MessageType, SyncDigestPayload, CAPABILITY_SYNC_DIGEST};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Outcome of a digest exchange with a peer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SyncReport {
+    pub peer_id: String,
+    /// The peer's digest matched this node's
+    pub in_sync: bool,
+    /// CDMs the peer listed from the buckets that differ
+    pub listed: usize,
+    /// Listed CDMs missing here or held at an older version
+    pub missing: usize,
+    /// The peer had more CDMs in those buckets than it listed; the rest
+    /// follow on the next exchange
+    pub truncated: bool,
+    /// Pulling the missing CDMs, when there were any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched: Option<CdmQueryReport>,
+}
+
+/// Bucket checksums over a set of CDMs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    buckets: Vec<u64>,
+}
+
+impl Digest {
+    /// Spread `cdms` over `bucket_count` buckets
+    pub fn new<'a>(cdms: impl IntoIterator<Item = &'a CdmRecord>, bucket_count: usize) -> Self {
+        let mut buckets = vec![0u64; bucket_count.max(1)];
+        for cdm in cdms {
+            let bucket = bucket_of(&cdm.cdm_id, buckets.len());
+            buckets[bucket] ^= version_hash(&cdm.cdm_id, cdm.creation_date);
+        }
+        Self { buckets }
+    }
+
+    /// Checksum over every bucket
+    pub fn root(&self) -> u64 {
+        let bytes: Vec<u8> = self.buckets.iter().flat_map(|bucket| bucket.to_be_bytes()).collect();
+        hash(&bytes)
+    }
+
+    /// Buckets whose checksums differ from `other`'s
+    fn differing(&self, other: &[u64]) -> Vec<bool> {
+        self.buckets.iter().zip(other).map(|(ours, theirs)| ours != theirs).collect()
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let sum = digest(&SHA256, bytes);
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&sum.as_ref()[..8]);
+    u64::from_be_bytes(first)
+}
+
+fn bucket_of(cdm_id: &str, bucket_count: usize) -> usize {
+    (hash(cdm_id.as_bytes()) % bucket_count as u64) as usize
+}
+
+/// Hash of one CDM version; creation dates count to the millisecond, the
+/// finest precision every timestamp profile carries
+fn version_hash(cdm_id: &str, creation_date: DateTime<Utc>) -> u64 {
+    hash(format!("{}\n{}", cdm_id, creation_date.timestamp_millis()).as_bytes())
+}
+
+fn to_hex(checksum: u64) -> String {
+    format!("{:016x}", checksum)
+}
+
+fn from_hex(checksum: &str) -> Result<u64> {
+    u64::from_str_radix(checksum, 16).map_err(|_| Error::Protocol(format!("invalid digest checksum {:?}", checksum)))
+}
+
+/// Answer a peer's SYNC_DIGEST with this node's CDMs in the buckets that
+/// differ
+pub(crate) async fn answer_sync_digest(state: &AppState, envelope: &Envelope, sender: &str) -> Result<Envelope> {
+    query_policies(state, sender).await?;
+    let request: SyncDigestPayload = envelope.payload.parse()?;
+    if request.buckets.is_empty() {
+        return Err(Error::Protocol("SYNC_DIGEST carries no buckets".to_string()));
+    }
+    let theirs = request.buckets.iter().map(|bucket| from_hex(bucket)).collect::<Result<Vec<u64>>>()?;
+
+    let cdms = state.storage.list_cdms().await?;
+    let ours = Digest::new(&cdms, theirs.len());
+    let root = ours.root();
+    let mut entries = Vec::new();
+    if to_hex(root) != request.root {
+        let differing = ours.differing(&theirs);
+        entries = cdms
+            .iter()
+            .filter(|cdm| differing[bucket_of(&cdm.cdm_id, theirs.len())])
+            .map(|cdm| DigestEntry {
+                cdm_id: cdm.cdm_id.clone(),
+                creation_date: cdm.creation_date,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.cdm_id.cmp(&b.cdm_id));
+    }
+    let limit = state.config.get().protocol.max_query_results;
+    let truncated = entries.len() > limit;
+    entries.truncate(limit);
+    debug!("SYNC_DIGEST from {}: listing {} CDMs", sender, entries.len());
+
+    let reply = SyncDigestPayload {
+        request_id: request.request_id,
+        root: to_hex(root),
+        buckets: Vec::new(),
+        entries,
+        truncated,
+    };
+    Ok(Envelope::new(
+        state.config.get().node.id.clone(),
+        MessageType::SyncDigest,
+        serde_json::to_value(reply)?,
+    ))
+}
+
+/// Exchange digests with a connected peer and pull the CDMs this node is
+/// missing
+///
+/// Fails as [`query_peer`] does, with [`Error::Protocol`] also when the
+/// peer does not offer SYNC_DIGEST.
+pub async fn sync_with_peer(state: &AppState, peer_id: &str) -> Result<SyncReport> {
+    let transport = reply_transport(state, peer_id, CAPABILITY_SYNC_DIGEST).await?;
+    let cdms = state.storage.list_cdms().await?;
+    let digest = Digest::new(&cdms, state.config.get().protocol.sync.digest_buckets);
+    let request = SyncDigestPayload {
+        request_id: uuid::Uuid::new_v4().to_string(),
+        root: to_hex(digest.root()),
+        buckets: digest.buckets.iter().map(|bucket| to_hex(*bucket)).collect(),
+        entries: Vec::new(),
+        truncated: false,
+    };
+    let envelope = Envelope::new(
+        state.config.get().node.id.clone(),
+        MessageType::SyncDigest,
+        serde_json::to_value(&request)?,
+    );
+    state.peers.write().await.record_sent(peer_id, &MessageType::SyncDigest);
+    let reply = transport
+        .send(&envelope)
+        .await?
+        .ok_or_else(|| Error::Peer(format!("{} did not answer SYNC_DIGEST", peer_id)))?;
+    if reply.message_type != MessageType::SyncDigest {
+        return Err(Error::Peer(format!("{} answered SYNC_DIGEST with {}", peer_id, reply.message_type)));
+    }
+    state.peers.write().await.record_received(peer_id, &MessageType::SyncDigest);
+    let reply: SyncDigestPayload = reply.payload.parse()?;
+    if reply.request_id != request.request_id {
+        return Err(Error::Peer(format!(
+            "{} answered request {} instead of {}",
+            peer_id, reply.request_id, request.request_id
+        )));
+    }
+    state.metrics.sync_rounds.fetch_add(1, Ordering::Relaxed);
+
+    let mut report = SyncReport {
+        peer_id: peer_id.to_string(),
+        in_sync: reply.root == request.root,
+        listed: reply.entries.len(),
+        truncated: reply.truncated,
+        ..Default::default()
+    };
+    if report.in_sync {
+        debug!("In sync with {}", peer_id);
+        return Ok(report);
+    }
+
+    let held: HashMap<&str, i64> =
+        cdms.iter().map(|cdm| (cdm.cdm_id.as_str(), cdm.creation_date.timestamp_millis())).collect();
+    let mut skipped: HashSet<String> =
+        state.storage.list_withdrawn_cdms().await?.into_iter().map(|withdrawn| withdrawn.record.cdm_id).collect();
+    skipped.extend(state.quarantine.list().into_iter().map(|held| held.cdm.cdm_id));
+    let missing: Vec<String> = reply
+        .entries
+        .into_iter()
+        .filter(|entry| !skipped.contains(&entry.cdm_id))
+        .filter(|entry| held.get(entry.cdm_id.as_str()).is_none_or(|ours| *ours < entry.creation_date.timestamp_millis()))
+        .map(|entry| entry.cdm_id)
+        .collect();
+    report.missing = missing.len();
+    if !missing.is_empty() {
+        let query = CdmQuery {
+            cdm_ids: missing,
+            ..Default::default()
+        };
+        let fetched = query_peer(state, peer_id, query).await?;
+        state.metrics.sync_cdms_fetched.fetch_add(fetched.stored as u64, Ordering::Relaxed);
+        report.fetched = Some(fetched);
+    }
+    info!("Synced with {}: {} CDMs listed, {} missing here", peer_id, report.listed, report.missing);
+    Ok(report)
+}
+
+/// Sync with a peer in the background if it offers SYNC_DIGEST
+pub(crate) fn spawn_sync(state: &AppState, peer_id: &str) {
+    let (state, peer_id) = (state.clone(), peer_id.to_string());
+    tokio::spawn(async move {
+        let offered = state
+            .peers
+            .read()
+            .await
+            .session(&peer_id)
+            .is_some_and(|s| s.capabilities.iter().any(|c| c == CAPABILITY_SYNC_DIGEST));
+        if !offered {
+            return;
+        }
+        if let Err(e) = sync_with_peer(&state, &peer_id).await {
+            warn!("Sync with {} failed: {}", peer_id, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::config::PeerConfig;
+    use crate::node::server::receive_message;
+    use crate::node::server::tests::test_state;
+    use crate::node::{PeerInfo, PeerStatus, PROTOCOL_ENDPOINT};
+    use crate::protocol::HelloPayload;
+    use chrono::Duration;
+
+    fn peer(id: &str, address: &str) -> PeerInfo {
+        let config: PeerConfig = serde_yaml::from_str(&format!("id: {}\naddress: {}", id, address)).unwrap();
+        PeerInfo::from_config(&config)
+    }
+
+    fn cdm(id: &str) -> CdmRecord {
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = id.to_string();
+        cdm
+    }
+
+    #[test]
+    fn test_digest() {
+        let (a, b) = (cdm("CDM-A"), cdm("CDM-B"));
+        let digest = Digest::new([&a, &b], 16);
+        // Order does not matter
+        assert_eq!(digest, Digest::new([&b, &a], 16));
+        assert_ne!(digest.root(), Digest::new([&a], 16).root());
+
+        // A newer version changes only its own bucket
+        let mut newer = b.clone();
+        newer.creation_date += Duration::minutes(5);
+        let changed = Digest::new([&a, &newer], 16);
+        assert_ne!(digest.root(), changed.root());
+        let differing = digest.differing(&changed.buckets);
+        assert_eq!(differing.iter().filter(|d| **d).count(), 1);
+        assert!(differing[bucket_of("CDM-B", 16)]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_with_peer() {
+        // Node B holds three CDMs; node A holds one of them and an older
+        // version of another
+        let remote = test_state("node-b");
+        remote.peers.write().await.add_peer(peer("node-a", "http://127.0.0.1:1"));
+        let (shared, updated, new) = (cdm("CDM-SHARED"), cdm("CDM-UPDATED"), cdm("CDM-NEW"));
+        for cdm in [shared.clone(), updated.clone(), new.clone()] {
+            remote.storage.store_cdm(cdm).await.unwrap();
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new()
+            .route(PROTOCOL_ENDPOINT, axum::routing::post(receive_message))
+            .with_state(remote.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let local = test_state("node-a");
+        {
+            let mut peers = local.peers.write().await;
+            peers.add_peer(peer("node-b", &address));
+            peers.set_peer_status("node-b", PeerStatus::Connected);
+            peers.record_handshake("node-b", "1.0".into(), HelloPayload::default().capabilities);
+        }
+        let mut older = updated.clone();
+        older.creation_date -= Duration::hours(1);
+        local.storage.store_cdm(shared).await.unwrap();
+        local.storage.store_cdm(older).await.unwrap();
+
+        let report = sync_with_peer(&local, "node-b").await.unwrap();
+        assert!(!report.in_sync);
+        assert_eq!(report.missing, 2);
+        assert_eq!(report.fetched.as_ref().map(|f| f.stored), Some(2));
+        assert_eq!(local.storage.get_cdm("CDM-UPDATED").await.unwrap().unwrap().creation_date, updated.creation_date);
+        assert!(local.storage.get_cdm("CDM-NEW").await.unwrap().is_some());
+
+        let report = sync_with_peer(&local, "node-b").await.unwrap();
+        assert!(report.in_sync);
+        assert_eq!((report.listed, report.missing), (0, 0));
+
+        // A CDM withdrawn here is not pulled back
+        local.storage.withdraw_cdm("CDM-NEW").await.unwrap();
+        let report = sync_with_peer(&local, "node-b").await.unwrap();
+        assert_eq!((report.in_sync, report.missing), (false, 0));
+        assert!(local.storage.get_cdm("CDM-NEW").await.unwrap().is_none());
+
+        // B answers digests only for peers it serves CDM queries
+        remote.peers.write().await.get_peer_mut("node-a").unwrap().policies.serve_cdm_queries = false;
+        assert!(matches!(sync_with_peer(&local, "node-b").await, Err(Error::PeerRefused(_))));
+    }
+}
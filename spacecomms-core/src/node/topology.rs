@@ -0,0 +1,561 @@
+//! Network topology
+//!
+//! `GET /network/topology` maps the part of the mesh this node can see. Its
+//! own peers come from the peer table and the sessions' HELLO exchange;
+//! nodes further out come from the provenance chains of stored CDMs, each
+//! pair of consecutive hops being a link a CDM crossed. Nodes that never
+//! passed a CDM towards this node, and links CDMs did not take, are not
+//! shown.
+//!
+//! `GET /network/objects/{id}/sources` narrows the same records to one
+//! object: the nodes whose state announcements for it were stored here, the
+//! nodes that originated stored CDMs involving it and the peers they came
+//! through, and the peers whose interests cover it.
+
+use crate::config::PeerTransport;
+use crate::node::{AppState, PeerStatus, TenantScope};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/// How a node in the topology is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyNodeKind {
+    /// This node
+    Local,
+    /// A configured peer
+    Peer,
+    /// Seen only in CDM provenance
+    Remote,
+}
+
+/// A node of the mesh
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TopologyNode {
+    pub id: String,
+    pub kind: TopologyNodeKind,
+    /// Session status, for peers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<PeerStatus>,
+    /// Address the peer advertised in HELLO, or else the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Protocol version agreed with the peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+    /// Stored CDMs whose provenance starts at the node
+    pub cdms_originated: usize,
+    /// Latest time the node received a stored CDM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// What a link between two nodes is known from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyEdgeKind {
+    /// A peer session of this node
+    Session,
+    /// Consecutive hops in stored CDM provenance
+    Path,
+}
+
+/// A link between two nodes; path links point the way CDMs travelled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TopologyEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: TopologyEdgeKind,
+    /// Session status, for session links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<PeerStatus>,
+    /// Session transport, for session links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<PeerTransport>,
+    /// Stored CDMs that crossed the link, for path links
+    #[serde(default)]
+    pub cdms: usize,
+    /// Latest time a stored CDM crossed the link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// The mesh as seen from one node
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Topology {
+    pub node_id: String,
+    pub generated_at: DateTime<Utc>,
+    /// This node first, then peers and remote nodes by ID
+    pub nodes: Vec<TopologyNode>,
+    /// Session links, then path links, by endpoints
+    pub edges: Vec<TopologyEdge>,
+}
+
+impl Topology {
+    /// Graphviz DOT rendering; sessions are bold and undirected, paths are
+    /// arrows labelled with their CDM count, and remote nodes are dashed
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph spacecomms {\n    rankdir=LR;\n");
+        for node in &self.nodes {
+            let (shape, style) = match node.kind {
+                TopologyNodeKind::Local => ("doublecircle", "solid"),
+                TopologyNodeKind::Peer => ("box", "solid"),
+                TopologyNodeKind::Remote => ("ellipse", "dashed"),
+            };
+            let label = match &node.status {
+                Some(status) => format!("{}\\n{}", quote(&node.id), status_name(status)),
+                None => quote(&node.id),
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\", shape={}, style={}];",
+                quote(&node.id),
+                label,
+                shape,
+                style
+            );
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                TopologyEdgeKind::Session => {
+                    let style = if edge.status == Some(PeerStatus::Connected) { "bold" } else { "dotted" };
+                    format!("dir=none, style={}", style)
+                }
+                TopologyEdgeKind::Path => format!("label=\"{} CDM{}\"", edge.cdms, if edge.cdms == 1 { "" } else { "s" }),
+            };
+            let _ = writeln!(dot, "    \"{}\" -> \"{}\" [{}];", quote(&edge.from), quote(&edge.to), attributes);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escape a DOT quoted string
+fn quote(id: &str) -> String {
+    id.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn status_name(status: &PeerStatus) -> &'static str {
+    match status {
+        PeerStatus::Connected => "connected",
+        PeerStatus::Connecting => "connecting",
+        PeerStatus::Disconnected => "disconnected",
+        PeerStatus::AdminDown => "admin_down",
+        PeerStatus::Blocked => "blocked",
+    }
+}
+
+/// Map the mesh from the peer table and stored CDM provenance
+pub async fn build_topology(state: &AppState) -> Result<Topology> {
+    let node_id = state.config.get().node.id.clone();
+    let mut nodes = BTreeMap::new();
+    let mut edges = Vec::new();
+    {
+        let peers = state.peers.read().await;
+        for peer in peers.list_peers() {
+            let session = peers.session(&peer.id).unwrap_or_default();
+            nodes.insert(
+                peer.id.clone(),
+                TopologyNode {
+                    id: peer.id.clone(),
+                    kind: TopologyNodeKind::Peer,
+                    status: Some(peer.status.clone()),
+                    address: Some(session.advertised_address.unwrap_or_else(|| peer.address.clone())),
+                    protocol_version: session.protocol_version,
+                    cdms_originated: 0,
+                    last_seen: None,
+                },
+            );
+            edges.push(TopologyEdge {
+                from: node_id.clone(),
+                to: peer.id.clone(),
+                kind: TopologyEdgeKind::Session,
+                status: Some(peer.status.clone()),
+                transport: Some(peer.transport),
+                cdms: 0,
+                last_seen: None,
+            });
+        }
+    }
+    edges.sort_by(|a, b| a.to.cmp(&b.to));
+
+    let mut paths: BTreeMap<(String, String), TopologyEdge> = BTreeMap::new();
+    let (mut local_originated, mut local_seen) = (0, None);
+    for cdm in state.storage.list_cdms().await? {
+        let Some(chain) = state.storage.get_cdm_provenance(&cdm.cdm_id).await? else {
+            continue;
+        };
+        for (i, hop) in chain.iter().enumerate() {
+            let seen = if hop.node_id == node_id {
+                if i == 0 {
+                    local_originated += 1;
+                }
+                &mut local_seen
+            } else {
+                let node = nodes.entry(hop.node_id.clone()).or_insert_with(|| TopologyNode {
+                    id: hop.node_id.clone(),
+                    kind: TopologyNodeKind::Remote,
+                    status: None,
+                    address: None,
+                    protocol_version: None,
+                    cdms_originated: 0,
+                    last_seen: None,
+                });
+                if i == 0 {
+                    node.cdms_originated += 1;
+                }
+                &mut node.last_seen
+            };
+            *seen = (*seen).max(Some(hop.received_at));
+            if i > 0 {
+                let from = chain[i - 1].node_id.clone();
+                let edge = paths.entry((from.clone(), hop.node_id.clone())).or_insert_with(|| TopologyEdge {
+                    from,
+                    to: hop.node_id.clone(),
+                    kind: TopologyEdgeKind::Path,
+                    status: None,
+                    transport: None,
+                    cdms: 0,
+                    last_seen: None,
+                });
+                edge.cdms += 1;
+                edge.last_seen = edge.last_seen.max(Some(hop.received_at));
+            }
+        }
+    }
+    edges.extend(paths.into_values());
+
+    let mut topology_nodes = vec![TopologyNode {
+        id: node_id.clone(),
+        kind: TopologyNodeKind::Local,
+        status: None,
+        address: None,
+        protocol_version: None,
+        cdms_originated: local_originated,
+        last_seen: local_seen,
+    }];
+    topology_nodes.extend(nodes.into_values());
+    Ok(Topology {
+        node_id,
+        generated_at: Utc::now(),
+        nodes: topology_nodes,
+        edges,
+    })
+}
+
+/// A node announcing an object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ObjectSource {
+    pub node_id: String,
+    pub kind: TopologyNodeKind,
+    /// Session status, for peers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<PeerStatus>,
+    /// Source of the object state stored now
+    pub current: bool,
+    /// Stored versions of the object's state the node announced
+    pub state_announcements: usize,
+    /// Epoch of the latest state the node announced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_epoch: Option<DateTime<Utc>>,
+    /// Stored CDMs involving the object whose provenance starts at the node
+    pub cdms_originated: usize,
+    /// Peers those CDMs arrived through
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub via: Vec<String>,
+    /// Latest time this node received a state or CDM from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+impl ObjectSource {
+    fn new(node_id: &str) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            kind: TopologyNodeKind::Remote,
+            status: None,
+            current: false,
+            state_announcements: 0,
+            last_epoch: None,
+            cdms_originated: 0,
+            via: Vec::new(),
+            last_seen: None,
+        }
+    }
+}
+
+/// Which nodes announce an object
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ObjectSources {
+    pub object_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_operator: Option<String>,
+    /// The current source first, then by state announcements, CDMs and ID
+    pub sources: Vec<ObjectSource>,
+    /// Peers that advertised interests covering the object; peers without
+    /// interests take every announcement and are not listed
+    pub interested_peers: Vec<String>,
+}
+
+/// Find the nodes announcing an object, from its stored state history, the
+/// provenance of stored CDMs involving it and the peers' interests
+///
+/// Fails with [`Error::NotFound`] when nothing about the object is stored,
+/// or none of it is visible in `scope`.
+pub async fn object_sources(state: &AppState, scope: &TenantScope, object_id: &str) -> Result<ObjectSources> {
+    let node_id = state.config.get().node.id.clone();
+    let object = state.storage.get_object(object_id).await?;
+    if object.as_ref().is_some_and(|object| !scope.sees_object(object)) {
+        return Err(Error::NotFound(format!("Object not found: {}", object_id)));
+    }
+    let mut owner_operator = object.as_ref().and_then(|object| object.owner_operator.clone());
+    let mut sources: BTreeMap<String, ObjectSource> = BTreeMap::new();
+
+    if let Some(object) = &object {
+        for change in state.storage.object_state_history(object_id).await? {
+            let source = sources.entry(change.source_node.clone()).or_insert_with(|| ObjectSource::new(&change.source_node));
+            source.state_announcements += 1;
+            source.last_epoch = source.last_epoch.max(Some(change.epoch));
+            source.last_seen = source.last_seen.max(Some(change.recorded_at));
+        }
+        let source = sources.entry(object.source_node.clone()).or_insert_with(|| ObjectSource::new(&object.source_node));
+        source.current = true;
+    }
+
+    let mut cdms_seen = false;
+    for cdm in state.storage.list_cdms().await? {
+        let involved = [&cdm.object1, &cdm.object2].into_iter().find(|o| o.object_id == object_id);
+        let Some(involved) = involved.filter(|_| scope.sees_cdm(&cdm)) else {
+            continue;
+        };
+        cdms_seen = true;
+        if owner_operator.is_none() {
+            owner_operator = involved.owner_operator.clone();
+        }
+        let chain = state.storage.get_cdm_provenance(&cdm.cdm_id).await?.unwrap_or_default();
+        let (Some(first), Some(last)) = (chain.first(), chain.last()) else {
+            continue;
+        };
+        // The hop before this node's own is the peer the CDM came through
+        let via = match chain.len() {
+            len if len > 1 && last.node_id == node_id => Some(chain[len - 2].node_id.clone()),
+            _ => None,
+        };
+        let source = sources.entry(first.node_id.clone()).or_insert_with(|| ObjectSource::new(&first.node_id));
+        source.cdms_originated += 1;
+        source.last_seen = source.last_seen.max(Some(last.received_at));
+        if let Some(via) = via.filter(|via| !source.via.contains(via)) {
+            source.via.push(via);
+        }
+    }
+    if object.is_none() && !cdms_seen {
+        return Err(Error::NotFound(format!("Object not found: {}", object_id)));
+    }
+
+    let mut interested_peers = Vec::new();
+    {
+        let peers = state.peers.read().await;
+        for peer in peers.list_peers() {
+            if let Some(source) = sources.get_mut(&peer.id) {
+                source.kind = TopologyNodeKind::Peer;
+                source.status = Some(peer.status.clone());
+            }
+            if peers.interests(&peer.id).is_some_and(|i| i.wants_object(object_id, owner_operator.as_deref())) {
+                interested_peers.push(peer.id.clone());
+            }
+        }
+    }
+    if let Some(source) = sources.get_mut(&node_id) {
+        source.kind = TopologyNodeKind::Local;
+    }
+
+    let mut sources: Vec<_> = sources.into_values().collect();
+    for source in &mut sources {
+        source.via.sort();
+    }
+    sources.sort_by(|a, b| {
+        b.current
+            .cmp(&a.current)
+            .then(b.state_announcements.cmp(&a.state_announcements))
+            .then(b.cdms_originated.cmp(&a.cdms_originated))
+            .then(a.node_id.cmp(&b.node_id))
+    });
+    interested_peers.sort();
+    Ok(ObjectSources {
+        object_id: object_id.to_string(),
+        owner_operator,
+        sources,
+        interested_peers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::node::server::tests::test_state;
+    use crate::cdm::ObjectRecord;
+    use crate::node::PeerInfo;
+    use crate::protocol::{attest, Interests};
+
+    /// Store CDMs about NORAD-12345 and NORAD-99999 that took `paths`
+    async fn store_cdms(state: &AppState, paths: &[&[&str]]) {
+        for (i, path) in paths.iter().enumerate() {
+            let mut cdm = generate_demo_cdm();
+            cdm.cdm_id = format!("CDM-{}", i);
+            let payload = serde_json::to_value(&cdm).unwrap();
+            let mut chain = Vec::new();
+            for node_id in *path {
+                attest(&mut chain, &cdm.cdm_id, &payload, node_id, None);
+            }
+            let cdm_id = cdm.cdm_id.clone();
+            state.storage.store_cdm(cdm).await.unwrap();
+            state.storage.store_cdm_provenance(&cdm_id, chain).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_topology() {
+        let state = test_state("node-local");
+        {
+            let mut peers = state.peers.write().await;
+            for id in ["node-a", "node-b"] {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1' }}", id);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+            }
+            peers.set_peer_status("node-a", PeerStatus::Connected);
+            peers.record_advertised_address("node-a", Some("https://a.example".into()));
+        }
+        store_cdms(
+            &state,
+            &[
+                &["node-x", "node-a", "node-local"],
+                &["node-y", "node-x", "node-a", "node-local"],
+                &["node-local"],
+            ],
+        )
+        .await;
+
+        let topology = build_topology(&state).await.unwrap();
+        let nodes: Vec<_> = topology.nodes.iter().map(|n| (n.id.as_str(), n.kind, n.cdms_originated)).collect();
+        assert_eq!(
+            nodes,
+            [
+                ("node-local", TopologyNodeKind::Local, 1),
+                ("node-a", TopologyNodeKind::Peer, 0),
+                ("node-b", TopologyNodeKind::Peer, 0),
+                ("node-x", TopologyNodeKind::Remote, 1),
+                ("node-y", TopologyNodeKind::Remote, 1),
+            ]
+        );
+        assert_eq!(topology.nodes[1].address.as_deref(), Some("https://a.example"));
+        assert_eq!(topology.nodes[2].address.as_deref(), Some("http://127.0.0.1:1"));
+        assert!(topology.nodes[2].last_seen.is_none());
+
+        let edges: Vec<_> = topology.edges.iter().map(|e| (e.from.as_str(), e.to.as_str(), e.kind, e.cdms)).collect();
+        assert_eq!(
+            edges,
+            [
+                ("node-local", "node-a", TopologyEdgeKind::Session, 0),
+                ("node-local", "node-b", TopologyEdgeKind::Session, 0),
+                ("node-a", "node-local", TopologyEdgeKind::Path, 2),
+                ("node-x", "node-a", TopologyEdgeKind::Path, 2),
+                ("node-y", "node-x", TopologyEdgeKind::Path, 1),
+            ]
+        );
+
+        let dot = topology.to_dot();
+        assert!(dot.starts_with("digraph spacecomms {"));
+        assert!(dot.contains("\"node-a\" [label=\"node-a\\nconnected\", shape=box, style=solid];"));
+        assert!(dot.contains("\"node-local\" -> \"node-a\" [dir=none, style=bold];"));
+        assert!(dot.contains("\"node-local\" -> \"node-b\" [dir=none, style=dotted];"));
+        assert!(dot.contains("\"node-y\" -> \"node-x\" [label=\"1 CDM\"];"));
+        assert_eq!(quote("a\"b"), "a\\\"b");
+    }
+
+    #[tokio::test]
+    async fn test_object_sources() {
+        let state = test_state("node-local");
+        {
+            let mut peers = state.peers.write().await;
+            for id in ["node-a", "node-b", "node-c"] {
+                let config = format!("{{ id: {}, address: 'http://127.0.0.1:1' }}", id);
+                peers.add_peer(PeerInfo::from_config(&serde_yaml::from_str(&config).unwrap()));
+            }
+            peers.set_peer_status("node-a", PeerStatus::Connected);
+            let interests = |pattern: &str| Interests {
+                object_ids: vec![pattern.to_string()],
+                ..Default::default()
+            };
+            peers.record_interests("node-a", Some(interests("NORAD-123*")));
+            peers.record_interests("node-b", Some(interests("NORAD-9*")));
+        }
+        let object: ObjectRecord = serde_json::from_value(serde_json::json!({
+            "object_id": "NORAD-12345",
+            "object_name": "STARLINK-1234",
+            "object_type": "PAYLOAD",
+            "epoch": "2024-01-16T06:00:00Z",
+            "state_vector": {
+                "reference_frame": "TEME",
+                "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0,
+                "vx_km_s": 0.0, "vy_km_s": 7.546, "vz_km_s": 0.0
+            },
+            "source_node": "node-x",
+            "last_updated": "2024-01-16T06:00:00Z"
+        }))
+        .unwrap();
+        for (hours, source) in [(0, "node-x"), (1, "node-x"), (2, "node-a")] {
+            let update = ObjectRecord {
+                epoch: object.epoch + chrono::Duration::hours(hours),
+                source_node: source.to_string(),
+                ..object.clone()
+            };
+            state.storage.store_object(update).await.unwrap();
+        }
+        store_cdms(
+            &state,
+            &[
+                &["node-x", "node-a", "node-local"],
+                &["node-y", "node-b", "node-local"],
+                &["node-local"],
+            ],
+        )
+        .await;
+
+        let scope = TenantScope::default();
+        let found = object_sources(&state, &scope, "NORAD-12345").await.unwrap();
+        let sources: Vec<_> = found
+            .sources
+            .iter()
+            .map(|s| (s.node_id.as_str(), s.kind, s.current, s.state_announcements, s.cdms_originated))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("node-a", TopologyNodeKind::Peer, true, 1, 0),
+                ("node-x", TopologyNodeKind::Remote, false, 2, 1),
+                ("node-local", TopologyNodeKind::Local, false, 0, 1),
+                ("node-y", TopologyNodeKind::Remote, false, 0, 1),
+            ]
+        );
+        assert_eq!(found.sources[0].status, Some(PeerStatus::Connected));
+        assert_eq!(found.sources[1].last_epoch, Some(object.epoch + chrono::Duration::hours(1)));
+        assert_eq!(found.sources[1].via, ["node-a"]);
+        assert!(found.sources[2].via.is_empty());
+        assert_eq!(found.sources[3].via, ["node-b"]);
+        assert_eq!(found.interested_peers, ["node-a"]);
+
+        // Known only from CDMs
+        let found = object_sources(&state, &scope, "NORAD-99999").await.unwrap();
+        assert_eq!(found.sources.len(), 3);
+        assert!(found.sources.iter().all(|s| !s.current && s.state_announcements == 0));
+        assert_eq!(found.interested_peers, ["node-b"]);
+
+        let err = object_sources(&state, &scope, "NORAD-00001").await.unwrap_err();
+        assert!(err.is_not_found());
+    }
+}
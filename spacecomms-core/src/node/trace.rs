@@ -0,0 +1,183 @@
+//! Per-CDM pipeline tracing (debug mode)
+//!
+//! When a client asks for a trace, every pipeline stage records its
+//! decision and timing into a [`Tracer`]. Forwarding outcomes arrive after
+//! the HTTP response has been sent, so the trace stays shared and the
+//! completed version can be fetched later from the [`TraceStore`].
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Header that requests a trace, as an alternative to `?trace=true`
+pub const TRACE_HEADER: &str = "x-spacecomms-trace";
+
+/// Number of traces kept for later retrieval
+const MAX_TRACES: usize = 1000;
+
+/// Outcome of a pipeline stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StageOutcome {
+    Ok,
+    Rejected,
+    Skipped,
+    Pending,
+    Failed,
+}
+
+/// One recorded pipeline stage
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TraceStage {
+    /// Stage name, e.g. "parse" or "forward:node-b"
+    pub stage: String,
+    pub outcome: StageOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Microseconds from the start of the trace to the end of the stage
+    pub at_us: u64,
+    /// Stage duration in microseconds, when timed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+}
+
+/// Structured trace of one CDM through the pipeline
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PipelineTrace {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cdm_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub stages: Vec<TraceStage>,
+}
+
+/// Shared recorder for a pipeline trace
+#[derive(Clone)]
+pub struct Tracer {
+    start: Instant,
+    trace: Arc<Mutex<PipelineTrace>>,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tracer {
+    /// Start a new trace
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            trace: Arc::new(Mutex::new(PipelineTrace {
+                cdm_id: None,
+                message_id: None,
+                started_at: Utc::now(),
+                stages: Vec::new(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PipelineTrace> {
+        self.trace.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record the CDM the trace belongs to
+    pub fn set_cdm_id(&self, cdm_id: &str) {
+        self.lock().cdm_id = Some(cdm_id.to_string());
+    }
+
+    /// Record the envelope carrying the CDM
+    pub fn set_message_id(&self, message_id: &str) {
+        self.lock().message_id = Some(message_id.to_string());
+    }
+
+    /// Record an untimed stage
+    pub fn record(&self, stage: impl Into<String>, outcome: StageOutcome, detail: Option<String>) {
+        self.push(stage.into(), outcome, detail, None);
+    }
+
+    /// Record a stage that took `duration`
+    pub fn record_timed(&self, stage: impl Into<String>, outcome: StageOutcome, detail: Option<String>, duration: Duration) {
+        self.push(stage.into(), outcome, detail, Some(duration));
+    }
+
+    fn push(&self, stage: String, outcome: StageOutcome, detail: Option<String>, duration: Option<Duration>) {
+        let at_us = self.start.elapsed().as_micros() as u64;
+        self.lock().stages.push(TraceStage {
+            stage,
+            outcome,
+            detail,
+            at_us,
+            duration_us: duration.map(|d| d.as_micros() as u64),
+        });
+    }
+
+    /// Current state of the trace
+    pub fn snapshot(&self) -> PipelineTrace {
+        self.lock().clone()
+    }
+}
+
+/// Recently captured traces, keyed by CDM ID
+#[derive(Default)]
+pub struct TraceStore {
+    inner: Mutex<(HashMap<String, Tracer>, VecDeque<String>)>,
+}
+
+impl TraceStore {
+    /// Keep a trace, evicting the oldest once the store is full
+    pub fn insert(&self, cdm_id: &str, tracer: Tracer) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let (traces, order) = &mut *inner;
+        if traces.insert(cdm_id.to_string(), tracer).is_none() {
+            order.push_back(cdm_id.to_string());
+        }
+        while order.len() > MAX_TRACES {
+            if let Some(oldest) = order.pop_front() {
+                traces.remove(&oldest);
+            }
+        }
+    }
+
+    /// Latest trace for a CDM
+    pub fn get(&self, cdm_id: &str) -> Option<PipelineTrace> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.0.get(cdm_id).map(Tracer::snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracer_records_stages() {
+        let tracer = Tracer::new();
+        tracer.set_cdm_id("CDM-1");
+        tracer.record_timed("parse", StageOutcome::Ok, None, Duration::from_micros(40));
+        let shared = tracer.clone();
+        shared.record("forward:node-b", StageOutcome::Failed, Some("timeout".into()));
+
+        let trace = tracer.snapshot();
+        assert_eq!(trace.cdm_id.as_deref(), Some("CDM-1"));
+        assert_eq!(trace.stages.len(), 2);
+        assert_eq!(trace.stages[0].duration_us, Some(40));
+        assert_eq!(trace.stages[1].outcome, StageOutcome::Failed);
+        assert!(trace.stages[1].at_us >= trace.stages[0].at_us);
+    }
+
+    #[test]
+    fn test_store_is_bounded() {
+        let store = TraceStore::default();
+        for i in 0..MAX_TRACES + 5 {
+            store.insert(&format!("CDM-{}", i), Tracer::new());
+        }
+        assert!(store.get("CDM-0").is_none());
+        assert!(store.get(&format!("CDM-{}", MAX_TRACES + 4)).is_some());
+    }
+}
@@ -0,0 +1,267 @@
+//! Synthetic traffic generator for developer mode
+//!
+//! Produces a steady stream of object state updates, CDMs and maneuver
+//! intents for a small fixed constellation, so the dashboard and adapters
+//! have data without any external mocks running.
+
+use crate::cdm::generate_synthetic_cdm;
+use crate::node::{apply_announcement, originate, AppState};
+use crate::protocol::{
+    Envelope, ManeuverIntentPayload, ManeuverType, MessageType, ObjectStateAnnouncePayload, ObjectType, StateVector,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::TAU;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const EARTH_RADIUS_KM: f64 = 6378.137;
+const EARTH_MU_KM3_S2: f64 = 398600.4418;
+
+/// Object in the demo catalog
+struct DemoObject {
+    id: &'static str,
+    name: &'static str,
+    object_type: ObjectType,
+    owner: Option<&'static str>,
+    altitude_km: f64,
+    inclination_deg: f64,
+}
+
+const fn demo(
+    id: &'static str,
+    name: &'static str,
+    object_type: ObjectType,
+    owner: Option<&'static str>,
+    altitude_km: f64,
+    inclination_deg: f64,
+) -> DemoObject {
+    DemoObject {
+        id,
+        name,
+        object_type,
+        owner,
+        altitude_km,
+        inclination_deg,
+    }
+}
+
+const DEMO_OBJECTS: &[DemoObject] = &[
+    demo("NORAD-25544", "ISS (ZARYA)", ObjectType::Payload, Some("ISS Partners"), 420.0, 51.6),
+    demo("NORAD-44713", "STARLINK-1007", ObjectType::Payload, Some("SpaceX"), 550.0, 53.0),
+    demo("NORAD-48274", "ONEWEB-0120", ObjectType::Payload, Some("OneWeb"), 1200.0, 87.9),
+    demo("NORAD-40697", "SENTINEL-2A", ObjectType::Payload, Some("ESA"), 786.0, 98.6),
+    demo("NORAD-41917", "IRIDIUM 106", ObjectType::Payload, Some("Iridium"), 780.0, 86.4),
+    demo("NORAD-29228", "FENGYUN 1C DEB", ObjectType::Debris, None, 850.0, 98.8),
+    demo("NORAD-33772", "COSMOS 2251 DEB", ObjectType::Debris, None, 790.0, 74.0),
+    demo("NORAD-33873", "IRIDIUM 33 DEB", ObjectType::Debris, None, 780.0, 86.4),
+    demo("NORAD-22566", "SL-16 R/B", ObjectType::RocketBody, None, 840.0, 71.0),
+];
+
+/// Generator of realistic-looking protocol traffic
+pub struct TrafficGenerator {
+    node_id: String,
+    rng: StdRng,
+    started: DateTime<Utc>,
+    phases: Vec<f64>,
+    ticks: u64,
+}
+
+impl TrafficGenerator {
+    /// Create a generator for a node; the seed makes the traffic reproducible
+    pub fn new(node_id: String, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let phases = DEMO_OBJECTS.iter().map(|_| rng.gen_range(0.0..TAU)).collect();
+        Self {
+            node_id,
+            rng,
+            started: Utc::now(),
+            phases,
+            ticks: 0,
+        }
+    }
+
+    /// Envelopes for the next tick
+    ///
+    /// The first tick announces the whole demo catalog; later ticks update a
+    /// couple of objects, raise a CDM and, for high-risk conjunctions of a
+    /// maneuverable payload, announce an avoidance maneuver.
+    pub fn next_batch(&mut self) -> Vec<Envelope> {
+        let now = Utc::now();
+        let mut batch = Vec::new();
+
+        let updated: Vec<usize> = if self.ticks == 0 {
+            (0..DEMO_OBJECTS.len()).collect()
+        } else {
+            (0..2).map(|_| self.rng.gen_range(0..DEMO_OBJECTS.len())).collect()
+        };
+        for index in updated {
+            batch.push(self.envelope(MessageType::ObjectStateAnnounce, self.object_state(index, now)));
+        }
+
+        let payloads: Vec<usize> = (0..DEMO_OBJECTS.len())
+            .filter(|&i| DEMO_OBJECTS[i].object_type == ObjectType::Payload)
+            .collect();
+        let others: Vec<usize> = (0..DEMO_OBJECTS.len())
+            .filter(|&i| DEMO_OBJECTS[i].object_type != ObjectType::Payload)
+            .collect();
+        let primary = payloads[self.rng.gen_range(0..payloads.len())];
+        let secondary = others[self.rng.gen_range(0..others.len())];
+
+        // Log-uniform miss distance between 20 m and 5 km
+        let miss_distance_m = 10f64.powf(self.rng.gen_range(1.3..3.7));
+        let collision_probability = (1e-2 * (-miss_distance_m / 150.0).exp()).max(1e-9);
+        let tca = now + ChronoDuration::minutes(self.rng.gen_range(60..72 * 60));
+
+        let (object1, object2) = (&DEMO_OBJECTS[primary], &DEMO_OBJECTS[secondary]);
+        let mut cdm = generate_synthetic_cdm(
+            object1.id,
+            object1.name,
+            object2.id,
+            object2.name,
+            tca,
+            miss_distance_m,
+            collision_probability,
+        );
+        cdm.originator = self.node_id.clone();
+        cdm.object1.owner_operator = object1.owner.map(str::to_string);
+        cdm.object1.state_vector = self.state_vector(primary, now);
+        cdm.object2.object_type = object2.object_type.clone();
+        cdm.object2.state_vector = self.state_vector(secondary, now);
+        let cdm_id = cdm.cdm_id.clone();
+        if let Ok(payload) = serde_json::to_value(&cdm) {
+            batch.push(self.envelope(MessageType::CdmAnnounce, payload));
+        }
+
+        if collision_probability > 1e-4 {
+            let planned_start = (tca - ChronoDuration::hours(6)).max(now + ChronoDuration::minutes(10));
+            let intent = ManeuverIntentPayload {
+                maneuver_id: format!("MNVR-DEV-{}", &uuid::Uuid::new_v4().to_string()[..8].to_uppercase()),
+                object_id: object1.id.to_string(),
+                related_cdm_id: Some(cdm_id),
+                planned_start,
+                planned_duration_s: self.rng.gen_range(5.0..60.0),
+                maneuver_type: ManeuverType::CollisionAvoidance,
+                delta_v: None,
+                predicted_post_maneuver_state: None,
+            };
+            if let Ok(payload) = serde_json::to_value(&intent) {
+                batch.push(self.envelope(MessageType::ManeuverIntent, payload));
+            }
+        }
+
+        self.ticks += 1;
+        batch
+    }
+
+    fn envelope(&self, message_type: MessageType, payload: serde_json::Value) -> Envelope {
+        Envelope::new(self.node_id.clone(), message_type, payload)
+    }
+
+    fn object_state(&self, index: usize, now: DateTime<Utc>) -> serde_json::Value {
+        let object = &DEMO_OBJECTS[index];
+        let announce = ObjectStateAnnouncePayload {
+            object_id: object.id.to_string(),
+            object_name: object.name.to_string(),
+            object_type: object.object_type.clone(),
+            owner_operator: object.owner.map(str::to_string),
+            epoch: now,
+            state_vector: self.state_vector(index, now),
+            covariance: None,
+            metadata: Default::default(),
+        };
+        serde_json::to_value(announce).unwrap_or_default()
+    }
+
+    /// Circular orbit state at `epoch`
+    fn state_vector(&self, index: usize, epoch: DateTime<Utc>) -> StateVector {
+        let object = &DEMO_OBJECTS[index];
+        let radius_km = EARTH_RADIUS_KM + object.altitude_km;
+        let speed_km_s = (EARTH_MU_KM3_S2 / radius_km).sqrt();
+        let elapsed_s = (epoch - self.started).num_milliseconds() as f64 / 1000.0;
+        let theta = self.phases[index] + speed_km_s / radius_km * elapsed_s;
+        let (sin_i, cos_i) = object.inclination_deg.to_radians().sin_cos();
+        let (sin_t, cos_t) = theta.sin_cos();
+
+        StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(epoch),
+            x_km: radius_km * cos_t,
+            y_km: radius_km * sin_t * cos_i,
+            z_km: radius_km * sin_t * sin_i,
+            vx_km_s: -speed_km_s * sin_t,
+            vy_km_s: speed_km_s * cos_t * cos_i,
+            vz_km_s: speed_km_s * cos_t * sin_i,
+        }
+    }
+}
+
+/// Apply a generated envelope locally and announce it to peers, returning
+/// whether it was stored
+pub(crate) async fn publish(state: &AppState, envelope: Envelope) -> bool {
+    match apply_announcement(state, &envelope, None).await {
+        Ok(true) => {
+            originate(state, envelope).await;
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            warn!("Generated {} rejected: {}", envelope.message_type, e);
+            false
+        }
+    }
+}
+
+/// Run the traffic generator in the background
+pub fn spawn_traffic_generator(state: AppState, interval: Duration) {
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut generator = TrafficGenerator::new(state.config.get().node.id.clone(), rand::random());
+        let mut ticker = tokio::time::interval(interval);
+        info!("Traffic generator running every {:?}", interval);
+        loop {
+            ticker.tick().await;
+            let batch = generator.next_batch();
+            debug!("Generated {} envelopes", batch.len());
+            for envelope in batch {
+                publish(&state, envelope).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::server::tests::test_state;
+    use crate::protocol::{validate_envelope, EnvelopeLimits};
+
+    #[test]
+    fn test_generated_traffic_is_valid() {
+        let mut generator = TrafficGenerator::new("dev-node".to_string(), 7);
+        let first = generator.next_batch();
+        let objects = first
+            .iter()
+            .filter(|e| e.message_type == MessageType::ObjectStateAnnounce)
+            .count();
+        assert_eq!(objects, DEMO_OBJECTS.len());
+
+        for envelope in first.iter().chain(&generator.next_batch()) {
+            assert!(validate_envelope(envelope, &EnvelopeLimits::default()).is_ok(), "{}", envelope.payload);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_populates_storage() {
+        let state = test_state("dev-node");
+        let mut generator = TrafficGenerator::new("dev-node".to_string(), 7);
+        for _ in 0..3 {
+            for envelope in generator.next_batch() {
+                publish(&state, envelope).await;
+            }
+        }
+        assert_eq!(state.storage.list_objects().await.unwrap().len(), DEMO_OBJECTS.len());
+        assert_eq!(state.storage.list_cdms().await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+//! Peer transports
+//!
+//! A transport carries protocol envelopes to a single peer. The HTTP
+//! transport posts each envelope to the peer's protocol endpoint; the gRPC
+//! transport (see `grpc.rs`) keeps a bidirectional stream open instead.
+
+use crate::config::PeerTransport;
+use crate::protocol::{Encoding, Envelope, ErrorPayload, MessageType, TimestampFormat, MAX_INFLATED_BYTES};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+
+/// Path of the protocol endpoint on every node
+pub const PROTOCOL_ENDPOINT: &str = "/spacecomms/v1/messages";
+
+/// Header identifying the node that sent an envelope (the previous hop)
+pub const NODE_ID_HEADER: &str = "x-spacecomms-node-id";
+
+/// Envelope transport to a single peer
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Transport kind
+    fn kind(&self) -> PeerTransport;
+
+    /// Send an envelope, returning the peer's synchronous reply (if any)
+    async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>>;
+
+    /// Whether envelopes for the peer may be sent as an ENVELOPE_BATCH
+    fn accepts_batches(&self) -> bool {
+        false
+    }
+}
+
+/// HTTP transport: one POST per envelope
+pub struct HttpTransport {
+    client: reqwest::Client,
+    endpoint: String,
+    local_node_id: String,
+    auth_token: Option<String>,
+    encoding: Encoding,
+    timestamp_format: TimestampFormat,
+    batching: bool,
+    /// Smallest payload sent compressed, once the peer offered PAYLOAD_GZIP
+    compress_from: Option<usize>,
+}
+
+impl HttpTransport {
+    /// Create a JSON transport for a peer base address
+    pub fn new(address: &str, local_node_id: &str, auth_token: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: format!("{}{}", address.trim_end_matches('/'), PROTOCOL_ENDPOINT),
+            local_node_id: local_node_id.to_string(),
+            auth_token,
+            encoding: Encoding::Json,
+            timestamp_format: TimestampFormat::Auto,
+            batching: false,
+            compress_from: None,
+        }
+    }
+
+    /// Compress payloads of at least `min_bytes` of JSON, once the peer
+    /// offered PAYLOAD_GZIP
+    pub fn with_compression(mut self, min_bytes: usize) -> Self {
+        self.compress_from = Some(min_bytes);
+        self
+    }
+
+    /// Let the fan-out batch envelopes, once the peer offered BATCHING
+    pub fn with_batching(mut self) -> Self {
+        self.batching = true;
+        self
+    }
+
+    /// Use a different envelope encoding for requests
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Use a timestamp profile for outbound envelopes
+    pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// Envelope encoding used for requests
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    fn kind(&self) -> PeerTransport {
+        PeerTransport::Http
+    }
+
+    fn accepts_batches(&self) -> bool {
+        self.batching
+    }
+
+    async fn send(&self, envelope: &Envelope) -> Result<Option<Envelope>> {
+        let format = self.timestamp_format;
+        let body = match self.compress_from {
+            Some(min_bytes) if envelope.payload.to_json(format)?.len() >= min_bytes => {
+                envelope.compressed(format)?.encode_with(self.encoding, format)?
+            }
+            _ => envelope.encode_with(self.encoding, format)?,
+        };
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(NODE_ID_HEADER, &self.local_node_id)
+            .header(CONTENT_TYPE, self.encoding.content_type())
+            .body(body);
+        if let Some(token) = &self.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let resp = request.send().await?;
+        let status = resp.status();
+        if status == StatusCode::ACCEPTED || status == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        // Replies use the encoding the peer chose for its response
+        let encoding = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_content_type)
+            .unwrap_or(Encoding::Json);
+        let body = resp.bytes().await?;
+        if status == StatusCode::OK {
+            let mut reply = Envelope::decode(&body, encoding)?;
+            reply.inflate(MAX_INFLATED_BYTES)?;
+            return Ok(Some(reply));
+        }
+
+        // Rejections carry an ERROR envelope; fall back to the raw body
+        let error = Envelope::decode(&body, encoding)
+            .ok()
+            .filter(|reply| reply.message_type == MessageType::Error)
+            .and_then(|reply| reply.payload.parse::<ErrorPayload>().ok());
+        match error {
+            Some(error) => Err(Error::PeerRefused(format!(
+                "{} rejected {}: {} {:?}: {}",
+                self.endpoint, envelope.message_type, status, error.error_code, error.error_message
+            ))),
+            None => Err(Error::Peer(format!(
+                "{} rejected {}: {} {}",
+                self.endpoint,
+                envelope.message_type,
+                status,
+                String::from_utf8_lossy(&body)
+            ))),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Registered-asset watchlist
+//!
+//! Operators register the NORAD catalog numbers of the assets they own with
+//! `POST /watchlist`. CDMs involving a watched asset are tagged
+//! `involves_watched_asset` when stored, can be listed with
+//! `GET /cdms?watched=true`, and watchers can follow only them with
+//! `GET /events/cdms?watched=true`. Changing the list re-tags the active
+//! CDMs. Object IDs match in any form [`ObjectIdentifier`] reads as a
+//! catalog number. The tag is local to this node and is not sent to peers.
+
+use crate::cdm::{CdmRecord, ObjectIdentifier};
+use crate::node::AppState;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tracing::debug;
+use utoipa::ToSchema;
+
+/// An asset on the watchlist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WatchedAsset {
+    /// NORAD catalog number, without prefix or leading zeros
+    pub norad_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub registered_at: DateTime<Utc>,
+}
+
+/// Catalog number of an object ID such as `NORAD-25544`, `25544` or
+/// `00005`; `None` for IDs that are not NORAD numbers
+pub fn norad_number(object_id: &str) -> Option<String> {
+    ObjectIdentifier::parse(object_id).norad_number().map(str::to_string)
+}
+
+/// Assets this node's operator owns
+#[derive(Default)]
+pub struct Watchlist {
+    assets: RwLock<BTreeMap<String, WatchedAsset>>,
+}
+
+impl Watchlist {
+    /// Add an asset, returning whether it was new, or `None` if the ID is
+    /// not a catalog number; a known asset takes the new name
+    pub fn register(&self, norad_id: &str, name: Option<String>) -> Option<bool> {
+        let id = norad_number(norad_id)?;
+        let mut assets = self.assets.write().ok()?;
+        if let Some(existing) = assets.get_mut(&id) {
+            if name.is_some() {
+                existing.name = name;
+            }
+            return Some(false);
+        }
+        assets.insert(
+            id.clone(),
+            WatchedAsset {
+                norad_id: id,
+                name,
+                registered_at: Utc::now(),
+            },
+        );
+        Some(true)
+    }
+
+    /// Remove an asset, returning whether it was listed
+    pub fn remove(&self, norad_id: &str) -> bool {
+        let Some(id) = norad_number(norad_id) else {
+            return false;
+        };
+        self.assets.write().is_ok_and(|mut assets| assets.remove(&id).is_some())
+    }
+
+    /// Watched assets by catalog number
+    pub fn list(&self) -> Vec<WatchedAsset> {
+        self.assets
+            .read()
+            .map(|assets| assets.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether an object is watched
+    pub fn watches(&self, object_id: &str) -> bool {
+        norad_number(object_id).is_some_and(|id| self.assets.read().is_ok_and(|assets| assets.contains_key(&id)))
+    }
+
+    /// The listed asset an object is, if any
+    pub fn asset(&self, object_id: &str) -> Option<WatchedAsset> {
+        let id = norad_number(object_id)?;
+        self.assets.read().ok()?.get(&id).cloned()
+    }
+
+    /// Whether either object of a CDM is watched
+    pub fn involves(&self, cdm: &CdmRecord) -> bool {
+        self.watches(&cdm.object1.object_id) || self.watches(&cdm.object2.object_id)
+    }
+}
+
+/// Bring the tag of every active CDM in line with the watchlist, returning
+/// the number of CDMs changed
+///
+/// A CDM replaced while being re-tagged keeps the tag it was stored with,
+/// which was computed against the current list.
+pub(crate) async fn retag_cdms(state: &AppState) -> Result<usize> {
+    let mut changed = 0;
+    for cdm in state.storage.list_cdms().await? {
+        let watched = state.watchlist.involves(&cdm);
+        if cdm.involves_watched_asset == watched {
+            continue;
+        }
+        let Some(current) = state.storage.get_cdm_versioned(&cdm.cdm_id).await? else {
+            continue;
+        };
+        let mut record = current.record;
+        record.involves_watched_asset = watched;
+        if state.storage.compare_and_swap_cdm(record, Some(current.revision)).await?.written() {
+            changed += 1;
+        }
+    }
+    debug!("Watchlist change re-tagged {} CDMs", changed);
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    #[test]
+    fn test_watchlist() {
+        assert_eq!(norad_number("NORAD-25544").as_deref(), Some("25544"));
+        assert_eq!(norad_number("norad-00005").as_deref(), Some("5"));
+        assert_eq!(norad_number(" 25544 ").as_deref(), Some("25544"));
+        assert_eq!(norad_number("000").as_deref(), Some("0"));
+        assert_eq!(norad_number("STARLINK-1234"), None);
+        assert_eq!(norad_number("NORAD-"), None);
+
+        let watchlist = Watchlist::default();
+        let cdm = generate_demo_cdm();
+        assert!(!watchlist.involves(&cdm));
+        assert_eq!(watchlist.register("012345", Some("SAT-1".into())), Some(true));
+        assert_eq!(watchlist.register("NORAD-12345", None), Some(false));
+        assert_eq!(watchlist.list()[0].name.as_deref(), Some("SAT-1"));
+        assert_eq!(watchlist.register("ISS", None), None);
+        assert!(watchlist.involves(&cdm));
+        assert!(watchlist.remove("12345"));
+        assert!(!watchlist.remove("12345"));
+        assert!(!watchlist.involves(&cdm));
+    }
+}
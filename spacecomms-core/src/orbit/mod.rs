@@ -0,0 +1,11 @@
+//! Orbit module - state propagation and conjunction screening for tracked objects
+
+mod propagation;
+mod screening;
+mod sgp4;
+mod vector;
+
+pub use propagation::*;
+pub use screening::*;
+pub use sgp4::*;
+pub(crate) use vector::*;
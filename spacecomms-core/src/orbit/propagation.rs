@@ -0,0 +1,342 @@
+//! Orbit propagation
+//!
+//! Predicts an object's state at another epoch by integrating its stored
+//! state vector with a fixed-step Runge-Kutta scheme. Two force models are
+//! available: point-mass gravity (`two_body`) and point mass plus Earth
+//! oblateness (`j2`, the default). Neither models drag, third bodies or
+//! solar radiation pressure, so predictions degrade with the propagation
+//! span, fastest in low orbits. Every [`Prediction`] grades its accuracy and
+//! lists the caveats that apply.
+
+use crate::cdm::ObjectRecord;
+use crate::protocol::StateVector;
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Earth gravitational parameter (km³/s²)
+pub const EARTH_MU_KM3_S2: f64 = 398600.4418;
+
+/// Earth equatorial radius (km)
+pub const EARTH_RADIUS_KM: f64 = 6378.137;
+
+/// Earth second zonal harmonic
+pub const EARTH_J2: f64 = 1.08262668e-3;
+
+/// Longest span a state is propagated over, in days
+pub const MAX_PROPAGATION_DAYS: i64 = 30;
+
+/// Largest integration step in seconds
+const MAX_STEP_SECONDS: f64 = 30.0;
+
+/// Altitude below which drag dominates the error budget (km)
+const LOW_ORBIT_ALTITUDE_KM: f64 = 2000.0;
+
+/// Frames a state can be integrated in directly
+const INERTIAL_FRAMES: [&str; 5] = ["TEME", "GCRF", "ICRF", "EME2000", "J2000"];
+
+/// Force model used for propagation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PropagationModel {
+    /// Point-mass Earth gravity only
+    TwoBody,
+    /// Point mass plus the J2 oblateness term
+    #[default]
+    J2,
+}
+
+/// How far a prediction can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Accuracy {
+    /// Short span; errors of a few kilometres at most
+    Nominal,
+    /// Errors of tens of kilometres are likely; fine for display, not for screening decisions
+    Degraded,
+    /// Indicative only
+    Low,
+}
+
+/// Predicted state of an object
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Prediction {
+    pub model: PropagationModel,
+    /// Epoch of the stored state the prediction starts from
+    pub from_epoch: DateTime<Utc>,
+    /// Seconds propagated; negative when predicting backwards
+    pub span_seconds: f64,
+    /// Predicted state, with `epoch` set to the requested time
+    pub state_vector: StateVector,
+    pub accuracy: Accuracy,
+    /// Reasons to treat the prediction with care
+    pub caveats: Vec<String>,
+}
+
+/// Position (km) and velocity (km/s)
+pub(crate) type State = [f64; 6];
+
+fn acceleration(state: &State, model: PropagationModel) -> [f64; 3] {
+    let [x, y, z, ..] = *state;
+    let r2 = x * x + y * y + z * z;
+    let r = r2.sqrt();
+    let point_mass = -EARTH_MU_KM3_S2 / (r2 * r);
+    let mut a = [point_mass * x, point_mass * y, point_mass * z];
+    if model == PropagationModel::J2 {
+        let k = 1.5 * EARTH_J2 * EARTH_MU_KM3_S2 * EARTH_RADIUS_KM * EARTH_RADIUS_KM / (r2 * r2 * r);
+        let zz = 5.0 * z * z / r2;
+        a[0] += k * x * (zz - 1.0);
+        a[1] += k * y * (zz - 1.0);
+        a[2] += k * z * (zz - 3.0);
+    }
+    a
+}
+
+fn derivative(state: &State, model: PropagationModel) -> State {
+    let a = acceleration(state, model);
+    [state[3], state[4], state[5], a[0], a[1], a[2]]
+}
+
+fn rk4_step(state: &State, dt: f64, model: PropagationModel) -> State {
+    let add = |s: &State, k: &State, h: f64| -> State { std::array::from_fn(|i| s[i] + h * k[i]) };
+    let k1 = derivative(state, model);
+    let k2 = derivative(&add(state, &k1, dt / 2.0), model);
+    let k3 = derivative(&add(state, &k2, dt / 2.0), model);
+    let k4 = derivative(&add(state, &k3, dt), model);
+    std::array::from_fn(|i| state[i] + dt / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+}
+
+/// Propagate a state vector from `from` to `to`
+///
+/// The state is treated as inertial whatever its reference frame, and the
+/// result keeps the input frame.
+pub fn propagate(
+    state_vector: &StateVector,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    model: PropagationModel,
+) -> Result<StateVector> {
+    let span = to - from;
+    if span.num_days().abs() > MAX_PROPAGATION_DAYS {
+        return Err(Error::Propagation(format!(
+            "span of {} days exceeds {} days",
+            span.num_days().abs(),
+            MAX_PROPAGATION_DAYS
+        )));
+    }
+    let sv = state_vector;
+    let state: State = [sv.x_km, sv.y_km, sv.z_km, sv.vx_km_s, sv.vy_km_s, sv.vz_km_s];
+    let state = propagate_state(&state, span.num_milliseconds() as f64 / 1000.0, model)?;
+
+    Ok(StateVector {
+        reference_frame: sv.reference_frame.clone(),
+        epoch: Some(to),
+        x_km: state[0],
+        y_km: state[1],
+        z_km: state[2],
+        vx_km_s: state[3],
+        vy_km_s: state[4],
+        vz_km_s: state[5],
+    })
+}
+
+/// Propagate a bare state `seconds` forwards (or backwards when negative)
+pub(crate) fn propagate_state(state: &State, seconds: f64, model: PropagationModel) -> Result<State> {
+    if state.iter().any(|v| !v.is_finite()) || radius(state) < EARTH_RADIUS_KM {
+        return Err(Error::Propagation("state vector is not a valid orbit".into()));
+    }
+    let steps = (seconds.abs() / MAX_STEP_SECONDS).ceil().max(1.0) as usize;
+    let dt = seconds / steps as f64;
+    let mut state = *state;
+    for _ in 0..steps {
+        state = rk4_step(&state, dt, model);
+        if radius(&state) < EARTH_RADIUS_KM {
+            return Err(Error::Propagation("trajectory intersects the Earth".into()));
+        }
+    }
+    Ok(state)
+}
+
+fn radius(state: &State) -> f64 {
+    (state[0] * state[0] + state[1] * state[1] + state[2] * state[2]).sqrt()
+}
+
+/// Predict an object's state at `at` from its stored state vector
+pub fn predict(object: &ObjectRecord, at: DateTime<Utc>, model: PropagationModel) -> Result<Prediction> {
+    let sv = &object.state_vector;
+    let from = sv.epoch.unwrap_or(object.epoch);
+    let state_vector = propagate(sv, from, at, model)?;
+
+    let span = at - from;
+    let altitude = radius(&[sv.x_km, sv.y_km, sv.z_km, 0.0, 0.0, 0.0]) - EARTH_RADIUS_KM;
+    let low_orbit = altitude < LOW_ORBIT_ALTITUDE_KM;
+    let (nominal, degraded) = if low_orbit {
+        (Duration::hours(6), Duration::days(3))
+    } else {
+        (Duration::days(1), Duration::days(7))
+    };
+    let magnitude = span.abs();
+    let accuracy = if magnitude <= nominal {
+        Accuracy::Nominal
+    } else if magnitude <= degraded {
+        Accuracy::Degraded
+    } else {
+        Accuracy::Low
+    };
+
+    let mut caveats = Vec::new();
+    if accuracy != Accuracy::Nominal {
+        caveats.push(format!(
+            "propagated {:.1} days from the stored state; request fresh tracking data",
+            magnitude.num_seconds() as f64 / 86400.0
+        ));
+    }
+    if model == PropagationModel::TwoBody && !magnitude.is_zero() {
+        caveats.push("J2 oblateness ignored; node and perigee drift are not modeled".to_string());
+    }
+    if low_orbit && !magnitude.is_zero() {
+        caveats.push("atmospheric drag is not modeled; low orbits decay faster than predicted".to_string());
+    }
+    let frame = sv.reference_frame.to_uppercase();
+    if !INERTIAL_FRAMES.contains(&frame.as_str()) {
+        caveats.push(format!(
+            "reference frame {} is not inertial; propagated as if it were",
+            sv.reference_frame
+        ));
+    }
+    if object.covariance.is_some() {
+        caveats.push("covariance is not propagated".to_string());
+    }
+
+    Ok(Prediction {
+        model,
+        from_epoch: from,
+        span_seconds: span.num_milliseconds() as f64 / 1000.0,
+        state_vector,
+        accuracy,
+        caveats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ObjectType;
+
+    /// Circular equatorial orbit at `radius_km`
+    fn circular(radius_km: f64, epoch: DateTime<Utc>) -> StateVector {
+        StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(epoch),
+            x_km: radius_km,
+            y_km: 0.0,
+            z_km: 0.0,
+            vx_km_s: 0.0,
+            vy_km_s: (EARTH_MU_KM3_S2 / radius_km).sqrt(),
+            vz_km_s: 0.0,
+        }
+    }
+
+    fn object(state_vector: StateVector) -> ObjectRecord {
+        ObjectRecord {
+            object_id: "SAT-1".to_string(),
+            object_name: "TEST".to_string(),
+            object_type: ObjectType::Payload,
+            owner_operator: None,
+            rcs_size: None,
+            epoch: state_vector.epoch.unwrap(),
+            state_vector,
+            covariance: None,
+            source_node: "node-a".to_string(),
+            last_updated: Utc::now(),
+            organization: None,
+        }
+    }
+
+    #[test]
+    fn test_two_body_period() {
+        let epoch = Utc::now();
+        let radius = 7000.0;
+        let start = circular(radius, epoch);
+        let period = std::f64::consts::TAU * (radius.powi(3) / EARTH_MU_KM3_S2).sqrt();
+        let later = epoch + Duration::milliseconds((period * 1000.0) as i64);
+        let end = propagate(&start, epoch, later, PropagationModel::TwoBody).unwrap();
+        assert!((end.x_km - start.x_km).abs() < 0.01, "{:?}", end);
+        assert!(end.y_km.abs() < 0.01);
+        assert_eq!(end.epoch, Some(later));
+
+        // Backwards and forwards again returns the start
+        let back = propagate(&end, later, epoch, PropagationModel::TwoBody).unwrap();
+        assert!((back.x_km - start.x_km).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_j2_regresses_node() {
+        // Inclined LEO orbit: J2 turns the orbit plane westwards
+        let epoch = Utc::now();
+        let v = (EARTH_MU_KM3_S2 / 7000.0).sqrt();
+        let (s, c) = 51.6f64.to_radians().sin_cos();
+        let start = StateVector {
+            vy_km_s: v * c,
+            vz_km_s: v * s,
+            ..circular(7000.0, epoch)
+        };
+        let day = epoch + Duration::days(1);
+        let two_body = propagate(&start, epoch, day, PropagationModel::TwoBody).unwrap();
+        let j2 = propagate(&start, epoch, day, PropagationModel::J2).unwrap();
+        let node = |sv: &StateVector| {
+            // Ascending node direction: z-axis x angular momentum
+            let h = [
+                sv.y_km * sv.vz_km_s - sv.z_km * sv.vy_km_s,
+                sv.z_km * sv.vx_km_s - sv.x_km * sv.vz_km_s,
+            ];
+            (-h[0]).atan2(h[1]).to_degrees()
+        };
+        // About -4.5 degrees per day at this altitude and inclination
+        let drift = (node(&j2) - node(&two_body) + 180.0).rem_euclid(360.0) - 180.0;
+        assert!((-4.7..-4.3).contains(&drift), "{}", drift);
+    }
+
+    #[test]
+    fn test_prediction_caveats() {
+        let epoch = Utc::now();
+        let leo = object(circular(6878.0, epoch));
+        let prediction = predict(&leo, epoch + Duration::hours(1), PropagationModel::J2).unwrap();
+        assert_eq!(prediction.accuracy, Accuracy::Nominal);
+        assert_eq!(prediction.span_seconds, 3600.0);
+        assert_eq!(prediction.caveats.len(), 1, "{:?}", prediction.caveats);
+
+        let prediction = predict(&leo, epoch - Duration::days(4), PropagationModel::TwoBody).unwrap();
+        assert_eq!(prediction.accuracy, Accuracy::Low);
+        assert_eq!(prediction.caveats.len(), 3, "{:?}", prediction.caveats);
+
+        let mut geo = object(circular(42164.0, epoch));
+        geo.state_vector.reference_frame = "ITRF".to_string();
+        let prediction = predict(&geo, epoch + Duration::days(2), PropagationModel::J2).unwrap();
+        assert_eq!(prediction.accuracy, Accuracy::Degraded);
+        assert!(prediction.caveats.iter().any(|c| c.contains("ITRF")));
+    }
+
+    #[test]
+    fn test_invalid_propagation() {
+        let epoch = Utc::now();
+        let start = circular(7000.0, epoch);
+        let too_far = epoch + Duration::days(MAX_PROPAGATION_DAYS + 1);
+        assert!(matches!(
+            propagate(&start, epoch, too_far, PropagationModel::J2),
+            Err(Error::Propagation(_))
+        ));
+
+        let underground = circular(6000.0, epoch);
+        assert!(propagate(&underground, epoch, epoch, PropagationModel::J2).is_err());
+
+        // Sub-orbital: falls back in within the hour
+        let falling = StateVector {
+            vy_km_s: 2.0,
+            ..circular(6500.0, epoch)
+        };
+        let hour = epoch + Duration::hours(1);
+        assert!(propagate(&falling, epoch, hour, PropagationModel::TwoBody).is_err());
+    }
+}
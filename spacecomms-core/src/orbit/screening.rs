@@ -0,0 +1,800 @@
+//! Conjunction screening
+//!
+//! Finds close approaches between catalog objects over a time window. An
+//! all-pairs comparison grows with the square of the catalog, so objects
+//! are first partitioned into orbital-regime bins by perigee band, apogee
+//! band and inclination band. Two bins only need comparing when their
+//! altitude ranges come within the screening distance of each other; each
+//! such bin pair is a shard. Within a shard, pairs whose perigee/apogee
+//! shells do not overlap are dropped before any distance is computed.
+//!
+//! The window is walked in segments. For each segment every paired object's
+//! samples are propagated once, then the shards' pairs are compared, with
+//! both phases spread over worker tasks on the blocking pool. A local minimum of
+//! the sampled distance that could hide an approach inside the screening
+//! distance is refined to a TCA by golden-section search. States are
+//! treated as inertial whatever their reference frame, as in [`predict`].
+//! A [`ScreeningPlan`] holds the catalog at the window start with its shards,
+//! so that parts of them can be screened elsewhere with [`screen_pairs`] and
+//! the results combined.
+//!
+//! [`predict`]: super::predict
+
+use super::propagation::{propagate_state, State, EARTH_MU_KM3_S2, EARTH_RADIUS_KM, MAX_PROPAGATION_DAYS};
+use super::vector::{cross, dot, norm, sub};
+use super::PropagationModel;
+use crate::cdm::ObjectRecord;
+use crate::protocol::StateVector;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+/// Samples propagated per object before the shards are compared
+const SEGMENT_SAMPLES: usize = 60;
+
+/// Object pairs compared by a worker task at a time
+const PAIRS_PER_TASK: usize = 256;
+
+/// Golden-section iterations when refining a TCA
+const REFINE_ITERATIONS: usize = 40;
+
+/// Highest altitude of low Earth orbit (km)
+const LEO_CEILING_KM: f64 = 2000.0;
+
+/// Geostationary altitude, and the band around it counted as GEO (km)
+const GEO_ALTITUDE_KM: f64 = 35786.0;
+const GEO_BAND_KM: f64 = 500.0;
+
+/// Eccentricity above which an orbit counts as highly elliptical
+const HEO_MIN_ECCENTRICITY: f64 = 0.25;
+
+/// How a screening run is partitioned and sampled
+#[derive(Debug, Clone)]
+pub struct ScreeningOptions {
+    /// Separation below which an approach is reported (km)
+    pub threshold_km: f64,
+    /// Length of the window screened from its start
+    pub window: Duration,
+    /// Spacing of the coarse distance samples in seconds
+    pub step_seconds: f64,
+    /// Width of the perigee and apogee bands objects are binned by (km)
+    pub altitude_band_km: f64,
+    /// Width of the inclination bands objects are binned by (degrees)
+    pub inclination_band_deg: f64,
+    /// Worker tasks propagating objects and comparing shards
+    pub workers: usize,
+    pub model: PropagationModel,
+}
+
+impl Default for ScreeningOptions {
+    fn default() -> Self {
+        Self {
+            threshold_km: 10.0,
+            window: Duration::hours(24),
+            step_seconds: 60.0,
+            altitude_band_km: 50.0,
+            inclination_band_deg: 10.0,
+            workers: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            model: PropagationModel::J2,
+        }
+    }
+}
+
+/// Shape of a bound orbit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OrbitShape {
+    pub perigee_altitude_km: f64,
+    pub apogee_altitude_km: f64,
+    pub inclination_deg: f64,
+}
+
+impl OrbitShape {
+    /// Osculating shape of a state; `None` for escape trajectories
+    pub fn from_state(sv: &StateVector) -> Option<Self> {
+        Self::from_cartesian(&cartesian(sv))
+    }
+
+    fn from_cartesian(state: &State) -> Option<Self> {
+        let r = [state[0], state[1], state[2]];
+        let v = [state[3], state[4], state[5]];
+        let radius = norm(r);
+        let energy = dot(v, v) / 2.0 - EARTH_MU_KM3_S2 / radius;
+        if !energy.is_finite() || energy >= 0.0 {
+            return None;
+        }
+        let semi_major_axis = -EARTH_MU_KM3_S2 / (2.0 * energy);
+        let h = cross(r, v);
+        let eccentricity = (1.0 - dot(h, h) / (EARTH_MU_KM3_S2 * semi_major_axis)).max(0.0).sqrt();
+        Some(Self {
+            perigee_altitude_km: semi_major_axis * (1.0 - eccentricity) - EARTH_RADIUS_KM,
+            apogee_altitude_km: semi_major_axis * (1.0 + eccentricity) - EARTH_RADIUS_KM,
+            inclination_deg: (h[2] / norm(h)).clamp(-1.0, 1.0).acos().to_degrees(),
+        })
+    }
+
+    pub fn eccentricity(&self) -> f64 {
+        let perigee = self.perigee_altitude_km + EARTH_RADIUS_KM;
+        let apogee = self.apogee_altitude_km + EARTH_RADIUS_KM;
+        (apogee - perigee) / (apogee + perigee)
+    }
+
+    /// Whether the altitude shells of two orbits come within `threshold_km`
+    fn overlaps(&self, other: &Self, threshold_km: f64) -> bool {
+        self.perigee_altitude_km - threshold_km <= other.apogee_altitude_km
+            && other.perigee_altitude_km - threshold_km <= self.apogee_altitude_km
+    }
+}
+
+/// Orbital-regime bin: objects sharing a perigee, apogee and inclination band
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegimeBin {
+    pub perigee_band: u32,
+    pub apogee_band: u32,
+    pub inclination_band: u32,
+    /// Indexes of the member objects
+    pub members: Vec<usize>,
+    /// Altitude range spanned by the members
+    pub shape: OrbitShape,
+}
+
+/// Bin objects by orbital regime
+///
+/// `shapes` are indexed like the objects; `None` entries are left out.
+pub fn partition(shapes: &[Option<OrbitShape>], options: &ScreeningOptions) -> Vec<RegimeBin> {
+    let band = |value: f64, width: f64| (value.max(0.0) / width).floor() as u32;
+    let mut bins: BTreeMap<(u32, u32, u32), Vec<usize>> = BTreeMap::new();
+    for (index, shape) in shapes.iter().enumerate() {
+        if let Some(shape) = shape {
+            let key = (
+                band(shape.perigee_altitude_km, options.altitude_band_km),
+                band(shape.apogee_altitude_km, options.altitude_band_km),
+                band(shape.inclination_deg, options.inclination_band_deg),
+            );
+            bins.entry(key).or_default().push(index);
+        }
+    }
+    bins.into_iter()
+        .map(|((perigee_band, apogee_band, inclination_band), members)| {
+            let shapes = members.iter().filter_map(|&i| shapes[i]);
+            let shape = shapes.fold(
+                OrbitShape {
+                    perigee_altitude_km: f64::INFINITY,
+                    apogee_altitude_km: f64::NEG_INFINITY,
+                    inclination_deg: inclination_band as f64 * options.inclination_band_deg,
+                },
+                |range, s| OrbitShape {
+                    perigee_altitude_km: range.perigee_altitude_km.min(s.perigee_altitude_km),
+                    apogee_altitude_km: range.apogee_altitude_km.max(s.apogee_altitude_km),
+                    ..range
+                },
+            );
+            RegimeBin { perigee_band, apogee_band, inclination_band, members, shape }
+        })
+        .collect()
+}
+
+/// Pairs of bins (by index, first not after second) whose objects can come
+/// within `threshold_km` of each other
+pub fn shards(bins: &[RegimeBin], threshold_km: f64) -> Vec<(usize, usize)> {
+    let mut shards = Vec::new();
+    for (a, first) in bins.iter().enumerate() {
+        for (b, second) in bins.iter().enumerate().skip(a) {
+            if first.shape.overlaps(&second.shape, threshold_km) && (a != b || first.members.len() > 1) {
+                shards.push((a, b));
+            }
+        }
+    }
+    shards
+}
+
+/// A close approach found by screening
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseApproach {
+    pub object1_id: String,
+    pub object2_id: String,
+    pub tca: DateTime<Utc>,
+    pub miss_distance_km: f64,
+    pub relative_speed_km_s: f64,
+    /// Both objects' states at TCA, in their stored frames
+    pub state1: StateVector,
+    pub state2: StateVector,
+}
+
+/// Orbital regime a close approach happens in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OrbitalRegime {
+    Leo,
+    Meo,
+    Geo,
+    Heo,
+}
+
+impl OrbitalRegime {
+    /// HEO when either orbit is highly eccentric, otherwise by the first
+    /// object's altitude at TCA
+    pub fn of(approach: &CloseApproach) -> Self {
+        let shapes = [&approach.state1, &approach.state2].map(OrbitShape::from_state);
+        if shapes.iter().any(|s| s.is_none_or(|s| s.eccentricity() > HEO_MIN_ECCENTRICITY)) {
+            return Self::Heo;
+        }
+        let sv = &approach.state1;
+        let altitude = norm([sv.x_km, sv.y_km, sv.z_km]) - EARTH_RADIUS_KM;
+        if altitude < LEO_CEILING_KM {
+            Self::Leo
+        } else if (GEO_ALTITUDE_KM - altitude).abs() <= GEO_BAND_KM {
+            Self::Geo
+        } else {
+            Self::Meo
+        }
+    }
+}
+
+/// Outcome of a screening run
+#[derive(Debug, Clone, Serialize)]
+pub struct Screening {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Objects screened for the whole window
+    pub objects: usize,
+    pub bins: usize,
+    pub shards: usize,
+    /// Object pairs compared after pruning, against n(n-1)/2 for all pairs
+    pub pairs: usize,
+    /// Approaches ordered by TCA
+    pub approaches: Vec<CloseApproach>,
+    /// Objects left out or dropped part-way, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// An object and its state at the start of the window screened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenedObject {
+    pub object_id: String,
+    /// State at the window start, in the object's stored frame
+    pub state_vector: StateVector,
+}
+
+/// The catalog propagated to the window start and partitioned into shards
+#[derive(Debug, Clone)]
+pub struct ScreeningPlan {
+    pub start: DateTime<Utc>,
+    /// Objects that reached the window start
+    pub objects: Vec<ScreenedObject>,
+    pub bins: usize,
+    /// Object pairs compared in each shard, as indexes into `objects`
+    pub shards: Vec<Vec<(usize, usize)>>,
+    /// Objects left out, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+impl ScreeningPlan {
+    /// Propagate `objects` to `start` and partition them into shards
+    pub fn new(objects: &[ObjectRecord], start: DateTime<Utc>, options: &ScreeningOptions) -> Self {
+        let mut screened = Vec::new();
+        let mut skipped = Vec::new();
+        for object in objects {
+            let sv = &object.state_vector;
+            let from = sv.epoch.unwrap_or(object.epoch);
+            if (start - from).num_days().abs() > MAX_PROPAGATION_DAYS {
+                skipped.push((object.object_id.clone(), format!("state is over {} days old", MAX_PROPAGATION_DAYS)));
+                continue;
+            }
+            let seconds = (start - from).num_milliseconds() as f64 / 1000.0;
+            match propagate_state(&cartesian(sv), seconds, options.model) {
+                Ok(state) => screened.push(ScreenedObject {
+                    object_id: object.object_id.clone(),
+                    state_vector: state_vector(&state, &sv.reference_frame, start),
+                }),
+                Err(e) => skipped.push((object.object_id.clone(), e.to_string())),
+            }
+        }
+        let shapes: Vec<Option<OrbitShape>> = screened.iter().map(|o| OrbitShape::from_state(&o.state_vector)).collect();
+        let bins = partition(&shapes, options);
+        let shards = shards(&bins, options.threshold_km)
+            .into_iter()
+            .map(|(a, b)| shard_pairs(&bins, &shapes, a, b, options.threshold_km))
+            .collect();
+        Self {
+            start,
+            objects: screened,
+            bins: bins.len(),
+            shards,
+            skipped,
+        }
+    }
+
+    /// Object pairs compared across all shards
+    pub fn pairs(&self) -> usize {
+        self.shards.iter().map(Vec::len).sum()
+    }
+
+    /// Split the shards into `parts` lists of pairs of about equal length,
+    /// keeping each shard whole
+    pub fn split(&self, parts: usize) -> Vec<Vec<(usize, usize)>> {
+        let mut split = vec![Vec::new(); parts.max(1)];
+        let mut largest_first: Vec<&Vec<(usize, usize)>> = self.shards.iter().collect();
+        largest_first.sort_by_key(|shard| std::cmp::Reverse(shard.len()));
+        for shard in largest_first {
+            if let Some(part) = split.iter_mut().min_by_key(|part| part.len()) {
+                part.extend_from_slice(shard);
+            }
+        }
+        split
+    }
+
+    /// The run's outcome from the screenings of all its pairs
+    pub fn finish(self, parts: impl IntoIterator<Item = PairScreening>, options: &ScreeningOptions) -> Screening {
+        let mut approaches = Vec::new();
+        let mut dropped = BTreeMap::new();
+        for part in parts {
+            approaches.extend(part.approaches);
+            for (object_id, reason) in part.skipped {
+                dropped.entry(object_id).or_insert(reason);
+            }
+        }
+        approaches.sort_by(|a, b| {
+            (a.tca, &a.object1_id, &a.object2_id).cmp(&(b.tca, &b.object1_id, &b.object2_id))
+        });
+        let pairs = self.pairs();
+        let last = last_sample(options);
+        let mut skipped = self.skipped;
+        let objects = self.objects.len().saturating_sub(dropped.len());
+        skipped.extend(dropped);
+        Screening {
+            start: self.start,
+            end: self.start + Duration::milliseconds((last as f64 * options.step_seconds * 1000.0) as i64),
+            objects,
+            bins: self.bins,
+            shards: self.shards.len(),
+            pairs,
+            approaches,
+            skipped,
+        }
+    }
+}
+
+/// Close approaches among some object pairs, and the objects dropped
+/// part-way through the window
+#[derive(Debug, Clone, Default)]
+pub struct PairScreening {
+    pub approaches: Vec<CloseApproach>,
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Samples of every object over one segment of the window
+struct Segment {
+    /// Sample index of the first entry
+    first: usize,
+    /// Last sample index of the window
+    last: usize,
+    /// Samples per object; `None` once an object has failed to propagate
+    states: Vec<Option<Vec<State>>>,
+}
+
+/// Screen `objects` for close approaches over the window starting at `start`
+pub async fn screen(objects: Vec<ObjectRecord>, start: DateTime<Utc>, options: &ScreeningOptions) -> Screening {
+    let plan = ScreeningPlan::new(&objects, start, options);
+    let found = screen_pairs(plan.objects.clone(), plan.shards.concat(), start, options).await;
+    plan.finish([found], options)
+}
+
+/// Compare `pairs` of `objects`, given as indexes with the objects' states
+/// at `start`, over the window
+///
+/// Only objects in some pair are propagated. Every index must be within
+/// `objects`.
+pub async fn screen_pairs(
+    objects: Vec<ScreenedObject>,
+    pairs: Vec<(usize, usize)>,
+    start: DateTime<Utc>,
+    options: &ScreeningOptions,
+) -> PairScreening {
+    let step = options.step_seconds;
+    let last = last_sample(options);
+    let options = Arc::new(options.clone());
+    let mut skipped = Vec::new();
+
+    let mut paired = vec![false; objects.len()];
+    for &(i, j) in &pairs {
+        paired[i] = true;
+        paired[j] = true;
+    }
+    let mut current: Vec<Option<State>> = objects
+        .iter()
+        .zip(paired)
+        .map(|(object, paired)| paired.then(|| cartesian(&object.state_vector)))
+        .collect();
+    let tasks: Vec<Vec<(usize, usize)>> = pairs.chunks(PAIRS_PER_TASK).map(<[_]>::to_vec).collect();
+    let objects = Arc::new(objects);
+
+    let mut approaches = Vec::new();
+    let mut first = 0;
+    // The previous segment's last two samples, so minima on the boundary
+    // have both neighbours
+    let mut carried: Vec<Vec<State>> = current.iter().map(|s| s.iter().copied().collect()).collect();
+    while first <= last {
+        let end = (first + SEGMENT_SAMPLES).min(last);
+        let seeds: Vec<(Option<State>, Vec<State>)> = current.iter().copied().zip(carried).collect();
+        let model = options.model;
+        let samples = end - first;
+        let propagated = on_workers(seeds, options.workers, move |(state, earlier)| {
+            let mut samples_out = earlier.clone();
+            let mut state = (*state)?;
+            for _ in 0..samples {
+                state = propagate_state(&state, step, model).ok()?;
+                samples_out.push(state);
+            }
+            Some(samples_out)
+        })
+        .await;
+
+        for (index, samples) in propagated.iter().enumerate() {
+            if samples.is_none() && current[index].is_some() {
+                skipped.push((objects[index].object_id.clone(), "trajectory intersects the Earth".to_string()));
+            }
+        }
+        let segment = Arc::new(Segment {
+            first: first.saturating_sub(1),
+            last,
+            states: propagated,
+        });
+        current = segment.states.iter().map(|s| s.as_ref().and_then(|s| s.last().copied())).collect();
+        carried = segment
+            .states
+            .iter()
+            .map(|s| s.as_ref().map(|s| s[s.len().saturating_sub(2)..].to_vec()).unwrap_or_default())
+            .collect();
+
+        // Minima are judged at samples first..end (exclusive), or through
+        // the final sample on the last segment
+        let judged = (first, if end == last { last + 1 } else { end });
+        let (objects, options) = (objects.clone(), options.clone());
+        let found = on_workers(tasks.clone(), options.workers, move |pairs| {
+            let mut found = Vec::new();
+            for &(i, j) in pairs {
+                if let (Some(s1), Some(s2)) = (&segment.states[i], &segment.states[j]) {
+                    for (seconds, miss, speed, state1, state2) in pair_approaches(&segment, s1, s2, judged, &options) {
+                        let tca = start + Duration::milliseconds((seconds * 1000.0).round() as i64);
+                        found.push(CloseApproach {
+                            object1_id: objects[i].object_id.clone(),
+                            object2_id: objects[j].object_id.clone(),
+                            tca,
+                            miss_distance_km: miss,
+                            relative_speed_km_s: speed,
+                            state1: state_vector(&state1, &objects[i].state_vector.reference_frame, tca),
+                            state2: state_vector(&state2, &objects[j].state_vector.reference_frame, tca),
+                        });
+                    }
+                }
+            }
+            found
+        })
+        .await;
+        approaches.extend(found.into_iter().flatten());
+        if end == last {
+            break;
+        }
+        first = end;
+    }
+    PairScreening { approaches, skipped }
+}
+
+/// Index of the last sample of the window
+fn last_sample(options: &ScreeningOptions) -> usize {
+    (options.window.num_milliseconds() as f64 / 1000.0 / options.step_seconds).ceil().max(1.0) as usize
+}
+
+/// Object pairs of a shard whose altitude shells overlap
+fn shard_pairs(
+    bins: &[RegimeBin],
+    shapes: &[Option<OrbitShape>],
+    a: usize,
+    b: usize,
+    threshold_km: f64,
+) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (n, &i) in bins[a].members.iter().enumerate() {
+        let partners = if a == b { &bins[b].members[n + 1..] } else { &bins[b].members[..] };
+        for &j in partners {
+            if let (Some(s1), Some(s2)) = (&shapes[i], &shapes[j]) {
+                if s1.overlaps(s2, threshold_km) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// Approaches of one pair whose sampled minimum falls in `judged`, as
+/// (seconds from window start, miss km, relative speed km/s, states)
+fn pair_approaches(
+    segment: &Segment,
+    s1: &[State],
+    s2: &[State],
+    judged: (usize, usize),
+    options: &ScreeningOptions,
+) -> Vec<(f64, f64, f64, State, State)> {
+    let distance = |k: usize| separation(&s1[k], &s2[k]);
+    let mut found = Vec::new();
+    for sample in judged.0.max(segment.first)..judged.1 {
+        let k = sample - segment.first;
+        let d = distance(k);
+        let before = k.checked_sub(1).map(distance);
+        let after = (k + 1 < s1.len()).then(|| distance(k + 1));
+        if before.is_some_and(|b| b < d) || after.is_some_and(|a| a <= d) {
+            continue;
+        }
+        // Under straight-line relative motion the true minimum is at most
+        // half a step of relative travel below the nearest sample
+        let speed = separation(&s1[k][3..], &s2[k][3..]);
+        if d > options.threshold_km + speed * options.step_seconds {
+            continue;
+        }
+        let low = if sample == 0 { 0.0 } else { -options.step_seconds };
+        let high = if sample == segment.last { 0.0 } else { options.step_seconds };
+        if let Some((offset, miss, state1, state2)) = refine(&s1[k], &s2[k], low, high, options.model) {
+            if miss <= options.threshold_km {
+                let speed = separation(&state1[3..], &state2[3..]);
+                found.push((sample as f64 * options.step_seconds + offset, miss, speed, state1, state2));
+            }
+        }
+    }
+    found
+}
+
+/// Golden-section search for the minimum separation within `low..=high`
+/// seconds of two sampled states
+fn refine(s1: &State, s2: &State, low: f64, high: f64, model: PropagationModel) -> Option<(f64, f64, State, State)> {
+    let at = |t: f64| -> Option<(f64, State, State)> {
+        let a = propagate_state(s1, t, model).ok()?;
+        let b = propagate_state(s2, t, model).ok()?;
+        Some((separation(&a, &b), a, b))
+    };
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (low, high);
+    for _ in 0..REFINE_ITERATIONS {
+        let left = high - ratio * (high - low);
+        let right = low + ratio * (high - low);
+        if at(left)?.0 < at(right)?.0 {
+            high = right;
+        } else {
+            low = left;
+        }
+    }
+    let t = (low + high) / 2.0;
+    let (miss, a, b) = at(t)?;
+    Some((t, miss, a, b))
+}
+
+/// Run `work` over `items` on up to `workers` blocking tasks, returning the
+/// results in item order
+async fn on_workers<T, R, F>(items: Vec<T>, workers: usize, work: F) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    let count = items.len();
+    let items = Arc::new(items);
+    let work = Arc::new(work);
+    let next = Arc::new(AtomicUsize::new(0));
+    let mut tasks = JoinSet::new();
+    for _ in 0..workers.clamp(1, count.max(1)) {
+        let (items, work, next) = (items.clone(), work.clone(), next.clone());
+        tasks.spawn_blocking(move || {
+            let mut done = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else { break };
+                done.push((index, work(item)));
+            }
+            done
+        });
+    }
+    let mut results: Vec<(usize, R)> = Vec::with_capacity(count);
+    while let Some(done) = tasks.join_next().await {
+        match done {
+            Ok(done) => results.extend(done),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn cartesian(sv: &StateVector) -> State {
+    [sv.x_km, sv.y_km, sv.z_km, sv.vx_km_s, sv.vy_km_s, sv.vz_km_s]
+}
+
+fn state_vector(state: &State, frame: &str, epoch: DateTime<Utc>) -> StateVector {
+    StateVector {
+        reference_frame: frame.to_string(),
+        epoch: Some(epoch),
+        x_km: state[0],
+        y_km: state[1],
+        z_km: state[2],
+        vx_km_s: state[3],
+        vy_km_s: state[4],
+        vz_km_s: state[5],
+    }
+}
+
+/// Distance between the first three components of `a` and `b`: positions,
+/// or velocities when given `state[3..]`
+fn separation(a: &[f64], b: &[f64]) -> f64 {
+    norm(sub([a[0], a[1], a[2]], [b[0], b[1], b[2]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit::propagate;
+    use crate::protocol::ObjectType;
+
+    /// Circular orbit at `radius_km` whose plane contains the x axis,
+    /// passing through +x at `epoch`
+    fn circular(radius_km: f64, inclination_deg: f64, epoch: DateTime<Utc>) -> StateVector {
+        let v = (EARTH_MU_KM3_S2 / radius_km).sqrt();
+        let (s, c) = inclination_deg.to_radians().sin_cos();
+        StateVector {
+            reference_frame: "TEME".to_string(),
+            epoch: Some(epoch),
+            x_km: radius_km,
+            y_km: 0.0,
+            z_km: 0.0,
+            vx_km_s: 0.0,
+            vy_km_s: v * c,
+            vz_km_s: v * s,
+        }
+    }
+
+    fn object(id: &str, state_vector: StateVector) -> ObjectRecord {
+        ObjectRecord {
+            object_id: id.to_string(),
+            object_name: id.to_string(),
+            object_type: ObjectType::Payload,
+            owner_operator: None,
+            rcs_size: None,
+            epoch: state_vector.epoch.unwrap(),
+            state_vector,
+            covariance: None,
+            source_node: "node-a".to_string(),
+            last_updated: Utc::now(),
+            organization: None,
+        }
+    }
+
+    /// Object whose state at `start` was propagated back from `at_tca`
+    fn arriving(id: &str, at_tca: StateVector, start: DateTime<Utc>) -> ObjectRecord {
+        let tca = at_tca.epoch.unwrap();
+        object(id, propagate(&at_tca, tca, start, PropagationModel::J2).unwrap())
+    }
+
+    #[test]
+    fn test_orbit_shape() {
+        let shape = OrbitShape::from_state(&circular(7000.0, 51.6, Utc::now())).unwrap();
+        assert!((shape.perigee_altitude_km - 621.863).abs() < 1e-3, "{:?}", shape);
+        assert!((shape.apogee_altitude_km - 621.863).abs() < 1e-3);
+        assert!((shape.inclination_deg - 51.6).abs() < 1e-9);
+
+        // Faster at the same point: perigee there, apogee higher up
+        let mut elliptical = circular(7000.0, 98.0, Utc::now());
+        elliptical.vy_km_s *= 1.1;
+        elliptical.vz_km_s *= 1.1;
+        let shape = OrbitShape::from_state(&elliptical).unwrap();
+        assert!((shape.perigee_altitude_km - 621.863).abs() < 1e-3);
+        assert!(shape.apogee_altitude_km > 3000.0);
+        assert!((shape.inclination_deg - 98.0).abs() < 1e-9);
+
+        elliptical.vz_km_s = 11.0;
+        assert!(OrbitShape::from_state(&elliptical).is_none());
+    }
+
+    #[test]
+    fn test_orbital_regime() {
+        let epoch = Utc::now();
+        let approach = |state1: StateVector, state2: StateVector| CloseApproach {
+            object1_id: "A".to_string(),
+            object2_id: "B".to_string(),
+            tca: epoch,
+            miss_distance_km: 0.0,
+            relative_speed_km_s: 0.0,
+            state1,
+            state2,
+        };
+        let regime = |r1: f64, r2: f64| OrbitalRegime::of(&approach(circular(r1, 0.0, epoch), circular(r2, 60.0, epoch)));
+        assert_eq!(regime(7000.0, 7000.0), OrbitalRegime::Leo);
+        assert_eq!(regime(26560.0, 26560.0), OrbitalRegime::Meo);
+        assert_eq!(regime(42164.0, 42164.0), OrbitalRegime::Geo);
+
+        // A transfer orbit passing through LEO
+        let mut transfer = circular(7000.0, 28.5, epoch);
+        transfer.vy_km_s *= 1.3;
+        transfer.vz_km_s *= 1.3;
+        assert_eq!(OrbitalRegime::of(&approach(circular(7000.0, 0.0, epoch), transfer)), OrbitalRegime::Heo);
+    }
+
+    #[test]
+    fn test_partition_prunes_pairs() {
+        let epoch = Utc::now();
+        let options = ScreeningOptions::default();
+        let orbits = [(7000.0, 51.6), (7005.0, 53.0), (7020.0, 97.0), (7400.0, 51.6), (42164.0, 0.1), (42166.0, 0.0)];
+        let shapes: Vec<Option<OrbitShape>> = orbits
+            .iter()
+            .map(|&(radius, inclination)| OrbitShape::from_state(&circular(radius, inclination, epoch)))
+            .collect();
+        let bins = partition(&shapes, &options);
+        // The first two share a perigee, apogee and inclination band, as do the two GEO objects
+        assert_eq!(bins.len(), 4);
+        assert_eq!(bins.iter().map(|b| b.members.len()).sum::<usize>(), orbits.len());
+
+        let shards = shards(&bins, options.threshold_km);
+        let pairs: Vec<(usize, usize)> = shards
+            .iter()
+            .flat_map(|&(a, b)| shard_pairs(&bins, &shapes, a, b, options.threshold_km))
+            .collect();
+        // Of 15 pairs, only those within 10 km of altitude of each other are compared
+        assert_eq!(pairs, vec![(0, 1), (4, 5)]);
+    }
+
+    #[tokio::test]
+    async fn test_screen_finds_close_approach() {
+        let start = Utc::now();
+        let tca = start + Duration::seconds(1000);
+        let objects = vec![
+            // Equatorial and polar orbits crossing 500 m apart over +x
+            arriving("SAT-EQ", circular(7000.5, 0.0, tca), start),
+            arriving("SAT-POLAR", circular(7000.0, 90.0, tca), start),
+            // Same altitude, far from both at the crossing
+            arriving("SAT-FAR", circular(7000.0, 45.0, tca - Duration::minutes(20)), start),
+            object("SAT-HIGH", circular(7600.0, 0.0, start)),
+            object("SAT-GEO", circular(42164.0, 0.0, start)),
+            object("SAT-OLD", circular(7000.0, 0.0, start - Duration::days(MAX_PROPAGATION_DAYS + 1))),
+        ];
+        let options = ScreeningOptions {
+            threshold_km: 2.0,
+            window: Duration::hours(1),
+            workers: 4,
+            ..Default::default()
+        };
+        let screening = screen(objects.clone(), start, &options).await;
+        assert_eq!(screening.objects, 5);
+        assert_eq!(screening.skipped.len(), 1);
+        assert_eq!(screening.skipped[0].0, "SAT-OLD");
+        assert_eq!(screening.pairs, 3);
+
+        assert_eq!(screening.approaches.len(), 1, "{:?}", screening.approaches);
+        let approach = &screening.approaches[0];
+        assert_eq!((approach.object1_id.as_str(), approach.object2_id.as_str()), ("SAT-EQ", "SAT-POLAR"));
+        assert!((approach.tca - tca).num_milliseconds().abs() < 100, "{}", approach.tca);
+        assert!((approach.miss_distance_km - 0.5).abs() < 1e-3, "{}", approach.miss_distance_km);
+        assert!((approach.relative_speed_km_s - 7.546 * 2f64.sqrt()).abs() < 0.01);
+        assert_eq!(approach.state1.epoch, Some(approach.tca));
+        assert!((approach.state2.x_km - 7000.0).abs() < 0.01);
+
+        // One worker finds the same
+        let single = screen(objects.clone(), start, &ScreeningOptions { workers: 1, ..options.clone() }).await;
+        assert_eq!(single.approaches.len(), 1);
+        assert_eq!(single.approaches[0].tca, approach.tca);
+
+        // So do the shards screened in separate parts
+        let plan = ScreeningPlan::new(&objects, start, &options);
+        let parts = plan.split(3);
+        assert_eq!(parts.iter().map(Vec::len).sum::<usize>(), 3);
+        assert!(parts.iter().all(|part| !part.is_empty()));
+        let mut found = Vec::new();
+        for pairs in parts {
+            found.push(screen_pairs(plan.objects.clone(), pairs, start, &options).await);
+        }
+        let split = plan.finish(found, &options);
+        assert_eq!((split.objects, split.pairs, split.skipped.len()), (5, 3, 1));
+        assert_eq!(split.approaches.len(), 1);
+        assert_eq!(split.approaches[0].tca, approach.tca);
+    }
+}
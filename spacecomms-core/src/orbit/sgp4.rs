@@ -0,0 +1,206 @@
+//! SGP4 initialization
+//!
+//! Mean elements published in OMMs and TLEs are not osculating elements:
+//! they are fitted to the SGP4 theory and only reproduce the tracked orbit
+//! when run back through it. This module performs SGP4 initialization for an
+//! element set and evaluates the model at the element epoch, giving the TEME
+//! state vector the catalog intended. Only the near-Earth branch is
+//! implemented; deep-space sets (periods of 225 minutes or more) skip the
+//! lunar-solar periodics, which are worth a few kilometres at epoch.
+
+use crate::{Error, Result};
+use std::f64::consts::PI;
+
+/// WGS-72 gravitational parameter (km³/s²), as used by SGP4
+const MU_KM3_S2: f64 = 398600.8;
+
+/// WGS-72 equatorial radius (km)
+const RADIUS_KM: f64 = 6378.135;
+
+/// WGS-72 zonal harmonics
+const J2: f64 = 0.001082616;
+const J3: f64 = -0.00000253881;
+
+/// Period above which SGP4 switches to its deep-space branch (minutes)
+pub const DEEP_SPACE_PERIOD_MINUTES: f64 = 225.0;
+
+/// SGP4 mean elements, angles in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeanElements {
+    /// Kozai mean motion (revolutions per day)
+    pub mean_motion_rev_day: f64,
+    pub eccentricity: f64,
+    pub inclination_deg: f64,
+    pub raan_deg: f64,
+    pub arg_of_perigee_deg: f64,
+    pub mean_anomaly_deg: f64,
+}
+
+impl MeanElements {
+    /// Orbital period in minutes
+    pub fn period_minutes(&self) -> f64 {
+        1440.0 / self.mean_motion_rev_day
+    }
+
+    /// Whether SGP4 treats these elements as deep space
+    pub fn is_deep_space(&self) -> bool {
+        self.period_minutes() >= DEEP_SPACE_PERIOD_MINUTES
+    }
+}
+
+/// Time units per minute: sqrt(mu / R³) with R in Earth radii
+fn xke() -> f64 {
+    60.0 / (RADIUS_KM.powi(3) / MU_KM3_S2).sqrt()
+}
+
+/// TEME position (km) and velocity (km/s) at the element epoch
+pub fn sgp4_epoch_state(elements: &MeanElements) -> Result<[f64; 6]> {
+    let ecco = elements.eccentricity;
+    if !(0.0..1.0).contains(&ecco) {
+        return Err(Error::Propagation(format!("eccentricity {} is outside [0, 1)", ecco)));
+    }
+    if !elements.mean_motion_rev_day.is_finite() || elements.mean_motion_rev_day <= 0.0 {
+        return Err(Error::Propagation(format!(
+            "mean motion {} rev/day must be positive",
+            elements.mean_motion_rev_day
+        )));
+    }
+    if !(0.0..=180.0).contains(&elements.inclination_deg) {
+        return Err(Error::Propagation(format!(
+            "inclination {} deg is outside [0, 180]",
+            elements.inclination_deg
+        )));
+    }
+
+    let xke = xke();
+    let j3oj2 = J3 / J2;
+    let no_kozai = elements.mean_motion_rev_day * 2.0 * PI / 1440.0;
+    let inclo = elements.inclination_deg.to_radians();
+    let nodeo = elements.raan_deg.to_radians();
+    let argpo = elements.arg_of_perigee_deg.to_radians();
+    let mo = elements.mean_anomaly_deg.to_radians();
+
+    // Recover the Brouwer mean motion and semi-major axis from the Kozai value
+    let omeosq = 1.0 - ecco * ecco;
+    let rteosq = omeosq.sqrt();
+    let cosio = inclo.cos();
+    let cosio2 = cosio * cosio;
+    let sinio = inclo.sin();
+    let ak = (xke / no_kozai).powf(2.0 / 3.0);
+    let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+    let del = d1 / (ak * ak);
+    let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+    let del = d1 / (adel * adel);
+    let no = no_kozai / (1.0 + del);
+    let ao = (xke / no).powf(2.0 / 3.0);
+
+    let con41 = 3.0 * cosio2 - 1.0;
+    let x1mth2 = 1.0 - cosio2;
+    let x7thm1 = 7.0 * cosio2 - 1.0;
+    let xlcof_denominator = if (cosio + 1.0).abs() > 1.5e-12 { 1.0 + cosio } else { 1.5e-12 };
+    let xlcof = -0.25 * j3oj2 * sinio * (3.0 + 5.0 * cosio) / xlcof_denominator;
+    let aycof = -0.5 * j3oj2 * sinio;
+
+    // Secular and drag terms vanish at epoch; apply the long-period periodics
+    let am = ao;
+    let axnl = ecco * argpo.cos();
+    let temp = 1.0 / (am * omeosq);
+    let aynl = ecco * argpo.sin() + temp * aycof;
+    let xl = mo + argpo + nodeo + temp * xlcof * axnl;
+
+    // Kepler's equation in equinoctial form
+    let u = (xl - nodeo).rem_euclid(2.0 * PI);
+    let mut eo1 = u;
+    let (mut sineo1, mut coseo1) = (eo1.sin(), eo1.cos());
+    for _ in 0..10 {
+        sineo1 = eo1.sin();
+        coseo1 = eo1.cos();
+        let step = (u - aynl * coseo1 + axnl * sineo1 - eo1) / (1.0 - coseo1 * axnl - sineo1 * aynl);
+        let step = step.clamp(-0.95, 0.95);
+        eo1 += step;
+        if step.abs() < 1e-12 {
+            break;
+        }
+    }
+
+    // Short-period periodics
+    let ecose = axnl * coseo1 + aynl * sineo1;
+    let esine = axnl * sineo1 - aynl * coseo1;
+    let el2 = axnl * axnl + aynl * aynl;
+    let pl = am * (1.0 - el2);
+    if pl < 0.0 {
+        return Err(Error::Propagation("semi-latus rectum is negative".to_string()));
+    }
+    let rl = am * (1.0 - ecose);
+    let rdotl = am.sqrt() * esine / rl;
+    let rvdotl = pl.sqrt() / rl;
+    let betal = (1.0 - el2).sqrt();
+    let temp = esine / (1.0 + betal);
+    let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+    let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+    let su = sinu.atan2(cosu);
+    let sin2u = 2.0 * cosu * sinu;
+    let cos2u = 1.0 - 2.0 * sinu * sinu;
+    let temp = 1.0 / pl;
+    let temp1 = 0.5 * J2 * temp;
+    let temp2 = temp1 * temp;
+
+    let mrt = rl * (1.0 - 1.5 * temp2 * betal * con41) + 0.5 * temp1 * x1mth2 * cos2u;
+    if mrt < 1.0 {
+        return Err(Error::Propagation("elements describe an orbit inside the Earth".to_string()));
+    }
+    let su = su - 0.25 * temp2 * x7thm1 * sin2u;
+    let xnode = nodeo + 1.5 * temp2 * cosio * sin2u;
+    let xinc = inclo + 1.5 * temp2 * cosio * sinio * cos2u;
+    let mvt = rdotl - no * temp1 * x1mth2 * sin2u / xke;
+    let rvdot = rvdotl + no * temp1 * (x1mth2 * cos2u + 1.5 * con41) / xke;
+
+    // Orientation vectors
+    let (sinsu, cossu) = su.sin_cos();
+    let (snod, cnod) = xnode.sin_cos();
+    let (sini, cosi) = xinc.sin_cos();
+    let xmx = -snod * cosi;
+    let xmy = cnod * cosi;
+    let ux = [xmx * sinsu + cnod * cossu, xmy * sinsu + snod * cossu, sini * sinsu];
+    let vx = [xmx * cossu - cnod * sinsu, xmy * cossu - snod * sinsu, sini * cossu];
+
+    let vkmpersec = RADIUS_KM * xke / 60.0;
+    let mut state = [0.0; 6];
+    for i in 0..3 {
+        state[i] = mrt * ux[i] * RADIUS_KM;
+        state[i + 3] = (mvt * ux[i] + rvdot * vx[i]) * vkmpersec;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_state_matches_reference() {
+        // Satellite 00005 from the SGP4 verification set, evaluated at epoch
+        let elements = MeanElements {
+            mean_motion_rev_day: 10.82419157,
+            eccentricity: 0.1859667,
+            inclination_deg: 34.2682,
+            raan_deg: 348.7242,
+            arg_of_perigee_deg: 331.7664,
+            mean_anomaly_deg: 19.3264,
+        };
+        assert!(!elements.is_deep_space());
+        let state = sgp4_epoch_state(&elements).unwrap();
+        let expected = [7022.46529266, -1400.08296755, 0.03995155, 1.893841015, 6.405893759, 4.534807250];
+        for (got, want) in state[..3].iter().zip(&expected[..3]) {
+            assert!((got - want).abs() < 1e-3, "position {} vs {}", got, want);
+        }
+        for (got, want) in state[3..].iter().zip(&expected[3..]) {
+            assert!((got - want).abs() < 1e-6, "velocity {} vs {}", got, want);
+        }
+
+        let decayed = MeanElements { mean_motion_rev_day: 18.0, ..elements };
+        assert!(matches!(sgp4_epoch_state(&decayed), Err(Error::Propagation(_))));
+        let hyperbolic = MeanElements { eccentricity: 1.2, ..elements };
+        assert!(sgp4_epoch_state(&hyperbolic).is_err());
+    }
+}
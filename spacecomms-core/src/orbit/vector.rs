@@ -0,0 +1,84 @@
+//! Three-vector arithmetic and the RTN frame
+//!
+//! The RTN (radial, transverse, normal) frame of a state has R along the
+//! position, N along the orbital angular momentum and T completing the
+//! right-handed set, close to the velocity for a near-circular orbit.
+
+use crate::protocol::StateVector;
+
+pub(crate) type Vec3 = [f64; 3];
+
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn norm(a: Vec3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub(crate) fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: Vec3, s: f64) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+/// `a` scaled to length 1; `None` for a zero or non-finite vector
+pub(crate) fn unit(a: Vec3) -> Option<Vec3> {
+    let length = norm(a);
+    (length > 0.0 && length.is_finite()).then(|| scale(a, 1.0 / length))
+}
+
+/// Position and velocity of a state (km, km/s)
+pub(crate) fn position_velocity(sv: &StateVector) -> (Vec3, Vec3) {
+    ([sv.x_km, sv.y_km, sv.z_km], [sv.vx_km_s, sv.vy_km_s, sv.vz_km_s])
+}
+
+/// Unit R, T and N axes of the RTN frame at position `r` with velocity `v`;
+/// `None` when the two are parallel or either is zero
+pub(crate) fn rtn_basis(r: Vec3, v: Vec3) -> Option<[Vec3; 3]> {
+    let radial = unit(r)?;
+    let normal = unit(cross(r, v))?;
+    Some([radial, cross(normal, radial), normal])
+}
+
+/// Position and velocity of `secondary` relative to `primary`, in the
+/// primary's RTN frame (km, km/s)
+pub(crate) fn relative_rtn(primary: &StateVector, secondary: &StateVector) -> Option<(Vec3, Vec3)> {
+    let (r, v) = position_velocity(primary);
+    let (r2, v2) = position_velocity(secondary);
+    let axes = rtn_basis(r, v)?;
+    let rtn = |d: Vec3| axes.map(|axis| dot(d, axis));
+    Some((rtn(sub(r2, r)), rtn(sub(v2, v))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_rtn() {
+        let sv = |x: f64, y: f64, vx: f64, vy: f64, vz: f64| StateVector {
+            reference_frame: "EME2000".into(),
+            epoch: None,
+            x_km: x,
+            y_km: y,
+            z_km: 0.0,
+            vx_km_s: vx,
+            vy_km_s: vy,
+            vz_km_s: vz,
+        };
+        // Primary on the x axis moving along y: R = x, T = y, N = z
+        let primary = sv(7000.0, 0.0, 0.0, 7.5, 0.0);
+        let (position, velocity) = relative_rtn(&primary, &sv(7001.0, 2.0, 0.0, 7.5, 0.1)).unwrap();
+        assert!(norm(sub(position, [1.0, 2.0, 0.0])) < 1e-12);
+        assert!(norm(sub(velocity, [0.0, 0.0, 0.1])) < 1e-12);
+        assert!(relative_rtn(&sv(7000.0, 0.0, 1.0, 0.0, 0.0), &primary).is_none());
+        assert_eq!(unit([0.0; 3]), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Protocol version compatibility
+//!
+//! Features beyond the base message types are each offered as a HELLO
+//! capability and recorded here with the protocol version that introduced
+//! them. A session uses a feature only when the peer offers it, the version
+//! negotiated in the handshake includes it and it is not disabled locally.
+//! A 1.1 node peering with a 1.0 node therefore negotiates 1.0 and falls
+//! back, for that session only, to the 1.0 behavior of every later feature;
+//! base CDMs, object states and maneuvers flow either way.
+
+use crate::protocol::{
+    HelloPayload, CAPABILITY_ACK, CAPABILITY_BATCHING, CAPABILITY_CDM_QUERY, CAPABILITY_DELIVERY_RECEIPT,
+    CAPABILITY_ENCODING_CBOR, CAPABILITY_GRPC_STREAM, CAPABILITY_INTERESTS, CAPABILITY_PAYLOAD_GZIP,
+    CAPABILITY_SCREENING, CAPABILITY_SIGNING, CAPABILITY_SYNC_DIGEST,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Protocol version offered in HELLO by default
+pub const LATEST_VERSION: &str = "1.1";
+
+/// Protocol versions this node speaks, oldest first
+pub const SUPPORTED_VERSIONS: &[&str] = &["1.0", LATEST_VERSION];
+
+/// A protocol feature negotiated as a capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Feature {
+    /// Capability advertised in HELLO
+    pub capability: &'static str,
+    /// First protocol version including it
+    pub since: &'static str,
+    /// What a session without it does instead
+    pub fallback: &'static str,
+}
+
+/// Negotiated features, by the version that introduced them
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        capability: CAPABILITY_ENCODING_CBOR,
+        since: "1.0",
+        fallback: "envelopes are sent as JSON",
+    },
+    Feature {
+        capability: CAPABILITY_GRPC_STREAM,
+        since: "1.0",
+        fallback: "envelopes are sent over HTTP",
+    },
+    Feature {
+        capability: CAPABILITY_CDM_QUERY,
+        since: "1.0",
+        fallback: "CDMs are not pulled from the peer",
+    },
+    Feature {
+        capability: CAPABILITY_BATCHING,
+        since: "1.1",
+        fallback: "envelopes are sent one per request",
+    },
+    Feature {
+        capability: CAPABILITY_INTERESTS,
+        since: "1.1",
+        fallback: "every announcement is sent, and interests are neither advertised nor taken",
+    },
+    Feature {
+        capability: CAPABILITY_PAYLOAD_GZIP,
+        since: "1.1",
+        fallback: "payloads are sent uncompressed",
+    },
+    Feature {
+        capability: CAPABILITY_SYNC_DIGEST,
+        since: "1.1",
+        fallback: "no digest sync with the peer",
+    },
+    Feature {
+        capability: CAPABILITY_ACK,
+        since: "1.1",
+        fallback: "CDM announcements count as delivered once sent",
+    },
+    Feature {
+        capability: CAPABILITY_DELIVERY_RECEIPT,
+        since: "1.1",
+        fallback: "no delivery receipts are routed through the peer",
+    },
+    Feature {
+        capability: CAPABILITY_SIGNING,
+        since: "1.1",
+        fallback: "CDMs are sent without their provenance chain",
+    },
+    Feature {
+        capability: CAPABILITY_SCREENING,
+        since: "1.1",
+        fallback: "no screening shards are sent to the peer",
+    },
+];
+
+/// Major and minor number of a version such as `1.1` or `1.0.0`
+pub fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.parse().ok()?,
+        None => 0,
+    };
+    Some((major, minor))
+}
+
+/// The negotiated feature behind a capability; None for the base message
+/// categories and capabilities this node does not know
+pub fn feature(capability: &str) -> Option<&'static Feature> {
+    FEATURES.iter().find(|f| f.capability == capability)
+}
+
+/// Whether a session at `version` may use a feature
+pub fn version_includes(version: &str, feature: &Feature) -> bool {
+    match (parse_version(version), parse_version(feature.since)) {
+        (Some((major, minor)), Some((since_major, since_minor))) => {
+            major == since_major && minor >= since_minor
+        }
+        _ => false,
+    }
+}
+
+/// Whether a node at `version`, with `disabled` features turned off, may
+/// offer or use a capability; capabilities outside [`FEATURES`] always may
+pub fn allows(capability: &str, version: &str, disabled: &[String]) -> bool {
+    match feature(capability) {
+        Some(feature) => version_includes(version, feature) && !disabled.iter().any(|d| d == capability),
+        None => true,
+    }
+}
+
+/// Cut a peer's HELLO down to what a session at the negotiated `version`
+/// uses, returning the capabilities left out
+///
+/// Interests the peer sent are dropped along with `INTERESTS`.
+pub fn downgrade(remote: &mut HelloPayload, version: &str, disabled: &[String]) -> Vec<String> {
+    let (kept, withheld) = std::mem::take(&mut remote.capabilities)
+        .into_iter()
+        .partition(|c| allows(c, version, disabled));
+    remote.capabilities = kept;
+    if !remote.has_capability(CAPABILITY_INTERESTS) {
+        remote.interests = None;
+    }
+    withheld
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{negotiate_version, Interests, VersionNegotiationResult};
+
+    #[test]
+    fn test_downgrade_to_one_zero() {
+        assert_eq!(parse_version("1.0.0"), Some((1, 0)));
+        assert_eq!(parse_version("2"), Some((2, 0)));
+        assert_eq!(parse_version("one"), None);
+        assert!(FEATURES.iter().all(|f| SUPPORTED_VERSIONS.contains(&f.since)));
+
+        let local = HelloPayload::default();
+        let mut remote = HelloPayload {
+            protocol_version: "1.0".to_string(),
+            interests: Some(Interests {
+                object_ids: vec!["NORAD-4*".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let VersionNegotiationResult::Compatible(version) = negotiate_version(&local, &remote) else {
+            panic!("Expected compatible");
+        };
+        assert_eq!(version, "1.0");
+        let withheld = downgrade(&mut remote, &version, &[]);
+        assert!(withheld.iter().all(|c| feature(c).is_some_and(|f| f.since == "1.1")));
+        assert!(withheld.contains(&CAPABILITY_SIGNING.to_string()));
+        assert!(remote.has_capability("CDM") && remote.has_capability(CAPABILITY_CDM_QUERY));
+        assert!(!remote.has_capability(CAPABILITY_BATCHING));
+        assert!(remote.interests.is_none());
+
+        // A feature disabled locally is left out of a 1.1 session
+        let mut remote = HelloPayload::default();
+        let withheld = downgrade(&mut remote, "1.1", &[CAPABILITY_ACK.to_string()]);
+        assert_eq!(withheld, [CAPABILITY_ACK]);
+        assert!(remote.has_capability(CAPABILITY_SIGNING));
+    }
+}
@@ -0,0 +1,65 @@
+//! Payload compression
+//!
+//! Large payloads, such as bulk CDM responses, can be sent compressed to
+//! peers that offer `PAYLOAD_GZIP`. The envelope header stays readable:
+//! only the payload is replaced by its compressed JSON encoding, as base64
+//! text, and `payload_encoding` names the compression. Receivers inflate the
+//! payload before validating it.
+
+use crate::{Error, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use utoipa::ToSchema;
+
+/// Largest payload inflated from a reply, where no envelope limit applies
+pub const MAX_INFLATED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Compression of an envelope payload on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    /// gzip (RFC 1952), negotiated via `PAYLOAD_GZIP`
+    Gzip,
+}
+
+impl PayloadEncoding {
+    /// Compress a payload's JSON encoding into its wire text
+    pub fn compress(&self, json: &[u8]) -> Result<String> {
+        match self {
+            PayloadEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(json)?;
+                Ok(STANDARD.encode(encoder.finish()?))
+            }
+        }
+    }
+
+    /// Recover a payload's JSON encoding from its wire text, refusing to
+    /// inflate it beyond `max_bytes`
+    pub fn decompress(&self, text: &str, max_bytes: usize) -> Result<Vec<u8>> {
+        let compressed = STANDARD
+            .decode(text)
+            .map_err(|e| Error::Protocol(format!("compressed payload is not base64: {}", e)))?;
+        match self {
+            PayloadEncoding::Gzip => {
+                let mut json = Vec::new();
+                GzDecoder::new(compressed.as_slice())
+                    .take(max_bytes as u64 + 1)
+                    .read_to_end(&mut json)
+                    .map_err(|e| Error::Protocol(format!("invalid gzip payload: {}", e)))?;
+                if json.len() > max_bytes {
+                    return Err(Error::LimitExceeded(format!(
+                        "compressed payload inflates beyond {} bytes",
+                        max_bytes
+                    )));
+                }
+                Ok(json)
+            }
+        }
+    }
+}
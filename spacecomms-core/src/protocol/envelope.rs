@@ -1,14 +1,60 @@
 //! Protocol message envelope
 
+use crate::protocol::timestamp::{self, format_timestamp, TimestampFormat};
+use crate::protocol::{ErrorPayload, Payload, PayloadEncoding, ProvenanceHop};
+use crate::{Error, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Protocol version
 pub const PROTOCOL_VERSION: &str = "1.0.0";
 
+/// TTL of envelopes created without a per-message-type TTL
+pub const DEFAULT_TTL: u32 = 10;
+
+/// Content type of JSON-encoded envelopes
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// Content type of CBOR-encoded envelopes
+pub const CONTENT_TYPE_CBOR: &str = "application/cbor";
+
+/// Wire encoding of an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// JSON text (always supported)
+    #[default]
+    Json,
+    /// CBOR binary (RFC 8949), negotiated via `ENCODING_CBOR`
+    Cbor,
+}
+
+impl Encoding {
+    /// HTTP content type for this encoding
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Json => CONTENT_TYPE_JSON,
+            Encoding::Cbor => CONTENT_TYPE_CBOR,
+        }
+    }
+
+    /// Select an encoding from an HTTP content type, ignoring parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        if media_type.eq_ignore_ascii_case(CONTENT_TYPE_JSON) {
+            Some(Encoding::Json)
+        } else if media_type.eq_ignore_ascii_case(CONTENT_TYPE_CBOR) {
+            Some(Encoding::Cbor)
+        } else {
+            None
+        }
+    }
+}
+
 /// Message envelope wrapping all protocol messages
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Envelope {
     /// Protocol version
     pub protocol_version: String,
@@ -17,6 +63,7 @@ pub struct Envelope {
     pub message_id: String,
     
     /// Message timestamp
+    #[serde(deserialize_with = "timestamp::tolerant")]
     pub timestamp: DateTime<Utc>,
     
     /// Source node identifier
@@ -30,11 +77,52 @@ pub struct Envelope {
     
     /// Time to live (max remaining hops)
     pub ttl: u32,
+
+    /// Sequence number on the link it arrived over, set by the sending link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+
+    /// W3C trace context of the hop that sent this envelope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+
+    /// Compression of the payload, which is then the compressed JSON as
+    /// base64 text; received payloads are inflated before they are handled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_encoding: Option<PayloadEncoding>,
+
+    /// Nodes a CDM_ANNOUNCE passed through, originator first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<ProvenanceHop>,
     
     /// Message payload
-    pub payload: serde_json::Value,
+    #[schema(value_type = Object)]
+    pub payload: Payload,
 }
 
+/// Envelope fields ahead of the payload, borrowed for encoding
+#[derive(Serialize)]
+struct Header<'a> {
+    protocol_version: &'a str,
+    message_id: &'a str,
+    timestamp: String,
+    source_node_id: &'a str,
+    message_type: &'a MessageType,
+    hop_count: u32,
+    ttl: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequence: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    traceparent: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_encoding: Option<PayloadEncoding>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    provenance: &'a [ProvenanceHop],
+}
+
+/// Room reserved for the header when encoding
+const HEADER_CAPACITY: usize = 512;
+
 impl Envelope {
     /// Create a new envelope for a message
     pub fn new(
@@ -49,8 +137,21 @@ impl Envelope {
             source_node_id,
             message_type,
             hop_count: 0,
-            ttl: 10,
-        payload,
+            ttl: DEFAULT_TTL,
+            sequence: None,
+            traceparent: None,
+            payload_encoding: None,
+            provenance: Vec::new(),
+            payload: payload.into(),
+        }
+    }
+
+    /// Create an ERROR envelope; errors are never forwarded
+    pub fn error(source_node_id: String, payload: ErrorPayload) -> Self {
+        let payload = serde_json::to_value(payload).unwrap_or_default();
+        Self {
+            ttl: 1,
+            ..Self::new(source_node_id, MessageType::Error, payload)
         }
     }
 
@@ -68,18 +169,125 @@ impl Envelope {
             message_type: self.message_type.clone(),
             hop_count: self.hop_count + 1,
             ttl: self.ttl - 1,
+            sequence: None,
+            // Replaced by the forwarding span's context when this node exports traces
+            traceparent: self.traceparent.clone(),
+            payload_encoding: self.payload_encoding,
+            provenance: self.provenance.clone(),
             payload: self.payload.clone(),
         })
     }
 
+    /// Copy of this envelope with its payload compressed
+    ///
+    /// Timestamps in the payload are written with the given profile before
+    /// compressing. Copies sharing a payload compress it once.
+    pub fn compressed(&self, format: TimestampFormat) -> Result<Self> {
+        if self.payload_encoding.is_some() {
+            return Ok(self.clone());
+        }
+        let text = self.payload.to_gzip(format)?;
+        Ok(Self {
+            payload_encoding: Some(PayloadEncoding::Gzip),
+            payload: serde_json::Value::String(text.to_string()).into(),
+            ..self.clone()
+        })
+    }
+
+    /// Replace a compressed payload with its content, refusing one that
+    /// inflates beyond `max_bytes` of JSON
+    pub fn inflate(&mut self, max_bytes: usize) -> Result<()> {
+        let Some(encoding) = self.payload_encoding else {
+            return Ok(());
+        };
+        let text = self
+            .payload
+            .as_str()
+            .ok_or_else(|| Error::Protocol(format!("{:?} payload must be a string", encoding)))?;
+        let json = encoding.decompress(text, max_bytes)?;
+        self.payload = serde_json::from_slice::<serde_json::Value>(&json)?.into();
+        self.payload_encoding = None;
+        Ok(())
+    }
+
     /// Check if this message can be forwarded
     pub fn can_forward(&self) -> bool {
         self.ttl > 0
     }
+
+    /// Encode this envelope as CBOR
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes).map_err(|e| Error::Cbor(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Decode an envelope from CBOR
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        ciborium::from_reader(bytes).map_err(|e| Error::Cbor(e.to_string()))
+    }
+
+    /// Encode this envelope with the given encoding
+    pub fn encode(&self, encoding: Encoding) -> Result<Vec<u8>> {
+        self.encode_with(encoding, TimestampFormat::Auto)
+    }
+
+    /// JSON encoding: the header is written around the payload's shared
+    /// encoding instead of serializing the payload again
+    fn to_json(&self, format: TimestampFormat) -> Result<Vec<u8>> {
+        let payload = self.payload.to_json(format)?;
+        let header = Header {
+            protocol_version: &self.protocol_version,
+            message_id: &self.message_id,
+            timestamp: format_timestamp(&self.timestamp, format),
+            source_node_id: &self.source_node_id,
+            message_type: &self.message_type,
+            hop_count: self.hop_count,
+            ttl: self.ttl,
+            sequence: self.sequence,
+            traceparent: self.traceparent.as_deref(),
+            payload_encoding: self.payload_encoding,
+            provenance: &self.provenance,
+        };
+        let mut bytes = Vec::with_capacity(HEADER_CAPACITY + payload.len());
+        serde_json::to_writer(&mut bytes, &header)?;
+        // Reopen the header object to append the payload
+        bytes.pop();
+        bytes.extend_from_slice(b",\"payload\":");
+        bytes.extend_from_slice(payload);
+        bytes.push(b'}');
+        Ok(bytes)
+    }
+
+    /// Encode this envelope with the given encoding and timestamp profile
+    ///
+    /// Every timestamp in the envelope, including those inside the payload,
+    /// is written with the profile's precision.
+    pub fn encode_with(&self, encoding: Encoding, format: TimestampFormat) -> Result<Vec<u8>> {
+        match (encoding, format) {
+            (Encoding::Json, format) => self.to_json(format),
+            (Encoding::Cbor, TimestampFormat::Auto) => self.to_cbor(),
+            (Encoding::Cbor, format) => {
+                let mut value = serde_json::to_value(self)?;
+                timestamp::reformat_timestamps(&mut value, format);
+                let mut bytes = Vec::new();
+                ciborium::into_writer(&value, &mut bytes).map_err(|e| Error::Cbor(e.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Decode an envelope with the given encoding
+    pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<Self> {
+        match encoding {
+            Encoding::Json => Ok(serde_json::from_slice(bytes)?),
+            Encoding::Cbor => Self::from_cbor(bytes),
+        }
+    }
 }
 
 /// Message type enumeration
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MessageType {
     Hello,
@@ -91,6 +299,31 @@ pub enum MessageType {
     ManeuverStatus,
     Heartbeat,
     Error,
+    CdmRequest,
+    CdmResponse,
+    InterestUpdate,
+    EnvelopeBatch,
+    SyncDigest,
+    Ack,
+    DeliveryReceipt,
+    ScreeningRequest,
+    ScreeningResponse,
+}
+
+impl MessageType {
+    /// Announcements and withdrawals: what nodes relay to each other, and
+    /// all an ENVELOPE_BATCH may carry
+    pub fn is_relayed(&self) -> bool {
+        matches!(
+            self,
+            MessageType::CdmAnnounce
+                | MessageType::CdmWithdraw
+                | MessageType::ObjectStateAnnounce
+                | MessageType::ObjectStateWithdraw
+                | MessageType::ManeuverIntent
+                | MessageType::ManeuverStatus
+        )
+    }
 }
 
 impl std::fmt::Display for MessageType {
@@ -105,6 +338,15 @@ impl std::fmt::Display for MessageType {
             MessageType::ManeuverStatus => write!(f, "MANEUVER_STATUS"),
             MessageType::Heartbeat => write!(f, "HEARTBEAT"),
             MessageType::Error => write!(f, "ERROR"),
+            MessageType::CdmRequest => write!(f, "CDM_REQUEST"),
+            MessageType::CdmResponse => write!(f, "CDM_RESPONSE"),
+            MessageType::InterestUpdate => write!(f, "INTEREST_UPDATE"),
+            MessageType::EnvelopeBatch => write!(f, "ENVELOPE_BATCH"),
+            MessageType::SyncDigest => write!(f, "SYNC_DIGEST"),
+            MessageType::Ack => write!(f, "ACK"),
+            MessageType::DeliveryReceipt => write!(f, "DELIVERY_RECEIPT"),
+            MessageType::ScreeningRequest => write!(f, "SCREENING_REQUEST"),
+            MessageType::ScreeningResponse => write!(f, "SCREENING_RESPONSE"),
         }
     }
 }
@@ -153,4 +395,137 @@ mod tests {
         assert!(!env.can_forward());
         assert!(env.forwarded().is_none());
     }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let cdm = crate::cdm::generate_demo_cdm();
+        let env = Envelope::new(
+            "node-1".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(&cdm).unwrap(),
+        );
+
+        let bytes = env.to_cbor().unwrap();
+        let decoded = Envelope::from_cbor(&bytes).unwrap();
+        assert_eq!(decoded.message_id, env.message_id);
+        assert_eq!(decoded.timestamp, env.timestamp);
+        assert_eq!(decoded.message_type, MessageType::CdmAnnounce);
+        assert_eq!(decoded.payload, env.payload);
+
+        let parsed = crate::cdm::parse_cdm(decoded.payload.into_value()).unwrap();
+        assert_eq!(parsed.cdm_id, cdm.cdm_id);
+        assert_eq!(parsed.collision_probability, cdm.collision_probability);
+    }
+
+    #[test]
+    fn test_cbor_smaller_than_json() {
+        let env = Envelope::new(
+            "node-1".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(crate::cdm::generate_demo_cdm()).unwrap(),
+        );
+
+        let json = env.encode(Encoding::Json).unwrap();
+        let cbor = env.encode(Encoding::Cbor).unwrap();
+        assert!(cbor.len() < json.len());
+        assert_eq!(Envelope::decode(&json, Encoding::Json).unwrap().payload, env.payload);
+        assert_eq!(Envelope::decode(&cbor, Encoding::Cbor).unwrap().payload, env.payload);
+    }
+
+    #[test]
+    fn test_json_encoding_matches_serde() {
+        let mut env = Envelope::new(
+            "node-1".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(crate::cdm::generate_demo_cdm()).unwrap(),
+        );
+        env.sequence = Some(7);
+        env.traceparent = Some("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".into());
+        assert_eq!(env.encode(Encoding::Json).unwrap(), serde_json::to_vec(&env).unwrap());
+
+        // Copies for other links share the payload encoding
+        let mut relayed = env.forwarded().unwrap();
+        relayed.sequence = Some(8);
+        let decoded = Envelope::decode(&relayed.encode(Encoding::Json).unwrap(), Encoding::Json).unwrap();
+        assert_eq!((decoded.hop_count, decoded.sequence), (1, Some(8)));
+        assert_eq!(decoded.payload, env.payload);
+    }
+
+    #[test]
+    fn test_compressed_payload_round_trip() {
+        let mut env = Envelope::new(
+            "node-1".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(crate::cdm::generate_demo_cdm()).unwrap(),
+        );
+        env.sequence = Some(3);
+        let compressed = env.compressed(TimestampFormat::Auto).unwrap();
+        assert_eq!(compressed.payload_encoding, Some(PayloadEncoding::Gzip));
+        assert!(compressed.payload.is_string());
+        assert_eq!(compressed.encode(Encoding::Json).unwrap(), serde_json::to_vec(&compressed).unwrap());
+
+        for encoding in [Encoding::Json, Encoding::Cbor] {
+            let bytes = compressed.encode(encoding).unwrap();
+            let mut decoded = Envelope::decode(&bytes, encoding).unwrap();
+            assert!(decoded.inflate(1024).is_err());
+            decoded.inflate(1024 * 1024).unwrap();
+            assert_eq!((decoded.payload_encoding, decoded.sequence), (None, Some(3)));
+            assert_eq!(decoded.payload, env.payload);
+        }
+
+        // Inflating an uncompressed payload changes nothing
+        env.inflate(0).unwrap();
+        let mut garbled = compressed.clone();
+        garbled.payload = serde_json::json!("bm90IGd6aXA=").into();
+        assert!(garbled.inflate(1024 * 1024).is_err());
+    }
+
+    #[test]
+    fn test_invalid_cbor_rejected() {
+        assert!(Envelope::from_cbor(&[0xff, 0x00, 0x13]).is_err());
+    }
+
+    #[test]
+    fn test_encoding_from_content_type() {
+        assert_eq!(Encoding::from_content_type("application/json"), Some(Encoding::Json));
+        assert_eq!(Encoding::from_content_type("application/json; charset=utf-8"), Some(Encoding::Json));
+        assert_eq!(Encoding::from_content_type("Application/CBOR"), Some(Encoding::Cbor));
+        assert_eq!(Encoding::from_content_type("text/plain"), None);
+    }
+
+    #[test]
+    fn test_encode_with_timestamp_profile() {
+        let env = Envelope::new(
+            "node-1".to_string(),
+            MessageType::CdmAnnounce,
+            serde_json::to_value(crate::cdm::generate_demo_cdm()).unwrap(),
+        );
+
+        for encoding in [Encoding::Json, Encoding::Cbor] {
+            let bytes = env.encode_with(encoding, TimestampFormat::Millis).unwrap();
+            let decoded = Envelope::decode(&bytes, encoding).unwrap();
+            assert_eq!(decoded.timestamp.timestamp_millis(), env.timestamp.timestamp_millis());
+            assert_eq!(decoded.timestamp.timestamp_subsec_nanos() % 1_000_000, 0);
+
+            let tca = decoded.payload["tca"].as_str().unwrap();
+            assert!(tca.ends_with('Z'));
+            assert_eq!(tca.split('.').nth(1).map(str::len), Some(4));
+        }
+    }
+
+    #[test]
+    fn test_tolerant_envelope_timestamp() {
+        let json = r#"{
+            "protocol_version": "1.0.0",
+            "message_id": "msg-1",
+            "timestamp": "2024-01-15 12:00:00.123",
+            "source_node_id": "node-1",
+            "message_type": "HEARTBEAT",
+            "hop_count": 0,
+            "ttl": 10,
+            "payload": {}
+        }"#;
+        let env: Envelope = serde_json::from_str(json).unwrap();
+        assert_eq!(env.timestamp.to_rfc3339(), "2024-01-15T12:00:00.123+00:00");
+    }
 }
@@ -0,0 +1,207 @@
+//! Replay protection
+//!
+//! Receivers reject envelopes created too long ago or too far in the
+//! future, so a captured envelope cannot be replayed once the dedup cache
+//! has forgotten its message ID. Envelopes sent over a peer link also carry
+//! a per-link sequence number; a receiver accepts each number from a peer
+//! once. Sequence numbers start from the sender's clock in microseconds when
+//! the link is set up, so they keep increasing across reconnects and
+//! restarts.
+//!
+//! Peers' clocks are not trusted to agree with ours. Each HELLO exchange and
+//! heartbeat gives a sample of a peer's clock offset, and the median of the
+//! recent samples is subtracted from the peer's own envelope timestamps
+//! before their age is judged.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// How far behind the highest sequence number seen from a peer a late
+/// envelope may arrive (concurrent sends can be delivered out of order)
+pub const SEQUENCE_WINDOW: u64 = 128;
+
+/// Reject a timestamp older than `max_age_seconds` or more than
+/// `max_skew_seconds` ahead of `now`; a limit of 0 disables that side
+pub fn check_timestamp(
+    timestamp: DateTime<Utc>,
+    now: DateTime<Utc>,
+    max_age_seconds: u64,
+    max_skew_seconds: u64,
+) -> Result<()> {
+    let age = now - timestamp;
+    if max_age_seconds > 0 && age > Duration::seconds(max_age_seconds as i64) {
+        return Err(Error::Replay(format!(
+            "envelope is {}s old, limit is {}s",
+            age.num_seconds(),
+            max_age_seconds
+        )));
+    }
+    if max_skew_seconds > 0 && -age > Duration::seconds(max_skew_seconds as i64) {
+        return Err(Error::Replay(format!(
+            "envelope is {}s in the future, limit is {}s",
+            (-age).num_seconds(),
+            max_skew_seconds
+        )));
+    }
+    Ok(())
+}
+
+/// Clock offset samples kept per peer
+pub const CLOCK_SAMPLES: usize = 8;
+
+/// Recent clock offset samples from one peer, in milliseconds (positive
+/// when the peer's clock is ahead)
+#[derive(Debug, Clone, Default)]
+pub struct ClockOffsetEstimator {
+    samples: VecDeque<i64>,
+}
+
+impl ClockOffsetEstimator {
+    /// Add a sample, dropping the oldest beyond [`CLOCK_SAMPLES`]
+    pub fn add(&mut self, offset_ms: i64) {
+        self.samples.push_back(offset_ms);
+        if self.samples.len() > CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Median of the recent samples; one delayed message does not move it
+    pub fn offset_ms(&self) -> Option<i64> {
+        let mut sorted: Vec<i64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        match sorted.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(sorted[n / 2]),
+            n => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2),
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+/// Offset of a peer's clock from a request sent at `sent` and answered
+/// with a message stamped `remote` that arrived at `received`, assuming the
+/// two legs took equally long
+pub fn round_trip_offset(sent: DateTime<Utc>, remote: DateTime<Utc>, received: DateTime<Utc>) -> i64 {
+    let midpoint = sent + (received - sent) / 2;
+    (remote - midpoint).num_milliseconds()
+}
+
+/// A peer's timestamp on this node's clock, correcting at most
+/// `max_correction_seconds` of offset
+pub fn correct_timestamp(timestamp: DateTime<Utc>, offset_ms: i64, max_correction_seconds: u64) -> DateTime<Utc> {
+    let limit = (max_correction_seconds as i64).saturating_mul(1000);
+    timestamp - Duration::milliseconds(offset_ms.clamp(-limit, limit))
+}
+
+/// First sequence number for a new link
+pub fn initial_sequence() -> u64 {
+    Utc::now().timestamp_micros().max(0) as u64
+}
+
+/// Sequence numbers seen from one peer
+///
+/// Tracks the highest number and which of the [`SEQUENCE_WINDOW`] numbers
+/// below it have arrived, so envelopes reordered in flight are accepted
+/// once and anything older is refused.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceWindow {
+    highest: u64,
+    /// Bit `n` is set once `highest - n` has been seen
+    seen: u128,
+}
+
+impl SequenceWindow {
+    /// Accept a sequence number not seen before and within the window
+    pub fn check(&mut self, sequence: u64) -> Result<()> {
+        if self.seen == 0 || sequence > self.highest {
+            let shift = sequence.saturating_sub(self.highest);
+            self.seen = if self.seen == 0 || shift >= SEQUENCE_WINDOW {
+                1
+            } else {
+                (self.seen << shift) | 1
+            };
+            self.highest = sequence;
+            return Ok(());
+        }
+        let offset = self.highest - sequence;
+        if offset >= SEQUENCE_WINDOW {
+            return Err(Error::Replay(format!(
+                "sequence {} is more than {} behind {}",
+                sequence, SEQUENCE_WINDOW, self.highest
+            )));
+        }
+        if self.seen & (1 << offset) != 0 {
+            return Err(Error::Replay(format!("sequence {} was already received", sequence)));
+        }
+        self.seen |= 1 << offset;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_timestamp() {
+        let now = Utc::now();
+        assert!(check_timestamp(now - Duration::seconds(59), now, 60, 10).is_ok());
+        assert!(check_timestamp(now + Duration::seconds(9), now, 60, 10).is_ok());
+        assert!(matches!(
+            check_timestamp(now - Duration::seconds(61), now, 60, 10),
+            Err(Error::Replay(_))
+        ));
+        assert!(check_timestamp(now + Duration::seconds(11), now, 60, 10).is_err());
+        // 0 disables a limit
+        assert!(check_timestamp(now - Duration::days(400), now, 0, 10).is_ok());
+        assert!(check_timestamp(now + Duration::days(1), now, 60, 0).is_ok());
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let mut estimator = ClockOffsetEstimator::default();
+        assert_eq!(estimator.offset_ms(), None);
+        // One slow heartbeat does not move the median
+        for offset in [600_000, 600_200, 599_900, 540_000] {
+            estimator.add(offset);
+        }
+        assert_eq!(estimator.offset_ms(), Some(599_950));
+        for _ in 0..CLOCK_SAMPLES {
+            estimator.add(-2000);
+        }
+        assert_eq!((estimator.offset_ms(), estimator.samples()), (Some(-2000), CLOCK_SAMPLES));
+
+        let sent = Utc::now();
+        let offset = round_trip_offset(sent, sent + Duration::seconds(30), sent + Duration::milliseconds(400));
+        assert_eq!(offset, 29_800);
+
+        // A peer ten minutes ahead is fresh once corrected, within the limit
+        let now = Utc::now();
+        let stamped = now + Duration::minutes(10);
+        assert!(check_timestamp(correct_timestamp(stamped, 600_000, 3600), now, 60, 10).is_ok());
+        assert!(check_timestamp(correct_timestamp(stamped, 600_000, 60), now, 60, 10).is_err());
+    }
+
+    #[test]
+    fn test_sequence_window() {
+        let mut window = SequenceWindow::default();
+        for sequence in [1000, 1002, 1001, 1005] {
+            window.check(sequence).unwrap();
+        }
+        // Replays of accepted numbers
+        for sequence in [1000, 1001, 1005] {
+            assert!(matches!(window.check(sequence), Err(Error::Replay(_))));
+        }
+        // Late but unseen numbers inside the window are accepted once
+        window.check(1003).unwrap();
+        assert!(window.check(1003).is_err());
+
+        window.check(1005 + SEQUENCE_WINDOW).unwrap();
+        assert!(window.check(1004).is_err());
+        window.check(1006).unwrap();
+    }
+}
@@ -1,7 +1,12 @@
 //! Protocol message types
 
+use crate::orbit::{CloseApproach, PropagationModel, ScreenedObject, MAX_PROPAGATION_DAYS};
+use crate::protocol::{parse_version, Envelope, LATEST_VERSION, SUPPORTED_VERSIONS};
+use crate::{Error, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::ToSchema;
 
 // ============================================================================
 // HELLO Message
@@ -25,22 +30,100 @@ pub struct HelloPayload {
     /// Optional authentication token
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auth_token: Option<String>,
+
+    /// Port of the gRPC envelope stream listener (with `GRPC_STREAM`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_port: Option<u16>,
+
+    /// Base URL the sender is reached at, when configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advertise_address: Option<String>,
+
+    /// Objects the sender wants announcements about; absent means all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interests: Option<Interests>,
+
+    /// The sender has shut the session down for maintenance and neither
+    /// sends to nor accepts from the receiver until it is re-enabled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub admin_down: bool,
+
+    /// Operator's note on the shutdown, such as a maintenance window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_down_reason: Option<String>,
+}
+
+impl HelloPayload {
+    /// Check whether the sender advertised a capability
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
 }
 
 impl Default for HelloPayload {
     fn default() -> Self {
         Self {
             node_name: "SpaceComms Node".to_string(),
-            protocol_version: PROTOCOL_VERSION.to_string(),
-            capabilities: vec!["CDM".to_string(), "OBJECT_STATE".to_string(), "MANEUVER".to_string()],
-            supported_versions: vec!["1.0".to_string(), "1.1".to_string()],
+            protocol_version: LATEST_VERSION.to_string(),
+            capabilities: vec![
+                "CDM".to_string(),
+                "OBJECT_STATE".to_string(),
+                "MANEUVER".to_string(),
+                CAPABILITY_ENCODING_CBOR.to_string(),
+                CAPABILITY_CDM_QUERY.to_string(),
+                CAPABILITY_INTERESTS.to_string(),
+                CAPABILITY_BATCHING.to_string(),
+                CAPABILITY_PAYLOAD_GZIP.to_string(),
+                CAPABILITY_SYNC_DIGEST.to_string(),
+                CAPABILITY_ACK.to_string(),
+                CAPABILITY_DELIVERY_RECEIPT.to_string(),
+                CAPABILITY_SIGNING.to_string(),
+                CAPABILITY_SCREENING.to_string(),
+            ],
+            supported_versions: SUPPORTED_VERSIONS.iter().map(|v| v.to_string()).collect(),
             auth_token: None,
+            grpc_port: None,
+            advertise_address: None,
+            interests: None,
+            admin_down: false,
+            admin_down_reason: None,
         }
     }
 }
 
-/// Current protocol version
-pub const PROTOCOL_VERSION: &str = "1.0";
+/// Capability: node accepts a bidirectional gRPC envelope stream
+pub const CAPABILITY_GRPC_STREAM: &str = "GRPC_STREAM";
+
+/// Capability: node accepts CBOR-encoded envelopes on the protocol endpoint
+pub const CAPABILITY_ENCODING_CBOR: &str = "ENCODING_CBOR";
+
+/// Capability: node answers CDM_REQUEST queries
+pub const CAPABILITY_CDM_QUERY: &str = "CDM_QUERY";
+
+/// Capability: node accepts INTEREST_UPDATE
+pub const CAPABILITY_INTERESTS: &str = "INTERESTS";
+
+/// Capability: node accepts ENVELOPE_BATCH
+pub const CAPABILITY_BATCHING: &str = "BATCHING";
+
+/// Capability: node inflates gzip-compressed payloads
+pub const CAPABILITY_PAYLOAD_GZIP: &str = "PAYLOAD_GZIP";
+
+/// Capability: node answers SYNC_DIGEST
+pub const CAPABILITY_SYNC_DIGEST: &str = "SYNC_DIGEST";
+
+/// Capability: node acknowledges CDM announcements and withdrawals
+pub const CAPABILITY_ACK: &str = "ACK";
+
+/// Capability: node takes in DELIVERY_RECEIPT and passes it on
+pub const CAPABILITY_DELIVERY_RECEIPT: &str = "DELIVERY_RECEIPT";
+
+/// Capability: node carries the signed provenance chain of CDM_ANNOUNCE
+pub const CAPABILITY_SIGNING: &str = "SIGNING";
+
+/// Capability: node answers SCREENING_REQUEST
+pub const CAPABILITY_SCREENING: &str = "SCREENING";
+
 
 /// Result of version negotiation
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -53,18 +136,6 @@ pub enum VersionNegotiationResult {
 
 /// Negotiate protocol version between two nodes
 pub fn negotiate_version(local: &HelloPayload, remote: &HelloPayload) -> VersionNegotiationResult {
-    // Parse major.minor from version strings
-    let parse_version = |v: &str| -> Option<(u32, u32)> {
-        let parts: Vec<&str> = v.split('.').collect();
-        if parts.len() >= 2 {
-            Some((parts[0].parse().ok()?, parts[1].parse().ok()?))
-        } else if parts.len() == 1 {
-            Some((parts[0].parse().ok()?, 0))
-        } else {
-            None
-        }
-    };
-
     let local_version = parse_version(&local.protocol_version);
     let remote_version = parse_version(&remote.protocol_version);
 
@@ -100,6 +171,32 @@ pub fn negotiate_version(local: &HelloPayload, remote: &HelloPayload) -> Version
 mod version_tests {
     use super::*;
 
+    #[test]
+    fn test_interests() {
+        assert!(Interests::default().wants_object("NORAD-1", None));
+        let interests = Interests {
+            object_ids: vec!["NORAD-4*".to_string(), "SAT-*-B".to_string(), "DEB-7".to_string()],
+            owners: vec!["spacex".to_string()],
+        };
+        assert!(!interests.is_all());
+        assert!(interests.wants_object("NORAD-44713", None));
+        assert!(interests.wants_object("SAT-12-B", None));
+        assert!(interests.wants_object("DEB-7", None));
+        assert!(!interests.wants_object("DEB-71", None));
+        assert!(!interests.wants_object("SAT-12-BX", None));
+        assert!(!interests.wants_object("NORAD-12345", Some("OneWeb")));
+        assert!(interests.wants_object("NORAD-12345", Some("SpaceX")));
+
+        let everything = Interests {
+            owners: vec!["*".to_string()],
+            ..Default::default()
+        };
+        assert!(everything.is_all());
+        assert!(everything.wants_object("NORAD-12345", None));
+        assert!(wildcard_match("A*A", "AA"));
+        assert!(!wildcard_match("AB*BA", "ABA"));
+    }
+
     #[test]
     fn test_same_version_compatible() {
         let local = HelloPayload {
@@ -159,13 +256,17 @@ mod version_tests {
 // ============================================================================
 
 /// State vector in a reference frame
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct StateVector {
     /// Reference frame (e.g., "TEME", "ITRF")
     pub reference_frame: String,
     
     /// Epoch of state vector
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::protocol::timestamp::tolerant_option"
+    )]
     pub epoch: Option<DateTime<Utc>>,
     
     /// X position in km
@@ -188,7 +289,7 @@ pub struct StateVector {
 }
 
 /// Covariance matrix in RTN frame
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CovarianceRtn {
     /// Reference frame
     #[serde(default = "default_rtn")]
@@ -221,7 +322,7 @@ fn default_rtn() -> String {
 }
 
 /// Object type enumeration
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ObjectType {
     Payload,
@@ -230,6 +331,15 @@ pub enum ObjectType {
     Unknown,
 }
 
+/// Radar cross-section size class, as published by object catalogs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RcsSize {
+    Small,
+    Medium,
+    Large,
+}
+
 /// Object state announcement payload
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectStateAnnouncePayload {
@@ -247,6 +357,7 @@ pub struct ObjectStateAnnouncePayload {
     pub owner_operator: Option<String>,
     
     /// State vector epoch
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub epoch: DateTime<Utc>,
     
     /// State vector
@@ -262,7 +373,7 @@ pub struct ObjectStateAnnouncePayload {
 }
 
 /// Reason for object state withdrawal
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum WithdrawReason {
     Decayed,
@@ -281,6 +392,7 @@ pub struct ObjectStateWithdrawPayload {
     pub reason: WithdrawReason,
     
     /// When withdrawal takes effect
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub effective_time: DateTime<Utc>,
 }
 
@@ -312,9 +424,175 @@ pub struct CdmWithdrawPayload {
     pub superseded_by: Option<String>,
     
     /// When withdrawal takes effect
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub effective_time: DateTime<Utc>,
 }
 
+/// Filter for CDMs pulled from a peer; unset fields do not filter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CdmQuery {
+    /// Only these CDMs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cdm_ids: Vec<String>,
+
+    /// Only CDMs involving one of these objects (either side)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub object_ids: Vec<String>,
+
+    /// Only CDMs with TCA at or after this time
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::protocol::timestamp::tolerant_option"
+    )]
+    pub tca_from: Option<DateTime<Utc>>,
+
+    /// Only CDMs with TCA before this time
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::protocol::timestamp::tolerant_option"
+    )]
+    pub tca_to: Option<DateTime<Utc>>,
+
+    /// Most CDMs wanted; the responder may return fewer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// CDM query payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdmRequestPayload {
+    /// Echoed in the response
+    pub request_id: String,
+
+    #[serde(flatten)]
+    pub query: CdmQuery,
+}
+
+/// Answer to a CDM query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdmResponsePayload {
+    /// ID of the request being answered
+    pub request_id: String,
+
+    /// Matching active CDMs, ordered by TCA
+    pub cdms: Vec<serde_json::Value>,
+
+    /// More CDMs matched than were returned
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Most buckets a SYNC_DIGEST may carry
+pub const MAX_DIGEST_BUCKETS: usize = 4096;
+
+/// Digest of a node's active CDMs, exchanged to find where two nodes differ
+///
+/// Each CDM ID falls in a bucket chosen by its hash. A bucket's checksum
+/// combines the hashes of its CDMs' IDs and creation dates, and the root
+/// hashes every bucket checksum in order, so equal roots mean equal sets.
+/// Checksums are 16 hex digits. The request carries `buckets`; the reply
+/// carries the responder's `entries` in the buckets whose checksums differ.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncDigestPayload {
+    /// Echoed in the reply
+    pub request_id: String,
+
+    /// Checksum over all buckets
+    pub root: String,
+
+    /// Checksum of each bucket, in order (request only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<String>,
+
+    /// The responder's CDMs in the differing buckets (reply only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<DigestEntry>,
+
+    /// The differing buckets held more CDMs than the reply lists
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// A CDM version listed in a SYNC_DIGEST reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestEntry {
+    pub cdm_id: String,
+
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
+    pub creation_date: DateTime<Utc>,
+}
+
+// ============================================================================
+// Interests
+// ============================================================================
+
+/// Objects a node wants CDM and object state announcements about
+///
+/// An object is of interest when its ID matches one of `object_ids` or its
+/// owner matches one of `owners` (ignoring case). Patterns match the whole
+/// value, with `*` standing for any run of characters: `NORAD-4*` is a
+/// prefix and `*` matches everything. With both lists empty the node wants
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Interests {
+    /// Object ID patterns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub object_ids: Vec<String>,
+
+    /// Owner/operator name patterns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+}
+
+impl Interests {
+    /// Whether every object is of interest
+    pub fn is_all(&self) -> bool {
+        (self.object_ids.is_empty() && self.owners.is_empty())
+            || self.object_ids.iter().any(|p| p == "*")
+            || self.owners.iter().any(|p| p == "*")
+    }
+
+    /// Whether an object is of interest
+    pub fn wants_object(&self, object_id: &str, owner: Option<&str>) -> bool {
+        self.is_all()
+            || self.object_ids.iter().any(|p| wildcard_match(p, object_id))
+            || owner.is_some_and(|owner| {
+                let owner = owner.to_lowercase();
+                self.owners.iter().any(|p| wildcard_match(&p.to_lowercase(), &owner))
+            })
+    }
+}
+
+/// Match `value` against a pattern where `*` stands for any run of characters
+pub(crate) fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Replacement interests for the rest of a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterestUpdatePayload {
+    #[serde(flatten)]
+    pub interests: Interests,
+}
+
 // ============================================================================
 // MANEUVER Messages
 // ============================================================================
@@ -364,6 +642,7 @@ pub struct ManeuverIntentPayload {
     pub related_cdm_id: Option<String>,
     
     /// Planned burn start time
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
     pub planned_start: DateTime<Utc>,
     
     /// Planned burn duration in seconds
@@ -405,7 +684,11 @@ pub struct ManeuverStatusPayload {
     pub status: ManeuverStatusType,
     
     /// Actual burn start time
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::protocol::timestamp::tolerant_option"
+    )]
     pub actual_start: Option<DateTime<Utc>>,
     
     /// Actual burn duration in seconds
@@ -438,6 +721,182 @@ pub struct HeartbeatPayload {
     /// Number of active CDMs
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cdms_active: Option<u64>,
+
+    /// Envelopes the sender will take from the receiver until its next
+    /// heartbeat; absent means no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit_window: Option<u64>,
+
+    /// The last heartbeat received from the receiver, for it to measure the
+    /// round trip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub echo: Option<HeartbeatEcho>,
+}
+
+/// A heartbeat handed back to its sender
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatEcho {
+    /// `sequence` of the heartbeat received
+    pub sequence: u64,
+    /// Milliseconds between receiving it and sending this one
+    pub held_ms: u64,
+}
+
+// ============================================================================
+// ENVELOPE_BATCH Message
+// ============================================================================
+
+/// Most envelopes in one ENVELOPE_BATCH
+pub const MAX_BATCH_ENVELOPES: usize = 1000;
+
+/// Envelopes sent to a peer in one request
+///
+/// A batch carries announcements and withdrawals only. The receiver handles
+/// each as if it had arrived alone; its reply, if any, is a batch of ERROR
+/// envelopes for the ones it refused and ACK envelopes for the ones it
+/// acknowledges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvelopeBatchPayload {
+    /// In send order
+    pub envelopes: Vec<Envelope>,
+}
+
+// ============================================================================
+// ACK Message
+// ============================================================================
+
+/// Acknowledgement of a CDM_ANNOUNCE or CDM_WITHDRAW
+///
+/// Sent to a peer offering ACK for each of those it forwarded that this node
+/// took in, including duplicates and CDMs it quarantined: as the reply on
+/// the protocol endpoint, or on the stream for gRPC peers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckPayload {
+    /// ID of the envelope acknowledged
+    pub related_message_id: String,
+}
+
+// ============================================================================
+// DELIVERY_RECEIPT Message
+// ============================================================================
+
+/// Confirmation that a CDM reached a node watching one of its objects
+///
+/// Sent by that node toward the CDM's originator: straight to it when the
+/// two are peers, otherwise to the node before it in the CDM's provenance,
+/// which passes it on the same way. Only sent to peers offering
+/// DELIVERY_RECEIPT.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryReceiptPayload {
+    pub cdm_id: String,
+    /// ID of the CDM_ANNOUNCE received
+    pub related_message_id: String,
+    /// Node the receipt is bound for
+    pub originator_node_id: String,
+    /// Node whose watchlist matched
+    pub receiving_node_id: String,
+    /// Watched objects the CDM involves
+    pub matched_object_ids: Vec<String>,
+    /// Hops the CDM took to reach the receiving node
+    pub hop_count: u32,
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
+    pub received_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// SCREENING_REQUEST / SCREENING_RESPONSE Messages
+// ============================================================================
+
+/// Most object pairs in one SCREENING_REQUEST
+pub const MAX_SCREENING_PAIRS: usize = 10_000;
+
+/// Most objects in one SCREENING_REQUEST, keeping it within the default
+/// `protocol.max_envelope_bytes`
+pub const MAX_SCREENING_OBJECTS: usize = 2_000;
+
+/// Finest sample spacing a SCREENING_REQUEST may ask for (seconds)
+pub const MIN_SCREENING_STEP_SECONDS: f64 = 1.0;
+
+/// Object pairs of a conjunction screening handed to a peer
+///
+/// Each object's state is taken to be at `start`, whatever epoch it
+/// carries; pairs name objects by their index in `objects`. The peer screens
+/// the pairs over the window and answers with a SCREENING_RESPONSE.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreeningRequestPayload {
+    /// Echoed in the response
+    pub request_id: String,
+
+    /// Start of the window screened
+    #[serde(deserialize_with = "crate::protocol::timestamp::tolerant")]
+    pub start: DateTime<Utc>,
+
+    /// Length of the window
+    pub window_seconds: u64,
+
+    /// Spacing of the coarse distance samples
+    pub step_seconds: f64,
+
+    /// Separation at or below which an approach is reported (km)
+    pub threshold_km: f64,
+
+    /// Force model to propagate with
+    #[serde(default)]
+    pub model: PropagationModel,
+
+    pub objects: Vec<ScreenedObject>,
+
+    /// Pairs to compare, as indexes into `objects`
+    pub pairs: Vec<(usize, usize)>,
+}
+
+impl ScreeningRequestPayload {
+    /// Check the request against the limits above and its pairs against
+    /// `objects`
+    pub fn check(&self) -> Result<()> {
+        if self.pairs.len() > MAX_SCREENING_PAIRS || self.objects.len() > MAX_SCREENING_OBJECTS {
+            return Err(Error::LimitExceeded(format!(
+                "screening of {} pairs of {} objects exceeds {} pairs or {} objects",
+                self.pairs.len(),
+                self.objects.len(),
+                MAX_SCREENING_PAIRS,
+                MAX_SCREENING_OBJECTS
+            )));
+        }
+        if self.window_seconds == 0 || self.window_seconds > MAX_PROPAGATION_DAYS as u64 * 86_400 {
+            return Err(Error::Protocol(format!(
+                "screening window must be non-zero and at most {} days",
+                MAX_PROPAGATION_DAYS
+            )));
+        }
+        if !(self.step_seconds >= MIN_SCREENING_STEP_SECONDS && self.step_seconds.is_finite()) {
+            return Err(Error::Protocol(format!(
+                "screening step must be at least {} s",
+                MIN_SCREENING_STEP_SECONDS
+            )));
+        }
+        if !(self.threshold_km >= 0.0 && self.threshold_km.is_finite()) {
+            return Err(Error::Protocol("screening threshold must be non-negative".to_string()));
+        }
+        let count = self.objects.len();
+        if let Some((i, j)) = self.pairs.iter().find(|(i, j)| i == j || *i >= count || *j >= count) {
+            return Err(Error::Protocol(format!("pair ({}, {}) does not name two of {} objects", i, j, count)));
+        }
+        Ok(())
+    }
+}
+
+/// Close approaches found among the pairs of a SCREENING_REQUEST
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreeningResponsePayload {
+    /// ID of the request being answered
+    pub request_id: String,
+
+    pub approaches: Vec<CloseApproach>,
+
+    /// Objects dropped part-way through the window, as `[object_id, reason]`
+    #[serde(default)]
+    pub skipped: Vec<(String, String)>,
 }
 
 // ============================================================================
@@ -455,6 +914,9 @@ pub enum ErrorCode {
     InternalError,
 }
 
+/// Message sent to peers in place of the details of an internal error
+pub const INTERNAL_ERROR_MESSAGE: &str = "internal error on the receiving node";
+
 /// Error payload
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorPayload {
@@ -468,3 +930,25 @@ pub struct ErrorPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub related_message_id: Option<String>,
 }
+
+impl ErrorPayload {
+    /// Describe a processing failure for the peer that sent a message
+    ///
+    /// Failures on this node (storage, I/O, configuration and the like)
+    /// are logged here and reported to the peer only as an internal error,
+    /// so their details stay on this node.
+    pub fn from_error(err: &crate::Error, related_message_id: Option<String>) -> Self {
+        let error_code = err.error_code();
+        let error_message = if error_code == ErrorCode::InternalError {
+            error!("Internal error handling message {:?}: {}", related_message_id, err);
+            INTERNAL_ERROR_MESSAGE.to_string()
+        } else {
+            err.to_string()
+        };
+        Self {
+            error_code,
+            error_message,
+            related_message_id,
+        }
+    }
+}
@@ -1,7 +1,31 @@
 //! Protocol module - message types and encoding
 
+mod compatibility;
+mod compression;
 mod envelope;
+mod freshness;
 mod messages;
+mod payload;
+mod provenance;
+pub mod timestamp;
+mod validation;
 
-pub use envelope::*;
+pub use envelope::{
+    Encoding, Envelope, MessageType, CONTENT_TYPE_CBOR, CONTENT_TYPE_JSON, DEFAULT_TTL, PROTOCOL_VERSION,
+};
+pub use compatibility::{
+    allows, downgrade, feature, parse_version, version_includes, Feature, FEATURES, LATEST_VERSION,
+    SUPPORTED_VERSIONS,
+};
+pub use compression::{PayloadEncoding, MAX_INFLATED_BYTES};
+pub use freshness::{
+    check_timestamp, correct_timestamp, initial_sequence, round_trip_offset, ClockOffsetEstimator, SequenceWindow,
+    CLOCK_SAMPLES, SEQUENCE_WINDOW,
+};
 pub use messages::*;
+pub use payload::Payload;
+pub use provenance::{
+    attest, check_public_key, payload_digest, verify_hop, ProvenanceHop, ProvenanceSigner, MAX_PROVENANCE_HOPS,
+};
+pub use timestamp::{format_timestamp, parse_timestamp, TimestampFormat};
+pub use validation::{payload_depth, validate_envelope, EnvelopeLimits};
@@ -0,0 +1,163 @@
+//! Shared envelope payloads
+//!
+//! A CDM announcement goes to every peer with the same payload; only the
+//! hop fields in the envelope header differ. [`Payload`] lets the copies
+//! share one JSON value and encodes it once per timestamp profile, so
+//! fanning out to many peers serializes each payload once.
+
+use crate::protocol::timestamp::{self, TimestampFormat};
+use crate::protocol::PayloadEncoding;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+/// Timestamp profiles, indexing the JSON encodings of a payload
+const PROFILES: [TimestampFormat; 5] = [
+    TimestampFormat::Auto,
+    TimestampFormat::Seconds,
+    TimestampFormat::Millis,
+    TimestampFormat::Micros,
+    TimestampFormat::Nanos,
+];
+
+/// Message payload, cheap to clone and encoded at most once per timestamp
+/// profile
+///
+/// Reads go through `Deref` to the JSON value; [`Payload::make_mut`]
+/// gives a copy of its own to change.
+#[derive(Clone, Default)]
+pub struct Payload(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    value: serde_json::Value,
+    /// JSON encodings, by position in [`PROFILES`]
+    json: [OnceLock<Vec<u8>>; 5],
+    /// gzip-compressed JSON encodings as base64 text, likewise
+    gzip: [OnceLock<String>; 5],
+}
+
+impl Payload {
+    pub fn new(value: serde_json::Value) -> Self {
+        Self(Arc::new(Inner {
+            value,
+            json: Default::default(),
+            gzip: Default::default(),
+        }))
+    }
+
+    /// The value to change, copied first if other envelopes share it
+    pub fn make_mut(&mut self) -> &mut serde_json::Value {
+        if Arc::get_mut(&mut self.0).is_none() {
+            *self = Self::new(self.0.value.clone());
+        }
+        let inner = Arc::get_mut(&mut self.0).expect("payload was just made unique");
+        inner.json = Default::default();
+        inner.gzip = Default::default();
+        &mut inner.value
+    }
+
+    /// Deserialize the payload as a message type
+    pub fn parse<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        T::deserialize(&self.0.value)
+    }
+
+    pub fn into_value(self) -> serde_json::Value {
+        Arc::try_unwrap(self.0).map_or_else(|shared| shared.value.clone(), |inner| inner.value)
+    }
+
+    /// JSON encoding with timestamps in the given profile, computed once
+    pub fn to_json(&self, format: TimestampFormat) -> serde_json::Result<&[u8]> {
+        let slot = &self.0.json[PROFILES.iter().position(|f| *f == format).unwrap_or(0)];
+        if let Some(bytes) = slot.get() {
+            return Ok(bytes);
+        }
+        let bytes = if format == TimestampFormat::Auto {
+            serde_json::to_vec(&self.0.value)?
+        } else {
+            let mut value = self.0.value.clone();
+            timestamp::reformat_timestamps(&mut value, format);
+            serde_json::to_vec(&value)?
+        };
+        // A racing encoder may have filled the slot; both results are equal
+        Ok(slot.get_or_init(|| bytes))
+    }
+
+    /// Compressed JSON encoding as base64 text, computed once per profile
+    pub fn to_gzip(&self, format: TimestampFormat) -> crate::Result<&str> {
+        let slot = &self.0.gzip[PROFILES.iter().position(|f| *f == format).unwrap_or(0)];
+        if let Some(text) = slot.get() {
+            return Ok(text);
+        }
+        let text = PayloadEncoding::Gzip.compress(self.to_json(format)?)?;
+        Ok(slot.get_or_init(|| text))
+    }
+}
+
+impl Deref for Payload {
+    type Target = serde_json::Value;
+
+    fn deref(&self) -> &serde_json::Value {
+        &self.0.value
+    }
+}
+
+impl From<serde_json::Value> for Payload {
+    fn from(value: serde_json::Value) -> Self {
+        Self::new(value)
+    }
+}
+
+impl PartialEq for Payload {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.value == other.0.value
+    }
+}
+
+impl fmt::Debug for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0.value, f)
+    }
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.value, f)
+    }
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.0.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        serde_json::Value::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_copies_share_until_changed() {
+        let original = Payload::new(json!({ "tca": "2024-01-15T14:30:00.123456Z", "n": 1 }));
+        let encoded = original.to_json(TimestampFormat::Millis).unwrap().as_ptr();
+        let copy = original.clone();
+        assert_eq!(copy.to_json(TimestampFormat::Millis).unwrap().as_ptr(), encoded);
+        assert_eq!(copy.to_json(TimestampFormat::Millis).unwrap(), br#"{"n":1,"tca":"2024-01-15T14:30:00.123Z"}"#);
+
+        let mut changed = copy.clone();
+        changed.make_mut()["n"] = json!(2);
+        assert_eq!(changed["n"], 2);
+        assert_eq!(original["n"], 1);
+        assert_eq!(changed.to_json(TimestampFormat::Auto).unwrap(), br#"{"n":2,"tca":"2024-01-15T14:30:00.123456Z"}"#);
+        assert_eq!(copy.into_value(), original.into_value());
+    }
+}
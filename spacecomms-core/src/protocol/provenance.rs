@@ -0,0 +1,217 @@
+//! CDM provenance chain
+//!
+//! A CDM_ANNOUNCE carries the path it took: the originating node and each
+//! node that received and passed it on append a hop with their ID and the
+//! time they received it, with a SHA-256 digest of the CDM payload as it
+//! arrived. A node with `provenance.signing_key` signs its hop with Ed25519
+//! over the CDM ID, its node ID, the receive time in whole seconds, the
+//! payload digest and the previous hop's signature, so a signed hop cannot be
+//! moved to another CDM, reordered or kept on a changed payload without its
+//! signature failing. Hops are verified against the public keys an operator
+//! trusts; unsigned hops are reported as they are.
+//!
+//! The digest is taken over the payload's JSON with keys in sorted order and
+//! timestamps in whole seconds, the encoding every timestamp profile
+//! preserves. A relay that redacts fields changes the payload, so hops signed
+//! before it no longer verify downstream.
+
+use crate::protocol::timestamp::{self, TimestampFormat};
+use crate::{Error, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, SubsecRound, Utc};
+use rand::RngCore;
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Most hops a received chain may carry
+pub const MAX_PROVENANCE_HOPS: usize = 64;
+
+/// One node's attestation that it received a CDM
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProvenanceHop {
+    pub node_id: String,
+    /// When the node received the CDM, in whole seconds so every timestamp
+    /// profile keeps it exactly
+    #[serde(deserialize_with = "timestamp::tolerant")]
+    pub received_at: DateTime<Utc>,
+    /// SHA-256 of the CDM payload as the node received it, base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_sha256: Option<String>,
+    /// Ed25519 signature, base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// SHA-256 of a CDM payload's canonical encoding, base64
+pub fn payload_digest(payload: &serde_json::Value) -> String {
+    let mut canonical = payload.clone();
+    timestamp::reformat_timestamps(&mut canonical, TimestampFormat::Seconds);
+    let bytes = serde_json::to_vec(&canonical).unwrap_or_default();
+    STANDARD.encode(digest(&SHA256, &bytes).as_ref())
+}
+
+/// What a hop's signature covers
+fn signing_input(cdm_id: &str, hop: &ProvenanceHop, previous: Option<&ProvenanceHop>) -> Vec<u8> {
+    let previous = previous.and_then(|p| p.signature.as_deref()).unwrap_or("");
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        cdm_id,
+        hop.node_id,
+        hop.received_at.timestamp(),
+        hop.payload_sha256.as_deref().unwrap_or(""),
+        previous
+    )
+    .into_bytes()
+}
+
+fn decode_key(key: &str, what: &str) -> Result<Vec<u8>> {
+    let bytes = STANDARD
+        .decode(key.trim())
+        .map_err(|e| Error::Config(format!("{} is not base64: {}", what, e)))?;
+    if bytes.len() != 32 {
+        return Err(Error::Config(format!("{} must be 32 bytes, got {}", what, bytes.len())));
+    }
+    Ok(bytes)
+}
+
+/// Check that a base64 Ed25519 public key is well formed
+pub fn check_public_key(key: &str) -> Result<()> {
+    decode_key(key, "provenance public key").map(|_| ())
+}
+
+/// Signs this node's provenance hops
+pub struct ProvenanceSigner {
+    key_pair: Ed25519KeyPair,
+}
+
+impl ProvenanceSigner {
+    /// Signer from a base64 32-byte Ed25519 seed
+    pub fn from_seed(seed: &str) -> Result<Self> {
+        let seed = decode_key(seed, "provenance.signing_key")?;
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&seed)
+            .map_err(|_| Error::Config("provenance.signing_key is not a valid Ed25519 seed".into()))?;
+        Ok(Self { key_pair })
+    }
+
+    /// A new random seed, base64, for `provenance.signing_key`
+    pub fn generate_seed() -> String {
+        let mut seed = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut seed);
+        STANDARD.encode(seed)
+    }
+
+    /// Public key, base64, for other operators' `provenance.trusted_keys`
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.key_pair.public_key().as_ref())
+    }
+
+    fn sign(&self, cdm_id: &str, hop: &ProvenanceHop, previous: Option<&ProvenanceHop>) -> String {
+        let signature = self.key_pair.sign(&signing_input(cdm_id, hop, previous));
+        STANDARD.encode(signature.as_ref())
+    }
+}
+
+/// Append this node's hop for the CDM payload it received to the CDM's
+/// chain, signed when a signer is given
+pub fn attest(
+    chain: &mut Vec<ProvenanceHop>,
+    cdm_id: &str,
+    payload: &serde_json::Value,
+    node_id: &str,
+    signer: Option<&ProvenanceSigner>,
+) {
+    let mut hop = ProvenanceHop {
+        node_id: node_id.to_string(),
+        received_at: Utc::now().trunc_subsecs(0),
+        payload_sha256: Some(payload_digest(payload)),
+        signature: None,
+    };
+    if let Some(signer) = signer {
+        hop.signature = Some(signer.sign(cdm_id, &hop, chain.last()));
+    }
+    chain.push(hop);
+}
+
+/// Whether a hop carries a valid signature from the holder of `public_key`
+/// over the payload with digest `payload_sha256`
+pub fn verify_hop(
+    public_key: &str,
+    cdm_id: &str,
+    payload_sha256: &str,
+    hop: &ProvenanceHop,
+    previous: Option<&ProvenanceHop>,
+) -> bool {
+    if hop.payload_sha256.as_deref() != Some(payload_sha256) {
+        return false;
+    }
+    let (Ok(key), Some(signature)) = (decode_key(public_key, "public key"), hop.signature.as_deref()) else {
+        return false;
+    };
+    let Ok(signature) = STANDARD.decode(signature) else {
+        return false;
+    };
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(&signing_input(cdm_id, hop, previous), &signature)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance_chain() {
+        let origin = ProvenanceSigner::from_seed(&ProvenanceSigner::generate_seed()).unwrap();
+        let relay = ProvenanceSigner::from_seed(&ProvenanceSigner::generate_seed()).unwrap();
+        let payload = serde_json::json!({"cdm_id": "CDM-1", "tca": "2024-01-15T12:00:00.250Z", "miss_distance_m": 150.0});
+        let digest = payload_digest(&payload);
+        let mut chain = Vec::new();
+        attest(&mut chain, "CDM-1", &payload, "node-a", Some(&origin));
+        attest(&mut chain, "CDM-1", &payload, "node-b", None);
+        attest(&mut chain, "CDM-1", &payload, "node-c", Some(&relay));
+        assert_eq!(chain.len(), 3);
+        assert!(chain[1].signature.is_none());
+        assert_eq!(chain[1].payload_sha256.as_deref(), Some(digest.as_str()));
+
+        assert!(verify_hop(&origin.public_key(), "CDM-1", &digest, &chain[0], None));
+        assert!(verify_hop(&relay.public_key(), "CDM-1", &digest, &chain[2], Some(&chain[1])));
+        // Wrong key, another CDM, or a changed receive time
+        assert!(!verify_hop(&relay.public_key(), "CDM-1", &digest, &chain[0], None));
+        assert!(!verify_hop(&origin.public_key(), "CDM-2", &digest, &chain[0], None));
+        let mut moved = chain[0].clone();
+        moved.received_at += chrono::Duration::seconds(1);
+        assert!(!verify_hop(&origin.public_key(), "CDM-1", &digest, &moved, None));
+
+        // The signed time survives a coarse timestamp profile
+        let json = serde_json::to_string(&chain[0]).unwrap();
+        assert_eq!(serde_json::from_str::<ProvenanceHop>(&json).unwrap(), chain[0]);
+        assert!(ProvenanceSigner::from_seed("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_changed_payload_fails_verification() {
+        let origin = ProvenanceSigner::from_seed(&ProvenanceSigner::generate_seed()).unwrap();
+        let payload = serde_json::json!({"cdm_id": "CDM-1", "tca": "2024-01-15T12:00:00.250Z", "miss_distance_m": 150.0});
+        let mut chain = Vec::new();
+        attest(&mut chain, "CDM-1", &payload, "node-a", Some(&origin));
+
+        // The digest ignores key order and the timestamp profile it was sent in
+        let resent = serde_json::json!({"miss_distance_m": 150.0, "tca": "2024-01-15T12:00:00Z", "cdm_id": "CDM-1"});
+        assert!(verify_hop(&origin.public_key(), "CDM-1", &payload_digest(&resent), &chain[0], None));
+
+        // A relay that changes the payload cannot keep the origin's hop valid
+        let mut tampered = payload.clone();
+        tampered["miss_distance_m"] = serde_json::json!(15000.0);
+        let digest = payload_digest(&tampered);
+        assert!(!verify_hop(&origin.public_key(), "CDM-1", &digest, &chain[0], None));
+        let mut rewritten = chain[0].clone();
+        rewritten.payload_sha256 = Some(digest.clone());
+        assert!(!verify_hop(&origin.public_key(), "CDM-1", &digest, &rewritten, None));
+        let mut unhashed = chain[0].clone();
+        unhashed.payload_sha256 = None;
+        assert!(!verify_hop(&origin.public_key(), "CDM-1", &digest, &unhashed, None));
+    }
+}
@@ -0,0 +1,216 @@
+//! Timestamp serialization profiles
+//!
+//! Outbound timestamps are RFC 3339 in UTC with a `Z` suffix; the number of
+//! fractional digits is selected per peer with [`TimestampFormat`]. Inbound
+//! timestamps are parsed tolerantly so partners using common variants (no
+//! offset, space separator, numeric offsets, CCSDS day-of-year) interoperate.
+
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
+
+/// Precision used when serializing timestamps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    /// As many fractional digits as needed (0, 3, 6 or 9)
+    #[default]
+    Auto,
+    /// Whole seconds: `2024-01-15T12:00:00Z`
+    Seconds,
+    /// Milliseconds: `2024-01-15T12:00:00.000Z`
+    Millis,
+    /// Microseconds: `2024-01-15T12:00:00.000000Z`
+    Micros,
+    /// Nanoseconds: `2024-01-15T12:00:00.000000000Z`
+    Nanos,
+}
+
+impl TimestampFormat {
+    fn seconds_format(self) -> SecondsFormat {
+        match self {
+            TimestampFormat::Auto => SecondsFormat::AutoSi,
+            TimestampFormat::Seconds => SecondsFormat::Secs,
+            TimestampFormat::Millis => SecondsFormat::Millis,
+            TimestampFormat::Micros => SecondsFormat::Micros,
+            TimestampFormat::Nanos => SecondsFormat::Nanos,
+        }
+    }
+}
+
+/// Format a timestamp using a serialization profile
+pub fn format_timestamp(timestamp: &DateTime<Utc>, format: TimestampFormat) -> String {
+    timestamp.to_rfc3339_opts(format.seconds_format(), true)
+}
+
+/// Naive layouts accepted when a timestamp carries no offset (assumed UTC)
+const NAIVE_LAYOUTS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parse a timestamp, accepting common variants of RFC 3339
+///
+/// Accepted in addition to strict RFC 3339: lowercase `t`/`z`, a space
+/// instead of `T`, numeric offsets without a colon (`+0000`), no offset at
+/// all (UTC is assumed, as in CCSDS KVN), and CCSDS day-of-year dates
+/// (`2024-015T12:00:00`).
+pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+
+    let upper = value.to_ascii_uppercase();
+    let normalized = upper.strip_suffix('Z').unwrap_or(&upper);
+
+    for layout in ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z"] {
+        if let Ok(ts) = DateTime::parse_from_str(normalized, layout) {
+            return Ok(ts.with_timezone(&Utc));
+        }
+    }
+
+    for layout in NAIVE_LAYOUTS {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(normalized, layout) {
+            return Ok(ts.and_utc());
+        }
+    }
+
+    if let Some(ts) = parse_day_of_year(normalized) {
+        return Ok(ts);
+    }
+
+    Err(Error::Protocol(format!("unrecognized timestamp: {}", value)))
+}
+
+/// Parse a CCSDS ASCII time code B timestamp (`YYYY-DDDThh:mm:ss[.f]`)
+fn parse_day_of_year(value: &str) -> Option<DateTime<Utc>> {
+    let (date, time) = value.split_once('T')?;
+    let (year, day) = date.split_once('-')?;
+    if day.len() != 3 {
+        return None;
+    }
+    let date = NaiveDate::from_yo_opt(year.parse().ok()?, day.parse().ok()?)?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f").ok()?;
+    Some(date.and_time(time).and_utc())
+}
+
+/// Rewrite every RFC 3339 timestamp string in a JSON value to a profile
+///
+/// Used when encoding envelopes for a peer, so payload timestamps (which
+/// travel as untyped JSON) follow the same profile as the envelope itself.
+pub fn reformat_timestamps(value: &mut serde_json::Value, format: TimestampFormat) {
+    match value {
+        serde_json::Value::String(s) => {
+            if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+                *s = format_timestamp(&ts.with_timezone(&Utc), format);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                reformat_timestamps(item, format);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for item in map.values_mut() {
+                reformat_timestamps(item, format);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Serde helper: `#[serde(deserialize_with = "tolerant")]`
+pub fn tolerant<'de, D>(deserializer: D) -> std::result::Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_timestamp(&value).map_err(serde::de::Error::custom)
+}
+
+/// Serde helper for optional timestamps (combine with `#[serde(default)]`)
+pub fn tolerant_option<'de, D>(deserializer: D) -> std::result::Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse_timestamp(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn sample() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, 12, 30, 45).unwrap() + chrono::Duration::nanoseconds(123_456_789)
+    }
+
+    #[test]
+    fn test_format_profiles() {
+        let ts = sample();
+        assert_eq!(format_timestamp(&ts, TimestampFormat::Auto), "2024-01-15T12:30:45.123456789Z");
+        assert_eq!(format_timestamp(&ts, TimestampFormat::Seconds), "2024-01-15T12:30:45Z");
+        assert_eq!(format_timestamp(&ts, TimestampFormat::Millis), "2024-01-15T12:30:45.123Z");
+        assert_eq!(format_timestamp(&ts, TimestampFormat::Micros), "2024-01-15T12:30:45.123456Z");
+        assert_eq!(format_timestamp(&ts, TimestampFormat::Nanos), "2024-01-15T12:30:45.123456789Z");
+    }
+
+    #[test]
+    fn test_round_trip_each_profile() {
+        let ts = sample();
+        let cases = [
+            (TimestampFormat::Auto, 123_456_789),
+            (TimestampFormat::Seconds, 0),
+            (TimestampFormat::Millis, 123_000_000),
+            (TimestampFormat::Micros, 123_456_000),
+            (TimestampFormat::Nanos, 123_456_789),
+        ];
+        for (format, nanos) in cases {
+            let parsed = parse_timestamp(&format_timestamp(&ts, format)).unwrap();
+            assert_eq!(parsed.timestamp(), ts.timestamp(), "{:?}", format);
+            assert_eq!(parsed.timestamp_subsec_nanos(), nanos, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_tolerant_parsing() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 15, 12, 30, 45).unwrap();
+        for input in [
+            "2024-01-15T12:30:45Z",
+            "2024-01-15t12:30:45z",
+            "2024-01-15 12:30:45Z",
+            "2024-01-15T12:30:45",
+            "2024-01-15T12:30:45.000",
+            "2024-01-15T12:30:45+00:00",
+            "2024-01-15T13:30:45+0100",
+            "2024-015T12:30:45",
+            " 2024-01-15T12:30:45Z ",
+        ] {
+            assert_eq!(parse_timestamp(input).unwrap(), expected, "{}", input);
+        }
+
+        assert!(parse_timestamp("yesterday").is_err());
+        assert!(parse_timestamp("2024-13-45T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn test_reformat_nested_timestamps() {
+        let mut value = serde_json::json!({
+            "cdm_id": "CDM-1",
+            "tca": "2024-01-15T12:30:45.123456789Z",
+            "objects": [{ "epoch": "2024-01-15T12:30:45Z" }],
+            "miss_distance_m": 150.0
+        });
+        reformat_timestamps(&mut value, TimestampFormat::Millis);
+        assert_eq!(value["cdm_id"], "CDM-1");
+        assert_eq!(value["tca"], "2024-01-15T12:30:45.123Z");
+        assert_eq!(value["objects"][0]["epoch"], "2024-01-15T12:30:45.000Z");
+        assert_eq!(value["miss_distance_m"], 150.0);
+    }
+}
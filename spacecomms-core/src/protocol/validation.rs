@@ -0,0 +1,273 @@
+//! Structural validation of received envelopes
+//!
+//! Checks run before an envelope is dispatched, so oversized, deeply nested
+//! or malformed messages from a peer are rejected without touching storage
+//! or routing.
+
+use crate::cdm::{validate_cdm, CdmRecord};
+use crate::protocol::{
+    AckPayload, CdmRequestPayload, CdmResponsePayload, CdmWithdrawPayload, DeliveryReceiptPayload, EnvelopeBatchPayload, InterestUpdatePayload, Envelope, ErrorPayload, HeartbeatPayload, HelloPayload, ManeuverIntentPayload,
+    MAX_BATCH_ENVELOPES, MAX_DIGEST_BUCKETS, MAX_PROVENANCE_HOPS,
+    ManeuverStatusPayload, MessageType, ObjectStateAnnouncePayload, ObjectStateWithdrawPayload, SyncDigestPayload,
+    ScreeningRequestPayload, ScreeningResponsePayload,
+};
+use crate::{Error, Result};
+use serde::de::DeserializeOwned;
+
+/// Longest accepted identifier in the envelope header
+const MAX_ID_LEN: usize = 256;
+
+/// Resource limits applied to received envelopes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvelopeLimits {
+    /// Maximum encoded envelope size in bytes, enforced while the body is
+    /// read (HTTP) or the frame is decoded (gRPC), and on inflated payloads
+    pub max_envelope_bytes: usize,
+    /// Maximum nesting depth of the payload
+    pub max_payload_depth: usize,
+}
+
+impl Default for EnvelopeLimits {
+    fn default() -> Self {
+        Self {
+            max_envelope_bytes: 1024 * 1024,
+            max_payload_depth: 32,
+        }
+    }
+}
+
+/// Nesting depth of a JSON value (scalars have depth 0)
+pub fn payload_depth(value: &serde_json::Value) -> usize {
+    // Iterative so hostile input cannot exhaust the stack
+    let mut max = 0;
+    let mut stack = vec![(value, 0usize)];
+    while let Some((value, depth)) = stack.pop() {
+        match value {
+            serde_json::Value::Array(items) => {
+                max = max.max(depth + 1);
+                stack.extend(items.iter().map(|v| (v, depth + 1)));
+            }
+            serde_json::Value::Object(map) => {
+                max = max.max(depth + 1);
+                stack.extend(map.values().map(|v| (v, depth + 1)));
+            }
+            _ => {}
+        }
+    }
+    max
+}
+
+/// Validate a received envelope's header, payload depth and payload schema
+pub fn validate_envelope(envelope: &Envelope, limits: &EnvelopeLimits) -> Result<()> {
+    for (field, value) in [
+        ("protocol_version", &envelope.protocol_version),
+        ("message_id", &envelope.message_id),
+        ("source_node_id", &envelope.source_node_id),
+    ] {
+        if value.is_empty() || value.len() > MAX_ID_LEN {
+            return Err(Error::Protocol(format!(
+                "{} must be 1-{} characters",
+                field, MAX_ID_LEN
+            )));
+        }
+    }
+    if envelope.traceparent.as_ref().is_some_and(|t| t.len() > MAX_ID_LEN) {
+        return Err(Error::Protocol(format!("traceparent must be at most {} characters", MAX_ID_LEN)));
+    }
+    if envelope.provenance.len() > MAX_PROVENANCE_HOPS {
+        return Err(Error::LimitExceeded(format!(
+            "provenance has {} hops, more than {}",
+            envelope.provenance.len(),
+            MAX_PROVENANCE_HOPS
+        )));
+    }
+    for hop in &envelope.provenance {
+        if hop.node_id.is_empty() || hop.node_id.len() > MAX_ID_LEN {
+            return Err(Error::Protocol(format!("provenance node_id must be 1-{} characters", MAX_ID_LEN)));
+        }
+        if hop.signature.as_ref().is_some_and(|s| s.len() > MAX_ID_LEN) {
+            return Err(Error::Protocol(format!(
+                "provenance signature must be at most {} characters",
+                MAX_ID_LEN
+            )));
+        }
+        if hop.payload_sha256.as_ref().is_some_and(|s| s.len() > MAX_ID_LEN) {
+            return Err(Error::Protocol(format!(
+                "provenance payload_sha256 must be at most {} characters",
+                MAX_ID_LEN
+            )));
+        }
+    }
+
+    let depth = payload_depth(&envelope.payload);
+    if depth > limits.max_payload_depth {
+        return Err(Error::LimitExceeded(format!(
+            "payload nesting depth {} exceeds {}",
+            depth, limits.max_payload_depth
+        )));
+    }
+
+    if !envelope.payload.is_object() {
+        return Err(Error::Protocol(format!(
+            "{} payload must be an object",
+            envelope.message_type
+        )));
+    }
+
+    match envelope.message_type {
+        MessageType::Hello => check_schema::<HelloPayload>(envelope),
+        MessageType::Heartbeat => check_schema::<HeartbeatPayload>(envelope),
+        MessageType::Error => check_schema::<ErrorPayload>(envelope),
+        MessageType::Ack => check_schema::<AckPayload>(envelope),
+        MessageType::DeliveryReceipt => check_schema::<DeliveryReceiptPayload>(envelope),
+        MessageType::CdmAnnounce => {
+            let cdm: CdmRecord = deserialize(envelope)?;
+            validate_cdm(&cdm)
+        }
+        MessageType::CdmWithdraw => check_schema::<CdmWithdrawPayload>(envelope),
+        MessageType::CdmRequest => check_schema::<CdmRequestPayload>(envelope),
+        MessageType::CdmResponse => check_schema::<CdmResponsePayload>(envelope),
+        MessageType::InterestUpdate => check_schema::<InterestUpdatePayload>(envelope),
+        MessageType::ObjectStateAnnounce => check_schema::<ObjectStateAnnouncePayload>(envelope),
+        MessageType::ObjectStateWithdraw => check_schema::<ObjectStateWithdrawPayload>(envelope),
+        MessageType::ManeuverIntent => check_schema::<ManeuverIntentPayload>(envelope),
+        MessageType::ManeuverStatus => check_schema::<ManeuverStatusPayload>(envelope),
+        MessageType::EnvelopeBatch => {
+            let batch: EnvelopeBatchPayload = deserialize(envelope)?;
+            if batch.envelopes.len() > MAX_BATCH_ENVELOPES {
+                return Err(Error::LimitExceeded(format!(
+                    "batch of {} envelopes exceeds {}",
+                    batch.envelopes.len(),
+                    MAX_BATCH_ENVELOPES
+                )));
+            }
+            Ok(())
+        }
+        MessageType::SyncDigest => {
+            let digest: SyncDigestPayload = deserialize(envelope)?;
+            if digest.buckets.len() > MAX_DIGEST_BUCKETS {
+                return Err(Error::LimitExceeded(format!(
+                    "digest of {} buckets exceeds {}",
+                    digest.buckets.len(),
+                    MAX_DIGEST_BUCKETS
+                )));
+            }
+            Ok(())
+        }
+        MessageType::ScreeningRequest => deserialize::<ScreeningRequestPayload>(envelope)?.check(),
+        MessageType::ScreeningResponse => check_schema::<ScreeningResponsePayload>(envelope),
+    }
+}
+
+fn check_schema<T: DeserializeOwned>(envelope: &Envelope) -> Result<()> {
+    deserialize::<T>(envelope).map(drop)
+}
+
+fn deserialize<T: DeserializeOwned>(envelope: &Envelope) -> Result<T> {
+    envelope.payload.parse::<T>()
+        .map_err(|e| Error::Protocol(format!("invalid {} payload: {}", envelope.message_type, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use crate::protocol::{MAX_SCREENING_OBJECTS, MAX_SCREENING_PAIRS};
+    use serde_json::json;
+
+    fn envelope(message_type: MessageType, payload: serde_json::Value) -> Envelope {
+        Envelope::new("node-1".to_string(), message_type, payload)
+    }
+
+    fn nested(depth: usize) -> serde_json::Value {
+        (0..depth).fold(json!(1), |inner, _| json!([inner]))
+    }
+
+    #[test]
+    fn test_payload_depth() {
+        assert_eq!(payload_depth(&json!(1)), 0);
+        assert_eq!(payload_depth(&json!({})), 1);
+        assert_eq!(payload_depth(&json!({ "a": [1, { "b": 2 }] })), 3);
+        assert_eq!(payload_depth(&nested(100)), 100);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let limits = EnvelopeLimits::default();
+        let deep = envelope(MessageType::Heartbeat, json!({ "sequence": 1, "extra": nested(40) }));
+        assert!(matches!(validate_envelope(&deep, &limits), Err(Error::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_valid_payloads() {
+        let limits = EnvelopeLimits::default();
+        let cdm = envelope(MessageType::CdmAnnounce, serde_json::to_value(generate_demo_cdm()).unwrap());
+        assert!(validate_envelope(&cdm, &limits).is_ok());
+
+        // Unknown fields are preserved for forward compatibility
+        let heartbeat = envelope(MessageType::Heartbeat, json!({ "sequence": 7, "future_field": true }));
+        assert!(validate_envelope(&heartbeat, &limits).is_ok());
+    }
+
+    #[test]
+    fn test_schema_violations() {
+        let limits = EnvelopeLimits::default();
+        let cases = [
+            envelope(MessageType::Heartbeat, json!({ "sequence": "seven" })),
+            envelope(MessageType::Heartbeat, json!([1, 2, 3])),
+            envelope(MessageType::CdmWithdraw, json!({ "cdm_id": "CDM-1" })),
+            envelope(MessageType::CdmAnnounce, json!({ "cdm_id": "CDM-1" })),
+            envelope(MessageType::ManeuverStatus, json!({ "maneuver_id": 42 })),
+        ];
+        for case in cases {
+            assert!(
+                matches!(validate_envelope(&case, &limits), Err(Error::Protocol(_))),
+                "{} {}",
+                case.message_type,
+                case.payload
+            );
+        }
+
+        let mut missing_id = envelope(MessageType::Heartbeat, json!({ "sequence": 1 }));
+        missing_id.message_id = String::new();
+        assert!(validate_envelope(&missing_id, &limits).is_err());
+    }
+
+    #[test]
+    fn test_screening_request_limits() {
+        let limits = EnvelopeLimits::default();
+        let object = |id: &str| {
+            json!({ "object_id": id, "state_vector": {
+                "reference_frame": "TEME", "x_km": 7000.0, "y_km": 0.0, "z_km": 0.0, "vx_km_s": 0.0, "vy_km_s": 7.5, "vz_km_s": 0.0
+            } })
+        };
+        let request = |changes: serde_json::Value| {
+            let mut payload = json!({
+                "request_id": "r-1",
+                "start": "2024-01-15T14:30:00.000Z",
+                "window_seconds": 3600,
+                "step_seconds": 60.0,
+                "threshold_km": 5.0,
+                "objects": [object("A"), object("B")],
+                "pairs": [[0, 1]]
+            });
+            payload.as_object_mut().unwrap().extend(changes.as_object().unwrap().clone());
+            validate_envelope(&envelope(MessageType::ScreeningRequest, payload), &limits)
+        };
+        assert!(request(json!({})).is_ok());
+        for changes in [
+            json!({ "pairs": [[0, 2]] }),
+            json!({ "pairs": [[1, 1]] }),
+            json!({ "step_seconds": 0.1 }),
+            json!({ "window_seconds": 0 }),
+            json!({ "window_seconds": 31 * 86_400 }),
+            json!({ "threshold_km": -1.0 }),
+        ] {
+            assert!(matches!(request(changes.clone()), Err(Error::Protocol(_))), "{}", changes);
+        }
+        let pairs = vec![[0, 1]; MAX_SCREENING_PAIRS + 1];
+        assert!(matches!(request(json!({ "pairs": pairs })), Err(Error::LimitExceeded(_))));
+        let objects = vec![object("A"); MAX_SCREENING_OBJECTS + 1];
+        assert!(matches!(request(json!({ "objects": objects })), Err(Error::LimitExceeded(_))));
+    }
+}
@@ -0,0 +1,374 @@
+//! Archive of records moved out of the hot store
+//!
+//! Withdrawn and expired CDMs and stale object states are appended to JSON
+//! Lines files, one per record kind and UTC day (`cdms-2024-01-15.jsonl.gz`).
+//! Compressed files get one gzip member per appended batch, which gzip
+//! readers treat as a single stream. With `storage.encryption` configured,
+//! each batch is sealed as one frame and the file name gains `.enc`; files
+//! written before encryption was turned on stay readable.
+
+use crate::cdm::{CdmRecord, ObjectRecord};
+use crate::config::Config;
+use crate::storage::encryption::RecordCipher;
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Why a record was archived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveReason {
+    /// Withdrawn by its originator, a peer or an operator
+    Withdrawn,
+    /// TCA passed longer ago than `archive.cdm_expiry_hours`
+    Expired,
+    /// Not updated for `archive.object_stale_hours`
+    Stale,
+    /// Object state replaced by the state after a completed maneuver
+    Maneuvered,
+}
+
+/// Kind of archived record; each kind has its own files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ArchiveKind {
+    Cdm,
+    Object,
+}
+
+impl ArchiveKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            ArchiveKind::Cdm => "cdms",
+            ArchiveKind::Object => "objects",
+        }
+    }
+}
+
+/// An archived CDM or object state
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", content = "record", rename_all = "snake_case")]
+pub enum ArchivedRecord {
+    Cdm(Box<CdmRecord>),
+    Object(Box<ObjectRecord>),
+}
+
+impl ArchivedRecord {
+    pub fn kind(&self) -> ArchiveKind {
+        match self {
+            ArchivedRecord::Cdm(_) => ArchiveKind::Cdm,
+            ArchivedRecord::Object(_) => ArchiveKind::Object,
+        }
+    }
+
+    /// Whether the record is about an object (either side of a CDM)
+    pub fn involves(&self, object_id: &str) -> bool {
+        match self {
+            ArchivedRecord::Cdm(cdm) => cdm.object1.object_id == object_id || cdm.object2.object_id == object_id,
+            ArchivedRecord::Object(obj) => obj.object_id == object_id,
+        }
+    }
+}
+
+/// One line of the archive
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveEntry {
+    pub archived_at: DateTime<Utc>,
+    pub reason: ArchiveReason,
+    #[serde(flatten)]
+    pub record: ArchivedRecord,
+}
+
+impl ArchiveEntry {
+    pub fn cdm(cdm: CdmRecord, reason: ArchiveReason) -> Self {
+        Self {
+            archived_at: Utc::now(),
+            reason,
+            record: ArchivedRecord::Cdm(Box::new(cdm)),
+        }
+    }
+
+    pub fn object(obj: ObjectRecord, reason: ArchiveReason) -> Self {
+        Self {
+            archived_at: Utc::now(),
+            reason,
+            record: ArchivedRecord::Object(Box::new(obj)),
+        }
+    }
+}
+
+/// Filter for archive queries
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveQuery {
+    /// Archived at or after
+    pub since: Option<DateTime<Utc>>,
+    /// Archived before
+    pub until: Option<DateTime<Utc>>,
+    /// Only records about this object
+    pub object_id: Option<String>,
+    pub limit: usize,
+}
+
+impl ArchiveQuery {
+    fn matches(&self, entry: &ArchiveEntry) -> bool {
+        self.since.is_none_or(|since| entry.archived_at >= since)
+            && self.until.is_none_or(|until| entry.archived_at < until)
+            && self.object_id.as_deref().is_none_or(|id| entry.record.involves(id))
+    }
+}
+
+/// Entries matching a query, oldest first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ArchivePage {
+    pub entries: Vec<ArchiveEntry>,
+    /// More entries matched than the limit allowed
+    pub truncated: bool,
+}
+
+/// Archive kept as daily JSON Lines files in a directory
+pub struct FileArchive {
+    directory: PathBuf,
+    compress: bool,
+    /// Seals new batches and opens encrypted files
+    cipher: Option<RecordCipher>,
+    /// Serializes appends so batches never interleave
+    lock: Mutex<()>,
+}
+
+impl FileArchive {
+    pub fn new(directory: impl Into<PathBuf>, compress: bool) -> Self {
+        Self {
+            directory: directory.into(),
+            compress,
+            cipher: None,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Encrypt new batches with `cipher`
+    pub fn with_cipher(mut self, cipher: RecordCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    fn file_name(&self, kind: ArchiveKind, day: NaiveDate) -> String {
+        let extension = if self.compress { "jsonl.gz" } else { "jsonl" };
+        let sealed = if self.cipher.is_some() { ".enc" } else { "" };
+        format!("{}-{}.{}{}", kind.prefix(), day.format("%Y-%m-%d"), extension, sealed)
+    }
+
+    /// Append entries to the files for their kind and day
+    pub fn append(&self, entries: &[ArchiveEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut files: BTreeMap<(ArchiveKind, NaiveDate), Vec<u8>> = BTreeMap::new();
+        for entry in entries {
+            let lines = files.entry((entry.record.kind(), entry.archived_at.date_naive())).or_default();
+            serde_json::to_writer(&mut *lines, entry)?;
+            lines.push(b'\n');
+        }
+
+        let _appending = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(&self.directory)?;
+        for ((kind, day), lines) in files {
+            let name = self.file_name(kind, day);
+            let mut file = OpenOptions::new().create(true).append(true).open(self.directory.join(&name))?;
+            let lines = if self.compress {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&lines)?;
+                encoder.finish()?
+            } else {
+                lines
+            };
+            match &self.cipher {
+                // The name binds each frame to its file
+                Some(cipher) => file.write_all(&cipher.seal(&lines, name.as_bytes())?)?,
+                None => file.write_all(&lines)?,
+            }
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Archived records of one kind matching a query, oldest first
+    pub fn query(&self, kind: ArchiveKind, query: &ArchiveQuery) -> Result<ArchivePage> {
+        let first_day = query.since.map(|t| t.date_naive());
+        let last_day = query.until.map(|t| t.date_naive());
+        let mut days: Vec<(NaiveDate, PathBuf)> = match fs::read_dir(&self.directory) {
+            Ok(dir) => dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    let day = parse_file_day(&name, kind)?;
+                    Some((day, entry.path()))
+                })
+                .filter(|(day, _)| first_day.is_none_or(|d| *day >= d) && last_day.is_none_or(|d| *day <= d))
+                .collect(),
+            // Nothing archived yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        days.sort();
+
+        let mut entries = Vec::new();
+        for (_, path) in days {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let reader: Box<dyn Read> = match name.strip_suffix(".enc") {
+                Some(plain_name) => {
+                    let cipher = self.cipher.as_ref().ok_or_else(|| {
+                        Error::Storage(format!("{} is encrypted but storage.encryption is not configured", path.display()))
+                    })?;
+                    let bytes = cipher.open(&fs::read(&path)?, name.as_bytes())?;
+                    if plain_name.ends_with(".gz") {
+                        Box::new(MultiGzDecoder::new(std::io::Cursor::new(bytes)))
+                    } else {
+                        Box::new(std::io::Cursor::new(bytes))
+                    }
+                }
+                None => {
+                    let file = File::open(&path)?;
+                    if name.ends_with(".gz") {
+                        Box::new(MultiGzDecoder::new(file))
+                    } else {
+                        Box::new(file)
+                    }
+                }
+            };
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<ArchiveEntry>(&line) {
+                    Ok(entry) if query.matches(&entry) => entries.push(entry),
+                    Ok(_) => {}
+                    Err(e) => warn!("Skipping unreadable archive line in {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.archived_at);
+        let truncated = entries.len() > query.limit;
+        entries.truncate(query.limit);
+        Ok(ArchivePage { entries, truncated })
+    }
+}
+
+/// Day of an archive file of this kind, from its name
+fn parse_file_day(name: &str, kind: ArchiveKind) -> Option<NaiveDate> {
+    let rest = name.strip_prefix(kind.prefix())?.strip_prefix('-')?;
+    let rest = rest.strip_suffix(".enc").unwrap_or(rest);
+    let day = rest.strip_suffix(".jsonl.gz").or_else(|| rest.strip_suffix(".jsonl"))?;
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()
+}
+
+/// Create the archive from configuration, if archiving is enabled
+///
+/// Encryption keys are read from their sources here, so a missing key
+/// fails startup rather than the first sweep.
+pub fn create_archive(config: &Config) -> Result<Option<Arc<FileArchive>>> {
+    let Some(archive) = config.archive.as_ref() else {
+        return Ok(None);
+    };
+    let mut file_archive = FileArchive::new(&archive.directory, archive.compress);
+    if let Some(encryption) = &config.storage.encryption {
+        file_archive = file_archive.with_cipher(RecordCipher::from_config(encryption)?);
+    }
+    Ok(Some(Arc::new(file_archive)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+    use chrono::Duration;
+
+    #[test]
+    fn test_append_and_query() {
+        for compress in [true, false] {
+            let dir = tempfile::tempdir().unwrap();
+            let archive = FileArchive::new(dir.path(), compress);
+            let mut old = ArchiveEntry::cdm(generate_demo_cdm(), ArchiveReason::Expired);
+            old.archived_at -= Duration::days(2);
+            let mut other = generate_demo_cdm();
+            other.cdm_id = "CDM-OTHER".into();
+            other.object1.object_id = "NORAD-11111".into();
+            other.object2.object_id = "NORAD-22222".into();
+            // Two batches to the same file
+            archive.append(&[old.clone()]).unwrap();
+            archive.append(&[ArchiveEntry::cdm(other, ArchiveReason::Withdrawn)]).unwrap();
+
+            let all = ArchiveQuery {
+                limit: 10,
+                ..Default::default()
+            };
+            let page = archive.query(ArchiveKind::Cdm, &all).unwrap();
+            assert_eq!(page.entries.len(), 2);
+            assert_eq!(page.entries[0].reason, ArchiveReason::Expired);
+            assert!(archive.query(ArchiveKind::Object, &all).unwrap().entries.is_empty());
+
+            let recent = ArchiveQuery {
+                since: Some(Utc::now() - Duration::days(1)),
+                ..all.clone()
+            };
+            assert_eq!(archive.query(ArchiveKind::Cdm, &recent).unwrap().entries.len(), 1);
+            let by_object = ArchiveQuery {
+                object_id: Some(generate_demo_cdm().object1.object_id),
+                ..all.clone()
+            };
+            assert_eq!(archive.query(ArchiveKind::Cdm, &by_object).unwrap().entries.len(), 1);
+            let limited = ArchiveQuery { limit: 1, ..all };
+            assert!(archive.query(ArchiveKind::Cdm, &limited).unwrap().truncated);
+        }
+    }
+
+    #[test]
+    fn test_encrypted_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = |id: &str, byte: u8| (id.to_string(), vec![byte; crate::storage::encryption::KEY_LEN]);
+        let all = ArchiveQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        // A plaintext batch from before encryption was turned on
+        FileArchive::new(dir.path(), true).append(&[ArchiveEntry::cdm(generate_demo_cdm(), ArchiveReason::Expired)]).unwrap();
+        let first = FileArchive::new(dir.path(), true).with_cipher(RecordCipher::new(vec![key("k1", 1)]).unwrap());
+        first.append(&[ArchiveEntry::cdm(generate_demo_cdm(), ArchiveReason::Withdrawn)]).unwrap();
+        let name = first.file_name(ArchiveKind::Cdm, Utc::now().date_naive());
+        assert!(name.ends_with(".jsonl.gz.enc"));
+        let raw = fs::read(dir.path().join(&name)).unwrap();
+        assert!(!raw.windows(4).any(|w| w == b"CDM-"));
+
+        // Rotated: k2 seals, k1 still opens older frames in the same file
+        let rotated = FileArchive::new(dir.path(), true)
+            .with_cipher(RecordCipher::new(vec![key("k2", 2), key("k1", 1)]).unwrap());
+        rotated.append(&[ArchiveEntry::cdm(generate_demo_cdm(), ArchiveReason::Stale)]).unwrap();
+        assert_eq!(rotated.query(ArchiveKind::Cdm, &all).unwrap().entries.len(), 3);
+
+        // Once k1 is dropped its frames cannot be read, nor without any key
+        let k2_only = FileArchive::new(dir.path(), true).with_cipher(RecordCipher::new(vec![key("k2", 2)]).unwrap());
+        assert!(matches!(k2_only.query(ArchiveKind::Cdm, &all), Err(Error::Storage(_))));
+        assert!(FileArchive::new(dir.path(), true).query(ArchiveKind::Cdm, &all).is_err());
+    }
+
+    #[test]
+    fn test_missing_directory_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = FileArchive::new(dir.path().join("absent"), true);
+        let query = ArchiveQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        assert!(archive.query(ArchiveKind::Cdm, &query).unwrap().entries.is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+//! Memory accounting and admission
+//!
+//! Every stored record, dedup entry and queued outbound envelope is charged
+//! against a single byte budget using a deterministic footprint estimate, so
+//! the node's resident data can be capped at a configured size. Estimates
+//! cover the record itself, its heap allocations and a fixed per-entry index
+//! overhead; allocator slack is not modelled.
+
+use crate::cdm::{CdmObject, CdmRecord, ObjectRecord, ScreeningData};
+use crate::config::{EvictionPolicy, MemoryLimitsConfig};
+use crate::protocol::{CovarianceRtn, DeliveryReceiptPayload, Envelope, ProvenanceHop, StateVector};
+use serde::Serialize;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Estimated bookkeeping cost of one map or queue entry
+pub const ENTRY_OVERHEAD: usize = 64;
+
+/// Estimated in-memory size of a value
+pub trait Footprint {
+    /// Bytes owned on the heap
+    fn heap_bytes(&self) -> usize;
+
+    /// Inline size plus heap allocations
+    fn footprint(&self) -> usize
+    where
+        Self: Sized,
+    {
+        size_of::<Self>() + self.heap_bytes()
+    }
+}
+
+impl Footprint for String {
+    fn heap_bytes(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: Footprint> Footprint for Option<T> {
+    fn heap_bytes(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_bytes)
+    }
+}
+
+impl Footprint for StateVector {
+    fn heap_bytes(&self) -> usize {
+        self.reference_frame.heap_bytes()
+    }
+}
+
+impl Footprint for CovarianceRtn {
+    fn heap_bytes(&self) -> usize {
+        self.reference_frame.heap_bytes()
+    }
+}
+
+impl Footprint for ScreeningData {
+    fn heap_bytes(&self) -> usize {
+        self.screen_volume_shape.heap_bytes()
+    }
+}
+
+impl Footprint for CdmObject {
+    fn heap_bytes(&self) -> usize {
+        self.object_id.heap_bytes()
+            + self.object_name.heap_bytes()
+            + self.owner_operator.heap_bytes()
+            + self.state_vector.heap_bytes()
+            + self.covariance_rtm.heap_bytes()
+    }
+}
+
+impl Footprint for CdmRecord {
+    fn heap_bytes(&self) -> usize {
+        self.cdm_id.heap_bytes()
+            + self.originator.heap_bytes()
+            + self.message_for.heap_bytes()
+            + self.object1.heap_bytes()
+            + self.object2.heap_bytes()
+            + self.screening_data.heap_bytes()
+    }
+}
+
+impl Footprint for ObjectRecord {
+    fn heap_bytes(&self) -> usize {
+        self.object_id.heap_bytes()
+            + self.object_name.heap_bytes()
+            + self.owner_operator.heap_bytes()
+            + self.state_vector.heap_bytes()
+            + self.covariance.heap_bytes()
+            + self.source_node.heap_bytes()
+    }
+}
+
+impl Footprint for DeliveryReceiptPayload {
+    fn heap_bytes(&self) -> usize {
+        self.cdm_id.heap_bytes()
+            + self.related_message_id.heap_bytes()
+            + self.originator_node_id.heap_bytes()
+            + self.receiving_node_id.heap_bytes()
+            + self.matched_object_ids.iter().map(|id| id.footprint()).sum::<usize>()
+    }
+}
+
+impl Footprint for serde_json::Value {
+    fn heap_bytes(&self) -> usize {
+        match self {
+            serde_json::Value::String(s) => s.heap_bytes(),
+            serde_json::Value::Array(items) => items.iter().map(|v| v.footprint()).sum(),
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| ENTRY_OVERHEAD + k.footprint() + v.footprint())
+                .sum(),
+            _ => 0,
+        }
+    }
+}
+
+impl Footprint for Envelope {
+    fn heap_bytes(&self) -> usize {
+        self.protocol_version.heap_bytes()
+            + self.message_id.heap_bytes()
+            + self.source_node_id.heap_bytes()
+            + self.traceparent.heap_bytes()
+            + self
+                .provenance
+                .iter()
+                .map(|hop| size_of::<ProvenanceHop>() + hop.node_id.heap_bytes() + hop.signature.heap_bytes())
+                .sum::<usize>()
+            + self.payload.heap_bytes()
+    }
+}
+
+/// Estimated cost of storing `value` in a map keyed by `key`
+pub fn entry_footprint<T: Footprint>(key: &str, value: &T) -> usize {
+    ENTRY_OVERHEAD + size_of::<String>() + key.len() + value.footprint()
+}
+
+/// What a budget charge is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCategory {
+    Cdms,
+    Objects,
+    Dedup,
+    Queues,
+}
+
+/// Snapshot of memory usage
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct MemoryUsage {
+    /// Estimated bytes held by stored CDMs
+    pub cdm_bytes: usize,
+    /// Estimated bytes held by tracked objects
+    pub object_bytes: usize,
+    /// Estimated bytes held by message deduplication state
+    pub dedup_bytes: usize,
+    /// Estimated bytes held by queued outbound envelopes
+    pub queue_bytes: usize,
+    /// Sum of all categories
+    pub total_bytes: usize,
+    /// Configured budget (unlimited if unset)
+    pub max_bytes: Option<usize>,
+    /// Records evicted to stay within the budget since startup
+    pub evicted: u64,
+    /// Records refused because the budget was exhausted since startup
+    pub rejected: u64,
+    /// Whether usage is above the alert threshold
+    pub alerting: bool,
+}
+
+/// Byte budget shared by storage and the outbound queues
+pub struct MemoryBudget {
+    max_bytes: Option<usize>,
+    eviction: EvictionPolicy,
+    alert_percent: u8,
+    total: AtomicUsize,
+    used: [AtomicUsize; 4],
+    evicted: AtomicU64,
+    rejected: AtomicU64,
+    alerting: AtomicBool,
+}
+
+impl MemoryBudget {
+    /// Create a budget from configuration
+    pub fn new(limits: &MemoryLimitsConfig) -> Self {
+        Self {
+            max_bytes: limits.max_bytes,
+            eviction: limits.eviction,
+            alert_percent: limits.alert_percent,
+            total: AtomicUsize::new(0),
+            used: Default::default(),
+            evicted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            alerting: AtomicBool::new(false),
+        }
+    }
+
+    /// Configured budget in bytes
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// How records make room when the budget is exhausted
+    pub fn eviction(&self) -> EvictionPolicy {
+        self.eviction
+    }
+
+    /// Charge `add` bytes while releasing `release` bytes, if the result fits
+    pub fn try_charge(&self, category: MemoryCategory, add: usize, release: usize) -> bool {
+        let fits = self
+            .total
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+                let next = total.saturating_sub(release) + add;
+                match self.max_bytes {
+                    Some(max) if add > release && next > max => None,
+                    _ => Some(next),
+                }
+            })
+            .is_ok();
+        if fits {
+            let _ = self.used[category as usize].fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(release) + add)
+            });
+            self.check_alert();
+        }
+        fits
+    }
+
+    /// Charge bytes that cannot be refused
+    pub fn charge(&self, category: MemoryCategory, bytes: usize) {
+        self.total.fetch_add(bytes, Ordering::AcqRel);
+        self.used[category as usize].fetch_add(bytes, Ordering::AcqRel);
+        self.check_alert();
+    }
+
+    /// Return previously charged bytes
+    pub fn release(&self, category: MemoryCategory, bytes: usize) {
+        let _ = self.try_charge(category, 0, bytes);
+    }
+
+    /// Set a category to a measured value, ignoring the limit
+    pub(crate) fn reconcile(&self, category: MemoryCategory, actual: usize) {
+        let used = self.used[category as usize].load(Ordering::Acquire);
+        if actual > used {
+            self.charge(category, actual - used);
+        } else {
+            self.release(category, used - actual);
+        }
+    }
+
+    /// Charge a queued outbound envelope until the returned guard is dropped
+    pub fn charge_queue(self: &Arc<Self>, bytes: usize) -> QueueCharge {
+        self.charge(MemoryCategory::Queues, bytes);
+        QueueCharge {
+            budget: self.clone(),
+            bytes,
+        }
+    }
+
+    pub(crate) fn record_eviction(&self) {
+        self.evicted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rejection(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current usage
+    pub fn usage(&self) -> MemoryUsage {
+        let used = |category: MemoryCategory| self.used[category as usize].load(Ordering::Acquire);
+        MemoryUsage {
+            cdm_bytes: used(MemoryCategory::Cdms),
+            object_bytes: used(MemoryCategory::Objects),
+            dedup_bytes: used(MemoryCategory::Dedup),
+            queue_bytes: used(MemoryCategory::Queues),
+            total_bytes: self.total.load(Ordering::Acquire),
+            max_bytes: self.max_bytes,
+            evicted: self.evicted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            alerting: self.alerting.load(Ordering::Relaxed),
+        }
+    }
+
+    fn check_alert(&self) {
+        let Some(max) = self.max_bytes else {
+            return;
+        };
+        let total = self.total.load(Ordering::Acquire);
+        let above = total as u128 * 100 >= max as u128 * u128::from(self.alert_percent);
+        if self.alerting.swap(above, Ordering::AcqRel) == above {
+            return;
+        }
+        if above {
+            warn!("Memory budget at {}/{} bytes", total, max);
+        } else {
+            info!("Memory budget back to {}/{} bytes", total, max);
+        }
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::new(&MemoryLimitsConfig::default())
+    }
+}
+
+/// Queue charge released on drop
+pub struct QueueCharge {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl Drop for QueueCharge {
+    fn drop(&mut self) {
+        self.budget.release(MemoryCategory::Queues, self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdm::generate_demo_cdm;
+
+    fn budget(max_bytes: usize) -> Arc<MemoryBudget> {
+        Arc::new(MemoryBudget::new(&MemoryLimitsConfig {
+            max_bytes: Some(max_bytes),
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_footprint_tracks_heap() {
+        let mut cdm = generate_demo_cdm();
+        let base = cdm.footprint() - cdm.originator.capacity();
+        assert!(base > size_of::<CdmRecord>());
+        cdm.originator = "X".repeat(1000);
+        assert_eq!(cdm.footprint(), base + cdm.originator.capacity());
+    }
+
+    #[test]
+    fn test_budget_admission() {
+        let budget = budget(1000);
+        assert!(budget.try_charge(MemoryCategory::Cdms, 600, 0));
+        assert!(!budget.try_charge(MemoryCategory::Objects, 500, 0));
+        // Replacing a record only needs room for the difference
+        assert!(budget.try_charge(MemoryCategory::Cdms, 900, 600));
+        assert_eq!(budget.usage().cdm_bytes, 900);
+        assert!(budget.usage().alerting);
+
+        budget.release(MemoryCategory::Cdms, 900);
+        let usage = budget.usage();
+        assert_eq!(usage.total_bytes, 0);
+        assert!(!usage.alerting);
+    }
+
+    #[test]
+    fn test_queue_charge_released_on_drop() {
+        let budget = budget(100);
+        let charge = budget.charge_queue(150);
+        assert_eq!(budget.usage().queue_bytes, 150);
+        assert!(!budget.try_charge(MemoryCategory::Dedup, 1, 0));
+        drop(charge);
+        assert_eq!(budget.usage().total_bytes, 0);
+    }
+}
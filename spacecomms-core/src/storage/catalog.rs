@@ -0,0 +1,400 @@
+//! Object catalog with capacity limits
+//!
+//! Bounds the number of tracked objects so a misbehaving peer cannot
+//! exhaust memory by announcing fake objects. Objects from untrusted sources
+//! are subject to per-source quotas, and when the catalog is full the
+//! eviction policy decides whether a new object replaces an existing one.
+
+use crate::cdm::ObjectRecord;
+use crate::config::{EvictionPolicy, ObjectLimitsConfig};
+use crate::storage::entry_footprint;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Catalog capacity event delivered to alerting hooks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CapacityEvent {
+    /// The catalog filled past the alert threshold
+    HighWater { tracked: usize, max_objects: usize },
+    /// The catalog dropped back below the alert threshold
+    Recovered { tracked: usize, max_objects: usize },
+    /// An object was evicted to make room
+    Evicted { object_id: String, source_node: String },
+    /// An object was rejected by a quota or a full catalog
+    Rejected { object_id: String, source_node: String, reason: String },
+}
+
+impl CapacityEvent {
+    /// Whether the event crosses the alert threshold, one way or the other
+    pub fn is_alert(&self) -> bool {
+        matches!(self, CapacityEvent::HighWater { .. } | CapacityEvent::Recovered { .. })
+    }
+}
+
+/// Alerting hook; runs while the catalog is locked, so it must be cheap
+/// and must not call back into storage
+pub type CapacityHook = Arc<dyn Fn(&CapacityEvent) + Send + Sync>;
+
+/// Snapshot of catalog usage
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ObjectCapacity {
+    /// Objects currently tracked
+    pub tracked: usize,
+    /// Configured maximum (unlimited if unset)
+    pub max_objects: Option<usize>,
+    /// Objects evicted since startup
+    pub evicted: u64,
+    /// Objects rejected since startup
+    pub rejected: u64,
+    /// Whether the catalog is above the alert threshold
+    pub alerting: bool,
+    /// Tracked objects per source node
+    pub by_source: BTreeMap<String, usize>,
+}
+
+/// Eviction order: untrusted before trusted, then oldest epoch first
+type EvictionKey = (bool, DateTime<Utc>, String);
+
+/// Object records indexed for quota and eviction decisions
+pub(crate) struct ObjectCatalog {
+    limits: ObjectLimitsConfig,
+    trusted: HashSet<String>,
+    records: HashMap<String, ObjectRecord>,
+    per_source: HashMap<String, usize>,
+    order: BTreeSet<EvictionKey>,
+    bytes: usize,
+    evicted: u64,
+    rejected: u64,
+    alerting: bool,
+    hook: Option<CapacityHook>,
+}
+
+impl ObjectCatalog {
+    pub(crate) fn new(limits: ObjectLimitsConfig) -> Self {
+        Self {
+            trusted: limits.trusted_sources.iter().cloned().collect(),
+            limits,
+            records: HashMap::new(),
+            per_source: HashMap::new(),
+            order: BTreeSet::new(),
+            bytes: 0,
+            evicted: 0,
+            rejected: 0,
+            alerting: false,
+            hook: None,
+        }
+    }
+
+    /// Apply new limits to future inserts; tracked objects are not evicted
+    pub(crate) fn set_limits(&mut self, limits: ObjectLimitsConfig) {
+        let trusted: HashSet<String> = limits.trusted_sources.iter().cloned().collect();
+        if trusted != self.trusted {
+            // Trust is part of the eviction key, so rebuild the order
+            self.order = self
+                .records
+                .values()
+                .map(|obj| (trusted.contains(&obj.source_node), obj.epoch, obj.object_id.clone()))
+                .collect();
+            self.trusted = trusted;
+        }
+        self.limits = limits;
+        if self.limits.max_objects.is_none() {
+            self.alerting = false;
+        }
+        self.check_alert();
+    }
+
+    pub(crate) fn set_hook(&mut self, hook: CapacityHook) {
+        self.hook = Some(hook);
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&ObjectRecord> {
+        self.records.get(id)
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &ObjectRecord> {
+        self.records.values()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Estimated memory held by tracked objects
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Insert or replace an object, enforcing quotas and capacity
+    pub(crate) fn insert(&mut self, obj: ObjectRecord) -> Result<()> {
+        let trusted = self.trusted.contains(&obj.source_node);
+        let existing_source = self.records.get(&obj.object_id).map(|r| r.source_node.clone());
+
+        if let Some(quota) = self.limits.max_objects_per_source {
+            let count = self.per_source.get(&obj.source_node).copied().unwrap_or(0);
+            let new_to_source = existing_source.as_deref() != Some(obj.source_node.as_str());
+            if !trusted && new_to_source && count >= quota {
+                let reason = format!("source {} is at its quota of {} objects", obj.source_node, quota);
+                return Err(self.reject(&obj, reason));
+            }
+        }
+
+        if let (None, Some(max)) = (&existing_source, self.limits.max_objects) {
+            if self.records.len() >= max {
+                self.make_room(&obj, trusted, max)?;
+            }
+        }
+
+        self.detach(&obj.object_id);
+        *self.per_source.entry(obj.source_node.clone()).or_insert(0) += 1;
+        self.order.insert((trusted, obj.epoch, obj.object_id.clone()));
+        self.bytes += entry_footprint(&obj.object_id, &obj);
+        self.records.insert(obj.object_id.clone(), obj);
+        self.check_alert();
+        Ok(())
+    }
+
+    /// Remove an object, returning it if it was tracked
+    pub(crate) fn remove(&mut self, id: &str) -> Option<ObjectRecord> {
+        let obj = self.detach(id)?;
+        self.check_alert();
+        Some(obj)
+    }
+
+    fn detach(&mut self, id: &str) -> Option<ObjectRecord> {
+        let obj = self.records.remove(id)?;
+        let trusted = self.trusted.contains(&obj.source_node);
+        self.order.remove(&(trusted, obj.epoch, obj.object_id.clone()));
+        self.bytes -= entry_footprint(&obj.object_id, &obj);
+        if let Some(count) = self.per_source.get_mut(&obj.source_node) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_source.remove(&obj.source_node);
+            }
+        }
+        Some(obj)
+    }
+
+    pub(crate) fn capacity(&self) -> ObjectCapacity {
+        ObjectCapacity {
+            tracked: self.records.len(),
+            max_objects: self.limits.max_objects,
+            evicted: self.evicted,
+            rejected: self.rejected,
+            alerting: self.alerting,
+            by_source: self.per_source.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+        }
+    }
+
+    /// Evict one object for `obj`, or reject it if nothing ranks below it
+    fn make_room(&mut self, obj: &ObjectRecord, trusted: bool, max: usize) -> Result<()> {
+        if self.limits.eviction == EvictionPolicy::Reject {
+            let reason = format!("object catalog is full ({} objects)", max);
+            return Err(self.reject(obj, reason));
+        }
+
+        if self.evict_below(obj, trusted).is_none() {
+            let reason = format!("object catalog is full ({} objects) with higher-priority entries", max);
+            return Err(self.reject(obj, reason));
+        }
+        Ok(())
+    }
+
+    /// Evict the lowest-ranked object other than `obj`, if it ranks below it
+    pub(crate) fn evict_below(&mut self, obj: &ObjectRecord, trusted: bool) -> Option<String> {
+        let incoming = (trusted, obj.epoch, obj.object_id.clone());
+        let victim = self
+            .order
+            .iter()
+            .find(|key| key.2 != obj.object_id)
+            .filter(|key| **key < incoming)?
+            .2
+            .clone();
+
+        let evicted = self.detach(&victim)?;
+        self.evicted += 1;
+        debug!("Evicted object {} from {} to admit {}", evicted.object_id, evicted.source_node, obj.object_id);
+        self.emit(CapacityEvent::Evicted {
+            object_id: evicted.object_id.clone(),
+            source_node: evicted.source_node,
+        });
+        Some(evicted.object_id)
+    }
+
+    /// Record a rejection made outside the catalog's own limits
+    pub(crate) fn reject_for(&mut self, obj: &ObjectRecord, reason: String) -> Error {
+        self.reject(obj, reason)
+    }
+
+    pub(crate) fn is_trusted(&self, source_node: &str) -> bool {
+        self.trusted.contains(source_node)
+    }
+
+    fn reject(&mut self, obj: &ObjectRecord, reason: String) -> Error {
+        self.rejected += 1;
+        debug!("Rejected object {} from {}: {}", obj.object_id, obj.source_node, reason);
+        self.emit(CapacityEvent::Rejected {
+            object_id: obj.object_id.clone(),
+            source_node: obj.source_node.clone(),
+            reason: reason.clone(),
+        });
+        Error::QuotaExceeded(reason)
+    }
+
+    fn check_alert(&mut self) {
+        let Some(max) = self.limits.max_objects else {
+            return;
+        };
+        let tracked = self.records.len();
+        let above = tracked * 100 >= max * usize::from(self.limits.alert_percent);
+        if above == self.alerting {
+            return;
+        }
+
+        self.alerting = above;
+        if above {
+            warn!("Object catalog at {}/{} objects", tracked, max);
+            self.emit(CapacityEvent::HighWater { tracked, max_objects: max });
+        } else {
+            info!("Object catalog back to {}/{} objects", tracked, max);
+            self.emit(CapacityEvent::Recovered { tracked, max_objects: max });
+        }
+    }
+
+    fn emit(&self, event: CapacityEvent) {
+        if let Some(hook) = &self.hook {
+            hook(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ObjectType, StateVector};
+    use chrono::{Duration, TimeZone};
+    use std::sync::Mutex;
+
+    fn object(id: &str, source: &str, age_hours: i64) -> ObjectRecord {
+        let epoch = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap() - Duration::hours(age_hours);
+        ObjectRecord {
+            object_id: id.to_string(),
+            object_name: id.to_string(),
+            object_type: ObjectType::Debris,
+            owner_operator: None,
+            rcs_size: None,
+            epoch,
+            state_vector: StateVector {
+                reference_frame: "TEME".to_string(),
+                epoch: Some(epoch),
+                x_km: 7000.0,
+                y_km: 0.0,
+                z_km: 0.0,
+                vx_km_s: 0.0,
+                vy_km_s: 7.5,
+                vz_km_s: 0.0,
+            },
+            covariance: None,
+            source_node: source.to_string(),
+            last_updated: epoch,
+            organization: None,
+        }
+    }
+
+    fn limits(max_objects: usize, eviction: EvictionPolicy) -> ObjectLimitsConfig {
+        ObjectLimitsConfig {
+            max_objects: Some(max_objects),
+            eviction,
+            trusted_sources: vec!["node-local".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reject_when_full() {
+        let mut catalog = ObjectCatalog::new(limits(2, EvictionPolicy::Reject));
+        catalog.insert(object("A", "peer", 1)).unwrap();
+        catalog.insert(object("B", "peer", 1)).unwrap();
+        assert!(matches!(catalog.insert(object("C", "peer", 0)), Err(Error::QuotaExceeded(_))));
+
+        // Updates to tracked objects are always accepted
+        catalog.insert(object("A", "peer", 0)).unwrap();
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(catalog.capacity().rejected, 1);
+    }
+
+    #[test]
+    fn test_evicts_untrusted_oldest_first() {
+        let mut catalog = ObjectCatalog::new(limits(3, EvictionPolicy::OldestEpoch));
+        catalog.insert(object("local-old", "node-local", 10)).unwrap();
+        catalog.insert(object("peer-old", "peer", 5)).unwrap();
+        catalog.insert(object("peer-new", "peer", 1)).unwrap();
+
+        catalog.insert(object("incoming", "peer", 0)).unwrap();
+        assert!(catalog.get("peer-old").is_none());
+        assert!(catalog.get("local-old").is_some());
+
+        // An untrusted object never displaces a newer one or a trusted one
+        assert!(catalog.insert(object("stale", "peer", 20)).is_err());
+        catalog.insert(object("local-new", "node-local", 0)).unwrap();
+        assert!(catalog.get("peer-new").is_none());
+        assert_eq!(catalog.capacity().evicted, 2);
+        assert_eq!(catalog.len(), 3);
+    }
+
+    #[test]
+    fn test_per_source_quota() {
+        let mut catalog = ObjectCatalog::new(ObjectLimitsConfig {
+            max_objects_per_source: Some(2),
+            trusted_sources: vec!["node-local".to_string()],
+            ..Default::default()
+        });
+        catalog.insert(object("A", "rogue", 0)).unwrap();
+        catalog.insert(object("B", "rogue", 0)).unwrap();
+        assert!(catalog.insert(object("C", "rogue", 0)).is_err());
+        catalog.insert(object("A", "rogue", 0)).unwrap();
+
+        // Other and trusted sources are unaffected
+        catalog.insert(object("D", "other", 0)).unwrap();
+        for i in 0..5 {
+            catalog.insert(object(&format!("L{}", i), "node-local", 0)).unwrap();
+        }
+
+        catalog.remove("A");
+        catalog.insert(object("C", "rogue", 0)).unwrap();
+        let capacity = catalog.capacity();
+        assert_eq!(capacity.by_source["rogue"], 2);
+        assert_eq!(capacity.by_source["node-local"], 5);
+    }
+
+    #[test]
+    fn test_capacity_alert_hook() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let mut catalog = ObjectCatalog::new(ObjectLimitsConfig {
+            alert_percent: 50,
+            ..limits(4, EvictionPolicy::Reject)
+        });
+        catalog.set_hook(Arc::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        catalog.insert(object("A", "peer", 0)).unwrap();
+        assert!(!catalog.capacity().alerting);
+        catalog.insert(object("B", "peer", 0)).unwrap();
+        assert!(catalog.capacity().alerting);
+        catalog.remove("B");
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                CapacityEvent::HighWater { tracked: 2, max_objects: 4 },
+                CapacityEvent::Recovered { tracked: 1, max_objects: 4 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Encryption of stored records at rest
+//!
+//! Records written to disk are sealed with AES-256-GCM. Each sealed frame
+//! names the key it was sealed with, so keys can be rotated: the first
+//! configured key seals new frames, and every configured key can open old
+//! ones until they are no longer needed.
+//!
+//! Frame layout: `SCE1`, key ID length (1 byte), key ID, 12-byte nonce,
+//! ciphertext length (4 bytes, big endian), ciphertext with its 16-byte tag.
+//! Frames are self-delimiting, so a file may hold any number of them.
+
+use crate::config::{EncryptionConfig, EncryptionKeyConfig};
+use crate::{Error, Result};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::process::Command;
+
+const MAGIC: &[u8; 4] = b"SCE1";
+
+/// Length of an AES-256 key in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Decode a base64 key and check its length
+pub(crate) fn decode_key(encoded: &str) -> std::result::Result<Vec<u8>, String> {
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("key is not base64: {}", e))?;
+    if key.len() != KEY_LEN {
+        return Err(format!("key is {} bytes, AES-256 needs {}", key.len(), KEY_LEN));
+    }
+    Ok(key)
+}
+
+/// Fetch a key from wherever its configuration points
+fn resolve_key(config: &EncryptionKeyConfig) -> Result<Vec<u8>> {
+    let fail = |message: String| Error::Config(format!("storage.encryption key {}: {}", config.id, message));
+    let encoded = if let Some(key) = &config.key {
+        key.clone()
+    } else if let Some(var) = &config.key_env {
+        std::env::var(var).map_err(|_| fail(format!("environment variable {} is not set", var)))?
+    } else if let Some(command) = &config.key_command {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| fail(format!("could not run key_command: {}", e)))?;
+        if !output.status.success() {
+            return Err(fail(format!("key_command exited with {}", output.status)));
+        }
+        String::from_utf8(output.stdout).map_err(|_| fail("key_command printed non-UTF-8 output".to_string()))?
+    } else {
+        return Err(fail("no key source".to_string()));
+    };
+    decode_key(&encoded).map_err(fail)
+}
+
+/// Seals and opens frames with the configured keys
+pub struct RecordCipher {
+    /// Key IDs and keys; the first seals
+    keys: Vec<(String, LessSafeKey)>,
+    random: SystemRandom,
+}
+
+impl std::fmt::Debug for RecordCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|(id, _)| id.as_str()).collect();
+        f.debug_struct("RecordCipher").field("keys", &ids).finish()
+    }
+}
+
+impl RecordCipher {
+    /// Cipher from raw keys, the first sealing
+    pub fn new(keys: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(Error::Config("storage.encryption needs at least one key".into()));
+        }
+        let keys = keys
+            .into_iter()
+            .map(|(id, key)| {
+                let key = UnboundKey::new(&AES_256_GCM, &key)
+                    .map_err(|_| Error::Config(format!("storage.encryption key {} is not an AES-256 key", id)))?;
+                Ok((id, LessSafeKey::new(key)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            keys,
+            random: SystemRandom::new(),
+        })
+    }
+
+    /// Cipher from configuration, reading keys from their sources
+    pub fn from_config(config: &EncryptionConfig) -> Result<Self> {
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| Ok((key.id.clone(), resolve_key(key)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(keys)
+    }
+
+    /// ID of the key new frames are sealed with
+    pub fn current_key_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    /// Seal `plaintext` into one frame; `aad` must be given again to open it
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let (id, key) = &self.keys[0];
+        let mut nonce = [0u8; NONCE_LEN];
+        self.random
+            .fill(&mut nonce)
+            .map_err(|_| Error::Storage("no randomness for an encryption nonce".into()))?;
+        let mut sealed = plaintext.to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut sealed)
+            .map_err(|_| Error::Storage("encryption failed".into()))?;
+        let length = u32::try_from(sealed.len()).map_err(|_| Error::Storage("record too large to encrypt".into()))?;
+
+        let mut frame = Vec::with_capacity(MAGIC.len() + 1 + id.len() + NONCE_LEN + 4 + sealed.len());
+        frame.extend_from_slice(MAGIC);
+        frame.push(id.len() as u8);
+        frame.extend_from_slice(id.as_bytes());
+        frame.extend_from_slice(&nonce);
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.extend_from_slice(&sealed);
+        Ok(frame)
+    }
+
+    /// Open every frame in `data`, concatenating their plaintexts
+    pub fn open(&self, mut data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let corrupt = |what: &str| Error::Storage(format!("encrypted data is corrupt: {}", what));
+        let mut plaintext = Vec::with_capacity(data.len());
+        while !data.is_empty() {
+            let rest = data.strip_prefix(MAGIC.as_slice()).ok_or_else(|| corrupt("bad frame marker"))?;
+            let (&id_len, rest) = rest.split_first().ok_or_else(|| corrupt("truncated frame"))?;
+            let id_len = id_len as usize;
+            if rest.len() < id_len + NONCE_LEN + 4 {
+                return Err(corrupt("truncated frame"));
+            }
+            let (id, rest) = rest.split_at(id_len);
+            let (nonce, rest) = rest.split_at(NONCE_LEN);
+            let (length, rest) = rest.split_at(4);
+            let length = u32::from_be_bytes(length.try_into().expect("four bytes")) as usize;
+            if rest.len() < length {
+                return Err(corrupt("truncated frame"));
+            }
+            let (sealed, rest) = rest.split_at(length);
+
+            let id = String::from_utf8_lossy(id);
+            let (_, key) = self.keys.iter().find(|(known, _)| *known == id).ok_or_else(|| {
+                Error::Storage(format!("data is encrypted with key {}, which is not configured", id))
+            })?;
+            let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| corrupt("bad nonce"))?;
+            let mut opened = sealed.to_vec();
+            let opened = key
+                .open_in_place(nonce, Aad::from(aad), &mut opened)
+                .map_err(|_| Error::Storage(format!("data sealed with key {} failed authentication", id)))?;
+            plaintext.extend_from_slice(opened);
+            data = rest;
+        }
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_and_rotation() {
+        let old = RecordCipher::new(vec![("2024-01".into(), vec![1; KEY_LEN])]).unwrap();
+        let mut data = old.seal(b"first batch\n", b"cdms-2024-01-15").unwrap();
+        assert!(!data.windows(5).any(|w| w == b"first"));
+
+        // After rotation the new key seals, and both open
+        let rotated = RecordCipher::new(vec![("2024-06".into(), vec![2; KEY_LEN]), ("2024-01".into(), vec![1; KEY_LEN])]).unwrap();
+        assert_eq!(rotated.current_key_id(), "2024-06");
+        data.extend(rotated.seal(b"second batch\n", b"cdms-2024-01-15").unwrap());
+        assert_eq!(rotated.open(&data, b"cdms-2024-01-15").unwrap(), b"first batch\nsecond batch\n");
+
+        // The old cipher alone cannot read the new frame
+        assert!(matches!(old.open(&data, b"cdms-2024-01-15"), Err(Error::Storage(_))));
+        // Frames are bound to their file
+        assert!(rotated.open(&data, b"cdms-2024-01-16").is_err());
+        let mut tampered = data.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(rotated.open(&tampered, b"cdms-2024-01-15").is_err());
+        assert!(rotated.open(&data[..data.len() - 3], b"cdms-2024-01-15").is_err());
+    }
+
+    #[test]
+    fn test_key_sources() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode([7u8; KEY_LEN]);
+        let key = |yaml: &str| serde_yaml::from_str::<EncryptionKeyConfig>(yaml).unwrap();
+        assert_eq!(resolve_key(&key(&format!("{{ id: a, key: '{}' }}", encoded))).unwrap(), vec![7; KEY_LEN]);
+        assert_eq!(
+            resolve_key(&key(&format!("{{ id: a, key_command: 'echo {}' }}", encoded))).unwrap(),
+            vec![7; KEY_LEN]
+        );
+        assert!(resolve_key(&key("{ id: a, key_command: 'exit 3' }")).is_err());
+        assert!(resolve_key(&key("{ id: a, key_env: SPACECOMMS_TEST_UNSET_KEY }")).is_err());
+        assert!(decode_key("c2hvcnQ=").is_err());
+    }
+}
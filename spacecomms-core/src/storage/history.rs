@@ -0,0 +1,145 @@
+//! Versioned history of object states
+//!
+//! Each state an object is stored with gets the next version number for
+//! that object, so operators can follow how its reported orbit evolved, for
+//! instance around a conjunction. Backends keep the latest
+//! `storage.object_history_limit` states per object and forget an object's
+//! history when the object itself is withdrawn or evicted.
+
+use crate::cdm::ObjectRecord;
+use crate::orbit::{norm, propagate, PropagationModel};
+use crate::protocol::StateVector;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A state an object was stored with
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ObjectStateChange {
+    /// Counts up from 1 for each object
+    pub version: u64,
+    pub epoch: DateTime<Utc>,
+    pub state_vector: StateVector,
+    pub source_node: String,
+    pub recorded_at: DateTime<Utc>,
+    /// Completed maneuver that produced this state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maneuver_id: Option<String>,
+}
+
+impl ObjectStateChange {
+    /// The state `obj` is stored with; the backend assigns the version
+    pub fn new(obj: &ObjectRecord, maneuver_id: Option<String>) -> Self {
+        Self {
+            version: 0,
+            epoch: obj.epoch,
+            state_vector: obj.state_vector.clone(),
+            source_node: obj.source_node.clone(),
+            recorded_at: obj.last_updated,
+            maneuver_id,
+        }
+    }
+}
+
+/// How a state differs from the version before it
+///
+/// States at different epochs are compared by propagating the earlier one
+/// to the later epoch with the J2 model, so the residuals show how far the
+/// new report departs from where the old one said the object would be.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StateDiff {
+    /// Version compared with
+    pub from_version: u64,
+    /// Seconds from the earlier state's epoch to this one's
+    pub epoch_delta_seconds: f64,
+    /// Distance from the propagated earlier state; None when it cannot be
+    /// propagated or is in another frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_residual_km: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity_residual_m_s: Option<f64>,
+    pub frame_changed: bool,
+    pub source_changed: bool,
+}
+
+impl StateDiff {
+    /// Compare `later` with the `earlier` version
+    pub fn between(earlier: &ObjectStateChange, later: &ObjectStateChange) -> Self {
+        let frame_changed = !earlier
+            .state_vector
+            .reference_frame
+            .eq_ignore_ascii_case(&later.state_vector.reference_frame);
+        let from = earlier.state_vector.epoch.unwrap_or(earlier.epoch);
+        let to = later.state_vector.epoch.unwrap_or(later.epoch);
+        let residuals = (!frame_changed)
+            .then(|| propagate(&earlier.state_vector, from, to, PropagationModel::J2).ok())
+            .flatten()
+            .map(|expected| {
+                let sv = &later.state_vector;
+                let position = [sv.x_km - expected.x_km, sv.y_km - expected.y_km, sv.z_km - expected.z_km];
+                let velocity = [sv.vx_km_s - expected.vx_km_s, sv.vy_km_s - expected.vy_km_s, sv.vz_km_s - expected.vz_km_s];
+                (norm(position), norm(velocity) * 1000.0)
+            });
+        Self {
+            from_version: earlier.version,
+            epoch_delta_seconds: (to - from).num_milliseconds() as f64 / 1000.0,
+            position_residual_km: residuals.map(|(position, _)| position),
+            velocity_residual_m_s: residuals.map(|(_, velocity)| velocity),
+            frame_changed,
+            source_changed: earlier.source_node != later.source_node,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit::EARTH_MU_KM3_S2;
+    use chrono::TimeZone;
+
+    fn state(version: u64, epoch: DateTime<Utc>, state_vector: StateVector) -> ObjectStateChange {
+        ObjectStateChange {
+            version,
+            epoch,
+            state_vector,
+            source_node: "node-b".into(),
+            recorded_at: epoch,
+            maneuver_id: None,
+        }
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let epoch = Utc.with_ymd_and_hms(2024, 1, 16, 6, 0, 0).unwrap();
+        let circular = StateVector {
+            reference_frame: "TEME".into(),
+            epoch: Some(epoch),
+            x_km: 7000.0,
+            y_km: 0.0,
+            z_km: 0.0,
+            vx_km_s: 0.0,
+            vy_km_s: (EARTH_MU_KM3_S2 / 7000.0).sqrt(),
+            vz_km_s: 0.0,
+        };
+        let earlier = state(1, epoch, circular.clone());
+        let later_epoch = epoch + chrono::Duration::minutes(30);
+        let mut expected = propagate(&circular, epoch, later_epoch, PropagationModel::J2).unwrap();
+
+        // A report where the old state said the object would be
+        let on_track = state(2, later_epoch, expected.clone());
+        let diff = StateDiff::between(&earlier, &on_track);
+        assert_eq!((diff.from_version, diff.epoch_delta_seconds), (1, 1800.0));
+        assert!(diff.position_residual_km.unwrap() < 1e-9);
+        assert!(!diff.frame_changed && !diff.source_changed);
+
+        // A burn of 1 m/s along track
+        expected.vy_km_s += 0.001;
+        let burned = StateDiff::between(&earlier, &state(2, later_epoch, expected.clone()));
+        assert!((burned.velocity_residual_m_s.unwrap() - 1.0).abs() < 1e-6);
+
+        expected.reference_frame = "ITRF".into();
+        let reframed = StateDiff::between(&earlier, &state(2, later_epoch, expected));
+        assert!(reframed.frame_changed);
+        assert_eq!(reframed.position_residual_km, None);
+    }
+}
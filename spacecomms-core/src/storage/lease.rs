@@ -0,0 +1,24 @@
+//! Named leases held by one instance at a time
+//!
+//! Instances sharing a storage backend use leases to agree on which of them
+//! does something, such as leading a node. A lease is held until it expires
+//! unless its holder renews it; after that anyone may take it.
+
+use chrono::{DateTime, Utc};
+
+/// A lease as it stands after an attempt to take it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease {
+    pub name: String,
+    pub holder: String,
+    /// When the current holder took it; renewals keep this
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    /// Whether the lease has run out at `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
@@ -1,27 +1,218 @@
 //! In-memory storage implementation
 
-use crate::cdm::{CdmRecord, ObjectRecord};
-use crate::storage::Storage;
+use crate::cdm::{CdmRecord, ConjunctionKey, ObjectRecord};
+use crate::config::{EvictionPolicy, ObjectLimitsConfig, PeerPolicies};
+use crate::protocol::{DeliveryReceiptPayload, ProvenanceHop};
+use crate::storage::{
+    entry_footprint, Footprint, ApiTokenRecord, BlockedNode, CapacityHook, IdempotencyClaim, IdempotentResponse, Lease, MemoryBudget, MemoryCategory, ObjectCapacity,
+    ObjectCatalog, ObjectStateChange, StatMetric, StatSample, StatSeries, Storage, Versioned, WithdrawnCdm, WriteOutcome, ENTRY_OVERHEAD,
+    MAX_RECEIPTS_PER_CDM,
+};
 use crate::{Error, Result};
 use async_trait::async_trait;
-use std::collections::{HashMap, HashSet};
-use std::sync::RwLock;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument};
+
+/// Seen message IDs, oldest first for pruning under memory pressure
+#[derive(Default)]
+struct SeenMessages {
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenMessages {
+    /// Estimated cost of tracking one message ID (it is held twice)
+    fn entry_bytes(message_id: &str) -> usize {
+        2 * (ENTRY_OVERHEAD + std::mem::size_of::<String>() + message_id.len())
+    }
+}
+
+/// A claimed idempotency key and, once completed, its response
+struct IdempotencyEntry {
+    fingerprint: u64,
+    response: Option<IdempotentResponse>,
+    expires_at: Instant,
+    /// Position in the claim order
+    seq: u64,
+    /// Bytes charged to the budget
+    bytes: usize,
+}
+
+/// Idempotency keys, oldest claim first for expiry and pruning under memory
+/// pressure
+#[derive(Default)]
+struct IdempotencyKeys {
+    entries: HashMap<String, IdempotencyEntry>,
+    /// Claims in order; stale after a key is released and claimed again
+    order: VecDeque<(u64, String)>,
+    last_seq: u64,
+}
+
+impl IdempotencyKeys {
+    /// Estimated cost of a claim before its response is recorded (the key is
+    /// held twice)
+    fn claim_bytes(key: &str) -> usize {
+        2 * (ENTRY_OVERHEAD + std::mem::size_of::<String>() + key.len()) + std::mem::size_of::<IdempotencyEntry>()
+    }
+
+    /// Forget the oldest claim, returning the bytes it held
+    fn pop_oldest(&mut self) -> Option<usize> {
+        while let Some((seq, key)) = self.order.pop_front() {
+            if self.entries.get(&key).is_some_and(|e| e.seq == seq) {
+                return self.entries.remove(&key).map(|e| e.bytes);
+            }
+        }
+        None
+    }
+}
+
+/// Stored CDMs indexed by ID and by conjunction
+#[derive(Default)]
+struct CdmTable {
+    records: HashMap<String, CdmRecord>,
+    revisions: HashMap<String, u64>,
+    conjunctions: HashMap<ConjunctionKey, BTreeSet<String>>,
+    bucket_seconds: u64,
+    /// Last revision handed out; shared by all CDMs so it never repeats
+    last_revision: u64,
+    /// Withdrawn CDMs, oldest withdrawal first
+    withdrawn: VecDeque<WithdrawnCdm>,
+    history_limit: usize,
+    /// Provenance chains of stored CDMs, dropped with the CDM
+    provenance: HashMap<String, Vec<ProvenanceHop>>,
+    /// Delivery receipts of stored CDMs, oldest first, dropped with the CDM
+    receipts: HashMap<String, Vec<DeliveryReceiptPayload>>,
+}
+
+impl CdmTable {
+    /// Bytes charged for a stored CDM and the receipts recorded on it
+    fn footprint(&self, id: &str) -> usize {
+        let record = self.records.get(id).map_or(0, |cdm| entry_footprint(&cdm.cdm_id, cdm));
+        let receipts = self.receipts.get(id).map_or(0, |held| held.iter().map(receipt_footprint).sum());
+        record + receipts
+    }
+
+    /// Store a CDM under a new revision and return it
+    fn insert(&mut self, cdm: CdmRecord) -> u64 {
+        self.remove(&cdm.cdm_id);
+        self.last_revision += 1;
+        let key = ConjunctionKey::for_cdm(&cdm, self.bucket_seconds);
+        self.conjunctions.entry(key).or_default().insert(cdm.cdm_id.clone());
+        self.revisions.insert(cdm.cdm_id.clone(), self.last_revision);
+        self.records.insert(cdm.cdm_id.clone(), cdm);
+        self.last_revision
+    }
+
+    fn remove(&mut self, id: &str) -> Option<CdmRecord> {
+        let cdm = self.records.remove(id)?;
+        self.revisions.remove(id);
+        self.provenance.remove(id);
+        self.receipts.remove(id);
+        let key = ConjunctionKey::for_cdm(&cdm, self.bucket_seconds);
+        if let Some(ids) = self.conjunctions.get_mut(&key) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.conjunctions.remove(&key);
+            }
+        }
+        Some(cdm)
+    }
+}
+
+/// Estimated cost of keeping a delivery receipt
+fn receipt_footprint(receipt: &DeliveryReceiptPayload) -> usize {
+    ENTRY_OVERHEAD + receipt.footprint()
+}
 
 /// In-memory storage backend
 pub struct MemoryStorage {
-    cdms: RwLock<HashMap<String, CdmRecord>>,
-    objects: RwLock<HashMap<String, ObjectRecord>>,
-    seen_messages: RwLock<HashSet<String>>,
+    cdms: RwLock<CdmTable>,
+    objects: RwLock<ObjectCatalog>,
+    /// States of tracked objects, oldest first
+    object_states: RwLock<HashMap<String, VecDeque<ObjectStateChange>>>,
+    object_history_limit: usize,
+    seen_messages: RwLock<SeenMessages>,
+    idempotency: RwLock<IdempotencyKeys>,
+    peer_policies: RwLock<HashMap<String, PeerPolicies>>,
+    blocked_nodes: RwLock<HashMap<String, BlockedNode>>,
+    api_tokens: RwLock<HashMap<String, ApiTokenRecord>>,
+    leases: RwLock<HashMap<String, Lease>>,
+    stats: RwLock<StatSeries>,
+    budget: Arc<MemoryBudget>,
 }
 
 impl MemoryStorage {
     /// Create a new in-memory storage
     pub fn new() -> Self {
+        Self::with_object_limits(ObjectLimitsConfig::default())
+    }
+
+    /// Create an in-memory storage with object catalog limits
+    pub fn with_object_limits(limits: ObjectLimitsConfig) -> Self {
         Self {
-            cdms: RwLock::new(HashMap::new()),
-            objects: RwLock::new(HashMap::new()),
-            seen_messages: RwLock::new(HashSet::new()),
+            cdms: RwLock::new(CdmTable {
+                bucket_seconds: crate::config::StorageConfig::default().conjunction_bucket_seconds,
+                history_limit: crate::config::StorageConfig::default().cdm_history_limit,
+                ..Default::default()
+            }),
+            objects: RwLock::new(ObjectCatalog::new(limits)),
+            object_states: RwLock::new(HashMap::new()),
+            object_history_limit: crate::config::StorageConfig::default().object_history_limit,
+            seen_messages: RwLock::new(SeenMessages::default()),
+            idempotency: RwLock::new(IdempotencyKeys::default()),
+            peer_policies: RwLock::new(HashMap::new()),
+            blocked_nodes: RwLock::new(HashMap::new()),
+            api_tokens: RwLock::new(HashMap::new()),
+            leases: RwLock::new(HashMap::new()),
+            stats: RwLock::new(StatSeries::default()),
+            budget: Arc::new(MemoryBudget::default()),
+        }
+    }
+
+    /// Charge records against a memory budget; set before storing anything
+    pub fn with_memory_budget(mut self, budget: Arc<MemoryBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Group CDMs into conjunctions by TCA buckets of this width; set before
+    /// storing anything
+    pub fn with_conjunction_bucket(self, seconds: u64) -> Self {
+        if let Ok(mut cdms) = self.cdms.write() {
+            cdms.bucket_seconds = seconds;
+        }
+        self
+    }
+
+    /// Keep up to `limit` withdrawn CDMs for history; set before storing
+    /// anything
+    pub fn with_cdm_history(self, limit: usize) -> Self {
+        if let Ok(mut cdms) = self.cdms.write() {
+            cdms.history_limit = limit;
+        }
+        self
+    }
+
+    /// Keep up to `limit` states per object; set before storing anything
+    pub fn with_object_history(mut self, limit: usize) -> Self {
+        self.object_history_limit = limit;
+        self
+    }
+
+    /// Count a refusal and describe it
+    fn budget_exhausted(&self) -> String {
+        self.budget.record_rejection();
+        format!("memory budget of {} bytes exhausted", self.budget.max_bytes().unwrap_or_default())
+    }
+
+    /// Register a hook notified of catalog capacity events
+    pub fn with_capacity_hook(self, hook: CapacityHook) -> Self {
+        if let Ok(mut objects) = self.objects.write() {
+            objects.set_hook(hook);
         }
+        self
     }
 }
 
@@ -31,58 +222,308 @@ impl Default for MemoryStorage {
     }
 }
 
+impl MemoryStorage {
+    /// Store an object and record its state in the object's history
+    fn write_object(&self, obj: ObjectRecord, maneuver_id: Option<String>) -> Result<()> {
+        let mut objects = self.objects.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let size = entry_footprint(&obj.object_id, &obj);
+        let replaced = objects.get(&obj.object_id).map_or(0, |old| entry_footprint(&old.object_id, old));
+
+        // Reserve room, evicting in catalog order if the policy allows
+        let trusted = objects.is_trusted(&obj.source_node);
+        while !self.budget.try_charge(MemoryCategory::Objects, size, replaced) {
+            let evicted = match self.budget.eviction() {
+                EvictionPolicy::OldestEpoch => objects.evict_below(&obj, trusted),
+                EvictionPolicy::Reject => None,
+            };
+            if evicted.is_none() {
+                let reason = self.budget_exhausted();
+                return Err(objects.reject_for(&obj, reason));
+            }
+            self.budget.record_eviction();
+            self.budget.reconcile(MemoryCategory::Objects, objects.bytes());
+        }
+
+        // The catalog may still refuse the object or evict for its own limits
+        let mut change = ObjectStateChange::new(&obj, maneuver_id);
+        let object_id = obj.object_id.clone();
+        let result = objects.insert(obj);
+        self.budget.reconcile(MemoryCategory::Objects, objects.bytes());
+        result?;
+        if self.object_history_limit == 0 {
+            return Ok(());
+        }
+
+        let mut states = self.object_states.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let history = states.entry(object_id).or_default();
+        change.version = history.back().map_or(1, |last| last.version + 1);
+        while history.len() >= self.object_history_limit {
+            history.pop_front();
+        }
+        history.push_back(change);
+        if states.len() > objects.len() {
+            // Objects evicted to make room take their history with them
+            states.retain(|id, _| objects.get(id).is_some());
+        }
+        Ok(())
+    }
+
+    /// Charge the budget for a CDM and store it, with the table lock held
+    fn write_cdm(&self, cdms: &mut CdmTable, cdm: CdmRecord) -> Result<u64> {
+        let size = entry_footprint(&cdm.cdm_id, &cdm);
+        // Storing a CDM again drops its receipts
+        let replaced = cdms.footprint(&cdm.cdm_id);
+
+        // Make room by dropping withdrawn history, then the CDMs whose
+        // conjunctions happen earliest
+        while !self.budget.try_charge(MemoryCategory::Cdms, size, replaced) {
+            if self.forget_oldest_withdrawn(cdms) {
+                continue;
+            }
+            let victim = match self.budget.eviction() {
+                EvictionPolicy::OldestEpoch => cdms
+                    .records
+                    .values()
+                    .filter(|c| c.cdm_id != cdm.cdm_id)
+                    .min_by_key(|c| c.tca)
+                    .map(|c| c.cdm_id.clone()),
+                EvictionPolicy::Reject => None,
+            };
+            let Some((old, released)) = victim.and_then(|id| {
+                let released = cdms.footprint(&id);
+                cdms.remove(&id).map(|old| (old, released))
+            }) else {
+                return Err(Error::QuotaExceeded(self.budget_exhausted()));
+            };
+            debug!("Evicted CDM {} to admit {}", old.cdm_id, cdm.cdm_id);
+            self.budget.release(MemoryCategory::Cdms, released);
+            self.budget.record_eviction();
+        }
+
+        Ok(cdms.insert(cdm))
+    }
+
+    /// Keep a withdrawn CDM for history if the limit and budget allow
+    fn archive_withdrawn(&self, cdms: &mut CdmTable, cdm: CdmRecord) {
+        if cdms.history_limit == 0 {
+            return;
+        }
+        let size = entry_footprint(&cdm.cdm_id, &cdm);
+        while cdms.withdrawn.len() >= cdms.history_limit || !self.budget.try_charge(MemoryCategory::Cdms, size, 0) {
+            if !self.forget_oldest_withdrawn(cdms) {
+                return;
+            }
+        }
+        cdms.withdrawn.push_back(WithdrawnCdm {
+            record: cdm,
+            withdrawn_at: chrono::Utc::now(),
+        });
+    }
+
+    /// Drop the oldest withdrawn CDM; false if none is kept
+    fn forget_oldest_withdrawn(&self, cdms: &mut CdmTable) -> bool {
+        let Some(oldest) = cdms.withdrawn.pop_front() else {
+            return false;
+        };
+        self.budget.release(MemoryCategory::Cdms, entry_footprint(&oldest.record.cdm_id, &oldest.record));
+        true
+    }
+}
+
 #[async_trait]
 impl Storage for MemoryStorage {
+    #[instrument(name = "storage.store_cdm", skip_all, fields(cdm_id = %cdm.cdm_id))]
     async fn store_cdm(&self, cdm: CdmRecord) -> Result<()> {
         let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        cdms.insert(cdm.cdm_id.clone(), cdm);
-        Ok(())
+        self.write_cdm(&mut cdms, cdm).map(|_| ())
+    }
+
+    #[instrument(name = "storage.get_cdm_versioned", skip_all, fields(id = %id))]
+    async fn get_cdm_versioned(&self, id: &str) -> Result<Option<Versioned<CdmRecord>>> {
+        let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(cdms.records.get(id).map(|cdm| Versioned {
+            revision: cdms.revisions.get(id).copied().unwrap_or_default(),
+            record: cdm.clone(),
+        }))
+    }
+
+    #[instrument(name = "storage.upsert_cdm_if_newer", skip_all, fields(cdm_id = %cdm.cdm_id))]
+    async fn upsert_cdm_if_newer(&self, cdm: CdmRecord) -> Result<WriteOutcome> {
+        let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let previous = cdms.revisions.get(&cdm.cdm_id).copied();
+        if let Some(stored) = cdms.records.get(&cdm.cdm_id) {
+            if stored.creation_date >= cdm.creation_date {
+                return Ok(WriteOutcome::Skipped { current: previous });
+            }
+        }
+        let revision = self.write_cdm(&mut cdms, cdm)?;
+        Ok(WriteOutcome::Written { revision, previous })
     }
 
+    #[instrument(name = "storage.compare_and_swap_cdm", skip_all, fields(cdm_id = %cdm.cdm_id))]
+    async fn compare_and_swap_cdm(&self, cdm: CdmRecord, expected: Option<u64>) -> Result<WriteOutcome> {
+        let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let previous = cdms.revisions.get(&cdm.cdm_id).copied();
+        if previous != expected {
+            return Ok(WriteOutcome::Skipped { current: previous });
+        }
+        let revision = self.write_cdm(&mut cdms, cdm)?;
+        Ok(WriteOutcome::Written { revision, previous })
+    }
+
+    #[instrument(name = "storage.get_cdm", skip_all, fields(id = %id))]
     async fn get_cdm(&self, id: &str) -> Result<Option<CdmRecord>> {
         let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        Ok(cdms.get(id).cloned())
+        Ok(cdms.records.get(id).cloned())
     }
 
+    #[instrument(name = "storage.list_cdms", skip_all)]
     async fn list_cdms(&self) -> Result<Vec<CdmRecord>> {
         let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        Ok(cdms.values().cloned().collect())
+        Ok(cdms.records.values().cloned().collect())
     }
 
+    #[instrument(name = "storage.withdraw_cdm", skip_all, fields(id = %id))]
     async fn withdraw_cdm(&self, id: &str) -> Result<()> {
         let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        if cdms.remove(id).is_none() {
+        let released = cdms.footprint(id);
+        let Some(cdm) = cdms.remove(id) else {
             return Err(Error::NotFound(format!("CDM not found: {}", id)));
+        };
+        self.budget.release(MemoryCategory::Cdms, released);
+        self.archive_withdrawn(&mut cdms, cdm);
+        Ok(())
+    }
+
+    async fn store_cdm_provenance(&self, cdm_id: &str, chain: Vec<ProvenanceHop>) -> Result<()> {
+        let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        if !cdms.records.contains_key(cdm_id) {
+            return Err(Error::NotFound(format!("CDM not found: {}", cdm_id)));
         }
+        cdms.provenance.insert(cdm_id.to_string(), chain);
         Ok(())
     }
 
+    async fn get_cdm_provenance(&self, cdm_id: &str) -> Result<Option<Vec<ProvenanceHop>>> {
+        let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(cdms.provenance.get(cdm_id).cloned())
+    }
+
+    async fn store_delivery_receipt(&self, cdm_id: &str, receipt: DeliveryReceiptPayload) -> Result<()> {
+        let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        if !cdms.records.contains_key(cdm_id) {
+            return Err(Error::NotFound(format!("CDM not found: {}", cdm_id)));
+        }
+        let receipts = cdms.receipts.entry(cdm_id.to_string()).or_default();
+        // Bounded by the cap, so the scan stays short
+        let held = receipts.iter().position(|held| held.receiving_node_id == receipt.receiving_node_id);
+        if held.is_none() && receipts.len() >= MAX_RECEIPTS_PER_CDM {
+            return Err(Error::LimitExceeded(format!(
+                "CDM {} already holds receipts from {} nodes",
+                cdm_id, MAX_RECEIPTS_PER_CDM
+            )));
+        }
+        let replaced = held.map_or(0, |i| receipt_footprint(&receipts[i]));
+        if !self.budget.try_charge(MemoryCategory::Cdms, receipt_footprint(&receipt), replaced) {
+            return Err(Error::QuotaExceeded(self.budget_exhausted()));
+        }
+        if let Some(i) = held {
+            receipts.remove(i);
+        }
+        receipts.push(receipt);
+        Ok(())
+    }
+
+    async fn get_delivery_receipts(&self, cdm_id: &str) -> Result<Vec<DeliveryReceiptPayload>> {
+        let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(cdms.receipts.get(cdm_id).cloned().unwrap_or_default())
+    }
+
     async fn cdm_count(&self) -> Result<usize> {
         let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        Ok(cdms.len())
+        Ok(cdms.records.len())
+    }
+
+    async fn list_withdrawn_cdms(&self) -> Result<Vec<WithdrawnCdm>> {
+        let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(cdms.withdrawn.iter().cloned().collect())
+    }
+
+    async fn discard_withdrawn_cdms(&self, archived: &[WithdrawnCdm]) -> Result<()> {
+        let mut cdms = self.cdms.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let archived: HashSet<(&str, chrono::DateTime<chrono::Utc>)> =
+            archived.iter().map(|w| (w.record.cdm_id.as_str(), w.withdrawn_at)).collect();
+        let mut released = 0;
+        cdms.withdrawn.retain(|w| {
+            let discard = archived.contains(&(w.record.cdm_id.as_str(), w.withdrawn_at));
+            if discard {
+                released += entry_footprint(&w.record.cdm_id, &w.record);
+            }
+            !discard
+        });
+        self.budget.release(MemoryCategory::Cdms, released);
+        Ok(())
+    }
+
+    #[instrument(name = "storage.list_conjunctions", skip_all)]
+    async fn list_conjunctions(&self) -> Result<Vec<(ConjunctionKey, Vec<CdmRecord>)>> {
+        let cdms = self.cdms.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(cdms
+            .conjunctions
+            .iter()
+            .map(|(key, ids)| (key.clone(), ids.iter().filter_map(|id| cdms.records.get(id).cloned()).collect()))
+            .collect())
     }
 
+    #[instrument(name = "storage.store_object", skip_all, fields(object_id = %obj.object_id))]
     async fn store_object(&self, obj: ObjectRecord) -> Result<()> {
-        let mut objects = self.objects.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        objects.insert(obj.object_id.clone(), obj);
+        self.write_object(obj, None)
+    }
+
+    #[instrument(name = "storage.store_maneuvered_object", skip_all, fields(object_id = %obj.object_id))]
+    async fn store_maneuvered_object(&self, obj: ObjectRecord, maneuver_id: &str) -> Result<()> {
+        self.write_object(obj, Some(maneuver_id.to_string()))
+    }
+
+    async fn object_state_history(&self, id: &str) -> Result<Vec<ObjectStateChange>> {
+        let states = self.object_states.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(states.get(id).map(|h| h.iter().cloned().collect()).unwrap_or_default())
+    }
+
+    async fn restore_object_history(&self, id: &str, history: Vec<ObjectStateChange>) -> Result<()> {
+        let objects = self.objects.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        if objects.get(id).is_none() {
+            return Err(Error::NotFound(format!("Object not found: {}", id)));
+        }
+        if self.object_history_limit == 0 {
+            return Ok(());
+        }
+        let skip = history.len().saturating_sub(self.object_history_limit);
+        let mut states = self.object_states.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        states.insert(id.to_string(), history.into_iter().skip(skip).collect());
         Ok(())
     }
 
+    #[instrument(name = "storage.get_object", skip_all, fields(id = %id))]
     async fn get_object(&self, id: &str) -> Result<Option<ObjectRecord>> {
         let objects = self.objects.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
         Ok(objects.get(id).cloned())
     }
 
+    #[instrument(name = "storage.list_objects", skip_all)]
     async fn list_objects(&self) -> Result<Vec<ObjectRecord>> {
         let objects = self.objects.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
         Ok(objects.values().cloned().collect())
     }
 
+    #[instrument(name = "storage.withdraw_object", skip_all, fields(id = %id))]
     async fn withdraw_object(&self, id: &str) -> Result<()> {
         let mut objects = self.objects.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
         if objects.remove(id).is_none() {
             return Err(Error::NotFound(format!("Object not found: {}", id)));
         }
+        self.budget.reconcile(MemoryCategory::Objects, objects.bytes());
+        self.object_states.write().map_err(|_| Error::Storage("lock poisoned".into()))?.remove(id);
         Ok(())
     }
 
@@ -91,16 +532,223 @@ impl Storage for MemoryStorage {
         Ok(objects.len())
     }
 
+    async fn object_capacity(&self) -> Result<ObjectCapacity> {
+        let objects = self.objects.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(objects.capacity())
+    }
+
+    #[instrument(name = "storage.has_seen_message", skip_all)]
     async fn has_seen_message(&self, message_id: &str) -> Result<bool> {
         let seen = self.seen_messages.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        Ok(seen.contains(message_id))
+        Ok(seen.ids.contains(message_id))
     }
 
+    #[instrument(name = "storage.mark_message_seen", skip_all)]
     async fn mark_message_seen(&self, message_id: &str) -> Result<()> {
         let mut seen = self.seen_messages.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
-        seen.insert(message_id.to_string());
+        if seen.ids.contains(message_id) {
+            return Ok(());
+        }
+
+        // Forget the oldest IDs rather than refuse; hop limits still bound
+        // any loop a forgotten ID lets through
+        let size = SeenMessages::entry_bytes(message_id);
+        while !self.budget.try_charge(MemoryCategory::Dedup, size, 0) {
+            let Some(oldest) = seen.order.pop_front() else {
+                self.budget.charge(MemoryCategory::Dedup, size);
+                break;
+            };
+            seen.ids.remove(&oldest);
+            self.budget.release(MemoryCategory::Dedup, SeenMessages::entry_bytes(&oldest));
+        }
+
+        seen.ids.insert(message_id.to_string());
+        seen.order.push_back(message_id.to_string());
+        Ok(())
+    }
+
+    #[instrument(name = "storage.claim_idempotency_key", skip_all)]
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: u64, ttl: Duration) -> Result<IdempotencyClaim> {
+        let mut keys = self.idempotency.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let now = Instant::now();
+        // Expire from the oldest claim on
+        while let Some((seq, oldest)) = keys.order.front() {
+            match keys.entries.get(oldest) {
+                Some(entry) if entry.seq == *seq && entry.expires_at > now => break,
+                Some(entry) if entry.seq == *seq => {
+                    let oldest = oldest.clone();
+                    if let Some(expired) = keys.entries.remove(&oldest) {
+                        self.budget.release(MemoryCategory::Dedup, expired.bytes);
+                    }
+                }
+                _ => {}
+            }
+            keys.order.pop_front();
+        }
+
+        if let Some(entry) = keys.entries.get(key).filter(|e| e.expires_at > now) {
+            return Ok(if entry.fingerprint != fingerprint {
+                IdempotencyClaim::Mismatch
+            } else {
+                match &entry.response {
+                    Some(response) => IdempotencyClaim::Completed(response.clone()),
+                    None => IdempotencyClaim::InProgress,
+                }
+            });
+        }
+        if let Some(expired) = keys.entries.remove(key) {
+            self.budget.release(MemoryCategory::Dedup, expired.bytes);
+        }
+
+        // Forget the oldest keys rather than refuse the request
+        let bytes = IdempotencyKeys::claim_bytes(key);
+        while !self.budget.try_charge(MemoryCategory::Dedup, bytes, 0) {
+            let Some(released) = keys.pop_oldest() else {
+                self.budget.charge(MemoryCategory::Dedup, bytes);
+                break;
+            };
+            self.budget.release(MemoryCategory::Dedup, released);
+        }
+
+        keys.last_seq += 1;
+        let seq = keys.last_seq;
+        keys.order.push_back((seq, key.to_string()));
+        keys.entries.insert(
+            key.to_string(),
+            IdempotencyEntry {
+                fingerprint,
+                response: None,
+                expires_at: now + ttl,
+                seq,
+                bytes,
+            },
+        );
+        Ok(IdempotencyClaim::Claimed)
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, response: Option<IdempotentResponse>) -> Result<()> {
+        let mut keys = self.idempotency.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let Some(response) = response else {
+            if let Some(entry) = keys.entries.remove(key) {
+                self.budget.release(MemoryCategory::Dedup, entry.bytes);
+            }
+            return Ok(());
+        };
+        let Some(entry) = keys.entries.get_mut(key) else {
+            // Pruned while the request ran
+            return Ok(());
+        };
+        let body_bytes = response.body.to_string().len();
+        self.budget.charge(MemoryCategory::Dedup, body_bytes);
+        entry.bytes += body_bytes;
+        entry.response = Some(response);
         Ok(())
     }
+
+    async fn set_object_limits(&self, limits: ObjectLimitsConfig) -> Result<()> {
+        let mut objects = self.objects.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        objects.set_limits(limits);
+        Ok(())
+    }
+
+    async fn record_stats(
+        &self,
+        at: chrono::DateTime<chrono::Utc>,
+        samples: &[(StatMetric, f64)],
+        keep_since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let mut stats = self.stats.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        stats.record(at, samples, keep_since);
+        Ok(())
+    }
+
+    async fn stats_history(&self, metric: StatMetric, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<StatSample>> {
+        let stats = self.stats.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(stats.since(metric, since))
+    }
+
+    async fn store_peer_policies(&self, peer_id: &str, policies: &PeerPolicies) -> Result<()> {
+        let mut kept = self.peer_policies.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        kept.insert(peer_id.to_string(), policies.clone());
+        Ok(())
+    }
+
+    async fn list_peer_policies(&self) -> Result<Vec<(String, PeerPolicies)>> {
+        let kept = self.peer_policies.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(kept.iter().map(|(id, policies)| (id.clone(), policies.clone())).collect())
+    }
+
+    async fn remove_peer_policies(&self, peer_id: &str) -> Result<()> {
+        let mut kept = self.peer_policies.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        kept.remove(peer_id);
+        Ok(())
+    }
+
+    async fn store_blocked_node(&self, node: &BlockedNode) -> Result<()> {
+        let mut blocked = self.blocked_nodes.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        blocked.insert(node.node_id.clone(), node.clone());
+        Ok(())
+    }
+
+    async fn list_blocked_nodes(&self) -> Result<Vec<BlockedNode>> {
+        let blocked = self.blocked_nodes.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(blocked.values().cloned().collect())
+    }
+
+    async fn remove_blocked_node(&self, node_id: &str) -> Result<()> {
+        let mut blocked = self.blocked_nodes.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        blocked.remove(node_id);
+        Ok(())
+    }
+
+    async fn store_api_token(&self, token: ApiTokenRecord) -> Result<()> {
+        let mut tokens = self.api_tokens.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        tokens.insert(token.id.clone(), token);
+        Ok(())
+    }
+
+    async fn get_api_token(&self, id: &str) -> Result<Option<ApiTokenRecord>> {
+        let tokens = self.api_tokens.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        Ok(tokens.get(id).cloned())
+    }
+
+    async fn list_api_tokens(&self) -> Result<Vec<ApiTokenRecord>> {
+        let tokens = self.api_tokens.read().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let mut listed: Vec<ApiTokenRecord> = tokens.values().cloned().collect();
+        listed.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(listed)
+    }
+
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease> {
+        let mut leases = self.leases.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::from_std(ttl).map_err(|e| Error::Storage(e.to_string()))?;
+        let acquired_at = match leases.get(name) {
+            Some(lease) if lease.holder != holder && !lease.is_expired(now) => return Ok(lease.clone()),
+            Some(lease) if lease.holder == holder && !lease.is_expired(now) => lease.acquired_at,
+            _ => now,
+        };
+        let lease = Lease {
+            name: name.to_string(),
+            holder: holder.to_string(),
+            acquired_at,
+            expires_at,
+        };
+        leases.insert(name.to_string(), lease.clone());
+        Ok(lease)
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<()> {
+        let mut leases = self.leases.write().map_err(|_| Error::Storage("lock poisoned".into()))?;
+        if leases.get(name).is_some_and(|lease| lease.holder == holder) {
+            leases.remove(name);
+        }
+        Ok(())
+    }
+
+    fn memory_budget(&self) -> Option<Arc<MemoryBudget>> {
+        Some(self.budget.clone())
+    }
 }
 
 #[cfg(test)]
@@ -139,4 +787,293 @@ mod tests {
         storage.mark_message_seen("msg-1").await.unwrap();
         assert!(storage.has_seen_message("msg-1").await.unwrap());
     }
+
+    fn budgeted(cdms: usize, eviction: EvictionPolicy) -> MemoryStorage {
+        let cdm = generate_demo_cdm();
+        let limits = crate::config::MemoryLimitsConfig {
+            max_bytes: Some(cdms * entry_footprint(&cdm.cdm_id, &cdm) + 10),
+            eviction,
+            ..Default::default()
+        };
+        MemoryStorage::new().with_memory_budget(Arc::new(MemoryBudget::new(&limits)))
+    }
+
+    fn demo_cdm(id: &str, tca_hours: i64) -> CdmRecord {
+        let mut cdm = generate_demo_cdm();
+        cdm.cdm_id = format!("{:<width$}", id, width = cdm.cdm_id.len());
+        cdm.tca = chrono::Utc::now() + chrono::Duration::hours(tca_hours);
+        cdm
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_rejects() {
+        let storage = budgeted(2, EvictionPolicy::Reject);
+        storage.store_cdm(demo_cdm("A", 1)).await.unwrap();
+        storage.store_cdm(demo_cdm("B", 2)).await.unwrap();
+        assert!(matches!(storage.store_cdm(demo_cdm("C", 3)).await, Err(Error::QuotaExceeded(_))));
+        // Replacing a stored CDM needs no extra room
+        storage.store_cdm(demo_cdm("A", 4)).await.unwrap();
+
+        let usage = storage.memory_budget().unwrap().usage();
+        assert_eq!(usage.rejected, 1);
+        assert!(usage.cdm_bytes <= usage.max_bytes.unwrap());
+
+        storage.withdraw_cdm(&demo_cdm("A", 0).cdm_id).await.unwrap();
+        storage.store_cdm(demo_cdm("C", 3)).await.unwrap();
+    }
+
+    fn receipt(cdm_id: &str, receiving_node_id: &str) -> DeliveryReceiptPayload {
+        DeliveryReceiptPayload {
+            cdm_id: cdm_id.to_string(),
+            related_message_id: uuid::Uuid::new_v4().to_string(),
+            originator_node_id: "node-origin".to_string(),
+            receiving_node_id: receiving_node_id.to_string(),
+            matched_object_ids: vec!["NORAD-12345".to_string()],
+            hop_count: 1,
+            received_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receipt_limits() {
+        let storage = budgeted(100, EvictionPolicy::Reject);
+        let cdm = demo_cdm("A", 1);
+        storage.store_cdm(cdm.clone()).await.unwrap();
+        let budget = storage.memory_budget().unwrap();
+        let stored = budget.usage().cdm_bytes;
+
+        // A relay inventing receiving nodes fills the cap and no more
+        for i in 0..MAX_RECEIPTS_PER_CDM * 2 {
+            let result = storage.store_delivery_receipt(&cdm.cdm_id, receipt(&cdm.cdm_id, &format!("node-{}", i))).await;
+            assert_eq!(result.is_ok(), i < MAX_RECEIPTS_PER_CDM, "{}", i);
+            if i >= MAX_RECEIPTS_PER_CDM {
+                assert!(matches!(result, Err(Error::LimitExceeded(_))));
+            }
+        }
+        // A node already held still replaces its receipt, which moves last
+        storage.store_delivery_receipt(&cdm.cdm_id, receipt(&cdm.cdm_id, "node-0")).await.unwrap();
+        let receipts = storage.get_delivery_receipts(&cdm.cdm_id).await.unwrap();
+        assert_eq!(receipts.len(), MAX_RECEIPTS_PER_CDM);
+        assert_eq!(receipts.last().unwrap().receiving_node_id, "node-0");
+
+        // Receipts are charged to the budget and released with the CDM
+        let charged = budget.usage().cdm_bytes - stored;
+        assert_eq!(charged, receipts.iter().map(receipt_footprint).sum::<usize>());
+        storage.store_cdm(cdm.clone()).await.unwrap();
+        assert!(storage.get_delivery_receipts(&cdm.cdm_id).await.unwrap().is_empty());
+        assert_eq!(budget.usage().cdm_bytes, stored);
+
+        // and refused once it is exhausted
+        let storage = budgeted(1, EvictionPolicy::Reject);
+        storage.store_cdm(cdm.clone()).await.unwrap();
+        let mut refused = None;
+        for i in 0..MAX_RECEIPTS_PER_CDM {
+            if let Err(e) = storage.store_delivery_receipt(&cdm.cdm_id, receipt(&cdm.cdm_id, &format!("node-{}", i))).await {
+                refused = Some(e);
+                break;
+            }
+        }
+        assert!(matches!(refused, Some(Error::QuotaExceeded(_))));
+        let usage = storage.memory_budget().unwrap().usage();
+        assert!(usage.cdm_bytes <= usage.max_bytes.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_evicts_earliest_tca() {
+        let storage = budgeted(2, EvictionPolicy::OldestEpoch);
+        storage.store_cdm(demo_cdm("A", 5)).await.unwrap();
+        storage.store_cdm(demo_cdm("B", 1)).await.unwrap();
+        storage.store_cdm(demo_cdm("C", 3)).await.unwrap();
+
+        assert_eq!(storage.cdm_count().await.unwrap(), 2);
+        assert!(storage.get_cdm(&demo_cdm("B", 0).cdm_id).await.unwrap().is_none());
+        assert_eq!(storage.memory_budget().unwrap().usage().evicted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_pruned_under_memory_pressure() {
+        let limit = 3 * SeenMessages::entry_bytes("msg-0");
+        let budget = Arc::new(MemoryBudget::new(&crate::config::MemoryLimitsConfig {
+            max_bytes: Some(limit),
+            ..Default::default()
+        }));
+        let storage = MemoryStorage::new().with_memory_budget(budget.clone());
+
+        for i in 0..5 {
+            storage.mark_message_seen(&format!("msg-{}", i)).await.unwrap();
+        }
+        assert!(!storage.has_seen_message("msg-0").await.unwrap());
+        assert!(storage.has_seen_message("msg-4").await.unwrap());
+        assert_eq!(budget.usage().dedup_bytes, limit);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_keys() {
+        let storage = MemoryStorage::new();
+        let ttl = Duration::from_secs(60);
+        let response = IdempotentResponse {
+            status: 201,
+            body: serde_json::json!({ "cdm_id": "CDM-1" }),
+        };
+
+        assert_eq!(storage.claim_idempotency_key("k1", 7, ttl).await.unwrap(), IdempotencyClaim::Claimed);
+        assert_eq!(storage.claim_idempotency_key("k1", 7, ttl).await.unwrap(), IdempotencyClaim::InProgress);
+        storage.complete_idempotency_key("k1", Some(response.clone())).await.unwrap();
+        assert_eq!(
+            storage.claim_idempotency_key("k1", 7, ttl).await.unwrap(),
+            IdempotencyClaim::Completed(response)
+        );
+        assert_eq!(storage.claim_idempotency_key("k1", 8, ttl).await.unwrap(), IdempotencyClaim::Mismatch);
+
+        // A released claim can be taken again
+        storage.claim_idempotency_key("k2", 1, ttl).await.unwrap();
+        storage.complete_idempotency_key("k2", None).await.unwrap();
+        assert_eq!(storage.claim_idempotency_key("k2", 2, ttl).await.unwrap(), IdempotencyClaim::Claimed);
+
+        // Expired keys are forgotten and their memory released
+        let usage = storage.memory_budget().unwrap().usage().dedup_bytes;
+        storage.claim_idempotency_key("k3", 1, Duration::ZERO).await.unwrap();
+        assert_eq!(storage.claim_idempotency_key("k3", 2, ttl).await.unwrap(), IdempotencyClaim::Claimed);
+        assert_eq!(storage.memory_budget().unwrap().usage().dedup_bytes, usage + IdempotencyKeys::claim_bytes("k3"));
+    }
+
+    #[tokio::test]
+    async fn test_conjunction_index() {
+        let storage = MemoryStorage::new().with_conjunction_bucket(3600);
+        let mut first = demo_cdm("A", 1);
+        first.tca = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2030, 1, 1, 12, 5, 0).unwrap();
+        let mut second = first.clone();
+        second.cdm_id = demo_cdm("B", 1).cdm_id;
+        std::mem::swap(&mut second.object1, &mut second.object2);
+        second.tca += chrono::Duration::minutes(10);
+        storage.store_cdm(first.clone()).await.unwrap();
+        storage.store_cdm(second.clone()).await.unwrap();
+
+        let conjunctions = storage.list_conjunctions().await.unwrap();
+        assert_eq!(conjunctions.len(), 1);
+        assert_eq!(conjunctions[0].1.len(), 2);
+
+        // A re-issued CDM moves to its new conjunction
+        second.tca += chrono::Duration::hours(2);
+        storage.store_cdm(second).await.unwrap();
+        assert_eq!(storage.list_conjunctions().await.unwrap().len(), 2);
+
+        storage.withdraw_cdm(&first.cdm_id).await.unwrap();
+        let conjunctions = storage.list_conjunctions().await.unwrap();
+        assert_eq!(conjunctions.len(), 1);
+        assert_eq!(conjunctions[0].1[0].cdm_id, demo_cdm("B", 0).cdm_id);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_upsert_if_newer_racing_writers() {
+        let storage = Arc::new(MemoryStorage::new());
+        let base = generate_demo_cdm();
+        let writers: Vec<_> = (0..32)
+            .map(|i| {
+                let storage = storage.clone();
+                let mut cdm = base.clone();
+                cdm.creation_date += chrono::Duration::seconds(i);
+                cdm.miss_distance_m = i as f64;
+                tokio::spawn(async move { storage.upsert_cdm_if_newer(cdm).await.unwrap() })
+            })
+            .collect();
+        let mut revisions = Vec::new();
+        for writer in writers {
+            if let WriteOutcome::Written { revision, previous } = writer.await.unwrap() {
+                assert!(previous.is_none_or(|p| p < revision));
+                revisions.push(revision);
+            }
+        }
+
+        // The newest CDM wins whatever order the writers ran in
+        let stored = storage.get_cdm_versioned(&base.cdm_id).await.unwrap().unwrap();
+        assert_eq!(stored.record.miss_distance_m, 31.0);
+        assert_eq!(stored.revision, *revisions.iter().max().unwrap());
+        let written = revisions.len();
+        revisions.sort();
+        revisions.dedup();
+        assert_eq!(revisions.len(), written);
+        assert_eq!(storage.cdm_count().await.unwrap(), 1);
+
+        // Equal or older creation dates are skipped
+        let outcome = storage.upsert_cdm_if_newer(stored.record.clone()).await.unwrap();
+        assert_eq!(outcome, WriteOutcome::Skipped { current: Some(stored.revision) });
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_compare_and_swap_racing_writers() {
+        let storage = Arc::new(MemoryStorage::new());
+        let mut cdm = generate_demo_cdm();
+        cdm.miss_distance_m = 0.0;
+        let id = cdm.cdm_id.clone();
+        assert!(storage.compare_and_swap_cdm(cdm.clone(), None).await.unwrap().written());
+        assert!(!storage.compare_and_swap_cdm(cdm, None).await.unwrap().written());
+
+        // Read-modify-write increments; a lost update would leave the total short
+        let writers: Vec<_> = (0..16)
+            .map(|_| {
+                let storage = storage.clone();
+                let id = id.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let current = storage.get_cdm_versioned(&id).await.unwrap().unwrap();
+                        let mut next = current.record;
+                        next.miss_distance_m += 1.0;
+                        if storage.compare_and_swap_cdm(next, Some(current.revision)).await.unwrap().written() {
+                            break;
+                        }
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert_eq!(storage.get_cdm(&id).await.unwrap().unwrap().miss_distance_m, 16.0);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawn_history() {
+        let storage = budgeted(2, EvictionPolicy::Reject).with_cdm_history(2);
+        for id in ["A", "B", "C"] {
+            storage.store_cdm(demo_cdm(id, 1)).await.unwrap();
+            storage.withdraw_cdm(&demo_cdm(id, 0).cdm_id).await.unwrap();
+        }
+        // Only the two latest withdrawals are kept
+        let history = storage.list_withdrawn_cdms().await.unwrap();
+        let ids: Vec<&str> = history.iter().map(|w| w.record.cdm_id.trim()).collect();
+        assert_eq!(ids, ["B", "C"]);
+
+        // History gives way to live CDMs, even under the reject policy
+        storage.store_cdm(demo_cdm("D", 1)).await.unwrap();
+        storage.store_cdm(demo_cdm("E", 1)).await.unwrap();
+        assert!(storage.list_withdrawn_cdms().await.unwrap().is_empty());
+        assert_eq!(storage.memory_budget().unwrap().usage().rejected, 0);
+
+        let storage = MemoryStorage::new().with_cdm_history(0);
+        storage.store_cdm(demo_cdm("A", 1)).await.unwrap();
+        storage.withdraw_cdm(&demo_cdm("A", 0).cdm_id).await.unwrap();
+        assert!(storage.list_withdrawn_cdms().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_leases() {
+        let storage = MemoryStorage::new();
+        let ttl = Duration::from_millis(100);
+        let taken = storage.acquire_lease("leader", "a", ttl).await.unwrap();
+        assert_eq!(taken.holder, "a");
+        assert_eq!(storage.acquire_lease("leader", "b", ttl).await.unwrap().holder, "a");
+        let renewed = storage.acquire_lease("leader", "a", ttl).await.unwrap();
+        assert_eq!(renewed.acquired_at, taken.acquired_at);
+        assert!(renewed.expires_at >= taken.expires_at);
+
+        // Unrenewed, the lease passes on
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(storage.acquire_lease("leader", "b", ttl).await.unwrap().holder, "b");
+        storage.release_lease("leader", "a").await.unwrap();
+        assert_eq!(storage.acquire_lease("leader", "a", ttl).await.unwrap().holder, "b");
+        storage.release_lease("leader", "b").await.unwrap();
+        assert_eq!(storage.acquire_lease("leader", "a", ttl).await.unwrap().holder, "a");
+    }
 }
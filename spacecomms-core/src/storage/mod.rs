@@ -1,13 +1,107 @@
 //! Storage module
 
+mod archive;
+mod budget;
+mod catalog;
+mod lease;
+mod encryption;
+mod history;
 mod memory;
+mod stats;
+mod tokens;
 
+pub use archive::*;
+pub use budget::{
+    entry_footprint, Footprint, MemoryBudget, MemoryCategory, MemoryUsage, QueueCharge, ENTRY_OVERHEAD,
+};
+pub use catalog::{CapacityEvent, CapacityHook, ObjectCapacity};
+pub(crate) use catalog::ObjectCatalog;
 pub use memory::*;
+pub use stats::{StatMetric, StatSample, MAX_STAT_SAMPLES};
+pub(crate) use stats::StatSeries;
+pub(crate) use encryption::decode_key;
+pub use encryption::RecordCipher;
+pub use history::{ObjectStateChange, StateDiff};
+pub use lease::Lease;
+pub use tokens::ApiTokenRecord;
 
-use crate::cdm::{CdmRecord, ObjectRecord};
+use crate::cdm::{CdmRecord, ConjunctionKey, ObjectRecord};
+use crate::config::{Config, ObjectLimitsConfig, PeerPolicies};
+use crate::protocol::{DeliveryReceiptPayload, ProvenanceHop};
 use crate::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Receiving nodes whose receipts are kept on one CDM; receipts from further
+/// nodes are refused
+pub const MAX_RECEIPTS_PER_CDM: usize = 64;
+
+/// A stored record and its revision
+///
+/// Every write gets a new revision, higher than any handed out before by the
+/// same backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Versioned<T> {
+    pub revision: u64,
+    pub record: T,
+}
+
+/// A node blocked through the API: its messages are refused whichever
+/// way they arrive, and no session is kept with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BlockedNode {
+    pub node_id: String,
+    pub since: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Result of a conditional write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// Stored under `revision`; `previous` is the revision it replaced
+    Written { revision: u64, previous: Option<u64> },
+    /// Left unchanged; `current` is the stored revision, if any
+    Skipped { current: Option<u64> },
+}
+
+impl WriteOutcome {
+    pub fn written(&self) -> bool {
+        matches!(self, WriteOutcome::Written { .. })
+    }
+}
+
+/// A withdrawn CDM kept for history
+#[derive(Debug, Clone)]
+pub struct WithdrawnCdm {
+    pub record: CdmRecord,
+    pub withdrawn_at: DateTime<Utc>,
+}
+
+/// Response recorded for an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotentResponse {
+    /// HTTP status
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+/// What a request found when claiming its idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    /// New (or expired) key, now held by the caller until completed
+    Claimed,
+    /// Another request holding the key has not completed yet
+    InProgress,
+    /// Completed earlier with this response
+    Completed(IdempotentResponse),
+    /// Used earlier with a different request body
+    Mismatch,
+}
 
 /// Storage backend trait
 #[async_trait]
@@ -15,9 +109,59 @@ pub trait Storage: Send + Sync {
     // CDM operations
     async fn store_cdm(&self, cdm: CdmRecord) -> Result<()>;
     async fn get_cdm(&self, id: &str) -> Result<Option<CdmRecord>>;
+
+    /// A CDM with its current revision
+    async fn get_cdm_versioned(&self, id: &str) -> Result<Option<Versioned<CdmRecord>>>;
+
+    /// Store a CDM unless one with the same ID and the same or a later
+    /// `creation_date` is already stored; checked and written atomically
+    async fn upsert_cdm_if_newer(&self, cdm: CdmRecord) -> Result<WriteOutcome>;
+
+    /// Store a CDM only if its stored revision is still `expected` (`None`:
+    /// only if absent); checked and written atomically
+    async fn compare_and_swap_cdm(&self, cdm: CdmRecord, expected: Option<u64>) -> Result<WriteOutcome>;
+
     async fn list_cdms(&self) -> Result<Vec<CdmRecord>>;
     async fn withdraw_cdm(&self, id: &str) -> Result<()>;
     async fn cdm_count(&self) -> Result<usize>;
+
+    /// Withdrawn CDMs still kept for history, oldest withdrawal first;
+    /// backends that keep none return nothing
+    async fn list_withdrawn_cdms(&self) -> Result<Vec<WithdrawnCdm>> {
+        Ok(Vec::new())
+    }
+
+    /// Forget withdrawn CDMs that have been archived elsewhere
+    async fn discard_withdrawn_cdms(&self, _archived: &[WithdrawnCdm]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Keep the provenance chain a stored CDM arrived with; storing the CDM
+    /// again forgets it
+    async fn store_cdm_provenance(&self, _cdm_id: &str, _chain: Vec<ProvenanceHop>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Provenance chain of a stored CDM; backends that keep none return
+    /// nothing
+    async fn get_cdm_provenance(&self, _cdm_id: &str) -> Result<Option<Vec<ProvenanceHop>>> {
+        Ok(None)
+    }
+
+    /// Record a delivery receipt on a stored CDM, replacing any from the
+    /// same receiving node; one from a node beyond [`MAX_RECEIPTS_PER_CDM`]
+    /// is refused. Backends that keep none drop it
+    async fn store_delivery_receipt(&self, _cdm_id: &str, _receipt: DeliveryReceiptPayload) -> Result<()> {
+        Ok(())
+    }
+
+    /// Delivery receipts recorded on a stored CDM, oldest first
+    async fn get_delivery_receipts(&self, _cdm_id: &str) -> Result<Vec<DeliveryReceiptPayload>> {
+        Ok(Vec::new())
+    }
+
+    /// Stored CDMs grouped by conjunction identity
+    async fn list_conjunctions(&self) -> Result<Vec<(ConjunctionKey, Vec<CdmRecord>)>>;
     
     // Object operations
     async fn store_object(&self, obj: ObjectRecord) -> Result<()>;
@@ -25,15 +169,129 @@ pub trait Storage: Send + Sync {
     async fn list_objects(&self) -> Result<Vec<ObjectRecord>>;
     async fn withdraw_object(&self, id: &str) -> Result<()>;
     async fn object_count(&self) -> Result<usize>;
+
+    /// Store an object's state after a completed maneuver, linking its
+    /// history entry to the maneuver; backends that keep no history just
+    /// store it
+    async fn store_maneuvered_object(&self, obj: ObjectRecord, _maneuver_id: &str) -> Result<()> {
+        self.store_object(obj).await
+    }
+
+    /// States a tracked object has been stored with, oldest version first;
+    /// backends that keep none return nothing
+    async fn object_state_history(&self, _id: &str) -> Result<Vec<ObjectStateChange>> {
+        Ok(Vec::new())
+    }
+
+    /// Replace a tracked object's history with `history`, oldest version
+    /// first, as read from a snapshot; backends that keep none drop it
+    async fn restore_object_history(&self, _id: &str, _history: Vec<ObjectStateChange>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Catalog usage; backends without limits report the object count only
+    async fn object_capacity(&self) -> Result<ObjectCapacity> {
+        Ok(ObjectCapacity {
+            tracked: self.object_count().await?,
+            ..Default::default()
+        })
+    }
+
+    /// Replace the object catalog limits; objects already stored are kept
+    async fn set_object_limits(&self, _limits: ObjectLimitsConfig) -> Result<()> {
+        Ok(())
+    }
+
+    /// Memory budget the backend charges records against, if it keeps any
+    /// in memory
+    fn memory_budget(&self) -> Option<Arc<MemoryBudget>> {
+        None
+    }
     
     // Message deduplication
     async fn has_seen_message(&self, message_id: &str) -> Result<bool>;
     async fn mark_message_seen(&self, message_id: &str) -> Result<()>;
+
+    // Idempotent ingestion
+    /// Claim an idempotency key for a request body with this fingerprint,
+    /// remembering it for `ttl`; checked and claimed atomically
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: u64, ttl: Duration) -> Result<IdempotencyClaim>;
+
+    /// Record the response for a claimed key, or release the claim with
+    /// `None` so a retry runs again
+    async fn complete_idempotency_key(&self, key: &str, response: Option<IdempotentResponse>) -> Result<()>;
+
+    // Statistics history
+    /// Record samples taken at `at`, forgetting those older than
+    /// `keep_since`; backends that keep no history drop them
+    async fn record_stats(
+        &self,
+        _at: DateTime<Utc>,
+        _samples: &[(StatMetric, f64)],
+        _keep_since: DateTime<Utc>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Samples of a metric taken at or after `since`, oldest first
+    async fn stats_history(&self, _metric: StatMetric, _since: DateTime<Utc>) -> Result<Vec<StatSample>> {
+        Ok(Vec::new())
+    }
+
+    // Peer policies set through the API
+    /// Keep a peer's policies, replacing any kept before
+    async fn store_peer_policies(&self, peer_id: &str, policies: &PeerPolicies) -> Result<()>;
+    /// Kept policies, by peer
+    async fn list_peer_policies(&self) -> Result<Vec<(String, PeerPolicies)>>;
+    async fn remove_peer_policies(&self, peer_id: &str) -> Result<()>;
+
+    // Nodes blocked through the API
+    /// Keep a blocked node, replacing any kept under the same ID
+    async fn store_blocked_node(&self, node: &BlockedNode) -> Result<()>;
+    async fn list_blocked_nodes(&self) -> Result<Vec<BlockedNode>>;
+    async fn remove_blocked_node(&self, node_id: &str) -> Result<()>;
+
+    // API tokens issued at runtime
+    /// Keep a token, replacing any stored under the same ID
+    async fn store_api_token(&self, token: ApiTokenRecord) -> Result<()>;
+    async fn get_api_token(&self, id: &str) -> Result<Option<ApiTokenRecord>>;
+    /// Every stored token, revoked and expired ones included
+    async fn list_api_tokens(&self) -> Result<Vec<ApiTokenRecord>>;
+
+    // Leases coordinating instances that share the backend
+    /// Take lease `name` for `holder` until `ttl` from now, or renew it if
+    /// `holder` has it. Returns the lease as it stands, whoever holds it.
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease>;
+    /// Give up lease `name` if `holder` has it
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<()>;
+}
+
+/// Object catalog limits for a configuration
+pub(crate) fn object_limits(config: &Config) -> ObjectLimitsConfig {
+    // The local node's own objects are always trusted
+    let mut limits = config.storage.object_limits.clone();
+    limits.trusted_sources.push(config.node.id.clone());
+    limits
 }
 
-/// Create storage from configuration
-pub fn create_storage(storage_type: &str) -> Arc<dyn Storage> {
-    match storage_type {
-        "memory" | _ => Arc::new(MemoryStorage::new()),
+/// Create storage from configuration, passing object catalog capacity
+/// events to `capacity_hook` when one is given
+pub fn create_storage(config: &Config, capacity_hook: Option<CapacityHook>) -> Arc<dyn Storage> {
+    let limits = object_limits(config);
+
+    let budget = Arc::new(MemoryBudget::new(&config.storage.memory));
+
+    match config.storage.storage_type.as_str() {
+        "memory" => {}
+        other => tracing::warn!("Unsupported storage type '{}', using in-memory storage", other),
+    }
+    let mut storage = MemoryStorage::with_object_limits(limits)
+        .with_memory_budget(budget)
+        .with_conjunction_bucket(config.storage.conjunction_bucket_seconds)
+        .with_cdm_history(config.storage.cdm_history_limit)
+        .with_object_history(config.storage.object_history_limit);
+    if let Some(hook) = capacity_hook {
+        storage = storage.with_capacity_hook(hook);
     }
+    Arc::new(storage)
 }
@@ -0,0 +1,88 @@
+//! Time series of node statistics
+//!
+//! The node samples a few headline numbers every `stats.sample_interval_seconds`
+//! and keeps them for `stats.retention_hours`, so dashboards can plot trends
+//! from `GET /stats/history` without an external metrics system.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+/// Samples kept per metric whatever the retention, to bound memory when
+/// the interval is short
+pub const MAX_STAT_SAMPLES: usize = 100_000;
+
+/// A sampled statistic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatMetric {
+    /// CDMs announced per minute, ingested or received from peers
+    CdmIngestRate,
+    /// CDMs stored
+    CdmsActive,
+    /// Peers with a session up
+    PeersConnected,
+    /// Peers known, connected or not
+    PeersKnown,
+    /// Envelopes waiting to be forwarded, across all peers
+    QueueDepth,
+    /// Envelopes received per minute
+    MessagesReceivedRate,
+    /// Envelopes sent per minute
+    MessagesSentRate,
+    /// Errors per minute
+    ErrorRate,
+    /// Dead letters held
+    DeadLetters,
+}
+
+impl StatMetric {
+    pub const ALL: [StatMetric; 9] = [
+        StatMetric::CdmIngestRate,
+        StatMetric::CdmsActive,
+        StatMetric::PeersConnected,
+        StatMetric::PeersKnown,
+        StatMetric::QueueDepth,
+        StatMetric::MessagesReceivedRate,
+        StatMetric::MessagesSentRate,
+        StatMetric::ErrorRate,
+        StatMetric::DeadLetters,
+    ];
+}
+
+/// One sample of a metric
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StatSample {
+    pub at: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// Samples by metric, oldest first
+#[derive(Default)]
+pub(crate) struct StatSeries {
+    series: HashMap<StatMetric, VecDeque<StatSample>>,
+}
+
+impl StatSeries {
+    pub(crate) fn record(&mut self, at: DateTime<Utc>, samples: &[(StatMetric, f64)], keep_since: DateTime<Utc>) {
+        for &(metric, value) in samples {
+            self.series.entry(metric).or_default().push_back(StatSample { at, value });
+        }
+        for series in self.series.values_mut() {
+            while series
+                .front()
+                .is_some_and(|sample| sample.at < keep_since || series.len() > MAX_STAT_SAMPLES)
+            {
+                series.pop_front();
+            }
+        }
+    }
+
+    pub(crate) fn since(&self, metric: StatMetric, since: DateTime<Utc>) -> Vec<StatSample> {
+        self.series
+            .get(&metric)
+            .map(|series| series.iter().filter(|sample| sample.at >= since).copied().collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,133 @@
+//! API tokens issued at runtime
+//!
+//! Tokens from `api.auth.tokens` live in the configuration. Tokens issued
+//! through `POST /auth/tokens` are kept by the storage backend instead, with
+//! only a SHA-256 hash of their secret. Revoked tokens stay stored so the
+//! listing shows who held what; a rotated token accepts its previous secret
+//! until the rotation's grace period ends.
+
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+
+/// A token issued through the API
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiTokenRecord {
+    pub id: String,
+    /// Hex SHA-256 of the secret
+    pub secret_hash: String,
+    pub permissions: Vec<String>,
+    pub roles: Vec<String>,
+    pub organization: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Token that issued this one
+    pub issued_by: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    /// Secret replaced by the last rotation, accepted until
+    /// `previous_secret_expires_at`
+    pub previous_secret_hash: Option<String>,
+    pub previous_secret_expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiTokenRecord {
+    /// Hash a secret the way it is stored
+    pub fn hash_secret(secret: &str) -> String {
+        digest(&SHA256, secret.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// `active`, `expired` or `revoked` at `now`
+    pub fn status(&self, now: DateTime<Utc>) -> &'static str {
+        if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.expires_at.is_some_and(|at| at <= now) {
+            "expired"
+        } else {
+            "active"
+        }
+    }
+
+    /// Whether `hash` is the current secret, or the previous one within
+    /// its grace period
+    pub fn matches(&self, hash: &str, now: DateTime<Utc>) -> bool {
+        let previous = self
+            .previous_secret_hash
+            .as_deref()
+            .filter(|_| self.previous_secret_expires_at.is_some_and(|at| at > now));
+        constant_time_eq(self.secret_hash.as_bytes(), hash.as_bytes())
+            || previous.is_some_and(|previous| constant_time_eq(previous.as_bytes(), hash.as_bytes()))
+    }
+}
+
+/// Compare hashes without revealing where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn token(secret: &str) -> ApiTokenRecord {
+        ApiTokenRecord {
+            id: "ingest".to_string(),
+            secret_hash: ApiTokenRecord::hash_secret(secret),
+            permissions: vec!["read".to_string()],
+            roles: Vec::new(),
+            organization: None,
+            created_at: Utc::now(),
+            issued_by: None,
+            expires_at: None,
+            revoked_at: None,
+            rotated_at: None,
+            previous_secret_hash: None,
+            previous_secret_expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_previous_secret_grace_period() {
+        let now = Utc::now();
+        let mut rotated = token("new-secret");
+        rotated.rotated_at = Some(now);
+        rotated.previous_secret_hash = Some(ApiTokenRecord::hash_secret("old-secret"));
+        rotated.previous_secret_expires_at = Some(now + Duration::seconds(300));
+        let old = ApiTokenRecord::hash_secret("old-secret");
+        let new = ApiTokenRecord::hash_secret("new-secret");
+
+        // Both secrets work inside the grace period, only the new one after
+        assert!(rotated.matches(&new, now));
+        assert!(rotated.matches(&old, now + Duration::seconds(299)));
+        assert!(!rotated.matches(&old, now + Duration::seconds(300)));
+        assert!(rotated.matches(&new, now + Duration::seconds(300)));
+        assert!(!rotated.matches(&ApiTokenRecord::hash_secret("other"), now));
+
+        // A rotation without grace keeps no previous secret to match
+        let mut immediate = rotated.clone();
+        (immediate.previous_secret_hash, immediate.previous_secret_expires_at) = (None, None);
+        assert!(!immediate.matches(&old, now));
+        assert!(immediate.matches(&new, now));
+        // Nor does a previous hash whose expiry is missing
+        immediate.previous_secret_hash = Some(old.clone());
+        assert!(!immediate.matches(&old, now));
+    }
+
+    #[test]
+    fn test_token_status() {
+        let now = Utc::now();
+        let mut record = token("secret");
+        assert_eq!(record.status(now), "active");
+        record.expires_at = Some(now + Duration::seconds(60));
+        assert_eq!(record.status(now), "active");
+        assert_eq!(record.status(now + Duration::seconds(60)), "expired");
+        // Revocation wins over expiry
+        record.revoked_at = Some(now);
+        assert_eq!(record.status(now), "revoked");
+        assert_eq!(record.status(now + Duration::seconds(60)), "revoked");
+    }
+}
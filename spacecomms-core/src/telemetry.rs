@@ -0,0 +1,105 @@
+//! OpenTelemetry distributed tracing
+//!
+//! With `telemetry` configured, the node exports its tracing spans over
+//! OTLP/gRPC: one per API request, storage call, received envelope and
+//! peer forward. A forward stamps its W3C `traceparent` into the envelope,
+//! and the receiving node continues the trace from it, so a CDM's path
+//! through the mesh shows up as one distributed trace.
+
+use crate::config::TelemetryConfig;
+use crate::{Error, Result};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::collections::HashMap;
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// W3C trace context header, as carried in [`Envelope::traceparent`](crate::protocol::Envelope)
+const TRACEPARENT: &str = "traceparent";
+
+/// Layer exporting spans to the configured collector, or `None` when
+/// telemetry is off. Must be called from within the Tokio runtime.
+pub fn layer<S>(config: Option<&TelemetryConfig>, node_id: &str) -> Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+{
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()
+        .map_err(|e| Error::Config(format!("telemetry: {}", e)))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::new([
+            KeyValue::new("service.name", config.service_name.clone()),
+            KeyValue::new("service.instance.id", node_id.to_string()),
+        ]))
+        .build();
+    let tracer = provider.tracer("spacecomms");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer).boxed()))
+}
+
+/// Flush spans still buffered for export
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The span's W3C `traceparent`, if it is being exported
+pub fn traceparent(span: &Span) -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Make `span` continue the trace a peer sent; call before entering it
+pub fn continue_trace(span: &Span, traceparent: Option<&str>) {
+    let Some(traceparent) = traceparent else {
+        return;
+    };
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    span.set_parent(TraceContextPropagator::new().extract(&carrier));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_trace_context_round_trip() {
+        // Without an OpenTelemetry layer nothing is stamped
+        assert_eq!(traceparent(&info_span!("untraced")), None);
+
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let forward = info_span!("forward");
+            let sent = traceparent(&forward).unwrap();
+            assert!(sent.starts_with("00-") && sent.ends_with("-01"));
+
+            // The next hop's span joins the sender's trace as its child
+            let receive = info_span!("receive");
+            continue_trace(&receive, Some(&sent));
+            let received = traceparent(&receive).unwrap();
+            assert_eq!(received[3..35], sent[3..35]);
+            assert_ne!(received[36..52], sent[36..52]);
+
+            let fresh = info_span!("fresh");
+            assert_ne!(traceparent(&fresh).unwrap()[3..35], sent[3..35]);
+        });
+    }
+}
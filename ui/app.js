@@ -9,6 +9,9 @@ const CONFIG = {
   constellationHubUrl: "http://localhost:9001", // Constellation Hub Mock
   refreshInterval: 5000, // 5 seconds
   maxRetries: 3,
+  demo: false, // Set by ?demo or ?hub; shows the banner and hub alerts
+  eventsTimeout: 25, // Seconds each event poll waits for news
+  maxEvents: 100, // Events kept in the feed
 };
 
 // State
@@ -19,6 +22,8 @@ let state = {
   cdms: [],
   metrics: null,
   alerts: [],
+  conjunctions: [],
+  token: sessionStorage.getItem("spacecomms-token") || "",
   retryCount: 0,
 };
 
@@ -30,11 +35,19 @@ const elements = {
   healthUptime: document.getElementById("health-uptime"),
   healthPeers: document.getElementById("health-peers"),
   healthCdms: document.getElementById("health-cdms"),
+  tokenForm: document.getElementById("token-form"),
+  tokenInput: document.getElementById("api-token"),
+  conjunctionsCount: document.getElementById("conjunctions-count"),
+  conjunctionsTable: document.getElementById("conjunctions-table"),
+  eventsList: document.getElementById("events-list"),
   topologySvg: document.getElementById("topology-svg"),
   peersCount: document.getElementById("peers-count"),
   peersTable: document.getElementById("peers-table"),
   cdmsCount: document.getElementById("cdms-count"),
   cdmsTable: document.getElementById("cdms-table"),
+  cdmDetail: document.getElementById("cdm-detail"),
+  cdmDetailId: document.getElementById("cdm-detail-id"),
+  pcTable: document.getElementById("pc-table"),
   metricAnnounced: document.getElementById("metric-announced"),
   metricWithdrawn: document.getElementById("metric-withdrawn"),
   metricSent: document.getElementById("metric-sent"),
@@ -45,27 +58,60 @@ const elements = {
 };
 
 // API Functions
+
+// Node requests carry the API token, if one was entered
+async function nodeFetch(path) {
+  const headers = state.token ? { Authorization: `Bearer ${state.token}` } : {};
+  const response = await fetch(`${CONFIG.nodeUrl}${path}`, { headers });
+  if (response.status === 401 || response.status === 403) {
+    elements.tokenForm.hidden = false;
+  }
+  return response;
+}
+
 async function fetchHealth() {
-  const response = await fetch(`${CONFIG.nodeUrl}/health`);
+  const response = await nodeFetch("/health");
   if (!response.ok) throw new Error("Health check failed");
   return response.json();
 }
 
 async function fetchPeers() {
-  const response = await fetch(`${CONFIG.nodeUrl}/peers`);
+  const response = await nodeFetch("/peers");
   if (!response.ok) throw new Error("Failed to fetch peers");
   return response.json();
 }
 
 async function fetchCdms() {
-  const response = await fetch(`${CONFIG.nodeUrl}/cdms`);
+  const response = await nodeFetch("/cdms");
   if (!response.ok) throw new Error("Failed to fetch CDMs");
   return response.json();
 }
 
+async function fetchConjunctions() {
+  const response = await nodeFetch("/conjunctions");
+  if (!response.ok) throw new Error("Failed to fetch conjunctions");
+  return response.json();
+}
+
+async function fetchEvents(since) {
+  const query = since === null ? "" : `&since=${since}`;
+  const response = await nodeFetch(
+    `/events/cdms?timeout_seconds=${CONFIG.eventsTimeout}${query}`,
+  );
+  if (!response.ok) throw new Error("Failed to fetch events");
+  return response.json();
+}
+
+async function fetchPcComparison(cdmId) {
+  const response = await nodeFetch(`/cdms/${encodeURIComponent(cdmId)}/pc`);
+  const body = await response.json();
+  if (!response.ok) throw new Error(body.message || "Failed to compute Pc");
+  return body;
+}
+
 async function fetchMetrics() {
   try {
-    const response = await fetch(`${CONFIG.nodeUrl}/metrics`);
+    const response = await nodeFetch("/metrics");
     if (!response.ok) return null;
     return response.json();
   } catch {
@@ -74,6 +120,7 @@ async function fetchMetrics() {
 }
 
 async function fetchAlerts() {
+  if (!CONFIG.demo) return { alerts: [] };
   try {
     const response = await fetch(`${CONFIG.constellationHubUrl}/alerts`);
     if (!response.ok) return { alerts: [] };
@@ -148,10 +195,10 @@ function updatePeers(peersData) {
     .map(
       (peer) => `
         <tr>
-            <td><code>${peer.id ? peer.id.slice(0, 12) + "..." : "Unknown"}</code></td>
-            <td>${peer.address || "--"}</td>
-            <td><span class="status-badge ${getStatusClass(peer.status)}">${peer.status || "Unknown"}</span></td>
-            <td>${peer.messages_received || 0} / ${peer.messages_sent || 0}</td>
+            <td><code>${peer.id ? escapeHtml(peer.id.slice(0, 12)) + "..." : "Unknown"}</code></td>
+            <td>${escapeHtml(peer.address || "--")}</td>
+            <td><span class="status-badge ${getStatusClass(peer.status)}">${escapeHtml(peer.status || "Unknown")}</span></td>
+            <td>${Number(peer.messages_received) || 0} / ${Number(peer.messages_sent) || 0}</td>
         </tr>
     `,
     )
@@ -176,10 +223,10 @@ function updateCdms(cdmsData) {
     .map((cdm) => {
       const riskClass = getRiskClass(cdm.collision_probability);
       return `
-            <tr>
-                <td><code>${cdm.cdm_id ? cdm.cdm_id.slice(0, 16) : "--"}</code></td>
-                <td>${cdm.object1_id || "--"}</td>
-                <td>${cdm.object2_id || "--"}</td>
+            <tr class="clickable" data-cdm-id="${escapeHtml(cdm.cdm_id)}">
+                <td><code>${escapeHtml(cdm.cdm_id ? cdm.cdm_id.slice(0, 16) : "--")}</code></td>
+                <td>${escapeHtml(cdm.object1_id || "--")}</td>
+                <td>${escapeHtml(cdm.object2_id || "--")}</td>
                 <td>${formatDate(cdm.tca)}</td>
                 <td>${cdm.miss_distance_m ? (cdm.miss_distance_m / 1000).toFixed(3) + " km" : "--"}</td>
                 <td class="${riskClass}">${cdm.collision_probability ? formatProbability(cdm.collision_probability) : "--"}</td>
@@ -189,6 +236,124 @@ function updateCdms(cdmsData) {
     .join("");
 }
 
+// Fused figure where there is one, otherwise the best provider's
+function assessment(conjunction) {
+  return conjunction.fused || conjunction.best;
+}
+
+function updateConjunctions(conjunctionsData) {
+  const now = Date.now();
+  const conjunctions = (conjunctionsData.conjunctions || [])
+    .filter((c) => new Date(c.best.tca).getTime() >= now)
+    .sort(
+      (a, b) =>
+        assessment(b).collision_probability -
+        assessment(a).collision_probability,
+    );
+  state.conjunctions = conjunctions;
+
+  elements.conjunctionsCount.textContent = conjunctions.length;
+
+  const tbody = elements.conjunctionsTable.querySelector("tbody");
+
+  if (conjunctions.length === 0) {
+    tbody.innerHTML =
+      '<tr class="empty-row"><td colspan="6">No active conjunctions</td></tr>';
+    return;
+  }
+
+  tbody.innerHTML = conjunctions
+    .map((c) => {
+      const a = assessment(c);
+      const category = escapeHtml(a.conjunction_category);
+      return `
+            <tr class="clickable" data-cdm-id="${escapeHtml(c.best.cdm_id)}">
+                <td>${escapeHtml(c.object1_id)} / ${escapeHtml(c.object2_id)}</td>
+                <td>${formatDate(c.best.tca)}</td>
+                <td class="${getRiskClass(a.collision_probability)}">${formatProbability(a.collision_probability)}</td>
+                <td>${(a.miss_distance_m / 1000).toFixed(3)} km</td>
+                <td>${category ? `<span class="severity-badge ${category.toLowerCase()}">${category}</span>` : "--"}</td>
+                <td>${c.providers.map(escapeHtml).join(", ")}</td>
+            </tr>
+        `;
+    })
+    .join("");
+}
+
+function describeEvent(event) {
+  const cdm = event.cdm;
+  const objects = cdm ? ` ${cdm.object1.object_id} / ${cdm.object2.object_id}` : "";
+  const pc =
+    event.collision_probability != null
+      ? ` Pc ${formatProbability(event.collision_probability)}`
+      : "";
+  const reason = event.reason ? ` (${event.reason})` : "";
+  return `${formatDate(event.at)} ${event.kind.toUpperCase()} ${event.cdm_id}${objects}${pc}${reason}`;
+}
+
+function addEvents(events) {
+  const list = elements.eventsList;
+  list.querySelector(".empty")?.remove();
+  events.forEach((event) => {
+    const item = document.createElement("li");
+    item.className = event.kind;
+    item.textContent = describeEvent(event);
+    list.prepend(item);
+  });
+  while (list.children.length > CONFIG.maxEvents) {
+    list.lastElementChild.remove();
+  }
+}
+
+// Long-poll the CDM change feed so events show as they happen
+async function followEvents() {
+  let since = null;
+  for (;;) {
+    try {
+      const page = await fetchEvents(since);
+      since = page.next_seq;
+      if (page.events.length > 0) {
+        addEvents(page.events);
+        fetchConjunctions().then(updateConjunctions).catch(() => {});
+      }
+    } catch (error) {
+      console.error("Event feed failed:", error);
+      await new Promise((resolve) =>
+        setTimeout(resolve, CONFIG.refreshInterval),
+      );
+    }
+  }
+}
+
+async function showCdmDetail(cdmId) {
+  elements.cdmDetail.hidden = false;
+  elements.cdmDetailId.textContent = cdmId;
+  const tbody = elements.pcTable.querySelector("tbody");
+  tbody.innerHTML = '<tr class="empty-row"><td colspan="3">Computing…</td></tr>';
+
+  try {
+    const comparison = await fetchPcComparison(cdmId);
+    tbody.innerHTML = comparison.results
+      .map((result) => {
+        const ratio = comparison.reported_pc
+          ? (result.pc / comparison.reported_pc).toFixed(2) + "×"
+          : "--";
+        const marker = result.method === comparison.default_method ? " ★" : "";
+        return `
+            <tr>
+                <td>${escapeHtml(result.method)}${marker}</td>
+                <td class="${getRiskClass(result.pc)}">${formatProbability(result.pc)}</td>
+                <td>${ratio}</td>
+            </tr>
+        `;
+      })
+      .join("");
+  } catch (error) {
+    tbody.innerHTML = '<tr class="empty-row"><td colspan="3"></td></tr>';
+    tbody.querySelector("td").textContent = error.message;
+  }
+}
+
 function updateAlerts(alertsData) {
   const alerts = alertsData.alerts || [];
   state.alerts = alerts;
@@ -215,12 +380,12 @@ function updateAlerts(alertsData) {
 
   tbody.innerHTML = alerts
     .map((alert) => {
-      const severityClass = (alert.severity || "low").toLowerCase();
+      const severityClass = escapeHtml(alert.severity || "low").toLowerCase();
       return `
             <tr>
-                <td><span class="severity-badge ${severityClass}">${alert.severity || "N/A"}</span></td>
-                <td>${alert.satellite_name || "--"}</td>
-                <td><code>${alert.cdm_id ? alert.cdm_id.slice(0, 16) : "--"}</code></td>
+                <td><span class="severity-badge ${severityClass}">${escapeHtml(alert.severity || "N/A")}</span></td>
+                <td>${escapeHtml(alert.satellite_name || "--")}</td>
+                <td><code>${escapeHtml(alert.cdm_id ? alert.cdm_id.slice(0, 16) : "--")}</code></td>
                 <td>${formatDate(alert.tca)}</td>
                 <td class="${getRiskClass(alert.collision_probability)}">${alert.collision_probability ? formatProbability(alert.collision_probability) : "--"}</td>
                 <td><span class="status-badge ${alert.acknowledged ? "connected" : "pending"}">${alert.acknowledged ? "Acked" : "New"}</span></td>
@@ -335,6 +500,12 @@ function formatProbability(prob) {
   return prob.toExponential(2);
 }
 
+// Safe in element content and in quoted attribute values
+function escapeHtml(text) {
+  const entities = { "&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;", "'": "&#39;" };
+  return String(text ?? "").replace(/[&<>"']/g, (c) => entities[c]);
+}
+
 function getStatusClass(status) {
   if (!status) return "";
   const s = status.toLowerCase();
@@ -350,22 +521,24 @@ function getRiskClass(probability) {
   return "risk-low";
 }
 
-// Modal toggle function (global for onclick)
-window.toggleDemoInfo = function () {
+// Modal toggle for the demo info links
+function toggleDemoInfo(event) {
+  event.preventDefault();
   const modal = document.getElementById("demo-modal");
   if (modal) {
     modal.style.display = modal.style.display === "none" ? "flex" : "none";
   }
-};
+}
 
 // Main Update Loop
 async function refresh() {
   try {
     // Fetch all data in parallel (including alerts from Constellation Hub)
-    const [health, peers, cdms, metrics, alerts] = await Promise.all([
+    const [health, peers, cdms, conjunctions, metrics, alerts] = await Promise.all([
       fetchHealth().catch(() => null),
       fetchPeers().catch(() => ({ peers: [] })),
       fetchCdms().catch(() => ({ cdms: [] })),
+      fetchConjunctions().catch(() => ({ conjunctions: [] })),
       fetchMetrics(),
       fetchAlerts(),
     ]);
@@ -379,6 +552,7 @@ async function refresh() {
 
     updatePeers(peers);
     updateCdms(cdms);
+    updateConjunctions(conjunctions);
     updateMetrics(metrics);
     updateAlerts(alerts);
     updateTopology();
@@ -392,10 +566,16 @@ async function refresh() {
 // Initialize
 function init() {
   console.log("SpaceComms Dashboard initializing...");
-  console.log("🔬 Demo Mode: Connecting to mock services");
 
   // Check for custom node URL in query params
   const urlParams = new URLSearchParams(window.location.search);
+  CONFIG.demo = urlParams.has("demo") || urlParams.has("hub");
+  if (CONFIG.demo) {
+    console.log("🔬 Demo Mode: Connecting to mock services");
+    document.getElementById("demo-badge").hidden = false;
+    document.getElementById("demo-banner").hidden = false;
+  }
+
   const customUrl = urlParams.get("node");
   if (customUrl) {
     CONFIG.nodeUrl = customUrl;
@@ -408,8 +588,32 @@ function init() {
     console.log("Using custom hub URL:", CONFIG.constellationHubUrl);
   }
 
+  // CDM and conjunction rows open the detail view
+  [elements.cdmsTable, elements.conjunctionsTable].forEach((table) => {
+    table.querySelector("tbody").addEventListener("click", (event) => {
+      const row = event.target.closest("tr[data-cdm-id]");
+      if (row && row.dataset.cdmId) showCdmDetail(row.dataset.cdmId);
+    });
+  });
+
+  // Handlers are attached here: the page's Content-Security-Policy blocks
+  // inline ones
+  document.querySelectorAll('[data-action="toggle-demo-info"]').forEach((el) => {
+    el.addEventListener("click", toggleDemoInfo);
+  });
+
+  // A token is only needed when the node has api.auth enabled
+  elements.tokenForm.addEventListener("submit", (event) => {
+    event.preventDefault();
+    state.token = elements.tokenInput.value.trim();
+    sessionStorage.setItem("spacecomms-token", state.token);
+    elements.tokenForm.hidden = true;
+    refresh();
+  });
+
   // Initial refresh
   refresh();
+  followEvents();
 
   // Set up periodic refresh
   setInterval(refresh, CONFIG.refreshInterval);